@@ -0,0 +1,74 @@
+//! Exercises the plonky2 verifier from a `#![no_std]` crate, so that building this crate for a
+//! bare-metal target such as `thumbv7em-none-eabihf` checks that deserializing and verifying a
+//! proof needs only `core` and `alloc`.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use anyhow::Result;
+use plonky2::plonk::circuit_data::VerifierCircuitData;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::util::serialization::DefaultGateSerializer;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// Verifies a serialized proof against serialized verifier data, as produced by
+/// `ProofWithPublicInputs::to_bytes` and `VerifierCircuitData::to_bytes`.
+pub fn verify_proof_bytes(verifier_bytes: Vec<u8>, proof_bytes: Vec<u8>) -> Result<()> {
+    let verifier_data =
+        VerifierCircuitData::<F, C, D>::from_bytes(verifier_bytes, &DefaultGateSerializer)
+            .map_err(anyhow::Error::msg)?;
+    let proof = ProofWithPublicInputs::from_bytes(proof_bytes, &verifier_data.common)?;
+    verifier_data.verify(proof)
+}
+
+#[cfg(target_os = "none")]
+fn unsupported_getrandom(_buf: &mut [u8]) -> Result<(), getrandom::Error> {
+    Err(getrandom::Error::UNSUPPORTED)
+}
+
+#[cfg(target_os = "none")]
+getrandom::register_custom_getrandom!(unsupported_getrandom);
+
+#[cfg(test)]
+mod tests {
+    use plonky2::field::types::Field;
+    use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::circuit_data::CircuitConfig;
+
+    use super::*;
+
+    #[test]
+    fn verify_serialized_proof() -> Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let z = builder.mul(x, y);
+        builder.register_public_input(z);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(6));
+        pw.set_target(y, F::from_canonical_u32(7));
+        let proof = data.prove(pw)?;
+
+        let verifier_bytes = data
+            .verifier_data()
+            .to_bytes(&DefaultGateSerializer)
+            .map_err(anyhow::Error::msg)?;
+        verify_proof_bytes(verifier_bytes.clone(), proof.to_bytes())?;
+
+        let mut tampered = proof.clone();
+        tampered.public_inputs[0] = F::from_canonical_u32(43);
+        assert!(verify_proof_bytes(verifier_bytes, tampered.to_bytes()).is_err());
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 // Set up Jemalloc
-#[cfg(not(target_env = "msvc"))]
+#[cfg(not(any(target_env = "msvc", target_arch = "wasm32")))]
 use jemallocator::Jemalloc;
 
-#[cfg(not(target_env = "msvc"))]
+#[cfg(not(any(target_env = "msvc", target_arch = "wasm32")))]
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
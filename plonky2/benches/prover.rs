@@ -0,0 +1,245 @@
+mod allocator;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use plonky2::field::fft::fft_root_table;
+use plonky2::field::polynomial::PolynomialValues;
+use plonky2::field::types::Sample;
+use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::gates::noop::NoopGate;
+use plonky2::iop::target::Target;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::prover::ProverOptions;
+use plonky2::util::timing::TimingTree;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// Returns a builder for a circuit of `2^degree_bits` rows, mostly padding, along with its input.
+fn padded_builder(degree_bits: usize) -> (CircuitBuilder<F, D>, Target) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let x = builder.add_virtual_target();
+    let mut y = x;
+    for _ in 0..1000 {
+        y = builder.mul_add(y, y, y);
+    }
+    builder.register_public_input(y);
+    // Leave room for the constant and public input gates added when building.
+    while builder.num_gates() < (1 << degree_bits) - 10 {
+        builder.add_gate(NoopGate, vec![]);
+    }
+    (builder, x)
+}
+
+/// Builds a circuit of `2^degree_bits` rows, mostly padding, along with inputs for it.
+fn padded_circuit(degree_bits: usize) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let (builder, x) = padded_builder(degree_bits);
+    let data = builder.build::<C>();
+    assert_eq!(data.common.degree_bits(), degree_bits);
+
+    let mut inputs = PartialWitness::new();
+    inputs.set_target(x, F::rand());
+    (data, inputs)
+}
+
+/// Benchmarks proving. The prover's rayon pool respects `RAYON_NUM_THREADS`, which is included in
+/// the benchmark ID so that runs with different thread counts can be compared.
+pub(crate) fn bench_prove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prove");
+    group.sample_size(10);
+
+    let threads = std::env::var("RAYON_NUM_THREADS").unwrap_or_else(|_| "default".to_string());
+    for degree_bits in [12, 16] {
+        let (data, inputs) = padded_circuit(degree_bits);
+        group.bench_with_input(
+            BenchmarkId::new(format!("threads={}", threads), 1 << degree_bits),
+            &degree_bits,
+            |b, _| b.iter(|| data.prove(inputs.clone()).unwrap()),
+        );
+    }
+}
+
+/// Benchmarks proving a circuit of narrow gates with an increasingly wide config. The columns
+/// beyond the widest gate are committed to without being interpolated, so the proving time should
+/// grow much slower than the number of wires.
+pub(crate) fn bench_prove_wide_config(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prove_wide_config");
+    group.sample_size(10);
+
+    for num_wires in [135, 270, 540] {
+        let config = CircuitConfig {
+            num_wires,
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let mut y = x;
+        for _ in 0..1000 {
+            y = builder.mul_add(y, y, y);
+        }
+        builder.register_public_input(y);
+        while builder.num_gates() < (1 << 14) - 10 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let data = builder.build::<C>();
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::rand());
+
+        group.bench_with_input(
+            BenchmarkId::new("num_wires", num_wires),
+            &num_wires,
+            |b, _| b.iter(|| data.prove(inputs.clone()).unwrap()),
+        );
+    }
+}
+
+/// Benchmarks proving a batch of witnesses for the same circuit with an increasing number of
+/// worker threads.
+pub(crate) fn bench_prove_batch(c: &mut Criterion) {
+    const BATCH_SIZE: usize = 8;
+    let mut group = c.benchmark_group("prove_batch");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(BATCH_SIZE as u64));
+
+    let (data, inputs) = padded_circuit(14);
+    let data = data.prover_data();
+    for parallelism in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::new("threads", parallelism),
+            &parallelism,
+            |b, &parallelism| {
+                b.iter(|| {
+                    let witnesses = vec![inputs.clone(); BATCH_SIZE];
+                    for result in data.prove_batch(witnesses, parallelism) {
+                        result.unwrap();
+                    }
+                })
+            },
+        );
+    }
+}
+
+/// Benchmarks building, which is dominated by the sigma and constant polynomials and their
+/// commitment.
+pub(crate) fn bench_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    group.sample_size(10);
+
+    let threads = std::env::var("RAYON_NUM_THREADS").unwrap_or_else(|_| "default".to_string());
+    for degree_bits in [16, 20] {
+        group.bench_with_input(
+            BenchmarkId::new(format!("threads={}", threads), 1 << degree_bits),
+            &degree_bits,
+            |b, &degree_bits| {
+                b.iter_batched(
+                    || padded_builder(degree_bits).0,
+                    |builder| builder.build::<C>(),
+                    BatchSize::PerIteration,
+                )
+            },
+        );
+    }
+}
+
+/// Benchmarks committing to a batch of polynomials given by their values, as for wires, against
+/// committing to the same polynomials already in coefficient form, as for quotient chunks.
+pub(crate) fn bench_commit(c: &mut Criterion) {
+    const NUM_POLYS: usize = 80;
+    const RATE_BITS: usize = 3;
+    const CAP_HEIGHT: usize = 4;
+    let mut group = c.benchmark_group("commit");
+    group.sample_size(10);
+
+    for degree_bits in [12, 14] {
+        let values = (0..NUM_POLYS)
+            .map(|_| PolynomialValues::new(F::rand_vec(1 << degree_bits)))
+            .collect::<Vec<_>>();
+        let coeffs = values.iter().map(|v| v.clone().ifft()).collect::<Vec<_>>();
+        let root_table = fft_root_table(1 << (degree_bits + RATE_BITS));
+
+        group.bench_with_input(
+            BenchmarkId::new("from_values", 1 << degree_bits),
+            &values,
+            |b, values| {
+                b.iter_batched(
+                    || values.clone(),
+                    |values| {
+                        PolynomialBatch::<F, C, D>::from_values(
+                            values,
+                            RATE_BITS,
+                            false,
+                            CAP_HEIGHT,
+                            &mut TimingTree::default(),
+                            Some(&root_table),
+                        )
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("from_coeffs", 1 << degree_bits),
+            &coeffs,
+            |b, coeffs| {
+                b.iter_batched(
+                    || coeffs.clone(),
+                    |coeffs| {
+                        PolynomialBatch::<F, C, D>::from_coeffs(
+                            coeffs,
+                            RATE_BITS,
+                            false,
+                            CAP_HEIGHT,
+                            &mut TimingTree::default(),
+                            Some(&root_table),
+                        )
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+}
+
+/// Benchmarks proving a small circuit with a high proof-of-work difficulty, which makes the
+/// proof-of-work search dominate, on an increasing number of threads.
+pub(crate) fn bench_proof_of_work(c: &mut Criterion) {
+    let mut group = c.benchmark_group("proof_of_work");
+    group.sample_size(10);
+
+    let mut config = CircuitConfig::standard_recursion_config();
+    config.fri_config.proof_of_work_bits = 22;
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let x = builder.add_virtual_target();
+    let y = builder.square(x);
+    builder.register_public_input(y);
+    let data = builder.build::<C>();
+    let mut inputs = PartialWitness::new();
+    inputs.set_target(x, F::rand());
+
+    for pow_threads in [1, 2, 4, 8] {
+        let options = ProverOptions {
+            pow_threads: Some(pow_threads),
+            ..Default::default()
+        };
+        group.bench_with_input(
+            BenchmarkId::new("threads", pow_threads),
+            &options,
+            |b, options| b.iter(|| data.prove_with_options(inputs.clone(), options).unwrap()),
+        );
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    bench_prove(c);
+    bench_prove_wide_config(c);
+    bench_prove_batch(c);
+    bench_build(c);
+    bench_commit(c);
+    bench_proof_of_work(c);
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,137 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use plonky2::field::types::Sample;
+use plonky2::gates::noop::NoopGate;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::prover::ProverOptions;
+
+/// Wraps the system allocator to track the peak number of bytes allocated.
+struct PeakAlloc {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = self.current.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            self.peak.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+impl PeakAlloc {
+    /// Runs `f` and returns the peak number of bytes allocated while it ran, on top of what was
+    /// already allocated.
+    fn measure<T>(&self, f: impl FnOnce() -> T) -> usize {
+        let baseline = self.current.load(Ordering::Relaxed);
+        self.peak.store(baseline, Ordering::Relaxed);
+        drop(f());
+        self.peak.load(Ordering::Relaxed) - baseline
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc {
+    current: AtomicUsize::new(0),
+    peak: AtomicUsize::new(0),
+};
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// Builds a circuit of `2^degree_bits` rows, mostly padding, along with inputs for it.
+fn padded_circuit(degree_bits: usize) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let x = builder.add_virtual_target();
+    let y = builder.square(x);
+    builder.register_public_input(y);
+    while builder.num_gates() < (1 << degree_bits) - 10 {
+        builder.add_gate(NoopGate, vec![]);
+    }
+    let data = builder.build::<C>();
+
+    let mut inputs = PartialWitness::new();
+    inputs.set_target(x, F::rand());
+    (data, inputs)
+}
+
+/// Benchmarks proving with different `ProverOptions::quotient_chunk_size`s, and prints the peak
+/// memory allocated by the prover for each.
+pub(crate) fn bench_quotient_chunk_size(c: &mut Criterion) {
+    const DEGREE_BITS: usize = 14;
+
+    let mut group = c.benchmark_group("prove-quotient-chunk-size");
+    group.sample_size(10);
+
+    let (data, inputs) = padded_circuit(DEGREE_BITS);
+    for quotient_chunk_size in [None, Some(1 << 14), Some(1 << 10)] {
+        let options = ProverOptions {
+            quotient_chunk_size,
+            ..Default::default()
+        };
+        let prove = || data.prove_with_options(inputs.clone(), &options).unwrap();
+        let label = quotient_chunk_size.map_or("unchunked".to_string(), |n| n.to_string());
+        println!(
+            "quotient chunk size {}: peak prover allocation {} MiB",
+            label,
+            GLOBAL.measure(prove) >> 20
+        );
+        group.bench_with_input(BenchmarkId::from_parameter(&label), &label, |b, _| {
+            b.iter(prove)
+        });
+    }
+}
+
+/// Builds a circuit squaring a value repeatedly, so that most rows have witness generators.
+fn squaring_circuit(num_squarings: usize) -> CircuitBuilder<F, D> {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let mut x = builder.add_virtual_target();
+    for _ in 0..num_squarings {
+        x = builder.square(x);
+    }
+    builder.register_public_input(x);
+    builder
+}
+
+/// Benchmarks building only the verifier data against building the full circuit data, and prints
+/// the peak memory allocated by each.
+pub(crate) fn bench_build_verifier(c: &mut Criterion) {
+    const NUM_SQUARINGS: usize = 100_000;
+
+    let mut group = c.benchmark_group("build-verifier");
+    group.sample_size(10);
+
+    let full = || squaring_circuit(NUM_SQUARINGS).build::<C>();
+    let verifier = || squaring_circuit(NUM_SQUARINGS).build_verifier::<C>();
+    let full_peak = GLOBAL.measure(full);
+    let verifier_peak = GLOBAL.measure(verifier);
+    println!(
+        "full build: peak allocation {} MiB, verifier build: peak allocation {} MiB",
+        full_peak >> 20,
+        verifier_peak >> 20
+    );
+    group.bench_function("full", |b| b.iter(full));
+    group.bench_function("verifier", |b| b.iter(verifier));
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    bench_quotient_chunk_size(c);
+    bench_build_verifier(c);
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
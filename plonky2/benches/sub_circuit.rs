@@ -0,0 +1,81 @@
+mod allocator;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use plonky2::field::types::Field;
+use plonky2::iop::target::Target;
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::sub_circuit::SubCircuitTemplate;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+const WIDTH: usize = 12;
+
+/// A round of a Poseidon-like permutation: adds round constants, applies the `x^7` S-box to each
+/// element, and mixes the state with a circulant matrix. It takes about 200 arithmetic operations.
+fn hash_round(builder: &mut CircuitBuilder<F, D>, state: &[Target]) -> Vec<Target> {
+    let sboxed = state
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let x = builder.add_const(x, F::from_canonical_usize(i + 1));
+            builder.exp_u64(x, 7)
+        })
+        .collect::<Vec<_>>();
+    (0..WIDTH)
+        .map(|i| {
+            let mut acc = builder.zero();
+            for (j, &x) in sboxed.iter().enumerate() {
+                let c = F::from_canonical_usize(1 << ((WIDTH + j - i) % WIDTH));
+                acc = builder.mul_const_add(c, x, acc);
+            }
+            acc
+        })
+        .collect()
+}
+
+/// Chains `num_rounds` hash rounds, by calling `hash_round` or instantiating a template of it.
+fn hash_chain(
+    num_rounds: usize,
+    template: Option<&SubCircuitTemplate<F, D>>,
+) -> CircuitBuilder<F, D> {
+    let mut builder = CircuitBuilder::new(CircuitConfig::standard_recursion_config());
+    let mut state = builder.add_virtual_targets(WIDTH);
+    for _ in 0..num_rounds {
+        state = match template {
+            Some(template) => template.instantiate(&mut builder, &state),
+            None => hash_round(&mut builder, &state),
+        };
+    }
+    builder.register_public_inputs(&state);
+    builder
+}
+
+pub(crate) fn bench_sub_circuit_template(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hash-round-instances");
+    group.sample_size(10);
+
+    let config = CircuitConfig::standard_recursion_config();
+    let template = SubCircuitTemplate::new(&config, WIDTH, hash_round);
+    let num_rounds = 10_000;
+    group.bench_with_input(
+        BenchmarkId::new("naive", num_rounds),
+        &num_rounds,
+        |b, &n| b.iter(|| hash_chain(n, None)),
+    );
+    group.bench_with_input(
+        BenchmarkId::new("template", num_rounds),
+        &num_rounds,
+        |b, &n| b.iter(|| hash_chain(n, Some(&template))),
+    );
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    bench_sub_circuit_template(c);
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
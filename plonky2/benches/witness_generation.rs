@@ -0,0 +1,229 @@
+mod allocator;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use plonky2::field::types::Sample;
+use plonky2::iop::generator::{generate_partial_witness, generate_partial_witness_with_options};
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::prover::ProverOptions;
+use plonky2::util::timing::TimingTree;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+const NUM_SOURCES: usize = 16;
+
+/// Builds a circuit which mostly consists of copies between virtual targets, along with inputs for
+/// it. Copies are added one at a time, or as a single batch if `batched` is set.
+fn copy_heavy_circuit(
+    num_copies: usize,
+    batched: bool,
+) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let sources = builder.add_virtual_targets(NUM_SOURCES);
+    let srcs = (0..num_copies)
+        .map(|i| sources[i % NUM_SOURCES])
+        .collect::<Vec<_>>();
+    let dsts = builder.add_virtual_targets(num_copies);
+    if batched {
+        builder.generate_copies(&srcs, &dsts);
+    } else {
+        for (&src, &dst) in srcs.iter().zip(&dsts) {
+            builder.generate_copy(src, dst);
+        }
+    }
+    let x = builder.add_virtual_target();
+    let x_squared = builder.square(x);
+    builder.register_public_input(x_squared);
+    let data = builder.build::<C>();
+
+    let mut inputs = PartialWitness::new();
+    for &t in sources.iter().chain([&x]) {
+        inputs.set_target(t, F::rand());
+    }
+    (data, inputs)
+}
+
+/// Builds a circuit computing `x_{i+1} = y_i^2 + y_i` for `num_copies` steps, where `y_i` is a copy
+/// of `x_i`, so that the sources of the copies are set one at a time, along with inputs for it.
+/// Copies are added one at a time, or as a single batch if `batched` is set.
+fn incremental_copies_circuit(
+    num_copies: usize,
+    batched: bool,
+) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let x = builder.add_virtual_target();
+    let dsts = builder.add_virtual_targets(num_copies);
+    let srcs = dsts
+        .iter()
+        .map(|&y| builder.mul_add(y, y, y))
+        .collect::<Vec<_>>();
+    let (srcs, dsts) = ([&[x], &srcs[..num_copies - 1]].concat(), dsts);
+    if batched {
+        builder.generate_copies(&srcs, &dsts);
+    } else {
+        for (&src, &dst) in srcs.iter().zip(&dsts) {
+            builder.generate_copy(src, dst);
+        }
+    }
+    let data = builder.build::<C>();
+
+    let mut inputs = PartialWitness::new();
+    inputs.set_target(x, F::rand());
+    (data, inputs)
+}
+
+/// Builds a circuit computing `x_{i+1} = x_i^2 + x_i` for `length` steps, so that each generator
+/// depends on the previous one, along with inputs for it.
+fn sequential_chain_circuit(length: usize) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let x = builder.add_virtual_target();
+    let mut y = x;
+    for _ in 0..length {
+        y = builder.mul_add(y, y, y);
+    }
+    builder.register_public_input(y);
+    let data = builder.build::<C>();
+
+    let mut inputs = PartialWitness::new();
+    inputs.set_target(x, F::rand());
+    (data, inputs)
+}
+
+/// Builds a circuit inverting `num_inversions` independent inputs, along with inputs for it.
+fn inversion_heavy_circuit(num_inversions: usize) -> (CircuitData<F, C, D>, PartialWitness<F>) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let xs = builder.add_virtual_targets(num_inversions);
+    for &x in &xs {
+        builder.inverse(x);
+    }
+    let data = builder.build::<C>();
+
+    let mut inputs = PartialWitness::new();
+    for &x in &xs {
+        inputs.set_target(x, F::rand());
+    }
+    (data, inputs)
+}
+
+pub(crate) fn bench_inversion_heavy_witness(c: &mut Criterion, eager_inversions: bool) {
+    let mut group = c.benchmark_group(if eager_inversions {
+        "eager-inversions-witness-generation"
+    } else {
+        "batched-inversions-witness-generation"
+    });
+    group.sample_size(10);
+
+    let options = ProverOptions {
+        eager_inversions,
+        ..Default::default()
+    };
+    let num_inversions = 100_000;
+    let (data, inputs) = inversion_heavy_circuit(num_inversions);
+    group.bench_with_input(
+        BenchmarkId::from_parameter(num_inversions),
+        &num_inversions,
+        |b, _| {
+            b.iter_batched(
+                || inputs.clone(),
+                |inputs| {
+                    generate_partial_witness_with_options(
+                        inputs,
+                        &data.prover_only,
+                        &data.common,
+                        &options,
+                        &mut TimingTree::default(),
+                    )
+                    .unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        },
+    );
+}
+
+pub(crate) fn bench_sequential_chain_witness(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sequential-chain-witness-generation");
+    group.sample_size(10);
+
+    for length in [10_000, 100_000] {
+        let (data, inputs) = sequential_chain_circuit(length);
+        group.bench_with_input(BenchmarkId::from_parameter(length), &length, |b, _| {
+            b.iter_batched(
+                || inputs.clone(),
+                |inputs| generate_partial_witness(inputs, &data.prover_only, &data.common).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+}
+
+pub(crate) fn bench_copy_heavy_witness(c: &mut Criterion, batched: bool) {
+    let mut group = c.benchmark_group(if batched {
+        "batched-copies-witness-generation"
+    } else {
+        "copy-heavy-witness-generation"
+    });
+    group.sample_size(10);
+
+    for num_copies_log in [16, 18, 20] {
+        let num_copies = 1 << num_copies_log;
+        let (data, inputs) = copy_heavy_circuit(num_copies, batched);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_copies),
+            &num_copies,
+            |b, _| {
+                b.iter_batched(
+                    || inputs.clone(),
+                    |inputs| {
+                        generate_partial_witness(inputs, &data.prover_only, &data.common).unwrap()
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+}
+
+pub(crate) fn bench_incremental_copies_witness(c: &mut Criterion, batched: bool) {
+    let mut group = c.benchmark_group(if batched {
+        "incremental-batched-copies-witness-generation"
+    } else {
+        "incremental-copies-witness-generation"
+    });
+    group.sample_size(10);
+
+    for num_copies_log in [12, 14] {
+        let num_copies = 1 << num_copies_log;
+        let (data, inputs) = incremental_copies_circuit(num_copies, batched);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(num_copies),
+            &num_copies,
+            |b, _| {
+                b.iter_batched(
+                    || inputs.clone(),
+                    |inputs| {
+                        generate_partial_witness(inputs, &data.prover_only, &data.common).unwrap()
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    bench_copy_heavy_witness(c, false);
+    bench_copy_heavy_witness(c, true);
+    bench_incremental_copies_witness(c, false);
+    bench_incremental_copies_witness(c, true);
+    bench_sequential_chain_witness(c);
+    bench_inversion_heavy_witness(c, true);
+    bench_inversion_heavy_witness(c, false);
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -24,6 +24,7 @@ use plonky2::iop::witness::{PartialWitness, WitnessWrite};
 use plonky2::plonk::circuit_builder::CircuitBuilder;
 use plonky2::plonk::circuit_data::{CircuitConfig, CommonCircuitData, VerifierOnlyCircuitData};
 use plonky2::plonk::config::{AlgebraicHasher, GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::plonk_common::PlonkOracle;
 use plonky2::plonk::proof::{CompressedProofWithPublicInputs, ProofWithPublicInputs};
 use plonky2::plonk::prover::prove;
 use plonky2::util::serialization::DefaultGateSerializer;
@@ -216,7 +217,8 @@ where
     let mut builder = CircuitBuilder::<F, D>::new(config.clone());
     let pt = builder.add_virtual_proof_with_pis(inner_cd);
 
-    let inner_data = builder.add_virtual_verifier_data(inner_cd.config.fri_config.cap_height);
+    let inner_data = builder
+        .add_virtual_verifier_data(inner_cd.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
 
     builder.verify_proof::<InnerC>(&pt, &inner_data, inner_cd);
     builder.print_gate_counts(0);
@@ -41,14 +41,16 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
         let fri_betas = commit_phase_merkle_caps
             .iter()
             .map(|cap| {
-                self.observe_cap::<C::Hasher>(cap);
+                self.labeled("fri_commit_phase_cap", |c| c.observe_cap::<C::Hasher>(cap));
                 self.get_extension_challenge::<D>()
             })
             .collect();
 
-        self.observe_extension_elements(&final_poly.coeffs);
+        self.labeled("fri_final_poly", |c| {
+            c.observe_extension_elements(&final_poly.coeffs)
+        });
 
-        self.observe_element(pow_witness);
+        self.observe_labeled("fri_pow_witness", &[pow_witness]);
         let fri_pow_response = self.get_challenge();
 
         let fri_query_indices = (0..num_fri_queries)
@@ -89,14 +91,16 @@ impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
         let fri_betas = commit_phase_merkle_caps
             .iter()
             .map(|cap| {
-                self.observe_cap(cap);
+                self.labeled("fri_commit_phase_cap", |c| c.observe_cap(cap));
                 self.get_extension_challenge(builder)
             })
             .collect();
 
-        self.observe_extension_elements(&final_poly.0);
+        self.labeled("fri_final_poly", |c| {
+            c.observe_extension_elements(&final_poly.0)
+        });
 
-        self.observe_element(pow_witness);
+        self.observe_labeled("fri_pow_witness", &[pow_witness]);
         let fri_pow_response = self.get_challenge(builder);
 
         let fri_query_indices = (0..num_fri_queries)
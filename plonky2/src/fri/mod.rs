@@ -6,9 +6,13 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
-use serde::Serialize;
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
 
 use crate::fri::reduction_strategies::FriReductionStrategy;
+use crate::fri::structure::FriOracleInfo;
+use crate::plonk::config::{GenericConfig, Hasher};
+use crate::plonk::plonk_common::salt_size;
 
 mod challenges;
 pub mod oracle;
@@ -22,7 +26,7 @@ pub mod verifier;
 pub mod witness_util;
 
 /// A configuration for the FRI protocol.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct FriConfig {
     /// `rate = 2^{-rate_bits}`.
     pub rate_bits: usize,
@@ -38,6 +42,13 @@ pub struct FriConfig {
 
     /// Number of query rounds to perform.
     pub num_query_rounds: usize,
+
+    /// The commit phase never reduces the polynomial below degree `2^final_poly_degree_bits`, so
+    /// that the final polynomial sent in the proof has at least this many coefficients. Stopping
+    /// early trades a larger final polynomial for fewer reduction rounds, which can make proofs of
+    /// small circuits smaller. Zero lets the reduction strategy decide alone.
+    #[serde(default)]
+    pub final_poly_degree_bits: usize,
 }
 
 impl FriConfig {
@@ -51,23 +62,106 @@ impl FriConfig {
             self.rate_bits,
             self.cap_height,
             self.num_query_rounds,
+            self.final_poly_degree_bits,
         );
         FriParams {
             config: self.clone(),
             hiding,
             degree_bits,
             reduction_arity_bits,
+            initial_cap_heights: Vec::new(),
         }
     }
 
+    /// The conjectured security of FRI with this configuration over a field of `field_bits` bits,
+    /// following the ethSTARK paper: each query round adds `rate_bits` bits, the proof of work
+    /// adds its own bits, and the total can't exceed the field's size. The final polynomial is
+    /// sent whole and checked at every query's folded point, so the last layer is a codeword of
+    /// the same rate as the others: `final_poly_degree_bits` trades reduction rounds for proof
+    /// size without changing the bound.
+    pub fn conjectured_security_bits(&self, field_bits: usize) -> usize {
+        let query_security_bits =
+            self.num_query_rounds * self.rate_bits + self.proof_of_work_bits as usize;
+        query_security_bits.min(field_bits)
+    }
+
     pub const fn num_cap_elements(&self) -> usize {
         1 << self.cap_height
     }
+
+    /// Estimates the size of a serialized proof opening the oracles described by `shape`, whose
+    /// polynomials have degree `2^degree_bits`, with `C`'s field and hasher. This covers the caps,
+    /// openings, query rounds, final polynomial and proof-of-work witness, but not any data the
+    /// proof system adds on top, such as PLONK's public inputs.
+    pub fn estimated_proof_size_bytes<C: GenericConfig<D>, const D: usize>(
+        &self,
+        degree_bits: usize,
+        shape: &OracleShape,
+    ) -> usize {
+        const FIELD_BYTES: usize = 8;
+        let ext_bytes = D * FIELD_BYTES;
+        let hash_bytes = <C::Hasher as Hasher<C::F>>::HASH_SIZE;
+
+        let fri_params = FriParams {
+            initial_cap_heights: shape.initial_cap_heights.clone(),
+            ..self.fri_params(degree_bits, shape.hiding)
+        };
+        let cap_bytes = |cap_height: usize| (1 << cap_height) * hash_bytes;
+        // A Merkle proof is its length as a byte, followed by the siblings up to the cap.
+        let merkle_proof_bytes =
+            |tree_height: usize, cap_height: usize| 1 + (tree_height - cap_height) * hash_bytes;
+
+        let caps = (shape.num_preprocessed_oracles..shape.oracles.len())
+            .map(|i| cap_bytes(fri_params.initial_cap_height(i)))
+            .sum::<usize>()
+            + fri_params.reduction_arity_bits.len() * cap_bytes(self.cap_height);
+        let openings = shape.num_openings * ext_bytes;
+
+        let mut query_round_bytes = shape
+            .oracles
+            .iter()
+            .enumerate()
+            .map(|(i, oracle)| {
+                let leaf_len = oracle.num_polys + salt_size(shape.hiding && oracle.blinding);
+                leaf_len * FIELD_BYTES
+                    + merkle_proof_bytes(fri_params.lde_bits(), fri_params.initial_cap_height(i))
+            })
+            .sum::<usize>();
+        let mut tree_height = fri_params.lde_bits();
+        for &arity_bits in &fri_params.reduction_arity_bits {
+            tree_height -= arity_bits;
+            query_round_bytes +=
+                (1 << arity_bits) * ext_bytes + merkle_proof_bytes(tree_height, self.cap_height);
+        }
+        let queries = self.num_query_rounds * query_round_bytes;
+
+        let final_poly = fri_params.final_poly_len() * ext_bytes;
+        let pow = FIELD_BYTES;
+
+        caps + openings + queries + final_poly + pow
+    }
+}
+
+/// The oracles opened by a FRI proof, as needed to estimate its size with
+/// [`FriConfig::estimated_proof_size_bytes`].
+#[derive(Clone, Debug, Default)]
+pub struct OracleShape {
+    /// The oracles, in the order they are opened.
+    pub oracles: Vec<FriOracleInfo>,
+    /// How many of the first `oracles` are preprocessed, so that their caps are part of the
+    /// verifier's data rather than of the proof.
+    pub num_preprocessed_oracles: usize,
+    /// The total number of extension field evaluations sent for all opening points.
+    pub num_openings: usize,
+    /// Whether the blinded oracles' leaves are salted, as in zero-knowledge proofs.
+    pub hiding: bool,
+    /// The cap heights of the oracles' Merkle trees, as in [`FriParams::initial_cap_heights`].
+    pub initial_cap_heights: Vec<usize>,
 }
 
 /// FRI parameters, including generated parameters which are specific to an instance size, in
 /// contrast to `FriConfig` which is user-specified and independent of instance size.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct FriParams {
     /// User-specified FRI configuration.
     pub config: FriConfig,
@@ -83,6 +177,11 @@ pub struct FriParams {
     /// a 4-to-1 reduction, then a 2-to-1 reduction. After these reductions, the reduced polynomial
     /// is sent directly.
     pub reduction_arity_bits: Vec<usize>,
+
+    /// The cap heights of the Merkle trees of the initial oracles, in the order they are opened.
+    /// Oracles not listed use `config.cap_height`, as do the commit phase's trees.
+    #[serde(default)]
+    pub initial_cap_heights: Vec<usize>,
 }
 
 impl FriParams {
@@ -90,6 +189,79 @@ impl FriParams {
         self.reduction_arity_bits.iter().sum()
     }
 
+    /// The cap height of the Merkle tree of the `oracle_index`th initial oracle.
+    pub fn initial_cap_height(&self, oracle_index: usize) -> usize {
+        self.initial_cap_heights
+            .get(oracle_index)
+            .copied()
+            .unwrap_or(self.config.cap_height)
+    }
+
+    /// The greatest cap height of any Merkle tree, initial or from the commit phase.
+    pub(crate) fn max_cap_height(&self) -> usize {
+        self.initial_cap_heights
+            .iter()
+            .copied()
+            .fold(self.config.cap_height, usize::max)
+    }
+
+    /// Checks that the initial trees' caps fit the LDE, and that the reduction arities fit the
+    /// instance: they can't reduce the polynomial below the configured final degree, nor make the
+    /// last FRI tree shorter than the cap height.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        for (i, &cap_height) in self.initial_cap_heights.iter().enumerate() {
+            ensure!(
+                cap_height <= self.lde_bits(),
+                "The cap height {} of initial oracle {} exceeds the {} bits of the LDE",
+                cap_height,
+                i,
+                self.lde_bits()
+            );
+        }
+        let final_poly_degree_bits = self.config.final_poly_degree_bits;
+        ensure!(
+            final_poly_degree_bits <= self.degree_bits,
+            "FRI final polynomial degree bits {} exceed degree_bits = {}",
+            final_poly_degree_bits,
+            self.degree_bits,
+        );
+        let max_total_arities = (self.degree_bits - final_poly_degree_bits)
+            .min(self.lde_bits().saturating_sub(self.config.cap_height));
+        ensure!(
+            self.total_arities() <= max_total_arities,
+            "FRI reduction strategy {:?} chose arities {:?}, totalling {} bits, but at most {} are \
+             allowed with degree_bits = {}, rate_bits = {}, cap_height = {} and \
+             final_poly_degree_bits = {}",
+            self.config.reduction_strategy,
+            self.reduction_arity_bits,
+            self.total_arities(),
+            max_total_arities,
+            self.degree_bits,
+            self.config.rate_bits,
+            self.config.cap_height,
+            final_poly_degree_bits,
+        );
+        Ok(())
+    }
+
+    /// Checks that the configuration reaches `security_bits` bits of conjectured security over a
+    /// field of `field_bits` bits. See [`FriConfig::conjectured_security_bits`].
+    pub fn validate_security(&self, security_bits: usize, field_bits: usize) -> anyhow::Result<()> {
+        let conjectured_security_bits = self.config.conjectured_security_bits(field_bits);
+        ensure!(
+            conjectured_security_bits >= security_bits,
+            "FRI params fall short of target security {}, reaching only {} with {} query rounds, \
+             rate_bits = {}, proof_of_work_bits = {} and a final polynomial of degree 2^{}",
+            security_bits,
+            conjectured_security_bits,
+            self.config.num_query_rounds,
+            self.config.rate_bits,
+            self.config.proof_of_work_bits,
+            self.final_poly_bits(),
+        );
+        Ok(())
+    }
+
     pub(crate) fn max_arity_bits(&self) -> Option<usize> {
         self.reduction_arity_bits.iter().copied().max()
     }
@@ -109,4 +281,19 @@ impl FriParams {
     pub fn final_poly_len(&self) -> usize {
         1 << self.final_poly_bits()
     }
+
+    /// The number of values derived from a polynomial which a proof reveals, when the polynomial
+    /// is opened at `num_points` points in an extension of degree `extension_degree`: the openings
+    /// themselves, then in each query round the polynomial's leaf value, the other evaluations of
+    /// each folded coset and the coefficients of the final polynomial. Leaves are salted when
+    /// hiding, so the Merkle paths don't reveal anything more.
+    pub fn num_revealed_values(&self, extension_degree: usize, num_points: usize) -> usize {
+        let folded_evals = self
+            .reduction_arity_bits
+            .iter()
+            .map(|&arity_bits| (1 << arity_bits) - 1)
+            .sum::<usize>();
+        let query_round_values = 1 + extension_degree * (folded_evals + self.final_poly_len());
+        num_points * extension_degree + self.config.num_query_rounds * query_round_values
+    }
 }
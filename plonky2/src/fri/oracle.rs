@@ -1,5 +1,5 @@
 #[cfg(not(feature = "std"))]
-use alloc::{format, vec::Vec};
+use alloc::{format, vec, vec::Vec};
 
 use itertools::Itertools;
 use plonky2_field::types::Field;
@@ -10,7 +10,7 @@ use crate::field::fft::FftRootTable;
 use crate::field::packed::PackedField;
 use crate::field::polynomial::{PolynomialCoeffs, PolynomialValues};
 use crate::fri::proof::FriProof;
-use crate::fri::prover::fri_proof;
+use crate::fri::prover::{fri_proof, FriProverOptions};
 use crate::fri::structure::{FriBatchInfo, FriInstanceInfo};
 use crate::fri::FriParams;
 use crate::hash::hash_types::RichField;
@@ -20,7 +20,7 @@ use crate::plonk::config::GenericConfig;
 use crate::timed;
 use crate::util::reducing::ReducingFactor;
 use crate::util::timing::TimingTree;
-use crate::util::{log2_strict, reverse_bits, reverse_index_bits_in_place, transpose};
+use crate::util::{log2_strict, reverse_bits, reverse_index_bits_in_place};
 
 /// Four (~64 bit) field elements gives ~128 bit security.
 pub const SALT_SIZE: usize = 4;
@@ -62,17 +62,80 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         timing: &mut TimingTree,
         fft_root_table: Option<&FftRootTable<F>>,
     ) -> Self {
+        let num_nonzero = values.len();
+        Self::from_values_with_zero_suffix(
+            values,
+            num_nonzero,
+            rate_bits,
+            blinding,
+            cap_height,
+            timing,
+            fft_root_table,
+        )
+    }
+
+    /// Like `from_values`, but the polynomials after the first `num_nonzero` are known to be zero,
+    /// so their IFFTs and LDEs are skipped.
+    pub fn from_values_with_zero_suffix(
+        values: Vec<PolynomialValues<F>>,
+        num_nonzero: usize,
+        rate_bits: usize,
+        blinding: bool,
+        cap_height: usize,
+        timing: &mut TimingTree,
+        fft_root_table: Option<&FftRootTable<F>>,
+    ) -> Self {
+        Self::from_values_chunked(
+            values,
+            num_nonzero,
+            rate_bits,
+            blinding,
+            cap_height,
+            None,
+            timing,
+            fft_root_table,
+        )
+    }
+
+    /// Like `from_values_with_zero_suffix`, but computes the LDE in chunks of `lde_chunk_size`
+    /// points, as described in `lde_leaves`.
+    pub(crate) fn from_values_chunked(
+        values: Vec<PolynomialValues<F>>,
+        num_nonzero: usize,
+        rate_bits: usize,
+        blinding: bool,
+        cap_height: usize,
+        lde_chunk_size: Option<usize>,
+        timing: &mut TimingTree,
+        fft_root_table: Option<&FftRootTable<F>>,
+    ) -> Self {
+        debug_assert!(values[num_nonzero..]
+            .iter()
+            .all(|v| v.values.iter().all(|x| x.is_zero())));
+        let degree = values[0].len();
         let coeffs = timed!(
             timing,
             "IFFT",
-            values.into_par_iter().map(|v| v.ifft()).collect::<Vec<_>>()
+            values
+                .into_par_iter()
+                .enumerate()
+                .map(|(i, v)| {
+                    if i < num_nonzero {
+                        v.ifft()
+                    } else {
+                        PolynomialCoeffs::zero(degree)
+                    }
+                })
+                .collect::<Vec<_>>()
         );
 
-        Self::from_coeffs(
+        Self::from_coeffs_chunked(
             coeffs,
+            num_nonzero,
             rate_bits,
             blinding,
             cap_height,
+            lde_chunk_size,
             timing,
             fft_root_table,
         )
@@ -86,16 +149,45 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         cap_height: usize,
         timing: &mut TimingTree,
         fft_root_table: Option<&FftRootTable<F>>,
+    ) -> Self {
+        let num_nonzero = polynomials.len();
+        Self::from_coeffs_chunked(
+            polynomials,
+            num_nonzero,
+            rate_bits,
+            blinding,
+            cap_height,
+            None,
+            timing,
+            fft_root_table,
+        )
+    }
+
+    /// Like `from_coeffs`, but the polynomials after the first `num_nonzero` are known to be zero,
+    /// and the LDE is computed in chunks of `lde_chunk_size` points, as described in `lde_leaves`.
+    pub(crate) fn from_coeffs_chunked(
+        polynomials: Vec<PolynomialCoeffs<F>>,
+        num_nonzero: usize,
+        rate_bits: usize,
+        blinding: bool,
+        cap_height: usize,
+        lde_chunk_size: Option<usize>,
+        timing: &mut TimingTree,
+        fft_root_table: Option<&FftRootTable<F>>,
     ) -> Self {
         let degree = polynomials[0].len();
-        let lde_values = timed!(
+        let leaves = timed!(
             timing,
             "FFT + blinding",
-            Self::lde_values(&polynomials, rate_bits, blinding, fft_root_table)
+            Self::lde_leaves(
+                &polynomials,
+                num_nonzero,
+                rate_bits,
+                blinding,
+                lde_chunk_size,
+                fft_root_table
+            )
         );
-
-        let mut leaves = timed!(timing, "transpose LDEs", transpose(&lde_values));
-        reverse_index_bits_in_place(&mut leaves);
         let merkle_tree = timed!(
             timing,
             "build Merkle tree",
@@ -111,31 +203,83 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         }
     }
 
-    fn lde_values(
+    /// The Merkle leaves of the LDEs of `polynomials`, i.e. their values at each point of the LDE
+    /// domain in bit-reversed order, each followed by a salt if blinding. The LDEs of the
+    /// polynomials after the first `num_nonzero`, which are zero, aren't computed.
+    ///
+    /// The LDE domain is split into cosets of `lde_chunk_size` points, rounded down to a power of
+    /// two but to no less than the polynomials' degree, and the LDEs are computed one coset at a
+    /// time. A coset's values make up a contiguous range of the bit-reversed leaves, so only one
+    /// chunk of column-major values is alive next to the leaves, rather than all of them.
+    fn lde_leaves(
         polynomials: &[PolynomialCoeffs<F>],
+        num_nonzero: usize,
         rate_bits: usize,
         blinding: bool,
+        lde_chunk_size: Option<usize>,
         fft_root_table: Option<&FftRootTable<F>>,
     ) -> Vec<Vec<F>> {
         let degree = polynomials[0].len();
+        let lde_size = degree << rate_bits;
+        let lde_bits = log2_strict(lde_size);
+        let chunk_size = lde_chunk_size.map_or(lde_size, |n| {
+            (1 << n.max(1).ilog2()).clamp(degree, lde_size)
+        });
+        let chunk_bits = log2_strict(chunk_size);
+        // A root table for a larger FFT works too, so a table for the whole LDE serves every chunk.
+        let computed_root_table = fft_root_table
+            .is_none()
+            .then(|| crate::field::fft::fft_root_table(chunk_size));
+        let root_table = fft_root_table.or(computed_root_table.as_ref());
 
-        // If blinding, salt with two random elements to each leaf vector.
+        // If blinding, salt each leaf with random elements.
         let salt_size = if blinding { SALT_SIZE } else { 0 };
+        let leaf_size = polynomials.len() + salt_size;
 
-        polynomials
-            .par_iter()
-            .map(|p| {
-                assert_eq!(p.len(), degree, "Polynomial degrees inconsistent");
-                p.lde(rate_bits)
-                    .coset_fft_with_options(F::coset_shift(), Some(rate_bits), fft_root_table)
-                    .values
-            })
-            .chain(
-                (0..salt_size)
+        let mut leaves = Vec::with_capacity(lde_size);
+        for chunk in 0..lde_size / chunk_size {
+            // The chunk holds the bit-reversed values on the coset `shift * g^coset * <g^num_cosets>`,
+            // where `g` generates the LDE domain.
+            let coset = reverse_bits(chunk, lde_bits - chunk_bits);
+            let shift =
+                F::coset_shift() * F::primitive_root_of_unity(lde_bits).exp_u64(coset as u64);
+            let shift_powers = shift.powers().take(degree).collect::<Vec<_>>();
+            let columns = polynomials
+                .par_iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    assert_eq!(p.len(), degree, "Polynomial degrees inconsistent");
+                    if i < num_nonzero {
+                        let mut coeffs = p
+                            .coeffs
+                            .iter()
+                            .zip(&shift_powers)
+                            .map(|(&c, &r)| c * r)
+                            .collect::<Vec<_>>();
+                        coeffs.resize(chunk_size, F::ZERO);
+                        let mut values = PolynomialCoeffs::new(coeffs)
+                            .fft_with_options(Some(chunk_bits - log2_strict(degree)), root_table)
+                            .values;
+                        reverse_index_bits_in_place(&mut values);
+                        values
+                    } else {
+                        vec![F::ZERO; chunk_size]
+                    }
+                })
+                .collect::<Vec<_>>();
+            leaves.extend(
+                (0..chunk_size)
                     .into_par_iter()
-                    .map(|_| F::rand_vec(degree << rate_bits)),
-            )
-            .collect()
+                    .map(|row| {
+                        let mut leaf = Vec::with_capacity(leaf_size);
+                        leaf.extend(columns.iter().map(|column| column[row]));
+                        leaf.extend((0..salt_size).map(|_| F::rand()));
+                        leaf
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        leaves
     }
 
     /// Fetches LDE values at the `index * step`th point.
@@ -178,6 +322,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         oracles: &[&Self],
         challenger: &mut Challenger<F, C::Hasher>,
         fri_params: &FriParams,
+        options: &FriProverOptions,
         timing: &mut TimingTree,
     ) -> FriProof<F, C::Hasher, D> {
         assert!(D > 1, "Not implemented for D=1.");
@@ -226,9 +371,127 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
             lde_final_values,
             challenger,
             fri_params,
+            options,
             timing,
         );
 
         fri_proof
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::fft::fft_root_table;
+    use crate::field::types::Sample;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn from_coeffs_matches_from_values() {
+        let degree_log = 6;
+        let rate_bits = 2;
+        let cap_height = 2;
+        let values = (0..5)
+            .map(|_| PolynomialValues::new(F::rand_vec(1 << degree_log)))
+            .collect::<Vec<_>>();
+        let coeffs = values.iter().map(|v| v.clone().ifft()).collect::<Vec<_>>();
+        let root_table = fft_root_table(1 << (degree_log + rate_bits));
+
+        let from_values = PolynomialBatch::<F, C, D>::from_values(
+            values,
+            rate_bits,
+            false,
+            cap_height,
+            &mut TimingTree::default(),
+            None,
+        );
+        let from_coeffs = PolynomialBatch::<F, C, D>::from_coeffs(
+            coeffs.clone(),
+            rate_bits,
+            false,
+            cap_height,
+            &mut TimingTree::default(),
+            Some(&root_table),
+        );
+        assert_eq!(from_values, from_coeffs);
+        assert_eq!(from_coeffs.polynomials, coeffs);
+
+        // The stored LDE holds the evaluations over the coset `shift * <g>` of the LDE domain.
+        let g = F::primitive_root_of_unity(degree_log + rate_bits);
+        for (index, step) in [(0, 1), (5, 1), (3, 4)] {
+            let x = F::coset_shift() * g.exp_u64((index * step) as u64);
+            let expected = coeffs.iter().map(|p| p.eval(x)).collect::<Vec<_>>();
+            assert_eq!(from_coeffs.get_lde_values(index, step), expected);
+        }
+    }
+
+    #[test]
+    fn zero_suffix_matches_from_values() {
+        let degree_log = 6;
+        let values = (0..5)
+            .map(|i| {
+                if i < 3 {
+                    PolynomialValues::new(F::rand_vec(1 << degree_log))
+                } else {
+                    PolynomialValues::zero(1 << degree_log)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let from_values = PolynomialBatch::<F, C, D>::from_values(
+            values.clone(),
+            2,
+            false,
+            2,
+            &mut TimingTree::default(),
+            None,
+        );
+        let with_zero_suffix = PolynomialBatch::<F, C, D>::from_values_with_zero_suffix(
+            values,
+            3,
+            2,
+            false,
+            2,
+            &mut TimingTree::default(),
+            None,
+        );
+        assert_eq!(from_values, with_zero_suffix);
+    }
+
+    #[test]
+    fn chunked_lde_matches_unchunked() {
+        let degree_log = 6;
+        let rate_bits = 3;
+        let coeffs = (0..5)
+            .map(|i| {
+                if i < 3 {
+                    PolynomialCoeffs::new(F::rand_vec(1 << degree_log))
+                } else {
+                    PolynomialCoeffs::zero(1 << degree_log)
+                }
+            })
+            .collect::<Vec<_>>();
+        let commit = |lde_chunk_size| {
+            PolynomialBatch::<F, C, D>::from_coeffs_chunked(
+                coeffs.clone(),
+                3,
+                rate_bits,
+                false,
+                2,
+                lde_chunk_size,
+                &mut TimingTree::default(),
+                None,
+            )
+        };
+
+        let unchunked = commit(None);
+        // Chunks smaller than the degree are rounded up to it, and others down to a power of two.
+        for lde_chunk_size in [1, 1 << degree_log, 100, 1 << (degree_log + 2)] {
+            assert_eq!(commit(Some(lde_chunk_size)), unchunked);
+        }
+    }
+}
@@ -184,7 +184,8 @@ impl<F: RichField + Extendable<D>, H: Hasher<F>, const D: usize> FriProof<F, H,
         let initial_trees_proofs = initial_trees_indices
             .iter()
             .zip(initial_trees_proofs)
-            .map(|(is, ps)| compress_merkle_proofs(cap_height, is, &ps))
+            .enumerate()
+            .map(|(i, (is, ps))| compress_merkle_proofs(params.initial_cap_height(i), is, &ps))
             .collect::<Vec<_>>();
         let steps_proofs = steps_indices
             .iter()
@@ -321,7 +322,10 @@ impl<F: RichField + Extendable<D>, H: Hasher<F>, const D: usize> CompressedFriPr
             &initial_trees_indices,
             initial_trees_proofs
         )
-        .map(|(ls, is, ps)| decompress_merkle_proofs(ls, is, &ps, height, cap_height))
+        .enumerate()
+        .map(|(i, (ls, is, ps))| {
+            decompress_merkle_proofs(ls, is, &ps, height, params.initial_cap_height(i))
+        })
         .collect::<Vec<_>>();
         let steps_proofs = izip!(&steps_evals, &steps_indices, steps_proofs, heights)
             .map(|(ls, is, ps, h)| decompress_merkle_proofs(ls, is, &ps, h, cap_height))
@@ -1,5 +1,6 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 use plonky2_maybe_rayon::*;
 
@@ -13,10 +14,21 @@ use crate::hash::merkle_tree::MerkleTree;
 use crate::iop::challenger::Challenger;
 use crate::plonk::config::GenericConfig;
 use crate::plonk::plonk_common::reduce_with_powers;
+use crate::plonk::progress::{PhaseProgress, ProgressCallback, ProverPhase};
 use crate::timed;
 use crate::util::reverse_index_bits_in_place;
 use crate::util::timing::TimingTree;
 
+/// Options for building a FRI proof, which don't change the proof.
+#[derive(Clone, Debug, Default)]
+pub struct FriProverOptions {
+    /// If set, the proof-of-work witness is searched for on a dedicated pool of this many threads
+    /// rather than on the current rayon pool.
+    pub pow_threads: Option<usize>,
+    /// Called with the progress of each FRI phase.
+    pub progress: ProgressCallback,
+}
+
 /// Builds a FRI proof.
 pub fn fri_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     initial_merkle_trees: &[&MerkleTree<F, C::Hasher>],
@@ -26,8 +38,10 @@ pub fn fri_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const
     lde_polynomial_values: PolynomialValues<F::Extension>,
     challenger: &mut Challenger<F, C::Hasher>,
     fri_params: &FriParams,
+    options: &FriProverOptions,
     timing: &mut TimingTree,
 ) -> FriProof<F, C::Hasher, D> {
+    let progress = &options.progress;
     let n = lde_polynomial_values.len();
     assert_eq!(lde_polynomial_coeffs.len(), n);
 
@@ -40,19 +54,24 @@ pub fn fri_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const
             lde_polynomial_values,
             challenger,
             fri_params,
+            progress,
         )
     );
 
     // PoW phase
+    let pow_progress = PhaseProgress::start(progress, ProverPhase::ProofOfWork);
     let pow_witness = timed!(
         timing,
         "find proof-of-work witness",
-        fri_proof_of_work::<F, C, D>(challenger, &fri_params.config)
+        fri_proof_of_work::<F, C, D>(challenger, &fri_params.config, options.pow_threads)
     );
+    pow_progress.finish();
 
     // Query phase
+    let query_progress = PhaseProgress::start(progress, ProverPhase::FriQueries);
     let query_round_proofs =
         fri_prover_query_rounds::<F, C, D>(initial_merkle_trees, &trees, challenger, n, fri_params);
+    query_progress.finish();
 
     FriProof {
         commit_phase_merkle_caps: trees.iter().map(|t| t.cap.clone()).collect(),
@@ -72,11 +91,14 @@ fn fri_committed_trees<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>,
     mut values: PolynomialValues<F::Extension>,
     challenger: &mut Challenger<F, C::Hasher>,
     fri_params: &FriParams,
+    progress: &ProgressCallback,
 ) -> FriCommitedTrees<F, C, D> {
-    let mut trees = Vec::with_capacity(fri_params.reduction_arity_bits.len());
+    let num_rounds = fri_params.reduction_arity_bits.len();
+    let mut trees = Vec::with_capacity(num_rounds);
+    let progress = PhaseProgress::start(progress, ProverPhase::FriCommit);
 
     let mut shift = F::MULTIPLICATIVE_GROUP_GENERATOR;
-    for arity_bits in &fri_params.reduction_arity_bits {
+    for (round, arity_bits) in fri_params.reduction_arity_bits.iter().enumerate() {
         let arity = 1 << arity_bits;
 
         reverse_index_bits_in_place(&mut values.values);
@@ -100,8 +122,10 @@ fn fri_committed_trees<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>,
                 .collect::<Vec<_>>(),
         );
         shift = shift.exp_u64(arity as u64);
-        values = coeffs.coset_fft(shift.into())
+        values = coeffs.coset_fft(shift.into());
+        progress.update(round + 1, num_rounds);
     }
+    progress.finish();
 
     // The coefficients being removed here should always be zero.
     coeffs
@@ -113,9 +137,14 @@ fn fri_committed_trees<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>,
 }
 
 /// Performs the proof-of-work (a.k.a. grinding) step of the FRI protocol. Returns the PoW witness.
+///
+/// The candidates are split between threads, which stop as soon as a valid witness below their
+/// remaining candidates has been found. The witness is the smallest valid one, on any number of
+/// threads.
 fn fri_proof_of_work<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     challenger: &mut Challenger<F, C::Hasher>,
     config: &FriConfig,
+    num_threads: Option<usize>,
 ) -> F {
     let min_leading_zeros = config.proof_of_work_bits + (64 - F::order().bits()) as u32;
 
@@ -139,18 +168,56 @@ fn fri_proof_of_work<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, c
     let witness_input_pos = challenger.input_buffer.len();
     duplex_intermediate_state.set_from_iter(challenger.input_buffer.clone(), 0);
 
-    let pow_witness = (0..=F::NEG_ONE.to_canonical_u64())
-        .into_par_iter()
-        .find_any(|&candidate| {
-            let mut duplex_state = duplex_intermediate_state;
-            duplex_state.set_elt(F::from_canonical_u64(candidate), witness_input_pos);
-            duplex_state.permute();
-            let pow_response = duplex_state.squeeze().iter().last().unwrap();
-            let leading_zeros = pow_response.to_canonical_u64().leading_zeros();
-            leading_zeros >= min_leading_zeros
-        })
-        .map(F::from_canonical_u64)
-        .expect("Proof of work failed. This is highly unlikely!");
+    let is_valid = |candidate: u64| {
+        let mut duplex_state = duplex_intermediate_state;
+        duplex_state.set_elt(F::from_canonical_u64(candidate), witness_input_pos);
+        duplex_state.permute();
+        let pow_response = duplex_state.squeeze().iter().last().unwrap();
+        pow_response.to_canonical_u64().leading_zeros() >= min_leading_zeros
+    };
+
+    // We search for the smallest valid witness rather than any valid one, so that the proof doesn't
+    // depend on thread scheduling or on the number of threads. Each thread tries the candidates
+    // congruent to its index modulo the number of threads, in increasing order, until they exceed
+    // the smallest valid witness found so far. Every candidate below the final minimum is tried by
+    // some thread, so the minimum is the smallest valid witness.
+    let max_candidate = F::NEG_ONE.to_canonical_u64();
+    let smallest_witness = AtomicU64::new(u64::MAX);
+    let search = || {
+        #[cfg(feature = "parallel")]
+        let num_threads = plonky2_maybe_rayon::rayon::current_num_threads() as u64;
+        #[cfg(not(feature = "parallel"))]
+        let num_threads = 1;
+        (0..num_threads).into_par_iter().for_each(|thread| {
+            for candidate in (thread..=max_candidate).step_by(num_threads as usize) {
+                if candidate > smallest_witness.load(Ordering::Relaxed) {
+                    return;
+                }
+                if is_valid(candidate) {
+                    smallest_witness.fetch_min(candidate, Ordering::Relaxed);
+                    return;
+                }
+            }
+        });
+    };
+    #[cfg(feature = "parallel")]
+    match num_threads {
+        Some(num_threads) => plonky2_maybe_rayon::rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .expect("Failed to build the proof-of-work thread pool")
+            .install(search),
+        None => search(),
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = num_threads;
+        search();
+    }
+    let pow_witness = match smallest_witness.into_inner() {
+        u64::MAX => panic!("Proof of work failed. This is highly unlikely!"),
+        witness => F::from_canonical_u64(witness),
+    };
 
     // Recompute pow_response using our normal Challenger code, and make sure it matches.
     challenger.observe_element(pow_witness);
@@ -215,3 +282,42 @@ fn fri_prover_query_round<
         steps: query_steps,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::fri::reduction_strategies::FriReductionStrategy;
+    use crate::fri::verifier::fri_verify_proof_of_work;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn proof_of_work_is_valid_and_independent_of_threads() {
+        let config = FriConfig {
+            rate_bits: 3,
+            cap_height: 4,
+            proof_of_work_bits: 18,
+            reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
+            num_query_rounds: 28,
+            final_poly_degree_bits: 0,
+        };
+        let mut challenger = Challenger::<F, <C as GenericConfig<D>>::Hasher>::new();
+        challenger.observe_elements(&F::rand_vec(3));
+
+        let witnesses = [None, Some(1), Some(3), Some(4)].map(|num_threads| {
+            let mut challenger = challenger.clone();
+            fri_proof_of_work::<F, C, D>(&mut challenger, &config, num_threads)
+        });
+        assert!(witnesses.iter().all_equal());
+
+        challenger.observe_element(witnesses[0]);
+        let pow_response = challenger.get_challenge();
+        fri_verify_proof_of_work::<F, D>(pow_response, &config).unwrap();
+    }
+}
@@ -1,14 +1,19 @@
 #[cfg(not(feature = "std"))]
-use alloc::{format, vec::Vec};
+use alloc::{collections::BTreeMap, format, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
 
 use itertools::Itertools;
+use log::Level;
 
 use crate::field::extension::Extendable;
 use crate::fri::proof::{
     FriChallengesTarget, FriInitialTreeProofTarget, FriProofTarget, FriQueryRoundTarget,
     FriQueryStepTarget,
 };
-use crate::fri::structure::{FriBatchInfoTarget, FriInstanceInfoTarget, FriOpeningsTarget};
+use crate::fri::structure::{
+    FriBatchInfoTarget, FriInstanceInfoTarget, FriOpeningsTarget, FriOracleInfo,
+};
 use crate::fri::{FriConfig, FriParams};
 use crate::gates::coset_interpolation::CosetInterpolationGate;
 use crate::gates::gate::Gate;
@@ -18,9 +23,9 @@ use crate::iop::ext_target::{flatten_target, ExtensionTarget};
 use crate::iop::target::{BoolTarget, Target};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::plonk_common::salt_size;
 use crate::util::reducing::ReducingFactorTarget;
 use crate::util::{log2_strict, reverse_index_bits_in_place};
-use crate::with_context;
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// Computes P'(x^arity) from {P(x*g^i)}_(i=0..arity), where g is a `arity`-th root of unity
@@ -44,8 +49,21 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         reverse_index_bits_in_place(&mut evals);
         // Want `g^(arity - rev_x_index_within_coset)` as in the out-of-circuit version. Compute it
         // as `(g^-1)^rev_x_index_within_coset`.
-        let start = self.exp_from_bits_const_base(g_inv, x_index_within_coset_bits.iter().rev());
+        let start = if self.naive_index_selection {
+            let g_inv = self.constant(g_inv);
+            self.exp_from_bits(g_inv, x_index_within_coset_bits.iter().rev())
+        } else {
+            self.exp_from_bits_const_base(g_inv, x_index_within_coset_bits.iter().rev())
+        };
         let coset_start = self.mul(start, x);
+        if arity_bits == 1 {
+            // The coset is `{coset_start, -coset_start}`, so `P'` is a line.
+            let neg_coset_start = self.neg(coset_start);
+            let coset_start = self.convert_to_ext(coset_start);
+            let neg_coset_start = self.convert_to_ext(neg_coset_start);
+            return self
+                .interpolate2_ext([(coset_start, evals[0]), (neg_coset_start, evals[1])], beta);
+        }
 
         // The answer is gotten by interpolating {(x*g^i, P(x*g^i))} and evaluating at beta.
         let interpolation_gate = <CosetInterpolationGate<F, D>>::with_max_degree(
@@ -58,10 +76,10 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// Make sure we have enough wires and routed wires to do the FRI checks efficiently. This check
     /// isn't required -- without it we'd get errors elsewhere in the stack -- but just gives more
     /// helpful errors.
-    fn check_recursion_config(&self, max_fri_arity_bits: usize) {
+    fn check_recursion_config(&self, max_fri_arity_bits: usize, max_cap_height: usize) {
         let random_access = RandomAccessGate::<F, D>::new_from_config(
             &self.config,
-            max_fri_arity_bits.max(self.config.fri_config.cap_height),
+            max_fri_arity_bits.max(max_cap_height),
         );
         let interpolation_gate = CosetInterpolationGate::<F, D>::with_max_degree(
             max_fri_arity_bits,
@@ -109,10 +127,44 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     ) where
         C::Hasher: AlgebraicHasher<F>,
     {
+        self.check_fri_params(params);
+        self.verify_fri_proof_with_checked_params::<C>(
+            instance,
+            openings,
+            challenges,
+            initial_merkle_caps,
+            proof,
+            params,
+        );
+    }
+
+    /// Panics if `params` are invalid, or if this circuit's config can't verify FRI proofs with
+    /// them efficiently.
+    pub(crate) fn check_fri_params(&self, params: &FriParams) {
+        if let Err(e) = params.validate() {
+            panic!("{}", e);
+        }
         if let Some(max_arity_bits) = params.max_arity_bits() {
-            self.check_recursion_config(max_arity_bits);
+            self.check_recursion_config(max_arity_bits, params.max_cap_height());
         }
+        // Query indices are decomposed into bits non-canonically. Here we verify that this has a
+        // negligible impact on soundness error.
+        Self::assert_noncanonical_indices_ok(&params.config);
+    }
 
+    /// Like `verify_fri_proof`, for `params` already checked by `check_fri_params`, e.g. once for
+    /// a batch of proofs.
+    pub(crate) fn verify_fri_proof_with_checked_params<C: GenericConfig<D, F = F>>(
+        &mut self,
+        instance: &FriInstanceInfoTarget<D>,
+        openings: &FriOpeningsTarget<D>,
+        challenges: &FriChallengesTarget<D>,
+        initial_merkle_caps: &[MerkleCapTarget],
+        proof: &FriProofTarget<D>,
+        params: &FriParams,
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
+    {
         debug_assert_eq!(
             params.final_poly_len(),
             proof.final_poly.len(),
@@ -122,11 +174,9 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         // Size of the LDE domain.
         let n = params.lde_size();
 
-        with_context!(
-            self,
-            "check PoW",
-            self.fri_verify_proof_of_work(challenges.fri_pow_response, &params.config)
-        );
+        self.with_context(Level::Debug, "check PoW", |builder| {
+            builder.fri_verify_proof_of_work(challenges.fri_pow_response, &params.config)
+        });
 
         // Check that parameters are coherent.
         debug_assert_eq!(
@@ -135,42 +185,39 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             "Number of query rounds does not match config."
         );
 
-        let precomputed_reduced_evals = with_context!(
-            self,
-            "precompute reduced evaluations",
-            PrecomputedReducedOpeningsTarget::from_os_and_alpha(
-                openings,
-                challenges.fri_alpha,
-                self
-            )
-        );
+        let precomputed_reduced_evals =
+            self.with_context(Level::Debug, "precompute reduced evaluations", |builder| {
+                PrecomputedReducedOpeningsTarget::from_os_and_alpha(
+                    instance,
+                    openings,
+                    challenges.fri_alpha,
+                    builder,
+                )
+            });
 
         for (i, round_proof) in proof.query_round_proofs.iter().enumerate() {
             // To minimize noise in our logs, we will only record a context for a single FRI query.
             // The very first query will have some extra gates due to constants being registered, so
             // the second query is a better representative.
-            let level = if i == 1 {
-                log::Level::Debug
-            } else {
-                log::Level::Trace
-            };
+            let level = if i == 1 { Level::Debug } else { Level::Trace };
 
             let num_queries = proof.query_round_proofs.len();
-            with_context!(
-                self,
+            self.with_context(
                 level,
                 &format!("verify one (of {num_queries}) query rounds"),
-                self.fri_verifier_query_round::<C>(
-                    instance,
-                    challenges,
-                    &precomputed_reduced_evals,
-                    initial_merkle_caps,
-                    proof,
-                    challenges.fri_query_indices[i],
-                    n,
-                    round_proof,
-                    params,
-                )
+                |builder| {
+                    builder.fri_verifier_query_round::<C>(
+                        instance,
+                        challenges,
+                        &precomputed_reduced_evals,
+                        initial_merkle_caps,
+                        proof,
+                        challenges.fri_query_indices[i],
+                        n,
+                        round_proof,
+                        params,
+                    )
+                },
             );
         }
     }
@@ -180,24 +227,32 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         x_index_bits: &[BoolTarget],
         proof: &FriInitialTreeProofTarget,
         initial_merkle_caps: &[MerkleCapTarget],
-        cap_index: Target,
+        params: &FriParams,
     ) {
+        // Trees with the same cap height share the index of the cap element their paths lead to.
+        let mut cap_indices = BTreeMap::new();
         for (i, ((evals, merkle_proof), cap)) in proof
             .evals_proofs
             .iter()
             .zip(initial_merkle_caps)
             .enumerate()
         {
-            with_context!(
-                self,
+            let cap_height = params.initial_cap_height(i);
+            let cap_index = *cap_indices.entry(cap_height).or_insert_with(|| {
+                self.le_sum(x_index_bits[x_index_bits.len() - cap_height..].iter())
+            });
+            self.with_context(
+                Level::Debug,
                 &format!("verify {i}'th initial Merkle proof"),
-                self.verify_merkle_proof_to_cap_with_cap_index::<H>(
-                    evals.clone(),
-                    x_index_bits,
-                    cap_index,
-                    cap,
-                    merkle_proof
-                )
+                |builder| {
+                    builder.verify_merkle_proof_to_cap_with_cap_index::<H>(
+                        evals.clone(),
+                        x_index_bits,
+                        cap_index,
+                        cap,
+                        merkle_proof,
+                    )
+                },
             );
         }
     }
@@ -215,17 +270,18 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let degree_log = params.degree_bits;
         debug_assert_eq!(
             degree_log,
-            params.config.cap_height + proof.evals_proofs[0].1.siblings.len()
+            params.initial_cap_height(0) + proof.evals_proofs[0].1.siblings.len()
                 - params.config.rate_bits
         );
         let subgroup_x = self.convert_to_ext(subgroup_x);
         let mut alpha = ReducingFactorTarget::new(alpha);
         let mut sum = self.zero_extension();
 
-        for (batch, reduced_openings) in instance
+        for ((batch, neg_shifted_openings), scaled_point) in instance
             .batches
             .iter()
-            .zip(&precomputed_reduced_evals.reduced_openings_at_point)
+            .zip(&precomputed_reduced_evals.neg_shifted_openings)
+            .zip(&precomputed_reduced_evals.scaled_points)
         {
             let FriBatchInfoTarget { point, polynomials } = batch;
             let evals = polynomials
@@ -236,10 +292,16 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                     proof.unsalted_eval(p.oracle_index, p.polynomial_index, salted)
                 })
                 .collect_vec();
-            let reduced_evals = alpha.reduce_base(&evals, self);
-            let numerator = self.sub_extension(reduced_evals, *reduced_openings);
-            let denominator = self.sub_extension(subgroup_x, *point);
-            sum = alpha.shift(sum, self);
+            // Starting the reduction from the shifted openings subtracts them along the way.
+            let numerator = alpha.reduce_base_from(*neg_shifted_openings, &evals, self);
+            // Rather than shifting the sum by the later batches' powers of `alpha`, each term is
+            // divided by a denominator scaled by the inverse power, at no extra cost.
+            let denominator = match scaled_point {
+                Some((inv_power, neg_scaled_point)) => {
+                    self.mul_add_extension(*inv_power, subgroup_x, *neg_scaled_point)
+                }
+                None => self.sub_extension(subgroup_x, *point),
+            };
             sum = self.div_add_extension(numerator, denominator, sum);
         }
 
@@ -262,39 +324,42 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     {
         let n_log = log2_strict(n);
 
-        // Note that this `low_bits` decomposition permits non-canonical binary encodings. Here we
-        // verify that this has a negligible impact on soundness error.
-        Self::assert_noncanonical_indices_ok(&params.config);
-        let mut x_index_bits = self.low_bits(x_index, n_log, F::BITS);
+        // Note that this decomposition permits non-canonical binary encodings, which
+        // `check_fri_params` has checked are harmless.
+        let mut x_index_bits = self.split_le_noncanonical(x_index, F::BITS);
+        x_index_bits.truncate(n_log);
 
         let cap_index =
             self.le_sum(x_index_bits[x_index_bits.len() - params.config.cap_height..].iter());
-        with_context!(
-            self,
-            "check FRI initial proof",
-            self.fri_verify_initial_proof::<C::Hasher>(
+        self.with_context(Level::Debug, "check FRI initial proof", |builder| {
+            builder.fri_verify_initial_proof::<C::Hasher>(
                 &x_index_bits,
                 &round_proof.initial_trees_proof,
                 initial_merkle_caps,
-                cap_index
+                params,
             )
-        );
+        });
 
         // `subgroup_x` is `subgroup[x_index]`, i.e., the actual field element in the domain.
-        let mut subgroup_x = with_context!(self, "compute x from its index", {
-            let g = self.constant(F::coset_shift());
-            let phi = F::primitive_root_of_unity(n_log);
-            let phi = self.exp_from_bits_const_base(phi, x_index_bits.iter().rev());
-            // subgroup_x = g * phi
-            self.mul(g, phi)
-        });
+        let mut subgroup_x =
+            self.with_context(Level::Debug, "compute x from its index", |builder| {
+                let g = F::coset_shift();
+                let phi = F::primitive_root_of_unity(n_log);
+                if builder.naive_index_selection {
+                    let g = builder.constant(g);
+                    let phi = builder.constant(phi);
+                    let phi = builder.exp_from_bits(phi, x_index_bits.iter().rev());
+                    builder.mul(g, phi)
+                } else {
+                    // subgroup_x = g * phi, with the powers of phi and the factor g as constants.
+                    builder.scaled_exp_from_bits_const_base(g, phi, x_index_bits.iter().rev())
+                }
+            });
 
         // old_eval is the last derived evaluation; it will be checked for consistency with its
         // committed "parent" value in the next iteration.
-        let mut old_eval = with_context!(
-            self,
-            "combine initial oracles",
-            self.fri_combine_initial(
+        let mut old_eval = self.with_context(Level::Debug, "combine initial oracles", |builder| {
+            builder.fri_combine_initial(
                 instance,
                 &round_proof.initial_trees_proof,
                 challenges.fri_alpha,
@@ -302,7 +367,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                 precomputed_reduced_evals,
                 params,
             )
-        );
+        });
 
         for (i, &arity_bits) in params.reduction_arity_bits.iter().enumerate() {
             let evals = &round_proof.steps[i].evals;
@@ -310,36 +375,40 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             // Split x_index into the index of the coset x is in, and the index of x within that coset.
             let coset_index_bits = x_index_bits[arity_bits..].to_vec();
             let x_index_within_coset_bits = &x_index_bits[..arity_bits];
-            let x_index_within_coset = self.le_sum(x_index_within_coset_bits.iter());
 
             // Check consistency with our old evaluation from the previous round.
-            let new_eval = self.random_access_extension(x_index_within_coset, evals.clone());
+            let new_eval = if self.naive_index_selection {
+                self.select_ext_by_bits(x_index_within_coset_bits, evals.clone())
+            } else {
+                let x_index_within_coset = self.le_sum(x_index_within_coset_bits.iter());
+                self.random_access_extension(x_index_within_coset, evals.clone())
+            };
             self.connect_extension(new_eval, old_eval);
 
             // Infer P(y) from {P(x)}_{x^arity=y}.
-            old_eval = with_context!(
-                self,
+            old_eval = self.with_context(
+                Level::Debug,
                 "infer evaluation using interpolation",
-                self.compute_evaluation(
-                    subgroup_x,
-                    x_index_within_coset_bits,
-                    arity_bits,
-                    evals,
-                    challenges.fri_betas[i],
-                )
+                |builder| {
+                    builder.compute_evaluation(
+                        subgroup_x,
+                        x_index_within_coset_bits,
+                        arity_bits,
+                        evals,
+                        challenges.fri_betas[i],
+                    )
+                },
             );
 
-            with_context!(
-                self,
-                "verify FRI round Merkle proof.",
-                self.verify_merkle_proof_to_cap_with_cap_index::<C::Hasher>(
+            self.with_context(Level::Debug, "verify FRI round Merkle proof.", |builder| {
+                builder.verify_merkle_proof_to_cap_with_cap_index::<C::Hasher>(
                     flatten_target(evals),
                     &coset_index_bits,
                     cap_index,
                     &proof.commit_phase_merkle_caps[i],
                     &round_proof.steps[i].merkle_proof,
                 )
-            );
+            });
 
             // Update the point x to x^arity.
             subgroup_x = self.exp_power_of_2(subgroup_x, arity_bits);
@@ -349,13 +418,13 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         // Final check of FRI. After all the reductions, we check that the final polynomial is equal
         // to the one sent by the prover.
-        let eval = with_context!(
-            self,
+        let eval = self.with_context(
+            Level::Debug,
             &format!(
                 "evaluate final polynomial of length {}",
                 proof.final_poly.len()
             ),
-            proof.final_poly.eval_scalar(self, subgroup_x)
+            |builder| proof.final_poly.eval_scalar(builder, subgroup_x),
         );
         self.connect_extension(eval, old_eval);
     }
@@ -380,9 +449,11 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                 "A non-negligible portion of field elements are in the range that permits non-canonical encodings. Need to do more analysis or enforce canonical encodings.");
     }
 
+    /// Adds a virtual FRI proof opening `oracles`, whose leaves are salted if they're blinded and
+    /// `params` is hiding.
     pub fn add_virtual_fri_proof(
         &mut self,
-        num_leaves_per_oracle: &[usize],
+        oracles: &[FriOracleInfo],
         params: &FriParams,
     ) -> FriProofTarget<D> {
         let cap_height = params.config.cap_height;
@@ -391,7 +462,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             .map(|_| self.add_virtual_cap(cap_height))
             .collect();
         let query_round_proofs = (0..num_queries)
-            .map(|_| self.add_virtual_fri_query(num_leaves_per_oracle, params))
+            .map(|_| self.add_virtual_fri_query(oracles, params))
             .collect();
         let final_poly = self.add_virtual_poly_coeff_ext(params.final_poly_len());
         let pow_witness = self.add_virtual_target();
@@ -405,16 +476,15 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
     fn add_virtual_fri_query(
         &mut self,
-        num_leaves_per_oracle: &[usize],
+        oracles: &[FriOracleInfo],
         params: &FriParams,
     ) -> FriQueryRoundTarget<D> {
+        let initial_trees_proof = self.add_virtual_fri_initial_trees_proof(oracles, params);
+
         let cap_height = params.config.cap_height;
         assert!(params.lde_bits() >= cap_height);
         let mut merkle_proof_len = params.lde_bits() - cap_height;
 
-        let initial_trees_proof =
-            self.add_virtual_fri_initial_trees_proof(num_leaves_per_oracle, merkle_proof_len);
-
         let mut steps = Vec::with_capacity(params.reduction_arity_bits.len());
         for &arity_bits in &params.reduction_arity_bits {
             assert!(merkle_proof_len >= arity_bits);
@@ -430,14 +500,18 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
     fn add_virtual_fri_initial_trees_proof(
         &mut self,
-        num_leaves_per_oracle: &[usize],
-        initial_merkle_proof_len: usize,
+        oracles: &[FriOracleInfo],
+        params: &FriParams,
     ) -> FriInitialTreeProofTarget {
-        let evals_proofs = num_leaves_per_oracle
+        let evals_proofs = oracles
             .iter()
-            .map(|&num_oracle_leaves| {
-                let leaves = self.add_virtual_targets(num_oracle_leaves);
-                let merkle_proof = self.add_virtual_merkle_proof(initial_merkle_proof_len);
+            .enumerate()
+            .map(|(i, oracle)| {
+                let cap_height = params.initial_cap_height(i);
+                assert!(params.lde_bits() >= cap_height);
+                let leaf_len = oracle.num_polys + salt_size(params.hiding && oracle.blinding);
+                let leaves = self.add_virtual_targets(leaf_len);
+                let merkle_proof = self.add_virtual_merkle_proof(params.lde_bits() - cap_height);
                 (leaves, merkle_proof)
             })
             .collect();
@@ -456,26 +530,66 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 }
 
-/// For each opening point, holds the reduced (by `alpha`) evaluations of each polynomial that's
-/// opened at that point.
+/// The parts of the combination of initial openings which don't depend on the query, computed once
+/// and shared by every query round.
 #[derive(Clone)]
 struct PrecomputedReducedOpeningsTarget<const D: usize> {
-    reduced_openings_at_point: Vec<ExtensionTarget<D>>,
+    /// For each opening point, the reduced (by `alpha`) openings of the polynomials opened at that
+    /// point, negated and divided by `alpha^k`, where `k` is the power by which reducing a query's
+    /// evaluations shifts its initial accumulator. Starting from it yields their difference.
+    neg_shifted_openings: Vec<ExtensionTarget<D>>,
+    /// For each opening point but the last, `alpha^-k` and `-alpha^-k point`, where `k` is the
+    /// number of polynomials opened at later points. The batch's term in the combination is
+    /// `alpha^k (reduced_evals - reduced_openings) / (x - point)`, and dividing by
+    /// `alpha^-k x - alpha^-k point` saves shifting the sum in every query round.
+    scaled_points: Vec<Option<(ExtensionTarget<D>, ExtensionTarget<D>)>>,
 }
 
 impl<const D: usize> PrecomputedReducedOpeningsTarget<D> {
     fn from_os_and_alpha<F: RichField + Extendable<D>>(
+        instance: &FriInstanceInfoTarget<D>,
         openings: &FriOpeningsTarget<D>,
         alpha: ExtensionTarget<D>,
         builder: &mut CircuitBuilder<F, D>,
     ) -> Self {
-        let reduced_openings_at_point = openings
+        let zero = builder.zero_extension();
+        let neg_scaled = |builder: &mut CircuitBuilder<F, D>, exponent: u64, x| {
+            let power = builder.exp_u64_extension(alpha, exponent);
+            let inv_power = builder.inverse_extension(power);
+            let neg_scaled_x =
+                builder.arithmetic_extension(F::NEG_ONE, F::ZERO, inv_power, x, zero);
+            (inv_power, neg_scaled_x)
+        };
+
+        let neg_shifted_openings = openings
             .batches
             .iter()
-            .map(|batch| ReducingFactorTarget::new(alpha).reduce(&batch.values, builder))
+            .map(|batch| {
+                let reduced_openings =
+                    ReducingFactorTarget::new(alpha).reduce(&batch.values, builder);
+                let initial_power =
+                    ReducingFactorTarget::reduce_base_initial_power(batch.values.len(), builder);
+                neg_scaled(builder, initial_power, reduced_openings).1
+            })
             .collect();
+
+        let mut later_polys = 0;
+        let mut scaled_points = instance
+            .batches
+            .iter()
+            .rev()
+            .map(|batch| {
+                let scaled_point =
+                    (later_polys > 0).then(|| neg_scaled(builder, later_polys as u64, batch.point));
+                later_polys += batch.polynomials.len();
+                scaled_point
+            })
+            .collect::<Vec<_>>();
+        scaled_points.reverse();
+
         Self {
-            reduced_openings_at_point,
+            neg_shifted_openings,
+            scaled_points,
         }
     }
 }
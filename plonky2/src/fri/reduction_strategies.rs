@@ -2,12 +2,12 @@
 use alloc::{vec, vec::Vec};
 
 use log::debug;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 #[cfg(feature = "timing")]
 use web_time::Instant;
 
 /// A method for deciding what arity to use at each reduction layer.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum FriReductionStrategy {
     /// Specifies the exact sequence of arities (expressed in bits) to use.
     Fixed(Vec<usize>),
@@ -26,31 +26,38 @@ pub enum FriReductionStrategy {
 }
 
 impl FriReductionStrategy {
-    /// The arity of each FRI reduction step, expressed as the log2 of the actual arity.
+    /// The arity of each FRI reduction step, expressed as the log2 of the actual arity. The
+    /// strategies other than `Fixed` stop before reducing the degree below
+    /// `2^final_poly_degree_bits`.
     pub fn reduction_arity_bits(
         &self,
         mut degree_bits: usize,
         rate_bits: usize,
         cap_height: usize,
         num_queries: usize,
+        final_poly_degree_bits: usize,
     ) -> Vec<usize> {
         match self {
             FriReductionStrategy::Fixed(reduction_arity_bits) => reduction_arity_bits.to_vec(),
             &FriReductionStrategy::ConstantArityBits(arity_bits, final_poly_bits) => {
                 let mut result = Vec::new();
                 while degree_bits > final_poly_bits
+                    && degree_bits >= final_poly_degree_bits + arity_bits
                     && degree_bits + rate_bits - arity_bits >= cap_height
                 {
                     result.push(arity_bits);
-                    assert!(degree_bits >= arity_bits);
                     degree_bits -= arity_bits;
                 }
                 result.shrink_to_fit();
                 result
             }
-            FriReductionStrategy::MinSize(opt_max_arity_bits) => {
-                min_size_arity_bits(degree_bits, rate_bits, num_queries, *opt_max_arity_bits)
-            }
+            FriReductionStrategy::MinSize(opt_max_arity_bits) => min_size_arity_bits(
+                degree_bits,
+                rate_bits,
+                num_queries,
+                final_poly_degree_bits,
+                *opt_max_arity_bits,
+            ),
         }
     }
 }
@@ -59,6 +66,7 @@ fn min_size_arity_bits(
     degree_bits: usize,
     rate_bits: usize,
     num_queries: usize,
+    final_poly_degree_bits: usize,
     opt_max_arity_bits: Option<usize>,
 ) -> Vec<usize> {
     // 2^4 is the largest arity we see in optimal reduction sequences in practice. For 2^5 to occur
@@ -67,8 +75,14 @@ fn min_size_arity_bits(
 
     #[cfg(feature = "timing")]
     let start = Instant::now();
-    let (mut arity_bits, fri_proof_size) =
-        min_size_arity_bits_helper(degree_bits, rate_bits, num_queries, max_arity_bits, vec![]);
+    let (mut arity_bits, fri_proof_size) = min_size_arity_bits_helper(
+        degree_bits,
+        rate_bits,
+        num_queries,
+        final_poly_degree_bits,
+        max_arity_bits,
+        vec![],
+    );
     arity_bits.shrink_to_fit();
 
     #[cfg(feature = "timing")]
@@ -89,12 +103,13 @@ fn min_size_arity_bits_helper(
     degree_bits: usize,
     rate_bits: usize,
     num_queries: usize,
+    final_poly_degree_bits: usize,
     global_max_arity_bits: usize,
     prefix: Vec<usize>,
 ) -> (Vec<usize>, usize) {
     let sum_of_arities: usize = prefix.iter().sum();
     let current_layer_bits = degree_bits + rate_bits - sum_of_arities;
-    assert!(current_layer_bits >= rate_bits);
+    assert!(current_layer_bits >= rate_bits + final_poly_degree_bits);
 
     let mut best_arity_bits = prefix.clone();
     let mut best_size = relative_proof_size(degree_bits, rate_bits, num_queries, &prefix);
@@ -106,7 +121,7 @@ fn min_size_arity_bits_helper(
         .last()
         .copied()
         .unwrap_or(global_max_arity_bits)
-        .min(current_layer_bits - rate_bits);
+        .min(current_layer_bits - rate_bits - final_poly_degree_bits);
 
     for next_arity_bits in 1..=max_arity_bits {
         let mut extended_prefix = prefix.clone();
@@ -116,6 +131,7 @@ fn min_size_arity_bits_helper(
             degree_bits,
             rate_bits,
             num_queries,
+            final_poly_degree_bits,
             max_arity_bits,
             extended_prefix,
         );
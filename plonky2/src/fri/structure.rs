@@ -8,6 +8,8 @@ use core::ops::Range;
 use crate::field::extension::Extendable;
 use crate::hash::hash_types::RichField;
 use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::target::Target;
+use crate::plonk::circuit_builder::CircuitBuilder;
 
 /// Describes an instance of a FRI-based batch opening.
 #[derive(Debug)]
@@ -27,9 +29,44 @@ pub struct FriInstanceInfoTarget<const D: usize> {
     pub batches: Vec<FriBatchInfoTarget<D>>,
 }
 
+/// A `FriInstanceInfoTarget` whose points are yet to be derived from `zeta`. It only depends on
+/// the circuit whose proofs are opened, so a verifier of several of its proofs builds it once.
+#[derive(Debug)]
+pub(crate) struct FriInstanceShapeTarget<const D: usize> {
+    pub oracles: Vec<FriOracleInfo>,
+    /// The polynomials of each batch, and the constant its point is `zeta` scaled by, or `None`
+    /// if it is opened at `zeta` itself.
+    pub batches: Vec<(Option<Target>, Vec<FriPolynomialInfo>)>,
+}
+
+impl<const D: usize> FriInstanceShapeTarget<D> {
+    /// The instance of a proof whose first batch is opened at `zeta`.
+    pub(crate) fn at_zeta<F: RichField + Extendable<D>>(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        zeta: ExtensionTarget<D>,
+    ) -> FriInstanceInfoTarget<D> {
+        let batches = self
+            .batches
+            .iter()
+            .map(|(shift, polynomials)| FriBatchInfoTarget {
+                point: shift.map_or(zeta, |shift| builder.scalar_mul_ext(shift, zeta)),
+                polynomials: polynomials.clone(),
+            })
+            .collect();
+        FriInstanceInfoTarget {
+            oracles: self.oracles.clone(),
+            batches,
+        }
+    }
+}
+
+/// An oracle committed to before FRI's commit phase.
 #[derive(Copy, Clone, Debug)]
 pub struct FriOracleInfo {
+    /// The number of polynomials in the oracle, not counting salts.
     pub num_polys: usize,
+    /// Whether the oracle's leaves are salted when the proof is hiding.
     pub blinding: bool,
 }
 
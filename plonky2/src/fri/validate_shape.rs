@@ -1,3 +1,6 @@
+#[cfg(not(feature = "std"))]
+use alloc::format;
+
 use anyhow::ensure;
 
 use crate::field::extension::Extendable;
@@ -7,6 +10,7 @@ use crate::fri::FriParams;
 use crate::hash::hash_types::RichField;
 use crate::plonk::config::GenericConfig;
 use crate::plonk::plonk_common::salt_size;
+use crate::plonk::verifier::VerificationError;
 
 pub(crate) fn validate_fri_proof_shape<F, C, const D: usize>(
     proof: &FriProof<F, C::Hasher, D>,
@@ -17,6 +21,8 @@ where
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
 {
+    params.validate()?;
+
     let FriProof {
         commit_phase_merkle_caps,
         query_round_proofs,
@@ -25,29 +31,67 @@ where
     } = proof;
 
     let cap_height = params.config.cap_height;
-    for cap in commit_phase_merkle_caps {
-        ensure!(cap.height() == cap_height);
+    for (step, cap) in commit_phase_merkle_caps.iter().enumerate() {
+        ensure!(
+            cap.height() == cap_height,
+            VerificationError::shape(
+                format!("commit-phase cap height of step {}", step),
+                cap_height,
+                cap.height()
+            )
+        );
     }
 
-    for query_round in query_round_proofs {
+    for (round, query_round) in query_round_proofs.iter().enumerate() {
         let FriQueryRound {
             initial_trees_proof,
             steps,
         } = query_round;
 
-        ensure!(initial_trees_proof.evals_proofs.len() == instance.oracles.len());
-        for ((leaf, merkle_proof), oracle) in initial_trees_proof
+        ensure!(
+            initial_trees_proof.evals_proofs.len() == instance.oracles.len(),
+            VerificationError::shape(
+                format!("query round {} initial tree proofs", round),
+                instance.oracles.len(),
+                initial_trees_proof.evals_proofs.len()
+            )
+        );
+        for (i, ((leaf, merkle_proof), oracle)) in initial_trees_proof
             .evals_proofs
             .iter()
             .zip(&instance.oracles)
+            .enumerate()
         {
-            ensure!(leaf.len() == oracle.num_polys + salt_size(oracle.blinding && params.hiding));
-            ensure!(merkle_proof.len() + cap_height == params.lde_bits());
+            let leaf_len = oracle.num_polys + salt_size(oracle.blinding && params.hiding);
+            ensure!(
+                leaf.len() == leaf_len,
+                VerificationError::shape(
+                    format!("query round {} leaf of initial oracle {}", round, i),
+                    leaf_len,
+                    leaf.len()
+                )
+            );
+            let initial_cap_height = params.initial_cap_height(i);
+            ensure!(
+                merkle_proof.len() + initial_cap_height == params.lde_bits(),
+                VerificationError::shape(
+                    format!("query round {} Merkle proof of initial oracle {}", round, i),
+                    params.lde_bits().saturating_sub(initial_cap_height),
+                    merkle_proof.len()
+                )
+            );
         }
 
-        ensure!(steps.len() == params.reduction_arity_bits.len());
+        ensure!(
+            steps.len() == params.reduction_arity_bits.len(),
+            VerificationError::shape(
+                format!("query round {} steps", round),
+                params.reduction_arity_bits.len(),
+                steps.len()
+            )
+        );
         let mut codeword_len_bits = params.lde_bits();
-        for (step, arity_bits) in steps.iter().zip(&params.reduction_arity_bits) {
+        for (i, (step, arity_bits)) in steps.iter().zip(&params.reduction_arity_bits).enumerate() {
             let FriQueryStep {
                 evals,
                 merkle_proof,
@@ -56,12 +100,37 @@ where
             let arity = 1 << arity_bits;
             codeword_len_bits -= arity_bits;
 
-            ensure!(evals.len() == arity);
-            ensure!(merkle_proof.len() + cap_height == codeword_len_bits);
+            ensure!(
+                evals.len() == arity,
+                VerificationError::shape(
+                    format!("query round {} evaluations of step {}", round, i),
+                    arity,
+                    evals.len()
+                )
+            );
+            ensure!(
+                merkle_proof.len() + cap_height == codeword_len_bits,
+                VerificationError::shape(
+                    format!("query round {} Merkle proof of step {}", round, i),
+                    codeword_len_bits.saturating_sub(cap_height),
+                    merkle_proof.len()
+                )
+            );
         }
     }
 
-    ensure!(final_poly.len() == params.final_poly_len());
+    let max_len = params.final_poly_len();
+    ensure!(
+        final_poly.len() <= max_len,
+        VerificationError::FinalPolyDegreeTooHigh {
+            max_len,
+            len: final_poly.len()
+        }
+    );
+    ensure!(
+        final_poly.len() == max_len,
+        VerificationError::shape("final polynomial", max_len, final_poly.len())
+    );
 
     Ok(())
 }
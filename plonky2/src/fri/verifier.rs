@@ -4,7 +4,7 @@ use alloc::vec::Vec;
 use anyhow::{ensure, Result};
 
 use crate::field::extension::{flatten, Extendable, FieldExtension};
-use crate::field::interpolation::{barycentric_weights, interpolate};
+use crate::field::interpolation::{barycentric_weights, interpolate, interpolate2};
 use crate::field::types::Field;
 use crate::fri::proof::{FriChallenges, FriInitialTreeProof, FriProof, FriQueryRound};
 use crate::fri::structure::{FriBatchInfo, FriInstanceInfo, FriOpenings};
@@ -14,6 +14,7 @@ use crate::hash::hash_types::RichField;
 use crate::hash::merkle_proofs::verify_merkle_proof_to_cap;
 use crate::hash::merkle_tree::MerkleCap;
 use crate::plonk::config::{GenericConfig, Hasher};
+use crate::plonk::verifier::{MerkleOracle, VerificationError};
 use crate::util::reducing::ReducingFactor;
 use crate::util::{log2_strict, reverse_bits, reverse_index_bits_in_place};
 
@@ -36,6 +37,11 @@ pub(crate) fn compute_evaluation<F: Field + Extendable<D>, const D: usize>(
     reverse_index_bits_in_place(&mut evals);
     let rev_x_index_within_coset = reverse_bits(x_index_within_coset, arity_bits);
     let coset_start = x * g.exp_u64((arity - rev_x_index_within_coset) as u64);
+    if arity_bits == 1 {
+        // The coset is `{coset_start, -coset_start}`, so `P'` is a line.
+        let coset_start = F::Extension::from_basefield(coset_start);
+        return interpolate2([(coset_start, evals[0]), (-coset_start, evals[1])], beta);
+    }
     // The answer is gotten by interpolating {(x*g^i, P(x*g^i))} and evaluating at beta.
     let points = g
         .powers()
@@ -53,7 +59,7 @@ pub(crate) fn fri_verify_proof_of_work<F: RichField + Extendable<D>, const D: us
     ensure!(
         fri_pow_response.to_canonical_u64().leading_zeros()
             >= config.proof_of_work_bits + (64 - F::order().bits()) as u32,
-        "Invalid proof of work witness."
+        VerificationError::InvalidProofOfWork
     );
 
     Ok(())
@@ -82,22 +88,36 @@ pub fn verify_fri_proof<
     // Check that parameters are coherent.
     ensure!(
         params.config.num_query_rounds == proof.query_round_proofs.len(),
-        "Number of query rounds does not match config."
+        VerificationError::shape(
+            "query rounds",
+            params.config.num_query_rounds,
+            proof.query_round_proofs.len()
+        )
     );
 
     let precomputed_reduced_evals =
         PrecomputedReducedOpenings::from_os_and_alpha(openings, challenges.fri_alpha);
-    for (&x_index, round_proof) in challenges
+    for (round, (&x_index, round_proof)) in challenges
         .fri_query_indices
         .iter()
         .zip(&proof.query_round_proofs)
+        .enumerate()
     {
+        ensure!(
+            x_index < n,
+            VerificationError::FriQueryIndexOutOfRange {
+                round,
+                index: x_index,
+                domain_size: n,
+            }
+        );
         fri_verifier_query_round::<F, C, D>(
             instance,
             challenges,
             &precomputed_reduced_evals,
             initial_merkle_caps,
             proof,
+            round,
             x_index,
             n,
             round_proof,
@@ -109,12 +129,25 @@ pub fn verify_fri_proof<
 }
 
 fn fri_verify_initial_proof<F: RichField, H: Hasher<F>>(
+    round: usize,
     x_index: usize,
     proof: &FriInitialTreeProof<F, H>,
     initial_merkle_caps: &[MerkleCap<F, H>],
 ) -> Result<()> {
-    for ((evals, merkle_proof), cap) in proof.evals_proofs.iter().zip(initial_merkle_caps) {
-        verify_merkle_proof_to_cap::<F, H>(evals.clone(), x_index, cap, merkle_proof)?;
+    for (i, ((evals, merkle_proof), cap)) in proof
+        .evals_proofs
+        .iter()
+        .zip(initial_merkle_caps)
+        .enumerate()
+    {
+        verify_merkle_proof_to_cap::<F, H>(evals.clone(), x_index, cap, merkle_proof).map_err(
+            |_| {
+                anyhow::Error::msg(VerificationError::MerkleCapMismatch {
+                    round,
+                    oracle: MerkleOracle::Initial(i),
+                })
+            },
+        )?;
     }
 
     Ok(())
@@ -171,12 +204,14 @@ fn fri_verifier_query_round<
     precomputed_reduced_evals: &PrecomputedReducedOpenings<F, D>,
     initial_merkle_caps: &[MerkleCap<F, C::Hasher>],
     proof: &FriProof<F, C::Hasher, D>,
+    round: usize,
     mut x_index: usize,
     n: usize,
     round_proof: &FriQueryRound<F, C::Hasher, D>,
     params: &FriParams,
 ) -> Result<()> {
     fri_verify_initial_proof::<F, C::Hasher>(
+        round,
         x_index,
         &round_proof.initial_trees_proof,
         initial_merkle_caps,
@@ -206,7 +241,10 @@ fn fri_verifier_query_round<
         let x_index_within_coset = x_index & (arity - 1);
 
         // Check consistency with our old evaluation from the previous round.
-        ensure!(evals[x_index_within_coset] == old_eval);
+        ensure!(
+            evals[x_index_within_coset] == old_eval,
+            VerificationError::FriFoldingMismatch { round, step: i }
+        );
 
         // Infer P(y) from {P(x)}_{x^arity=y}.
         old_eval = compute_evaluation(
@@ -222,7 +260,13 @@ fn fri_verifier_query_round<
             coset_index,
             &proof.commit_phase_merkle_caps[i],
             &round_proof.steps[i].merkle_proof,
-        )?;
+        )
+        .map_err(|_| {
+            anyhow::Error::msg(VerificationError::MerkleCapMismatch {
+                round,
+                oracle: MerkleOracle::CommitPhase(i),
+            })
+        })?;
 
         // Update the point x to x^arity.
         subgroup_x = subgroup_x.exp_power_of_2(arity_bits);
@@ -234,7 +278,7 @@ fn fri_verifier_query_round<
     // to the one sent by the prover.
     ensure!(
         proof.final_poly.eval(subgroup_x.into()) == old_eval,
-        "Final polynomial evaluation is invalid."
+        VerificationError::FinalPolyEvaluationMismatch { round }
     );
 
     Ok(())
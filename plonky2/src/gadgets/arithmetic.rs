@@ -6,12 +6,14 @@ use alloc::{
 };
 use core::borrow::Borrow;
 
+use anyhow::Result;
+
 use crate::field::extension::Extendable;
 use crate::field::types::Field64;
 use crate::gates::arithmetic_base::ArithmeticGate;
 use crate::gates::exponentiation::ExponentiationGate;
 use crate::hash::hash_types::RichField;
-use crate::iop::generator::{GeneratedValues, SimpleGenerator};
+use crate::iop::generator::{GeneratedValues, GenerationError, SimpleGenerator};
 use crate::iop::target::{BoolTarget, Target};
 use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
@@ -80,7 +82,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             return result;
         }
 
-        // Otherwise, we must actually perform the operation using an ArithmeticExtensionGate slot.
+        // Otherwise, we must actually perform the operation using an ArithmeticGate slot.
         let result = self.add_base_arithmetic_operation(operation);
         self.base_arithmetic_results.insert(operation, result);
         result
@@ -280,15 +282,28 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         base: F,
         exponent_bits: impl IntoIterator<Item = impl Borrow<BoolTarget>>,
     ) -> Target {
-        let base_t = self.constant(base);
+        self.scaled_exp_from_bits_const_base(F::ONE, base, exponent_bits)
+    }
+
+    /// Computes `scale * base^exponent`, given the little-endian bits of `exponent`. The powers
+    /// `base^(2^i)` are precomputed, so each bit costs a single arithmetic operation, and the
+    /// constant factor comes for free as the initial value of the product.
+    pub fn scaled_exp_from_bits_const_base(
+        &mut self,
+        scale: F,
+        base: F,
+        exponent_bits: impl IntoIterator<Item = impl Borrow<BoolTarget>>,
+    ) -> Target {
         let exponent_bits: Vec<_> = exponent_bits.into_iter().map(|b| *b.borrow()).collect();
 
         if exponent_bits.len() > self.num_base_arithmetic_ops_per_gate() {
             // Cheaper to just use `ExponentiateGate`.
-            return self.exp_from_bits(base_t, exponent_bits);
+            let base_t = self.constant(base);
+            let power = self.exp_from_bits(base_t, exponent_bits);
+            return self.mul_const(scale, power);
         }
 
-        let mut product = self.one();
+        let mut product = self.constant(scale);
         for (i, bit) in exponent_bits.iter().enumerate() {
             let pow = 1 << i;
             // If the bit is on, we multiply product by base^pow.
@@ -320,14 +335,16 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.exp_from_bits(base, exp_bits)
     }
 
-    /// Computes `x / y`. Results in an unsatisfiable instance if `y = 0`.
+    /// Computes `x / y`. Results in an unsatisfiable instance if `y = 0`, which witness generation
+    /// reports as an error.
     pub fn div(&mut self, x: Target, y: Target) -> Target {
         let x = self.convert_to_ext(x);
         let y = self.convert_to_ext(y);
         self.div_extension(x, y).0[0]
     }
 
-    /// Computes `1 / x`. Results in an unsatisfiable instance if `x = 0`.
+    /// Computes `1 / x`. Results in an unsatisfiable instance if `x = 0`, which witness generation
+    /// reports as an error.
     pub fn inverse(&mut self, x: Target) -> Target {
         let x_ext = self.convert_to_ext(x);
         self.inverse_extension(x_ext).0[0]
@@ -366,6 +383,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let not_equal = self.not(equal);
         let inv = self.add_virtual_target();
         self.add_simple_generator(EqualityGenerator { x, y, equal, inv });
+        self.declare_checked_hints(&[equal.target, inv]);
 
         let diff = self.sub(x, y);
         let not_equal_check = self.mul(equal.target, diff);
@@ -378,6 +396,24 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         equal
     }
+
+    /// Enforces that `x` is nonzero, by constraining `x` times a witness inverse to be one. Witness
+    /// generation fails if `x = 0`.
+    pub fn assert_nonzero(&mut self, x: Target) {
+        let inv = self.add_virtual_target();
+        self.add_simple_generator(NonzeroGenerator { x, inv });
+        self.declare_checked_hints(&[inv]);
+
+        let x_inv = self.mul(x, inv);
+        self.assert_one(x_inv);
+    }
+
+    /// Enforces that `x` and `y` are different. Unlike `is_equal`, this doesn't output a boolean,
+    /// and so takes a single multiplication on top of the subtraction.
+    pub fn assert_ne(&mut self, x: Target, y: Target) {
+        let diff = self.sub(x, y);
+        self.assert_nonzero(diff);
+    }
 }
 
 #[derive(Debug, Default)]
@@ -401,10 +437,21 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Equ
         let x = witness.get_target(self.x);
         let y = witness.get_target(self.y);
 
-        let inv = if x != y { (x - y).inverse() } else { F::ZERO };
-
         out_buffer.set_bool_target(self.equal, x == y);
-        out_buffer.set_target(self.inv, inv);
+        if x != y {
+            out_buffer.set_quotients(&[self.inv], &[F::ONE], x - y);
+        } else {
+            out_buffer.set_target(self.inv, F::ZERO);
+        }
+    }
+
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        Some(Self {
+            x: map(self.x),
+            y: map(self.y),
+            equal: BoolTarget::new_unsafe(map(self.equal.target)),
+            inv: map(self.inv),
+        })
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
@@ -423,6 +470,63 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Equ
     }
 }
 
+/// Computes the inverse of a value which `assert_nonzero` enforces to be nonzero.
+#[derive(Debug, Default)]
+pub struct NonzeroGenerator {
+    x: Target,
+    inv: Target,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for NonzeroGenerator {
+    fn id(&self) -> String {
+        "NonzeroGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        vec![self.x]
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        if let Err(err) = self.try_run_once(witness, out_buffer) {
+            panic!("{}", err);
+        }
+    }
+
+    fn try_run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
+        let x = witness.get_target(self.x);
+        if x == F::ZERO {
+            return Err(anyhow::Error::msg(GenerationError {
+                reason: "value asserted to be nonzero is zero".to_string(),
+                observed: vec![(self.x, x)],
+            }));
+        }
+        out_buffer.set_quotients(&[self.inv], &[F::ONE], x);
+        Ok(())
+    }
+
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        Some(Self {
+            x: map(self.x),
+            inv: map(self.inv),
+        })
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target(self.x)?;
+        dst.write_target(self.inv)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let x = src.read_target()?;
+        let inv = src.read_target()?;
+        Ok(Self { x, inv })
+    }
+}
+
 /// Represents a base arithmetic operation in the circuit. Used to memoize results.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub(crate) struct BaseArithmeticOperation<F: Field64> {
@@ -432,3 +536,14 @@ pub(crate) struct BaseArithmeticOperation<F: Field64> {
     multiplicand_1: Target,
     addend: Target,
 }
+
+impl<F: Field64> BaseArithmeticOperation<F> {
+    pub(crate) fn remap(&self, map: &dyn Fn(Target) -> Target) -> Self {
+        Self {
+            multiplicand_0: map(self.multiplicand_0),
+            multiplicand_1: map(self.multiplicand_1),
+            addend: map(self.addend),
+            ..*self
+        }
+    }
+}
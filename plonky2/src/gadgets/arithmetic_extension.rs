@@ -6,15 +6,18 @@ use alloc::{
 };
 use core::borrow::Borrow;
 
-use crate::field::extension::{Extendable, FieldExtension, OEF};
+use anyhow::Result;
+
+use crate::field::extension::{Extendable, FieldExtension, Frobenius, OEF};
 use crate::field::types::{Field, Field64};
 use crate::gates::arithmetic_extension::ArithmeticExtensionGate;
 use crate::gates::multiplication_extension::MulExtensionGate;
+use crate::gates::scalar_arithmetic_extension::ScalarArithmeticExtensionGate;
 use crate::hash::hash_types::RichField;
 use crate::iop::ext_target::{ExtensionAlgebraTarget, ExtensionTarget};
-use crate::iop::generator::{GeneratedValues, SimpleGenerator};
+use crate::iop::generator::{GeneratedValues, GenerationError, SimpleGenerator};
 use crate::iop::target::Target;
-use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
+use crate::iop::witness::{PartitionWitness, Witness};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
 use crate::util::bits_u64;
@@ -64,6 +67,66 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         result
     }
 
+    /// Computes `const_0 * scalar * multiplicand + const_1 * addend`, where `scalar` is in the
+    /// base field. This takes a slot of a `ScalarArithmeticExtensionGate`, which fits more
+    /// operations in a row than an `ArithmeticExtensionGate`.
+    pub fn scalar_arithmetic_extension(
+        &mut self,
+        const_0: F,
+        const_1: F,
+        scalar: Target,
+        multiplicand: ExtensionTarget<D>,
+        addend: ExtensionTarget<D>,
+    ) -> ExtensionTarget<D> {
+        // Operations with a constant operand or without an addend are cheaper with
+        // `arithmetic_extension`, which folds constants and uses `MulExtensionGate`s.
+        if const_0 == F::ZERO
+            || const_1 == F::ZERO
+            || self.target_as_constant(scalar).is_some()
+            || self.target_as_constant_ext(multiplicand).is_some()
+            || self.target_as_constant_ext(addend) == Some(F::Extension::ZERO)
+        {
+            let scalar_ext = self.convert_to_ext(scalar);
+            return self.arithmetic_extension(const_0, const_1, scalar_ext, multiplicand, addend);
+        }
+
+        let operation = ScalarArithmeticOperation {
+            const_0,
+            const_1,
+            scalar,
+            multiplicand,
+            addend,
+        };
+        if let Some(&result) = self.scalar_arithmetic_results.get(&operation) {
+            return result;
+        }
+
+        let gate = ScalarArithmeticExtensionGate::new_from_config(&self.config);
+        let constants = vec![const_0, const_1];
+        let (gate, i) = self.find_slot(gate, &constants, &constants);
+        self.connect(
+            scalar,
+            Target::wire(gate, ScalarArithmeticExtensionGate::<D>::wire_ith_scalar(i)),
+        );
+        let wires_multiplicand = ExtensionTarget::from_range(
+            gate,
+            ScalarArithmeticExtensionGate::<D>::wires_ith_multiplicand(i),
+        );
+        let wires_addend = ExtensionTarget::from_range(
+            gate,
+            ScalarArithmeticExtensionGate::<D>::wires_ith_addend(i),
+        );
+        self.connect_extension(multiplicand, wires_multiplicand);
+        self.connect_extension(addend, wires_addend);
+
+        let result = ExtensionTarget::from_range(
+            gate,
+            ScalarArithmeticExtensionGate::<D>::wires_ith_output(i),
+        );
+        self.scalar_arithmetic_results.insert(operation, result);
+        result
+    }
+
     fn compute_arithmetic_extension_operation(
         &mut self,
         operation: ExtensionArithmeticOperation<F, D>,
@@ -95,16 +158,16 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     ) -> ExtensionTarget<D> {
         let gate = MulExtensionGate::new_from_config(&self.config);
         let constants = vec![operation.const_0];
-        let (gate, i) = self.find_slot(gate, &constants, &constants);
+        let (row, i) = self.find_slot(gate.clone(), &constants, &constants);
         let wires_multiplicand_0 =
-            ExtensionTarget::from_range(gate, MulExtensionGate::<D>::wires_ith_multiplicand_0(i));
+            ExtensionTarget::from_range(row, gate.wires_ith_multiplicand_0(i));
         let wires_multiplicand_1 =
-            ExtensionTarget::from_range(gate, MulExtensionGate::<D>::wires_ith_multiplicand_1(i));
+            ExtensionTarget::from_range(row, gate.wires_ith_multiplicand_1(i));
 
         self.connect_extension(operation.multiplicand_0, wires_multiplicand_0);
         self.connect_extension(operation.multiplicand_1, wires_multiplicand_1);
 
-        ExtensionTarget::from_range(gate, MulExtensionGate::<D>::wires_ith_output(i))
+        ExtensionTarget::from_range(row, gate.wires_ith_output(i))
     }
 
     /// Checks for special cases where the value of
@@ -341,10 +404,42 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.add_extension(x, c)
     }
 
-    /// Like `mul_const`, but for `ExtensionTarget`s.
-    pub fn mul_const_extension(&mut self, c: F, x: ExtensionTarget<D>) -> ExtensionTarget<D> {
-        let c = self.constant_extension(c.into());
-        self.mul_extension(c, x)
+    /// Like `mul_const`, but for `ExtensionTarget`s. If the config has room for `D` gate
+    /// constants, `c` is held in the constants of a `MulExtensionGate` rather than routed to its
+    /// wires, so that a row fits more such multiplications by the same constant.
+    pub fn mul_const_extension(
+        &mut self,
+        c: F::Extension,
+        x: ExtensionTarget<D>,
+    ) -> ExtensionTarget<D> {
+        if let Some(x) = self.target_as_constant_ext(x) {
+            return self.constant_extension(c * x);
+        }
+        if c.is_zero() {
+            return self.zero_extension();
+        }
+        if c.is_one() {
+            return x;
+        }
+        if D > self.config.num_constants {
+            let c = self.constant_extension(c);
+            return self.mul_extension(c, x);
+        }
+
+        if let Some(&result) = self.mul_const_results.get(&(c, x)) {
+            return result;
+        }
+
+        let gate = MulExtensionGate::new_constant_multiplicand_from_config(&self.config);
+        let constants = c.to_basefield_array().to_vec();
+        let (row, i) = self.find_slot(gate.clone(), &constants, &constants);
+        let wires_multiplicand_1 =
+            ExtensionTarget::from_range(row, gate.wires_ith_multiplicand_1(i));
+        self.connect_extension(x, wires_multiplicand_1);
+
+        let result = ExtensionTarget::from_range(row, gate.wires_ith_output(i));
+        self.mul_const_results.insert((c, x), result);
+        result
     }
 
     /// Like `mul_const_add`, but for `ExtensionTarget`s.
@@ -358,15 +453,14 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.mul_add_extension(c, x, y)
     }
 
-    /// Like `mul_add`, but for `ExtensionTarget`s.
+    /// Returns `a * b + c`, where `b, c` are in the extension field and `a` is in the base field.
     pub fn scalar_mul_add_extension(
         &mut self,
         a: Target,
         b: ExtensionTarget<D>,
         c: ExtensionTarget<D>,
     ) -> ExtensionTarget<D> {
-        let a_ext = self.convert_to_ext(a);
-        self.arithmetic_extension(F::ONE, F::ONE, a_ext, b, c)
+        self.scalar_arithmetic_extension(F::ONE, F::ONE, a, b, c)
     }
 
     /// Like `mul_sub`, but for `ExtensionTarget`s.
@@ -379,15 +473,14 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.arithmetic_extension(F::ONE, F::NEG_ONE, a, b, c)
     }
 
-    /// Like `mul_sub`, but for `ExtensionTarget`s.
+    /// Returns `a * b - c`, where `b, c` are in the extension field and `a` is in the base field.
     pub fn scalar_mul_sub_extension(
         &mut self,
         a: Target,
         b: ExtensionTarget<D>,
         c: ExtensionTarget<D>,
     ) -> ExtensionTarget<D> {
-        let a_ext = self.convert_to_ext(a);
-        self.arithmetic_extension(F::ONE, F::NEG_ONE, a_ext, b, c)
+        self.scalar_arithmetic_extension(F::ONE, F::NEG_ONE, a, b, c)
     }
 
     /// Returns `a * b`, where `b` is in the extension field and `a` is in the base field.
@@ -460,7 +553,8 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         product
     }
 
-    /// Computes `x / y`. Results in an unsatisfiable instance if `y = 0`.
+    /// Computes `x / y`. Results in an unsatisfiable instance if `y = 0`, which witness generation
+    /// reports as an error.
     pub fn div_extension(
         &mut self,
         x: ExtensionTarget<D>,
@@ -470,13 +564,27 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.div_add_extension(x, y, zero)
     }
 
-    /// Computes ` x / y + z`.
+    /// Computes ` x / y + z`. If `x` and `y` are constants, the quotient is computed when building
+    /// the circuit, which panics if `y = 0`.
     pub fn div_add_extension(
         &mut self,
         x: ExtensionTarget<D>,
         y: ExtensionTarget<D>,
         z: ExtensionTarget<D>,
     ) -> ExtensionTarget<D> {
+        if let (Some(x_const), Some(y_const)) = (
+            self.target_as_constant_ext(x),
+            self.target_as_constant_ext(y),
+        ) {
+            assert!(
+                y_const != F::Extension::ZERO,
+                "Division by the constant zero{}",
+                self.context_suffix()
+            );
+            let quotient = self.constant_extension(x_const / y_const);
+            return self.add_extension(quotient, z);
+        }
+
         let inv = self.add_virtual_extension_target();
         let one = self.one_extension();
         self.add_simple_generator(QuotientGeneratorExtension {
@@ -484,6 +592,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             denominator: y,
             quotient: inv,
         });
+        self.declare_checked_hints(&inv.0);
 
         // Enforce that y times its purported inverse equals 1.
         let y_inv = self.mul_extension(y, inv);
@@ -492,11 +601,24 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.mul_add_extension(x, inv, z)
     }
 
-    /// Computes `1 / x`. Results in an unsatisfiable instance if `x = 0`.
+    /// Computes `1 / x`. Results in an unsatisfiable instance if `x = 0`, which witness generation
+    /// reports as an error.
     pub fn inverse_extension(&mut self, x: ExtensionTarget<D>) -> ExtensionTarget<D> {
         let one = self.one_extension();
         self.div_extension(one, x)
     }
+
+    /// Enforces that `x` is nonzero, by constraining `x` times a witness inverse to be one. Witness
+    /// generation fails if `x = 0`.
+    pub fn assert_nonzero_extension(&mut self, x: ExtensionTarget<D>) {
+        self.inverse_extension(x);
+    }
+
+    /// Enforces that `x` and `y` are different.
+    pub fn assert_ne_extension(&mut self, x: ExtensionTarget<D>, y: ExtensionTarget<D>) {
+        let diff = self.sub_extension(x, y);
+        self.assert_nonzero_extension(diff);
+    }
 }
 
 #[derive(Debug, Default)]
@@ -520,10 +642,54 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
     }
 
     fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        if let Err(err) = self.try_run_once(witness, out_buffer) {
+            panic!("{}", err);
+        }
+    }
+
+    fn try_run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
         let num = witness.get_extension_target(self.numerator);
         let dem = witness.get_extension_target(self.denominator);
-        let quotient = num / dem;
-        out_buffer.set_extension_target(self.quotient, quotient)
+        if dem == F::Extension::ZERO {
+            let observed = self
+                .numerator
+                .to_target_array()
+                .into_iter()
+                .chain(self.denominator.to_target_array())
+                .map(|t| (t, witness.get_target(t)))
+                .collect();
+            return Err(anyhow::Error::msg(GenerationError {
+                reason: "division by zero".to_string(),
+                observed,
+            }));
+        }
+        // Write `num / dem` as `(num * conj) / norm`, where `conj` is the product of the other
+        // conjugates of `dem`, so that the norm `dem * conj` is in the base field. Then only the
+        // norm needs to be inverted, which the buffer may do in a batch with other inversions.
+        let (conj, norm) = if dem.is_in_basefield() {
+            (F::Extension::ONE, dem.to_basefield_array()[0])
+        } else {
+            let conj = (1..D).fold(F::Extension::ONE, |acc, i| acc * dem.repeated_frobenius(i));
+            (conj, (dem * conj).to_basefield_array()[0])
+        };
+        out_buffer.set_quotients(
+            &self.quotient.to_target_array(),
+            &(num * conj).to_basefield_array(),
+            norm,
+        );
+        Ok(())
+    }
+
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        Some(Self {
+            numerator: self.numerator.remap(map),
+            denominator: self.denominator.remap(map),
+            quotient: self.quotient.remap(map),
+        })
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
@@ -593,18 +759,57 @@ pub(crate) struct ExtensionArithmeticOperation<F: Field64 + Extendable<D>, const
     addend: ExtensionTarget<D>,
 }
 
+/// Represents a scalar extension arithmetic operation in the circuit. Used to memoize results.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub(crate) struct ScalarArithmeticOperation<F: Field64 + Extendable<D>, const D: usize> {
+    const_0: F,
+    const_1: F,
+    scalar: Target,
+    multiplicand: ExtensionTarget<D>,
+    addend: ExtensionTarget<D>,
+}
+
+impl<F: Field64 + Extendable<D>, const D: usize> ExtensionArithmeticOperation<F, D> {
+    pub(crate) fn remap(&self, map: &dyn Fn(Target) -> Target) -> Self {
+        Self {
+            multiplicand_0: self.multiplicand_0.remap(map),
+            multiplicand_1: self.multiplicand_1.remap(map),
+            addend: self.addend.remap(map),
+            ..*self
+        }
+    }
+}
+
+impl<F: Field64 + Extendable<D>, const D: usize> ScalarArithmeticOperation<F, D> {
+    pub(crate) fn remap(&self, map: &dyn Fn(Target) -> Target) -> Self {
+        Self {
+            scalar: map(self.scalar),
+            multiplicand: self.multiplicand.remap(map),
+            addend: self.addend.remap(map),
+            ..*self
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, vec};
+
     use anyhow::Result;
+    use log::Level;
 
     use crate::field::extension::algebra::ExtensionAlgebra;
-    use crate::field::types::Sample;
-    use crate::iop::ext_target::ExtensionAlgebraTarget;
+    use crate::field::extension::FieldExtension;
+    use crate::field::types::{Field, Sample};
+    use crate::iop::ext_target::{ExtensionAlgebraTarget, ExtensionTarget};
+    use crate::iop::generator::GenerationError;
     use crate::iop::witness::{PartialWitness, WitnessWrite};
     use crate::plonk::circuit_builder::CircuitBuilder;
     use crate::plonk::circuit_data::CircuitConfig;
     use crate::plonk::config::{GenericConfig, KeccakGoldilocksConfig, PoseidonGoldilocksConfig};
     use crate::plonk::verifier::verify;
+    use crate::util::testing::assert_differential;
 
     #[test]
     fn test_mul_many() -> Result<()> {
@@ -669,6 +874,280 @@ mod tests {
         verify(proof, &data.verifier_only, &data.common)
     }
 
+    #[test]
+    fn test_div_by_zero_reports_context() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.push_context(Level::Debug, "normalize balance");
+        let z = builder.div(x, y);
+        builder.pop_context();
+        builder.register_public_input(z);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::ONE);
+        pw.set_target(y, F::ZERO);
+        let err = data.prove(pw).unwrap_err();
+
+        let message = format!("{:#}", err);
+        assert!(message.contains("QuotientGeneratorExtension"));
+        assert!(message.contains("normalize balance"));
+        assert!(message.contains("division by zero"));
+        let cause = err.downcast_ref::<GenerationError<F>>().unwrap();
+        assert!(cause.observed.contains(&(y, F::ZERO)));
+    }
+
+    #[test]
+    fn test_div_add_extension_differential() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+
+        let ext = |values: &[F]| -> FF {
+            FieldExtension::<D>::from_basefield_array(values.try_into().unwrap())
+        };
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            32,
+            || F::rand_vec(3 * D),
+            |builder, inputs| {
+                let x = ExtensionTarget(inputs[..D].try_into().unwrap());
+                let y = ExtensionTarget(inputs[D..2 * D].try_into().unwrap());
+                let z = ExtensionTarget(inputs[2 * D..].try_into().unwrap());
+                let quotient = builder.div_extension(x, y);
+                let sum = builder.div_add_extension(x, y, z);
+                [quotient.to_target_array(), sum.to_target_array()].concat()
+            },
+            |inputs| {
+                let (x, y, z) = (
+                    ext(&inputs[..D]),
+                    ext(&inputs[D..2 * D]),
+                    ext(&inputs[2 * D..]),
+                );
+                let (quotient, sum): ([F; D], [F; D]) = (
+                    (x / y).to_basefield_array(),
+                    (x / y + z).to_basefield_array(),
+                );
+                [quotient, sum].concat()
+            },
+        );
+    }
+
+    #[test]
+    fn test_div_extension_constants_are_folded() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let (x, y) = (FF::rand(), FF::rand());
+        let xt = builder.constant_extension(x);
+        let yt = builder.constant_extension(y);
+        let operation_id = builder.next_operation_id();
+        let quotient = builder.div_extension(xt, yt);
+        let one = builder.one_extension();
+        let sum = builder.div_add_extension(xt, yt, one);
+        assert_eq!(builder.next_operation_id(), operation_id);
+        assert_eq!(builder.num_gates(), 0);
+        assert_eq!(builder.target_as_constant_ext(quotient), Some(x / y));
+        assert_eq!(builder.target_as_constant_ext(sum), Some(x / y + FF::ONE));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Division by the constant zero (added in context \"root > barycentric weights\")"
+    )]
+    fn test_div_extension_by_constant_zero() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let one = builder.one_extension();
+        let zero = builder.zero_extension();
+        builder.push_context(Level::Debug, "barycentric weights");
+        builder.div_extension(one, zero);
+    }
+
+    #[test]
+    fn test_div_extension_by_zero_reports_context() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_extension_target();
+        let y = builder.add_virtual_extension_target();
+        builder.push_context(Level::Debug, "barycentric weights");
+        let z = builder.div_extension(x, y);
+        builder.pop_context();
+        builder.register_public_inputs(&z.to_target_array());
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_extension_target(x, <C as GenericConfig<D>>::FE::ONE);
+        pw.set_extension_target(y, <C as GenericConfig<D>>::FE::ZERO);
+        let err = data.prove(pw).unwrap_err();
+
+        let message = format!("{:#}", err);
+        assert!(message.contains("barycentric weights"));
+        assert!(message.contains("division by zero"));
+    }
+
+    #[test]
+    fn test_assert_ne() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.assert_nonzero(x);
+        builder.assert_ne(x, y);
+        let xt = builder.add_virtual_extension_target();
+        let yt = builder.add_virtual_extension_target();
+        builder.assert_nonzero_extension(xt);
+        builder.assert_ne_extension(xt, yt);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        pw.set_target(y, F::rand());
+        let x_ext = FF::rand();
+        pw.set_extension_target(xt, x_ext);
+        // Differs from `x_ext` in a single coordinate, so that the difference isn't in the base
+        // field.
+        pw.set_extension_target(yt, x_ext + FF::from_basefield_array([F::ZERO, F::ONE]));
+        let proof = data.prove(pw)?;
+
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    fn test_assert_ne_reports_context() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.push_context(Level::Debug, "check fresh nullifier");
+        builder.assert_ne(x, y);
+        builder.pop_context();
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        let value = F::rand();
+        pw.set_target(x, value);
+        pw.set_target(y, value);
+        let err = data.prove(pw).unwrap_err();
+
+        let message = format!("{:#}", err);
+        assert!(message.contains("NonzeroGenerator"));
+        assert!(message.contains("check fresh nullifier"));
+        assert!(message.contains("value asserted to be nonzero is zero"));
+        assert!(err.downcast_ref::<GenerationError<F>>().is_some());
+    }
+
+    #[test]
+    fn test_is_equal_differential() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        // Half of the pairs are equal.
+        let mut equal = false;
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            32,
+            || {
+                equal = !equal;
+                let x = F::rand();
+                vec![x, if equal { x } else { F::rand() }]
+            },
+            |builder, inputs| vec![builder.is_equal(inputs[0], inputs[1]).target],
+            |inputs| vec![F::from_bool(inputs[0] == inputs[1])],
+        );
+    }
+
+    #[test]
+    fn test_scalar_mul_add_extension_differential() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+
+        let ext = |values: &[F]| -> FF {
+            FieldExtension::<D>::from_basefield_array(values.try_into().unwrap())
+        };
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            32,
+            || F::rand_vec(1 + 2 * D),
+            |builder, inputs| {
+                let x = ExtensionTarget(inputs[1..1 + D].try_into().unwrap());
+                let y = ExtensionTarget(inputs[1 + D..].try_into().unwrap());
+                let sum = builder.scalar_mul_add_extension(inputs[0], x, y);
+                let difference = builder.scalar_mul_sub_extension(inputs[0], x, y);
+                [sum.to_target_array(), difference.to_target_array()].concat()
+            },
+            |inputs| {
+                let (x, y) = (ext(&inputs[1..1 + D]), ext(&inputs[1 + D..]));
+                let sum = FF::from(inputs[0]) * x + y;
+                let difference = FF::from(inputs[0]) * x - y;
+                let (sum, difference): ([F; D], [F; D]) =
+                    (sum.to_basefield_array(), difference.to_basefield_array());
+                [sum, difference].concat()
+            },
+        );
+    }
+
+    /// Compares the rows taken by multiply-adds with a base field scalar, to those taken by the
+    /// same operations on `ArithmeticExtensionGate`s.
+    #[test]
+    fn test_scalar_mul_add_extension_rows() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+        let num_ops = 110;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        for _ in 0..num_ops {
+            let a = builder.add_virtual_target();
+            let b = builder.add_virtual_extension_target();
+            let c = builder.add_virtual_extension_target();
+            builder.scalar_mul_add_extension(a, b, c);
+        }
+        assert_eq!(builder.num_gates(), 10);
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        for _ in 0..num_ops {
+            let a = builder.add_virtual_target();
+            let b = builder.add_virtual_extension_target();
+            let c = builder.add_virtual_extension_target();
+            let a = builder.convert_to_ext(a);
+            builder.mul_add_extension(a, b, c);
+        }
+        assert_eq!(builder.num_gates(), 11);
+    }
+
     #[test]
     fn test_mul_algebra() -> Result<()> {
         const D: usize = 2;
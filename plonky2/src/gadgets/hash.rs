@@ -1,8 +1,12 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
 use crate::field::extension::Extendable;
-use crate::hash::hash_types::RichField;
+use crate::hash::hash_types::{RichField, NUM_HASH_OUT_ELTS};
+use crate::hash::hashing::PlonkyPermutation;
 use crate::iop::target::BoolTarget;
 use crate::plonk::circuit_builder::CircuitBuilder;
-use crate::plonk::config::AlgebraicHasher;
+use crate::plonk::config::{AlgebraicHasher, Hasher};
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     pub fn permute<H: AlgebraicHasher<F>>(
@@ -16,11 +20,30 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
     /// Conditionally swap two chunks of the inputs (useful in verifying Merkle proofs), then apply
     /// a cryptographic permutation.
+    ///
+    /// If the inputs and `swap` are all constants, the permutation is computed natively and its
+    /// outputs are returned as constants, so that no gate is needed.
     pub(crate) fn permute_swapped<H: AlgebraicHasher<F>>(
         &mut self,
         inputs: H::AlgebraicPermutation,
         swap: BoolTarget,
     ) -> H::AlgebraicPermutation {
-        H::permute_swapped(inputs, swap, self)
+        let constant_inputs = inputs
+            .as_ref()
+            .iter()
+            .map(|&t| self.target_as_constant(t))
+            .collect::<Option<Vec<_>>>();
+        match (constant_inputs, self.target_as_constant(swap.target)) {
+            (Some(mut values), Some(swap)) => {
+                if swap.is_one() {
+                    let (lhs, rhs) = values.split_at_mut(NUM_HASH_OUT_ELTS);
+                    lhs.swap_with_slice(&mut rhs[..NUM_HASH_OUT_ELTS]);
+                }
+                let mut state = <H as Hasher<F>>::Permutation::new(values);
+                state.permute();
+                H::AlgebraicPermutation::new(state.as_ref().iter().map(|&x| self.constant(x)))
+            }
+            _ => H::permute_swapped(inputs, swap, self),
+        }
     }
 }
@@ -2,6 +2,7 @@
 use alloc::vec;
 
 use plonky2_field::extension::Extendable;
+use plonky2_field::types::Field;
 
 use crate::gates::coset_interpolation::CosetInterpolationGate;
 use crate::hash::hash_types::RichField;
@@ -35,6 +36,36 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         eval
     }
+
+    /// Evaluates at `x` the line through the two `points`, like the native `interpolate2`. Results
+    /// in an unsatisfiable instance if the points have the same abscissa. If both abscissas are
+    /// constants, the inverse of their difference is computed when building the circuit.
+    pub fn interpolate2_ext(
+        &mut self,
+        points: [(ExtensionTarget<D>, ExtensionTarget<D>); 2],
+        x: ExtensionTarget<D>,
+    ) -> ExtensionTarget<D> {
+        let [(x0, y0), (x1, y1)] = points;
+        let dx = self.sub_extension(x, x0);
+        let dy = self.sub_extension(y1, y0);
+        if let (Some(c0), Some(c1)) = (
+            self.target_as_constant_ext(x0),
+            self.target_as_constant_ext(x1),
+        ) {
+            assert_ne!(
+                c0,
+                c1,
+                "Interpolating through two points with the same abscissa{}",
+                self.context_suffix()
+            );
+            let slope = self.mul_const_extension((c1 - c0).inverse(), dy);
+            return self.mul_add_extension(dx, slope, y0);
+        }
+
+        let run = self.sub_extension(x1, x0);
+        let rise = self.mul_extension(dx, dy);
+        self.div_add_extension(rise, run, y0)
+    }
 }
 
 #[cfg(test)]
@@ -45,14 +76,16 @@ mod tests {
     use anyhow::Result;
 
     use crate::field::extension::FieldExtension;
-    use crate::field::interpolation::interpolant;
+    use crate::field::interpolation::{interpolant, interpolate2};
     use crate::field::types::{Field, Sample};
     use crate::gates::coset_interpolation::CosetInterpolationGate;
-    use crate::iop::witness::PartialWitness;
+    use crate::iop::ext_target::ExtensionTarget;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
     use crate::plonk::circuit_builder::CircuitBuilder;
     use crate::plonk::circuit_data::CircuitConfig;
     use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
     use crate::plonk::verifier::verify;
+    use crate::util::testing::assert_differential;
 
     #[test]
     fn test_interpolate() -> Result<()> {
@@ -111,4 +144,88 @@ mod tests {
 
         verify(proof, &data.verifier_only, &data.common)
     }
+
+    #[test]
+    fn test_interpolate2_ext_differential() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+
+        let ext = |values: &[F]| -> FF {
+            FieldExtension::<D>::from_basefield_array(values.try_into().unwrap())
+        };
+        let target = |targets: &[_]| ExtensionTarget::<D>(targets.try_into().unwrap());
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            16,
+            || F::rand_vec(5 * D),
+            |builder, inputs| {
+                let points = [
+                    (target(&inputs[..D]), target(&inputs[D..2 * D])),
+                    (target(&inputs[2 * D..3 * D]), target(&inputs[3 * D..4 * D])),
+                ];
+                let x = target(&inputs[4 * D..]);
+                builder
+                    .interpolate2_ext(points, x)
+                    .to_target_array()
+                    .to_vec()
+            },
+            |inputs| {
+                let points = [
+                    (ext(&inputs[..D]), ext(&inputs[D..2 * D])),
+                    (ext(&inputs[2 * D..3 * D]), ext(&inputs[3 * D..4 * D])),
+                ];
+                let x = ext(&inputs[4 * D..]);
+                let eval: [F; D] = interpolate2(points, x).to_basefield_array();
+                eval.to_vec()
+            },
+        );
+    }
+
+    #[test]
+    fn test_interpolate2_ext_constant_abscissas() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+
+        let (x0, x1) = (FF::rand(), FF::rand());
+        let (y0, y1, x) = (FF::rand(), FF::rand(), FF::rand());
+        let x0t = builder.constant_extension(x0);
+        let x1t = builder.constant_extension(x1);
+        let y0t = builder.add_virtual_extension_target();
+        let y1t = builder.add_virtual_extension_target();
+        let xt = builder.add_virtual_extension_target();
+
+        let start = builder.next_operation_id();
+        let eval = builder.interpolate2_ext([(x0t, y0t), (x1t, y1t)], xt);
+        let constant_ops = builder.next_operation_id().0 - start.0;
+
+        // The same interpolation with abscissas which aren't known when building the circuit.
+        let x0v = builder.add_virtual_extension_target();
+        let x1v = builder.add_virtual_extension_target();
+        let start = builder.next_operation_id();
+        let eval_virtual = builder.interpolate2_ext([(x0v, y0t), (x1v, y1t)], xt);
+        let virtual_ops = builder.next_operation_id().0 - start.0;
+        assert!(
+            constant_ops < virtual_ops,
+            "{constant_ops} operations with constant abscissas, {virtual_ops} without"
+        );
+
+        let expected = builder.constant_extension(interpolate2([(x0, y0), (x1, y1)], x));
+        builder.connect_extension(eval, expected);
+        builder.connect_extension(eval_virtual, expected);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_extension_target(y0t, y0);
+        pw.set_extension_target(y1t, y1);
+        pw.set_extension_target(xt, x);
+        pw.set_extension_target(x0v, x0);
+        pw.set_extension_target(x1v, x1);
+        let proof = data.prove(pw)?;
+        verify(proof, &data.verifier_only, &data.common)
+    }
 }
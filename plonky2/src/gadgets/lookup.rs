@@ -8,6 +8,7 @@ use crate::gates::noop::NoopGate;
 use crate::hash::hash_types::RichField;
 use crate::iop::target::Target;
 use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::ceil_div_usize;
 
 /// Lookup tables used in the tests and benchmarks.
 ///
@@ -75,6 +76,18 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         looking_out
     }
 
+    /// The rows of `LookupGate` and of `LookupTableGate` which `add_all_lookups` adds for the
+    /// `lut_index`th table, not counting the `NoopGate` which follows them.
+    pub(crate) fn lookup_rows(&self, lut_index: usize) -> (usize, usize) {
+        let num_lookups = self.get_lut_lookups(lut_index).len();
+        let lookup_rows = ceil_div_usize(num_lookups, LookupGate::num_slots(&self.config));
+        let table_rows = ceil_div_usize(
+            self.get_luts_idx_length(lut_index),
+            LookupTableGate::num_slots(&self.config),
+        );
+        (lookup_rows, table_rows)
+    }
+
     /// We call this function at the end of circuit building right before the PI gate to add all `LookupTableGate` and `LookupGate`.
     /// It also updates `self.lookup_rows` accordingly.
     pub fn add_all_lookups(&mut self) {
@@ -128,8 +141,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
                 // Create LUT gates. Nothing is connected to them.
                 let last_lut_gate = self.num_gates();
-                let num_lut_entries = LookupTableGate::num_slots(&self.config);
-                let num_lut_rows = (self.get_luts_idx_length(lut_index) - 1) / num_lut_entries + 1;
+                let (_, num_lut_rows) = self.lookup_rows(lut_index);
                 let gate =
                     LookupTableGate::new_from_table(&self.config, lut.clone(), last_lut_gate);
                 // Also instances of `LookupTableGate` can be placed with the `add_gate` function
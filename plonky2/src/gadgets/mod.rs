@@ -13,3 +13,5 @@ pub mod range_check;
 pub mod select;
 pub mod split_base;
 pub mod split_join;
+pub mod stack;
+pub mod switch;
@@ -1,5 +1,5 @@
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 
 use crate::field::extension::Extendable;
 use crate::gates::random_access::RandomAccessGate;
@@ -19,10 +19,10 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         if vec_size == 1 {
             return v[0];
         }
-        let claimed_element = self.add_virtual_target();
-
         let dummy_gate = RandomAccessGate::<F, D>::new_from_config(&self.config, bits);
-        let (row, copy) = self.find_slot(dummy_gate, &[], &[]);
+        // The extra constants are placeholders, set when building the circuit.
+        let extra_constants = vec![F::ZERO; dummy_gate.num_extra_constants];
+        let (row, copy) = self.find_slot(dummy_gate, &[], &extra_constants);
 
         v.iter().enumerate().for_each(|(i, &val)| {
             self.connect(val, Target::wire(row, dummy_gate.wire_list_item(i, copy)));
@@ -31,12 +31,9 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             access_index,
             Target::wire(row, dummy_gate.wire_access_index(copy)),
         );
-        self.connect(
-            claimed_element,
-            Target::wire(row, dummy_gate.wire_claimed_element(copy)),
-        );
 
-        claimed_element
+        // The gate's own wire is returned, rather than a virtual target copied to it.
+        Target::wire(row, dummy_gate.wire_claimed_element(copy))
     }
 
     /// Like `random_access`, but with `ExtensionTarget`s rather than simple `Target`s.
@@ -105,6 +102,7 @@ mod tests {
     use anyhow::Result;
 
     use super::*;
+    use crate::circuit_size_test;
     use crate::field::types::{Field, Sample};
     use crate::iop::witness::PartialWitness;
     use crate::plonk::circuit_data::CircuitConfig;
@@ -143,4 +141,27 @@ mod tests {
         }
         Ok(())
     }
+
+    circuit_size_test!(
+        random_access_size,
+        CircuitConfig::standard_recursion_config(),
+        |builder| {
+            let list = builder.add_virtual_targets(16);
+            for _ in 0..8 {
+                let index = builder.add_virtual_target();
+                builder.random_access(index, list.clone());
+            }
+            let extension_list = builder.add_virtual_extension_targets(4);
+            for _ in 0..8 {
+                let index = builder.add_virtual_target();
+                builder.random_access_extension(index, extension_list.clone());
+            }
+        },
+        degree_bits: 3,
+        num_rows: 5,
+        gates: {
+            "PublicInputGate" => 1,
+            "RandomAccessGate" => 4,
+        },
+    );
 }
@@ -1,25 +1,134 @@
 #[cfg(not(feature = "std"))]
 use alloc::{
+    collections::BTreeMap,
     string::{String, ToString},
     vec,
     vec::Vec,
 };
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use hashbrown::HashMap;
 
 use crate::field::extension::Extendable;
+use crate::gates::gate::GateRef;
+use crate::gates::range_check::RangeCheckGate;
 use crate::hash::hash_types::RichField;
 use crate::iop::generator::{GeneratedValues, SimpleGenerator};
 use crate::iop::target::{BoolTarget, Target};
 use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
+use crate::util::ceil_div_usize;
 use crate::util::serialization::{Buffer, IoResult, Read, Write};
 
+/// The range checks enqueued with `CircuitBuilder::enqueue_range_check`, which `build` packs into
+/// `RangeCheckGate`s.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RangeCheckService {
+    pending: Vec<(Target, usize)>,
+}
+
+impl RangeCheckService {
+    fn enqueue(&mut self, x: Target, num_bits: usize) {
+        self.pending.push((x, num_bits));
+    }
+
+    /// The pending checks, in the order they were enqueued.
+    pub(crate) fn into_pending(self) -> Vec<(Target, usize)> {
+        self.pending
+    }
+
+    /// The pending checks, grouped by width in increasing order. A target checked several times
+    /// is only checked once, against the smallest width.
+    fn grouped(&self) -> Vec<(usize, Vec<Target>)> {
+        let mut widths = HashMap::<Target, usize>::new();
+        for &(x, num_bits) in &self.pending {
+            widths
+                .entry(x)
+                .and_modify(|w| *w = (*w).min(num_bits))
+                .or_insert(num_bits);
+        }
+
+        let mut groups = BTreeMap::<usize, Vec<Target>>::new();
+        for &(x, _) in &self.pending {
+            if let Some(num_bits) = widths.remove(&x) {
+                groups.entry(num_bits).or_default().push(x);
+            }
+        }
+        groups.into_iter().collect()
+    }
+
+    /// Takes the pending checks, grouped as by `grouped`.
+    fn take_grouped(&mut self) -> Vec<(usize, Vec<Target>)> {
+        let groups = self.grouped();
+        self.pending.clear();
+        groups
+    }
+}
+
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// Checks that `x < 2^n_log` using a `BaseSumGate`.
     pub fn range_check(&mut self, x: Target, n_log: usize) {
         self.split_le(x, n_log);
     }
 
+    /// Checks that `x < 2^num_bits`, like `range_check`, but defers the check to `build`, which
+    /// packs all enqueued checks of the same width into shared `RangeCheckGate` rows. This is much
+    /// cheaper than `range_check` for many narrow checks. A constant `x` is checked right away,
+    /// and checks of 64 bits or more, which any field element passes, are dropped.
+    pub fn enqueue_range_check(&mut self, x: Target, num_bits: usize) {
+        if num_bits > RangeCheckGate::MAX_BITS {
+            return;
+        }
+        if let Some(c) = self.target_as_constant(x) {
+            assert!(
+                c.to_canonical_u64() >> num_bits == 0,
+                "Constant {} doesn't fit in {} bits",
+                c,
+                num_bits
+            );
+            return;
+        }
+        self.range_checks.enqueue(x, num_bits);
+    }
+
+    /// Places the range checks enqueued with `enqueue_range_check`. Called by `build`, so that
+    /// no circuit can be proven without them.
+    pub(crate) fn place_range_checks(&mut self) {
+        for (num_bits, targets) in self.range_checks.take_grouped() {
+            if num_bits == 0 {
+                targets.into_iter().for_each(|x| self.assert_zero(x));
+            } else {
+                let gate = RangeCheckGate::new_from_config(&self.config, num_bits);
+                for x in targets {
+                    let (row, i) = self.find_slot(gate, &[], &[]);
+                    self.connect(x, Target::wire(row, RangeCheckGate::wire_ith_input(i)));
+                }
+            }
+        }
+    }
+
+    /// The rows of `RangeCheckGate` which `place_range_checks` would add for the enqueued checks,
+    /// by gate. Slots left free in a row already placed are filled first.
+    pub(crate) fn pending_range_check_rows(&self) -> Vec<(RangeCheckGate, usize)> {
+        self.range_checks
+            .grouped()
+            .into_iter()
+            .filter(|&(num_bits, _)| num_bits > 0)
+            .map(|(num_bits, targets)| {
+                let gate = RangeCheckGate::new_from_config(&self.config, num_bits);
+                let free_slots = self
+                    .current_slots
+                    .get(&GateRef::new(gate))
+                    .and_then(|slot| slot.current_slot.get(&Vec::new()))
+                    .map_or(0, |&(_, i)| gate.num_ops - i);
+                let rows = ceil_div_usize(targets.len().saturating_sub(free_slots), gate.num_ops);
+                (gate, rows)
+            })
+            .collect()
+    }
+
     /// Returns the first `num_low_bits` little-endian bits of `x`.
     pub fn low_bits(&mut self, x: Target, num_low_bits: usize, num_bits: usize) -> Vec<BoolTarget> {
         let mut res = self.split_le(x, num_bits);
@@ -28,8 +137,14 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 
     /// Returns `(a,b)` such that `x = a + 2^n_log * b` with `a < 2^n_log`.
-    /// `x` is assumed to be range-checked for having `num_bits` bits.
+    /// `x` is assumed to be range-checked for having `num_bits` bits. With 64 bits or more, e.g.
+    /// to split a full field element into 32-bit limbs, `a + 2^n_log * b` is also checked to be
+    /// less than the field order, so that it is `x`'s canonical representation.
     pub fn split_low_high(&mut self, x: Target, n_log: usize, num_bits: usize) -> (Target, Target) {
+        assert!(
+            n_log <= num_bits && n_log < 64,
+            "Can't split {num_bits} bits after the first {n_log}"
+        );
         let low = self.add_virtual_target();
         let high = self.add_virtual_target();
 
@@ -39,9 +154,18 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             low,
             high,
         });
+        self.declare_checked_hints(&[low, high]);
 
-        self.range_check(low, n_log);
-        self.range_check(high, num_bits - n_log);
+        if num_bits < 64 {
+            // `high * 2^n_log + low` can't overflow, so the limbs only need range checks, which
+            // are packed with the circuit's other enqueued checks.
+            self.enqueue_range_check(low, n_log);
+            self.enqueue_range_check(high, num_bits - n_log);
+        } else {
+            let low_bits = self.split_le_noncanonical(low, n_log);
+            let high_bits = self.split_le_noncanonical(high, num_bits - n_log);
+            self.assert_canonical_le_bits(&[low_bits, high_bits].concat());
+        }
 
         let pow2 = self.constant(F::from_canonical_u64(1 << n_log));
         let comp_x = self.mul_add(high, pow2, low);
@@ -50,6 +174,17 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         (low, high)
     }
 
+    /// Returns the low 32 bits of a field element `x`, range-checked to fit in a `u32`.
+    pub fn low_u32(&mut self, x: Target) -> Target {
+        self.split_64_to_32(x).0
+    }
+
+    /// Splits a field element `x` into its canonical low and high 32-bit limbs, each
+    /// range-checked to fit in a `u32`.
+    pub fn split_64_to_32(&mut self, x: Target) -> (Target, Target) {
+        self.split_low_high(x, 32, 64)
+    }
+
     pub fn assert_bool(&mut self, b: BoolTarget) {
         let z = self.mul_sub(b.target, b.target, b.target);
         let zero = self.zero();
@@ -83,6 +218,15 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Low
         out_buffer.set_target(self.high, F::from_canonical_u64(high));
     }
 
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        Some(Self {
+            integer: map(self.integer),
+            n_log: self.n_log,
+            low: map(self.low),
+            high: map(self.high),
+        })
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
         dst.write_target(self.integer)?;
         dst.write_usize(self.n_log)?;
@@ -103,3 +247,182 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Low
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, vec};
+
+    use anyhow::Result;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+
+    use crate::field::types::{Field, Field64, PrimeField64, Sample};
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::util::testing::assert_differential;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_split_64_to_32() -> Result<()> {
+        let mut values = vec![0, u32::MAX as u64, 1 << 32, (1 << 32) + 1, F::ORDER - 1];
+        values.extend((0..8).map(|_| OsRng.gen_range(0..F::ORDER)));
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut pw = PartialWitness::new();
+        for value in values {
+            let x = builder.add_virtual_target();
+            pw.set_target(x, F::from_canonical_u64(value));
+            let (low, high) = builder.split_64_to_32(x);
+            let low_u32 = builder.low_u32(x);
+            let expected_low = builder.constant(F::from_canonical_u64(value as u32 as u64));
+            let expected_high = builder.constant(F::from_canonical_u64(value >> 32));
+            builder.connect(low, expected_low);
+            builder.connect(low_u32, expected_low);
+            builder.connect(high, expected_high);
+        }
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_split_low_high() -> Result<()> {
+        let (n_log, num_bits) = (10, 40);
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut pw = PartialWitness::new();
+        for _ in 0..8 {
+            let value = OsRng.gen_range(0..1u64 << num_bits);
+            let x = builder.add_virtual_target();
+            pw.set_target(x, F::from_canonical_u64(value));
+            let (low, high) = builder.split_low_high(x, n_log, num_bits);
+            let expected_low = builder.constant(F::from_canonical_u64(value & ((1 << n_log) - 1)));
+            let expected_high = builder.constant(F::from_canonical_u64(value >> n_log));
+            builder.connect(low, expected_low);
+            builder.connect(high, expected_high);
+        }
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    #[should_panic(expected = "value range-checked to 30 bits doesn't fit")]
+    fn test_split_low_high_too_many_bits() {
+        // `2^40` doesn't fit in the 30 high bits left after the low 10.
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        builder.split_low_high(x, 10, 40);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u64(1 << 40));
+        data.prove(pw).unwrap();
+    }
+
+    #[test]
+    fn test_split_64_to_32_differential() {
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            16,
+            || vec![F::rand()],
+            |builder, inputs| {
+                let (low, high) = builder.split_64_to_32(inputs[0]);
+                let low_u32 = builder.low_u32(inputs[0]);
+                vec![low, high, low_u32]
+            },
+            |inputs| {
+                let x = inputs[0].to_canonical_u64();
+                vec![
+                    F::from_canonical_u32(x as u32),
+                    F::from_canonical_u64(x >> 32),
+                    F::from_canonical_u32(x as u32),
+                ]
+            },
+        );
+    }
+
+    #[test]
+    fn test_enqueue_range_check() -> Result<()> {
+        let widths = [0, 1, 7, 7, 16, 63, 64];
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut pw = PartialWitness::new();
+        for num_bits in widths {
+            let max = if num_bits < 64 {
+                (1 << num_bits) - 1
+            } else {
+                F::ORDER - 1
+            };
+            for value in [0, max, OsRng.gen_range(0..=max)] {
+                let x = builder.add_virtual_target();
+                pw.set_target(x, F::from_canonical_u64(value));
+                builder.enqueue_range_check(x, num_bits);
+            }
+        }
+        // Checking a target again is free.
+        let x = builder.add_virtual_target();
+        pw.set_target(x, F::from_canonical_u64(3));
+        builder.enqueue_range_check(x, 7);
+        builder.enqueue_range_check(x, 2);
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_enqueue_range_check_too_many_bits() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.enqueue_range_check(x, 10);
+        builder.enqueue_range_check(y, 10);
+        // `x` is also checked to 3 bits, which it doesn't fit in.
+        builder.enqueue_range_check(x, 3);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u64(8));
+        pw.set_target(y, F::from_canonical_u64(1023));
+        let err = data.prove(pw).unwrap_err();
+        assert!(format!("{err:#}").contains("doesn't fit"), "{err:#}");
+    }
+
+    #[test]
+    fn test_enqueued_range_checks_are_packed() {
+        let config = CircuitConfig::standard_recursion_config();
+        let widths = [1, 4, 8, 8, 12, 16, 16, 24, 32, 48];
+
+        let mut eager = CircuitBuilder::<F, D>::new(config.clone());
+        let mut batched = CircuitBuilder::<F, D>::new(config);
+        for i in 0..1000 {
+            let num_bits = widths[i % widths.len()];
+            let x = eager.add_virtual_target();
+            eager.range_check(x, num_bits);
+            let x = batched.add_virtual_target();
+            batched.enqueue_range_check(x, num_bits);
+        }
+        assert_eq!(batched.num_gates(), 0);
+        batched.place_range_checks();
+
+        // The eager checks take a row each, while a row of `RangeCheckGate` checks 67 one-bit
+        // values, 15 eight-bit values or 2 48-bit values.
+        assert_eq!(eager.num_gates(), 1000);
+        assert_eq!(batched.num_gates(), 154);
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant 8 doesn't fit in 3 bits")]
+    fn test_enqueue_range_check_constant() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.constant(F::from_canonical_u64(7));
+        builder.enqueue_range_check(x, 3);
+        assert_eq!(builder.num_gates(), 0);
+        let x = builder.constant(F::from_canonical_u64(8));
+        builder.enqueue_range_check(x, 3);
+    }
+}
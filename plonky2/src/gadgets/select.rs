@@ -1,3 +1,6 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
 use crate::field::extension::Extendable;
 use crate::hash::hash_types::RichField;
 use crate::iop::ext_target::ExtensionTarget;
@@ -34,18 +37,50 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let tmp = self.mul_sub(b.target, y, y);
         self.mul_sub(b.target, x, tmp)
     }
+
+    /// Returns `v[index]`, where `index` is given by its little-endian bits, with a tree of
+    /// `select`s. This costs `v.len() - 1` selections, so `random_access` is usually much cheaper.
+    pub fn select_by_bits(&mut self, index_bits: &[BoolTarget], v: Vec<Target>) -> Target {
+        debug_assert_eq!(v.len(), 1 << index_bits.len());
+        index_bits.iter().fold(v, |v, &bit| {
+            v.chunks(2)
+                .map(|pair| self.select(bit, pair[1], pair[0]))
+                .collect()
+        })[0]
+    }
+
+    /// Like `select_by_bits`, but with `ExtensionTarget`s rather than simple `Target`s.
+    pub fn select_ext_by_bits(
+        &mut self,
+        index_bits: &[BoolTarget],
+        v: Vec<ExtensionTarget<D>>,
+    ) -> ExtensionTarget<D> {
+        debug_assert_eq!(v.len(), 1 << index_bits.len());
+        index_bits.iter().fold(v, |v, &bit| {
+            v.chunks(2)
+                .map(|pair| self.select_ext(bit, pair[1], pair[0]))
+                .collect()
+        })[0]
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{vec, vec::Vec};
+
     use anyhow::Result;
+    use rand::rngs::OsRng;
+    use rand::Rng;
 
-    use crate::field::types::Sample;
+    use crate::field::types::{Field, Sample};
+    use crate::iop::target::BoolTarget;
     use crate::iop::witness::{PartialWitness, WitnessWrite};
     use crate::plonk::circuit_builder::CircuitBuilder;
     use crate::plonk::circuit_data::CircuitConfig;
     use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
     use crate::plonk::verifier::verify;
+    use crate::util::testing::assert_differential;
 
     #[test]
     fn test_select() -> Result<()> {
@@ -77,4 +112,71 @@ mod tests {
 
         verify(proof, &data.verifier_only, &data.common)
     }
+
+    #[test]
+    fn test_select_by_bits() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let values = F::rand_vec(8);
+        let ext_values = FF::rand_vec(8);
+        let vt = builder.add_virtual_targets(8);
+        let ext_vt = builder.add_virtual_extension_targets(8);
+        pw.set_target_arr(&vt, &values);
+        for (&t, &v) in ext_vt.iter().zip(&ext_values) {
+            pw.set_extension_target(t, v);
+        }
+
+        for index in 0..8 {
+            let index_bits = (0..3)
+                .map(|i| {
+                    let bit = builder.add_virtual_bool_target_safe();
+                    pw.set_bool_target(bit, index >> i & 1 == 1);
+                    bit
+                })
+                .collect::<Vec<_>>();
+            let selected = builder.select_by_bits(&index_bits, vt.clone());
+            builder.connect(selected, vt[index]);
+            let selected = builder.select_ext_by_bits(&index_bits, ext_vt.clone());
+            builder.connect_extension(selected, ext_vt[index]);
+        }
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+
+        verify(proof, &data.verifier_only, &data.common)
+    }
+
+    #[test]
+    fn test_select_differential() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            32,
+            || {
+                let b = F::from_bool(OsRng.gen());
+                vec![b, F::rand(), F::rand()]
+            },
+            |builder, inputs| {
+                let b = BoolTarget::new_unsafe(inputs[0]);
+                builder.assert_bool(b);
+                vec![builder.select(b, inputs[1], inputs[2])]
+            },
+            |inputs| {
+                vec![if inputs[0].is_one() {
+                    inputs[1]
+                } else {
+                    inputs[2]
+                }]
+            },
+        );
+    }
 }
@@ -110,6 +110,18 @@ impl<F: RichField + Extendable<D>, const B: usize, const D: usize> SimpleGenerat
         out_buffer.set_target(Target::wire(self.row, BaseSumGate::<B>::WIRE_SUM), sum);
     }
 
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        let sum = map(Target::wire(self.row, BaseSumGate::<B>::WIRE_SUM));
+        Some(Self {
+            row: sum.row()?,
+            limbs: self
+                .limbs
+                .iter()
+                .map(|b| BoolTarget::new_unsafe(map(b.target)))
+                .collect(),
+        })
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
         dst.write_usize(self.row)?;
         dst.write_target_bool_vec(&self.limbs)
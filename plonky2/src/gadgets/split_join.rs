@@ -21,7 +21,24 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// bit of the integer, with little-endian ordering.
     /// Verifies that the decomposition is correct by using `k` `BaseSum<2>` gates
     /// with `k` such that `k * num_routed_wires >= num_bits`.
+    ///
+    /// With 64 bits or more, the decomposition is also checked to be canonical, i.e. to encode an
+    /// integer less than the field order. Otherwise, integers below `2^64 - F::ORDER` could be
+    /// decomposed as the bits of `integer + F::ORDER` too.
     pub fn split_le(&mut self, integer: Target, num_bits: usize) -> Vec<BoolTarget> {
+        let bits = self.split_le_noncanonical(integer, num_bits);
+        self.assert_canonical_le_bits(&bits);
+        bits
+    }
+
+    /// Like `split_le`, but permits non-canonical decompositions when `num_bits >= 64`. Callers
+    /// must make sure that choosing between the two decompositions of an integer doesn't help a
+    /// malicious prover.
+    pub(crate) fn split_le_noncanonical(
+        &mut self,
+        integer: Target,
+        num_bits: usize,
+    ) -> Vec<BoolTarget> {
         if num_bits == 0 {
             return Vec::new();
         }
@@ -59,6 +76,39 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         bits
     }
+
+    /// Asserts that the little-endian `bits` of a field element, if there are at least 64 of
+    /// them, are its canonical representation: bits past the 64th are zero, and the first 64 are
+    /// checked with `assert_canonical_u64_repr`. Fewer bits can't encode an integer above the
+    /// order.
+    pub(crate) fn assert_canonical_le_bits(&mut self, bits: &[BoolTarget]) {
+        if bits.len() < 64 {
+            return;
+        }
+        for b in &bits[64..] {
+            self.assert_zero(b.target);
+        }
+        self.assert_canonical_u64_repr(bits[..64].try_into().unwrap());
+    }
+
+    /// Asserts that the integer with little-endian bits `bits` is less than the Goldilocks order
+    /// `p = 2^64 - 2^32 + 1`, so that it is the canonical representation of a field element.
+    ///
+    /// Writing the integer as `low + 2^32 * high` with 32-bit limbs, this holds exactly when
+    /// `high` isn't all ones, or `low` is zero.
+    pub fn assert_canonical_u64_repr(&mut self, bits: &[BoolTarget; 64]) {
+        assert_eq!(
+            F::ORDER,
+            ((u32::MAX as u64) << 32) + 1,
+            "Canonical representations are only checked for the Goldilocks field"
+        );
+        let low = self.le_sum(bits[..32].iter());
+        let high = self.le_sum(bits[32..].iter());
+        let max_high = self.constant(F::from_canonical_u32(u32::MAX));
+        let high_is_max = self.is_equal(high, max_high);
+        let low_if_high_is_max = self.mul(high_is_max.target, low);
+        self.assert_zero(low_if_high_is_max);
+    }
 }
 
 #[derive(Debug, Default)]
@@ -91,6 +141,13 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Spl
         );
     }
 
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        Some(Self {
+            integer: map(self.integer),
+            bits: self.bits.iter().map(|&b| map(b)).collect(),
+        })
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
         dst.write_target(self.integer)?;
         dst.write_target_vec(&self.bits)
@@ -146,6 +203,19 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Wir
         );
     }
 
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        let gates = self
+            .gates
+            .iter()
+            .map(|&gate| map(Target::wire(gate, BaseSumGate::<2>::WIRE_SUM)).row())
+            .collect::<Option<_>>()?;
+        Some(Self {
+            integer: map(self.integer),
+            gates,
+            num_limbs: self.num_limbs,
+        })
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
         dst.write_target(self.integer)?;
         dst.write_usize_vec(&self.gates)?;
@@ -163,3 +233,117 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Wir
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::{Field, Field64, PrimeField64, Sample};
+    use crate::iop::witness::PartialWitness;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::util::testing::assert_differential;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_split_le_canonical_boundaries() -> Result<()> {
+        // The largest integer with a second 64-bit decomposition, the largest integer below the
+        // order, and values around the limbs' boundary.
+        let values = [
+            0,
+            1,
+            u32::MAX as u64 - 1,
+            u32::MAX as u64,
+            1 << 32,
+            F::ORDER - 2,
+            F::ORDER - 1,
+        ];
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut pw = PartialWitness::new();
+        for value in values {
+            let x = builder.add_virtual_target();
+            pw.set_target(x, F::from_canonical_u64(value));
+            let bits = builder.split_le(x, 64);
+            let (low, high) = builder.split_low_high(x, 32, 64);
+            for (i, b) in bits.iter().enumerate() {
+                let expected = builder.constant_bool((value >> i) & 1 == 1);
+                builder.connect(b.target, expected.target);
+            }
+            let expected_low = builder.constant(F::from_canonical_u64(value & u32::MAX as u64));
+            let expected_high = builder.constant(F::from_canonical_u64(value >> 32));
+            builder.connect(low, expected_low);
+            builder.connect(high, expected_high);
+        }
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    /// Proves that some bits, chosen freely as a malicious prover would choose the limbs of
+    /// `split_le`, are the canonical representation of the element they encode.
+    fn prove_canonical_u64_repr(value: u64) -> Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let bits: [BoolTarget; 64] =
+            core::array::from_fn(|_| builder.add_virtual_bool_target_safe());
+        let mut x = builder.zero();
+        for b in bits.iter().rev() {
+            x = builder.mul_const_add(F::TWO, x, b.target);
+        }
+        builder.register_public_input(x);
+        builder.assert_canonical_u64_repr(&bits);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        for (i, &b) in bits.iter().enumerate() {
+            pw.set_bool_target(b, (value >> i) & 1 == 1);
+        }
+        let proof = data.prove(pw)?;
+        assert_eq!(proof.public_inputs[0], F::from_noncanonical_u64(value));
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_canonical_u64_repr() -> Result<()> {
+        prove_canonical_u64_repr(5)?;
+        prove_canonical_u64_repr(1 << 32)?;
+        prove_canonical_u64_repr(F::ORDER - 1)
+    }
+
+    #[test]
+    #[should_panic(expected = "was set twice with different values")]
+    fn test_non_canonical_u64_repr() {
+        // The bits of `5 + p`, which encode `5` too.
+        prove_canonical_u64_repr(5 + F::ORDER).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "was set twice with different values")]
+    fn test_order_u64_repr() {
+        prove_canonical_u64_repr(F::ORDER).unwrap();
+    }
+
+    #[test]
+    fn test_split_le_differential() {
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            16,
+            || vec![F::rand()],
+            |builder, inputs| {
+                let bits = builder.split_le(inputs[0], 64);
+                let low = builder.le_sum(bits[..32].iter());
+                bits.iter().map(|b| b.target).chain([low]).collect()
+            },
+            |inputs| {
+                let x = inputs[0].to_canonical_u64();
+                (0..64)
+                    .map(|i| F::from_bool((x >> i) & 1 == 1))
+                    .chain([F::from_canonical_u32(x as u32)])
+                    .collect()
+            },
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! A stack of bounded depth, whose operations are chosen by the prover.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use crate::field::extension::Extendable;
+use crate::field::types::Field;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::{BoolTarget, Target};
+use crate::iop::witness::WitnessWrite;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::util::log2_strict;
+
+/// An operation on a stack.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StackOp<F: Field> {
+    /// Pushes a value on the stack.
+    Push(F),
+    /// Removes the top value of the stack.
+    Pop,
+    /// Reads the top value of the stack without removing it.
+    Peek,
+}
+
+/// An operation on a [`StackTargets`], chosen by the prover. It is a push if `push` is true, a
+/// pop if `pop` is true, and a peek if neither is.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StackOpTarget {
+    pub push: BoolTarget,
+    pub pop: BoolTarget,
+    /// The pushed value. It is ignored by pops and peeks.
+    pub value: Target,
+}
+
+/// A stack holding at most `items.len()` values, which starts empty.
+///
+/// Each operation reads the slot below the stack pointer with `random_access`, or the slot at it
+/// for pushes, and writes pushed values through a mask decoded from the bits of that index. The
+/// index is range checked as it is split into bits, which makes popping or peeking into an empty
+/// stack, and pushing onto a full one, unsatisfiable.
+///
+/// The mask makes each operation cost `O(max_depth)` arithmetic operations: about 2.3 rows per
+/// operation at depth 4, 3.2 at depth 8, 4.9 at depth 16 and 15 at depth 64 in the standard
+/// recursion config, whose `random_access` doesn't fit deeper stacks. A write log whose reads are
+/// checked against a sorted permutation of it would take about 3 rows per operation at any depth,
+/// for the switches of a permutation network and for range checking the order of the sorted log.
+/// It would only pay off beyond depth 8 or so, and the log can only be checked once all the
+/// operations are known, which would need a final call to close the stack, so this stack is meant
+/// for shallow depths.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StackTargets {
+    /// The slots of the stack, from the bottom. Slots at or above `sp` hold stale values.
+    pub items: Vec<Target>,
+    /// The stack pointer, i.e. the number of values on the stack.
+    pub sp: Target,
+}
+
+impl StackTargets {
+    /// Creates an empty stack of the given maximum depth, which must be a power of two.
+    pub fn new<F: RichField + Extendable<D>, const D: usize>(
+        builder: &mut CircuitBuilder<F, D>,
+        max_depth: usize,
+    ) -> Self {
+        assert!(
+            max_depth > 1 && max_depth.is_power_of_two(),
+            "The maximum depth of a stack must be a power of two greater than one, not {}",
+            max_depth
+        );
+        let zero = builder.zero();
+        Self {
+            items: vec![zero; max_depth],
+            sp: zero,
+        }
+    }
+
+    /// The number of values the stack can hold.
+    pub fn max_depth(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Applies `op` to the stack. Returns the pushed value for a push, the removed value for a
+    /// pop and the top value for a peek.
+    pub fn apply<F: RichField + Extendable<D>, const D: usize>(
+        &mut self,
+        builder: &mut CircuitBuilder<F, D>,
+        op: StackOpTarget,
+    ) -> Target {
+        // Pushes access the slot at the stack pointer, the others the slot below it.
+        let sp_minus_one = builder.add_const(self.sp, F::NEG_ONE);
+        let index = builder.add(sp_minus_one, op.push.target);
+        let index_bits = builder.split_le(index, log2_strict(self.max_depth()));
+        let read = builder.random_access(index, self.items.clone());
+
+        // Decode the index into a mask selecting its slot, which is zero unless pushing. Bits are
+        // taken from the most significant, so that the mask ends up in slot order.
+        let mut mask = vec![op.push.target];
+        for bit in index_bits.iter().rev() {
+            mask = mask
+                .into_iter()
+                .flat_map(|m| {
+                    let m_and_bit = builder.mul(m, bit.target);
+                    let m_and_not_bit = builder.sub(m, m_and_bit);
+                    [m_and_not_bit, m_and_bit]
+                })
+                .collect();
+        }
+        for (item, m) in self.items.iter_mut().zip(mask) {
+            *item = builder.select(BoolTarget::new_unsafe(m), op.value, *item);
+        }
+
+        let sp_plus_push = builder.add(self.sp, op.push.target);
+        self.sp = builder.sub(sp_plus_push, op.pop.target);
+        builder.select(op.push, op.value, read)
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Adds targets for a stack operation, constrained to be a push, a pop or a peek.
+    pub fn add_virtual_stack_op(&mut self) -> StackOpTarget {
+        let push = self.add_virtual_bool_target_safe();
+        let pop = self.add_virtual_bool_target_safe();
+        let push_and_pop = self.and(push, pop);
+        self.assert_zero(push_and_pop.target);
+        StackOpTarget {
+            push,
+            pop,
+            value: self.add_virtual_target(),
+        }
+    }
+}
+
+/// Sets the targets of a `StackOpTarget` to describe `op`.
+pub fn set_stack_op_target<F, W>(witness: &mut W, op_target: &StackOpTarget, op: StackOp<F>)
+where
+    F: Field,
+    W: WitnessWrite<F> + ?Sized,
+{
+    witness.set_bool_target(op_target.push, matches!(op, StackOp::Push(_)));
+    witness.set_bool_target(op_target.pop, op == StackOp::Pop);
+    let value = match op {
+        StackOp::Push(value) => value,
+        StackOp::Pop | StackOp::Peek => F::ZERO,
+    };
+    witness.set_target(op_target.value, value);
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, string::String};
+
+    use anyhow::Result;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+
+    use super::*;
+    use crate::circuit_size_test;
+    use crate::field::types::{PrimeField64, Sample};
+    use crate::iop::generator::{GeneratedValues, WitnessGenerator, WitnessGeneratorRef};
+    use crate::iop::witness::{PartialWitness, PartitionWitness, Witness};
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData, CommonCircuitData};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::proof::ProofWithPublicInputs;
+    use crate::util::serialization::{Buffer, IoError, IoResult};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Wraps a generator which would refuse an out-of-range stack index, i.e. one of those splitting
+    /// it into bits or reading the slot at it, by running it as if the index were reduced modulo the
+    /// stack's depth. The index itself stays out of range in the witness, so that only the
+    /// constraints can reject it.
+    #[derive(Debug)]
+    struct ReducedIndexGenerator {
+        inner: WitnessGeneratorRef<F, D>,
+        max_depth: u64,
+    }
+
+    impl WitnessGenerator<F, D> for ReducedIndexGenerator {
+        fn id(&self) -> String {
+            format!("ReducedIndexGenerator({})", self.inner.0.id())
+        }
+
+        fn watch_list(&self) -> Vec<Target> {
+            self.inner.0.watch_list()
+        }
+
+        fn run(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) -> bool {
+            self.try_run(witness, out_buffer).unwrap()
+        }
+
+        fn try_run(
+            &self,
+            witness: &PartitionWitness<F>,
+            out_buffer: &mut GeneratedValues<F>,
+        ) -> Result<bool> {
+            // Both generators watch the index first.
+            let index = self.watch_list()[0];
+            let mut reduced = witness.clone();
+            if let Some(value) = witness.try_get_target(index) {
+                let rep = witness.representative_map[witness.target_index(index)];
+                reduced.values[rep] = Some(F::from_canonical_u64(
+                    value.to_canonical_u64() % self.max_depth,
+                ));
+            }
+            // The index and its copies keep their out-of-range value.
+            let mut values = GeneratedValues::empty();
+            let finished = self.inner.0.try_run(&reduced, &mut values)?;
+            for (target, value) in values.target_values {
+                if !witness.contains(target) {
+                    out_buffer.set_target(target, value);
+                }
+            }
+            Ok(finished)
+        }
+
+        fn serialize(
+            &self,
+            _dst: &mut Vec<u8>,
+            _common_data: &CommonCircuitData<F, D>,
+        ) -> IoResult<()> {
+            Err(IoError)
+        }
+
+        fn deserialize(
+            _src: &mut Buffer,
+            _common_data: &CommonCircuitData<F, D>,
+        ) -> IoResult<Self> {
+            Err(IoError)
+        }
+    }
+
+    /// Proves `pw` for a stack circuit of depth `max_depth`, letting the prover use out-of-range
+    /// indices.
+    fn prove_out_of_range(
+        data: &mut CircuitData<F, C, D>,
+        max_depth: u64,
+        pw: PartialWitness<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        data.prover_only.generators = core::mem::take(&mut data.prover_only.generators)
+            .into_iter()
+            .map(|inner| {
+                let id = inner.0.id();
+                if id.starts_with("BaseSplitGenerator")
+                    || id == "WireSplitGenerator"
+                    || id == "RandomAccessGenerator"
+                {
+                    WitnessGeneratorRef::new(ReducedIndexGenerator { inner, max_depth })
+                } else {
+                    inner
+                }
+            })
+            .collect();
+        data.prove(pw)
+    }
+
+    /// Builds a circuit applying `num_ops` operations to a stack of depth `max_depth`, with the
+    /// results as public inputs.
+    fn stack_circuit(
+        max_depth: usize,
+        num_ops: usize,
+    ) -> (CircuitData<F, C, D>, Vec<StackOpTarget>) {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let mut stack = StackTargets::new(&mut builder, max_depth);
+        let ops = (0..num_ops)
+            .map(|_| {
+                let op = builder.add_virtual_stack_op();
+                let result = stack.apply(&mut builder, op);
+                builder.register_public_input(result);
+                op
+            })
+            .collect();
+        (builder.build::<C>(), ops)
+    }
+
+    #[test]
+    fn test_stack_matches_vec() -> Result<()> {
+        const MAX_DEPTH: usize = 8;
+        const NUM_OPS: usize = 100;
+        let (data, op_targets) = stack_circuit(MAX_DEPTH, NUM_OPS);
+
+        let mut rng = OsRng;
+        let mut model = Vec::new();
+        let mut expected = Vec::new();
+        let mut pw = PartialWitness::new();
+        for op_target in &op_targets {
+            let op = match rng.gen_range(0..3) {
+                _ if model.is_empty() => StackOp::Push(F::rand()),
+                _ if model.len() == MAX_DEPTH => StackOp::Pop,
+                0 => StackOp::Push(F::rand()),
+                1 => StackOp::Pop,
+                _ => StackOp::Peek,
+            };
+            expected.push(match op {
+                StackOp::Push(value) => {
+                    model.push(value);
+                    value
+                }
+                StackOp::Pop => model.pop().unwrap(),
+                StackOp::Peek => *model.last().unwrap(),
+            });
+            set_stack_op_target(&mut pw, op_target, op);
+        }
+
+        let proof = data.prove(pw)?;
+        assert_eq!(proof.public_inputs, expected);
+        data.verify(proof)
+    }
+
+    // Each operation at this depth takes about five rows: a `BaseSumGate` splitting the index, a
+    // quarter of a `RandomAccessGate`, and three or four `ArithmeticGate`s, mostly for the mask's
+    // 2 * 16 products and differences and its 16 selects.
+    circuit_size_test!(
+        stack_size,
+        CircuitConfig::standard_recursion_config(),
+        |builder| {
+            let mut stack = StackTargets::new(builder, 16);
+            for _ in 0..8 {
+                let op = builder.add_virtual_stack_op();
+                let result = stack.apply(builder, op);
+                builder.register_public_input(result);
+            }
+        },
+        degree_bits: 6,
+        num_rows: 41,
+        gates: {
+            "ArithmeticGate" => 29,
+            "BaseSumGate" => 8,
+            "PoseidonGate" => 1,
+            "PublicInputGate" => 1,
+            "RandomAccessGate" => 2,
+        },
+    );
+
+    #[test]
+    fn test_stack_underflow() -> Result<()> {
+        let (mut data, op_targets) = stack_circuit(4, 2);
+        let mut pw = PartialWitness::new();
+        set_stack_op_target(&mut pw, &op_targets[0], StackOp::Push(F::ONE));
+        set_stack_op_target(&mut pw, &op_targets[1], StackOp::Peek);
+        let proof = data.prove(pw)?;
+        data.verify(proof)?;
+
+        let mut pw = PartialWitness::new();
+        set_stack_op_target(&mut pw, &op_targets[0], StackOp::Pop);
+        set_stack_op_target(&mut pw, &op_targets[1], StackOp::Push(F::ONE));
+        let proof = prove_out_of_range(&mut data, 4, pw)?;
+        assert!(data.verify(proof).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stack_overflow() -> Result<()> {
+        let (mut data, op_targets) = stack_circuit(2, 3);
+        let mut pw = PartialWitness::new();
+        for op_target in &op_targets {
+            set_stack_op_target(&mut pw, op_target, StackOp::Push(F::TWO));
+        }
+        let proof = prove_out_of_range(&mut data, 2, pw)?;
+        assert!(data.verify(proof).is_err());
+        Ok(())
+    }
+}
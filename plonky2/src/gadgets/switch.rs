@@ -0,0 +1,194 @@
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use crate::field::extension::Extendable;
+use crate::gates::switch::SwitchGate;
+use crate::hash::hash_types::{HashOutTarget, RichField, NUM_HASH_OUT_ELTS};
+use crate::iop::target::{BoolTarget, Target};
+use crate::plonk::circuit_builder::CircuitBuilder;
+
+/// The number of values switched by each copy of a `SwitchGate`, so that hashes fill copies
+/// exactly.
+const SWITCH_CHUNK_SIZE: usize = NUM_HASH_OUT_ELTS;
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Returns `(x, y)` if `b` is false, and `(y, x)` if it is true.
+    ///
+    /// The states are switched in chunks of `SWITCH_CHUNK_SIZE` values, by copies of a
+    /// `SwitchGate` which all share the switch bit `b`. This takes a single routed wire per value
+    /// of each state and output, where switching values one by one with `select`s takes two
+    /// arithmetic operations per output.
+    pub fn switch(
+        &mut self,
+        b: BoolTarget,
+        x: &[Target],
+        y: &[Target],
+    ) -> (Vec<Target>, Vec<Target>) {
+        assert_eq!(
+            x.len(),
+            y.len(),
+            "Switched states must have the same length"
+        );
+        if let Some(b) = self.target_as_constant(b.target) {
+            return if b.is_one() {
+                (y.to_vec(), x.to_vec())
+            } else {
+                (x.to_vec(), y.to_vec())
+            };
+        }
+
+        let zero = self.zero();
+        let gate = SwitchGate::<F, D>::new_from_config(&self.config, SWITCH_CHUNK_SIZE);
+        let mut first_outputs = Vec::with_capacity(x.len());
+        let mut second_outputs = Vec::with_capacity(y.len());
+        for (x_chunk, y_chunk) in x.chunks(SWITCH_CHUNK_SIZE).zip(y.chunks(SWITCH_CHUNK_SIZE)) {
+            let (row, copy) = self.find_slot(gate, &[], &[]);
+            self.connect(b.target, Target::wire(row, gate.wire_switch_bool(copy)));
+            for e in 0..SWITCH_CHUNK_SIZE {
+                // The last chunk is padded with zeros, so that the generator can run.
+                let x_e = x_chunk.get(e).copied().unwrap_or(zero);
+                let y_e = y_chunk.get(e).copied().unwrap_or(zero);
+                self.connect(x_e, Target::wire(row, gate.wire_first_input(copy, e)));
+                self.connect(y_e, Target::wire(row, gate.wire_second_input(copy, e)));
+            }
+            for e in 0..x_chunk.len() {
+                first_outputs.push(Target::wire(row, gate.wire_first_output(copy, e)));
+                second_outputs.push(Target::wire(row, gate.wire_second_output(copy, e)));
+            }
+        }
+
+        (first_outputs, second_outputs)
+    }
+
+    /// Like `switch`, but with `HashOutTarget`s, which fill a copy of the gate each.
+    ///
+    /// The Merkle proof gadget orders each node and its sibling with the swap built into the
+    /// hasher's permutation gate, which is free, see `AlgebraicHasher::permute_swapped`. Hashers
+    /// whose gates can't swap their inputs can implement it with this instead.
+    pub fn switch_hashes(
+        &mut self,
+        b: BoolTarget,
+        x: HashOutTarget,
+        y: HashOutTarget,
+    ) -> (HashOutTarget, HashOutTarget) {
+        let (first, second) = self.switch(b, &x.elements, &y.elements);
+        (
+            HashOutTarget::from_vec(first),
+            HashOutTarget::from_vec(second),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    use anyhow::Result;
+
+    use crate::field::types::Sample;
+    use crate::hash::hash_types::HashOut;
+    use crate::iop::target::Target;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn test_switch() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut pw = PartialWitness::<F>::new();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        let (x, y) = (F::rand_vec(12), F::rand_vec(12));
+        let xt = builder.add_virtual_targets(12);
+        let yt = builder.add_virtual_targets(12);
+        pw.set_target_arr(&xt, &x);
+        pw.set_target_arr(&yt, &y);
+
+        for swap in [false, true] {
+            let b = builder.add_virtual_bool_target_safe();
+            pw.set_bool_target(b, swap);
+            let (first, second) = builder.switch(b, &xt, &yt);
+            let (expected_first, expected_second) = if swap { (&yt, &xt) } else { (&xt, &yt) };
+            for (&t, &expected) in first.iter().zip(expected_first) {
+                builder.connect(t, expected);
+            }
+            for (&t, &expected) in second.iter().zip(expected_second) {
+                builder.connect(t, expected);
+            }
+        }
+
+        // States which don't fill the last copy are padded.
+        let b = builder.add_virtual_bool_target_safe();
+        pw.set_bool_target(b, true);
+        let (first, second) = builder.switch(b, &xt[..3], &yt[..3]);
+        builder.connect(first[2], yt[2]);
+        builder.connect(second[2], xt[2]);
+
+        let hashes = [HashOut::<F>::rand(), HashOut::rand()];
+        let ht = [builder.add_virtual_hash(), builder.add_virtual_hash()];
+        pw.set_hash_target(ht[0], hashes[0]);
+        pw.set_hash_target(ht[1], hashes[1]);
+        let b = builder.add_virtual_bool_target_safe();
+        pw.set_bool_target(b, true);
+        let (first, second) = builder.switch_hashes(b, ht[0], ht[1]);
+        builder.connect_hashes(first, ht[1]);
+        builder.connect_hashes(second, ht[0]);
+
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    fn test_switch_constant_bit() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_targets(4);
+        let y = builder.add_virtual_targets(4);
+        let num_gates = builder.num_gates();
+
+        let _true = builder._true();
+        assert_eq!(builder.switch(_true, &x, &y), (y.clone(), x.clone()));
+        let _false = builder._false();
+        assert_eq!(builder.switch(_false, &x, &y), (x, y));
+        assert_eq!(builder.num_gates(), num_gates);
+    }
+
+    /// Compares the rows taken to switch a 12-element state with a `SwitchGate`, to those taken to
+    /// select each output separately.
+    #[test]
+    fn test_switch_rows() {
+        let config = CircuitConfig::standard_recursion_config();
+        let num_swaps = 16;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        for _ in 0..num_swaps {
+            let b = builder.add_virtual_bool_target_safe();
+            let x = builder.add_virtual_targets(12);
+            let y = builder.add_virtual_targets(12);
+            builder.switch(b, &x, &y);
+        }
+        let switch_rows = builder.num_gates();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        for _ in 0..num_swaps {
+            let b = builder.add_virtual_bool_target_safe();
+            let x = builder.add_virtual_targets(12);
+            let y = builder.add_virtual_targets(12);
+            let _: (Vec<Target>, Vec<Target>) = x
+                .iter()
+                .zip(&y)
+                .map(|(&x_i, &y_i)| (builder.select(b, y_i, x_i), builder.select(b, x_i, y_i)))
+                .unzip();
+        }
+        let select_rows = builder.num_gates();
+
+        assert!(switch_rows < select_rows);
+    }
+}
@@ -21,7 +21,7 @@ use crate::plonk::vars::{
     EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
     EvaluationVarsBasePacked,
 };
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// A gate which can perform a weighted multiply-add, i.e. `result = c0.x.y + c1.z`. If the config
 /// has enough routed wires, it can support several such operations in one gate.
@@ -67,8 +67,11 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ArithmeticGate
         dst.write_usize(self.num_ops)
     }
 
-    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
         let num_ops = src.read_usize()?;
+        if num_ops > Self::num_ops(&common_data.config) {
+            return Err(IoError);
+        }
         Ok(Self { num_ops })
     }
 
@@ -160,6 +163,16 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ArithmeticGate
     fn num_constraints(&self) -> usize {
         self.num_ops
     }
+
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        [
+            Self::wire_ith_multiplicand_0(op),
+            Self::wire_ith_multiplicand_1(op),
+            Self::wire_ith_addend(op),
+            Self::wire_ith_output(op),
+        ]
+        .to_vec()
+    }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> PackedEvaluableBase<F, D> for ArithmeticGate {
@@ -221,7 +234,7 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
         let computed_output =
             multiplicand_0 * multiplicand_1 * self.const_0 + addend * self.const_1;
 
-        out_buffer.set_target(output_target, computed_output)
+        out_buffer.set_target(output_target, computed_output);
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
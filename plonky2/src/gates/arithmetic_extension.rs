@@ -17,7 +17,7 @@ use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
 use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase};
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// A gate which can perform a weighted multiply-add, i.e. `result = c0.x.y + c1.z`. If the config
 /// has enough routed wires, it can support several such operations in one gate.
@@ -63,8 +63,11 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ArithmeticExte
         dst.write_usize(self.num_ops)
     }
 
-    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
         let num_ops = src.read_usize()?;
+        if num_ops > Self::num_ops(&common_data.config) {
+            return Err(IoError);
+        }
         Ok(Self { num_ops })
     }
 
@@ -165,6 +168,14 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ArithmeticExte
     fn num_constraints(&self) -> usize {
         self.num_ops * D
     }
+
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        Self::wires_ith_multiplicand_0(op)
+            .chain(Self::wires_ith_multiplicand_1(op))
+            .chain(Self::wires_ith_addend(op))
+            .chain(Self::wires_ith_output(op))
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -214,7 +225,7 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
         let computed_output = (multiplicand_0 * multiplicand_1).scalar_mul(self.const_0)
             + addend.scalar_mul(self.const_1);
 
-        out_buffer.set_extension_target(output_target, computed_output)
+        out_buffer.set_extension_target(output_target, computed_output);
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
@@ -246,7 +257,9 @@ mod tests {
     use crate::gates::arithmetic_extension::ArithmeticExtensionGate;
     use crate::gates::gate_testing::{test_eval_fns, test_low_degree};
     use crate::plonk::circuit_data::CircuitConfig;
-    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::config::{
+        GenericConfig, PoseidonGoldilocksConfig, PoseidonGoldilocksQuinticConfig,
+    };
 
     #[test]
     fn low_degree() {
@@ -264,4 +277,15 @@ mod tests {
             ArithmeticExtensionGate::new_from_config(&CircuitConfig::standard_recursion_config());
         test_eval_fns::<F, C, _, D>(gate)
     }
+
+    #[test]
+    fn eval_fns_quintic() -> Result<()> {
+        const D: usize = 5;
+        type C = PoseidonGoldilocksQuinticConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate =
+            ArithmeticExtensionGate::new_from_config(&CircuitConfig::standard_recursion_config());
+        test_low_degree::<F, _, D>(gate.clone());
+        test_eval_fns::<F, C, _, D>(gate)
+    }
 }
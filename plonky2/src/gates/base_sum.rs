@@ -2,6 +2,8 @@
 use alloc::{format, string::String, vec, vec::Vec};
 use core::ops::Range;
 
+use anyhow::Result;
+
 use crate::field::extension::Extendable;
 use crate::field::packed::PackedField;
 use crate::field::types::{Field, Field64};
@@ -21,7 +23,7 @@ use crate::plonk::vars::{
     EvaluationVarsBasePacked,
 };
 use crate::util::log_floor;
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// A gate which can decompose a number into base B little-endian limbs.
 #[derive(Copy, Clone, Debug)]
@@ -58,8 +60,11 @@ impl<F: RichField + Extendable<D>, const D: usize, const B: usize> Gate<F, D> fo
         dst.write_usize(self.num_limbs)
     }
 
-    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
         let num_limbs = src.read_usize()?;
+        if num_limbs >= common_data.config.num_routed_wires {
+            return Err(IoError);
+        }
         Ok(Self { num_limbs })
     }
 
@@ -134,6 +139,22 @@ impl<F: RichField + Extendable<D>, const D: usize, const B: usize> Gate<F, D> fo
         0
     }
 
+    // If `B^num_limbs` reached the field order, some values would have two decompositions, one of
+    // which doesn't fit the range being checked.
+    fn validate_constants(&self, _constants: &[F]) -> Result<(), String> {
+        if B < 2 {
+            return Err(format!("base {B} is less than 2"));
+        }
+        let max_limbs = log_floor(F::ORDER - 1, B as u64);
+        if self.num_limbs > max_limbs {
+            return Err(format!(
+                "{} base {B} limbs overflow the field, which fits {max_limbs}",
+                self.num_limbs
+            ));
+        }
+        Ok(())
+    }
+
     // Bounded by the range-check (x-0)*(x-1)*...*(x-B+1).
     fn degree(&self) -> usize {
         B
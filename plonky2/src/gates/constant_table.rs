@@ -0,0 +1,175 @@
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String, vec::Vec};
+
+use serde::{Deserialize, Serialize};
+
+use crate::field::extension::Extendable;
+use crate::field::packed::PackedField;
+use crate::gates::gate::Gate;
+use crate::gates::packed_util::PackedEvaluableBase;
+use crate::gates::util::StridedConstraintConsumer;
+use crate::hash::hash_types::RichField;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::generator::{ConstantGenerator, SimpleGenerator, WitnessGeneratorRef};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::CommonCircuitData;
+use crate::plonk::vars::{
+    EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
+    EvaluationVarsBasePacked,
+};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
+
+/// A gate which outputs a row of a table of constants, one value per wire.
+///
+/// Like `ConstantGate`, the values are the gate's constants, committed in the constants
+/// polynomials, so all tables share this one gate type, whatever their values. Its rows are kept
+/// apart from the constants pooled by `CircuitBuilder::constant`, so that a table's values can be
+/// read by index. See [`CircuitBuilder::constant_table`].
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ConstantTableGate {
+    pub(crate) num_values: usize,
+}
+
+impl ConstantTableGate {
+    pub const fn new(num_values: usize) -> Self {
+        Self { num_values }
+    }
+
+    const fn const_value(&self, i: usize) -> usize {
+        debug_assert!(i < self.num_values);
+        i
+    }
+
+    pub const fn wire_value(&self, i: usize) -> usize {
+        debug_assert!(i < self.num_values);
+        i
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ConstantTableGate {
+    fn id(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.num_values)
+    }
+
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let num_values = src.read_usize()?;
+        let config = &common_data.config;
+        if num_values == 0
+            || num_values > config.num_constants
+            || num_values > config.num_routed_wires
+        {
+            return Err(IoError);
+        }
+        Ok(Self { num_values })
+    }
+
+    fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+        (0..self.num_values)
+            .map(|i| {
+                vars.local_constants[self.const_value(i)] - vars.local_wires[self.wire_value(i)]
+            })
+            .collect()
+    }
+
+    fn eval_unfiltered_base_one(
+        &self,
+        _vars: EvaluationVarsBase<F>,
+        _yield_constr: StridedConstraintConsumer<F>,
+    ) {
+        panic!("use eval_unfiltered_base_packed instead");
+    }
+
+    fn eval_unfiltered_base_batch(&self, vars_base: EvaluationVarsBaseBatch<F>) -> Vec<F> {
+        self.eval_unfiltered_base_batch_packed(vars_base)
+    }
+
+    fn eval_unfiltered_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: EvaluationTargets<D>,
+    ) -> Vec<ExtensionTarget<D>> {
+        (0..self.num_values)
+            .map(|i| {
+                builder.sub_extension(
+                    vars.local_constants[self.const_value(i)],
+                    vars.local_wires[self.wire_value(i)],
+                )
+            })
+            .collect()
+    }
+
+    fn generators(&self, row: usize, local_constants: &[F]) -> Vec<WitnessGeneratorRef<F, D>> {
+        // Unlike `ConstantGate`'s, the values aren't pooled constants filled in when building, so
+        // they are copied to the wires here.
+        (0..self.num_values)
+            .map(|i| {
+                WitnessGeneratorRef::new(
+                    ConstantGenerator {
+                        row,
+                        constant_index: self.const_value(i),
+                        wire_index: self.wire_value(i),
+                        constant: local_constants[self.const_value(i)],
+                    }
+                    .adapter(),
+                )
+            })
+            .collect()
+    }
+
+    fn num_wires(&self) -> usize {
+        self.num_values
+    }
+
+    fn num_constants(&self) -> usize {
+        self.num_values
+    }
+
+    fn degree(&self) -> usize {
+        1
+    }
+
+    fn num_constraints(&self) -> usize {
+        self.num_values
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> PackedEvaluableBase<F, D> for ConstantTableGate {
+    fn eval_unfiltered_base_packed<P: PackedField<Scalar = F>>(
+        &self,
+        vars: EvaluationVarsBasePacked<P>,
+        mut yield_constr: StridedConstraintConsumer<P>,
+    ) {
+        yield_constr.many((0..self.num_values).map(|i| {
+            vars.local_constants[self.const_value(i)] - vars.local_wires[self.wire_value(i)]
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::field::goldilocks_field::GoldilocksField;
+    use crate::gates::constant_table::ConstantTableGate;
+    use crate::gates::gate_testing::{test_eval_fns, test_low_degree};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    #[test]
+    fn low_degree() {
+        let gate = ConstantTableGate::new(2);
+        test_low_degree::<GoldilocksField, _, 2>(gate)
+    }
+
+    #[test]
+    fn eval_fns() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate = ConstantTableGate::new(2);
+        test_eval_fns::<F, C, _, D>(gate)
+    }
+}
@@ -8,6 +8,8 @@ use alloc::{
 use core::marker::PhantomData;
 use core::ops::Range;
 
+use anyhow::Result;
+
 use crate::field::extension::algebra::ExtensionAlgebra;
 use crate::field::extension::{Extendable, FieldExtension, OEF};
 use crate::field::interpolation::barycentric_weights;
@@ -23,7 +25,7 @@ use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
 use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase};
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// One of the instantiations of `InterpolationGate`: allows constraints of variable
 /// degree, up to `1<<subgroup_bits`.
@@ -78,21 +80,23 @@ impl<F: RichField + Extendable<D>, const D: usize> CosetInterpolationGate<F, D>
         // Minimizing the degree this way allows the gate to be in a larger selector group
         let degree = (n_points - 2) / (n_intermediates + 1) + 2;
 
-        let barycentric_weights = barycentric_weights(
-            &F::two_adic_subgroup(subgroup_bits)
-                .into_iter()
-                .map(|x| (x, F::ZERO))
-                .collect::<Vec<_>>(),
-        );
-
         Self {
             subgroup_bits,
             degree,
-            barycentric_weights,
+            barycentric_weights: Self::subgroup_barycentric_weights(subgroup_bits),
             _phantom: PhantomData,
         }
     }
 
+    fn subgroup_barycentric_weights(subgroup_bits: usize) -> Vec<F> {
+        barycentric_weights(
+            &F::two_adic_subgroup(subgroup_bits)
+                .into_iter()
+                .map(|x| (x, F::ZERO))
+                .collect::<Vec<_>>(),
+        )
+    }
+
     const fn num_points(&self) -> usize {
         1 << self.subgroup_bits
     }
@@ -188,6 +192,15 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for CosetInterpola
         let degree = src.read_usize()?;
         let length = src.read_usize()?;
         let barycentric_weights: Vec<F> = src.read_field_vec(length)?;
+        // There are at least two points, one weight per point, and the constraints are at least
+        // quadratic.
+        if subgroup_bits == 0
+            || subgroup_bits > F::TWO_ADICITY
+            || length != 1 << subgroup_bits
+            || degree < 2
+        {
+            return Err(IoError);
+        }
         Ok(Self {
             subgroup_bits,
             degree,
@@ -379,10 +392,29 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for CosetInterpola
         self.end()
     }
 
+    fn num_routed_wires(&self) -> usize {
+        CosetInterpolationGate::num_routed_wires(self)
+    }
+
     fn num_constants(&self) -> usize {
         0
     }
 
+    // The barycentric weights are hardcoded into the constraints, so wrong weights would make the
+    // gate interpolate some other polynomial than the one through the given values.
+    fn validate_constants(&self, _constants: &[F]) -> Result<(), String> {
+        if self.subgroup_bits == 0 || self.subgroup_bits > F::TWO_ADICITY {
+            return Err(format!("no subgroup of {} bits", self.subgroup_bits));
+        }
+        if self.degree < 2 {
+            return Err("the degree is less than 2".to_string());
+        }
+        if self.barycentric_weights != Self::subgroup_barycentric_weights(self.subgroup_bits) {
+            return Err("the barycentric weights don't match the subgroup".to_string());
+        }
+        Ok(())
+    }
+
     fn degree(&self) -> usize {
         self.degree
     }
@@ -647,7 +679,9 @@ mod tests {
     use crate::field::types::Sample;
     use crate::gates::gate_testing::{test_eval_fns, test_low_degree};
     use crate::hash::hash_types::HashOut;
-    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::config::{
+        GenericConfig, PoseidonGoldilocksConfig, PoseidonGoldilocksQuinticConfig,
+    };
 
     #[test]
     fn test_degree_and_wires_minimized() {
@@ -795,6 +829,18 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn eval_fns_quintic() -> Result<()> {
+        const D: usize = 5;
+        type C = PoseidonGoldilocksQuinticConfig;
+        type F = <C as GenericConfig<D>>::F;
+        test_low_degree::<F, _, D>(CosetInterpolationGate::new(2));
+        for degree in 2..=4 {
+            test_eval_fns::<F, C, _, D>(CosetInterpolationGate::with_max_degree(2, degree))?;
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_gate_constraint() {
         const D: usize = 2;
@@ -844,6 +890,7 @@ mod tests {
             local_constants: &[],
             local_wires: &get_wires(shift, values, eval_point),
             public_inputs_hash: &HashOut::rand(),
+            extra_openings: &[],
         };
 
         assert!(
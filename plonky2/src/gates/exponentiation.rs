@@ -26,7 +26,7 @@ use crate::plonk::vars::{
     EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
     EvaluationVarsBasePacked,
 };
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// A gate for raising a value to a power.
 #[derive(Clone, Debug, Default)]
@@ -84,8 +84,16 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for Exponentiation
         dst.write_usize(self.num_power_bits)
     }
 
-    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
         let num_power_bits = src.read_usize()?;
+        let config = &common_data.config;
+        if num_power_bits == 0
+            || config.num_wires < 2
+            || config.num_routed_wires < 2
+            || num_power_bits > Self::max_power_bits(config.num_wires, config.num_routed_wires)
+        {
+            return Err(IoError);
+        }
         Ok(Self::new(num_power_bits))
     }
 
@@ -189,6 +197,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for Exponentiation
         self.wire_intermediate_value(self.num_power_bits - 1) + 1
     }
 
+    fn num_routed_wires(&self) -> usize {
+        self.wire_output() + 1
+    }
+
     fn num_constants(&self) -> usize {
         0
     }
@@ -420,6 +432,7 @@ mod tests {
             local_constants: &[],
             local_wires: &get_wires(base, power as u64),
             public_inputs_hash: &HashOut::rand(),
+            extra_openings: &[],
         };
         assert!(
             gate.eval_unfiltered(vars).iter().all(|x| x.is_zero()),
@@ -94,10 +94,20 @@ pub trait Gate<F: RichField + Extendable<D>, const D: usize>: 'static + Send + S
             .map(|w| F::Extension::from_basefield(*w))
             .collect::<Vec<_>>();
         let public_inputs_hash = &vars_base.public_inputs_hash;
+        let extra_openings = &(0..vars_base.num_extra_opening_points())
+            .map(|point| {
+                vars_base
+                    .extra_openings(point)
+                    .iter()
+                    .map(|v| F::Extension::from_basefield(*v))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
         let vars = EvaluationVars {
             local_constants,
             local_wires,
             public_inputs_hash,
+            extra_openings,
         };
         let values = self.eval_unfiltered(vars);
 
@@ -228,9 +238,22 @@ pub trait Gate<F: RichField + Extendable<D>, const D: usize>: 'static + Send + S
     /// single gate.
     fn num_wires(&self) -> usize;
 
+    /// The number of wires, at the start of the gate's wires, which may be copy constrained to
+    /// other wires. They must be routed wires of the circuit.
+    fn num_routed_wires(&self) -> usize {
+        self.num_wires()
+    }
+
     /// The number of constants used by this gate.
     fn num_constants(&self) -> usize;
 
+    /// Checks the constants of an instance of this gate, along with the values hardcoded in the gate
+    /// itself, which `CircuitBuilder::add_gate` rejects if they would make the circuit unsound or
+    /// unprovable. Accepts any constants by default.
+    fn validate_constants(&self, _constants: &[F]) -> Result<(), String> {
+        Ok(())
+    }
+
     /// The maximum degree among this gate's constraint polynomials.
     fn degree(&self) -> usize;
 
@@ -243,6 +266,20 @@ pub trait Gate<F: RichField + Extendable<D>, const D: usize>: 'static + Send + S
             .len()
     }
 
+    /// The wires of the `op`th operation of this gate. Every operation lists its wires in the same
+    /// order, so that an operation placed by `CircuitBuilder::find_slot` can be moved to another
+    /// slot. Gates performing several operations must override this.
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        assert_eq!(
+            self.num_ops(),
+            1,
+            "{} performs several operations, but doesn't list their wires",
+            self.id()
+        );
+        assert_eq!(op, 0, "{} performs a single operation", self.id());
+        (0..self.num_wires()).collect()
+    }
+
     /// Enables gates to store some "routed constants", if they have both unused constants and
     /// unused routed wires.
     ///
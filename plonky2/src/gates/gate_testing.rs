@@ -36,6 +36,7 @@ pub fn test_low_degree<F: RichField + Extendable<D>, G: Gate<F, D>, const D: usi
             local_constants,
             local_wires,
             public_inputs_hash,
+            extra_openings: &[],
         })
         .map(|vars| gate.eval_unfiltered(vars))
         .collect::<Vec<_>>();
@@ -114,6 +115,7 @@ pub fn test_eval_fns<
         local_constants: &constants,
         local_wires: &wires,
         public_inputs_hash: &public_inputs_hash,
+        extra_openings: &[],
     };
 
     let evals_base = gate.eval_unfiltered_base_batch(vars_base_batch);
@@ -146,6 +148,7 @@ pub fn test_eval_fns<
         local_constants: &constants,
         local_wires: &wires,
         public_inputs_hash: &public_inputs_hash,
+        extra_openings: &[],
     };
     let evals = gate.eval_unfiltered(vars);
 
@@ -153,6 +156,7 @@ pub fn test_eval_fns<
         local_constants: &constants_t,
         local_wires: &wires_t,
         public_inputs_hash: &public_inputs_hash_t,
+        extra_openings: &[],
     };
     let evals_t = gate.eval_unfiltered_circuit(&mut builder, vars_t);
     pw.set_extension_targets(&evals_t, &evals);
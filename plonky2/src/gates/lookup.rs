@@ -27,7 +27,7 @@ use crate::plonk::vars::{
     EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
     EvaluationVarsBasePacked,
 };
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 pub type Lookup = Vec<(Target, Target)>;
 
@@ -95,10 +95,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for LookupGate {
         let lut_index = src.read_usize()?;
         let mut lut_hash = [0u8; 32];
         src.read_exact(&mut lut_hash)?;
+        if num_slots > Self::num_slots(&common_data.config) {
+            return Err(IoError);
+        }
 
         Ok(Self {
             num_slots,
-            lut: common_data.luts[lut_index].clone(),
+            lut: common_data.luts.get(lut_index).ok_or(IoError)?.clone(),
             lut_hash,
         })
     }
@@ -193,25 +196,20 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Loo
         let get_wire = |wire: usize| -> F { witness.get_target(Target::wire(self.row, wire)) };
 
         let input_val = get_wire(LookupGate::wire_ith_looking_inp(self.slot_nb));
-        let (input, output) = self.lut[input_val.to_canonical_u64() as usize];
-        if input_val == F::from_canonical_u16(input) {
-            let output_val = F::from_canonical_u16(output);
-
-            let out_wire = Target::wire(self.row, LookupGate::wire_ith_looking_out(self.slot_nb));
-            out_buffer.set_target(out_wire, output_val);
-        } else {
-            for (input, output) in self.lut.iter() {
-                if input_val == F::from_canonical_u16(*input) {
-                    let output_val = F::from_canonical_u16(*output);
-
-                    let out_wire =
-                        Target::wire(self.row, LookupGate::wire_ith_looking_out(self.slot_nb));
-                    out_buffer.set_target(out_wire, output_val);
-                    return;
-                }
-            }
-            panic!("Incorrect input value provided");
-        };
+        // Tables usually list their inputs in order, so the input's index is tried first.
+        let (_, output) = self
+            .lut
+            .get(input_val.to_canonical_u64() as usize)
+            .filter(|&&(input, _)| input_val == F::from_canonical_u16(input))
+            .or_else(|| {
+                self.lut
+                    .iter()
+                    .find(|&&(input, _)| input_val == F::from_canonical_u16(input))
+            })
+            .expect("Incorrect input value provided");
+
+        let out_wire = Target::wire(self.row, LookupGate::wire_ith_looking_out(self.slot_nb));
+        out_buffer.set_target(out_wire, F::from_canonical_u16(*output));
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
@@ -233,7 +231,7 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Loo
 
         Ok(Self {
             row,
-            lut: common_data.luts[lut_index].clone(),
+            lut: common_data.luts.get(lut_index).ok_or(IoError)?.clone(),
             slot_nb,
         })
     }
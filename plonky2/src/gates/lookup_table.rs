@@ -30,7 +30,7 @@ use crate::plonk::vars::{
     EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
     EvaluationVarsBasePacked,
 };
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 pub type LookupTable = Arc<Vec<(u16, u16)>>;
 
@@ -111,10 +111,13 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for LookupTableGat
         let lut_index = src.read_usize()?;
         let mut lut_hash = [0u8; 32];
         src.read_exact(&mut lut_hash)?;
+        if num_slots > Self::num_slots(&common_data.config) {
+            return Err(IoError);
+        }
 
         Ok(Self {
             num_slots,
-            lut: common_data.luts[lut_index].clone(),
+            lut: common_data.luts.get(lut_index).ok_or(IoError)?.clone(),
             lut_hash,
             last_lut_row,
         })
@@ -250,7 +253,7 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Loo
 
         Ok(Self {
             row,
-            lut: common_data.luts[lut_index].clone(),
+            lut: common_data.luts.get(lut_index).ok_or(IoError)?.clone(),
             slot_nb,
             num_slots,
             last_lut_row,
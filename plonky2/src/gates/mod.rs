@@ -27,6 +27,7 @@ pub mod arithmetic_base;
 pub mod arithmetic_extension;
 pub mod base_sum;
 pub mod constant;
+pub mod constant_table;
 pub mod coset_interpolation;
 pub mod exponentiation;
 pub mod gate;
@@ -39,9 +40,12 @@ pub mod poseidon;
 pub mod poseidon_mds;
 pub mod public_input;
 pub mod random_access;
+pub mod range_check;
 pub mod reducing;
 pub mod reducing_extension;
+pub mod scalar_arithmetic_extension;
 pub(crate) mod selectors;
+pub mod switch;
 pub mod util;
 
 // Can't use #[cfg(test)] here because it needs to be visible to other crates.
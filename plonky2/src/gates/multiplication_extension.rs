@@ -6,74 +6,121 @@ use alloc::{
 };
 use core::ops::Range;
 
+use crate::field::extension::algebra::ExtensionAlgebra;
 use crate::field::extension::{Extendable, FieldExtension};
 use crate::gates::gate::Gate;
 use crate::gates::util::StridedConstraintConsumer;
 use crate::hash::hash_types::RichField;
-use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::ext_target::{ExtensionAlgebraTarget, ExtensionTarget};
 use crate::iop::generator::{GeneratedValues, SimpleGenerator, WitnessGeneratorRef};
 use crate::iop::target::Target;
 use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
 use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase};
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// A gate which can perform a weighted multiplication, i.e. `result = c0.x.y` on [`ExtensionTarget`].
 /// If the config has enough routed wires, it can support several such operations in one gate.
-#[derive(Debug, Clone)]
+///
+/// With `constant_multiplicand`, the gate instead computes `result = c.y`, where the extension
+/// field constant `c` is held in the gate's `D` constants and shared by all of its operations. This
+/// saves routing `c` to `D` wires of every operation, so that more operations fit in a row.
+#[derive(Debug, Clone, Default)]
 pub struct MulExtensionGate<const D: usize> {
     /// Number of multiplications performed by the gate.
     pub num_ops: usize,
+    /// Whether the first multiplicand of each operation is the constant held in the gate's
+    /// constants, rather than read from wires.
+    pub constant_multiplicand: bool,
 }
 
 impl<const D: usize> MulExtensionGate<D> {
     pub const fn new_from_config(config: &CircuitConfig) -> Self {
         Self {
-            num_ops: Self::num_ops(config),
+            num_ops: Self::num_ops(config, false),
+            constant_multiplicand: false,
+        }
+    }
+
+    /// A gate which multiplies by the constant held in its constants. It needs `D` constants.
+    pub const fn new_constant_multiplicand_from_config(config: &CircuitConfig) -> Self {
+        Self {
+            num_ops: Self::num_ops(config, true),
+            constant_multiplicand: true,
         }
     }
 
     /// Determine the maximum number of operations that can fit in one gate for the given config.
-    pub(crate) const fn num_ops(config: &CircuitConfig) -> usize {
-        let wires_per_op = 3 * D;
-        config.num_routed_wires / wires_per_op
+    pub(crate) const fn num_ops(config: &CircuitConfig, constant_multiplicand: bool) -> usize {
+        config.num_routed_wires / Self::wires_per_op(constant_multiplicand)
+    }
+
+    const fn wires_per_op(constant_multiplicand: bool) -> usize {
+        if constant_multiplicand {
+            2 * D
+        } else {
+            3 * D
+        }
     }
 
-    pub(crate) const fn wires_ith_multiplicand_0(i: usize) -> Range<usize> {
+    /// The wires of the first multiplicand, which gates with a constant multiplicand don't have.
+    pub(crate) const fn wires_ith_multiplicand_0(&self, i: usize) -> Range<usize> {
+        assert!(!self.constant_multiplicand);
         3 * D * i..3 * D * i + D
     }
-    pub(crate) const fn wires_ith_multiplicand_1(i: usize) -> Range<usize> {
-        3 * D * i + D..3 * D * i + 2 * D
+    pub(crate) const fn wires_ith_multiplicand_1(&self, i: usize) -> Range<usize> {
+        let start = Self::wires_per_op(self.constant_multiplicand) * (i + 1) - 2 * D;
+        start..start + D
     }
-    pub(crate) const fn wires_ith_output(i: usize) -> Range<usize> {
-        3 * D * i + 2 * D..3 * D * i + 3 * D
+    pub(crate) const fn wires_ith_output(&self, i: usize) -> Range<usize> {
+        let start = Self::wires_per_op(self.constant_multiplicand) * (i + 1) - D;
+        start..start + D
     }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for MulExtensionGate<D> {
     fn id(&self) -> String {
-        format!("{self:?}")
+        // Gates multiplying wires keep the id they had before constant multiplicands, which
+        // circuit digests depend on.
+        if self.constant_multiplicand {
+            format!("{self:?}")
+        } else {
+            format!("MulExtensionGate {{ num_ops: {} }}", self.num_ops)
+        }
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
-        dst.write_usize(self.num_ops)
+        dst.write_usize(self.num_ops)?;
+        dst.write_bool(self.constant_multiplicand)
     }
 
-    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
         let num_ops = src.read_usize()?;
-        Ok(Self { num_ops })
+        let constant_multiplicand = src.read_bool()?;
+        if num_ops > Self::num_ops(&common_data.config, constant_multiplicand) {
+            return Err(IoError);
+        }
+        Ok(Self {
+            num_ops,
+            constant_multiplicand,
+        })
     }
 
     fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
-        let const_0 = vars.local_constants[0];
-
         let mut constraints = Vec::with_capacity(self.num_ops * D);
         for i in 0..self.num_ops {
-            let multiplicand_0 = vars.get_local_ext_algebra(Self::wires_ith_multiplicand_0(i));
-            let multiplicand_1 = vars.get_local_ext_algebra(Self::wires_ith_multiplicand_1(i));
-            let output = vars.get_local_ext_algebra(Self::wires_ith_output(i));
-            let computed_output = (multiplicand_0 * multiplicand_1).scalar_mul(const_0);
+            let multiplicand_1 = vars.get_local_ext_algebra(self.wires_ith_multiplicand_1(i));
+            let output = vars.get_local_ext_algebra(self.wires_ith_output(i));
+            let computed_output = if self.constant_multiplicand {
+                let multiplicand_0 = ExtensionAlgebra::from_basefield_array(
+                    vars.local_constants[..D].try_into().unwrap(),
+                );
+                multiplicand_0 * multiplicand_1
+            } else {
+                let multiplicand_0 = vars.get_local_ext_algebra(self.wires_ith_multiplicand_0(i));
+                (multiplicand_0 * multiplicand_1).scalar_mul(vars.local_constants[0])
+            };
 
             constraints.extend((output - computed_output).to_basefield_array());
         }
@@ -86,13 +133,18 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for MulExtensionGa
         vars: EvaluationVarsBase<F>,
         mut yield_constr: StridedConstraintConsumer<F>,
     ) {
-        let const_0 = vars.local_constants[0];
-
         for i in 0..self.num_ops {
-            let multiplicand_0 = vars.get_local_ext(Self::wires_ith_multiplicand_0(i));
-            let multiplicand_1 = vars.get_local_ext(Self::wires_ith_multiplicand_1(i));
-            let output = vars.get_local_ext(Self::wires_ith_output(i));
-            let computed_output = (multiplicand_0 * multiplicand_1).scalar_mul(const_0);
+            let multiplicand_1 = vars.get_local_ext(self.wires_ith_multiplicand_1(i));
+            let output = vars.get_local_ext(self.wires_ith_output(i));
+            let computed_output = if self.constant_multiplicand {
+                let multiplicand_0 = F::Extension::from_basefield_array(
+                    vars.local_constants.view(0..D).try_into().unwrap(),
+                );
+                multiplicand_0 * multiplicand_1
+            } else {
+                let multiplicand_0 = vars.get_local_ext(self.wires_ith_multiplicand_0(i));
+                (multiplicand_0 * multiplicand_1).scalar_mul(vars.local_constants[0])
+            };
 
             yield_constr.many((output - computed_output).to_basefield_array());
         }
@@ -103,16 +155,18 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for MulExtensionGa
         builder: &mut CircuitBuilder<F, D>,
         vars: EvaluationTargets<D>,
     ) -> Vec<ExtensionTarget<D>> {
-        let const_0 = vars.local_constants[0];
-
         let mut constraints = Vec::with_capacity(self.num_ops * D);
         for i in 0..self.num_ops {
-            let multiplicand_0 = vars.get_local_ext_algebra(Self::wires_ith_multiplicand_0(i));
-            let multiplicand_1 = vars.get_local_ext_algebra(Self::wires_ith_multiplicand_1(i));
-            let output = vars.get_local_ext_algebra(Self::wires_ith_output(i));
-            let computed_output = {
+            let multiplicand_1 = vars.get_local_ext_algebra(self.wires_ith_multiplicand_1(i));
+            let output = vars.get_local_ext_algebra(self.wires_ith_output(i));
+            let computed_output = if self.constant_multiplicand {
+                let multiplicand_0 =
+                    ExtensionAlgebraTarget(vars.local_constants[..D].try_into().unwrap());
+                builder.mul_ext_algebra(multiplicand_0, multiplicand_1)
+            } else {
+                let multiplicand_0 = vars.get_local_ext_algebra(self.wires_ith_multiplicand_0(i));
                 let mul = builder.mul_ext_algebra(multiplicand_0, multiplicand_1);
-                builder.scalar_mul_ext_algebra(const_0, mul)
+                builder.scalar_mul_ext_algebra(vars.local_constants[0], mul)
             };
 
             let diff = builder.sub_ext_algebra(output, computed_output);
@@ -128,7 +182,8 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for MulExtensionGa
                 WitnessGeneratorRef::new(
                     MulExtensionGenerator {
                         row,
-                        const_0: local_constants[0],
+                        gate: self.clone(),
+                        constants: local_constants[..Gate::<F, D>::num_constants(self)].to_vec(),
                         i,
                     }
                     .adapter(),
@@ -138,26 +193,48 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for MulExtensionGa
     }
 
     fn num_wires(&self) -> usize {
-        self.num_ops * 3 * D
+        self.num_ops * Self::wires_per_op(self.constant_multiplicand)
     }
 
     fn num_constants(&self) -> usize {
-        1
+        if self.constant_multiplicand {
+            D
+        } else {
+            1
+        }
     }
 
     fn degree(&self) -> usize {
-        3
+        if self.constant_multiplicand {
+            2
+        } else {
+            3
+        }
     }
 
     fn num_constraints(&self) -> usize {
         self.num_ops * D
     }
+
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        let multiplicand_0 = if self.constant_multiplicand {
+            0..0
+        } else {
+            self.wires_ith_multiplicand_0(op)
+        };
+        multiplicand_0
+            .chain(self.wires_ith_multiplicand_1(op))
+            .chain(self.wires_ith_output(op))
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct MulExtensionGenerator<F: RichField + Extendable<D>, const D: usize> {
     row: usize,
-    const_0: F,
+    gate: MulExtensionGate<D>,
+    /// The gate's constants: the weight `c0`, or the constant multiplicand.
+    constants: Vec<F>,
     i: usize,
 }
 
@@ -169,8 +246,13 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
     }
 
     fn dependencies(&self) -> Vec<Target> {
-        MulExtensionGate::<D>::wires_ith_multiplicand_0(self.i)
-            .chain(MulExtensionGate::<D>::wires_ith_multiplicand_1(self.i))
+        let multiplicand_0 = if self.gate.constant_multiplicand {
+            0..0
+        } else {
+            self.gate.wires_ith_multiplicand_0(self.i)
+        };
+        multiplicand_0
+            .chain(self.gate.wires_ith_multiplicand_1(self.i))
             .map(|i| Target::wire(self.row, i))
             .collect()
     }
@@ -181,30 +263,47 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
             witness.get_extension_target(t)
         };
 
-        let multiplicand_0 =
-            extract_extension(MulExtensionGate::<D>::wires_ith_multiplicand_0(self.i));
-        let multiplicand_1 =
-            extract_extension(MulExtensionGate::<D>::wires_ith_multiplicand_1(self.i));
+        let multiplicand_1 = extract_extension(self.gate.wires_ith_multiplicand_1(self.i));
 
         let output_target =
-            ExtensionTarget::from_range(self.row, MulExtensionGate::<D>::wires_ith_output(self.i));
-
-        let computed_output = (multiplicand_0 * multiplicand_1).scalar_mul(self.const_0);
+            ExtensionTarget::from_range(self.row, self.gate.wires_ith_output(self.i));
+
+        let computed_output = if self.gate.constant_multiplicand {
+            let multiplicand_0 =
+                F::Extension::from_basefield_array(self.constants[..D].try_into().unwrap());
+            multiplicand_0 * multiplicand_1
+        } else {
+            let multiplicand_0 = extract_extension(self.gate.wires_ith_multiplicand_0(self.i));
+            (multiplicand_0 * multiplicand_1).scalar_mul(self.constants[0])
+        };
 
-        out_buffer.set_extension_target(output_target, computed_output)
+        out_buffer.set_extension_target(output_target, computed_output);
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
         dst.write_usize(self.row)?;
-        dst.write_field(self.const_0)?;
+        dst.write_usize(self.gate.num_ops)?;
+        dst.write_bool(self.gate.constant_multiplicand)?;
+        dst.write_field_vec(&self.constants)?;
         dst.write_usize(self.i)
     }
 
     fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
         let row = src.read_usize()?;
-        let const_0 = src.read_field()?;
+        let num_ops = src.read_usize()?;
+        let constant_multiplicand = src.read_bool()?;
+        let gate = MulExtensionGate {
+            num_ops,
+            constant_multiplicand,
+        };
+        let constants = src.read_field_vec(if constant_multiplicand { D } else { 1 })?;
         let i = src.read_usize()?;
-        Ok(Self { row, const_0, i })
+        Ok(Self {
+            row,
+            gate,
+            constants,
+            i,
+        })
     }
 }
 
@@ -215,7 +314,9 @@ mod tests {
     use super::*;
     use crate::field::goldilocks_field::GoldilocksField;
     use crate::gates::gate_testing::{test_eval_fns, test_low_degree};
-    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::config::{
+        GenericConfig, PoseidonGoldilocksConfig, PoseidonGoldilocksQuinticConfig,
+    };
 
     #[test]
     fn low_degree() {
@@ -231,4 +332,43 @@ mod tests {
         let gate = MulExtensionGate::new_from_config(&CircuitConfig::standard_recursion_config());
         test_eval_fns::<F, C, _, D>(gate)
     }
+
+    #[test]
+    fn eval_fns_quintic() -> Result<()> {
+        const D: usize = 5;
+        type C = PoseidonGoldilocksQuinticConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate = MulExtensionGate::new_from_config(&CircuitConfig::standard_recursion_config());
+        test_low_degree::<F, _, D>(gate.clone());
+        test_eval_fns::<F, C, _, D>(gate)
+    }
+
+    #[test]
+    fn low_degree_constant_multiplicand() {
+        let gate = MulExtensionGate::new_constant_multiplicand_from_config(
+            &CircuitConfig::standard_recursion_config(),
+        );
+        test_low_degree::<GoldilocksField, _, 4>(gate);
+    }
+
+    #[test]
+    fn eval_fns_constant_multiplicand() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate = MulExtensionGate::new_constant_multiplicand_from_config(
+            &CircuitConfig::standard_recursion_config(),
+        );
+        test_eval_fns::<F, C, _, D>(gate)
+    }
+
+    #[test]
+    fn constant_multiplicand_packs_more_ops() {
+        let config = CircuitConfig::standard_recursion_config();
+        assert_eq!(MulExtensionGate::<2>::new_from_config(&config).num_ops, 13);
+        assert_eq!(
+            MulExtensionGate::<2>::new_constant_multiplicand_from_config(&config).num_ops,
+            20
+        );
+    }
 }
@@ -27,7 +27,7 @@ use crate::plonk::vars::{
     EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
     EvaluationVarsBasePacked,
 };
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// A gate for checking that a particular element of a list matches a given value.
 #[derive(Copy, Clone, Debug, Default)]
@@ -59,13 +59,19 @@ impl<F: RichField + Extendable<D>, const D: usize> RandomAccessGate<F, D> {
         let vec_size = 1 << bits;
 
         // We need `(2 + vec_size) * num_copies` routed wires.
-        let max_copies = (config.num_routed_wires / (2 + vec_size)).min(
-            // We need `(2 + vec_size + bits) * num_copies` wires in total.
-            config.num_wires / (2 + vec_size + bits),
-        );
+        let max_copies = (config.num_routed_wires / (2 + vec_size))
+            .min(
+                // We need `(2 + vec_size + bits) * num_copies` wires in total.
+                config.num_wires / (2 + vec_size + bits),
+            )
+            // If not even one copy fits, the builder rejects the gate with a description of the
+            // wires it lacks.
+            .max(1);
 
         // Any leftover wires can be used for constants.
-        let max_extra_constants = config.num_routed_wires - (2 + vec_size) * max_copies;
+        let max_extra_constants = config
+            .num_routed_wires
+            .saturating_sub((2 + vec_size) * max_copies);
 
         Self::new(
             max_copies,
@@ -133,10 +139,31 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for RandomAccessGa
         Ok(())
     }
 
-    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
         let bits = src.read_usize()?;
         let num_copies = src.read_usize()?;
         let num_extra_constants = src.read_usize()?;
+        let config = &common_data.config;
+        // Check that the gate fits in a row without overflowing while computing its wires.
+        let num_routed_wires = u32::try_from(bits)
+            .ok()
+            .and_then(|bits| 1usize.checked_shl(bits))
+            .and_then(|vec_size| vec_size.checked_add(2))
+            .and_then(|wires_per_copy| wires_per_copy.checked_mul(num_copies))
+            .and_then(|wires| wires.checked_add(num_extra_constants))
+            .filter(|&wires| wires <= config.num_routed_wires)
+            .ok_or(IoError)?;
+        let num_wires = bits
+            .checked_mul(num_copies)
+            .and_then(|bit_wires| bit_wires.checked_add(num_routed_wires))
+            .ok_or(IoError)?;
+        if bits == 0
+            || num_copies == 0
+            || num_wires > config.num_wires
+            || num_extra_constants > config.num_constants
+        {
+            return Err(IoError);
+        }
         Ok(Self::new(num_copies, bits, num_extra_constants))
     }
 
@@ -275,6 +302,10 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for RandomAccessGa
         self.wire_bit(self.bits - 1, self.num_copies - 1) + 1
     }
 
+    fn num_routed_wires(&self) -> usize {
+        RandomAccessGate::num_routed_wires(self)
+    }
+
     fn num_constants(&self) -> usize {
         self.num_extra_constants
     }
@@ -288,6 +319,19 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for RandomAccessGa
         self.num_copies * constraints_per_copy + self.num_extra_constants
     }
 
+    fn op_wires(&self, copy: usize) -> Vec<usize> {
+        let list = (0..self.vec_size()).map(|i| self.wire_list_item(i, copy));
+        let bits = (0..self.bits).map(|i| self.wire_bit(i, copy));
+        [
+            self.wire_access_index(copy),
+            self.wire_claimed_element(copy),
+        ]
+        .into_iter()
+        .chain(list)
+        .chain(bits)
+        .collect()
+    }
+
     fn extra_constant_wires(&self) -> Vec<(usize, usize)> {
         (0..self.num_extra_constants)
             .map(|i| (i, self.wire_extra_constant(i)))
@@ -509,6 +553,7 @@ mod tests {
                 &constants,
             ),
             public_inputs_hash: &HashOut::rand(),
+            extra_openings: &[],
         };
         let bad_claimed_elements = F::rand_vec(4);
         let bad_vars = EvaluationVars {
@@ -521,6 +566,7 @@ mod tests {
                 &constants,
             ),
             public_inputs_hash: &HashOut::rand(),
+            extra_openings: &[],
         };
 
         assert!(
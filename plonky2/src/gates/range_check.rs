@@ -0,0 +1,327 @@
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::ops::Range;
+
+use anyhow::Result;
+
+use crate::field::extension::Extendable;
+use crate::field::packed::PackedField;
+use crate::field::types::Field;
+use crate::gates::gate::Gate;
+use crate::gates::packed_util::PackedEvaluableBase;
+use crate::gates::util::StridedConstraintConsumer;
+use crate::hash::hash_types::RichField;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::generator::{
+    GeneratedValues, GenerationError, SimpleGenerator, WitnessGeneratorRef,
+};
+use crate::iop::target::Target;
+use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
+use crate::plonk::plonk_common::{reduce_with_powers, reduce_with_powers_ext_circuit};
+use crate::plonk::vars::{
+    EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
+    EvaluationVarsBasePacked,
+};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
+
+/// A gate which checks that each of its inputs fits in `num_bits` bits, by decomposing it into
+/// binary limbs. Unlike `BaseSumGate`, only the inputs are routed, so a row can check several
+/// inputs.
+#[derive(Copy, Clone, Debug)]
+pub struct RangeCheckGate {
+    pub num_bits: usize,
+    pub num_ops: usize,
+}
+
+impl RangeCheckGate {
+    /// The largest number of bits this gate can check, so that limbs always sum to a canonical
+    /// field element.
+    pub const MAX_BITS: usize = 63;
+
+    pub fn new_from_config(config: &CircuitConfig, num_bits: usize) -> Self {
+        assert!(
+            (1..=Self::MAX_BITS).contains(&num_bits),
+            "Can't range check {num_bits} bits with a RangeCheckGate"
+        );
+        Self {
+            num_bits,
+            num_ops: Self::num_ops(config, num_bits),
+        }
+    }
+
+    /// Determine the maximum number of operations that can fit in one gate for the given config.
+    pub(crate) fn num_ops(config: &CircuitConfig, num_bits: usize) -> usize {
+        let wires_per_op = 1 + num_bits;
+        (config.num_wires / wires_per_op).min(config.num_routed_wires)
+    }
+
+    /// The inputs come first, so that they are routed.
+    pub(crate) const fn wire_ith_input(i: usize) -> usize {
+        i
+    }
+
+    /// Returns the index of the limb wires of the `i`th input.
+    pub(crate) const fn wires_ith_limbs(&self, i: usize) -> Range<usize> {
+        let start = self.num_ops + i * self.num_bits;
+        start..start + self.num_bits
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for RangeCheckGate {
+    fn id(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.num_bits)?;
+        dst.write_usize(self.num_ops)
+    }
+
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let num_bits = src.read_usize()?;
+        let num_ops = src.read_usize()?;
+        if !(1..=Self::MAX_BITS).contains(&num_bits)
+            || num_ops > Self::num_ops(&common_data.config, num_bits)
+        {
+            return Err(IoError);
+        }
+        Ok(Self { num_bits, num_ops })
+    }
+
+    fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+        let mut constraints = Vec::with_capacity(self.num_ops * (1 + self.num_bits));
+        for i in 0..self.num_ops {
+            let input = vars.local_wires[Self::wire_ith_input(i)];
+            let limbs = &vars.local_wires[self.wires_ith_limbs(i)];
+            let computed_input = reduce_with_powers(limbs, F::Extension::TWO);
+            constraints.push(computed_input - input);
+            constraints.extend(limbs.iter().map(|&limb| limb * (limb - F::Extension::ONE)));
+        }
+        constraints
+    }
+
+    fn eval_unfiltered_base_one(
+        &self,
+        _vars: EvaluationVarsBase<F>,
+        _yield_constr: StridedConstraintConsumer<F>,
+    ) {
+        panic!("use eval_unfiltered_base_packed instead");
+    }
+
+    fn eval_unfiltered_base_batch(&self, vars_base: EvaluationVarsBaseBatch<F>) -> Vec<F> {
+        self.eval_unfiltered_base_batch_packed(vars_base)
+    }
+
+    fn eval_unfiltered_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: EvaluationTargets<D>,
+    ) -> Vec<ExtensionTarget<D>> {
+        let two = builder.two();
+        let mut constraints = Vec::with_capacity(self.num_ops * (1 + self.num_bits));
+        for i in 0..self.num_ops {
+            let input = vars.local_wires[Self::wire_ith_input(i)];
+            let limbs = &vars.local_wires[self.wires_ith_limbs(i)];
+            let computed_input = reduce_with_powers_ext_circuit(builder, limbs, two);
+            constraints.push(builder.sub_extension(computed_input, input));
+            for &limb in limbs {
+                // limb * (limb - 1) = limb^2 - limb, in one arithmetic_extension call.
+                constraints.push(builder.arithmetic_extension(
+                    F::ONE,
+                    F::NEG_ONE,
+                    limb,
+                    limb,
+                    limb,
+                ));
+            }
+        }
+        constraints
+    }
+
+    fn generators(&self, row: usize, _local_constants: &[F]) -> Vec<WitnessGeneratorRef<F, D>> {
+        (0..self.num_ops)
+            .map(|i| {
+                WitnessGeneratorRef::new(
+                    RangeCheckGenerator {
+                        row,
+                        gate: *self,
+                        i,
+                    }
+                    .adapter(),
+                )
+            })
+            .collect()
+    }
+
+    // 1 for the input then `num_bits` for the limbs, for each operation.
+    fn num_wires(&self) -> usize {
+        self.num_ops * (1 + self.num_bits)
+    }
+
+    // Only the inputs are routed.
+    fn num_routed_wires(&self) -> usize {
+        self.num_ops
+    }
+
+    fn num_constants(&self) -> usize {
+        0
+    }
+
+    // Bounded by the limbs' range-check limb * (limb - 1).
+    fn degree(&self) -> usize {
+        2
+    }
+
+    // 1 for checking the sum then `num_bits` for range-checking the limbs, for each operation.
+    fn num_constraints(&self) -> usize {
+        self.num_ops * (1 + self.num_bits)
+    }
+
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        core::iter::once(Self::wire_ith_input(op))
+            .chain(self.wires_ith_limbs(op))
+            .collect()
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> PackedEvaluableBase<F, D> for RangeCheckGate {
+    fn eval_unfiltered_base_packed<P: PackedField<Scalar = F>>(
+        &self,
+        vars: EvaluationVarsBasePacked<P>,
+        mut yield_constr: StridedConstraintConsumer<P>,
+    ) {
+        for i in 0..self.num_ops {
+            let input = vars.local_wires[Self::wire_ith_input(i)];
+            let limbs = vars.local_wires.view(self.wires_ith_limbs(i));
+            let computed_input = reduce_with_powers(limbs, F::TWO);
+            yield_constr.one(computed_input - input);
+            yield_constr.many(limbs.iter().map(|&limb| limb * (limb - F::ONE)));
+        }
+    }
+}
+
+/// Decomposes the `i`th input of a `RangeCheckGate` into bits.
+#[derive(Debug)]
+pub struct RangeCheckGenerator {
+    row: usize,
+    gate: RangeCheckGate,
+    i: usize,
+}
+
+impl Default for RangeCheckGenerator {
+    fn default() -> Self {
+        Self {
+            row: 0,
+            gate: RangeCheckGate {
+                num_bits: 1,
+                num_ops: 1,
+            },
+            i: 0,
+        }
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for RangeCheckGenerator {
+    fn id(&self) -> String {
+        "RangeCheckGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        vec![Target::wire(
+            self.row,
+            RangeCheckGate::wire_ith_input(self.i),
+        )]
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        if let Err(err) = self.try_run_once(witness, out_buffer) {
+            panic!("{}", err);
+        }
+    }
+
+    fn try_run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
+        let input = Target::wire(self.row, RangeCheckGate::wire_ith_input(self.i));
+        let input_value = witness.get_target(input);
+        let value = input_value.to_canonical_u64();
+        if value >> self.gate.num_bits != 0 {
+            return Err(anyhow::Error::msg(GenerationError {
+                reason: format!(
+                    "value range-checked to {} bits doesn't fit",
+                    self.gate.num_bits
+                ),
+                observed: vec![(input, input_value)],
+            }));
+        }
+
+        for (j, limb) in self.gate.wires_ith_limbs(self.i).enumerate() {
+            out_buffer.set_target(
+                Target::wire(self.row, limb),
+                F::from_bool((value >> j) & 1 == 1),
+            );
+        }
+        Ok(())
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.row)?;
+        dst.write_usize(self.gate.num_bits)?;
+        dst.write_usize(self.gate.num_ops)?;
+        dst.write_usize(self.i)
+    }
+
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let row = src.read_usize()?;
+        let num_bits = src.read_usize()?;
+        let num_ops = src.read_usize()?;
+        let i = src.read_usize()?;
+        // `run_once` shifts by `num_bits` and indexes limbs with `i`, so both must be in range.
+        if !(1..=RangeCheckGate::MAX_BITS).contains(&num_bits)
+            || num_ops > RangeCheckGate::num_ops(&common_data.config, num_bits)
+            || i >= num_ops
+        {
+            return Err(IoError);
+        }
+        Ok(Self {
+            row,
+            gate: RangeCheckGate { num_bits, num_ops },
+            i,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::field::goldilocks_field::GoldilocksField;
+    use crate::gates::gate_testing::{test_eval_fns, test_low_degree};
+    use crate::gates::range_check::RangeCheckGate;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    #[test]
+    fn low_degree() {
+        let gate = RangeCheckGate::new_from_config(&CircuitConfig::standard_recursion_config(), 7);
+        test_low_degree::<GoldilocksField, _, 4>(gate);
+    }
+
+    #[test]
+    fn eval_fns() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate = RangeCheckGate::new_from_config(&CircuitConfig::standard_recursion_config(), 7);
+        test_eval_fns::<F, C, _, D>(gate)
+    }
+}
@@ -18,7 +18,7 @@ use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
 use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase};
-use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 /// Computes `sum alpha^i c_i` for a vector `c_i` of `num_coeffs` elements of the base field.
 #[derive(Debug, Default, Clone)]
@@ -70,11 +70,18 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ReducingGate<D
         Ok(())
     }
 
-    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self>
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self>
     where
         Self: Sized,
     {
         let num_coeffs = src.read_usize()?;
+        let config = &common_data.config;
+        if config.num_wires < 2 * D
+            || config.num_routed_wires < 3 * D
+            || num_coeffs > Self::max_coeffs_len(config.num_wires, config.num_routed_wires)
+        {
+            return Err(IoError);
+        }
         Ok(Self::new(num_coeffs))
     }
 
@@ -169,6 +176,11 @@ impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ReducingGate<D
         2 * D + self.num_coeffs * (D + 1)
     }
 
+    fn num_routed_wires(&self) -> usize {
+        // The accumulators, except the last which is the output, aren't routed.
+        self.start_accs()
+    }
+
     fn num_constants(&self) -> usize {
         0
     }
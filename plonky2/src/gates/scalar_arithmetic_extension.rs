@@ -0,0 +1,313 @@
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::ops::Range;
+
+use crate::field::extension::{Extendable, FieldExtension};
+use crate::gates::gate::Gate;
+use crate::gates::util::StridedConstraintConsumer;
+use crate::hash::hash_types::RichField;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::generator::{GeneratedValues, SimpleGenerator, WitnessGeneratorRef};
+use crate::iop::target::Target;
+use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
+use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
+
+/// A gate which can perform a weighted multiply-add of an extension element by a base field
+/// scalar, i.e. `result = c0.s.x + c1.z` with `s` in the base field. The scalar takes a single
+/// wire, rather than `D` for an extension operand of an `ArithmeticExtensionGate`, so that more
+/// operations fit in a row: 11 rather than 10 with the standard recursion config.
+#[derive(Debug, Clone)]
+pub struct ScalarArithmeticExtensionGate<const D: usize> {
+    /// Number of arithmetic operations performed by the gate.
+    pub num_ops: usize,
+}
+
+impl<const D: usize> ScalarArithmeticExtensionGate<D> {
+    pub const fn new_from_config(config: &CircuitConfig) -> Self {
+        Self {
+            num_ops: Self::num_ops(config),
+        }
+    }
+
+    /// Determine the maximum number of operations that can fit in one gate for the given config.
+    pub(crate) const fn num_ops(config: &CircuitConfig) -> usize {
+        config.num_routed_wires / Self::wires_per_op()
+    }
+
+    const fn wires_per_op() -> usize {
+        1 + 3 * D
+    }
+
+    pub(crate) const fn wire_ith_scalar(i: usize) -> usize {
+        Self::wires_per_op() * i
+    }
+    pub(crate) const fn wires_ith_multiplicand(i: usize) -> Range<usize> {
+        let start = Self::wires_per_op() * i + 1;
+        start..start + D
+    }
+    pub(crate) const fn wires_ith_addend(i: usize) -> Range<usize> {
+        let start = Self::wires_per_op() * i + 1 + D;
+        start..start + D
+    }
+    pub(crate) const fn wires_ith_output(i: usize) -> Range<usize> {
+        let start = Self::wires_per_op() * i + 1 + 2 * D;
+        start..start + D
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for ScalarArithmeticExtensionGate<D> {
+    fn id(&self) -> String {
+        format!("{self:?}")
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.num_ops)
+    }
+
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let num_ops = src.read_usize()?;
+        if num_ops > Self::num_ops(&common_data.config) {
+            return Err(IoError);
+        }
+        Ok(Self { num_ops })
+    }
+
+    fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+        let const_0 = vars.local_constants[0];
+        let const_1 = vars.local_constants[1];
+
+        let mut constraints = Vec::with_capacity(self.num_ops * D);
+        for i in 0..self.num_ops {
+            let scalar = vars.local_wires[Self::wire_ith_scalar(i)];
+            let multiplicand = vars.get_local_ext_algebra(Self::wires_ith_multiplicand(i));
+            let addend = vars.get_local_ext_algebra(Self::wires_ith_addend(i));
+            let output = vars.get_local_ext_algebra(Self::wires_ith_output(i));
+            let computed_output =
+                multiplicand.scalar_mul(const_0 * scalar) + addend.scalar_mul(const_1);
+
+            constraints.extend((output - computed_output).to_basefield_array());
+        }
+
+        constraints
+    }
+
+    fn eval_unfiltered_base_one(
+        &self,
+        vars: EvaluationVarsBase<F>,
+        mut yield_constr: StridedConstraintConsumer<F>,
+    ) {
+        let const_0 = vars.local_constants[0];
+        let const_1 = vars.local_constants[1];
+
+        for i in 0..self.num_ops {
+            let scalar = vars.local_wires[Self::wire_ith_scalar(i)];
+            let multiplicand = vars.get_local_ext(Self::wires_ith_multiplicand(i));
+            let addend = vars.get_local_ext(Self::wires_ith_addend(i));
+            let output = vars.get_local_ext(Self::wires_ith_output(i));
+            let computed_output =
+                multiplicand.scalar_mul(const_0 * scalar) + addend.scalar_mul(const_1);
+
+            yield_constr.many((output - computed_output).to_basefield_array());
+        }
+    }
+
+    fn eval_unfiltered_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: EvaluationTargets<D>,
+    ) -> Vec<ExtensionTarget<D>> {
+        let const_0 = vars.local_constants[0];
+        let const_1 = vars.local_constants[1];
+
+        let mut constraints = Vec::with_capacity(self.num_ops * D);
+        for i in 0..self.num_ops {
+            let scalar = vars.local_wires[Self::wire_ith_scalar(i)];
+            let multiplicand = vars.get_local_ext_algebra(Self::wires_ith_multiplicand(i));
+            let addend = vars.get_local_ext_algebra(Self::wires_ith_addend(i));
+            let output = vars.get_local_ext_algebra(Self::wires_ith_output(i));
+            let computed_output = {
+                let scaled_scalar = builder.mul_extension(const_0, scalar);
+                let mul = builder.scalar_mul_ext_algebra(scaled_scalar, multiplicand);
+                builder.scalar_mul_add_ext_algebra(const_1, addend, mul)
+            };
+
+            let diff = builder.sub_ext_algebra(output, computed_output);
+            constraints.extend(diff.to_ext_target_array());
+        }
+
+        constraints
+    }
+
+    fn generators(&self, row: usize, local_constants: &[F]) -> Vec<WitnessGeneratorRef<F, D>> {
+        (0..self.num_ops)
+            .map(|i| {
+                WitnessGeneratorRef::new(
+                    ScalarArithmeticExtensionGenerator {
+                        row,
+                        const_0: local_constants[0],
+                        const_1: local_constants[1],
+                        i,
+                    }
+                    .adapter(),
+                )
+            })
+            .collect()
+    }
+
+    fn num_wires(&self) -> usize {
+        self.num_ops * Self::wires_per_op()
+    }
+
+    fn num_constants(&self) -> usize {
+        2
+    }
+
+    fn degree(&self) -> usize {
+        3
+    }
+
+    fn num_constraints(&self) -> usize {
+        self.num_ops * D
+    }
+
+    fn op_wires(&self, op: usize) -> Vec<usize> {
+        core::iter::once(Self::wire_ith_scalar(op))
+            .chain(Self::wires_ith_multiplicand(op))
+            .chain(Self::wires_ith_addend(op))
+            .chain(Self::wires_ith_output(op))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ScalarArithmeticExtensionGenerator<F: RichField + Extendable<D>, const D: usize> {
+    row: usize,
+    const_0: F,
+    const_1: F,
+    i: usize,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D>
+    for ScalarArithmeticExtensionGenerator<F, D>
+{
+    fn id(&self) -> String {
+        "ScalarArithmeticExtensionGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        let scalar = ScalarArithmeticExtensionGate::<D>::wire_ith_scalar(self.i);
+        core::iter::once(scalar)
+            .chain(ScalarArithmeticExtensionGate::<D>::wires_ith_multiplicand(
+                self.i,
+            ))
+            .chain(ScalarArithmeticExtensionGate::<D>::wires_ith_addend(self.i))
+            .map(|i| Target::wire(self.row, i))
+            .collect()
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let extract_extension = |range: Range<usize>| -> F::Extension {
+            let t = ExtensionTarget::from_range(self.row, range);
+            witness.get_extension_target(t)
+        };
+
+        let scalar = witness.get_target(Target::wire(
+            self.row,
+            ScalarArithmeticExtensionGate::<D>::wire_ith_scalar(self.i),
+        ));
+        let multiplicand = extract_extension(
+            ScalarArithmeticExtensionGate::<D>::wires_ith_multiplicand(self.i),
+        );
+        let addend =
+            extract_extension(ScalarArithmeticExtensionGate::<D>::wires_ith_addend(self.i));
+
+        let output_target = ExtensionTarget::from_range(
+            self.row,
+            ScalarArithmeticExtensionGate::<D>::wires_ith_output(self.i),
+        );
+
+        let computed_output =
+            multiplicand.scalar_mul(self.const_0 * scalar) + addend.scalar_mul(self.const_1);
+
+        out_buffer.set_extension_target(output_target, computed_output);
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.row)?;
+        dst.write_field(self.const_0)?;
+        dst.write_field(self.const_1)?;
+        dst.write_usize(self.i)
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let row = src.read_usize()?;
+        let const_0 = src.read_field()?;
+        let const_1 = src.read_field()?;
+        let i = src.read_usize()?;
+        Ok(Self {
+            row,
+            const_0,
+            const_1,
+            i,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use crate::field::goldilocks_field::GoldilocksField;
+    use crate::gates::arithmetic_extension::ArithmeticExtensionGate;
+    use crate::gates::gate_testing::{test_eval_fns, test_low_degree};
+    use crate::gates::scalar_arithmetic_extension::ScalarArithmeticExtensionGate;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{
+        GenericConfig, PoseidonGoldilocksConfig, PoseidonGoldilocksQuinticConfig,
+    };
+
+    #[test]
+    fn packs_more_ops_than_arithmetic_extension_gate() {
+        let config = CircuitConfig::standard_recursion_config();
+        assert_eq!(ScalarArithmeticExtensionGate::<2>::num_ops(&config), 11);
+        assert_eq!(ArithmeticExtensionGate::<2>::num_ops(&config), 10);
+    }
+
+    #[test]
+    fn low_degree() {
+        let gate = ScalarArithmeticExtensionGate::new_from_config(
+            &CircuitConfig::standard_recursion_config(),
+        );
+        test_low_degree::<GoldilocksField, _, 4>(gate);
+    }
+
+    #[test]
+    fn eval_fns() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate = ScalarArithmeticExtensionGate::new_from_config(
+            &CircuitConfig::standard_recursion_config(),
+        );
+        test_eval_fns::<F, C, _, D>(gate)
+    }
+
+    #[test]
+    fn eval_fns_quintic() -> Result<()> {
+        const D: usize = 5;
+        type C = PoseidonGoldilocksQuinticConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let gate = ScalarArithmeticExtensionGate::new_from_config(
+            &CircuitConfig::standard_recursion_config(),
+        );
+        test_low_degree::<F, _, D>(gate.clone());
+        test_eval_fns::<F, C, _, D>(gate)
+    }
+}
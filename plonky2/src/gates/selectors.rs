@@ -96,6 +96,60 @@ pub(crate) fn selector_ends_lookups<F: RichField + Extendable<D>, const D: usize
     lookups_ends
 }
 
+/// Returns the smallest quotient degree factor for which `selector_groups` can group `gates`, i.e.
+/// with selector polynomials of degree up to the factor plus one.
+pub(crate) fn min_quotient_degree_factor<F: RichField + Extendable<D>, const D: usize>(
+    gates: &[GateRef<F, D>],
+) -> usize {
+    let max_gate_degree = gates.iter().map(|g| g.0.degree()).max().expect("No gates?");
+    // A single gate needs no selector, otherwise the highest degree gate needs one of degree one.
+    let min_factor = if gates.len() == 1 {
+        max_gate_degree - 1
+    } else {
+        max_gate_degree
+    };
+    // The permutation argument needs chunks of at least two terms.
+    min_factor.max(2)
+}
+
+/// Groups the gates, sorted by degree, into ranges which can share a selector polynomial. Panics if
+/// a gate's degree is too high to fit in `max_degree` with any selector.
+pub(crate) fn selector_groups<F: RichField + Extendable<D>, const D: usize>(
+    gates: &[GateRef<F, D>],
+    max_degree: usize,
+) -> Vec<Range<usize>> {
+    let num_gates = gates.len();
+    let max_gate_degree = gates.last().expect("No gates?").0.degree();
+
+    // Special case if we can use only one selector polynomial.
+    if max_gate_degree + num_gates - 1 <= max_degree {
+        // We *want* `groups` to be a vector containing one Range (all gates are in one selector group),
+        // but Clippy doesn't trust us.
+        #[allow(clippy::single_range_in_vec_init)]
+        return vec![0..num_gates];
+    }
+
+    if max_gate_degree >= max_degree {
+        panic!(
+            "{} has too high degree. Consider increasing `quotient_degree_factor`.",
+            gates.last().unwrap().0.id()
+        );
+    }
+
+    // Greedily construct the groups.
+    let mut groups = Vec::new();
+    let mut start = 0;
+    while start < num_gates {
+        let mut size = 0;
+        while (start + size < gates.len()) && (size + gates[start + size].0.degree() < max_degree) {
+            size += 1;
+        }
+        groups.push(start..start + size);
+        start += size;
+    }
+    groups
+}
+
 /// Returns the selector polynomials and related information.
 ///
 /// Selector polynomials are computed as follows:
@@ -115,15 +169,12 @@ pub(crate) fn selector_polynomials<F: RichField + Extendable<D>, const D: usize>
 ) -> (Vec<PolynomialValues<F>>, SelectorsInfo) {
     let n = instances.len();
     let num_gates = gates.len();
-    let max_gate_degree = gates.last().expect("No gates?").0.degree();
 
     let index = |id| gates.iter().position(|g| g.0.id() == id).unwrap();
 
+    let groups = selector_groups(gates, max_degree);
     // Special case if we can use only one selector polynomial.
-    if max_gate_degree + num_gates - 1 <= max_degree {
-        // We *want* `groups` to be a vector containing one Range (all gates are in one selector group),
-        // but Clippy doesn't trust us.
-        #[allow(clippy::single_range_in_vec_init)]
+    if groups.len() == 1 {
         return (
             vec![PolynomialValues::new(
                 instances
@@ -133,30 +184,11 @@ pub(crate) fn selector_polynomials<F: RichField + Extendable<D>, const D: usize>
             )],
             SelectorsInfo {
                 selector_indices: vec![0; num_gates],
-                groups: vec![0..num_gates],
+                groups,
             },
         );
     }
 
-    if max_gate_degree >= max_degree {
-        panic!(
-            "{} has too high degree. Consider increasing `quotient_degree_factor`.",
-            gates.last().unwrap().0.id()
-        );
-    }
-
-    // Greedily construct the groups.
-    let mut groups = Vec::new();
-    let mut start = 0;
-    while start < num_gates {
-        let mut size = 0;
-        while (start + size < gates.len()) && (size + gates[start + size].0.degree() < max_degree) {
-            size += 1;
-        }
-        groups.push(start..start + size);
-        start += size;
-    }
-
     let group = |i| groups.iter().position(|range| range.contains(&i)).unwrap();
 
     // `selector_indices[i] = j` iff the `i`-th gate uses the `j`-th selector polynomial.
@@ -0,0 +1,417 @@
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+use crate::field::extension::Extendable;
+use crate::field::packed::PackedField;
+use crate::field::types::Field;
+use crate::gates::gate::Gate;
+use crate::gates::packed_util::PackedEvaluableBase;
+use crate::gates::util::StridedConstraintConsumer;
+use crate::hash::hash_types::RichField;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::generator::{GeneratedValues, SimpleGenerator, WitnessGeneratorRef};
+use crate::iop::target::Target;
+use crate::iop::wire::Wire;
+use crate::iop::witness::{PartitionWitness, Witness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
+use crate::plonk::vars::{
+    EvaluationTargets, EvaluationVars, EvaluationVarsBase, EvaluationVarsBaseBatch,
+    EvaluationVarsBasePacked,
+};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
+
+/// A gate for conditionally swapping two chunks of values, based on a switch bit. Each copy outputs
+/// `(first_input, second_input)` if its switch is zero, and `(second_input, first_input)` if it is
+/// one.
+///
+/// The switch isn't checked to be boolean: if it is neither zero nor one, the constraints force
+/// both inputs and both outputs to be equal, which is still a valid output.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SwitchGate<F: RichField + Extendable<D>, const D: usize> {
+    /// The number of values in each input and output chunk.
+    pub chunk_size: usize,
+
+    /// How many separate copies are packed into one gate.
+    pub num_copies: usize,
+
+    _phantom: PhantomData<F>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SwitchGate<F, D> {
+    pub const fn new(num_copies: usize, chunk_size: usize) -> Self {
+        Self {
+            chunk_size,
+            num_copies,
+            _phantom: PhantomData,
+        }
+    }
+
+    pub fn new_from_config(config: &CircuitConfig, chunk_size: usize) -> Self {
+        // If not even one copy fits, the builder rejects the gate with a description of the wires
+        // it lacks.
+        let num_copies = (config.num_routed_wires / Self::wires_per_copy(chunk_size)).max(1);
+        Self::new(num_copies, chunk_size)
+    }
+
+    const fn wires_per_copy(chunk_size: usize) -> usize {
+        4 * chunk_size + 1
+    }
+
+    const fn copy_start(&self, copy: usize) -> usize {
+        debug_assert!(copy < self.num_copies);
+        copy * Self::wires_per_copy(self.chunk_size)
+    }
+
+    pub const fn wire_first_input(&self, copy: usize, element: usize) -> usize {
+        debug_assert!(element < self.chunk_size);
+        self.copy_start(copy) + element
+    }
+
+    pub const fn wire_second_input(&self, copy: usize, element: usize) -> usize {
+        debug_assert!(element < self.chunk_size);
+        self.copy_start(copy) + self.chunk_size + element
+    }
+
+    pub const fn wire_first_output(&self, copy: usize, element: usize) -> usize {
+        debug_assert!(element < self.chunk_size);
+        self.copy_start(copy) + 2 * self.chunk_size + element
+    }
+
+    pub const fn wire_second_output(&self, copy: usize, element: usize) -> usize {
+        debug_assert!(element < self.chunk_size);
+        self.copy_start(copy) + 3 * self.chunk_size + element
+    }
+
+    pub const fn wire_switch_bool(&self, copy: usize) -> usize {
+        self.copy_start(copy) + 4 * self.chunk_size
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for SwitchGate<F, D> {
+    fn id(&self) -> String {
+        format!("{self:?}<D={D}>")
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.chunk_size)?;
+        dst.write_usize(self.num_copies)?;
+        Ok(())
+    }
+
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let chunk_size = src.read_usize()?;
+        let num_copies = src.read_usize()?;
+        // Check that the gate fits in a row without overflowing while computing its wires.
+        chunk_size
+            .checked_mul(4)
+            .and_then(|wires| wires.checked_add(1))
+            .and_then(|wires_per_copy| wires_per_copy.checked_mul(num_copies))
+            .filter(|&wires| wires <= common_data.config.num_routed_wires)
+            .ok_or(IoError)?;
+        if chunk_size == 0 || num_copies == 0 {
+            return Err(IoError);
+        }
+        Ok(Self::new(num_copies, chunk_size))
+    }
+
+    fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+        let mut constraints = Vec::with_capacity(self.num_constraints());
+
+        for c in 0..self.num_copies {
+            let switch_bool = vars.local_wires[self.wire_switch_bool(c)];
+            let not_switch = F::Extension::ONE - switch_bool;
+
+            for e in 0..self.chunk_size {
+                let first_input = vars.local_wires[self.wire_first_input(c, e)];
+                let second_input = vars.local_wires[self.wire_second_input(c, e)];
+                let first_output = vars.local_wires[self.wire_first_output(c, e)];
+                let second_output = vars.local_wires[self.wire_second_output(c, e)];
+
+                constraints.push(not_switch * (first_input - first_output));
+                constraints.push(not_switch * (second_input - second_output));
+                constraints.push(switch_bool * (first_input - second_output));
+                constraints.push(switch_bool * (second_input - first_output));
+            }
+        }
+
+        constraints
+    }
+
+    fn eval_unfiltered_base_one(
+        &self,
+        _vars: EvaluationVarsBase<F>,
+        _yield_constr: StridedConstraintConsumer<F>,
+    ) {
+        panic!("use eval_unfiltered_base_packed instead");
+    }
+
+    fn eval_unfiltered_base_batch(&self, vars_base: EvaluationVarsBaseBatch<F>) -> Vec<F> {
+        self.eval_unfiltered_base_batch_packed(vars_base)
+    }
+
+    fn eval_unfiltered_circuit(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        vars: EvaluationTargets<D>,
+    ) -> Vec<ExtensionTarget<D>> {
+        let mut constraints = Vec::with_capacity(self.num_constraints());
+        let one = builder.one_extension();
+
+        for c in 0..self.num_copies {
+            let switch_bool = vars.local_wires[self.wire_switch_bool(c)];
+            let not_switch = builder.sub_extension(one, switch_bool);
+
+            for e in 0..self.chunk_size {
+                let first_input = vars.local_wires[self.wire_first_input(c, e)];
+                let second_input = vars.local_wires[self.wire_second_input(c, e)];
+                let first_output = vars.local_wires[self.wire_first_output(c, e)];
+                let second_output = vars.local_wires[self.wire_second_output(c, e)];
+
+                let first_not_switched = builder.sub_extension(first_input, first_output);
+                constraints.push(builder.mul_extension(not_switch, first_not_switched));
+                let second_not_switched = builder.sub_extension(second_input, second_output);
+                constraints.push(builder.mul_extension(not_switch, second_not_switched));
+                let first_switched = builder.sub_extension(first_input, second_output);
+                constraints.push(builder.mul_extension(switch_bool, first_switched));
+                let second_switched = builder.sub_extension(second_input, first_output);
+                constraints.push(builder.mul_extension(switch_bool, second_switched));
+            }
+        }
+
+        constraints
+    }
+
+    fn generators(&self, row: usize, _local_constants: &[F]) -> Vec<WitnessGeneratorRef<F, D>> {
+        (0..self.num_copies)
+            .map(|copy| {
+                WitnessGeneratorRef::new(
+                    SwitchGenerator {
+                        row,
+                        gate: *self,
+                        copy,
+                    }
+                    .adapter(),
+                )
+            })
+            .collect()
+    }
+
+    fn num_wires(&self) -> usize {
+        self.wire_switch_bool(self.num_copies - 1) + 1
+    }
+
+    fn num_constants(&self) -> usize {
+        0
+    }
+
+    fn degree(&self) -> usize {
+        2
+    }
+
+    fn num_constraints(&self) -> usize {
+        4 * self.num_copies * self.chunk_size
+    }
+
+    fn op_wires(&self, copy: usize) -> Vec<usize> {
+        // Each copy takes a contiguous range of wires.
+        let start = self.copy_start(copy);
+        (start..start + Self::wires_per_copy(self.chunk_size)).collect()
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> PackedEvaluableBase<F, D> for SwitchGate<F, D> {
+    fn eval_unfiltered_base_packed<P: PackedField<Scalar = F>>(
+        &self,
+        vars: EvaluationVarsBasePacked<P>,
+        mut yield_constr: StridedConstraintConsumer<P>,
+    ) {
+        for c in 0..self.num_copies {
+            let switch_bool = vars.local_wires[self.wire_switch_bool(c)];
+            let not_switch = P::ONES - switch_bool;
+
+            for e in 0..self.chunk_size {
+                let first_input = vars.local_wires[self.wire_first_input(c, e)];
+                let second_input = vars.local_wires[self.wire_second_input(c, e)];
+                let first_output = vars.local_wires[self.wire_first_output(c, e)];
+                let second_output = vars.local_wires[self.wire_second_output(c, e)];
+
+                yield_constr.one(not_switch * (first_input - first_output));
+                yield_constr.one(not_switch * (second_input - second_output));
+                yield_constr.one(switch_bool * (first_input - second_output));
+                yield_constr.one(switch_bool * (second_input - first_output));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SwitchGenerator<F: RichField + Extendable<D>, const D: usize> {
+    row: usize,
+    gate: SwitchGate<F, D>,
+    copy: usize,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for SwitchGenerator<F, D> {
+    fn id(&self) -> String {
+        "SwitchGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        let local_target = |column| Target::wire(self.row, column);
+
+        let mut deps = vec![local_target(self.gate.wire_switch_bool(self.copy))];
+        for e in 0..self.gate.chunk_size {
+            deps.push(local_target(self.gate.wire_first_input(self.copy, e)));
+            deps.push(local_target(self.gate.wire_second_input(self.copy, e)));
+        }
+        deps
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let local_wire = |column| Wire {
+            row: self.row,
+            column,
+        };
+
+        let get_local_wire = |column| witness.get_wire(local_wire(column));
+        let mut set_local_wire = |column, value| out_buffer.set_wire(local_wire(column), value);
+
+        let copy = self.copy;
+        let switch_value = get_local_wire(self.gate.wire_switch_bool(copy));
+        debug_assert!(
+            switch_value == F::ZERO || switch_value == F::ONE,
+            "Switch value {} is not boolean",
+            switch_value
+        );
+
+        for e in 0..self.gate.chunk_size {
+            let first_input = get_local_wire(self.gate.wire_first_input(copy, e));
+            let second_input = get_local_wire(self.gate.wire_second_input(copy, e));
+            let (first_output, second_output) = if switch_value == F::ONE {
+                (second_input, first_input)
+            } else {
+                (first_input, second_input)
+            };
+            set_local_wire(self.gate.wire_first_output(copy, e), first_output);
+            set_local_wire(self.gate.wire_second_output(copy, e), second_output);
+        }
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.row)?;
+        dst.write_usize(self.copy)?;
+        self.gate.serialize(dst, common_data)
+    }
+
+    fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let row = src.read_usize()?;
+        let copy = src.read_usize()?;
+        let gate = SwitchGate::<F, D>::deserialize(src, common_data)?;
+        if copy >= gate.num_copies {
+            return Err(IoError);
+        }
+        Ok(Self { row, gate, copy })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::goldilocks_field::GoldilocksField;
+    use crate::field::types::Sample;
+    use crate::gates::gate_testing::{test_eval_fns, test_low_degree};
+    use crate::hash::hash_types::HashOut;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    #[test]
+    fn wire_indices() {
+        let gate = SwitchGate::<GoldilocksField, 4>::new(2, 3);
+
+        assert_eq!(gate.wire_first_input(0, 0), 0);
+        assert_eq!(gate.wire_first_input(0, 2), 2);
+        assert_eq!(gate.wire_second_input(0, 0), 3);
+        assert_eq!(gate.wire_second_input(0, 2), 5);
+        assert_eq!(gate.wire_first_output(0, 0), 6);
+        assert_eq!(gate.wire_second_output(0, 2), 11);
+        assert_eq!(gate.wire_switch_bool(0), 12);
+        assert_eq!(gate.wire_first_input(1, 0), 13);
+        assert_eq!(gate.wire_second_output(1, 2), 24);
+        assert_eq!(gate.wire_switch_bool(1), 25);
+        assert_eq!(Gate::<GoldilocksField, 4>::num_wires(&gate), 26);
+    }
+
+    #[test]
+    fn low_degree() {
+        test_low_degree::<GoldilocksField, _, 4>(SwitchGate::<_, 4>::new(2, 3));
+    }
+
+    #[test]
+    fn eval_fns() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        test_eval_fns::<F, C, _, D>(SwitchGate::<_, D>::new(2, 3))
+    }
+
+    #[test]
+    fn test_gate_constraint() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+        const CHUNK_SIZE: usize = 4;
+        let num_copies = 3;
+
+        /// Returns the local wires for a switch gate given the inputs and the switch booleans.
+        fn get_wires(
+            first_inputs: &[[F; CHUNK_SIZE]],
+            second_inputs: &[[F; CHUNK_SIZE]],
+            switch_bools: &[bool],
+        ) -> Vec<FF> {
+            let mut v = Vec::new();
+            for ((first_input, second_input), &switch) in
+                first_inputs.iter().zip(second_inputs).zip(switch_bools)
+            {
+                let (first_output, second_output) = if switch {
+                    (second_input, first_input)
+                } else {
+                    (first_input, second_input)
+                };
+                v.extend(first_input);
+                v.extend(second_input);
+                v.extend(first_output);
+                v.extend(second_output);
+                v.push(F::from_bool(switch));
+            }
+
+            v.iter().map(|&x| x.into()).collect()
+        }
+
+        let first_inputs = (0..num_copies).map(|_| F::rand_array()).collect::<Vec<_>>();
+        let second_inputs = (0..num_copies).map(|_| F::rand_array()).collect::<Vec<_>>();
+        let switch_bools = [true, false, true];
+
+        let gate = SwitchGate::<F, D>::new(num_copies, CHUNK_SIZE);
+
+        let vars = EvaluationVars {
+            local_constants: &[],
+            local_wires: &get_wires(&first_inputs, &second_inputs, &switch_bools),
+            public_inputs_hash: &HashOut::rand(),
+            extra_openings: &[],
+        };
+
+        assert!(
+            gate.eval_unfiltered(vars).iter().all(|x| x.is_zero()),
+            "Gate constraints are not satisfied."
+        );
+    }
+}
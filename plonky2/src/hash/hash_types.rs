@@ -17,6 +17,33 @@ impl RichField for GoldilocksField {}
 
 pub const NUM_HASH_OUT_ELTS: usize = 4;
 
+/// Pads `elements` with `zero`s on the right to the size of a hash output. This is how both
+/// `HashOut::from_partial` and `HashOutTarget::from_partial` pad, and so how `hash_or_noop` turns
+/// short inputs into hashes, natively and in circuits. Panics if there are more than
+/// `NUM_HASH_OUT_ELTS` elements.
+fn pad_hash_elements<T: Copy>(elements: &[T], zero: T) -> [T; NUM_HASH_OUT_ELTS] {
+    assert!(
+        elements.len() <= NUM_HASH_OUT_ELTS,
+        "A hash output has {} elements, but was given {}",
+        NUM_HASH_OUT_ELTS,
+        elements.len()
+    );
+    let mut padded = [zero; NUM_HASH_OUT_ELTS];
+    padded[..elements.len()].copy_from_slice(elements);
+    padded
+}
+
+/// Returns the first `N` elements of a hash output. Panics if `N > NUM_HASH_OUT_ELTS`.
+fn truncate_hash_elements<T: Copy, const N: usize>(elements: &[T; NUM_HASH_OUT_ELTS]) -> [T; N] {
+    assert!(
+        N <= NUM_HASH_OUT_ELTS,
+        "Can't truncate a hash output of {} elements to {}",
+        NUM_HASH_OUT_ELTS,
+        N
+    );
+    core::array::from_fn(|i| elements[i])
+}
+
 /// Represents a ~256 bit hash output.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 #[serde(bound = "")]
@@ -37,10 +64,19 @@ impl<F: Field> HashOut<F> {
         }
     }
 
+    /// Pads `elements_in` with zeros to a hash output, as `HashOutTarget::from_partial` does.
+    /// Panics if there are more than `NUM_HASH_OUT_ELTS` elements.
     pub fn from_partial(elements_in: &[F]) -> Self {
-        let mut elements = [F::ZERO; NUM_HASH_OUT_ELTS];
-        elements[0..elements_in.len()].copy_from_slice(elements_in);
-        Self { elements }
+        Self {
+            elements: pad_hash_elements(elements_in, F::ZERO),
+        }
+    }
+
+    /// The first `N` elements of this hash, e.g. to use it as an address. Each element carries
+    /// `F::BITS` bits, so over a ~64 bit field the result resists collisions to about `32 * N` bits
+    /// of security and preimages to about `64 * N`. Panics if `N > NUM_HASH_OUT_ELTS`.
+    pub fn truncate<const N: usize>(&self) -> [F; N] {
+        truncate_hash_elements(&self.elements)
     }
 }
 
@@ -89,12 +125,13 @@ impl<F: RichField> GenericHashOut<F> for HashOut<F> {
             .collect()
     }
 
+    /// Non-canonical elements are reduced, so that `to_bytes` tells whether `bytes` were canonical.
     fn from_bytes(bytes: &[u8]) -> Self {
         HashOut {
             elements: bytes
                 .chunks(8)
                 .take(NUM_HASH_OUT_ELTS)
-                .map(|x| F::from_canonical_u64(u64::from_le_bytes(x.try_into().unwrap())))
+                .map(|x| F::from_noncanonical_u64(u64::from_le_bytes(x.try_into().unwrap())))
                 .collect::<Vec<_>>()
                 .try_into()
                 .unwrap(),
@@ -127,10 +164,17 @@ impl HashOutTarget {
         }
     }
 
+    /// Pads `elements_in` with `zero` to a hash output, as `HashOut::from_partial` does. Panics
+    /// if there are more than `NUM_HASH_OUT_ELTS` elements.
     pub fn from_partial(elements_in: &[Target], zero: Target) -> Self {
-        let mut elements = [zero; NUM_HASH_OUT_ELTS];
-        elements[0..elements_in.len()].copy_from_slice(elements_in);
-        Self { elements }
+        Self {
+            elements: pad_hash_elements(elements_in, zero),
+        }
+    }
+
+    /// The first `N` elements of this hash, like `HashOut::truncate`.
+    pub fn truncate<const N: usize>(&self) -> [Target; N] {
+        truncate_hash_elements(&self.elements)
     }
 }
 
@@ -8,7 +8,8 @@ use crate::field::types::Field;
 use crate::hash::hash_types::{HashOut, HashOutTarget, RichField, NUM_HASH_OUT_ELTS};
 use crate::iop::target::Target;
 use crate::plonk::circuit_builder::CircuitBuilder;
-use crate::plonk::config::AlgebraicHasher;
+use crate::plonk::circuit_data::PublicInputCommitment;
+use crate::plonk::config::{AlgebraicHasher, Hasher};
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     pub fn hash_or_noop<H: AlgebraicHasher<F>>(&mut self, inputs: Vec<Target>) -> HashOutTarget {
@@ -20,6 +21,42 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
+    /// Truncates `h` to its first `N` elements, e.g. to use it as an address into a table or
+    /// tree, as `HashOut::truncate` does natively. The security of the result is that of
+    /// `HashOut::truncate`: about `32 * N` bits against collisions over a ~64 bit field. Panics if
+    /// `N > NUM_HASH_OUT_ELTS`.
+    pub fn hash_to_address<const N: usize>(&self, h: HashOutTarget) -> [Target; N] {
+        h.truncate()
+    }
+
+    /// Hashes `public_inputs` as a circuit with the given commitment does to bind its proofs to
+    /// them.
+    pub fn hash_public_inputs<H: AlgebraicHasher<F>>(
+        &mut self,
+        commitment: PublicInputCommitment,
+        public_inputs: &[Target],
+    ) -> HashOutTarget {
+        match commitment {
+            PublicInputCommitment::Flat => self.hash_n_to_hash_no_pad::<H>(public_inputs.to_vec()),
+            PublicInputCommitment::Chunked { .. } => {
+                let digests = commitment
+                    .chunk_ranges(public_inputs.len())
+                    .into_iter()
+                    .map(|range| self.hash_n_to_hash_no_pad::<H>(public_inputs[range].to_vec()))
+                    .collect::<Vec<_>>();
+                self.hash_public_input_chunk_digests::<H>(&digests)
+            }
+        }
+    }
+
+    /// Combines the digests of the chunks of a chunked public input commitment.
+    pub fn hash_public_input_chunk_digests<H: AlgebraicHasher<F>>(
+        &mut self,
+        digests: &[HashOutTarget],
+    ) -> HashOutTarget {
+        self.hash_n_to_hash_no_pad::<H>(digests.iter().flat_map(|d| d.elements).collect())
+    }
+
     pub fn hash_n_to_hash_no_pad<H: AlgebraicHasher<F>>(
         &mut self,
         inputs: Vec<Target>,
@@ -27,11 +64,43 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         HashOutTarget::from_vec(self.hash_n_to_m_no_pad::<H>(inputs, NUM_HASH_OUT_ELTS))
     }
 
+    /// Hashes `inputs` after padding them with `padding`, as `Hasher::hash_with_padding` does
+    /// natively.
+    pub fn hash_n_to_hash<H: AlgebraicHasher<F>>(
+        &mut self,
+        inputs: Vec<Target>,
+        padding: HashPadding,
+    ) -> HashOutTarget {
+        HashOutTarget::from_vec(self.hash_n_to_m::<H>(inputs, NUM_HASH_OUT_ELTS, padding))
+    }
+
+    /// Like `hash_n_to_m_no_pad`, but pads `inputs` with `padding` first.
+    pub fn hash_n_to_m<H: AlgebraicHasher<F>>(
+        &mut self,
+        inputs: Vec<Target>,
+        num_outputs: usize,
+        padding: HashPadding,
+    ) -> Vec<Target> {
+        let zero = self.zero();
+        let one = self.one();
+        let padded_inputs = padding.pad(&inputs, H::AlgebraicPermutation::RATE, zero, one);
+        self.hash_n_to_m_no_pad::<H>(padded_inputs, num_outputs)
+    }
+
     pub fn hash_n_to_m_no_pad<H: AlgebraicHasher<F>>(
         &mut self,
         inputs: Vec<Target>,
         num_outputs: usize,
     ) -> Vec<Target> {
+        // As natively, an empty input hashes to the permutation of the zero state, which is a
+        // constant.
+        if inputs.is_empty() {
+            return hash_n_to_m_no_pad::<F, <H as Hasher<F>>::Permutation>(&[], num_outputs)
+                .into_iter()
+                .map(|x| self.constant(x))
+                .collect();
+        }
+
         let zero = self.zero();
         let mut state = H::AlgebraicPermutation::new(core::iter::repeat(zero));
 
@@ -58,6 +127,33 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 }
 
+/// How a sponge pads its inputs before absorbing them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum HashPadding {
+    /// No padding, which is only collision-resistant for inputs of a fixed length, since inputs
+    /// which only differ by trailing zeros in their last chunk hash alike. It suits internal uses
+    /// such as hashing public inputs or Merkle tree nodes.
+    NoPad,
+    /// The `pad10*1` rule: a one, as few zeros as possible, then a final one, so that the padded
+    /// inputs fill whole chunks of `RATE` elements. Inputs of any length can then be hashed.
+    Pad101,
+}
+
+impl HashPadding {
+    /// Pads `inputs` for a sponge absorbing `rate` elements at a time, using the given zero and
+    /// one. It is shared by native and in-circuit hashing, so that they pad alike.
+    pub fn pad<T: Copy>(self, inputs: &[T], rate: usize, zero: T, one: T) -> Vec<T> {
+        let mut padded = inputs.to_vec();
+        if self == Self::Pad101 {
+            padded.push(one);
+            let num_zeros = (rate - (padded.len() + 1) % rate) % rate;
+            padded.extend(core::iter::repeat_n(zero, num_zeros));
+            padded.push(one);
+        }
+        padded
+    }
+}
+
 /// Permutation that can be used in the sponge construction for an algebraic hash.
 pub trait PlonkyPermutation<T: Copy + Default>:
     AsRef<[T]> + Copy + Debug + Default + Eq + Sync + Send
@@ -115,6 +211,10 @@ pub fn compress<F: Field, P: PlonkyPermutation<F>>(x: HashOut<F>, y: HashOut<F>)
 
 /// Hash a message without any padding step. Note that this can enable length-extension attacks.
 /// However, it is still collision-resistant in cases where the input has a fixed length.
+///
+/// An empty message is hashed as the permutation of the zero state, rather than squeezing the zero
+/// state itself, so that its digest isn't trivially zero; e.g. the public inputs of a circuit
+/// without any.
 pub fn hash_n_to_m_no_pad<F: RichField, P: PlonkyPermutation<F>>(
     inputs: &[F],
     num_outputs: usize,
@@ -126,6 +226,9 @@ pub fn hash_n_to_m_no_pad<F: RichField, P: PlonkyPermutation<F>>(
         perm.set_from_slice(input_chunk, 0);
         perm.permute();
     }
+    if inputs.is_empty() {
+        perm.permute();
+    }
 
     // Squeeze until we have the desired number of outputs.
     let mut outputs = Vec::new();
@@ -143,3 +246,141 @@ pub fn hash_n_to_m_no_pad<F: RichField, P: PlonkyPermutation<F>>(
 pub fn hash_n_to_hash_no_pad<F: RichField, P: PlonkyPermutation<F>>(inputs: &[F]) -> HashOut<F> {
     HashOut::from_vec(hash_n_to_m_no_pad::<F, P>(inputs, NUM_HASH_OUT_ELTS))
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::hash::poseidon::PoseidonHash;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::util::testing::assert_differential;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Samples inputs of lengths `0, 1, ..., max_len, 0, ...` in turn.
+    fn sample_lengths(max_len: usize) -> impl FnMut() -> Vec<F> {
+        let mut len = 0;
+        move || {
+            let inputs = F::rand_vec(len);
+            len = (len + 1) % (max_len + 1);
+            inputs
+        }
+    }
+
+    #[test]
+    fn from_partial_matches_native() {
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            5,
+            sample_lengths(NUM_HASH_OUT_ELTS),
+            |builder, inputs| {
+                let zero = builder.zero();
+                HashOutTarget::from_partial(inputs, zero).elements.to_vec()
+            },
+            |inputs| HashOut::from_partial(inputs).elements.to_vec(),
+        );
+    }
+
+    #[test]
+    fn hash_or_noop_matches_native() {
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            6,
+            sample_lengths(NUM_HASH_OUT_ELTS + 1),
+            |builder, inputs| {
+                builder
+                    .hash_or_noop::<PoseidonHash>(inputs.to_vec())
+                    .elements
+                    .to_vec()
+            },
+            |inputs| PoseidonHash::hash_or_noop(inputs).elements.to_vec(),
+        );
+    }
+
+    #[test]
+    fn hash_to_address_matches_native() {
+        assert_differential::<F, C, D>(
+            CircuitConfig::standard_recursion_config(),
+            2,
+            || F::rand_vec(7),
+            |builder, inputs| {
+                let h = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs.to_vec());
+                builder.hash_to_address::<2>(h).to_vec()
+            },
+            |inputs| PoseidonHash::hash_no_pad(inputs).truncate::<2>().to_vec(),
+        );
+    }
+
+    #[test]
+    fn padded_hashes_match_native() {
+        let rate = <PoseidonHash as Hasher<F>>::Permutation::RATE;
+        for padding in [HashPadding::NoPad, HashPadding::Pad101] {
+            // Lengths from 0 to just over two chunks, on both sides of each chunk boundary.
+            assert_differential::<F, C, D>(
+                CircuitConfig::standard_recursion_config(),
+                2 * rate + 2,
+                sample_lengths(2 * rate + 1),
+                |builder, inputs| {
+                    builder
+                        .hash_n_to_hash::<PoseidonHash>(inputs.to_vec(), padding)
+                        .elements
+                        .to_vec()
+                },
+                |inputs| {
+                    PoseidonHash::hash_with_padding(inputs, padding)
+                        .elements
+                        .to_vec()
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn pad101_fills_whole_chunks() {
+        for len in 0..20 {
+            let padded = HashPadding::Pad101.pad(&vec![2; len], 8, 0, 1);
+            assert_eq!(padded.len() % 8, 0);
+            assert!(padded.len() >= len + 2 && padded.len() < len + 10);
+            assert_eq!(padded[..len], vec![2; len]);
+            assert_eq!(padded[len], 1);
+            assert_eq!(padded.last(), Some(&1));
+            assert!(padded[len + 1..padded.len() - 1].iter().all(|&x| x == 0));
+        }
+        assert_eq!(HashPadding::NoPad.pad(&[2, 3], 8, 0, 1), vec![2, 3]);
+    }
+
+    #[test]
+    fn pad101_distinguishes_trailing_zeros() {
+        let x = F::rand();
+        let hash = |inputs: &[F], padding| PoseidonHash::hash_with_padding(inputs, padding);
+        assert_eq!(
+            hash(&[x], HashPadding::NoPad),
+            hash(&[x, F::ZERO], HashPadding::NoPad)
+        );
+        assert_ne!(
+            hash(&[x], HashPadding::Pad101),
+            hash(&[x, F::ZERO], HashPadding::Pad101)
+        );
+    }
+
+    #[test]
+    fn empty_input_hashes_to_permuted_zero_state() {
+        let mut perm = <PoseidonHash as Hasher<F>>::Permutation::new(core::iter::repeat(F::ZERO));
+        perm.permute();
+        let hash = PoseidonHash::hash_no_pad(&[] as &[F]);
+        assert_eq!(hash.elements, perm.squeeze()[..NUM_HASH_OUT_ELTS]);
+        assert_ne!(hash, HashOut::ZERO);
+    }
+
+    #[test]
+    #[should_panic(expected = "A hash output has 4 elements, but was given 5")]
+    fn from_partial_rejects_long_inputs() {
+        HashOut::from_partial(&F::rand_vec(5));
+    }
+}
@@ -13,6 +13,7 @@ use crate::iop::target::{BoolTarget, Target};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::VerifierCircuitTarget;
 use crate::plonk::config::{AlgebraicHasher, Hasher};
+use crate::util::log2_strict;
 
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(bound = "")]
@@ -142,11 +143,15 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             };
         }
 
+        // The remaining index bits, if any, are those of `cap_index`.
+        let cap_index_bits = &leaf_index_bits[proof.siblings.len()..];
         for i in 0..NUM_HASH_OUT_ELTS {
-            let result = self.random_access(
-                cap_index,
-                merkle_cap.0.iter().map(|h| h.elements[i]).collect(),
-            );
+            let cap_elements = merkle_cap.0.iter().map(|h| h.elements[i]).collect();
+            let result = if self.naive_index_selection {
+                self.select_by_bits(cap_index_bits, cap_elements)
+            } else {
+                self.random_access(cap_index, cap_elements)
+            };
             self.connect(result, state.elements[i]);
         }
     }
@@ -163,9 +168,24 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
-    pub fn connect_verifier_data(&mut self, x: &VerifierCircuitTarget, y: &VerifierCircuitTarget) {
-        self.connect_merkle_caps(&x.constants_sigmas_cap, &y.constants_sigmas_cap);
-        self.connect_hashes(x.circuit_digest, y.circuit_digest);
+    /// Asserts that a constants-sigmas cap and a circuit digest computed in the circuit, e.g. read
+    /// from an inner proof's public inputs or hashed from a registry entry, are those of `vd`.
+    pub fn connect_verifier_data(
+        &mut self,
+        computed_cap: &MerkleCapTarget,
+        computed_digest: HashOutTarget,
+        vd: &VerifierCircuitTarget,
+    ) {
+        assert_eq!(
+            computed_cap.0.len(),
+            vd.constants_sigmas_cap.0.len(),
+            "Cannot connect a Merkle cap of height {} to verifier data whose constants-sigmas cap has \
+             height {}",
+            log2_strict(computed_cap.0.len()),
+            log2_strict(vd.constants_sigmas_cap.0.len())
+        );
+        self.connect_merkle_caps(computed_cap, &vd.constants_sigmas_cap);
+        self.connect_hashes(computed_digest, vd.circuit_digest);
     }
 }
 
@@ -175,13 +195,20 @@ mod tests {
     use rand::Rng;
 
     use super::*;
+    use crate::circuit_size_test;
     use crate::field::types::Field;
     use crate::hash::merkle_tree::MerkleTree;
+    use crate::hash::poseidon::PoseidonHash;
     use crate::iop::witness::{PartialWitness, WitnessWrite};
-    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
     use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::plonk_common::PlonkOracle;
     use crate::plonk::verifier::verify;
 
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
     fn random_data<F: Field>(n: usize, k: usize) -> Vec<Vec<F>> {
         (0..n).map(|_| F::rand_vec(k)).collect()
     }
@@ -230,4 +257,84 @@ mod tests {
 
         verify(proof, &data.verifier_only, &data.common)
     }
+
+    /// Builds a circuit asserting that a purported cap and digest, e.g. from a registry, are those
+    /// of some verifier data, and proves it for the data of `vd_circuit` and `entry_circuit`.
+    fn prove_verifier_data_entry(
+        vd_circuit: &CircuitData<F, C, D>,
+        entry_circuit: &CircuitData<F, C, D>,
+    ) -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let cap_height = config.cap_height(PlonkOracle::CONSTANTS_SIGMAS);
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let vd = builder.add_verifier_data_public_inputs();
+        let entry_cap = builder.add_virtual_cap(cap_height);
+        let entry_digest = builder.add_virtual_hash();
+        builder.connect_verifier_data(&entry_cap, entry_digest, &vd);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_verifier_data_target(&vd, &vd_circuit.verifier_only);
+        pw.set_cap_target(
+            &entry_cap,
+            &entry_circuit.verifier_only.constants_sigmas_cap,
+        );
+        pw.set_hash_target(entry_digest, entry_circuit.verifier_only.circuit_digest);
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    fn small_circuit(num_public_inputs: usize) -> CircuitData<F, C, D> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let inputs = builder.add_virtual_targets(num_public_inputs);
+        builder.register_public_inputs(&inputs);
+        builder.build::<C>()
+    }
+
+    #[test]
+    #[should_panic(expected = "was set twice with different values")]
+    fn connect_verifier_data_rejects_other_circuit() {
+        let a = small_circuit(1);
+        let b = small_circuit(2);
+        prove_verifier_data_entry(&a, &a).unwrap();
+        prove_verifier_data_entry(&a, &b).unwrap();
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Cannot connect a Merkle cap of height 2 to verifier data whose constants-sigmas \
+                    cap has height 4"
+    )]
+    fn connect_verifier_data_rejects_wrong_cap_height() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let vd = builder.add_virtual_verifier_data(4);
+        let cap = builder.add_virtual_cap(2);
+        let digest = builder.add_virtual_hash();
+        builder.connect_verifier_data(&cap, digest, &vd);
+    }
+
+    circuit_size_test!(
+        merkle_proof_size,
+        CircuitConfig::standard_recursion_config(),
+        |builder| {
+            let proof_t = MerkleProofTarget {
+                siblings: builder.add_virtual_hashes(8),
+            };
+            let cap_t = builder.add_virtual_cap(1);
+            let index = builder.add_virtual_target();
+            let index_bits = builder.split_le(index, 8);
+            let data = builder.add_virtual_targets(7);
+            builder.verify_merkle_proof_to_cap::<PoseidonHash>(data, &index_bits, &cap_t, &proof_t);
+        },
+        degree_bits: 4,
+        num_rows: 15,
+        gates: {
+            "BaseSumGate" => 1,
+            // Including the constant hash of the circuit's empty public inputs.
+            "ConstantGate" => 3,
+            "PoseidonGate" => 9,
+            "PublicInputGate" => 1,
+            "RandomAccessGate" => 1,
+        },
+    );
 }
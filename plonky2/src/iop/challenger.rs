@@ -1,5 +1,11 @@
 #[cfg(not(feature = "std"))]
-use alloc::{vec, vec::Vec};
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
 use core::marker::PhantomData;
 
 use crate::field::extension::{Extendable, FieldExtension};
@@ -8,15 +14,136 @@ use crate::hash::hashing::PlonkyPermutation;
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::target::Target;
+use crate::iop::witness::Witness;
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::config::{AlgebraicHasher, GenericHashOut, Hasher};
 
+/// Whether a transcript entry was observed by a challenger or squeezed from it.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TranscriptEvent {
+    Observe,
+    Challenge,
+}
+
+/// An element of a challenger's transcript.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TranscriptEntry<T> {
+    pub event: TranscriptEvent,
+    /// The label of the `observe_labeled` call which observed the element, if any.
+    pub label: Option<String>,
+    pub value: T,
+}
+
+/// Every element observed by a challenger and every challenge it produced, in order. It is only
+/// recorded once enabled with `enable_transcript_log`, to debug transcripts which diverge.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TranscriptLog<T> {
+    pub entries: Vec<TranscriptEntry<T>>,
+    /// The label of the ongoing `observe_labeled` call.
+    label: Option<String>,
+}
+
+impl<T> TranscriptLog<T> {
+    fn record(&mut self, event: TranscriptEvent, value: T) {
+        self.entries.push(TranscriptEntry {
+            event,
+            label: self.label.clone(),
+            value,
+        });
+    }
+}
+
+impl TranscriptLog<Target> {
+    /// The values of the logged targets in `witness`, to be compared with a native log by
+    /// [`diff_transcripts`].
+    pub fn values<F: RichField, W: Witness<F>>(&self, witness: &W) -> TranscriptLog<F> {
+        TranscriptLog {
+            entries: self
+                .entries
+                .iter()
+                .map(|entry| TranscriptEntry {
+                    event: entry.event,
+                    label: entry.label.clone(),
+                    value: witness.get_target(entry.value),
+                })
+                .collect(),
+            label: None,
+        }
+    }
+}
+
+/// The first position at which two transcripts differ, as found by [`diff_transcripts`]. An
+/// entry is `None` if its transcript ended before that position.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TranscriptDivergence<F> {
+    pub position: usize,
+    pub native: Option<TranscriptEntry<F>>,
+    pub circuit: Option<TranscriptEntry<F>>,
+}
+
+impl<F> TranscriptDivergence<F> {
+    /// The label of the native entry at the divergence, or failing that of the circuit's.
+    pub fn label(&self) -> Option<&str> {
+        [&self.native, &self.circuit]
+            .into_iter()
+            .flatten()
+            .find_map(|entry| entry.label.as_deref())
+    }
+}
+
+impl<F: fmt::Display> fmt::Display for TranscriptDivergence<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn describe<F: fmt::Display>(entry: &Option<TranscriptEntry<F>>) -> String {
+            match entry {
+                Some(entry) => format!(
+                    "{:?} {} (label {:?})",
+                    entry.event,
+                    entry.value,
+                    entry.label.as_deref().unwrap_or("none")
+                ),
+                None => "end of transcript".to_string(),
+            }
+        }
+        write!(
+            f,
+            "transcripts diverge at position {}: native {}, circuit {}",
+            self.position,
+            describe(&self.native),
+            describe(&self.circuit)
+        )
+    }
+}
+
+/// Compares a native transcript with the values of a recursive one, as given by
+/// [`TranscriptLog::values`]. Returns the first entry at which they differ, in event or value,
+/// or `None` if they are identical.
+pub fn diff_transcripts<F: RichField>(
+    native_log: &TranscriptLog<F>,
+    circuit_log: &TranscriptLog<F>,
+) -> Option<TranscriptDivergence<F>> {
+    let native = &native_log.entries;
+    let circuit = &circuit_log.entries;
+    let same =
+        |a: &TranscriptEntry<F>, b: &TranscriptEntry<F>| a.event == b.event && a.value == b.value;
+    let position = native
+        .iter()
+        .zip(circuit)
+        .position(|(a, b)| !same(a, b))
+        .or_else(|| (native.len() != circuit.len()).then(|| native.len().min(circuit.len())))?;
+    Some(TranscriptDivergence {
+        position,
+        native: native.get(position).cloned(),
+        circuit: circuit.get(position).cloned(),
+    })
+}
+
 /// Observes prover messages, and generates challenges by hashing the transcript, a la Fiat-Shamir.
 #[derive(Clone, Debug)]
 pub struct Challenger<F: RichField, H: Hasher<F>> {
     pub(crate) sponge_state: H::Permutation,
     pub(crate) input_buffer: Vec<F>,
     output_buffer: Vec<F>,
+    transcript_log: Option<TranscriptLog<F>>,
 }
 
 /// Observes prover messages, and generates verifier challenges based on the transcript.
@@ -33,13 +160,34 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
             sponge_state: H::Permutation::new(core::iter::repeat(F::ZERO)),
             input_buffer: Vec::with_capacity(H::Permutation::RATE),
             output_buffer: Vec::with_capacity(H::Permutation::RATE),
+            transcript_log: None,
         }
     }
 
+    /// Starts recording the transcript into a [`TranscriptLog`]. This is meant for debugging, as
+    /// it keeps every observed element and challenge.
+    pub fn enable_transcript_log(&mut self) {
+        self.transcript_log
+            .get_or_insert_with(TranscriptLog::default);
+    }
+
+    /// The transcript recorded since `enable_transcript_log` was called, if it was.
+    pub fn transcript_log(&self) -> Option<&TranscriptLog<F>> {
+        self.transcript_log.as_ref()
+    }
+
+    /// Takes the recorded transcript out of the challenger, which stops recording.
+    pub fn take_transcript_log(&mut self) -> Option<TranscriptLog<F>> {
+        self.transcript_log.take()
+    }
+
     pub fn observe_element(&mut self, element: F) {
         // Any buffered outputs are now invalid, since they wouldn't reflect this input.
         self.output_buffer.clear();
 
+        if let Some(log) = &mut self.transcript_log {
+            log.record(TranscriptEvent::Observe, element);
+        }
         self.input_buffer.push(element);
 
         if self.input_buffer.len() == H::Permutation::RATE {
@@ -60,6 +208,24 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
         }
     }
 
+    /// Like `observe_elements`, but tags the elements with `label` in the transcript log.
+    pub fn observe_labeled(&mut self, label: &str, elements: &[F]) {
+        self.labeled(label, |challenger| challenger.observe_elements(elements));
+    }
+
+    /// Runs `observe`, tagging everything it records in the transcript log with `label`, e.g. to
+    /// label a Merkle cap observed with `observe_cap`.
+    pub fn labeled<R>(&mut self, label: &str, observe: impl FnOnce(&mut Self) -> R) -> R {
+        if let Some(log) = &mut self.transcript_log {
+            log.label = Some(label.to_string());
+        }
+        let result = observe(self);
+        if let Some(log) = &mut self.transcript_log {
+            log.label = None;
+        }
+        result
+    }
+
     pub fn observe_extension_elements<const D: usize>(&mut self, elements: &[F::Extension])
     where
         F: RichField + Extendable<D>,
@@ -86,9 +252,14 @@ impl<F: RichField, H: Hasher<F>> Challenger<F, H> {
             self.duplexing();
         }
 
-        self.output_buffer
+        let challenge = self
+            .output_buffer
             .pop()
-            .expect("Output buffer should be non-empty")
+            .expect("Output buffer should be non-empty");
+        if let Some(log) = &mut self.transcript_log {
+            log.record(TranscriptEvent::Challenge, challenge);
+        }
+        challenge
     }
 
     pub fn get_n_challenges(&mut self, n: usize) -> Vec<F> {
@@ -167,6 +338,7 @@ pub struct RecursiveChallenger<F: RichField + Extendable<D>, H: AlgebraicHasher<
     sponge_state: H::AlgebraicPermutation,
     input_buffer: Vec<Target>,
     output_buffer: Vec<Target>,
+    transcript_log: Option<TranscriptLog<Target>>,
     __: PhantomData<(F, H)>,
 }
 
@@ -179,6 +351,7 @@ impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
             sponge_state: H::AlgebraicPermutation::new(core::iter::repeat(zero)),
             input_buffer: Vec::new(),
             output_buffer: Vec::new(),
+            transcript_log: None,
             __: PhantomData,
         }
     }
@@ -188,14 +361,35 @@ impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
             sponge_state,
             input_buffer: vec![],
             output_buffer: vec![],
+            transcript_log: None,
             __: PhantomData,
         }
     }
 
+    /// Starts recording the transcript's targets into a [`TranscriptLog`], whose values can be
+    /// compared with a native log once a witness is generated.
+    pub fn enable_transcript_log(&mut self) {
+        self.transcript_log
+            .get_or_insert_with(TranscriptLog::default);
+    }
+
+    /// The transcript recorded since `enable_transcript_log` was called, if it was.
+    pub fn transcript_log(&self) -> Option<&TranscriptLog<Target>> {
+        self.transcript_log.as_ref()
+    }
+
+    /// Takes the recorded transcript out of the challenger, which stops recording.
+    pub fn take_transcript_log(&mut self) -> Option<TranscriptLog<Target>> {
+        self.transcript_log.take()
+    }
+
     pub fn observe_element(&mut self, target: Target) {
         // Any buffered outputs are now invalid, since they wouldn't reflect this input.
         self.output_buffer.clear();
 
+        if let Some(log) = &mut self.transcript_log {
+            log.record(TranscriptEvent::Observe, target);
+        }
         self.input_buffer.push(target);
     }
 
@@ -205,6 +399,24 @@ impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
         }
     }
 
+    /// Like `observe_elements`, but tags the targets with `label` in the transcript log.
+    pub fn observe_labeled(&mut self, label: &str, targets: &[Target]) {
+        self.labeled(label, |challenger| challenger.observe_elements(targets));
+    }
+
+    /// Runs `observe`, tagging everything it records in the transcript log with `label`, e.g. to
+    /// label a Merkle cap observed with `observe_cap`.
+    pub fn labeled<R>(&mut self, label: &str, observe: impl FnOnce(&mut Self) -> R) -> R {
+        if let Some(log) = &mut self.transcript_log {
+            log.label = Some(label.to_string());
+        }
+        let result = observe(self);
+        if let Some(log) = &mut self.transcript_log {
+            log.label = None;
+        }
+        result
+    }
+
     pub fn observe_hash(&mut self, hash: &HashOutTarget) {
         self.observe_elements(&hash.elements)
     }
@@ -234,9 +446,14 @@ impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
             self.output_buffer = self.sponge_state.squeeze().to_vec();
         }
 
-        self.output_buffer
+        let challenge = self
+            .output_buffer
             .pop()
-            .expect("Output buffer should be non-empty")
+            .expect("Output buffer should be non-empty");
+        if let Some(log) = &mut self.transcript_log {
+            log.record(TranscriptEvent::Challenge, challenge);
+        }
+        challenge
     }
 
     pub fn get_n_challenges(
@@ -295,13 +512,16 @@ impl<F: RichField + Extendable<D>, H: AlgebraicHasher<F>, const D: usize>
 #[cfg(test)]
 mod tests {
     #[cfg(not(feature = "std"))]
-    use alloc::vec::Vec;
+    use alloc::{string::ToString, vec, vec::Vec};
 
     use crate::field::types::Sample;
-    use crate::iop::challenger::{Challenger, RecursiveChallenger};
+    use crate::gates::noop::NoopGate;
+    use crate::iop::challenger::{
+        diff_transcripts, Challenger, RecursiveChallenger, TranscriptEvent,
+    };
     use crate::iop::generator::generate_partial_witness;
     use crate::iop::target::Target;
-    use crate::iop::witness::{PartialWitness, Witness};
+    use crate::iop::witness::{PartialWitness, Witness, WitnessWrite};
     use crate::plonk::circuit_builder::CircuitBuilder;
     use crate::plonk::circuit_data::CircuitConfig;
     use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
@@ -365,7 +585,8 @@ mod tests {
         }
         let circuit = builder.build::<C>();
         let inputs = PartialWitness::new();
-        let witness = generate_partial_witness(inputs, &circuit.prover_only, &circuit.common);
+        let witness =
+            generate_partial_witness(inputs, &circuit.prover_only, &circuit.common).unwrap();
         let recursive_output_values_per_round: Vec<Vec<F>> = recursive_outputs_per_round
             .iter()
             .map(|outputs| witness.get_targets(outputs))
@@ -373,4 +594,126 @@ mod tests {
 
         assert_eq!(outputs_per_round, recursive_output_values_per_round);
     }
+
+    #[test]
+    fn diff_transcripts_finds_skipped_observation() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type H = <C as GenericConfig<D>>::InnerHasher;
+
+        let messages = [("wires_cap", 4), ("zs_cap", 3), ("quotient_cap", 4)];
+        let values: Vec<Vec<F>> = messages.iter().map(|&(_, n)| F::rand_vec(n)).collect();
+
+        let mut challenger = Challenger::<F, H>::new();
+        challenger.enable_transcript_log();
+        for ((label, _), values) in messages.iter().zip(&values) {
+            challenger.observe_labeled(label, values);
+            challenger.get_n_challenges(2);
+        }
+
+        // The recursive verifier forgets to observe the Zs cap.
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut recursive_challenger = RecursiveChallenger::<F, H, D>::new(&mut builder);
+        recursive_challenger.enable_transcript_log();
+        for ((label, _), values) in messages.iter().zip(&values) {
+            let targets = builder.constants(values);
+            if *label != "zs_cap" {
+                recursive_challenger.observe_labeled(label, &targets);
+            }
+            recursive_challenger.get_n_challenges(&mut builder, 2);
+        }
+        let recursive_log = recursive_challenger.transcript_log().unwrap().clone();
+        let circuit = builder.build::<C>();
+        let witness =
+            generate_partial_witness(PartialWitness::new(), &circuit.prover_only, &circuit.common)
+                .unwrap();
+
+        let native_log = challenger.transcript_log().unwrap();
+        assert_eq!(native_log.entries.len(), 4 + 3 + 4 + 3 * 2);
+        let circuit_log = recursive_log.values(&witness);
+        assert_eq!(diff_transcripts(native_log, native_log), None);
+
+        // The transcripts agree on the wires cap and the challenges drawn after it.
+        let divergence = diff_transcripts(native_log, &circuit_log).unwrap();
+        assert_eq!(divergence.position, 4 + 2);
+        assert_eq!(divergence.label(), Some("zs_cap"));
+        let native = divergence.native.as_ref().unwrap();
+        assert_eq!(native.event, TranscriptEvent::Observe);
+        assert_eq!(native.value, values[1][0]);
+        assert_eq!(
+            divergence.circuit.as_ref().unwrap().event,
+            TranscriptEvent::Challenge
+        );
+        assert!(divergence
+            .to_string()
+            .starts_with("transcripts diverge at position 6: native Observe"));
+    }
+
+    #[test]
+    fn recursive_verifier_transcript_matches_native() -> anyhow::Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_target();
+        let y = builder.mul(x, x);
+        builder.register_public_input(y);
+        // Enough rows for FRI to commit to at least one reduction.
+        for _ in 0..4000 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let inner = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        let proof = inner.prove(pw)?;
+        let (_, native_log) = proof.get_challenges_with_transcript(
+            proof.get_public_inputs_hash(&inner.common),
+            &inner.verifier_only.circuit_digest,
+            &inner.common,
+        )?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&inner.common);
+        let verifier_data = builder.constant_verifier_data(&inner.verifier_only);
+        let recursive_log = builder.verify_proof_with_transcript_log::<C>(
+            &proof_target,
+            &verifier_data,
+            &inner.common,
+        );
+        let outer = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&proof_target, &proof);
+        let witness = generate_partial_witness(pw, &outer.prover_only, &outer.common)?;
+
+        assert_eq!(
+            diff_transcripts(&native_log, &recursive_log.values(&witness)),
+            None
+        );
+        // Every observation of the proof is labeled, in the order the verifier observes them.
+        let mut labels: Vec<&str> = native_log
+            .entries
+            .iter()
+            .filter(|entry| entry.event == TranscriptEvent::Observe)
+            .map(|entry| entry.label.as_deref().expect("unlabeled observation"))
+            .collect();
+        labels.dedup();
+        assert_eq!(
+            labels,
+            [
+                "circuit_digest",
+                "public_inputs_hash",
+                "wires_cap",
+                "plonk_zs_partial_products_cap",
+                "quotient_polys_cap",
+                "openings",
+                "fri_commit_phase_cap",
+                "fri_final_poly",
+                "fri_pow_witness",
+            ]
+        );
+        Ok(())
+    }
 }
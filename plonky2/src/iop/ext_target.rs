@@ -28,6 +28,11 @@ impl<const D: usize> ExtensionTarget<D> {
         self.0
     }
 
+    /// Replaces each of the targets of this extension target `t` by `map(t)`.
+    pub fn remap(&self, map: &dyn Fn(Target) -> Target) -> Self {
+        Self(self.0.map(map))
+    }
+
     pub fn frobenius<F: RichField + Extendable<D>>(
         &self,
         builder: &mut CircuitBuilder<F, D>,
@@ -1,12 +1,22 @@
 #[cfg(not(feature = "std"))]
 use alloc::{
     boxed::Box,
+    collections::BTreeMap,
+    format,
     string::{String, ToString},
     vec,
     vec::Vec,
 };
 use core::fmt::Debug;
 use core::marker::PhantomData;
+use core::mem::swap;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+#[cfg(feature = "timing")]
+use web_time::{Duration, Instant};
 
 use crate::field::extension::Extendable;
 use crate::field::types::Field;
@@ -17,10 +27,19 @@ use crate::iop::wire::Wire;
 use crate::iop::witness::{PartialWitness, PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
 use crate::plonk::config::GenericConfig;
+use crate::plonk::progress::{PhaseProgress, ProverPhase};
+use crate::plonk::prover::ProverOptions;
 use crate::util::serialization::{Buffer, IoResult, Read, Write};
+use crate::util::timing::TimingTree;
+
+/// The maximum number of never-set targets listed when witness generation stalls.
+const MAX_REPORTED_MISSING_TARGETS: usize = 10;
 
 /// Given a `PartitionWitness` that has only inputs set, populates the rest of the witness using the
 /// given set of generators.
+///
+/// If some generators could not be run because their dependencies were never set, an error
+/// describing the missing targets (or the dependency cycle preventing progress) is returned.
 pub fn generate_partial_witness<
     'a,
     F: RichField + Extendable<D>,
@@ -30,9 +49,36 @@ pub fn generate_partial_witness<
     inputs: PartialWitness<F>,
     prover_data: &'a ProverOnlyCircuitData<F, C, D>,
     common_data: &'a CommonCircuitData<F, D>,
-) -> PartitionWitness<'a, F> {
+) -> Result<PartitionWitness<'a, F>> {
+    generate_partial_witness_with_options(
+        inputs,
+        prover_data,
+        common_data,
+        &ProverOptions::default(),
+        &mut TimingTree::default(),
+    )
+}
+
+/// Like `generate_partial_witness`, but lets the caller customize witness generation, e.g. to
+/// derive random values from a fixed seed or to time generators.
+pub fn generate_partial_witness_with_options<
+    'a,
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    inputs: PartialWitness<F>,
+    prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+    common_data: &'a CommonCircuitData<F, D>,
+    #[allow(unused_variables)] options: &ProverOptions,
+    #[allow(unused_variables)] timing: &mut TimingTree,
+) -> Result<PartitionWitness<'a, F>> {
     let config = &common_data.config;
     let generators = &prover_data.generators;
+    let copy_generators = &prover_data.copy_generators;
+    let batch_copy_generators = &prover_data.batch_copy_generators;
+    let first_batch_idx = generators.len() + copy_generators.len();
+    let num_generators = prover_data.num_generators();
     let generator_indices_by_watches = &prover_data.generator_indices_by_watches;
 
     let mut witness = PartitionWitness::new(
@@ -40,66 +86,342 @@ pub fn generate_partial_witness<
         common_data.degree(),
         &prover_data.representative_map,
     );
+    #[cfg(feature = "rand_chacha")]
+    {
+        witness.rng_seed = options.rng_seed;
+    }
 
     for (t, v) in inputs.target_values.into_iter() {
         witness.set_target(t, v);
     }
 
-    // Build a list of "pending" generators which are queued to be run. Initially, all generators
-    // are queued.
-    let mut pending_generator_indices: Vec<_> = (0..generators.len()).collect();
-
-    // We also track a list of "expired" generators which have already returned false.
-    let mut generator_is_expired = vec![false; generators.len()];
-    let mut remaining_generators = generators.len();
-
-    let mut buffer = GeneratedValues::empty();
+    // Build a queue of "pending" generators which are to be run. Initially, all generators are
+    // queued. A generator is queued at most once at a time, so a generator watching several
+    // targets which are populated in a row runs once rather than once per target.
+    let mut pending_generator_indices: Vec<_> = (0..num_generators).collect();
+    let mut next_pending_generator_indices = Vec::new();
+    let mut generator_is_queued = vec![true; num_generators];
+
+    // We also track a list of "expired" generators which have already returned true.
+    let mut generator_is_expired = vec![false; num_generators];
+    let mut remaining_generators = num_generators;
+
+    // Batches of copies watch many targets, so rather than rescanning all their pairs each time
+    // they are woken, they only copy the pairs whose source was populated since their last run.
+    let mut pending_copies = vec![PendingCopies::default(); batch_copy_generators.len()];
+    let progress = PhaseProgress::start(&options.progress, ProverPhase::WitnessGeneration);
+
+    // Divisions are deferred to the end of each pass over the queue, so that all the divisions of
+    // a pass share one field inversion.
+    let mut buffer = if options.eager_inversions {
+        GeneratedValues::empty()
+    } else {
+        GeneratedValues::with_batch_inversion()
+    };
+
+    // Time spent running each generator, if requested.
+    #[cfg(feature = "timing")]
+    let mut generator_durations = match options.time_generators {
+        true => vec![Duration::ZERO; num_generators],
+        false => Vec::new(),
+    };
 
     // Keep running generators until we fail to make progress.
     while !pending_generator_indices.is_empty() {
-        let mut next_pending_generator_indices = Vec::new();
-
         for &generator_idx in &pending_generator_indices {
+            generator_is_queued[generator_idx] = false;
             if generator_is_expired[generator_idx] {
                 continue;
             }
 
-            let finished = generators[generator_idx].0.run(&witness, &mut buffer);
+            #[cfg(feature = "timing")]
+            let start = options.time_generators.then(Instant::now);
+            let finished = if generator_idx < generators.len() {
+                generators[generator_idx]
+                    .0
+                    .try_run(&witness, &mut buffer)
+                    .map_err(|err| generator_error(err, prover_data, generator_idx))?
+            } else if generator_idx < first_batch_idx {
+                // Copy generators are simple enough to be run inline, without going through their
+                // dependency list.
+                let copy = &copy_generators[generator_idx - generators.len()].inner;
+                match witness.try_get_target(copy.src) {
+                    Some(value) => {
+                        buffer.set_target(copy.dst, value);
+                        true
+                    }
+                    None => false,
+                }
+            } else {
+                let batch_idx = generator_idx - first_batch_idx;
+                batch_copy_generators[batch_idx].copy_pending(
+                    &witness,
+                    &mut buffer,
+                    &mut pending_copies[batch_idx],
+                )
+            };
+            #[cfg(feature = "timing")]
+            if let Some(start) = start {
+                generator_durations[generator_idx] += start.elapsed();
+            }
             if finished {
                 generator_is_expired[generator_idx] = true;
                 remaining_generators -= 1;
+                progress.update(num_generators - remaining_generators, num_generators);
             }
 
-            // Merge any generated values into our witness, and get a list of newly-populated
-            // targets' representatives.
-            let new_target_reps = buffer
-                .target_values
-                .drain(..)
-                .flat_map(|(t, v)| witness.set_target_returning_rep(t, v));
-
-            // Enqueue unfinished generators that were watching one of the newly populated targets.
-            for watch in new_target_reps {
-                let opt_watchers = generator_indices_by_watches.get(&watch);
-                if let Some(watchers) = opt_watchers {
-                    for &watching_generator_idx in watchers {
-                        if !generator_is_expired[watching_generator_idx] {
-                            next_pending_generator_indices.push(watching_generator_idx);
-                        }
-                    }
+            merge_generated_values(
+                &mut witness,
+                &mut buffer,
+                generator_indices_by_watches,
+                &generator_is_expired,
+                &mut generator_is_queued,
+                &mut next_pending_generator_indices,
+                first_batch_idx,
+                &mut pending_copies,
+            );
+        }
+
+        // The results of the deferred divisions may unblock more generators, which then run in
+        // the next pass.
+        buffer.flush_divisions();
+        merge_generated_values(
+            &mut witness,
+            &mut buffer,
+            generator_indices_by_watches,
+            &generator_is_expired,
+            &mut generator_is_queued,
+            &mut next_pending_generator_indices,
+            first_batch_idx,
+            &mut pending_copies,
+        );
+
+        pending_generator_indices.clear();
+        swap(
+            &mut pending_generator_indices,
+            &mut next_pending_generator_indices,
+        );
+    }
+
+    #[cfg(feature = "timing")]
+    if options.time_generators {
+        let mut durations_by_id = BTreeMap::<String, Duration>::new();
+        for (i, duration) in generator_durations.into_iter().enumerate() {
+            *durations_by_id
+                .entry(prover_data.generator(i).id())
+                .or_default() += duration;
+        }
+        for (id, duration) in durations_by_id
+            .into_iter()
+            .sorted_by_key(|(_, duration)| core::cmp::Reverse(*duration))
+        {
+            timing.push_finished(&id, log::Level::Debug, duration);
+        }
+    }
+
+    if remaining_generators != 0 {
+        return Err(stalled_generation_error(
+            &witness,
+            prover_data,
+            &generator_is_expired,
+        ));
+    }
+    progress.finish();
+
+    Ok(witness)
+}
+
+/// Merges the values generated into `buffer` into `witness`, and queues the unfinished generators
+/// watching one of the newly populated targets. Batches of copies are also told which of their
+/// sources were populated.
+#[allow(clippy::too_many_arguments)]
+fn merge_generated_values<F: Field>(
+    witness: &mut PartitionWitness<F>,
+    buffer: &mut GeneratedValues<F>,
+    generator_indices_by_watches: &BTreeMap<usize, Vec<usize>>,
+    generator_is_expired: &[bool],
+    generator_is_queued: &mut [bool],
+    next_pending_generator_indices: &mut Vec<usize>,
+    first_batch_idx: usize,
+    pending_copies: &mut [PendingCopies],
+) {
+    let new_target_reps = buffer
+        .target_values
+        .drain(..)
+        .flat_map(|(t, v)| witness.set_target_returning_rep(t, v));
+
+    for watch in new_target_reps {
+        if let Some(watchers) = generator_indices_by_watches.get(&watch) {
+            for &watching_generator_idx in watchers {
+                if generator_is_expired[watching_generator_idx] {
+                    continue;
+                }
+                if watching_generator_idx >= first_batch_idx {
+                    pending_copies[watching_generator_idx - first_batch_idx]
+                        .populated_reps
+                        .push(watch);
+                }
+                if !generator_is_queued[watching_generator_idx] {
+                    generator_is_queued[watching_generator_idx] = true;
+                    next_pending_generator_indices.push(watching_generator_idx);
                 }
             }
         }
+    }
+}
 
-        pending_generator_indices = next_pending_generator_indices;
+/// Adds the generator which failed, and the context in which it was created, to `err`.
+fn generator_error<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    err: anyhow::Error,
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    generator_idx: usize,
+) -> anyhow::Error {
+    let id = prover_data.generator(generator_idx).id();
+    match prover_data.generator_context(generator_idx) {
+        Some(context) => err.context(format!("{} created in `{}` failed", id, context)),
+        None => err.context(format!("{} failed", id)),
+    }
+}
+
+/// Builds a post-mortem report for a witness generation which stalled before all generators ran.
+///
+/// To tell missing inputs apart from generators waiting on each other, each stalled generator is
+/// run once on a copy of the witness in which every unset partition holds a dummy value. The
+/// targets it writes tell us which of the missing targets it would have produced.
+fn stalled_generation_error<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    witness: &PartitionWitness<F>,
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    generator_is_expired: &[bool],
+) -> anyhow::Error {
+    let stalled: Vec<usize> = (0..prover_data.num_generators())
+        .filter(|&i| !generator_is_expired[i])
+        .collect();
+    let rep = |t: Target| witness.representative_map[witness.target_index(t)];
+
+    // For each stalled generator, the representatives of the unset targets it is waiting on.
+    let mut missing_reps_by_generator = BTreeMap::new();
+    let mut missing_targets = BTreeMap::new();
+    for &g in &stalled {
+        let mut missing_reps = Vec::new();
+        for t in prover_data.generator(g).watch_list() {
+            if !witness.contains(t) {
+                missing_reps.push(rep(t));
+                missing_targets.entry(rep(t)).or_insert(t);
+            }
+        }
+        missing_reps.sort_unstable();
+        missing_reps.dedup();
+        missing_reps_by_generator.insert(g, missing_reps);
     }
 
-    assert_eq!(
-        remaining_generators, 0,
-        "{} generators weren't run",
-        remaining_generators,
+    let mut probe = witness.clone();
+    probe
+        .values
+        .iter_mut()
+        .filter(|v| v.is_none())
+        .for_each(|v| *v = Some(F::ONE));
+    let mut producers_by_rep = BTreeMap::<usize, Vec<usize>>::new();
+    let mut buffer = GeneratedValues::empty();
+    for &g in &stalled {
+        // The dummy values may be rejected, in which case we learn nothing about this generator.
+        if prover_data
+            .generator(g)
+            .try_run(&probe, &mut buffer)
+            .is_err()
+        {
+            buffer.target_values.clear();
+        }
+        for (t, _) in buffer.target_values.drain(..) {
+            if !witness.contains(t) {
+                producers_by_rep.entry(rep(t)).or_default().push(g);
+            }
+        }
+    }
+
+    let mut msg = format!(
+        "Witness generation stalled: {} generators weren't run.",
+        stalled.len()
     );
 
-    witness
+    let never_set: Vec<(usize, Target)> = missing_targets
+        .into_iter()
+        .filter(|(r, _)| !producers_by_rep.contains_key(r))
+        .collect();
+    if !never_set.is_empty() {
+        msg += &format!(
+            "\n{} targets were never set, and no pending generator would set them:",
+            never_set.len()
+        );
+        for (r, t) in never_set.iter().take(MAX_REPORTED_MISSING_TARGETS) {
+            match prover_data.partition_contexts.get(r) {
+                Some(context) => msg += &format!("\n- {:?}, connected in `{}`", t, context),
+                None => msg += &format!("\n- {:?}", t),
+            }
+        }
+        if never_set.len() > MAX_REPORTED_MISSING_TARGETS {
+            msg += &format!(
+                "\n- ... and {} more",
+                never_set.len() - MAX_REPORTED_MISSING_TARGETS
+            );
+        }
+    }
+
+    // Generator `g` waits on generator `h` if `h` would set one of the targets `g` is missing.
+    let waits_on = |g: usize| -> Vec<usize> {
+        missing_reps_by_generator[&g]
+            .iter()
+            .flat_map(|r| producers_by_rep.get(r).into_iter().flatten().copied())
+            .collect()
+    };
+    if let Some(cycle) = find_cycle(&stalled, waits_on) {
+        msg += "\nPending generators wait on each other in a cycle: ";
+        msg += &cycle
+            .iter()
+            .chain(cycle.first())
+            .map(|&g| prover_data.generator(g).id())
+            .join(" -> ");
+    }
+
+    anyhow!(msg)
+}
+
+/// Finds a cycle in the directed graph over `nodes` given by `edges`, if there is one.
+fn find_cycle(nodes: &[usize], edges: impl Fn(usize) -> Vec<usize>) -> Option<Vec<usize>> {
+    // 1 means "on the current DFS path", 2 means "fully explored".
+    let mut state = BTreeMap::<usize, u8>::new();
+    for &root in nodes {
+        if state.contains_key(&root) {
+            continue;
+        }
+        let mut path = vec![root];
+        let mut stack = vec![edges(root)];
+        state.insert(root, 1);
+        while let Some(children) = stack.last_mut() {
+            match children.pop() {
+                Some(child) => match state.get(&child) {
+                    Some(1) => {
+                        let start = path.iter().position(|&n| n == child).unwrap();
+                        return Some(path[start..].to_vec());
+                    }
+                    Some(_) => {}
+                    None => {
+                        state.insert(child, 1);
+                        path.push(child);
+                        stack.push(edges(child));
+                    }
+                },
+                None => {
+                    state.insert(path.pop().unwrap(), 2);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    None
 }
 
 /// A generator participates in the generation of the witness.
@@ -117,6 +439,24 @@ pub trait WitnessGenerator<F: RichField + Extendable<D>, const D: usize>:
     /// run next time a target in its watch list is populated.
     fn run(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) -> bool;
 
+    /// Like `run`, but returns an error, which aborts witness generation, when the values of the
+    /// watched targets make it impossible to compute a satisfying witness, e.g. when dividing by
+    /// zero. Witness generation calls this method, which defaults to `run`.
+    fn try_run(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<bool> {
+        Ok(self.run(witness, out_buffer))
+    }
+
+    /// Returns a copy of this generator with each of its targets `t` replaced by `map(t)`, which
+    /// lets a `SubCircuitTemplate` stamp it out. Generators which don't support it return `None`,
+    /// and can't be added by the closure a template is recorded from.
+    fn remap_targets(&self, _map: &dyn Fn(Target) -> Target) -> Option<WitnessGeneratorRef<F, D>> {
+        None
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, common_data: &CommonCircuitData<F, D>) -> IoResult<()>;
 
     fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self>
@@ -154,11 +494,17 @@ impl<F: RichField + Extendable<D>, const D: usize> Debug for WitnessGeneratorRef
 #[derive(Debug)]
 pub struct GeneratedValues<F: Field> {
     pub target_values: Vec<(Target, F)>,
+    /// If set, the divisions requested through `set_quotients` are deferred to this inverter, and
+    /// only added to `target_values` by `flush_divisions`.
+    pub(crate) batch_inverter: Option<BatchInverter<F>>,
 }
 
 impl<F: Field> From<Vec<(Target, F)>> for GeneratedValues<F> {
     fn from(target_values: Vec<(Target, F)>) -> Self {
-        Self { target_values }
+        Self {
+            target_values,
+            batch_inverter: None,
+        }
     }
 }
 
@@ -185,6 +531,37 @@ impl<F: Field> GeneratedValues<F> {
         vec![(target, value)].into()
     }
 
+    /// Creates an empty buffer which defers divisions until `flush_divisions` is called.
+    pub(crate) fn with_batch_inversion() -> Self {
+        Self {
+            target_values: Vec::new(),
+            batch_inverter: Some(BatchInverter::default()),
+        }
+    }
+
+    /// Sets each of `targets` to the matching numerator divided by `denominator`, which must be
+    /// nonzero. The division is deferred if this buffer batches inversions.
+    pub(crate) fn set_quotients(&mut self, targets: &[Target], numerators: &[F], denominator: F) {
+        debug_assert_eq!(targets.len(), numerators.len());
+        debug_assert!(denominator.is_nonzero());
+        match &mut self.batch_inverter {
+            Some(inverter) => inverter.push(targets, numerators, denominator),
+            None => {
+                let inverse = denominator.inverse();
+                for (&t, &n) in targets.iter().zip(numerators) {
+                    self.set_target(t, n * inverse);
+                }
+            }
+        }
+    }
+
+    /// Performs all deferred divisions, and adds their results to `target_values`.
+    pub(crate) fn flush_divisions(&mut self) {
+        if let Some(inverter) = &mut self.batch_inverter {
+            inverter.flush(&mut self.target_values);
+        }
+    }
+
     pub fn singleton_extension_target<const D: usize>(
         et: ExtensionTarget<D>,
         value: F::Extension,
@@ -198,6 +575,73 @@ impl<F: Field> GeneratedValues<F> {
     }
 }
 
+/// Divisions deferred by generators, so that their denominators can be inverted all at once with
+/// Montgomery's trick, which costs a single inversion and three multiplications per denominator.
+#[derive(Debug, Default)]
+pub(crate) struct BatchInverter<F: Field> {
+    targets: Vec<Target>,
+    numerators: Vec<F>,
+    /// Each denominator, with the number of consecutive targets divided by it.
+    denominators: Vec<(F, usize)>,
+}
+
+impl<F: Field> BatchInverter<F> {
+    fn push(&mut self, targets: &[Target], numerators: &[F], denominator: F) {
+        self.targets.extend_from_slice(targets);
+        self.numerators.extend_from_slice(numerators);
+        self.denominators.push((denominator, targets.len()));
+    }
+
+    /// Performs the pending divisions, appending their results to `out`.
+    fn flush(&mut self, out: &mut Vec<(Target, F)>) {
+        if self.denominators.is_empty() {
+            return;
+        }
+        let denominators = self.denominators.iter().map(|&(d, _)| d).collect_vec();
+        let inverses = F::batch_multiplicative_inverse(&denominators);
+        let mut quotients = self.targets.drain(..).zip(self.numerators.drain(..));
+        for (inverse, (_, count)) in inverses.into_iter().zip(self.denominators.drain(..)) {
+            out.extend(
+                quotients
+                    .by_ref()
+                    .take(count)
+                    .map(|(t, numerator)| (t, numerator * inverse)),
+            );
+        }
+    }
+}
+
+/// An error returned by a generator whose dependencies hold values from which no satisfying
+/// witness can be computed, e.g. a zero denominator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GenerationError<F: Field> {
+    /// What went wrong.
+    pub reason: String,
+    /// The targets the generator read, with the values it observed.
+    pub observed: Vec<(Target, F)>,
+}
+
+impl<F: Field> core::fmt::Display for GenerationError<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.reason)?;
+        for (i, (target, value)) in self.observed.iter().enumerate() {
+            f.write_str(if i == 0 { "; observed " } else { ", " })?;
+            match target {
+                Target::Wire(Wire { row, column }) => {
+                    write!(f, "wire {} of row {} = {}", column, row, value)?
+                }
+                Target::VirtualTarget { index } => {
+                    write!(f, "virtual target {} = {}", index, value)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: Field> std::error::Error for GenerationError<F> {}
+
 /// A generator which runs once after a list of dependencies is present in the witness.
 pub trait SimpleGenerator<F: RichField + Extendable<D>, const D: usize>:
     'static + Send + Sync + Debug
@@ -208,6 +652,17 @@ pub trait SimpleGenerator<F: RichField + Extendable<D>, const D: usize>:
 
     fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>);
 
+    /// Like `run_once`, but returns an error, which aborts witness generation, when the values of
+    /// the dependencies make it impossible to compute a satisfying witness. Defaults to `run_once`.
+    fn try_run_once(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<()> {
+        self.run_once(witness, out_buffer);
+        Ok(())
+    }
+
     fn adapter(self) -> SimpleGeneratorAdapter<F, Self, D>
     where
         Self: Sized,
@@ -218,6 +673,15 @@ pub trait SimpleGenerator<F: RichField + Extendable<D>, const D: usize>:
         }
     }
 
+    /// Returns a copy of this generator with each of its targets `t` replaced by `map(t)`. See
+    /// `WitnessGenerator::remap_targets`.
+    fn remap_targets(&self, _map: &dyn Fn(Target) -> Target) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, common_data: &CommonCircuitData<F, D>) -> IoResult<()>;
 
     fn deserialize(src: &mut Buffer, common_data: &CommonCircuitData<F, D>) -> IoResult<Self>
@@ -225,14 +689,14 @@ pub trait SimpleGenerator<F: RichField + Extendable<D>, const D: usize>:
         Self: Sized;
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct SimpleGeneratorAdapter<
     F: RichField + Extendable<D>,
     SG: SimpleGenerator<F, D> + ?Sized,
     const D: usize,
 > {
     _phantom: PhantomData<F>,
-    inner: SG,
+    pub(crate) inner: SG,
 }
 
 impl<F: RichField + Extendable<D>, SG: SimpleGenerator<F, D>, const D: usize> WitnessGenerator<F, D>
@@ -255,6 +719,24 @@ impl<F: RichField + Extendable<D>, SG: SimpleGenerator<F, D>, const D: usize> Wi
         }
     }
 
+    fn try_run(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+    ) -> Result<bool> {
+        if witness.contains_all(&self.inner.dependencies()) {
+            self.inner.try_run_once(witness, out_buffer)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<WitnessGeneratorRef<F, D>> {
+        let inner = self.inner.remap_targets(map)?;
+        Some(WitnessGeneratorRef::new(inner.adapter()))
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
         self.inner.serialize(dst, common_data)
     }
@@ -268,7 +750,7 @@ impl<F: RichField + Extendable<D>, SG: SimpleGenerator<F, D>, const D: usize> Wi
 }
 
 /// A generator which copies one wire to another.
-#[derive(Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct CopyGenerator {
     pub(crate) src: Target,
     pub(crate) dst: Target,
@@ -288,6 +770,13 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Cop
         out_buffer.set_target(self.dst, value);
     }
 
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<Self> {
+        Some(Self {
+            src: map(self.src),
+            dst: map(self.dst),
+        })
+    }
+
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
         dst.write_target(self.src)?;
         dst.write_target(self.dst)
@@ -300,6 +789,113 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Cop
     }
 }
 
+/// A generator which copies many targets at once. Unlike a `SimpleGenerator`, it makes partial
+/// progress: each destination is set as soon as its source is available, exactly as if it had its
+/// own `CopyGenerator`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CopiesGenerator {
+    pub(crate) pairs: Vec<(Target, Target)>,
+}
+
+/// The progress of a `CopiesGenerator` during witness generation.
+#[derive(Clone, Debug, Default)]
+struct PendingCopies {
+    /// Whether the generator has run, i.e. scanned all its pairs, at least once.
+    started: bool,
+    /// The indices of the pairs not copied yet, by the representative of their source.
+    pairs_by_source_rep: BTreeMap<usize, Vec<usize>>,
+    /// The representatives of the sources populated since the generator last ran.
+    populated_reps: Vec<usize>,
+}
+
+impl CopiesGenerator {
+    /// Like `run`, but only copies the pairs whose source was populated since the previous call,
+    /// so that each pair is copied once however often the generator is woken. Returns whether all
+    /// pairs have been copied.
+    fn copy_pending<F: Field>(
+        &self,
+        witness: &PartitionWitness<F>,
+        out_buffer: &mut GeneratedValues<F>,
+        pending: &mut PendingCopies,
+    ) -> bool {
+        if !pending.started {
+            pending.started = true;
+            pending.populated_reps.clear();
+            out_buffer.target_values.reserve(self.pairs.len());
+            for (i, &(src, dst)) in self.pairs.iter().enumerate() {
+                match witness.try_get_target(src) {
+                    Some(value) => out_buffer.set_target(dst, value),
+                    None => {
+                        let src_rep = witness.representative_map[witness.target_index(src)];
+                        pending
+                            .pairs_by_source_rep
+                            .entry(src_rep)
+                            .or_default()
+                            .push(i);
+                    }
+                }
+            }
+        } else {
+            for rep in pending.populated_reps.drain(..) {
+                for i in pending.pairs_by_source_rep.remove(&rep).unwrap_or_default() {
+                    let (src, dst) = self.pairs[i];
+                    out_buffer.set_target(dst, witness.get_target(src));
+                }
+            }
+        }
+        pending.pairs_by_source_rep.is_empty()
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> WitnessGenerator<F, D> for CopiesGenerator {
+    fn id(&self) -> String {
+        "CopiesGenerator".to_string()
+    }
+
+    fn watch_list(&self) -> Vec<Target> {
+        self.pairs.iter().map(|&(src, _)| src).collect()
+    }
+
+    fn run(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) -> bool {
+        out_buffer.target_values.reserve(self.pairs.len());
+        let mut finished = true;
+        // Pairs copied in a previous run are simply copied again, which leaves the witness as is.
+        for &(src, dst) in &self.pairs {
+            match witness.try_get_target(src) {
+                Some(value) => out_buffer.set_target(dst, value),
+                None => finished = false,
+            }
+        }
+        finished
+    }
+
+    fn remap_targets(&self, map: &dyn Fn(Target) -> Target) -> Option<WitnessGeneratorRef<F, D>> {
+        let pairs = self
+            .pairs
+            .iter()
+            .map(|&(src, dst)| (map(src), map(dst)))
+            .collect();
+        Some(WitnessGeneratorRef::new(Self { pairs }))
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_usize(self.pairs.len())?;
+        for &(src, target) in &self.pairs {
+            dst.write_target(src)?;
+            dst.write_target(target)?;
+        }
+        Ok(())
+    }
+
+    fn deserialize(source: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self> {
+        let len = source.read_usize()?;
+        let pairs = (0..len)
+            .map(|_| Ok((source.read_target()?, source.read_target()?)))
+            .collect::<IoResult<_>>()?;
+        Ok(Self { pairs })
+    }
+}
+
 /// A generator for including a random value
 #[derive(Debug, Default)]
 pub struct RandomValueGenerator {
@@ -315,8 +911,8 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Ran
         Vec::new()
     }
 
-    fn run_once(&self, _witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
-        let random_value = F::rand();
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let random_value = witness.random_value(self.target);
         out_buffer.set_target(self.target, random_value);
     }
 
@@ -349,13 +945,11 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Non
     fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
         let to_test_value = witness.get_target(self.to_test);
 
-        let dummy_value = if to_test_value == F::ZERO {
-            F::ONE
+        if to_test_value == F::ZERO {
+            out_buffer.set_target(self.dummy, F::ONE);
         } else {
-            to_test_value.inverse()
-        };
-
-        out_buffer.set_target(self.dummy, dummy_value);
+            out_buffer.set_quotients(&[self.dummy], &[F::ONE], to_test_value);
+        }
     }
 
     fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
@@ -418,3 +1012,271 @@ impl<F: RichField + Extendable<D>, const D: usize> SimpleGenerator<F, D> for Con
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::poseidon::PoseidonHash;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{Hasher, PoseidonGoldilocksConfig};
+    use crate::util::serialization::DefaultGeneratorSerializer;
+    use crate::with_context;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn inspect_intermediate_values() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let product = builder.mul(x, y);
+        let hash = builder.hash_n_to_hash_no_pad::<PoseidonHash>(vec![x, product]);
+        let x_ext = builder.convert_to_ext(x);
+        let y_ext = builder.convert_to_ext(y);
+        let product_ext = builder.add_extension(x_ext, y_ext);
+        builder.register_public_input(hash.elements[0]);
+        let data = builder.build::<C>();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::from_canonical_u32(6));
+        inputs.set_target(y, F::from_canonical_u32(7));
+        let witness = data.generate_witness(inputs)?;
+
+        assert_eq!(witness.get_target(product), F::from_canonical_u32(42));
+        assert_eq!(
+            witness.try_get_target(product),
+            Some(F::from_canonical_u32(42))
+        );
+        assert_eq!(
+            witness.get_hash_target(hash),
+            PoseidonHash::hash_no_pad(&[F::from_canonical_u32(6), F::from_canonical_u32(42)])
+        );
+        assert_eq!(
+            witness.get_extension_target(product_ext),
+            <F as Extendable<D>>::Extension::from_canonical_u32(13)
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn queued_generation_matches_exhaustive_rescan() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let mut acc = builder.mul(x, y);
+        for _ in 0..20 {
+            acc = builder
+                .hash_n_to_hash_no_pad::<PoseidonHash>(vec![acc, x])
+                .elements[0];
+            acc = builder.mul_add(acc, y, x);
+        }
+        let copies = builder.add_virtual_targets(4);
+        builder.generate_copies(&[acc; 4], &copies);
+        let quotient = builder.div(copies[3], y);
+        builder.register_public_input(quotient);
+        let data = builder.build::<C>();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::from_canonical_u32(3));
+        inputs.set_target(y, F::from_canonical_u32(5));
+        // Seed random values so that both witnesses get the same ones.
+        let options = ProverOptions {
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        let witness = generate_partial_witness_with_options(
+            inputs.clone(),
+            &data.prover_only,
+            &data.common,
+            &options,
+            &mut TimingTree::default(),
+        )?;
+
+        // Run every unfinished generator over and over until none of them makes progress.
+        let prover_data = &data.prover_only;
+        let mut reference = PartitionWitness::new(
+            data.common.config.num_wires,
+            data.common.degree(),
+            &prover_data.representative_map,
+        );
+        reference.rng_seed = options.rng_seed;
+        for (t, v) in inputs.target_values {
+            reference.set_target(t, v);
+        }
+        let mut expired = vec![false; prover_data.num_generators()];
+        let mut buffer = GeneratedValues::empty();
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for (i, expired) in expired.iter_mut().enumerate() {
+                if !*expired {
+                    *expired = prover_data.generator(i).try_run(&reference, &mut buffer)?;
+                    for (t, v) in buffer.target_values.drain(..) {
+                        progress |= reference.set_target_returning_rep(t, v).is_some();
+                    }
+                }
+            }
+        }
+
+        assert!(expired.iter().all(|&e| e));
+        assert_eq!(witness.values, reference.values);
+        Ok(())
+    }
+
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn batched_inversions_match_eager_inversions() -> Result<()> {
+        use crate::field::types::Sample;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let xs = builder.add_virtual_targets(16);
+        let ys = builder.add_virtual_extension_targets(16);
+        let mut x_invs = vec![];
+        for (i, (&x, &y)) in xs.iter().zip(&ys).enumerate() {
+            // Each inverse unblocks the next one, so the divisions span several passes.
+            let x_inv = builder.inverse(x);
+            let x_inv_inv = builder.inverse(x_inv);
+            builder.connect(x_inv_inv, x);
+            builder.div(xs[(i + 1) % xs.len()], x_inv);
+            let x_ext = builder.convert_to_ext(x);
+            builder.div_extension(x_ext, y);
+            builder.is_equal(x, xs[0]);
+            x_invs.push(x_inv);
+        }
+        let data = builder.build::<C>();
+
+        let mut inputs = PartialWitness::new();
+        for (i, (&x, &y)) in xs.iter().zip(&ys).enumerate() {
+            inputs.set_target(x, F::from_canonical_usize(i + 1));
+            inputs.set_extension_target(y, <F as Extendable<D>>::Extension::rand());
+        }
+        let witness = |eager_inversions| {
+            generate_partial_witness_with_options(
+                inputs.clone(),
+                &data.prover_only,
+                &data.common,
+                &ProverOptions {
+                    rng_seed: Some(0),
+                    eager_inversions,
+                    ..Default::default()
+                },
+                &mut TimingTree::default(),
+            )
+        };
+        let (batched, eager) = (witness(false)?, witness(true)?);
+        assert_eq!(batched.values, eager.values);
+        assert_eq!(batched.get_target(x_invs[1]), F::TWO.inverse());
+        Ok(())
+    }
+
+    #[test]
+    fn batched_copies_match_single_copies() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let srcs = builder.add_virtual_targets(8);
+        let single_dsts = builder.add_virtual_targets(8);
+        let batched_dsts = builder.add_virtual_targets(8);
+        for (&src, &dst) in srcs.iter().zip(&single_dsts) {
+            builder.generate_copy(src, dst);
+        }
+        builder.generate_copies(&srcs, &batched_dsts);
+        let data = builder.build::<C>();
+
+        let mut inputs = PartialWitness::new();
+        for (i, &src) in srcs.iter().enumerate() {
+            inputs.set_target(src, F::from_canonical_usize(i * i + 1));
+        }
+        let witness = data.generate_witness(inputs)?;
+        assert_eq!(
+            witness.get_targets(&batched_dsts),
+            witness.get_targets(&single_dsts)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn batched_copies_make_partial_progress() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let [a, b, d] = [(); 3].map(|_| builder.add_virtual_target());
+        // `c` can only be computed once the batch has copied `a` to `b`.
+        let c = builder.square(b);
+        builder.generate_copies(&[a, c], &[b, d]);
+        builder.register_public_input(d);
+        let data = builder.build::<C>();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(a, F::from_canonical_u32(5));
+        let witness = data.generate_witness(inputs)?;
+        assert_eq!(witness.get_target(d), F::from_canonical_u32(25));
+
+        let generator_serializer = DefaultGeneratorSerializer::<C, D>::default();
+        let bytes = data
+            .prover_only
+            .to_bytes(&generator_serializer, &data.common)
+            .unwrap();
+        let prover_only = ProverOnlyCircuitData::<F, C, D>::from_bytes(
+            &bytes,
+            &generator_serializer,
+            &data.common,
+        )
+        .unwrap();
+        assert_eq!(prover_only, data.prover_only);
+        Ok(())
+    }
+
+    #[test]
+    fn stalled_generation_names_context() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = with_context!(builder, "square the unset input", builder.mul(x, x));
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let err = generate_partial_witness(PartialWitness::new(), &data.prover_only, &data.common)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("never set"), "{}", err);
+        assert!(err.contains("square the unset input"), "{}", err);
+        assert!(!err.contains("cycle"), "{}", err);
+    }
+
+    #[test]
+    fn stalled_generation_reports_cycle() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.generate_copy(x, y);
+        builder.generate_copy(y, x);
+        let z = builder.add(x, y);
+        builder.register_public_input(z);
+        let data = builder.build::<C>();
+
+        let err = generate_partial_witness(PartialWitness::new(), &data.prover_only, &data.common)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("cycle: CopyGenerator -> CopyGenerator -> CopyGenerator"),
+            "{}",
+            err
+        );
+        assert!(!err.contains("never set"), "{}", err);
+    }
+
+    #[test]
+    fn find_cycle_ignores_dags() {
+        let edges = |n: usize| if n < 3 { vec![n + 1] } else { vec![] };
+        assert_eq!(find_cycle(&[0, 1, 2, 3], edges), None);
+        let edges = |n: usize| vec![(n + 1) % 3];
+        assert_eq!(find_cycle(&[0, 1, 2], edges), Some(vec![0, 1, 2]));
+    }
+}
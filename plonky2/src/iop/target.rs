@@ -41,6 +41,14 @@ impl Target {
         Self::Wire(Wire { row, column })
     }
 
+    /// The row of this target, if it is a wire.
+    pub const fn row(&self) -> Option<usize> {
+        match self {
+            Self::Wire(wire) => Some(wire.row),
+            Self::VirtualTarget { .. } => None,
+        }
+    }
+
     pub const fn is_routable(&self, config: &CircuitConfig) -> bool {
         match self {
             Target::Wire(wire) => wire.is_routable(config),
@@ -59,6 +67,17 @@ impl Target {
         }
     }
 
+    /// The inverse of `index`: returns the target with the given index.
+    pub const fn from_index(index: usize, num_wires: usize, degree: usize) -> Self {
+        if index < degree * num_wires {
+            Target::wire(index / num_wires, index % num_wires)
+        } else {
+            Target::VirtualTarget {
+                index: index - degree * num_wires,
+            }
+        }
+    }
+
     /// Conversion to an `ExtensionTarget`.
     pub const fn to_ext_target<const D: usize>(self, zero: Self) -> ExtensionTarget<D> {
         let mut arr = [zero; D];
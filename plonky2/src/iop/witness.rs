@@ -1,8 +1,17 @@
 #[cfg(not(feature = "std"))]
-use alloc::{vec, vec::Vec};
-
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, ensure, Result};
 use hashbrown::HashMap;
 use itertools::{zip_eq, Itertools};
+use plonky2_util::ceil_div_usize;
 
 use crate::field::extension::{Extendable, FieldExtension};
 use crate::field::types::Field;
@@ -13,9 +22,15 @@ use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::target::{BoolTarget, Target};
 use crate::iop::wire::Wire;
-use crate::plonk::circuit_data::{VerifierCircuitTarget, VerifierOnlyCircuitData};
+use crate::plonk::circuit_data::{
+    CommonCircuitData, ProverOnlyCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
+};
 use crate::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
-use crate::plonk::proof::{Proof, ProofTarget, ProofWithPublicInputs, ProofWithPublicInputsTarget};
+use crate::plonk::proof::{
+    CompressedProofWithPublicInputs, Proof, ProofTarget, ProofWithPublicInputs,
+    ProofWithPublicInputsTarget, PublicInputChunkTarget, PublicInputChunksTarget,
+};
+use crate::util::serialization::{Buffer, Read, Write};
 
 pub trait WitnessWrite<F: Field> {
     fn set_target(&mut self, target: Target, value: F);
@@ -80,6 +95,7 @@ pub trait WitnessWrite<F: Field> {
         let ProofWithPublicInputs {
             proof,
             public_inputs,
+            circuit_digest_hint: _,
         } = proof_with_pis;
         let ProofWithPublicInputsTarget {
             proof: pt,
@@ -94,6 +110,55 @@ pub trait WitnessWrite<F: Field> {
         self.set_proof_target(pt, proof);
     }
 
+    /// Sets the targets in a `PublicInputChunksTarget` from all the public inputs of a proof: the
+    /// public inputs of the inspected chunks, and the digests of the others.
+    fn set_public_input_chunks_target<C: GenericConfig<D, F = F>, const D: usize>(
+        &mut self,
+        public_input_chunks: &PublicInputChunksTarget,
+        public_inputs: &[F],
+        common_data: &CommonCircuitData<F, D>,
+    ) where
+        F: RichField + Extendable<D>,
+    {
+        let chunk_ranges = common_data
+            .public_input_commitment
+            .chunk_ranges(public_inputs.len());
+        for (chunk, range) in public_input_chunks.chunks.iter().zip_eq(chunk_ranges) {
+            match chunk {
+                PublicInputChunkTarget::Inputs(targets) => {
+                    self.set_target_arr(targets, &public_inputs[range]);
+                }
+                &PublicInputChunkTarget::Digest(digest) => {
+                    self.set_hash_target(
+                        digest,
+                        C::InnerHasher::hash_no_pad(&public_inputs[range]),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Set the targets in a `ProofWithPublicInputsTarget` from a compressed proof. The recursive
+    /// verifier has a slot for every FRI query round, so the Merkle paths that compression shares
+    /// between repeated query indices are restored first.
+    fn set_compressed_proof_with_pis_target<C: GenericConfig<D, F = F>, const D: usize>(
+        &mut self,
+        proof_with_pis_target: &ProofWithPublicInputsTarget<D>,
+        compressed_proof_with_pis: &CompressedProofWithPublicInputs<F, C, D>,
+        verifier_data: &VerifierOnlyCircuitData<C, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Result<()>
+    where
+        F: RichField + Extendable<D>,
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let proof_with_pis = compressed_proof_with_pis
+            .clone()
+            .decompress(&verifier_data.circuit_digest, common_data)?;
+        self.set_proof_with_pis_target(proof_with_pis_target, &proof_with_pis);
+        Ok(())
+    }
+
     /// Set the targets in a `ProofTarget` to their corresponding values in a `Proof`.
     fn set_proof_target<C: GenericConfig<D, F = F>, const D: usize>(
         &mut self,
@@ -274,6 +339,47 @@ impl<F: Field> PartialWitness<F> {
             target_values: HashMap::new(),
         }
     }
+
+    /// Assigns `values` to the group of inputs declared as `name` in `schema`.
+    pub fn set_by_name(&mut self, schema: &WitnessSchema, name: &str, values: &[F]) -> Result<()> {
+        let targets = schema
+            .get(name)
+            .ok_or_else(|| anyhow!("No input named `{}` was declared", name))?;
+        ensure!(
+            targets.len() == values.len(),
+            "Input `{}` has {} targets, but {} values were given",
+            name,
+            targets.len(),
+            values.len()
+        );
+        self.set_target_arr(targets, values);
+        Ok(())
+    }
+}
+
+/// Named groups of input targets, declared with `CircuitBuilder::declare_input`, which let inputs
+/// be assigned by name once the circuit is built.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WitnessSchema {
+    pub(crate) inputs: BTreeMap<String, Vec<Target>>,
+}
+
+impl WitnessSchema {
+    /// Records a group of inputs. Panics if an input with the same name was already declared.
+    pub(crate) fn declare(&mut self, name: &str, targets: &[Target]) {
+        let previous = self.inputs.insert(name.to_string(), targets.to_vec());
+        assert!(previous.is_none(), "Input `{}` was declared twice", name);
+    }
+
+    /// The targets of the input named `name`, if it was declared.
+    pub fn get(&self, name: &str) -> Option<&[Target]> {
+        self.inputs.get(name).map(Vec::as_slice)
+    }
+
+    /// The names of all declared inputs, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.inputs.keys().map(String::as_str)
+    }
 }
 
 impl<F: Field> WitnessWrite<F> for PartialWitness<F> {
@@ -303,6 +409,10 @@ pub struct PartitionWitness<'a, F: Field> {
     pub representative_map: &'a [usize],
     pub num_wires: usize,
     pub degree: usize,
+    /// If set, random values requested during witness generation are derived from this seed
+    /// rather than from OS randomness.
+    #[cfg(feature = "rand_chacha")]
+    pub(crate) rng_seed: Option<u64>,
 }
 
 impl<'a, F: Field> PartitionWitness<'a, F> {
@@ -312,9 +422,28 @@ impl<'a, F: Field> PartitionWitness<'a, F> {
             representative_map,
             num_wires,
             degree,
+            #[cfg(feature = "rand_chacha")]
+            rng_seed: None,
         }
     }
 
+    /// Samples a random value for `target`. When the witness was seeded, the value only depends on
+    /// the seed and the target, so it doesn't matter in which order generators ask for them.
+    #[cfg_attr(not(feature = "rand_chacha"), allow(unused_variables))]
+    pub(crate) fn random_value(&self, target: Target) -> F {
+        #[cfg(feature = "rand_chacha")]
+        if let Some(seed) = self.rng_seed {
+            use rand::SeedableRng;
+            use rand_chacha::ChaCha8Rng;
+
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream(self.target_index(target) as u64);
+            return F::sample(&mut rng);
+        }
+
+        F::rand()
+    }
+
     /// Set a `Target`. On success, returns the representative index of the newly-set target. If the
     /// target was already set, returns `None`.
     pub fn set_target_returning_rep(&mut self, target: Target, value: F) -> Option<usize> {
@@ -352,6 +481,103 @@ impl<'a, F: Field> PartitionWitness<'a, F> {
     }
 }
 
+impl<'a, F: RichField> PartitionWitness<'a, F> {
+    /// Serializes this witness, which must have been generated for the circuit described by
+    /// `prover_data`, so that proving can later resume from it with `prove_with_witness` instead of
+    /// running witness generation again.
+    ///
+    /// The encoding starts with the circuit digest, followed by a bitmap of the partitions which
+    /// are set and their values. Partitions are listed column by column, followed by virtual
+    /// targets.
+    pub fn to_bytes<C: GenericConfig<D, F = F>, const D: usize>(
+        &self,
+        prover_data: &ProverOnlyCircuitData<F, C, D>,
+    ) -> Vec<u8>
+    where
+        F: Extendable<D>,
+    {
+        let values = self
+            .partition_order()
+            .map(|i| self.values[i])
+            .collect::<Vec<_>>();
+        let mut bitmap = vec![0u8; ceil_div_usize(values.len(), 8)];
+        for (i, value) in values.iter().enumerate() {
+            if value.is_some() {
+                bitmap[i / 8] |= 1 << (i % 8);
+            }
+        }
+
+        let mut buffer = Vec::new();
+        buffer
+            .write_hash::<F, C::Hasher>(prover_data.circuit_digest)
+            .and_then(|_| buffer.write_usize(self.values.len()))
+            .and_then(|_| buffer.write_all(&bitmap))
+            .and_then(|_| buffer.write_field_vec(&values.into_iter().flatten().collect_vec()))
+            .expect("Writing to a byte-vector cannot fail.");
+        buffer
+    }
+
+    /// Deserializes a witness written by `to_bytes`. Fails if the witness was generated for a
+    /// different circuit.
+    pub fn from_bytes<C: GenericConfig<D, F = F>, const D: usize>(
+        bytes: &[u8],
+        prover_data: &'a ProverOnlyCircuitData<F, C, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Result<Self>
+    where
+        F: Extendable<D>,
+    {
+        let mut witness = Self::new(
+            common_data.config.num_wires,
+            common_data.degree(),
+            &prover_data.representative_map,
+        );
+        let mut buffer = Buffer::new(bytes);
+
+        let circuit_digest = buffer
+            .read_hash::<F, C::Hasher>()
+            .map_err(anyhow::Error::msg)?;
+        ensure!(
+            circuit_digest == prover_data.circuit_digest,
+            "The witness was generated for a different circuit"
+        );
+        let num_values = buffer.read_usize().map_err(anyhow::Error::msg)?;
+        ensure!(
+            num_values == witness.values.len(),
+            "Expected a witness with {} values, got {}",
+            witness.values.len(),
+            num_values
+        );
+
+        let order = witness.partition_order().collect_vec();
+        let mut bitmap = vec![0u8; ceil_div_usize(order.len(), 8)];
+        buffer.read_exact(&mut bitmap).map_err(anyhow::Error::msg)?;
+        for (i, rep) in order.into_iter().enumerate() {
+            if bitmap[i / 8] >> (i % 8) & 1 == 1 {
+                witness.values[rep] = Some(buffer.read_field().map_err(anyhow::Error::msg)?);
+            }
+        }
+        ensure!(
+            buffer.unread_bytes().is_empty(),
+            "Trailing bytes after the witness"
+        );
+
+        Ok(witness)
+    }
+
+    /// The indices of partition representatives, wires first in column-major order, then virtual
+    /// targets.
+    fn partition_order(&self) -> impl Iterator<Item = usize> + '_ {
+        let wires = (0..self.num_wires).flat_map(move |column| {
+            (0..self.degree).map(move |row| self.target_index(Target::wire(row, column)))
+        });
+        let virtual_targets = self.num_wires * self.degree..self.values.len();
+        wires
+            .chain(virtual_targets)
+            .filter(|&i| self.representative_map[i] == i)
+    }
+}
+
 impl<'a, F: Field> WitnessWrite<F> for PartitionWitness<'a, F> {
     fn set_target(&mut self, target: Target, value: F) {
         self.set_target_returning_rep(target, value);
@@ -364,3 +590,114 @@ impl<'a, F: Field> Witness<F> for PartitionWitness<'a, F> {
         self.values[rep_index]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+    use crate::util::serialization::DefaultGeneratorSerializer;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn square_circuit(num_squarings: usize) -> (CircuitData<F, C, D>, Target) {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let mut y = x;
+        for _ in 0..num_squarings {
+            y = builder.square(y);
+        }
+        builder.register_public_input(y);
+        (builder.build::<C>(), x)
+    }
+
+    #[test]
+    fn witness_round_trip() -> Result<()> {
+        let (data, x) = square_circuit(3);
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::from_canonical_u32(3));
+        let witness = data.generate_witness(inputs)?;
+
+        let bytes = witness.to_bytes(&data.prover_only);
+        let restored = PartitionWitness::from_bytes(&bytes, &data.prover_only, &data.common)?;
+        assert_eq!(restored.values, witness.values);
+
+        let proof = data.prove_with_witness(restored)?;
+        assert_eq!(proof.public_inputs, vec![F::from_canonical_u32(6561)]);
+        data.verify(proof)
+    }
+
+    #[test]
+    fn witness_for_another_circuit_is_rejected() -> Result<()> {
+        let (data, x) = square_circuit(3);
+        let (other_data, _) = square_circuit(4);
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::from_canonical_u32(3));
+        let bytes = data.generate_witness(inputs)?.to_bytes(&data.prover_only);
+
+        let err = PartitionWitness::from_bytes(&bytes, &other_data.prover_only, &other_data.common)
+            .unwrap_err();
+        assert!(err.to_string().contains("different circuit"));
+        Ok(())
+    }
+
+    #[test]
+    fn prove_with_named_inputs() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let amounts = builder.add_virtual_targets(3);
+        let fee = builder.add_virtual_target();
+        builder.declare_input("amounts", &amounts);
+        builder.declare_input("fee", &[fee]);
+        let total = builder.add_many(&amounts);
+        let total = builder.sub(total, fee);
+        builder.register_public_input(total);
+        let data = builder.build::<C>();
+
+        let generator_serializer = DefaultGeneratorSerializer::<C, D>::default();
+        let bytes = data
+            .prover_only
+            .to_bytes(&generator_serializer, &data.common)
+            .unwrap();
+        let prover_only = ProverOnlyCircuitData::<F, C, D>::from_bytes(
+            &bytes,
+            &generator_serializer,
+            &data.common,
+        )
+        .unwrap();
+        let schema = &prover_only.witness_schema;
+        assert_eq!(schema, &data.prover_only.witness_schema);
+        assert_eq!(schema.names().collect_vec(), vec!["amounts", "fee"]);
+
+        let mut inputs = PartialWitness::new();
+        assert!(inputs
+            .set_by_name(schema, "amounts", &[F::ONE, F::TWO])
+            .is_err());
+        assert!(inputs.set_by_name(schema, "recipient", &[F::ONE]).is_err());
+        inputs.set_by_name(
+            schema,
+            "amounts",
+            &[F::ONE, F::TWO, F::from_canonical_u32(10)],
+        )?;
+        inputs.set_by_name(schema, "fee", &[F::from_canonical_u32(4)])?;
+
+        let proof = data.prove(inputs)?;
+        assert_eq!(proof.public_inputs, vec![F::from_canonical_u32(9)]);
+        data.verify(proof)
+    }
+
+    #[test]
+    #[should_panic(expected = "declared twice")]
+    fn duplicate_input_names_are_rejected() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.declare_input("x", &[x]);
+        builder.declare_input("x", &[y]);
+    }
+}
@@ -1,57 +1,89 @@
 //! Logic for building plonky2 circuits.
 
 #[cfg(not(feature = "std"))]
-use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
 use core::cmp::max;
+use core::ops::{Deref, DerefMut, Range};
 #[cfg(feature = "std")]
-use std::{collections::BTreeMap, sync::Arc, time::Instant};
+use std::{collections::BTreeMap, sync::Arc};
 
 use hashbrown::{HashMap, HashSet};
 use itertools::Itertools;
 use log::{debug, info, warn, Level};
+use plonky2_maybe_rayon::*;
 use plonky2_util::ceil_div_usize;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "timing")]
+use web_time::Instant;
 
 use crate::field::cosets::get_unique_coset_shifts;
 use crate::field::extension::{Extendable, FieldExtension};
-use crate::field::fft::fft_root_table;
+use crate::field::fft::{fft_root_table, FftRootTable};
 use crate::field::polynomial::PolynomialValues;
 use crate::field::types::Field;
 use crate::fri::oracle::PolynomialBatch;
-use crate::fri::{FriConfig, FriParams};
+use crate::fri::reduction_strategies::FriReductionStrategy;
+use crate::fri::FriParams;
 use crate::gadgets::arithmetic::BaseArithmeticOperation;
-use crate::gadgets::arithmetic_extension::ExtensionArithmeticOperation;
+use crate::gadgets::arithmetic_extension::{
+    ExtensionArithmeticOperation, ScalarArithmeticOperation,
+};
 use crate::gadgets::polynomial::PolynomialCoeffsExtTarget;
+use crate::gadgets::range_check::RangeCheckService;
 use crate::gates::arithmetic_base::ArithmeticGate;
 use crate::gates::arithmetic_extension::ArithmeticExtensionGate;
 use crate::gates::constant::ConstantGate;
-use crate::gates::gate::{CurrentSlot, Gate, GateInstance, GateRef};
+use crate::gates::constant_table::ConstantTableGate;
+use crate::gates::gate::{AnyGate, CurrentSlot, Gate, GateInstance, GateRef};
 use crate::gates::lookup::{Lookup, LookupGate};
-use crate::gates::lookup_table::LookupTable;
+use crate::gates::lookup_table::{LookupTable, LookupTableGate};
 use crate::gates::noop::NoopGate;
 use crate::gates::public_input::PublicInputGate;
-use crate::gates::selectors::{selector_ends_lookups, selector_polynomials, selectors_lookup};
-use crate::hash::hash_types::{HashOut, HashOutTarget, MerkleCapTarget, RichField};
+use crate::gates::selectors::{
+    min_quotient_degree_factor, selector_ends_lookups, selector_groups, selector_polynomials,
+    selectors_lookup, LookupSelectors, SelectorsInfo,
+};
+use crate::hash::hash_types::{
+    HashOut, HashOutTarget, MerkleCapTarget, RichField, NUM_HASH_OUT_ELTS,
+};
+use crate::hash::hashing::PlonkyPermutation;
 use crate::hash::merkle_proofs::MerkleProofTarget;
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::generator::{
-    ConstantGenerator, CopyGenerator, RandomValueGenerator, SimpleGenerator, WitnessGeneratorRef,
+    ConstantGenerator, CopiesGenerator, CopyGenerator, RandomValueGenerator, SimpleGenerator,
+    WitnessGeneratorRef,
 };
 use crate::iop::target::{BoolTarget, Target};
 use crate::iop::wire::Wire;
+use crate::iop::witness::WitnessSchema;
 use crate::plonk::circuit_data::{
-    CircuitConfig, CircuitData, CommonCircuitData, MockCircuitData, ProverCircuitData,
-    ProverOnlyCircuitData, VerifierCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
+    CircuitConfig, CircuitData, CommonCircuitData, MockCircuitData, OpeningPoint, OracleCapHeights,
+    PartialProductsInfo, ProverCircuitData, ProverOnlyCircuitData, PublicInputCommitment,
+    VerifierCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
 };
 use crate::plonk::config::{AlgebraicHasher, GenericConfig, GenericHashOut, Hasher};
 use crate::plonk::copy_constraint::CopyConstraint;
+use crate::plonk::cost_report::CostReport;
 use crate::plonk::permutation_argument::Forest;
 use crate::plonk::plonk_common::PlonkOracle;
+use crate::plonk::progress::{PhaseProgress, ProgressCallback, ProverPhase};
+use crate::plonk::row_attribution::RowContextLog;
+use crate::plonk::sub_circuit::{RecordedOp, Recording};
 use crate::timed;
 use crate::util::context_tree::ContextTree;
+#[cfg(feature = "std")]
+use crate::util::fft_root_table_cache::FftRootTableCache;
 use crate::util::partial_products::num_partial_products;
 use crate::util::timing::TimingTree;
-use crate::util::{log2_ceil, log2_strict, transpose, transpose_poly_values};
+use crate::util::{log2_ceil, log2_strict, transpose_poly_values};
 
 /// Number of random coins needed for lookups (for each challenge).
 /// A coin is a randomly sampled extension field element from the verifier,
@@ -71,6 +103,20 @@ pub enum LookupChallenges {
     ChallengeDelta = 3,
 }
 
+/// What [`CircuitBuilder::register_public_input`] does with a target which is already a public
+/// input, as happens easily when gadgets which each expose their outputs are composed.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum DuplicatePublicInputs {
+    /// The target is registered again, so its value appears once per registration in the public
+    /// inputs hash and in `ProofWithPublicInputs::public_inputs`.
+    #[default]
+    Allow,
+    /// The target keeps its first position and later registrations are ignored.
+    Dedup,
+    /// Registering the target again panics, naming the contexts of both registrations.
+    Panic,
+}
+
 /// Structure containing, for each lookup table, the indices of the last lookup row,
 /// the last lookup table row and the first lookup table row. Since the rows are in
 /// reverse order in the trace, they actually correspond, respectively, to: the indices
@@ -85,6 +131,133 @@ pub struct LookupWire {
     pub first_lut_gate: usize,
 }
 
+/// Identifies an operation placed in a slot of a gate by `CircuitBuilder::find_slot`, e.g. an
+/// arithmetic operation or a random access. Operations are numbered in the order they were placed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct OperationId(pub usize);
+
+/// A table of constants added by [`CircuitBuilder::constant_table`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TableHandle(usize);
+
+/// A read-only memory added by [`CircuitBuilder::add_rom`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RomHandle(usize);
+
+/// The number of bits of the limbs in which `rom_read` looks values up, as lookup tables map
+/// `u16`s to `u16`s.
+const ROM_LIMB_BITS: usize = 16;
+
+/// A read-only memory, whose lookup tables are only added when it is first read, as `build`
+/// rejects unused lookup tables.
+#[derive(Clone, Debug)]
+struct Rom<F: RichField> {
+    values: Vec<F>,
+    /// The index of the lookup table from indices to each limb of the values, from the least
+    /// significant. Empty until the memory is first read.
+    limb_luts: Vec<usize>,
+}
+
+/// The row and slot where each operation placed by `CircuitBuilder::find_slot` landed, returned by
+/// `CircuitBuilder::build_with_layout`. Slots are assigned as operations are added, so the layout
+/// only depends on the order in which the circuit was built.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OperationLayout {
+    pub slots: HashMap<OperationId, (usize, usize)>,
+}
+
+impl OperationLayout {
+    /// Returns the row and the slot within the row's gate of the given operation.
+    pub fn get(&self, id: OperationId) -> Option<(usize, usize)> {
+        self.slots.get(&id).copied()
+    }
+}
+
+/// Options for building a circuit in phases, with [`CircuitBuilder::build_layout`],
+/// [`CircuitLayout::build_commitments`] and [`CircuitLayout::finalize`].
+#[derive(Clone, Debug)]
+pub struct BuildOptions<F: Field> {
+    /// Skip the work only needed to prove, i.e. collecting and indexing the witness generators,
+    /// transposing the sigma polynomials and computing the FFT root table. The layout can then only
+    /// be finalized with [`CircuitLayout::finalize_verifier`].
+    pub skip_prover_data: bool,
+    /// An FFT root table to use instead of computing one, e.g. the `fft_root_table` of an earlier
+    /// build of a circuit of the same degree. It is ignored if it's for a different number of
+    /// points than the circuit needs.
+    pub reuse_fft_root_table: Option<Arc<FftRootTable<F>>>,
+    /// Whether to compute the FFT root table on another thread while the sigma polynomials are
+    /// generated. This has no effect without the `parallel` feature.
+    pub parallelism: bool,
+    /// Whether to commit to the constant and sigma polynomials. Without the commitment, the
+    /// circuit can only be used for mock proving.
+    pub commit_to_sigma: bool,
+    /// Whether to panic if the circuit doesn't match the common data expected by cyclic
+    /// recursion, if any. This is disabled to build candidate circuits while searching for that
+    /// common data, which only need to be inspected.
+    pub require_goal_common_data: bool,
+}
+
+impl<F: Field> Default for BuildOptions<F> {
+    fn default() -> Self {
+        Self {
+            skip_prover_data: false,
+            reuse_fft_root_table: None,
+            parallelism: true,
+            commit_to_sigma: true,
+            require_goal_common_data: true,
+        }
+    }
+}
+
+/// A circuit whose gates have all been placed, blinded and padded, along with its selector and
+/// constant polynomials. Returned by [`CircuitBuilder::build_layout`].
+#[derive(Debug)]
+pub struct CircuitLayout<F: RichField + Extendable<D>, const D: usize> {
+    builder: CircuitBuilder<F, D>,
+    options: BuildOptions<F>,
+    num_public_inputs: usize,
+    degree_bits: usize,
+    fri_params: FriParams,
+    /// The gate types, sorted by degree and ID.
+    gates: Vec<GateRef<F, D>>,
+    selectors_info: SelectorsInfo,
+    quotient_degree_factor: usize,
+    num_lookup_selectors: usize,
+    num_blinding_rows: usize,
+    /// The selector, lookup selector and constant polynomials, in the order they're committed to.
+    constant_vecs: Vec<PolynomialValues<F>>,
+    /// The progress of preprocessing, which runs until the circuit data is assembled.
+    progress: PhaseProgress,
+}
+
+/// The sigma polynomials of a [`CircuitLayout`] and the commitment to them and to the constant
+/// polynomials. Returned by [`CircuitLayout::build_commitments`].
+#[derive(Debug)]
+pub struct CircuitCommitments<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    /// Everything the sigma polynomials depend on, to tell whether they can be reused.
+    wiring: Wiring,
+    k_is: Vec<F>,
+    subgroup: Vec<F>,
+    sigma_vecs: Vec<PolynomialValues<F>>,
+    forest: Forest,
+    fft_root_table: Option<Arc<FftRootTable<F>>>,
+    constants_sigmas_commitment: PolynomialBatch<F, C, D>,
+}
+
+/// The shape of a circuit and its copy constraints, which determine its sigma polynomials.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Wiring {
+    degree_bits: usize,
+    num_wires: usize,
+    num_routed_wires: usize,
+    num_virtual_targets: usize,
+    copies: Vec<(Target, Target)>,
+}
+
 /// Structure used to construct a plonky2 circuit. It provides all the necessary toolkit that,
 /// from an initial circuit configuration, will enable one to design a circuit and its associated
 /// prover/verifier data.
@@ -146,7 +319,17 @@ pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     /// Defaults to the empty vector.
     domain_separator: Option<Vec<F>>,
 
-    /// The types of gates used in this circuit.
+    /// How the public inputs are hashed into the digest exposed by the `PublicInputGate`.
+    public_input_commitment: PublicInputCommitment,
+
+    /// What registering a target which is already a public input does.
+    duplicate_public_inputs: DuplicatePublicInputs,
+
+    /// The points at which polynomials are opened besides `zeta` and `g * zeta`.
+    extra_opening_points: Vec<OpeningPoint>,
+
+    /// The types of gates used in this circuit. Iterate over them with `sorted_gates`, as the
+    /// iteration order of hash sets isn't stable across builds of the crate.
     gates: HashSet<GateRef<F, D>>,
 
     /// The concrete placement of each gate.
@@ -155,6 +338,13 @@ pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     /// Targets to be made public.
     public_inputs: Vec<Target>,
 
+    /// The index of the first registration of each public input, along with the context it was
+    /// registered in.
+    public_input_indices: HashMap<Target, (usize, String)>,
+
+    /// The ranges of the public inputs registered with `register_named_public_inputs`, by name.
+    public_input_names: BTreeMap<String, Range<usize>>,
+
     /// The next available index for a `VirtualTarget`.
     virtual_target_index: usize,
 
@@ -163,24 +353,83 @@ pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     /// A tree of named scopes, used for debugging.
     context_log: ContextTree,
 
+    /// The contexts in which rows were added and operations placed, to attribute the rows of the
+    /// built circuit to contexts.
+    row_contexts: RowContextLog,
+
+    /// Named groups of inputs, declared with `declare_input`.
+    witness_schema: WitnessSchema,
+
+    /// Whether `build` panics when some virtual targets are unconstrained. See `set_strict`.
+    strict: bool,
+
+    /// In strict mode, the context in which virtual targets were added, keyed by the index of the
+    /// first virtual target added in each context. An empty string means no context was open.
+    virtual_target_contexts: BTreeMap<usize, String>,
+
+    /// Targets which strict mode treats as constrained although no gate outputs them: inputs
+    /// declared with `declare_external_input`, and hints which gadgets fill and then check.
+    external_inputs: HashSet<Target>,
+
     /// Generators used to generate the witness.
     generators: Vec<WitnessGeneratorRef<F, D>>,
 
+    /// Range checks enqueued by gadgets, which `build` packs into `RangeCheckGate`s.
+    pub(crate) range_checks: RangeCheckService,
+
+    /// The context in which generators were added, keyed by the index of the first generator added
+    /// in each context. An empty string means no context was open.
+    generator_contexts: BTreeMap<usize, String>,
+
+    /// Generators added by `generate_copy`. They are kept apart from `generators` so that they
+    /// don't need to be boxed, as circuits can contain a great many of them.
+    copy_generators: Vec<CopyGenerator>,
+
+    /// Generators added by `generate_copies`.
+    batch_copy_generators: Vec<CopiesGenerator>,
+
+    /// The target of each constant. `build` places the constants in increasing order, so that the
+    /// layout doesn't depend on the iteration order of this map.
     constants_to_targets: HashMap<F, Target>,
     targets_to_constants: HashMap<Target, F>,
 
+    /// Memoized results of `constant_table` calls.
+    constant_table_handles: HashMap<Vec<F>, TableHandle>,
+
+    /// The targets of each table created by `constant_table`, indexed by `TableHandle`.
+    constant_tables: Vec<Vec<Target>>,
+
+    /// The memories created by `add_rom`, indexed by `RomHandle`.
+    roms: Vec<Rom<F>>,
+
     /// Memoized results of `arithmetic` calls.
     pub(crate) base_arithmetic_results: HashMap<BaseArithmeticOperation<F>, Target>,
 
     /// Memoized results of `arithmetic_extension` calls.
     pub(crate) arithmetic_results: HashMap<ExtensionArithmeticOperation<F, D>, ExtensionTarget<D>>,
 
+    /// Memoized results of `scalar_arithmetic_extension` calls.
+    pub(crate) scalar_arithmetic_results:
+        HashMap<ScalarArithmeticOperation<F, D>, ExtensionTarget<D>>,
+
+    /// Memoized results of `mul_const_extension` calls.
+    pub(crate) mul_const_results: HashMap<(F::Extension, ExtensionTarget<D>), ExtensionTarget<D>>,
+
     /// Map between gate type and the current gate of this type with available slots.
-    current_slots: HashMap<GateRef<F, D>, CurrentSlot<F, D>>,
+    pub(crate) current_slots: HashMap<GateRef<F, D>, CurrentSlot<F, D>>,
 
     /// List of constant generators used to fill the constant wires.
     constant_generators: Vec<ConstantGenerator<F>>,
 
+    /// The row and slot of each operation placed by `find_slot`, indexed by `OperationId`.
+    operation_slots: Vec<(usize, usize)>,
+
+    /// Called with the progress of the preprocessing done by `build`.
+    progress: ProgressCallback,
+
+    /// The operations recorded so far, while this builder records a `SubCircuitTemplate`.
+    recorded_ops: Option<Vec<RecordedOp<F, D>>>,
+
     /// Rows for each LUT: [`LookupWire`] contains: first [`LookupGate`], first and last
     /// [LookupTableGate](crate::gates::lookup_table::LookupTableGate).
     lookup_rows: Vec<LookupWire>,
@@ -196,9 +445,51 @@ pub struct CircuitBuilder<F: RichField + Extendable<D>, const D: usize> {
     /// This is used in cyclic recursion.
     pub(crate) goal_common_data: Option<CommonCircuitData<F, D>>,
 
+    /// Whether `verify_proof` panics when the verifier doesn't fit in the degree of
+    /// `goal_common_data`. This is disabled while searching for the common data of a cyclic
+    /// circuit, when candidates are expected to be too small.
+    pub(crate) check_goal_degree: bool,
+
+    /// Whether the recursive verifier selects values by a query index with trees of `select`s,
+    /// and raises variable bases to that index, rather than using `random_access` and powers of
+    /// constants. This naive approach is only kept to measure what the default one saves.
+    pub(crate) naive_index_selection: bool,
+
     /// Optional verifier data that is registered as public inputs.
     /// This is used in cyclic recursion to hold the circuit's own verifier key.
     pub(crate) verifier_data_public_input: Option<VerifierCircuitTarget>,
+
+    /// Optional cache from which `build` takes the FFT root table, so that it is shared with other
+    /// circuits built with the same cache rather than computed anew.
+    #[cfg(feature = "std")]
+    fft_root_table_cache: Option<FftRootTableCache<F>>,
+}
+
+/// A context of a [`CircuitBuilder`], opened by [`CircuitBuilder::context`] and closed when the
+/// guard is dropped. The guard dereferences to the builder.
+#[derive(Debug)]
+pub struct ContextGuard<'a, F: RichField + Extendable<D>, const D: usize> {
+    builder: &'a mut CircuitBuilder<F, D>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Deref for ContextGuard<'_, F, D> {
+    type Target = CircuitBuilder<F, D>;
+
+    fn deref(&self) -> &Self::Target {
+        self.builder
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> DerefMut for ContextGuard<'_, F, D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.builder
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> Drop for ContextGuard<'_, F, D> {
+    fn drop(&mut self) {
+        self.builder.pop_context();
+    }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
@@ -210,52 +501,157 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let builder = CircuitBuilder {
             config,
             domain_separator: None,
+            public_input_commitment: PublicInputCommitment::Flat,
+            duplicate_public_inputs: DuplicatePublicInputs::Allow,
+            extra_opening_points: Vec::new(),
             gates: HashSet::new(),
             gate_instances: Vec::new(),
             public_inputs: Vec::new(),
+            public_input_indices: HashMap::new(),
+            public_input_names: BTreeMap::new(),
             virtual_target_index: 0,
             copy_constraints: Vec::new(),
             context_log: ContextTree::new(),
+            row_contexts: RowContextLog::new(),
+            witness_schema: WitnessSchema::default(),
+            strict: false,
+            virtual_target_contexts: BTreeMap::new(),
+            external_inputs: HashSet::new(),
             generators: Vec::new(),
+            range_checks: RangeCheckService::default(),
+            generator_contexts: BTreeMap::new(),
+            copy_generators: Vec::new(),
+            batch_copy_generators: Vec::new(),
             constants_to_targets: HashMap::new(),
             targets_to_constants: HashMap::new(),
+            constant_table_handles: HashMap::new(),
+            constant_tables: Vec::new(),
+            roms: Vec::new(),
             base_arithmetic_results: HashMap::new(),
             arithmetic_results: HashMap::new(),
+            scalar_arithmetic_results: HashMap::new(),
+            mul_const_results: HashMap::new(),
             current_slots: HashMap::new(),
             constant_generators: Vec::new(),
+            operation_slots: Vec::new(),
+            progress: ProgressCallback::default(),
+            recorded_ops: None,
             lookup_rows: Vec::new(),
             lut_to_lookups: Vec::new(),
             luts: Vec::new(),
             goal_common_data: None,
+            check_goal_degree: true,
+            naive_index_selection: false,
             verifier_data_public_input: None,
+            #[cfg(feature = "std")]
+            fft_root_table_cache: None,
         };
         builder.check_config();
         builder
     }
 
-    /// Assert that the configuration used to create this `CircuitBuilder` is consistent,
-    /// i.e. that the different parameters meet the targeted security level.
+    /// Assert that the configuration of this `CircuitBuilder` is consistent, i.e. that the
+    /// different parameters meet the targeted security level and that the public input commitment
+    /// is well-formed.
     fn check_config(&self) {
-        let &CircuitConfig {
-            security_bits,
-            fri_config:
-                FriConfig {
-                    rate_bits,
-                    proof_of_work_bits,
-                    num_query_rounds,
-                    ..
-                },
-            ..
-        } = &self.config;
+        assert!(
+            self.config.num_challenges > 0,
+            "num_challenges must be positive"
+        );
 
-        // Conjectured FRI security; see the ethSTARK paper.
         let fri_field_bits = F::Extension::order().bits() as usize;
-        let fri_query_security_bits = num_query_rounds * rate_bits + proof_of_work_bits as usize;
-        let fri_security_bits = fri_field_bits.min(fri_query_security_bits);
         assert!(
-            fri_security_bits >= security_bits,
+            self.config
+                .fri_config
+                .conjectured_security_bits(fri_field_bits)
+                >= self.config.security_bits,
             "FRI params fall short of target security"
         );
+
+        if let PublicInputCommitment::Chunked { chunk_size } = self.public_input_commitment {
+            assert!(chunk_size > 0, "Public input chunks can't be empty");
+        }
+    }
+
+    /// Warns if the permutation and lookup arguments fall short of the targeted security level
+    /// once the circuit's degree is known. This is only an estimate (see
+    /// `CircuitConfig::challenge_security_bits`), so the build isn't failed on it.
+    fn check_challenge_security(&self, degree_bits: usize) {
+        let challenge_security_bits = self.config.challenge_security_bits::<F>(degree_bits);
+        if challenge_security_bits < self.config.security_bits {
+            warn!(
+                "num_challenges = {} sets of challenges give {} bits of security at degree 2^{}, \
+                 short of the target of {} bits",
+                self.config.num_challenges,
+                challenge_security_bits,
+                degree_bits,
+                self.config.security_bits
+            );
+        }
+    }
+
+    /// Sets the callback receiving the progress of the preprocessing done when building the
+    /// circuit. The progress of proving is set in `ProverOptions`.
+    pub fn set_progress_callback(&mut self, progress: ProgressCallback) {
+        self.progress = progress;
+    }
+
+    /// Sets how the public inputs are hashed. With many public inputs, a chunked commitment lets
+    /// recursive verifiers hash only the chunks they inspect.
+    pub fn set_public_input_commitment(&mut self, commitment: PublicInputCommitment) {
+        self.public_input_commitment = commitment;
+        self.check_config();
+    }
+
+    /// Sets what registering a target which is already a public input does. This only affects
+    /// later registrations.
+    pub fn set_duplicate_public_inputs(&mut self, policy: DuplicatePublicInputs) {
+        self.duplicate_public_inputs = policy;
+    }
+
+    /// Sets whether `build` panics when some virtual targets are unconstrained, i.e. when they are
+    /// copied to the input of a gate, but their copy class holds no output of a gate, constant,
+    /// public input or declared input, so that a prover could set them freely. Genuine inputs,
+    /// such as the targets of a proof verified in the circuit, must then be declared with
+    /// `declare_input` or `declare_external_input`. The panic lists the contexts the targets were
+    /// added in, which are only recorded for targets added after strict mode is enabled.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Opens all polynomials of the given oracles at `g^shift * zeta` in proofs, where `g`
+    /// generates the circuit's subgroup, in addition to `zeta` and `g * zeta`. The values are in
+    /// the proof's `extra_openings`, one vector per opening point in the order the points were
+    /// first added, each holding the values of the oracles' polynomials in the oracles' order.
+    /// Gates read them through `extra_openings` in their evaluation vars, so the quotient oracle,
+    /// which is only committed once the gates have been evaluated, can't be opened at such points.
+    pub fn add_opening_point(&mut self, shift: isize, oracles: &[PlonkOracle]) {
+        assert_ne!(shift, 0, "All polynomials are already opened at zeta");
+        assert!(
+            oracles
+                .iter()
+                .all(|oracle| oracle.index < PlonkOracle::QUOTIENT.index),
+            "The quotient oracle can't be opened at extra points"
+        );
+        let opening_point = match self
+            .extra_opening_points
+            .iter_mut()
+            .find(|opening_point| opening_point.shift == shift)
+        {
+            Some(opening_point) => opening_point,
+            None => {
+                self.extra_opening_points.push(OpeningPoint {
+                    shift,
+                    oracles: Vec::new(),
+                });
+                self.extra_opening_points.last_mut().unwrap()
+            }
+        };
+        opening_point
+            .oracles
+            .extend(oracles.iter().map(|oracle| oracle.index));
+        opening_point.oracles.sort_unstable();
+        opening_point.oracles.dedup();
     }
 
     pub fn set_domain_separator(&mut self, separator: Vec<F>) {
@@ -263,13 +659,35 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         self.domain_separator = Some(separator);
     }
 
+    /// Makes `build` take the circuit's FFT root table from `cache`, sharing it with every other
+    /// circuit built with the same cache.
+    #[cfg(feature = "std")]
+    pub fn set_fft_root_table_cache(&mut self, cache: FftRootTableCache<F>) {
+        self.fft_root_table_cache = Some(cache);
+    }
+
     /// Outputs the number of gates in this circuit.
     pub fn num_gates(&self) -> usize {
         self.gate_instances.len()
     }
 
-    /// Registers the given target as a public input.
+    /// Registers the given target as a public input. If it already is one, this follows the policy
+    /// set with `set_duplicate_public_inputs`.
     pub fn register_public_input(&mut self, target: Target) {
+        let context = self.context_log.open_stack();
+        if let Some((index, first_context)) = self.public_input_indices.get(&target) {
+            match self.duplicate_public_inputs {
+                DuplicatePublicInputs::Allow => {}
+                DuplicatePublicInputs::Dedup => return,
+                DuplicatePublicInputs::Panic => panic!(
+                    "{:?} was registered as public input {} in context \"{}\", and again in context \"{}\"",
+                    target, index, first_context, context
+                ),
+            }
+        } else {
+            self.public_input_indices
+                .insert(target, (self.public_inputs.len(), context));
+        }
         self.public_inputs.push(target);
     }
 
@@ -278,11 +696,65 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         targets.iter().for_each(|&t| self.register_public_input(t));
     }
 
+    /// Registers `targets` as public inputs under `name`, so that verifiers can read them back with
+    /// `ProofWithPublicInputs::public_inputs_range`. The names are part of the circuit's common
+    /// data. Panics if the name was already used.
+    pub fn register_named_public_inputs(&mut self, name: &str, targets: &[Target]) {
+        let start = self.public_inputs.len();
+        self.register_public_inputs(targets);
+        assert_eq!(
+            self.public_inputs.len() - start,
+            targets.len(),
+            "Named public inputs \"{}\" include targets which are already public inputs",
+            name
+        );
+        let previous = self
+            .public_input_names
+            .insert(name.to_string(), start..self.public_inputs.len());
+        assert!(
+            previous.is_none(),
+            "Public inputs `{}` were declared twice",
+            name
+        );
+    }
+
+    /// Declares `targets` as an input named `name`, which can then be assigned with
+    /// `PartialWitness::set_by_name` using the circuit's witness schema. Panics if an input with
+    /// the same name was already declared.
+    pub fn declare_input(&mut self, name: &str, targets: &[Target]) {
+        self.witness_schema.declare(name, targets);
+    }
+
+    /// Declares `target` as an input set by the prover, so that strict mode doesn't report it as
+    /// unconstrained. See `set_strict`.
+    pub fn declare_external_input(&mut self, target: Target) {
+        self.external_inputs.insert(target);
+    }
+
+    /// Declares the given targets as inputs set by the prover.
+    pub fn declare_external_inputs(&mut self, targets: &[Target]) {
+        self.external_inputs.extend(targets);
+    }
+
+    /// Records that gadget-generated `targets` are checked by constraints which don't output them,
+    /// as when a hint is multiplied by a value to check it is its inverse.
+    pub(crate) fn declare_checked_hints(&mut self, targets: &[Target]) {
+        self.declare_external_inputs(targets);
+    }
+
     /// Outputs the number of public inputs in this circuit.
     pub fn num_public_inputs(&self) -> usize {
         self.public_inputs.len()
     }
 
+    /// Returns the index of `target` among this circuit's public inputs, or of its first
+    /// registration if it was registered several times.
+    pub fn public_input_index_of(&self, target: Target) -> Option<usize> {
+        self.public_input_indices
+            .get(&target)
+            .map(|&(index, _)| index)
+    }
+
     /// Adds lookup rows for a lookup table.
     pub fn add_lookup_rows(
         &mut self,
@@ -324,8 +796,16 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// virtual target, which can then be copied to other (virtual or concrete) targets. When we
     /// generate the final witness (a grid of wire values), these virtual targets will go away.
     pub fn add_virtual_target(&mut self) -> Target {
+        self.record(|| RecordedOp::VirtualTarget);
+        self.new_virtual_target()
+    }
+
+    fn new_virtual_target(&mut self) -> Target {
         let index = self.virtual_target_index;
         self.virtual_target_index += 1;
+        if self.strict {
+            self.record_virtual_target_context(index);
+        }
         Target::VirtualTarget { index }
     }
 
@@ -431,11 +911,12 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             "add_verifier_data_public_inputs only needs to be called once"
         );
 
-        let verifier_data = self.add_virtual_verifier_data(self.config.fri_config.cap_height);
+        let verifier_data =
+            self.add_virtual_verifier_data(self.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
         // The verifier data are public inputs.
         self.register_public_inputs(&verifier_data.circuit_digest.elements);
-        for i in 0..self.config.fri_config.num_cap_elements() {
-            self.register_public_inputs(&verifier_data.constants_sigmas_cap.0[i].elements);
+        for cap_hash in &verifier_data.constants_sigmas_cap.0 {
+            self.register_public_inputs(&cap_hash.elements);
         }
 
         self.verifier_data_public_input = Some(verifier_data.clone());
@@ -443,14 +924,41 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 
     /// Adds a gate to the circuit, and returns its index.
-    pub fn add_gate<G: Gate<F, D>>(&mut self, gate_type: G, mut constants: Vec<F>) -> usize {
-        self.check_gate_compatibility(&gate_type);
+    pub fn add_gate<G: Gate<F, D>>(&mut self, gate_type: G, constants: Vec<F>) -> usize {
+        self.add_gate_ref(GateRef::new(gate_type), constants)
+    }
 
-        assert!(
-            constants.len() <= gate_type.num_constants(),
-            "Too many constants."
+    /// Adds a gate of the type `gate_ref` to the circuit, and returns its index.
+    pub(crate) fn add_gate_ref(&mut self, gate_ref: GateRef<F, D>, constants: Vec<F>) -> usize {
+        self.record(|| RecordedOp::AddGate {
+            gate: gate_ref.clone(),
+            constants: constants.clone(),
+        });
+        self.push_gate(gate_ref, constants)
+    }
+
+    fn push_gate(&mut self, gate_ref: GateRef<F, D>, constants: Vec<F>) -> usize {
+        let gate_type = &*gate_ref.0;
+        self.check_gate_compatibility(gate_type);
+
+        assert_eq!(
+            constants.len(),
+            gate_type.num_constants(),
+            "{}{} takes {} constants, but was given {}",
+            gate_type.id(),
+            self.context_suffix(),
+            gate_type.num_constants(),
+            constants.len()
         );
-        constants.resize(gate_type.num_constants(), F::ZERO);
+        if let Err(reason) = gate_type.validate_constants(&constants) {
+            panic!(
+                "{}{} was given invalid constants {:?}: {}",
+                gate_type.id(),
+                self.context_suffix(),
+                constants,
+                reason
+            );
+        }
 
         let row = self.gate_instances.len();
 
@@ -469,9 +977,9 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         // `build` instead.
 
         // Register this gate type if we haven't seen it before.
-        let gate_ref = GateRef::new(gate_type);
         self.gates.insert(gate_ref.clone());
 
+        self.row_contexts.add_row();
         self.gate_instances.push(GateInstance {
             gate_ref,
             constants,
@@ -480,32 +988,106 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         row
     }
 
-    fn check_gate_compatibility<G: Gate<F, D>>(&self, gate: &G) {
+    /// Returns the target for the advice wire in column `column` of the gate at row `row`, e.g. as
+    /// returned by `add_gate` or `find_slot`.
+    ///
+    /// Advice wires are the columns from `num_routed_wires` onwards, which the gate's constraints
+    /// and generators can use for intermediate values without taking routing capacity. Their
+    /// targets can be read by generators, but `connect` rejects them. Routed wires are addressed
+    /// with `Target::wire`.
+    pub fn gate_wire(&self, row: usize, column: usize) -> Target {
+        assert!(
+            row < self.gate_instances.len(),
+            "There is no gate at row {row}"
+        );
+        let gate = &self.gate_instances[row].gate_ref.0;
+        assert!(
+            column < gate.num_wires(),
+            "{} at row {row} only has {} wires",
+            gate.id(),
+            gate.num_wires()
+        );
+        assert!(
+            column >= self.config.num_routed_wires,
+            "Column {column} is routed, but gate_wire only returns advice wires, from column {}",
+            self.config.num_routed_wires
+        );
+        Target::wire(row, column)
+    }
+
+    /// Panics if `gate` doesn't fit in a row of this circuit, describing the wires or constants it
+    /// lacks and the gadget which added it.
+    fn check_gate_compatibility(&self, gate: &dyn AnyGate<F, D>) {
+        let context = self.context_suffix();
+        let CircuitConfig {
+            num_wires,
+            num_routed_wires,
+            num_constants,
+            ..
+        } = self.config;
+        if gate.num_wires() > num_wires {
+            let wide_num_wires = CircuitConfig::wide_ecc_config().num_wires;
+            let hint = if gate.num_wires() <= wide_num_wires && num_wires < wide_num_wires {
+                format!(
+                    "; CircuitConfig::wide_ecc_config() has {} wires",
+                    wide_num_wires
+                )
+            } else {
+                String::new()
+            };
+            panic!(
+                "{:?}{} requires {} wires, but the CircuitConfig has num_wires = {}{}",
+                gate.id(),
+                context,
+                gate.num_wires(),
+                num_wires,
+                hint
+            );
+        }
         assert!(
-            gate.num_wires() <= self.config.num_wires,
-            "{:?} requires {} wires, but our CircuitConfig has only {}",
+            gate.num_routed_wires() <= num_routed_wires,
+            "{:?}{} requires {} routed wires, but the CircuitConfig has num_routed_wires = {} \
+             (of num_wires = {}); increase num_routed_wires",
             gate.id(),
-            gate.num_wires(),
-            self.config.num_wires
+            context,
+            gate.num_routed_wires(),
+            num_routed_wires,
+            num_wires
         );
         assert!(
-            gate.num_constants() <= self.config.num_constants,
-            "{:?} requires {} constants, but our CircuitConfig has only {}",
+            gate.num_constants() <= num_constants,
+            "{:?}{} requires {} constants, but the CircuitConfig has num_constants = {}",
             gate.id(),
+            context,
             gate.num_constants(),
-            self.config.num_constants
+            num_constants
         );
     }
 
     /// Adds a gate type to the set of gates to be used in this circuit. This can be useful
     /// in conditional recursion to uniformize the set of gates of the different circuits.
     pub fn add_gate_to_gate_set(&mut self, gate: GateRef<F, D>) {
+        self.record(|| RecordedOp::AddGateType(gate.clone()));
         self.gates.insert(gate);
     }
 
     /// Adds a generator which will copy `src` to `dst`.
     pub fn generate_copy(&mut self, src: Target, dst: Target) {
-        self.add_simple_generator(CopyGenerator { src, dst });
+        self.copy_generators.push(CopyGenerator { src, dst });
+    }
+
+    /// Adds a single generator which will copy each of `srcs` to the corresponding target of
+    /// `dsts`. This behaves like calling `generate_copy` on each pair, but is cheaper for long
+    /// vectors of targets.
+    pub fn generate_copies(&mut self, srcs: &[Target], dsts: &[Target]) {
+        assert_eq!(
+            srcs.len(),
+            dsts.len(),
+            "Number of sources and destinations must match"
+        );
+        self.batch_copy_generators.push(CopiesGenerator {
+            pairs: srcs.iter().copied().zip(dsts.iter().copied()).collect(),
+        });
     }
 
     /// Uses Plonk's permutation argument to require that two elements be equal.
@@ -521,6 +1103,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             y.is_routable(&self.config),
             "Tried to route a wire that isn't routable"
         );
+        self.record(|| RecordedOp::Connect(x, y));
         self.copy_constraints
             .push(CopyConstraint::new((x, y), self.context_log.open_stack()));
     }
@@ -559,12 +1142,14 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 
     pub fn add_generators(&mut self, generators: Vec<WitnessGeneratorRef<F, D>>) {
-        self.generators.extend(generators);
+        for generator in generators {
+            self.record(|| RecordedOp::Generator);
+            self.generators.push(generator);
+        }
     }
 
     pub fn add_simple_generator<G: SimpleGenerator<F, D>>(&mut self, generator: G) {
-        self.generators
-            .push(WitnessGeneratorRef::new(generator.adapter()));
+        self.add_generators(vec![WitnessGeneratorRef::new(generator.adapter())]);
     }
 
     /// Returns a routable target with a value of 0.
@@ -604,7 +1189,8 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             return target;
         }
 
-        let target = self.add_virtual_target();
+        self.record(|| RecordedOp::Constant(c));
+        let target = self.new_virtual_target();
         self.constants_to_targets.insert(c, target);
         self.targets_to_constants.insert(target, c);
 
@@ -616,6 +1202,103 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         constants.iter().map(|&c| self.constant(c)).collect()
     }
 
+    /// Returns a handle to a table holding the given constant values, which are then read with
+    /// [`Self::read_table`].
+    ///
+    /// The values are the constants of [`ConstantTableGate`] rows, `num_constants` per row, which
+    /// hold nothing but the table, in order. Tables with the same values are only added once.
+    pub fn constant_table(&mut self, values: &[F]) -> TableHandle {
+        if let Some(&handle) = self.constant_table_handles.get(values) {
+            return handle;
+        }
+
+        let targets = values
+            .chunks(self.config.num_constants)
+            .flat_map(|chunk| {
+                // The last row is padded with zeros, so that all tables share one gate type.
+                let gate = ConstantTableGate::new(self.config.num_constants);
+                let mut constants = chunk.to_vec();
+                constants.resize(gate.num_values, F::ZERO);
+                let row = self.add_gate(gate, constants);
+                (0..chunk.len()).map(move |i| Target::wire(row, gate.wire_value(i)))
+            })
+            .collect();
+        let handle = TableHandle(self.constant_tables.len());
+        self.constant_tables.push(targets);
+        self.constant_table_handles.insert(values.to_vec(), handle);
+        handle
+    }
+
+    /// Returns a routable target with the value at `index` in the given table.
+    pub fn read_table(&mut self, table: TableHandle, index: usize) -> Target {
+        self.constant_tables[table.0][index]
+    }
+
+    /// Returns a handle to a read-only memory holding the given values, which are then read at
+    /// indices chosen by the prover with [`Self::rom_read`]. There can be at most `2^16` values.
+    pub fn add_rom(&mut self, values: &[F]) -> RomHandle {
+        assert!(!values.is_empty(), "A read-only memory can't be empty");
+        assert!(
+            values.len() <= 1 << 16,
+            "A read-only memory holds at most 2^16 values, not {}",
+            values.len()
+        );
+        let handle = RomHandle(self.roms.len());
+        self.roms.push(Rom {
+            values: values.to_vec(),
+            limb_luts: Vec::new(),
+        });
+        handle
+    }
+
+    /// Returns the value at `index` in the given memory.
+    ///
+    /// The values are split into 16-bit limbs, and each limb is looked up in a table from indices
+    /// to limbs, which is shared by all reads. Unlike `random_access`, this doesn't route the
+    /// whole memory for every read, but it takes a lookup per limb the largest value needs. An
+    /// index outside of the memory isn't in the tables, so it can't satisfy the lookups.
+    pub fn rom_read(&mut self, rom: RomHandle, index: Target) -> Target {
+        if self.roms[rom.0].limb_luts.is_empty() {
+            let values = &self.roms[rom.0].values;
+            let max_bits = values
+                .iter()
+                .map(|v| 64 - v.to_canonical_u64().leading_zeros() as usize)
+                .max()
+                .unwrap();
+            let num_limbs = ceil_div_usize(max_bits, ROM_LIMB_BITS).max(1);
+            let limb_tables = (0..num_limbs)
+                .map(|limb| {
+                    let pairs = values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| {
+                            let limb = v.to_canonical_u64() >> (limb * ROM_LIMB_BITS);
+                            (i as u16, limb as u16)
+                        })
+                        .collect::<Vec<_>>();
+                    Arc::new(pairs)
+                })
+                .collect::<Vec<_>>();
+            let limb_luts = limb_tables
+                .into_iter()
+                .map(|table| self.update_luts_from_pairs(table))
+                .collect();
+            self.roms[rom.0].limb_luts = limb_luts;
+        }
+
+        let limb_luts = self.roms[rom.0].limb_luts.clone();
+        let limbs = limb_luts
+            .into_iter()
+            .map(|lut| self.add_lookup_from_index(index, lut))
+            .collect::<Vec<_>>();
+        let limb_base = F::from_canonical_u64(1 << ROM_LIMB_BITS);
+        limbs
+            .into_iter()
+            .rev()
+            .reduce(|acc, limb| self.mul_const_add(limb_base, acc, limb))
+            .unwrap()
+    }
+
     /// Returns a routable target with the given constant boolean value.
     pub fn constant_bool(&mut self, b: bool) -> BoolTarget {
         if b {
@@ -677,12 +1360,125 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
+    /// Opens a context, which stays open until `pop_context` is called. Prefer `context` or
+    /// `with_context`, which can't leave a context open by mistake.
     pub fn push_context(&mut self, level: log::Level, ctx: &str) {
+        self.record(|| RecordedOp::PushContext(level, ctx.to_string()));
         self.context_log.push(ctx, level, self.num_gates());
+        self.row_contexts.enter(self.context_log.open_stack());
+        self.record_generator_context();
     }
 
+    /// Closes the context opened last.
     pub fn pop_context(&mut self) {
+        self.record(|| RecordedOp::PopContext);
         self.context_log.pop(self.num_gates());
+        self.row_contexts.enter(self.context_log.open_stack());
+        self.record_generator_context();
+    }
+
+    /// Opens a context, and returns a guard which closes it when dropped, including on early
+    /// returns. The guard dereferences to this builder, and gates added through it are counted in
+    /// the context.
+    pub fn context(&mut self, level: log::Level, ctx: &str) -> ContextGuard<'_, F, D> {
+        self.push_context(level, ctx);
+        ContextGuard { builder: self }
+    }
+
+    /// Runs `f` in a new context, which is closed when `f` returns.
+    pub fn with_context<T>(
+        &mut self,
+        level: log::Level,
+        ctx: &str,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let mut guard = self.context(level, ctx);
+        f(&mut guard)
+    }
+
+    /// Describes the open context, if any, to be appended to a panic message.
+    pub(crate) fn context_suffix(&self) -> String {
+        if self.context_log.has_open_scope() {
+            format!(" (added in context \"{}\")", self.context_log.open_stack())
+        } else {
+            String::new()
+        }
+    }
+
+    /// Records `op`, if this builder records a `SubCircuitTemplate`.
+    fn record(&mut self, op: impl FnOnce() -> RecordedOp<F, D>) {
+        if let Some(ops) = &mut self.recorded_ops {
+            ops.push(op());
+        }
+    }
+
+    /// Starts recording the operations of a `SubCircuitTemplate`.
+    pub(crate) fn start_recording(&mut self) {
+        self.recorded_ops = Some(Vec::new());
+    }
+
+    /// Returns the operations recorded since `start_recording`, along with the state that
+    /// instances of the template append to that of their builder rather than replay. Panics if
+    /// anything was added which templates can't stamp out.
+    pub(crate) fn into_recording(self) -> Recording<F, D> {
+        assert!(
+            !self.context_log.has_open_scope(),
+            "A sub-circuit template left the context \"{}\" open",
+            self.context_log.open_stack()
+        );
+        let unsupported = [
+            (!self.public_inputs.is_empty(), "public inputs"),
+            (!self.witness_schema.inputs.is_empty(), "declared inputs"),
+            (!self.luts.is_empty(), "lookups"),
+            (!self.constant_tables.is_empty(), "constant tables"),
+            (!self.roms.is_empty(), "ROMs"),
+            (
+                !self.extra_opening_points.is_empty(),
+                "extra opening points",
+            ),
+            (self.domain_separator.is_some(), "a domain separator"),
+        ];
+        if let Some((_, what)) = unsupported.iter().find(|(used, _)| *used) {
+            panic!("Sub-circuit templates can't contain {what}");
+        }
+
+        Recording {
+            ops: self.recorded_ops.expect("Operations weren't recorded"),
+            generators: self.generators,
+            copy_generators: self.copy_generators,
+            batch_copy_generators: self.batch_copy_generators,
+            range_checks: self.range_checks.into_pending(),
+            external_inputs: self.external_inputs.into_iter().collect(),
+            base_arithmetic_results: self.base_arithmetic_results.into_iter().collect(),
+            arithmetic_results: self.arithmetic_results.into_iter().collect(),
+            scalar_arithmetic_results: self.scalar_arithmetic_results.into_iter().collect(),
+            mul_const_results: self.mul_const_results.into_iter().collect(),
+            operation_slots: self.operation_slots,
+        }
+    }
+
+    /// Records the current context as the one in which the next generators are added.
+    fn record_generator_context(&mut self) {
+        let context = if self.context_log.has_open_scope() {
+            self.context_log.open_stack()
+        } else {
+            String::new()
+        };
+        self.generator_contexts
+            .insert(self.generators.len(), context);
+    }
+
+    /// Records the current context as the one in which virtual targets are added from `index` on,
+    /// unless it is already the context of the previous ones.
+    fn record_virtual_target_context(&mut self, index: usize) {
+        let context = if self.context_log.has_open_scope() {
+            self.context_log.open_stack()
+        } else {
+            String::new()
+        };
+        if self.virtual_target_contexts.values().next_back() != Some(&context) {
+            self.virtual_target_contexts.insert(index, context);
+        }
     }
 
     /// Returns the total number of LUTs.
@@ -788,15 +1584,29 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         params: &[F],
         constants: &[F],
     ) -> (usize, usize) {
+        self.find_slot_ref(GateRef::new(gate), params, constants)
+    }
+
+    /// Like `find_slot`, for a gate of the type `gate_ref`.
+    pub(crate) fn find_slot_ref(
+        &mut self,
+        gate_ref: GateRef<F, D>,
+        params: &[F],
+        constants: &[F],
+    ) -> (usize, usize) {
+        self.record(|| RecordedOp::FindSlot {
+            gate: gate_ref.clone(),
+            params: params.to_vec(),
+            constants: constants.to_vec(),
+        });
         let num_gates = self.num_gates();
-        let num_ops = gate.num_ops();
-        let gate_ref = GateRef::new(gate.clone());
+        let num_ops = gate_ref.0.num_ops();
         let gate_slot = self.current_slots.entry(gate_ref.clone()).or_default();
         let slot = gate_slot.current_slot.get(params);
         let (gate_idx, slot_idx) = if let Some(&s) = slot {
             s
         } else {
-            self.add_gate(gate, constants.to_vec());
+            self.push_gate(gate_ref.clone(), constants.to_vec());
             (num_gates, 0)
         };
         let current_slot = &mut self.current_slots.get_mut(&gate_ref).unwrap().current_slot;
@@ -808,17 +1618,33 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             current_slot.insert(params.to_vec(), (gate_idx, slot_idx + 1));
         }
 
+        self.operation_slots.push((gate_idx, slot_idx));
+        self.row_contexts.add_op(gate_idx);
         (gate_idx, slot_idx)
     }
 
+    /// Returns the ID that the next operation placed by `find_slot` will get. The operations
+    /// placed by a gadget are those from the ID returned before calling it, up to the one returned
+    /// after; memoized or constant-folded operations aren't placed. Their rows and slots can be
+    /// found in the `OperationLayout` returned by `build_with_layout`.
+    pub fn next_operation_id(&self) -> OperationId {
+        OperationId(self.operation_slots.len())
+    }
+
     fn fri_params(&self, degree_bits: usize) -> FriParams {
-        self.config
-            .fri_config
-            .fri_params(degree_bits, self.config.zero_knowledge)
+        FriParams {
+            initial_cap_heights: self.config.initial_cap_heights(),
+            ..self
+                .config
+                .fri_config
+                .fri_params(degree_bits, self.config.zero_knowledge)
+        }
     }
 
     /// The number of (base field) `arithmetic` operations that can be performed in a single gate.
-    pub(crate) const fn num_base_arithmetic_ops_per_gate(&self) -> usize {
+    /// Without `use_base_arithmetic_gate`, each of them takes a slot of an
+    /// `ArithmeticExtensionGate`.
+    pub const fn num_base_arithmetic_ops_per_gate(&self) -> usize {
         if self.config.use_base_arithmetic_gate {
             ArithmeticGate::new_from_config(&self.config).num_ops
         } else {
@@ -827,7 +1653,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 
     /// The number of `arithmetic_extension` operations that can be performed in a single gate.
-    pub(crate) const fn num_ext_arithmetic_ops_per_gate(&self) -> usize {
+    pub const fn num_ext_arithmetic_ops_per_gate(&self) -> usize {
         ArithmeticExtensionGate::<D>::new_from_config(&self.config).num_ops
     }
 
@@ -836,31 +1662,20 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// dependence (the amount of blinding depends on the degree, which depends on the blinding),
     /// this function takes in an estimate of the degree.
     fn num_blinding_gates(&self, degree_estimate: usize) -> (usize, usize) {
-        let degree_bits_estimate = log2_strict(degree_estimate);
-        let fri_queries = self.config.fri_config.num_query_rounds;
-        let arities: Vec<usize> = self
-            .fri_params(degree_bits_estimate)
-            .reduction_arity_bits
-            .iter()
-            .map(|x| 1 << x)
-            .collect();
-        let total_fri_folding_points: usize = arities.iter().map(|x| x - 1).sum::<usize>();
-        let final_poly_coeffs: usize = degree_estimate / arities.iter().product::<usize>();
-        let fri_openings = fri_queries * (1 + D * total_fri_folding_points + D * final_poly_coeffs);
-
-        // We add D for openings at zeta.
-        let regular_poly_openings = D + fri_openings;
-        // We add 2 * D for openings at zeta and g * zeta.
-        let z_openings = 2 * D + fri_openings;
+        let fri_params = self.fri_params(log2_strict(degree_estimate));
+        // Regular polynomials are opened at zeta, and Z polynomials at zeta and g * zeta. Either
+        // may be opened at the extra opening points.
+        let num_extra_points = self.extra_opening_points.len();
+        let regular_poly_openings = fri_params.num_revealed_values(D, 1 + num_extra_points);
+        let z_openings = fri_params.num_revealed_values(D, 2 + num_extra_points);
 
         (regular_poly_openings, z_openings)
     }
 
     /// The number of polynomial values that will be revealed per opening, both for the "regular"
     /// polynomials (which are opened at only one location) and for the Z polynomials (which are
-    /// opened at two).
-    fn blinding_counts(&self) -> (usize, usize) {
-        let num_gates = self.gate_instances.len();
+    /// opened at two), along with the degree of the blinded circuit which they're computed for.
+    fn blinding_counts(&self, num_gates: usize) -> (usize, usize, usize) {
         let mut degree_estimate = 1 << log2_ceil(num_gates);
 
         loop {
@@ -872,7 +1687,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             let total_blinding_count = regular_poly_openings + 2 * z_openings;
 
             if num_gates + total_blinding_count <= degree_estimate {
-                return (regular_poly_openings, z_openings);
+                return (regular_poly_openings, z_openings, degree_estimate);
             }
 
             // The blinding gates do not fit within our estimated degree; increase our estimate.
@@ -880,23 +1695,48 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
-    fn blind_and_pad(&mut self) {
-        if self.config.zero_knowledge {
-            self.blind();
-        }
+    /// Adds the blinding rows if the circuit is zero-knowledge, then pads the circuit to a power of
+    /// two. Returns the number of blinding rows.
+    fn blind_and_pad(&mut self) -> usize {
+        let num_blinding_rows = if self.config.zero_knowledge {
+            self.blind()
+        } else {
+            0
+        };
 
         while !self.gate_instances.len().is_power_of_two() {
             self.add_gate(NoopGate, vec![]);
         }
+        num_blinding_rows
     }
 
-    fn blind(&mut self) {
-        let (regular_poly_openings, z_openings) = self.blinding_counts();
+    /// Adds rows of random values hiding the values revealed by a proof, and returns their number.
+    fn blind(&mut self) -> usize {
+        let (regular_poly_openings, z_openings, blinded_degree) =
+            self.blinding_counts(self.gate_instances.len());
         info!(
             "Adding {} blinding terms for witness polynomials, and {}*2 for Z polynomials",
             regular_poly_openings, z_openings
         );
 
+        let num_gates = self.gate_instances.len();
+        let degree_bits = log2_ceil(num_gates);
+        let blinded_degree_bits = log2_strict(blinded_degree);
+        let max_degree_bits = F::TWO_ADICITY - self.config.fri_config.rate_bits;
+        assert!(
+            blinded_degree_bits <= max_degree_bits,
+            "Zero-knowledge blinding grows the circuit to degree 2^{}, beyond the maximum of 2^{} for rate_bits = {}",
+            blinded_degree_bits,
+            max_degree_bits,
+            self.config.fri_config.rate_bits
+        );
+        if blinded_degree_bits > degree_bits {
+            warn!(
+                "Zero-knowledge blinding doesn't fit in the circuit's padding; degree grows from 2^{} to 2^{}",
+                degree_bits, blinded_degree_bits
+            );
+        }
+
         let num_routed_wires = self.config.num_routed_wires;
         let num_wires = self.config.num_wires;
 
@@ -912,7 +1752,8 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
 
         // For each z poly blinding factor, we add two new gates with the same random value, and
-        // enforce a copy constraint between them.
+        // enforce a copy constraint between them. Their other wires are random too, as they also
+        // blind the wire polynomials.
         // See https://mirprotocol.org/blog/Adding-zero-knowledge-to-Plonk-Halo
         for _ in 0..z_openings {
             let gate_1 = self.add_gate(NoopGate, vec![]);
@@ -936,7 +1777,22 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                     }),
                 );
             }
+            for row in [gate_1, gate_2] {
+                for w in num_routed_wires..num_wires {
+                    self.add_simple_generator(RandomValueGenerator {
+                        target: Target::Wire(Wire { row, column: w }),
+                    });
+                }
+            }
+        }
+
+        // The counts depend on the degree, so pad to the degree they were computed for, even if
+        // the blinded circuit would fit in a smaller power of two.
+        while self.gate_instances.len() < blinded_degree {
+            self.add_gate(NoopGate, vec![]);
         }
+
+        regular_poly_openings + 2 * z_openings
     }
 
     fn constant_polys(&self) -> Vec<PolynomialValues<F>> {
@@ -946,25 +1802,21 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             .map(|g| g.0.num_constants())
             .max()
             .unwrap();
-        transpose(
-            &self
-                .gate_instances
-                .iter()
-                .map(|g| {
-                    let mut consts = g.constants.clone();
-                    consts.resize(max_constants, F::ZERO);
-                    consts
-                })
-                .collect::<Vec<_>>(),
-        )
-        .into_iter()
-        .map(PolynomialValues::new)
-        .collect()
+        (0..max_constants)
+            .into_par_iter()
+            .map(|i| {
+                let values = self
+                    .gate_instances
+                    .iter()
+                    .map(|g| g.constants.get(i).copied().unwrap_or(F::ZERO))
+                    .collect();
+                PolynomialValues::new(values)
+            })
+            .collect()
     }
 
     fn sigma_vecs(&self, k_is: &[F], subgroup: &[F]) -> (Vec<PolynomialValues<F>>, Forest) {
         let degree = self.gate_instances.len();
-        let degree_log = log2_strict(degree);
         let config = &self.config;
         let mut forest = Forest::new(
             config.num_wires,
@@ -992,73 +1844,370 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         forest.compress_paths();
 
-        let wire_partition = forest.wire_partition();
-        (
-            wire_partition.get_sigma_polys(degree_log, k_is, subgroup),
-            forest,
-        )
+        (forest.sigma_polys(k_is, subgroup), forest)
     }
 
-    pub fn print_gate_counts(&self, min_delta: usize) {
-        // Print gate counts for each context.
-        self.context_log
-            .filter(self.num_gates(), min_delta)
-            .print(self.num_gates());
-
-        // Print total count of each gate type.
-        debug!("Total gate counts:");
-        for gate in self.gates.iter().cloned() {
-            let count = self
-                .gate_instances
-                .iter()
-                .filter(|inst| inst.gate_ref == gate)
-                .count();
-            debug!("- {} instances of {}", count, gate.0.id());
+    /// Panics if some virtual targets are unconstrained, as described in `set_strict`. A gate's
+    /// outputs are taken to be the wires which none of its generators watch.
+    fn check_virtual_targets_constrained(&self) {
+        let degree = self.gate_instances.len();
+        let config = &self.config;
+        let mut forest = Forest::new(
+            config.num_wires,
+            config.num_routed_wires,
+            degree,
+            self.virtual_target_index,
+        );
+        for row in 0..degree {
+            for column in 0..config.num_wires {
+                forest.add(Target::wire(row, column));
+            }
         }
+        for index in 0..self.virtual_target_index {
+            forest.add(Target::VirtualTarget { index });
+        }
+        for &CopyConstraint { pair: (a, b), .. } in &self.copy_constraints {
+            forest.merge(a, b);
+        }
+
+        let mut constrained = Vec::new();
+        let mut used = Vec::new();
+        for (row, gate) in self.gate_instances.iter().enumerate() {
+            let num_wires = gate.gate_ref.0.num_wires().min(config.num_routed_wires);
+            let generators = gate.gate_ref.0.generators(row, &gate.constants);
+            let watched = generators
+                .iter()
+                .flat_map(|g| g.0.watch_list())
+                .collect::<HashSet<_>>();
+            for column in 0..num_wires {
+                let wire = Target::wire(row, column);
+                if !generators.is_empty() && !watched.contains(&wire) {
+                    constrained.push(wire);
+                }
+                used.push(wire);
+            }
+        }
+        constrained.extend(self.targets_to_constants.keys());
+        constrained.extend(&self.public_inputs);
+        constrained.extend(self.witness_schema.inputs.values().flatten());
+        constrained.extend(&self.external_inputs);
+
+        let mut is_constrained = vec![false; forest.parents.len()];
+        for t in constrained {
+            let root = forest.find(forest.target_index(t));
+            is_constrained[root] = true;
+        }
+        let mut is_used = vec![false; forest.parents.len()];
+        for t in used {
+            let root = forest.find(forest.target_index(t));
+            is_used[root] = true;
+        }
+
+        let mut unconstrained_by_context = BTreeMap::<&str, Vec<usize>>::new();
+        for index in 0..self.virtual_target_index {
+            let root = forest.find(forest.target_index(Target::VirtualTarget { index }));
+            if is_used[root] && !is_constrained[root] {
+                let context = self
+                    .virtual_target_contexts
+                    .range(..=index)
+                    .next_back()
+                    .map_or("", |(_, context)| context.as_str());
+                unconstrained_by_context
+                    .entry(context)
+                    .or_default()
+                    .push(index);
+            }
+        }
+        if !unconstrained_by_context.is_empty() {
+            let report = unconstrained_by_context
+                .iter()
+                .map(|(context, indices)| {
+                    let context = if context.is_empty() {
+                        "outside any context".to_string()
+                    } else {
+                        format!("in context \"{}\"", context)
+                    };
+                    format!("virtual targets {:?} added {}", indices, context)
+                })
+                .join("; ");
+            panic!(
+                "Strict mode found unconstrained targets, which a prover could set freely: {}. \
+                 Declare genuine inputs with `declare_external_input`.",
+                report
+            );
+        }
+    }
+
+    /// Returns the number of gates added in each context, in the folded stack format read by
+    /// flamegraph tools such as `inferno`. See `export_circuit_flamegraph`.
+    pub fn circuit_flamegraph(&self) -> String {
+        self.context_log.export_folded(self.num_gates())
+    }
+
+    /// Writes the number of gates added in each context to `path`, in the folded stack format read
+    /// by flamegraph tools, e.g. `inferno-flamegraph < path > circuit.svg`. Call this before
+    /// `build`, which pads the circuit and consumes the builder.
+    #[cfg(feature = "std")]
+    pub fn export_circuit_flamegraph(
+        &self,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<()> {
+        std::fs::write(path, self.circuit_flamegraph() + "\n")
+    }
+
+    /// Logs the number of gates added in each context, and the number of instances of each gate.
+    /// Operations packed into shared rows are counted in the context which added the row; see
+    /// `CircuitData::print_row_attribution` for the rows of the built circuit attributed to the
+    /// contexts of their operations.
+    pub fn print_gate_counts(&self, min_delta: usize) {
+        // Print gate counts for each context.
+        self.context_log
+            .filter(self.num_gates(), min_delta)
+            .print(self.num_gates());
+
+        // Print total count of each gate type.
+        debug!("Total gate counts:");
+        for gate in sorted_gates(self.gates.iter().cloned()) {
+            let count = self
+                .gate_instances
+                .iter()
+                .filter(|inst| inst.gate_ref == gate)
+                .count();
+            debug!("- {} instances of {}", count, gate.0.id());
+        }
+    }
+
+    /// The gate of each row which one permutation of `H` adds, when its inputs aren't constant.
+    fn permutation_gates<H: AlgebraicHasher<F>>(&self) -> Vec<GateRef<F, D>> {
+        let mut scratch = Self::new(self.config.clone());
+        let inputs = (0..H::AlgebraicPermutation::WIDTH)
+            .map(|_| scratch.add_virtual_target())
+            .collect::<Vec<_>>();
+        scratch.permute::<H>(H::AlgebraicPermutation::new(inputs));
+        scratch
+            .gate_instances
+            .into_iter()
+            .map(|instance| instance.gate_ref)
+            .collect()
+    }
+
+    /// Estimates the cost of proving this circuit with the config `C`, without building it. The
+    /// rows that `build` adds are counted too. See [`CostReport`].
+    pub fn cost_report<C: GenericConfig<D, F = F>>(&self) -> CostReport {
+        let mut gates = self.gates.clone();
+        let mut gate_rows = BTreeMap::<String, usize>::new();
+        let mut add_rows = |gate: GateRef<F, D>, rows: usize| {
+            if rows > 0 {
+                *gate_rows.entry(gate.0.id()).or_default() += rows;
+                gates.insert(gate);
+            }
+        };
+        for instance in &self.gate_instances {
+            add_rows(instance.gate_ref.clone(), 1);
+        }
+        let mut num_rows = self.gate_instances.len();
+        // The enqueued range checks are placed first.
+        for (gate, rows) in self.pending_range_check_rows() {
+            add_rows(GateRef::new(gate), rows);
+            num_rows += rows;
+        }
+        let mut num_constants = self.constants_to_targets.len()
+            + usize::from(!self.constants_to_targets.contains_key(&F::ZERO));
+
+        // Hashing takes a permutation per chunk of the sponge's rate, except for chunks which are
+        // preceded by constants only: those are hashed natively, into new constants. The digest
+        // is constant if all chunks are. An empty input hashes to a constant digest.
+        let rate = <C::InnerHasher as AlgebraicHasher<F>>::AlgebraicPermutation::RATE;
+        let width = <C::InnerHasher as AlgebraicHasher<F>>::AlgebraicPermutation::WIDTH;
+        let mut num_permutations = 0;
+        let mut hash = |is_constant: &[bool]| {
+            if is_constant.is_empty() {
+                num_constants += NUM_HASH_OUT_ELTS;
+                return true;
+            }
+            let num_constant_chunks = is_constant
+                .chunks(rate)
+                .take_while(|chunk| chunk.iter().all(|&c| c))
+                .count();
+            let num_chunks = ceil_div_usize(is_constant.len(), rate);
+            num_permutations += num_chunks - num_constant_chunks;
+            num_constants += num_constant_chunks * width;
+            num_constant_chunks == num_chunks
+        };
+        let is_constant = self
+            .public_inputs
+            .iter()
+            .map(|&t| self.target_as_constant(t).is_some())
+            .collect::<Vec<_>>();
+        match self.public_input_commitment {
+            PublicInputCommitment::Flat => {
+                hash(&is_constant);
+            }
+            PublicInputCommitment::Chunked { .. } => {
+                let digest_is_constant = self
+                    .public_input_commitment
+                    .chunk_ranges(is_constant.len())
+                    .into_iter()
+                    .flat_map(|range| [hash(&is_constant[range]); NUM_HASH_OUT_ELTS])
+                    .collect::<Vec<_>>();
+                hash(&digest_is_constant);
+            }
+        }
+        let permutation_gates = self.permutation_gates::<C::InnerHasher>();
+        for gate in &permutation_gates {
+            add_rows(gate.clone(), num_permutations);
+        }
+        add_rows(GateRef::new(PublicInputGate), 1);
+        num_rows += num_permutations * permutation_gates.len() + 1;
+
+        for (lut_index, lut) in self.luts.iter().enumerate() {
+            let (lookup_rows, table_rows) = self.lookup_rows(lut_index);
+            add_rows(
+                GateRef::new(LookupGate::new_from_table(&self.config, lut.clone())),
+                lookup_rows,
+            );
+            num_rows += lookup_rows;
+            add_rows(
+                GateRef::new(LookupTableGate::new_from_table(
+                    &self.config,
+                    lut.clone(),
+                    num_rows,
+                )),
+                table_rows,
+            );
+            add_rows(GateRef::new(NoopGate), 1);
+            num_rows += table_rows + 1;
+        }
+
+        let missing_constants = num_constants.saturating_sub(self.constant_generators.len());
+        let constant_rows = ceil_div_usize(missing_constants, self.config.num_constants);
+        add_rows(
+            GateRef::new(ConstantGate {
+                num_consts: self.config.num_constants,
+            }),
+            constant_rows,
+        );
+        num_rows += constant_rows;
+
+        let num_blinding_rows = if self.config.zero_knowledge {
+            let (regular_poly_openings, z_openings, _) = self.blinding_counts(num_rows);
+            regular_poly_openings + 2 * z_openings
+        } else {
+            0
+        };
+        let degree_bits = log2_ceil(num_rows + num_blinding_rows);
+        if num_blinding_rows > 0 || num_rows < 1 << degree_bits {
+            // Blinding and padding rows are no-ops.
+            gates.insert(GateRef::new(NoopGate));
+        }
+
+        let gates = sorted_gates(gates);
+        let quotient_degree_factor =
+            PartialProductsInfo::choose(&self.config, min_quotient_degree_factor(&gates))
+                .unwrap_or_else(|e| panic!("{}", e))
+                .quotient_degree_factor;
+        let num_selectors = selector_groups(&gates, quotient_degree_factor + 1).len();
+        let num_lookup_selectors = if self.luts.is_empty() {
+            0
+        } else {
+            LookupSelectors::StartEnd as usize + self.luts.len()
+        };
+        let max_gate_constants = gates.iter().map(|g| g.0.num_constants()).max().unwrap();
+        let num_gate_constraints = gates.iter().map(|g| g.0.num_constraints()).max().unwrap();
+        let num_lookup_polys =
+            num_lookup_polys(&self.config, self.luts.len(), quotient_degree_factor);
+
+        CostReport::new::<F, C::Hasher>(
+            &self.config,
+            &self.fri_params(degree_bits),
+            D,
+            quotient_degree_factor,
+            gate_rows,
+            num_blinding_rows,
+            num_selectors,
+            num_gate_constraints,
+            num_selectors + num_lookup_selectors + max_gate_constants,
+            num_lookup_polys,
+        )
     }
 
     /// In PLONK's permutation argument, there's a slight chance of division by zero. We can
     /// mitigate this by randomizing some unused witness elements, so if proving fails with
     /// division by zero, the next attempt will have an (almost) independent chance of success.
-    /// See <https://github.com/0xPolygonZero/plonky2/issues/456>.
+    /// See <https://github.com/0xPolygonZero/plonky2/issues/456>. Only routed wires take part in
+    /// the permutation argument, so the others are left zero, which lets the prover skip
+    /// interpolating columns no gate uses.
     fn randomize_unused_pi_wires(&mut self, pi_gate: usize) {
-        for wire in PublicInputGate::wires_public_inputs_hash().end..self.config.num_wires {
+        for wire in PublicInputGate::wires_public_inputs_hash().end..self.config.num_routed_wires {
             self.add_simple_generator(RandomValueGenerator {
                 target: Target::wire(pi_gate, wire),
             });
         }
     }
 
-    /// Builds a "full circuit", with both prover and verifier data.
+    /// Returns the FFT root table for `max_fft_points`, from the cache if one was set.
+    fn fft_root_table(&self, max_fft_points: usize) -> Arc<FftRootTable<F>> {
+        #[cfg(feature = "std")]
+        if let Some(cache) = &self.fft_root_table_cache {
+            return cache.get(max_fft_points);
+        }
+        Arc::new(fft_root_table(max_fft_points))
+    }
+
+    /// Builds a "full circuit", with both prover and verifier data, running all phases of the
+    /// build with the given options.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `options.skip_prover_data` is set; use `build_verifier` instead.
     pub fn build_with_options<C: GenericConfig<D, F = F>>(
         self,
-        commit_to_sigma: bool,
+        options: BuildOptions<F>,
     ) -> CircuitData<F, C, D> {
-        let (circuit_data, success) = self.try_build_with_options(commit_to_sigma);
-        if !success {
-            panic!("Failed to build circuit");
-        }
+        let mut timing = TimingTree::new("preprocess", Level::Trace);
+        let circuit_data = self.build_with_options_and_timing(options, &mut timing);
+        timing.print();
         circuit_data
     }
 
-    pub fn try_build_with_options<C: GenericConfig<D, F = F>>(
+    /// Like `build_with_options`, but records the time spent in each step of the build in
+    /// `timing` rather than only logging it.
+    fn build_with_options_and_timing<C: GenericConfig<D, F = F>>(
+        self,
+        options: BuildOptions<F>,
+        timing: &mut TimingTree,
+    ) -> CircuitData<F, C, D> {
+        #[cfg(feature = "timing")]
+        let start = Instant::now();
+        let layout = self.build_layout::<C>(options, timing);
+        let commitments = layout.build_commitments::<C>(timing);
+        let circuit_data = layout.finalize(commitments, timing);
+        #[cfg(feature = "timing")]
+        debug!("Building circuit took {}s", start.elapsed().as_secs_f32());
+        circuit_data
+    }
+
+    /// The first phase of building the circuit: places the public input, lookup and constant
+    /// gates, blinds and pads the circuit, and computes its selector and constant polynomials.
+    pub fn build_layout<C: GenericConfig<D, F = F>>(
         mut self,
-        commit_to_sigma: bool,
-    ) -> (CircuitData<F, C, D>, bool) {
-        let mut timing = TimingTree::new("preprocess", Level::Trace);
+        options: BuildOptions<F>,
+        timing: &mut TimingTree,
+    ) -> CircuitLayout<F, D> {
+        let progress = PhaseProgress::start(&self.progress, ProverPhase::Preprocessing);
 
-        #[cfg(feature = "std")]
-        let start = Instant::now();
+        // Place the enqueued range checks first, as they may add constants.
+        self.place_range_checks();
 
-        let rate_bits = self.config.fri_config.rate_bits;
-        let cap_height = self.config.fri_config.cap_height;
         // Total number of LUTs.
         let num_luts = self.get_luts_length();
         // Hash the public inputs, and route them to a `PublicInputGate` which will enforce that
         // those hash wires match the claimed public inputs.
         let num_public_inputs = self.public_inputs.len();
-        let public_inputs_hash =
-            self.hash_n_to_hash_no_pad::<C::InnerHasher>(self.public_inputs.clone());
+        let public_inputs_hash = self.hash_public_inputs::<C::InnerHasher>(
+            self.public_input_commitment,
+            &self.public_inputs.clone(),
+        );
         let pi_gate = self.add_gate(PublicInputGate, vec![]);
         for (&hash_part, wire) in public_inputs_hash
             .elements
@@ -1074,11 +2223,12 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         // Make sure we have enough constant generators. If not, add a `ConstantGate`.
         while self.constants_to_targets.len() > self.constant_generators.len() {
+            // The constants are placeholders, set below.
             self.add_gate(
                 ConstantGate {
                     num_consts: self.config.num_constants,
                 },
-                vec![],
+                vec![F::ZERO; self.config.num_constants],
             );
         }
 
@@ -1101,24 +2251,35 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             self.add_simple_generator(const_gen);
         }
 
+        if self.strict {
+            self.check_virtual_targets_constrained();
+        }
+
         debug!(
             "Degree before blinding & padding: {}",
             self.gate_instances.len()
         );
-        self.blind_and_pad();
+        let num_blinding_rows = self.blind_and_pad();
         let degree = self.gate_instances.len();
         debug!("Degree after blinding & padding: {}", degree);
         let degree_bits = log2_strict(degree);
+        self.check_challenge_security(degree_bits);
         let fri_params = self.fri_params(degree_bits);
-        assert!(
-            fri_params.total_arities() <= degree_bits + rate_bits - cap_height,
-            "FRI total reduction arity is too large.",
-        );
+        let fri_field_bits = F::Extension::order().bits() as usize;
+        if let Err(e) = fri_params
+            .validate()
+            .and_then(|()| fri_params.validate_security(self.config.security_bits, fri_field_bits))
+        {
+            panic!("{}", e);
+        }
 
-        let quotient_degree_factor = self.config.max_quotient_degree_factor;
-        let mut gates = self.gates.iter().cloned().collect::<Vec<_>>();
-        // Gates need to be sorted by their degrees (and ID to make the ordering deterministic) to compute the selector polynomials.
-        gates.sort_unstable_by_key(|g| (g.0.degree(), g.0.id()));
+        timing.push("compute constant polynomials", Level::Debug);
+        let gates = sorted_gates(self.gates.iter().cloned());
+        let partial_products_info =
+            PartialProductsInfo::choose(&self.config, min_quotient_degree_factor(&gates))
+                .unwrap_or_else(|e| panic!("{}", e));
+        debug!("{}", partial_products_info);
+        let quotient_degree_factor = partial_products_info.quotient_degree_factor;
         let (mut constant_vecs, selectors_info) =
             selector_polynomials(&gates, &self.gate_instances, quotient_degree_factor + 1);
 
@@ -1136,45 +2297,377 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         };
 
         constant_vecs.extend(self.constant_polys());
-        let num_constants = constant_vecs.len();
+        timing.pop();
+        progress.update(1, 4);
+
+        CircuitLayout {
+            builder: self,
+            options,
+            num_public_inputs,
+            degree_bits,
+            fri_params,
+            gates,
+            selectors_info,
+            quotient_degree_factor,
+            num_lookup_selectors,
+            num_blinding_rows,
+            constant_vecs,
+            progress,
+        }
+    }
+
+    /// Builds a "full circuit", with both prover and verifier data.
+    pub fn build<C: GenericConfig<D, F = F>>(self) -> CircuitData<F, C, D> {
+        self.build_with_options(BuildOptions::default())
+    }
 
-        let subgroup = F::two_adic_subgroup(degree_bits);
+    /// Builds a "full circuit", and returns where each operation placed by `find_slot` landed.
+    pub fn build_with_layout<C: GenericConfig<D, F = F>>(
+        mut self,
+    ) -> (CircuitData<F, C, D>, OperationLayout) {
+        let operation_slots = core::mem::take(&mut self.operation_slots);
+        let circuit_data = self.build::<C>();
+        // Operations placed by `build` itself, i.e. lookups, are left out.
+        let layout = OperationLayout {
+            slots: operation_slots
+                .into_iter()
+                .enumerate()
+                .map(|(i, slot)| (OperationId(i), slot))
+                .collect(),
+        };
+        (circuit_data, layout)
+    }
+
+    /// Builds a "full circuit", recording the time spent in each step of the build in `timing`.
+    pub fn build_with_timing<C: GenericConfig<D, F = F>>(
+        self,
+        timing: &mut TimingTree,
+    ) -> CircuitData<F, C, D> {
+        self.build_with_options_and_timing(BuildOptions::default(), timing)
+    }
 
-        let k_is = get_unique_coset_shifts(degree, self.config.num_routed_wires);
-        let (sigma_vecs, forest) = timed!(
+    pub fn mock_build<C: GenericConfig<D, F = F>>(self) -> MockCircuitData<F, C, D> {
+        let circuit_data = self.build_with_options(BuildOptions {
+            commit_to_sigma: false,
+            ..BuildOptions::default()
+        });
+        MockCircuitData {
+            prover_only: circuit_data.prover_only,
+            common: circuit_data.common,
+        }
+    }
+    /// Builds a "prover circuit", with data needed to generate proofs but not verify them.
+    pub fn build_prover<C: GenericConfig<D, F = F>>(self) -> ProverCircuitData<F, C, D> {
+        let circuit_data = self.build::<C>();
+        circuit_data.prover_data()
+    }
+
+    /// Builds a "verifier circuit", with data needed to verify proofs but not generate them. This
+    /// skips the work only needed to prove; see [`BuildOptions::skip_prover_data`].
+    pub fn build_verifier<C: GenericConfig<D, F = F>>(self) -> VerifierCircuitData<F, C, D> {
+        let mut timing = TimingTree::new("preprocess", Level::Trace);
+        let options = BuildOptions {
+            skip_prover_data: true,
+            ..BuildOptions::default()
+        };
+        let layout = self.build_layout::<C>(options, &mut timing);
+        let verifier_data = layout.build_verifier_data::<C>(&mut timing);
+        timing.print();
+        verifier_data
+    }
+
+    /// Frees the state which is only needed to generate the prover data, so that a verifier-only
+    /// build doesn't hold on to it while committing to the constants and sigmas.
+    fn drop_prover_state(&mut self) {
+        self.gate_instances = Vec::new();
+        self.copy_constraints = Vec::new();
+        self.generators = Vec::new();
+        self.generator_contexts = BTreeMap::new();
+        self.copy_generators = Vec::new();
+        self.batch_copy_generators = Vec::new();
+        self.constant_generators = Vec::new();
+        self.constants_to_targets = HashMap::new();
+        self.targets_to_constants = HashMap::new();
+        self.base_arithmetic_results = HashMap::new();
+        self.arithmetic_results = HashMap::new();
+        self.mul_const_results = HashMap::new();
+        self.current_slots = HashMap::new();
+        self.virtual_target_contexts = BTreeMap::new();
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitLayout<F, D> {
+    pub fn degree_bits(&self) -> usize {
+        self.degree_bits
+    }
+
+    /// The second phase of building the circuit: generates the sigma polynomials and commits to
+    /// them and to the constant polynomials.
+    pub fn build_commitments<C: GenericConfig<D, F = F>>(
+        &self,
+        timing: &mut TimingTree,
+    ) -> CircuitCommitments<F, C, D> {
+        let degree = 1 << self.degree_bits;
+        let subgroup = F::two_adic_subgroup(self.degree_bits);
+        let k_is = get_unique_coset_shifts(degree, self.builder.config.num_routed_wires);
+        let ((sigma_vecs, forest), fft_root_table) = if self.options.parallelism {
+            // The FFT roots are precomputed on another thread while the sigma polynomials are
+            // generated.
+            timed!(
+                timing,
+                "generate sigma polynomials",
+                join(
+                    || self.builder.sigma_vecs(&k_is, &subgroup),
+                    || self.fft_root_table()
+                )
+            )
+        } else {
+            let sigmas = timed!(
+                timing,
+                "generate sigma polynomials",
+                self.builder.sigma_vecs(&k_is, &subgroup)
+            );
+            (sigmas, self.fft_root_table())
+        };
+        self.commit(k_is, subgroup, sigma_vecs, forest, fft_root_table, timing)
+    }
+
+    /// Like `build_commitments`, but reuses the sigma polynomials and FFT root table of
+    /// `previous`, an earlier build of a circuit with the same wiring whose constants may differ.
+    /// Only the commitment is recomputed. If the wiring changed, everything is recomputed.
+    pub fn build_commitments_reusing<C: GenericConfig<D, F = F>>(
+        &self,
+        previous: CircuitCommitments<F, C, D>,
+        timing: &mut TimingTree,
+    ) -> CircuitCommitments<F, C, D> {
+        if previous.wiring != self.wiring() {
+            debug!("The circuit's wiring changed, so its sigma polynomials can't be reused");
+            return self.build_commitments(timing);
+        }
+        let CircuitCommitments {
+            k_is,
+            subgroup,
+            sigma_vecs,
+            forest,
+            fft_root_table,
+            ..
+        } = previous;
+        let fft_root_table = if self.options.skip_prover_data {
+            None
+        } else {
+            fft_root_table.or_else(|| self.fft_root_table())
+        };
+        self.commit(k_is, subgroup, sigma_vecs, forest, fft_root_table, timing)
+    }
+
+    fn wiring(&self) -> Wiring {
+        let config = &self.builder.config;
+        Wiring {
+            degree_bits: self.degree_bits,
+            num_wires: config.num_wires,
+            num_routed_wires: config.num_routed_wires,
+            num_virtual_targets: self.builder.virtual_target_index,
+            copies: self
+                .builder
+                .copy_constraints
+                .iter()
+                .map(|constraint| constraint.pair)
+                .collect(),
+        }
+    }
+
+    /// Returns the FFT root table needed to prove, or `None` if prover data is skipped.
+    fn fft_root_table(&self) -> Option<Arc<FftRootTable<F>>> {
+        if self.options.skip_prover_data {
+            return None;
+        }
+        let config = &self.builder.config;
+        let rate_bits = config.fri_config.rate_bits;
+        let lg_fft_points =
+            self.degree_bits + max(rate_bits, log2_ceil(self.quotient_degree_factor));
+        match &self.options.reuse_fft_root_table {
+            Some(table) if table.len() == lg_fft_points => Some(table.clone()),
+            _ => Some(self.builder.fft_root_table(1 << lg_fft_points)),
+        }
+    }
+
+    /// Builds the verifier data in one go, as `build_commitments` followed by `finalize_verifier`
+    /// would, but drops the partition of the wires and the builder's prover-only state before
+    /// committing to the constants and sigmas, lowering the peak memory of the build.
+    fn build_verifier_data<C: GenericConfig<D, F = F>>(
+        mut self,
+        timing: &mut TimingTree,
+    ) -> VerifierCircuitData<F, C, D> {
+        let degree = 1 << self.degree_bits;
+        let subgroup = F::two_adic_subgroup(self.degree_bits);
+        let k_is = get_unique_coset_shifts(degree, self.builder.config.num_routed_wires);
+        let (sigma_vecs, _) = timed!(
             timing,
             "generate sigma polynomials",
-            self.sigma_vecs(&k_is, &subgroup)
+            self.builder.sigma_vecs(&k_is, &subgroup)
         );
+        self.builder.drop_prover_state();
+
+        self.progress.update(2, 4);
+        let constants_sigmas_vecs = [self.constant_vecs.clone(), sigma_vecs].concat();
+        let constants_sigmas_cap = if self.options.commit_to_sigma {
+            timed!(
+                timing,
+                "commit to constants and sigmas",
+                self.commit_constants_sigmas::<C>(constants_sigmas_vecs, None, timing)
+            )
+            .merkle_tree
+            .cap
+        } else {
+            PolynomialBatch::<F, C, D>::default().merkle_tree.cap
+        };
+        self.verifier_data(constants_sigmas_cap, k_is)
+    }
+
+    fn commit_constants_sigmas<C: GenericConfig<D, F = F>>(
+        &self,
+        constants_sigmas_vecs: Vec<PolynomialValues<F>>,
+        fft_root_table: Option<&FftRootTable<F>>,
+        timing: &mut TimingTree,
+    ) -> PolynomialBatch<F, C, D> {
+        let config = &self.builder.config;
+        PolynomialBatch::from_values(
+            constants_sigmas_vecs,
+            config.fri_config.rate_bits,
+            PlonkOracle::CONSTANTS_SIGMAS.blinding,
+            config.cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+            timing,
+            fft_root_table,
+        )
+    }
 
-        // Precompute FFT roots.
-        let max_fft_points = 1 << (degree_bits + max(rate_bits, log2_ceil(quotient_degree_factor)));
-        let fft_root_table = fft_root_table(max_fft_points);
-
-        let constants_sigmas_commitment = if commit_to_sigma {
-            let constants_sigmas_vecs = [constant_vecs, sigma_vecs.clone()].concat();
-            PolynomialBatch::<F, C, D>::from_values(
-                constants_sigmas_vecs,
-                rate_bits,
-                PlonkOracle::CONSTANTS_SIGMAS.blinding,
-                cap_height,
-                &mut timing,
-                Some(&fft_root_table),
+    fn commit<C: GenericConfig<D, F = F>>(
+        &self,
+        k_is: Vec<F>,
+        subgroup: Vec<F>,
+        sigma_vecs: Vec<PolynomialValues<F>>,
+        forest: Forest,
+        fft_root_table: Option<Arc<FftRootTable<F>>>,
+        timing: &mut TimingTree,
+    ) -> CircuitCommitments<F, C, D> {
+        self.progress.update(2, 4);
+
+        let constants_sigmas_commitment = if self.options.commit_to_sigma {
+            let constants_sigmas_vecs = [self.constant_vecs.clone(), sigma_vecs.clone()].concat();
+            timed!(
+                timing,
+                "commit to constants and sigmas",
+                self.commit_constants_sigmas(
+                    constants_sigmas_vecs,
+                    fft_root_table.as_deref(),
+                    timing
+                )
             )
         } else {
             PolynomialBatch::<F, C, D>::default()
         };
+        self.progress.update(3, 4);
 
+        CircuitCommitments {
+            wiring: self.wiring(),
+            k_is,
+            subgroup,
+            sigma_vecs,
+            forest,
+            fft_root_table,
+            constants_sigmas_commitment,
+        }
+    }
+
+    /// Returns the common circuit data, checking it against the data expected by cyclic
+    /// recursion, if any.
+    fn common_data(&self, k_is: Vec<F>) -> CommonCircuitData<F, D> {
+        let config = &self.builder.config;
+        let quotient_degree_factor = self.quotient_degree_factor;
+        let num_gate_constraints = self
+            .gates
+            .iter()
+            .map(|gate| gate.0.num_constraints())
+            .max()
+            .expect("No gates?");
+
+        let num_partial_products =
+            num_partial_products(config.num_routed_wires, quotient_degree_factor);
+
+        let num_lookup_polys =
+            num_lookup_polys(config, self.builder.luts.len(), quotient_degree_factor);
+        let common = CommonCircuitData {
+            config: config.clone(),
+            fri_params: self.fri_params.clone(),
+            gates: self.gates.clone(),
+            selectors_info: self.selectors_info.clone(),
+            quotient_degree_factor,
+            num_gate_constraints,
+            num_constants: self.constant_vecs.len(),
+            num_public_inputs: self.num_public_inputs,
+            public_input_commitment: self.builder.public_input_commitment,
+            duplicate_public_inputs: self.builder.duplicate_public_inputs,
+            k_is,
+            num_partial_products,
+            num_lookup_polys,
+            num_lookup_selectors: self.num_lookup_selectors,
+            luts: self.builder.luts.clone(),
+            num_blinding_rows: self.num_blinding_rows,
+            extra_opening_points: self.builder.extra_opening_points.clone(),
+            public_input_names: self.builder.public_input_names.clone(),
+        };
+
+        if let Some(goal_data) = &self.builder.goal_common_data {
+            if goal_data != &common {
+                warn!("The expected circuit data passed to cyclic recursion method did not match the actual circuit");
+                if self.options.require_goal_common_data {
+                    panic!("Failed to build circuit");
+                }
+            }
+        }
+        common
+    }
+
+    /// The last phase of building the circuit: collects the witness generators and assembles the
+    /// circuit data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the layout was built with `skip_prover_data`, or if the circuit doesn't match the
+    /// common data expected by cyclic recursion and `require_goal_common_data` is set.
+    pub fn finalize<C: GenericConfig<D, F = F>>(
+        self,
+        commitments: CircuitCommitments<F, C, D>,
+        timing: &mut TimingTree,
+    ) -> CircuitData<F, C, D> {
+        assert!(
+            !self.options.skip_prover_data,
+            "Prover data was skipped, so only `finalize_verifier` can be used"
+        );
+        let CircuitCommitments {
+            k_is,
+            subgroup,
+            sigma_vecs,
+            forest,
+            fft_root_table,
+            constants_sigmas_commitment,
+            ..
+        } = commitments;
+        let common = self.common_data(k_is);
+        let mut builder = self.builder;
+
+        timing.push("collect generators", Level::Debug);
         // Map between gates where not all generators are used and the gate's number of used generators.
-        let incomplete_gates = self
+        let incomplete_gates = builder
             .current_slots
             .values()
             .flat_map(|current_slot| current_slot.current_slot.values().copied())
             .collect::<HashMap<_, _>>();
 
         // Add gate generators.
-        self.add_generators(
-            self.gate_instances
+        builder.add_generators(
+            builder
+                .gate_instances
                 .iter()
                 .enumerate()
                 .flat_map(|(index, gate)| {
@@ -1188,133 +2681,1274 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                 .collect(),
         );
 
-        // Index generator indices by their watched targets.
+        // Index generator indices by their watched targets. Copy generators are indexed after the
+        // boxed generators, followed by batch copy generators.
+        let num_boxed_generators = builder.generators.len();
+        let first_batch_index = num_boxed_generators + builder.copy_generators.len();
+        let watches = builder
+            .generators
+            .iter()
+            .enumerate()
+            .flat_map(|(i, generator)| generator.0.watch_list().into_iter().map(move |w| (i, w)))
+            .chain(
+                builder
+                    .copy_generators
+                    .iter()
+                    .enumerate()
+                    .map(|(i, copy)| (num_boxed_generators + i, copy.src)),
+            )
+            .chain(
+                builder
+                    .batch_copy_generators
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(i, batch)| {
+                        batch
+                            .pairs
+                            .iter()
+                            .map(move |&(src, _)| (first_batch_index + i, src))
+                    }),
+            );
         let mut generator_indices_by_watches = BTreeMap::new();
-        for (i, generator) in self.generators.iter().enumerate() {
-            for watch in generator.0.watch_list() {
-                let watch_index = forest.target_index(watch);
-                let watch_rep_index = forest.parents[watch_index];
-                generator_indices_by_watches
-                    .entry(watch_rep_index)
-                    .or_insert_with(Vec::new)
-                    .push(i);
-            }
+        for (i, watch) in watches {
+            let watch_index = forest.target_index(watch);
+            let watch_rep_index = forest.parents[watch_index];
+            generator_indices_by_watches
+                .entry(watch_rep_index)
+                .or_insert_with(Vec::new)
+                .push(i);
         }
         for indices in generator_indices_by_watches.values_mut() {
             indices.dedup();
             indices.shrink_to_fit();
         }
 
-        let num_gate_constraints = gates
-            .iter()
-            .map(|gate| gate.0.num_constraints())
-            .max()
-            .expect("No gates?");
-
-        let num_partial_products =
-            num_partial_products(self.config.num_routed_wires, quotient_degree_factor);
-
-        let lookup_degree = self.config.max_quotient_degree_factor - 1;
-        let num_lookup_polys = if num_luts == 0 {
-            0
-        } else {
-            // There is 1 RE polynomial and multiple Sum/LDC polynomials.
-            ceil_div_usize(LookupGate::num_slots(&self.config), lookup_degree) + 1
-        };
-        let constants_sigmas_cap = constants_sigmas_commitment.merkle_tree.cap.clone();
-        let domain_separator = self.domain_separator.unwrap_or_default();
-        let domain_separator_digest = C::Hasher::hash_pad(&domain_separator);
-        // TODO: This should also include an encoding of gate constraints.
-        let circuit_digest_parts = [
-            constants_sigmas_cap.flatten(),
-            domain_separator_digest.to_vec(),
-            vec![
-                F::from_canonical_usize(degree_bits),
-                /* Add other circuit data here */
-            ],
-        ];
-        let circuit_digest = C::Hasher::hash_no_pad(&circuit_digest_parts.concat());
-
-        let common = CommonCircuitData {
-            config: self.config,
-            fri_params,
-            gates,
-            selectors_info,
-            quotient_degree_factor,
-            num_gate_constraints,
-            num_constants,
-            num_public_inputs,
-            k_is,
-            num_partial_products,
-            num_lookup_polys,
-            num_lookup_selectors,
-            luts: self.luts,
-        };
-
-        let mut success = true;
-
-        if let Some(goal_data) = self.goal_common_data {
-            if goal_data != common {
-                warn!("The expected circuit data passed to cyclic recursion method did not match the actual circuit");
-                success = false;
+        // Remember where each partition was connected, so that witness generation failures can be
+        // traced back to the gadget that caused them.
+        let mut partition_contexts = BTreeMap::<usize, String>::new();
+        for CopyConstraint { pair: (a, _), name } in &builder.copy_constraints {
+            let rep_index = forest.parents[forest.target_index(*a)];
+            let context = partition_contexts.entry(rep_index).or_default();
+            if name.len() > context.len() {
+                context.clone_from(name);
             }
         }
+        timing.pop();
+
+        let constants_sigmas_cap = constants_sigmas_commitment.merkle_tree.cap.clone();
+        let domain_separator = builder.domain_separator.unwrap_or_default();
+        let circuit_digest =
+            circuit_digest::<F, C, D>(&constants_sigmas_cap, &domain_separator, &common);
 
         let prover_only = ProverOnlyCircuitData::<F, C, D> {
-            generators: self.generators,
+            generators: builder.generators,
+            copy_generators: builder
+                .copy_generators
+                .into_iter()
+                .map(SimpleGenerator::<F, D>::adapter)
+                .collect(),
+            batch_copy_generators: builder.batch_copy_generators,
             generator_indices_by_watches,
             constants_sigmas_commitment,
-            sigmas: transpose_poly_values(sigma_vecs),
+            sigmas: timed!(
+                timing,
+                "transpose sigma polynomials",
+                transpose_poly_values(sigma_vecs)
+            ),
             subgroup,
-            public_inputs: self.public_inputs,
+            public_inputs: builder.public_inputs,
             representative_map: forest.parents,
-            fft_root_table: Some(fft_root_table),
+            fft_root_table,
             circuit_digest,
-            lookup_rows: self.lookup_rows.clone(),
-            lut_to_lookups: self.lut_to_lookups.clone(),
+            domain_separator: domain_separator.clone(),
+            lookup_rows: builder.lookup_rows,
+            lut_to_lookups: builder.lut_to_lookups,
+            partition_contexts,
+            generator_contexts: builder.generator_contexts,
+            witness_schema: builder.witness_schema,
+            row_attribution: builder.row_contexts.attribution(),
         };
 
         let verifier_only = VerifierOnlyCircuitData::<C, D> {
             constants_sigmas_cap,
             circuit_digest,
+            domain_separator,
         };
 
-        timing.print();
-        #[cfg(feature = "std")]
-        debug!("Building circuit took {}s", start.elapsed().as_secs_f32());
-        (
-            CircuitData {
-                prover_only,
-                verifier_only,
-                common,
-            },
-            success,
+        self.progress.finish();
+        CircuitData {
+            prover_only,
+            verifier_only,
+            common,
+        }
+    }
+
+    /// Like `finalize`, but only assembles the verifier data, skipping the witness generators.
+    pub fn finalize_verifier<C: GenericConfig<D, F = F>>(
+        self,
+        commitments: CircuitCommitments<F, C, D>,
+    ) -> VerifierCircuitData<F, C, D> {
+        self.verifier_data(
+            commitments.constants_sigmas_commitment.merkle_tree.cap,
+            commitments.k_is,
         )
     }
 
-    /// Builds a "full circuit", with both prover and verifier data.
-    pub fn build<C: GenericConfig<D, F = F>>(self) -> CircuitData<F, C, D> {
-        self.build_with_options(true)
+    fn verifier_data<C: GenericConfig<D, F = F>>(
+        self,
+        constants_sigmas_cap: MerkleCap<F, C::Hasher>,
+        k_is: Vec<F>,
+    ) -> VerifierCircuitData<F, C, D> {
+        let common = self.common_data(k_is);
+        let domain_separator = self.builder.domain_separator.unwrap_or_default();
+        let circuit_digest =
+            circuit_digest::<F, C, D>(&constants_sigmas_cap, &domain_separator, &common);
+
+        self.progress.finish();
+        VerifierCircuitData {
+            verifier_only: VerifierOnlyCircuitData {
+                constants_sigmas_cap,
+                circuit_digest,
+                domain_separator,
+            },
+            common,
+        }
     }
+}
 
-    pub fn mock_build<C: GenericConfig<D, F = F>>(self) -> MockCircuitData<F, C, D> {
-        let circuit_data = self.build_with_options(false);
-        MockCircuitData {
-            prover_only: circuit_data.prover_only,
-            common: circuit_data.common,
+/// Sorts gates by their degrees, as needed to compute the selector polynomials, and then by ID, so
+/// that the order doesn't depend on the iteration order of the set they were collected in. The
+/// order of the gates shapes the circuit, and thus its digest.
+fn sorted_gates<F: RichField + Extendable<D>, const D: usize>(
+    gates: impl IntoIterator<Item = GateRef<F, D>>,
+) -> Vec<GateRef<F, D>> {
+    let mut gates = gates.into_iter().collect::<Vec<_>>();
+    gates.sort_unstable_by_key(|g| (g.0.degree(), g.0.id()));
+    gates
+}
+
+/// The number of lookup polynomials of a circuit with `num_luts` lookup tables.
+fn num_lookup_polys(
+    config: &CircuitConfig,
+    num_luts: usize,
+    quotient_degree_factor: usize,
+) -> usize {
+    if num_luts == 0 {
+        0
+    } else {
+        // There is 1 RE polynomial and multiple Sum/LDC polynomials.
+        ceil_div_usize(LookupGate::num_slots(config), quotient_degree_factor - 1) + 1
+    }
+}
+
+/// Domain tag absorbed first into every circuit digest, versioning its encoding.
+const CIRCUIT_DIGEST_TAG: &[u8] = b"plonky2 circuit digest v1";
+
+/// Computes the digest identifying a circuit, which recursive verifiers use to identify the
+/// verifier key. Besides the constants and sigmas cap, it binds the gate set and everything in
+/// `common` that shapes the proof, so that circuits with coincidentally equal caps but different
+/// structure have different digests.
+pub(crate) fn circuit_digest<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    constants_sigmas_cap: &MerkleCap<F, C::Hasher>,
+    domain_separator: &[F],
+    common: &CommonCircuitData<F, D>,
+) -> <C::Hasher as Hasher<F>>::Hash {
+    fn push_usize<F: Field>(elements: &mut Vec<F>, x: usize) {
+        elements.push(F::from_canonical_usize(x));
+    }
+    fn push_bytes<F: Field>(elements: &mut Vec<F>, bytes: &[u8]) {
+        push_usize(elements, bytes.len());
+        elements.extend(bytes.iter().map(|&b| F::from_canonical_u8(b)));
+    }
+
+    let mut elements = Vec::new();
+    push_bytes(&mut elements, CIRCUIT_DIGEST_TAG);
+    elements.extend(constants_sigmas_cap.flatten());
+    elements.extend(C::Hasher::hash_pad(domain_separator).to_vec());
+
+    push_usize(&mut elements, common.gates.len());
+    for (gate, &selector_index) in common
+        .gates
+        .iter()
+        .zip(&common.selectors_info.selector_indices)
+    {
+        push_bytes(&mut elements, gate.0.id().as_bytes());
+        push_usize(&mut elements, selector_index);
+    }
+    push_usize(&mut elements, common.selectors_info.groups.len());
+    for group in &common.selectors_info.groups {
+        push_usize(&mut elements, group.start);
+        push_usize(&mut elements, group.end);
+    }
+
+    let config = &common.config;
+    for x in [
+        config.num_wires,
+        config.num_routed_wires,
+        config.num_constants,
+        config.use_base_arithmetic_gate as usize,
+        config.security_bits,
+        config.num_challenges,
+        config.zero_knowledge as usize,
+        config.max_quotient_degree_factor,
+    ] {
+        push_usize(&mut elements, x);
+    }
+    let fri_params = &common.fri_params;
+    let fri_config = &fri_params.config;
+    for x in [
+        fri_config.rate_bits,
+        fri_config.cap_height,
+        fri_config.proof_of_work_bits as usize,
+        fri_config.num_query_rounds,
+    ] {
+        push_usize(&mut elements, x);
+    }
+    match &fri_config.reduction_strategy {
+        FriReductionStrategy::Fixed(arities) => {
+            push_usize(&mut elements, 0);
+            push_usize(&mut elements, arities.len());
+            elements.extend(arities.iter().map(|&a| F::from_canonical_usize(a)));
+        }
+        &FriReductionStrategy::ConstantArityBits(arity_bits, final_poly_bits) => {
+            push_usize(&mut elements, 1);
+            push_usize(&mut elements, arity_bits);
+            push_usize(&mut elements, final_poly_bits);
+        }
+        &FriReductionStrategy::MinSize(opt_max_arity_bits) => {
+            push_usize(&mut elements, 2);
+            push_usize(&mut elements, opt_max_arity_bits.map_or(0, |bits| bits + 1));
         }
     }
-    /// Builds a "prover circuit", with data needed to generate proofs but not verify them.
-    pub fn build_prover<C: GenericConfig<D, F = F>>(self) -> ProverCircuitData<F, C, D> {
-        // TODO: Can skip parts of this.
-        let circuit_data = self.build::<C>();
-        circuit_data.prover_data()
+    push_usize(&mut elements, fri_params.hiding as usize);
+    push_usize(&mut elements, fri_params.degree_bits);
+    push_usize(&mut elements, fri_params.reduction_arity_bits.len());
+    elements.extend(
+        fri_params
+            .reduction_arity_bits
+            .iter()
+            .map(|&a| F::from_canonical_usize(a)),
+    );
+
+    for x in [
+        common.quotient_degree_factor,
+        common.num_gate_constraints,
+        common.num_constants,
+        common.num_public_inputs,
+        common.num_partial_products,
+        common.num_lookup_polys,
+        common.num_lookup_selectors,
+    ] {
+        push_usize(&mut elements, x);
+    }
+    push_usize(&mut elements, common.k_is.len());
+    elements.extend_from_slice(&common.k_is);
+    push_usize(&mut elements, common.luts.len());
+    for lut in &common.luts {
+        push_usize(&mut elements, lut.len());
+        for &(input, output) in lut.iter() {
+            elements.push(F::from_canonical_u16(input));
+            elements.push(F::from_canonical_u16(output));
+        }
+    }
+    // Appended only for chunked commitments, so that the digests of other circuits are unchanged.
+    if let PublicInputCommitment::Chunked { chunk_size } = common.public_input_commitment {
+        push_usize(&mut elements, chunk_size);
+    }
+    // Likewise for a duplicate public input policy other than the default, tagged to tell it
+    // apart from a chunk size.
+    if common.duplicate_public_inputs != DuplicatePublicInputs::Allow {
+        push_bytes(&mut elements, b"duplicate public inputs");
+        push_usize(&mut elements, common.duplicate_public_inputs as usize);
+    }
+    // And for extra opening points, tagged to tell them apart from a chunk size.
+    if !common.extra_opening_points.is_empty() {
+        push_bytes(&mut elements, b"extra opening points");
+        push_usize(&mut elements, common.extra_opening_points.len());
+        for opening_point in &common.extra_opening_points {
+            let shift = opening_point.shift.rem_euclid(common.degree() as isize);
+            push_usize(&mut elements, shift as usize);
+            push_usize(&mut elements, opening_point.oracles.len());
+            for &oracle in &opening_point.oracles {
+                push_usize(&mut elements, oracle);
+            }
+        }
+    }
+    // Likewise for a forced quotient degree factor.
+    if let Some(factor) = common.config.force_quotient_degree_factor {
+        push_bytes(&mut elements, b"force quotient degree factor");
+        push_usize(&mut elements, factor);
+    }
+    // And for per-oracle cap heights.
+    if common.config.oracle_cap_heights != OracleCapHeights::uniform() {
+        push_bytes(&mut elements, b"oracle cap heights");
+        for &cap_height in &fri_params.initial_cap_heights {
+            push_usize(&mut elements, cap_height);
+        }
+    }
+    // And for named public inputs, which verifiers rely on to read the public inputs.
+    if !common.public_input_names.is_empty() {
+        push_bytes(&mut elements, b"public input names");
+        push_usize(&mut elements, common.public_input_names.len());
+        for (name, range) in &common.public_input_names {
+            push_bytes(&mut elements, name.as_bytes());
+            push_usize(&mut elements, range.start);
+            push_usize(&mut elements, range.end);
+        }
     }
 
-    /// Builds a "verifier circuit", with data needed to verify proofs but not generate them.
-    pub fn build_verifier<C: GenericConfig<D, F = F>>(self) -> VerifierCircuitData<F, C, D> {
-        // TODO: Can skip parts of this.
-        let circuit_data = self.build::<C>();
-        circuit_data.verifier_data()
+    C::Hasher::hash_no_pad(&elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use plonky2_field::types::Sample;
+    use rand::rngs::OsRng;
+    use rand::Rng;
+
+    use super::*;
+    use crate::gates::arithmetic_base::ArithmeticGate;
+    use crate::gates::base_sum::BaseSumGate;
+    use crate::gates::coset_interpolation::CosetInterpolationGate;
+    use crate::gates::poseidon::PoseidonGate;
+    use crate::gates::random_access::RandomAccessGate;
+    use crate::hash::poseidon::PoseidonHash;
+    use crate::iop::generator::generate_partial_witness;
+    use crate::iop::witness::{PartialWitness, Witness, WitnessWrite};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+    use crate::plonk::plonk_common::{reduce_with_powers_circuit, salt_size, PlonkOracle};
+    use crate::util::serialization::DefaultGateSerializer;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn sample_circuit() -> CircuitBuilder<F, D> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let sum = builder.add(x, y);
+        let product = builder.mul(x, y);
+        let ratio = builder.div(sum, product);
+        builder.register_public_input(ratio);
+        builder.register_public_input(x);
+        builder
+    }
+
+    #[test]
+    fn circuit_flamegraph_nests_contexts() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        builder.add_gate(NoopGate, vec![]);
+        builder.push_context(Level::Debug, "a");
+        builder.add_gate(NoopGate, vec![]);
+        builder.push_context(Level::Debug, "b");
+        for _ in 0..2 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        builder.push_context(Level::Debug, "c; d");
+        for _ in 0..3 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        builder.pop_context();
+        for _ in 0..4 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        builder.pop_context();
+        // Scope `a` is left open, and counted up to the current number of gates.
+        builder.add_gate(NoopGate, vec![]);
+
+        let folded = builder.circuit_flamegraph();
+        let lines = folded.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            ["root 1", "root;a 2", "root;a;b 6", "root;a;b;c, d 3"]
+        );
+
+        #[cfg(feature = "std")]
+        {
+            let path = std::env::temp_dir().join("plonky2_circuit_flamegraph_test.folded");
+            builder.export_circuit_flamegraph(&path).unwrap();
+            assert_eq!(std::fs::read_to_string(&path).unwrap(), folded + "\n");
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn context_guards_close_on_early_returns() {
+        fn add_noops(builder: &mut CircuitBuilder<F, D>, n: usize) {
+            for _ in 0..n {
+                builder.add_gate(NoopGate, vec![]);
+            }
+        }
+
+        fn gadget(builder: &mut CircuitBuilder<F, D>, fail: bool) -> Option<()> {
+            let mut outer = builder.context(Level::Debug, "gadget");
+            add_noops(&mut outer, 1);
+            let mut inner = outer.context(Level::Debug, "inner");
+            add_noops(&mut inner, 2);
+            if fail {
+                return None;
+            }
+            add_noops(&mut inner, 3);
+            Some(())
+        }
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        assert_eq!(gadget(&mut builder, true), None);
+        let sum = builder.with_context(Level::Debug, "after", |builder| {
+            add_noops(builder, 4);
+            1 + 1
+        });
+        assert_eq!(sum, 2);
+
+        let lines = builder
+            .circuit_flamegraph()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        // `inner` was closed before its last gates, and `after` is a sibling of `gadget`.
+        assert_eq!(
+            lines,
+            ["root;gadget 1", "root;gadget;inner 2", "root;after 4"]
+        );
+    }
+
+    /// Runs `f`, which must panic, and returns the panic message.
+    #[cfg(feature = "std")]
+    fn panic_message(f: impl FnOnce()) -> String {
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_err();
+        err.downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn routed_wire_exhaustion_is_reported() {
+        // A list of 64 elements needs 66 routed wires to be accessed.
+        let config = CircuitConfig {
+            num_routed_wires: 40,
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let message = panic_message(|| {
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let index = builder.add_virtual_target();
+            let list = builder.add_virtual_targets(64);
+            builder.with_context(Level::Debug, "select a leaf", |builder| {
+                builder.random_access(index, list)
+            });
+        });
+        assert!(message.starts_with("\"RandomAccessGate"), "{message}");
+        assert!(message.contains(
+            "(added in context \"root > select a leaf\") requires 66 routed wires, but the CircuitConfig \
+             has num_routed_wires = 40 (of num_wires = 135)"
+        ));
+
+        // Poseidon routes its 12 inputs, 12 outputs and swap flag.
+        let config = CircuitConfig {
+            num_routed_wires: 20,
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let message = panic_message(|| {
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let inputs = builder.add_virtual_targets(8);
+            builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
+        });
+        assert!(message.starts_with("\"PoseidonGate"), "{message}");
+        assert!(message
+            .contains("requires 25 routed wires, but the CircuitConfig has num_routed_wires = 20"));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn wire_exhaustion_suggests_wide_config() {
+        let config = CircuitConfig {
+            num_wires: 100,
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let message = panic_message(|| {
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let inputs = builder.add_virtual_targets(8);
+            builder.push_context(Level::Debug, "outer");
+            builder.push_context(Level::Debug, "hash");
+            builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
+        });
+        assert!(message.contains(
+            "(added in context \"root > outer > hash\") requires 135 wires, but the CircuitConfig has \
+             num_wires = 100; CircuitConfig::wide_ecc_config() has 234 wires"
+        ));
+    }
+
+    #[test]
+    fn identical_gates_and_operations_are_deduplicated() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        builder.add_gate_to_gate_set(GateRef::new(ArithmeticGate::new_from_config(&config)));
+        builder.add_gate_to_gate_set(GateRef::new(ArithmeticGate::new_from_config(&config)));
+        assert_eq!(builder.gates.len(), 1);
+
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let product = builder.mul(x, y);
+        let num_gates = builder.num_gates();
+        assert_eq!(builder.mul(x, y), product);
+        assert_eq!(builder.num_gates(), num_gates);
+    }
+
+    #[test]
+    fn build_is_deterministic() {
+        let data_a = sample_circuit().build::<C>();
+        let data_b = sample_circuit().build::<C>();
+        assert_eq!(data_a.common, data_b.common);
+        assert_eq!(
+            data_a.verifier_only.circuit_digest,
+            data_b.verifier_only.circuit_digest
+        );
+        assert_eq!(
+            data_a.prover_only.representative_map,
+            data_b.prover_only.representative_map
+        );
+    }
+
+    #[test]
+    fn phased_build_matches_build() -> Result<()> {
+        let data = sample_circuit().build::<C>();
+
+        let mut timing = TimingTree::default();
+        let layout = sample_circuit().build_layout::<C>(BuildOptions::default(), &mut timing);
+        assert_eq!(layout.degree_bits(), data.common.degree_bits());
+        let commitments = layout.build_commitments::<C>(&mut timing);
+        let phased_data = layout.finalize(commitments, &mut timing);
+        assert_eq!(phased_data.common, data.common);
+        assert_eq!(phased_data.verifier_only, data.verifier_only);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(Target::VirtualTarget { index: 0 }, F::rand());
+        pw.set_target(Target::VirtualTarget { index: 1 }, F::rand());
+        let proof = phased_data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    fn build_verifier_skips_prover_data() {
+        let data = sample_circuit().build::<C>();
+        assert_eq!(sample_circuit().build_verifier::<C>(), data.verifier_data());
+
+        let options = BuildOptions {
+            skip_prover_data: true,
+            ..BuildOptions::default()
+        };
+        let mut timing = TimingTree::default();
+        let layout = sample_circuit().build_layout::<C>(options, &mut timing);
+        let commitments = layout.build_commitments::<C>(&mut timing);
+        assert!(commitments.fft_root_table.is_none());
+        let verifier_data = layout.finalize_verifier(commitments);
+        assert_eq!(verifier_data, data.verifier_data());
+    }
+
+    /// Builds a circuit computing `c * x^2`, whose wiring doesn't depend on `c`.
+    fn scaled_square_circuit(c: u64) -> CircuitBuilder<F, D> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_target();
+        let square = builder.square(x);
+        let scaled = builder.mul_const(F::from_canonical_u64(c), square);
+        builder.register_public_input(scaled);
+        builder
+    }
+
+    #[test]
+    fn rebuild_reuses_sigmas_when_only_constants_change() -> Result<()> {
+        let mut timing = TimingTree::default();
+        let layout = scaled_square_circuit(3).build_layout::<C>(Default::default(), &mut timing);
+        let commitments = layout.build_commitments::<C>(&mut timing);
+        let fft_root_table = commitments.fft_root_table.clone().unwrap();
+
+        let layout = scaled_square_circuit(5).build_layout::<C>(Default::default(), &mut timing);
+        let commitments = layout.build_commitments_reusing(commitments, &mut timing);
+        assert!(Arc::ptr_eq(
+            commitments.fft_root_table.as_ref().unwrap(),
+            &fft_root_table
+        ));
+        let data = layout.finalize(commitments, &mut timing);
+        let expected = scaled_square_circuit(5).build::<C>();
+        assert_eq!(data.verifier_only, expected.verifier_only);
+        assert_eq!(data.prover_only.sigmas, expected.prover_only.sigmas);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(Target::VirtualTarget { index: 0 }, F::TWO);
+        let proof = data.prove(pw)?;
+        assert_eq!(proof.public_inputs, [F::from_canonical_u64(20)]);
+        expected.verify(proof)?;
+
+        // Changing the wiring makes the sigma polynomials unusable, so they're recomputed.
+        let layout = scaled_square_circuit(3).build_layout::<C>(Default::default(), &mut timing);
+        let commitments = layout.build_commitments::<C>(&mut timing);
+        let layout = sample_circuit().build_layout::<C>(Default::default(), &mut timing);
+        let commitments = layout.build_commitments_reusing(commitments, &mut timing);
+        let data = layout.finalize(commitments, &mut timing);
+        assert_eq!(
+            data.verifier_only,
+            sample_circuit().build::<C>().verifier_only
+        );
+        Ok(())
+    }
+
+    /// `build_verifier` is faster than a full build because it skips collecting the witness
+    /// generators, transposing the sigmas and computing the FFT root table. The peak memory of
+    /// both builds is compared in `tests/build_verifier.rs`.
+    #[cfg(feature = "timing")]
+    #[test]
+    fn build_verifier_skips_prover_work() {
+        let options = BuildOptions {
+            skip_prover_data: true,
+            ..BuildOptions::default()
+        };
+        let mut timing = TimingTree::new("build", Level::Debug);
+        let layout = sample_circuit().build_layout::<C>(options, &mut timing);
+        assert!(layout.fft_root_table().is_none());
+        let verifier_data = layout.build_verifier_data::<C>(&mut timing);
+        assert_eq!(verifier_data, sample_circuit().build::<C>().verifier_data());
+
+        let scopes = timing
+            .to_entries()
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect::<Vec<_>>();
+        assert!(scopes.contains(&"commit to constants and sigmas".to_string()));
+        for skipped in ["collect generators", "transpose sigma polynomials"] {
+            assert!(!scopes.contains(&skipped.to_string()), "{} ran", skipped);
+        }
+    }
+
+    #[test]
+    fn operation_layout_is_deterministic() -> Result<()> {
+        let layout_circuit = || {
+            let mut builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            let index = builder.add_virtual_target();
+            let squared_index = builder.square(index);
+            builder.register_public_input(squared_index);
+            let list = builder.add_virtual_targets(8);
+            let first_access = builder.next_operation_id();
+            let claimed = builder.random_access(index, list.clone());
+            builder.random_access(index, list[..4].to_vec());
+            let num_accesses = builder.next_operation_id().0 - first_access.0;
+            builder.register_public_input(claimed);
+            let (data, layout) = builder.build_with_layout::<C>();
+            (data, layout, first_access, num_accesses, index, list)
+        };
+
+        let (data_a, layout_a, first_access, num_accesses, index, list) = layout_circuit();
+        let (data_b, layout_b, ..) = layout_circuit();
+        assert_eq!(layout_a, layout_b);
+        assert_eq!(
+            data_a.verifier_only.circuit_digest,
+            data_b.verifier_only.circuit_digest
+        );
+
+        // The two accesses use different gates, as the lists have different lengths.
+        assert_eq!(num_accesses, 2);
+        let (row, copy) = layout_a.get(first_access).unwrap();
+        let (second_row, _) = layout_a.get(OperationId(first_access.0 + 1)).unwrap();
+        assert_ne!(row, second_row);
+
+        // The first access's claimed element is found at the slot given by the layout.
+        let mut pw = PartialWitness::new();
+        pw.set_target(index, F::from_canonical_usize(3));
+        for (i, &item) in list.iter().enumerate() {
+            pw.set_target(item, F::from_canonical_usize(10 * i));
+        }
+        let witness = data_a.generate_witness(pw)?;
+        let gate = RandomAccessGate::<F, D>::new_from_config(&data_a.common.config, 3);
+        assert_eq!(
+            witness.get_target(Target::wire(row, gate.wire_claimed_element(copy))),
+            F::from_canonical_usize(30)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn circuit_digest_is_stable() {
+        // Pinned so that changes to the digest encoding, which change every verifier key, are
+        // deliberate.
+        let data = sample_circuit().build::<C>();
+        assert_eq!(
+            data.verifier_only.circuit_digest.elements,
+            [
+                16548931118631439472,
+                14639299626121164550,
+                2763243523228367692,
+                14328841473651247835,
+            ]
+            .map(F::from_canonical_u64)
+        );
+    }
+
+    #[test]
+    fn gate_wire_reads_advice_wires() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let num_routed_wires = config.num_routed_wires;
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let inputs = builder.add_virtual_targets(4);
+        let hash = builder.hash_n_to_hash_no_pad::<PoseidonHash>(inputs.clone());
+        builder.register_public_inputs(&hash.elements);
+        let row = builder
+            .gate_instances
+            .iter()
+            .position(|g| g.gate_ref.0.as_any().is::<PoseidonGate<F, D>>())
+            .unwrap();
+        let advice = builder.gate_wire(row, num_routed_wires);
+        assert!(!advice.is_routable(&builder.config));
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&inputs, &F::rand_vec(4));
+        let witness = generate_partial_witness(pw.clone(), &data.prover_only, &data.common)?;
+        assert!(witness.try_get_target(advice).is_some());
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    #[should_panic(expected = "Tried to route a wire that isn't routable")]
+    fn gate_wire_advice_wires_cant_be_routed() {
+        let config = CircuitConfig::standard_recursion_config();
+        let num_routed_wires = config.num_routed_wires;
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let row = builder.add_gate(PoseidonGate::<F, D>::new(), vec![]);
+        let advice = builder.gate_wire(row, num_routed_wires);
+        let x = builder.add_virtual_target();
+        builder.connect(x, advice);
+    }
+
+    #[test]
+    #[should_panic(expected = "Column 3 is routed")]
+    fn gate_wire_rejects_routed_columns() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let row = builder.add_gate(PoseidonGate::<F, D>::new(), vec![]);
+        builder.gate_wire(row, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "only has")]
+    fn gate_wire_rejects_missing_columns() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let row = builder.add_gate(NoopGate, vec![]);
+        builder.gate_wire(row, 0);
+    }
+
+    #[test]
+    fn base_arithmetic_gate_can_be_disabled() -> Result<()> {
+        for use_base_arithmetic_gate in [true, false] {
+            let config = CircuitConfig {
+                use_base_arithmetic_gate,
+                ..CircuitConfig::standard_recursion_config()
+            };
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_target();
+            let y = builder.add_virtual_target();
+            let terms = builder.add_virtual_targets(3);
+            let product = builder.mul(x, y);
+            let sum = builder.add(product, x);
+            let cube = builder.exp_u64(sum, 3);
+            let ratio = builder.div(cube, y);
+            let reduced = reduce_with_powers_circuit(&mut builder, &terms, ratio);
+            let bits = builder.split_le(x, 32);
+            let x_again = builder.le_sum(bits.into_iter());
+            builder.register_public_input(reduced);
+            builder.register_public_input(x_again);
+
+            let data = builder.build::<C>();
+            let has_base_arithmetic_gate = data
+                .common
+                .gates
+                .iter()
+                .any(|gate| gate.0.as_any().is::<ArithmeticGate>());
+            assert_eq!(has_base_arithmetic_gate, use_base_arithmetic_gate);
+
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u32(u32::MAX));
+            pw.set_target(y, F::rand());
+            pw.set_target_arr(&terms, &F::rand_vec(3));
+            let proof = data.prove(pw)?;
+            data.verify(proof)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn circuit_digest_binds_gates() {
+        let data = sample_circuit().build::<C>();
+        let cap = &data.verifier_only.constants_sigmas_cap;
+        assert_eq!(
+            circuit_digest::<F, C, D>(cap, &[], &data.common),
+            data.verifier_only.circuit_digest
+        );
+
+        // Swapping a single gate type changes the digest, even with the same cap.
+        let mut common = data.common.clone();
+        let public_input_gate = GateRef::new(PublicInputGate);
+        assert_ne!(common.gates[0], public_input_gate);
+        common.gates[0] = public_input_gate;
+        assert_ne!(
+            circuit_digest::<F, C, D>(cap, &[], &common),
+            data.verifier_only.circuit_digest
+        );
+    }
+
+    #[test]
+    fn constant_table_holds_values_as_constants() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let num_constants = config.num_constants;
+        let k_is = get_unique_coset_shifts::<F>(1 << 12, config.num_routed_wires);
+        let g = F::primitive_root_of_unity(12);
+        let g_powers = g.powers().take(5).collect::<Vec<_>>();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let table = builder.constant_table(&k_is);
+        assert_eq!(builder.constant_table(&k_is), table);
+        let powers_table = builder.constant_table(&g_powers);
+        for i in 0..k_is.len() {
+            let c = builder.read_table(table, i);
+            builder.register_public_input(c);
+        }
+        for i in 0..g_powers.len() {
+            let c = builder.read_table(powers_table, i);
+            builder.register_public_input(c);
+        }
+        // Both tables share one gate type, each row holding up to `num_constants` values.
+        let gate_rows = builder.cost_report::<C>().gate_rows;
+        let table_gate_ids = gate_rows
+            .keys()
+            .filter(|id| id.starts_with("ConstantTableGate"))
+            .collect::<Vec<_>>();
+        assert_eq!(table_gate_ids.len(), 1);
+        let table_gate = GateRef::<F, D>::new(ConstantTableGate::new(num_constants));
+        assert_eq!(
+            gate_rows[&table_gate.0.id()],
+            ceil_div_usize(k_is.len(), num_constants)
+                + ceil_div_usize(g_powers.len(), num_constants)
+        );
+
+        let data = builder.build::<C>();
+        let proof = data.prove(PartialWitness::new())?;
+        assert_eq!(proof.public_inputs, [k_is, g_powers].concat());
+        data.verify(proof)
+    }
+
+    #[test]
+    fn rom_reads_match_native_indexing() -> Result<()> {
+        let mut rng = OsRng;
+        let values = F::rand_vec(1024);
+        let indices = (0..100)
+            .map(|_| rng.gen_range(0..values.len()))
+            .collect::<Vec<_>>();
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let rom = builder.add_rom(&values);
+        let index_targets = builder.add_virtual_targets(indices.len());
+        for &index in &index_targets {
+            let value = builder.rom_read(rom, index);
+            builder.register_public_input(value);
+        }
+
+        let mut pw = PartialWitness::new();
+        for (&t, &index) in index_targets.iter().zip(&indices) {
+            pw.set_target(t, F::from_canonical_usize(index));
+        }
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        let expected = indices.iter().map(|&i| values[i]).collect::<Vec<_>>();
+        assert_eq!(proof.public_inputs, expected);
+        data.verify(proof)
+    }
+
+    #[test]
+    fn rom_takes_fewer_rows_than_random_access() {
+        let config = CircuitConfig::standard_recursion_config();
+        let values = F::rand_vec(64);
+
+        let mut naive = CircuitBuilder::<F, D>::new(config.clone());
+        let table = naive.constants(&values);
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let rom = builder.add_rom(&values);
+        for _ in 0..100 {
+            let index = naive.add_virtual_target();
+            naive.random_access(index, table.clone());
+            let index = builder.add_virtual_target();
+            builder.rom_read(rom, index);
+        }
+        assert!(builder.cost_report::<C>().num_rows < naive.cost_report::<C>().num_rows / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "Incorrect input value provided")]
+    fn rom_rejects_out_of_range_index() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let rom = builder.add_rom(&F::rand_vec(10));
+        let index = builder.constant(F::from_canonical_usize(10));
+        let value = builder.rom_read(rom, index);
+        builder.register_public_input(value);
+        let data = builder.build::<C>();
+        data.prove(PartialWitness::new()).unwrap();
+    }
+
+    #[test]
+    fn rom_read_out_of_range_fails_to_verify() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let values = (0..10)
+            .map(|i| F::from_canonical_u64(i * 1000 + 7))
+            .collect::<Vec<_>>();
+        let rom = builder.add_rom(&values);
+        let index = builder.constant(F::from_canonical_usize(10));
+        let value = builder.rom_read(rom, index);
+        builder.register_public_input(value);
+        let mut data = builder.build::<C>();
+
+        // Without the lookup generator, the prover can claim any value for the read, which the
+        // lookup argument must then reject since the index isn't in the table.
+        data.prover_only
+            .generators
+            .retain(|generator| generator.0.id() != "LookupGenerator");
+        let mut pw = PartialWitness::new();
+        pw.set_target(value, values[0]);
+        let proof = data.prove(pw).unwrap();
+        assert!(data.verify(proof).is_err());
+    }
+
+    #[test]
+    fn zero_knowledge_adds_blinding_rows() -> Result<()> {
+        let mut degrees = Vec::new();
+        for zero_knowledge in [false, true] {
+            let config = CircuitConfig {
+                zero_knowledge,
+                ..CircuitConfig::standard_recursion_config()
+            };
+            let num_wires = config.num_wires;
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_target();
+            let y = builder.square(x);
+            builder.register_public_input(y);
+
+            let num_gates = builder.num_gates();
+            let data = builder.build::<C>();
+            let common = &data.common;
+            assert_eq!(common.fri_params.hiding, zero_knowledge);
+            if zero_knowledge {
+                // Each opening at zeta reveals D values, and each query round reveals a leaf
+                // value, the other evaluations of each coset and the final polynomial.
+                let fri_params = &common.fri_params;
+                let arities = fri_params
+                    .reduction_arity_bits
+                    .iter()
+                    .map(|&bits| 1 << bits);
+                let query_round_values = 1 + D
+                    * (arities.map(|arity| arity - 1).sum::<usize>() + fri_params.final_poly_len());
+                let revealed_values = fri_params.config.num_query_rounds * query_round_values;
+                let num_blinding_rows = (D + revealed_values) + 2 * (2 * D + revealed_values);
+                assert_eq!(common.num_blinding_rows, num_blinding_rows);
+                // The blinding rows alone don't fit in the circuit without zero knowledge.
+                assert!(num_blinding_rows > degrees[0]);
+                assert!(common.degree() >= num_gates + num_blinding_rows);
+            } else {
+                assert_eq!(common.num_blinding_rows, 0);
+            }
+            common.validate()?;
+
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::rand());
+            let proof = data.prove(pw)?;
+            let (wires_leaf, _) = &proof.proof.opening_proof.query_round_proofs[0]
+                .initial_trees_proof
+                .evals_proofs[PlonkOracle::WIRES.index];
+            assert_eq!(wires_leaf.len(), num_wires + salt_size(zero_knowledge));
+            data.verify(proof)?;
+            degrees.push(data.common.degree());
+        }
+        assert!(degrees[1] > degrees[0]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "Public input chunks can't be empty")]
+    fn empty_public_input_chunks_are_rejected() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        builder.set_public_input_commitment(PublicInputCommitment::Chunked { chunk_size: 0 });
+    }
+
+    #[test]
+    #[should_panic(expected = "FRI reduction strategy Fixed([4, 4, 4])")]
+    fn oversized_fixed_reduction_strategy_is_rejected() {
+        let mut config = CircuitConfig::standard_recursion_config();
+        config.fri_config.reduction_strategy = FriReductionStrategy::Fixed(vec![4, 4, 4]);
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        builder.register_public_input(x);
+        builder.build::<C>();
+    }
+
+    #[test]
+    #[should_panic(expected = "FRI final polynomial degree bits 20 exceed degree_bits")]
+    fn oversized_final_poly_degree_is_rejected() {
+        let mut config = CircuitConfig::standard_recursion_config();
+        config.fri_config.final_poly_degree_bits = 20;
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        builder.register_public_input(x);
+        builder.build::<C>();
+    }
+
+    #[test]
+    #[should_panic(expected = "FRI params fall short of target security 100, reaching only 91")]
+    fn weakened_fri_config_is_rejected() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        // Stopping at a larger final polynomial doesn't make up for fewer query rounds.
+        builder.config.fri_config.num_query_rounds = 25;
+        builder.config.fri_config.final_poly_degree_bits = 1;
+        let x = builder.add_virtual_target();
+        builder.register_public_input(x);
+        builder.build::<C>();
+    }
+
+    #[test]
+    fn duplicate_public_inputs_are_deduplicated() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        builder.set_duplicate_public_inputs(DuplicatePublicInputs::Dedup);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let z = builder.add_virtual_target();
+        builder.register_public_inputs(&[x, y, x, z, y]);
+        assert_eq!(builder.num_public_inputs(), 3);
+        assert_eq!(builder.public_input_index_of(x), Some(0));
+        assert_eq!(builder.public_input_index_of(y), Some(1));
+        assert_eq!(builder.public_input_index_of(z), Some(2));
+        let unregistered = builder.add_virtual_target();
+        assert_eq!(builder.public_input_index_of(unregistered), None);
+
+        let data = builder.build::<C>();
+        assert_eq!(data.common.num_public_inputs, 3);
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::ONE);
+        pw.set_target(y, F::TWO);
+        pw.set_target(z, F::NEG_ONE);
+        let proof = data.prove(pw)?;
+        assert_eq!(proof.public_inputs, [F::ONE, F::TWO, F::NEG_ONE]);
+        data.verify(proof)
+    }
+
+    #[test]
+    fn duplicate_public_inputs_are_allowed_by_default() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.register_public_inputs(&[x, y, x]);
+        assert_eq!(builder.num_public_inputs(), 3);
+        assert_eq!(builder.public_input_index_of(x), Some(0));
+        assert_eq!(builder.public_input_index_of(y), Some(1));
+        let data = builder.build::<C>();
+        assert_eq!(data.common.num_public_inputs, 3);
+    }
+
+    #[test]
+    fn duplicate_public_input_policy_is_committed() {
+        let build = |policy| {
+            let config = CircuitConfig::standard_recursion_config();
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            builder.set_duplicate_public_inputs(policy);
+            let x = builder.add_virtual_target();
+            builder.register_public_input(x);
+            builder.build::<C>()
+        };
+        let allowed = build(DuplicatePublicInputs::Allow);
+        let deduplicated = build(DuplicatePublicInputs::Dedup);
+        assert_eq!(
+            deduplicated.common.duplicate_public_inputs,
+            DuplicatePublicInputs::Dedup
+        );
+        // The circuits are otherwise identical, but a verifier must not confuse them.
+        assert_ne!(
+            allowed.verifier_only.circuit_digest,
+            deduplicated.verifier_only.circuit_digest
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "registered as public input 0 in context \"root > outer\", and again in context \"root > outer > inner\""
+    )]
+    fn duplicate_public_inputs_can_panic() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        builder.set_duplicate_public_inputs(DuplicatePublicInputs::Panic);
+        let x = builder.add_virtual_target();
+        let mut outer = builder.context(log::Level::Debug, "outer");
+        outer.register_public_input(x);
+        let mut inner = outer.context(log::Level::Debug, "inner");
+        inner.register_public_input(x);
+    }
+
+    #[test]
+    #[should_panic(expected = "virtual targets [1] added in context \"root > hint\"")]
+    fn strict_mode_rejects_unconstrained_targets() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        builder.set_strict(true);
+        let x = builder.add_virtual_target();
+        builder.declare_external_input(x);
+        let mut hint = builder.context(log::Level::Debug, "hint");
+        // `y` is multiplied by `x`, but nothing constrains it.
+        let y = hint.add_virtual_target();
+        let z = hint.mul(x, y);
+        hint.register_public_input(z);
+        drop(hint);
+        builder.build::<C>();
+    }
+
+    #[test]
+    fn strict_mode_accepts_constrained_circuit() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        builder.set_strict(true);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let e = builder.add_virtual_extension_target();
+        builder.declare_input("x", &[x]);
+        builder.declare_external_input(y);
+        builder.declare_external_inputs(&e.0);
+
+        let equal = builder.is_equal(x, y);
+        builder.assert_nonzero(y);
+        let one = builder.one_extension();
+        let quotient = builder.div_extension(one, e);
+        let (low, high) = builder.split_low_high(x, 16, 32);
+        let bits = builder.split_le(y, 8);
+        let selected = builder.random_access(bits[0].target, vec![low, high]);
+        let lut = builder.add_lookup_table_from_fn(|i| i + 1, &[0, 1, 2, 3]);
+        let looked_up = builder.add_lookup_from_index(bits[1].target, lut);
+        let hash = builder.hash_n_to_hash_no_pad::<PoseidonHash>(vec![
+            equal.target,
+            quotient.0[0],
+            selected,
+            looked_up,
+        ]);
+        builder.register_public_inputs(&hash.elements);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(0x12345678));
+        pw.set_target(y, F::from_canonical_u32(3));
+        pw.set_extension_target(e, <F as Extendable<D>>::Extension::TWO);
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "ArithmeticGate { num_ops: 20 } (added in context \"root > scaling\") takes 2 constants, but was given 1"
+    )]
+    fn add_gate_rejects_wrong_number_of_constants() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let mut scaling = builder.context(log::Level::Debug, "scaling");
+        scaling.add_gate(ArithmeticGate::new_from_config(&config), vec![F::ONE]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "was given invalid constants []: 64 base 2 limbs overflow the field, which fits 63"
+    )]
+    fn add_gate_rejects_invalid_constants() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        builder.add_gate(BaseSumGate::<2>::new(64), vec![]);
+    }
+
+    #[test]
+    fn interpolation_gate_validates_weights() {
+        let gate = CosetInterpolationGate::<F, D>::new(4);
+        assert_eq!(gate.validate_constants(&[]), Ok(()));
+        let mut wrong_weights = gate.clone();
+        wrong_weights.barycentric_weights[3] += F::ONE;
+        assert_eq!(
+            wrong_weights.validate_constants(&[]),
+            Err("the barycentric weights don't match the subgroup".to_string())
+        );
+        let mut wrong_degree = gate;
+        wrong_degree.degree = 1;
+        assert!(wrong_degree.validate_constants(&[]).is_err());
+    }
+
+    /// A circuit using many constants and gate types, so that its layout would change if it
+    /// depended on the iteration order of the builder's hash maps and sets.
+    fn varied_circuit() -> CircuitBuilder<F, D> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_public_input();
+        let y = builder.add_virtual_target();
+        let e = builder.add_virtual_extension_target();
+
+        let mut acc = x;
+        for i in 0..64 {
+            let c = builder.constant(F::from_canonical_u64(0x9e37_79b9 * i + 1));
+            acc = builder.mul_add(acc, c, y);
+        }
+        for num_bits in [1, 8, 13, 16] {
+            builder.enqueue_range_check(y, num_bits);
+        }
+        let bits = builder.split_le(y, 8);
+        let selected = builder.random_access(bits[0].target, vec![x, acc]);
+        let lut = builder.add_lookup_table_from_fn(|i| i * 3, &[0, 1, 2, 3]);
+        let looked_up = builder.add_lookup_from_index(bits[1].target, lut);
+        let e_squared = builder.mul_extension(e, e);
+        let hash = builder.hash_n_to_hash_no_pad::<PoseidonHash>(
+            [selected, looked_up]
+                .into_iter()
+                .chain(e_squared.0)
+                .collect(),
+        );
+        builder.register_public_inputs(&hash.elements);
+        builder
+    }
+
+    /// Builds the same circuit on several threads, each with its own hash maps. Hash maps seeded
+    /// per instance, like `std`'s, would iterate in different orders in each build. The seeds of
+    /// `hashbrown`'s maps only vary between compilations, which a single test can't cover.
+    #[test]
+    fn builds_are_deterministic() {
+        let build = || varied_circuit().build::<C>();
+        #[cfg(feature = "std")]
+        let builds = std::thread::scope(|s| {
+            let handles = [s.spawn(build), s.spawn(build)];
+            handles.map(|h| h.join().unwrap())
+        });
+        #[cfg(not(feature = "std"))]
+        let builds = [build(), build()];
+
+        let expected = build();
+        let gate_serializer = DefaultGateSerializer;
+        let expected_bytes = expected.verifier_data().to_bytes(&gate_serializer).unwrap();
+        for data in builds {
+            assert_eq!(
+                data.verifier_only.circuit_digest,
+                expected.verifier_only.circuit_digest
+            );
+            assert_eq!(data.prover_only.sigmas, expected.prover_only.sigmas);
+            assert_eq!(
+                data.verifier_data().to_bytes(&gate_serializer).unwrap(),
+                expected_bytes
+            );
+        }
     }
 }
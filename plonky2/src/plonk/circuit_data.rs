@@ -13,52 +13,63 @@
 //! This is useful to allow even small devices to verify plonky2 proofs.
 
 #[cfg(not(feature = "std"))]
-use alloc::{collections::BTreeMap, vec, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+use core::fmt;
 use core::ops::{Range, RangeFrom};
+use core::str::FromStr;
 #[cfg(feature = "std")]
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Arc};
 
-use anyhow::Result;
-use serde::Serialize;
+use anyhow::{anyhow, bail, ensure, Result};
+use serde::{Deserialize, Serialize};
 
-use super::circuit_builder::LookupWire;
+use super::circuit_builder::{DuplicatePublicInputs, LookupWire};
 use crate::field::extension::Extendable;
 use crate::field::fft::FftRootTable;
 use crate::field::types::Field;
 use crate::fri::oracle::PolynomialBatch;
 use crate::fri::reduction_strategies::FriReductionStrategy;
 use crate::fri::structure::{
-    FriBatchInfo, FriBatchInfoTarget, FriInstanceInfo, FriInstanceInfoTarget, FriOracleInfo,
-    FriPolynomialInfo,
+    FriBatchInfo, FriInstanceInfo, FriInstanceShapeTarget, FriOracleInfo, FriPolynomialInfo,
 };
-use crate::fri::{FriConfig, FriParams};
+use crate::fri::{FriConfig, FriParams, OracleShape};
 use crate::gates::gate::GateRef;
-use crate::gates::lookup::Lookup;
+use crate::gates::lookup::{Lookup, LookupGate};
 use crate::gates::lookup_table::LookupTable;
-use crate::gates::selectors::SelectorsInfo;
+use crate::gates::selectors::{min_quotient_degree_factor, LookupSelectors, SelectorsInfo};
 use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
 use crate::hash::merkle_tree::MerkleCap;
-use crate::iop::ext_target::ExtensionTarget;
-use crate::iop::generator::{generate_partial_witness, WitnessGeneratorRef};
+use crate::iop::generator::{
+    generate_partial_witness, CopiesGenerator, CopyGenerator, SimpleGeneratorAdapter,
+    WitnessGenerator, WitnessGeneratorRef,
+};
 use crate::iop::target::Target;
-use crate::iop::witness::{PartialWitness, PartitionWitness};
+use crate::iop::witness::{PartialWitness, PartitionWitness, WitnessSchema};
 use crate::plonk::circuit_builder::CircuitBuilder;
-use crate::plonk::config::{GenericConfig, Hasher};
-use crate::plonk::plonk_common::PlonkOracle;
+use crate::plonk::config::{GenericConfig, GenericHashOut, Hasher};
+use crate::plonk::constraint_checker::{find_constraint_violations, ConstraintViolation};
+use crate::plonk::plonk_common::{salt_size, PlonkOracle};
 use crate::plonk::proof::{CompressedProofWithPublicInputs, ProofWithPublicInputs};
-use crate::plonk::prover::prove;
+use crate::plonk::prover::{
+    prove, prove_batch, prove_with_options, prove_with_partition_witness, ProverOptions,
+};
+use crate::plonk::row_attribution::RowAttribution;
 use crate::plonk::verifier::verify;
+#[cfg(feature = "std")]
+use crate::util::fft_root_table_cache::FftRootTableCache;
+use crate::util::partial_products::num_partial_products;
 use crate::util::serialization::{
-    Buffer, GateSerializer, IoResult, Read, WitnessGeneratorSerializer, Write,
+    Buffer, GateSerializer, IoResult, ProverDataEncoding, Read, WitnessGeneratorSerializer, Write,
 };
 use crate::util::timing::TimingTree;
+use crate::util::{ceil_div_usize, log2_ceil};
 
 /// Configuration to be used when building a circuit. This defines the shape of the circuit
 /// as well as its targeted security level and sub-protocol (e.g. FRI) parameters.
 ///
 /// It supports a [`Default`] implementation tailored for recursion with Poseidon hash (of width 12)
 /// as internal hash function and FRI rate of 1/8.
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CircuitConfig {
     /// The number of wires available at each row. This corresponds to the "width" of the circuit,
     /// and consists in the sum of routed wires and advice wires.
@@ -71,11 +82,15 @@ pub struct CircuitConfig {
     /// allows, the [`CircuitBuilder`] will complain when trying to add this gate to its set of gates.
     pub num_constants: usize,
     /// Whether to use a dedicated gate for base field arithmetic, rather than using a single gate
-    /// for both base field and extension field arithmetic.
+    /// for both base field and extension field arithmetic. Without it, each base field operation
+    /// takes a slot of an `ArithmeticExtensionGate`, which saves a gate type in circuits with few
+    /// base field operations, such as recursive verifiers.
     pub use_base_arithmetic_gate: bool,
     pub security_bits: usize,
     /// The number of challenge points to generate, for IOPs that have soundness errors of (roughly)
-    /// `degree / |F|`.
+    /// `degree / |F|`. The permutation and lookup arguments are run once per set of challenges,
+    /// each with its own Z and partial products polynomials. Building a circuit warns if
+    /// `challenge_security_bits` falls short of `security_bits` at the circuit's degree.
     pub num_challenges: usize,
     /// A boolean to activate the zero-knowledge property. When this is set to `false`, proofs *may*
     /// leak additional information.
@@ -83,9 +98,52 @@ pub struct CircuitConfig {
     /// A cap on the quotient polynomial's degree factor. The actual degree factor is derived
     /// systematically, but will never exceed this value.
     pub max_quotient_degree_factor: usize,
+    /// Uses this quotient degree factor rather than the cheapest one, to experiment with the
+    /// trade-off described in [`PartialProductsInfo`]. It must still fit the circuit's gates and
+    /// `max_quotient_degree_factor`.
+    #[serde(default)]
+    pub force_quotient_degree_factor: Option<usize>,
+    /// Overrides `fri_config.cap_height` for the Merkle trees of some PLONK oracles.
+    #[serde(default)]
+    pub oracle_cap_heights: OracleCapHeights,
     pub fri_config: FriConfig,
 }
 
+/// The cap heights of the Merkle trees of the PLONK oracles, where they differ from the FRI
+/// config's `cap_height`. A taller cap makes the Merkle proofs of every query shorter, at the cost
+/// of the cap itself. The constants and sigmas cap is part of the verifier data rather than of
+/// proofs, so it can usually be made much taller than the others for free.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OracleCapHeights {
+    pub constants_sigmas: Option<usize>,
+    pub wires: Option<usize>,
+    pub zs_partial_products: Option<usize>,
+    pub quotient: Option<usize>,
+}
+
+impl OracleCapHeights {
+    /// Uses the FRI config's `cap_height` for every oracle.
+    pub const fn uniform() -> Self {
+        Self {
+            constants_sigmas: None,
+            wires: None,
+            zs_partial_products: None,
+            quotient: None,
+        }
+    }
+
+    /// The cap height set for `oracle`, if any.
+    pub const fn get(&self, oracle: PlonkOracle) -> Option<usize> {
+        match oracle.index {
+            0 => self.constants_sigmas,
+            1 => self.wires,
+            2 => self.zs_partial_products,
+            3 => self.quotient,
+            _ => None,
+        }
+    }
+}
+
 impl Default for CircuitConfig {
     fn default() -> Self {
         Self::standard_recursion_config()
@@ -97,7 +155,36 @@ impl CircuitConfig {
         self.num_wires - self.num_routed_wires
     }
 
+    /// The conjectured security, in bits, of the permutation and lookup arguments of a circuit of
+    /// degree `2^degree_bits` over `F`. Each set of challenges is drawn from the base field and has
+    /// a soundness error of roughly `degree / |F|`, and running the arguments with several
+    /// independent sets multiplies the errors.
+    pub fn challenge_security_bits<F: Field>(&self, degree_bits: usize) -> usize {
+        let field_bits = F::order().bits() as usize;
+        self.num_challenges * field_bits.saturating_sub(degree_bits)
+    }
+
+    /// The cap heights of the Merkle trees of the PLONK oracles, in the order FRI opens them.
+    pub fn initial_cap_heights(&self) -> Vec<usize> {
+        PlonkOracle::ALL
+            .iter()
+            .map(|&oracle| self.cap_height(oracle))
+            .collect()
+    }
+
+    /// The cap height of the Merkle tree of `oracle`.
+    pub const fn cap_height(&self, oracle: PlonkOracle) -> usize {
+        match self.oracle_cap_heights.get(oracle) {
+            Some(cap_height) => cap_height,
+            None => self.fri_config.cap_height,
+        }
+    }
+
     /// A typical recursion config, without zero-knowledge, targeting ~100 bit security.
+    ///
+    /// A circuit with this config verifying a proof of another such circuit, of up to `2^12` rows,
+    /// fits in `2^12` rows. See `CommonCircuitData::minimum_recursion_degree_bits` for other inner
+    /// circuits.
     pub const fn standard_recursion_config() -> Self {
         Self {
             num_wires: 135,
@@ -108,12 +195,15 @@ impl CircuitConfig {
             num_challenges: 2,
             zero_knowledge: false,
             max_quotient_degree_factor: 8,
+            force_quotient_degree_factor: None,
+            oracle_cap_heights: OracleCapHeights::uniform(),
             fri_config: FriConfig {
                 rate_bits: 3,
                 cap_height: 4,
                 proof_of_work_bits: 16,
                 reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
                 num_query_rounds: 28,
+                final_poly_degree_bits: 0,
             },
         }
     }
@@ -151,7 +241,7 @@ pub struct MockCircuitData<F: RichField + Extendable<D>, C: GenericConfig<D, F =
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     MockCircuitData<F, C, D>
 {
-    pub fn generate_witness(&self, inputs: PartialWitness<F>) -> PartitionWitness<F> {
+    pub fn generate_witness(&self, inputs: PartialWitness<F>) -> Result<PartitionWitness<F>> {
         generate_partial_witness::<F, C, D>(inputs, &self.prover_only, &self.common)
     }
 }
@@ -171,9 +261,24 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         &self,
         gate_serializer: &dyn GateSerializer<F, D>,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+    ) -> IoResult<Vec<u8>> {
+        self.to_bytes_with_encoding(
+            gate_serializer,
+            generator_serializer,
+            ProverDataEncoding::Full,
+        )
+    }
+
+    /// Like `to_bytes`, but with the given encoding of the fields which can be recomputed when
+    /// loading the data. `from_bytes` reads either encoding.
+    pub fn to_bytes_with_encoding(
+        &self,
+        gate_serializer: &dyn GateSerializer<F, D>,
+        generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+        encoding: ProverDataEncoding,
     ) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
-        buffer.write_circuit_data(self, gate_serializer, generator_serializer)?;
+        buffer.write_circuit_data(self, gate_serializer, generator_serializer, encoding)?;
         Ok(buffer)
     }
 
@@ -186,11 +291,93 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         buffer.read_circuit_data(gate_serializer, generator_serializer)
     }
 
+    /// Runs the witness generators on `inputs` without generating a proof, which is handy to
+    /// inspect intermediate values while debugging a circuit.
+    pub fn generate_witness(&self, inputs: PartialWitness<F>) -> Result<PartitionWitness<'_, F>> {
+        generate_partial_witness::<F, C, D>(inputs, &self.prover_only, &self.common)
+    }
+
+    /// Replaces this circuit's FFT root table with the one held by `cache`, so that the prover
+    /// shares it with every other circuit using that cache. This is useful e.g. for prover data
+    /// that was deserialized, or built without a cache.
+    #[cfg(feature = "std")]
+    pub fn share_fft_root_table(&mut self, cache: &FftRootTableCache<F>) {
+        self.prover_only.fft_root_table = Some(cache.get(self.common.max_fft_points()));
+    }
+
+    /// Generates the witness for `inputs` and checks it against every gate and copy constraint,
+    /// returning the first violation found. This is much cheaper than proving, and points at the
+    /// faulty constraint rather than failing verification.
+    pub fn check_witness(&self, inputs: PartialWitness<F>) -> Result<(), ConstraintViolation> {
+        match find_constraint_violations(&self.prover_only, &self.common, inputs, false).pop() {
+            Some(violation) => Err(violation),
+            None => Ok(()),
+        }
+    }
+
     pub fn prove(&self, inputs: PartialWitness<F>) -> Result<ProofWithPublicInputs<F, C, D>> {
-        prove::<F, C, D>(
+        self.prove_with_timing(inputs, &mut TimingTree::default())
+    }
+
+    /// Like `prove`, but records the time spent in each step of proving in `timing`, e.g. to
+    /// export it with [`TimingTree::export`].
+    pub fn prove_with_timing(
+        &self,
+        inputs: PartialWitness<F>,
+        timing: &mut TimingTree,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove::<F, C, D>(&self.prover_only, &self.common, inputs, timing)
+    }
+
+    pub fn prove_with_options(
+        &self,
+        inputs: PartialWitness<F>,
+        options: &ProverOptions,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove_with_options::<F, C, D>(
             &self.prover_only,
             &self.common,
             inputs,
+            options,
+            &mut TimingTree::default(),
+        )
+    }
+
+    /// Proves each of `witnesses` on up to `parallelism` threads, sharing this circuit's data
+    /// between them. See [`prove_batch`] for details.
+    pub fn prove_batch(
+        &self,
+        witnesses: Vec<PartialWitness<F>>,
+        parallelism: usize,
+    ) -> Vec<Result<ProofWithPublicInputs<F, C, D>>> {
+        self.prove_batch_with_options(witnesses, parallelism, &ProverOptions::default())
+    }
+
+    pub fn prove_batch_with_options(
+        &self,
+        witnesses: Vec<PartialWitness<F>>,
+        parallelism: usize,
+        options: &ProverOptions,
+    ) -> Vec<Result<ProofWithPublicInputs<F, C, D>>> {
+        prove_batch::<F, C, D>(
+            &self.prover_only,
+            &self.common,
+            witnesses,
+            parallelism,
+            options,
+        )
+    }
+
+    /// Proves an already-generated witness, e.g. one restored with `PartitionWitness::from_bytes`,
+    /// skipping witness generation.
+    pub fn prove_with_witness(
+        &self,
+        witness: PartitionWitness<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove_with_partition_witness::<F, C, D>(
+            &self.prover_only,
+            &self.common,
+            witness,
             &mut TimingTree::default(),
         )
     }
@@ -199,6 +386,40 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         verify::<F, C, D>(proof_with_pis, &self.verifier_only, &self.common)
     }
 
+    /// Proves `inputs` and verifies the resulting proof, which is returned. This is mostly useful
+    /// in tests and benchmarks.
+    ///
+    /// ```rust
+    /// use plonky2::field::types::Field;
+    /// use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+    /// use plonky2::plonk::circuit_builder::CircuitBuilder;
+    /// use plonky2::plonk::circuit_data::CircuitConfig;
+    /// use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    ///
+    /// const D: usize = 2;
+    /// type C = PoseidonGoldilocksConfig;
+    /// type F = <C as GenericConfig<D>>::F;
+    ///
+    /// let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    /// let x = builder.add_virtual_target();
+    /// let y = builder.square(x);
+    /// builder.register_public_input(y);
+    /// let data = builder.build::<C>();
+    ///
+    /// let mut pw = PartialWitness::new();
+    /// pw.set_target(x, F::from_canonical_u64(3));
+    /// let proof = data.prove_and_verify(pw).unwrap();
+    /// assert_eq!(proof.public_inputs, [F::from_canonical_u64(9)]);
+    /// ```
+    pub fn prove_and_verify(
+        &self,
+        inputs: PartialWitness<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        let proof_with_pis = self.prove(inputs)?;
+        self.verify(proof_with_pis.clone())?;
+        Ok(proof_with_pis)
+    }
+
     pub fn verify_compressed(
         &self,
         compressed_proof_with_pis: CompressedProofWithPublicInputs<F, C, D>,
@@ -220,6 +441,23 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         proof.decompress(&self.verifier_only.circuit_digest, &self.common)
     }
 
+    /// The digest identifying this circuit.
+    pub fn digest(&self) -> CircuitDigest<C, D> {
+        self.verifier_only.digest()
+    }
+
+    /// The rows of this circuit, attributed to the contexts in which they were filled.
+    pub fn row_attribution(&self) -> &RowAttribution {
+        &self.prover_only.row_attribution
+    }
+
+    /// Logs the rows of this circuit attributed to each context, as a tree. Unlike
+    /// `CircuitBuilder::print_gate_counts`, rows shared by operations of several contexts are
+    /// split between them. Contexts with fewer than `min_delta` rows are left out.
+    pub fn print_row_attribution(&self, min_delta: usize) {
+        self.prover_only.row_attribution.print(min_delta);
+    }
+
     pub fn verifier_data(&self) -> VerifierCircuitData<F, C, D> {
         let CircuitData {
             verifier_only,
@@ -269,9 +507,24 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         &self,
         gate_serializer: &dyn GateSerializer<F, D>,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+    ) -> IoResult<Vec<u8>> {
+        self.to_bytes_with_encoding(
+            gate_serializer,
+            generator_serializer,
+            ProverDataEncoding::Full,
+        )
+    }
+
+    /// Like `to_bytes`, but with the given encoding of the fields which can be recomputed when
+    /// loading the data. `from_bytes` reads either encoding.
+    pub fn to_bytes_with_encoding(
+        &self,
+        gate_serializer: &dyn GateSerializer<F, D>,
+        generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+        encoding: ProverDataEncoding,
     ) -> IoResult<Vec<u8>> {
         let mut buffer = Vec::new();
-        buffer.write_prover_circuit_data(self, gate_serializer, generator_serializer)?;
+        buffer.write_prover_circuit_data(self, gate_serializer, generator_serializer, encoding)?;
         Ok(buffer)
     }
 
@@ -284,11 +537,93 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         buffer.read_prover_circuit_data(gate_serializer, generator_serializer)
     }
 
+    /// Runs the witness generators on `inputs` without generating a proof, which is handy to
+    /// inspect intermediate values while debugging a circuit.
+    pub fn generate_witness(&self, inputs: PartialWitness<F>) -> Result<PartitionWitness<'_, F>> {
+        generate_partial_witness::<F, C, D>(inputs, &self.prover_only, &self.common)
+    }
+
+    /// Replaces this circuit's FFT root table with the one held by `cache`, so that the prover
+    /// shares it with every other circuit using that cache. This is useful e.g. for prover data
+    /// that was deserialized, or built without a cache.
+    #[cfg(feature = "std")]
+    pub fn share_fft_root_table(&mut self, cache: &FftRootTableCache<F>) {
+        self.prover_only.fft_root_table = Some(cache.get(self.common.max_fft_points()));
+    }
+
+    /// Generates the witness for `inputs` and checks it against every gate and copy constraint,
+    /// returning the first violation found. This is much cheaper than proving, and points at the
+    /// faulty constraint rather than failing verification.
+    pub fn check_witness(&self, inputs: PartialWitness<F>) -> Result<(), ConstraintViolation> {
+        match find_constraint_violations(&self.prover_only, &self.common, inputs, false).pop() {
+            Some(violation) => Err(violation),
+            None => Ok(()),
+        }
+    }
+
     pub fn prove(&self, inputs: PartialWitness<F>) -> Result<ProofWithPublicInputs<F, C, D>> {
-        prove::<F, C, D>(
+        self.prove_with_timing(inputs, &mut TimingTree::default())
+    }
+
+    /// Like `prove`, but records the time spent in each step of proving in `timing`, e.g. to
+    /// export it with [`TimingTree::export`].
+    pub fn prove_with_timing(
+        &self,
+        inputs: PartialWitness<F>,
+        timing: &mut TimingTree,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove::<F, C, D>(&self.prover_only, &self.common, inputs, timing)
+    }
+
+    pub fn prove_with_options(
+        &self,
+        inputs: PartialWitness<F>,
+        options: &ProverOptions,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove_with_options::<F, C, D>(
             &self.prover_only,
             &self.common,
             inputs,
+            options,
+            &mut TimingTree::default(),
+        )
+    }
+
+    /// Proves each of `witnesses` on up to `parallelism` threads, sharing this circuit's data
+    /// between them. See [`prove_batch`] for details.
+    pub fn prove_batch(
+        &self,
+        witnesses: Vec<PartialWitness<F>>,
+        parallelism: usize,
+    ) -> Vec<Result<ProofWithPublicInputs<F, C, D>>> {
+        self.prove_batch_with_options(witnesses, parallelism, &ProverOptions::default())
+    }
+
+    pub fn prove_batch_with_options(
+        &self,
+        witnesses: Vec<PartialWitness<F>>,
+        parallelism: usize,
+        options: &ProverOptions,
+    ) -> Vec<Result<ProofWithPublicInputs<F, C, D>>> {
+        prove_batch::<F, C, D>(
+            &self.prover_only,
+            &self.common,
+            witnesses,
+            parallelism,
+            options,
+        )
+    }
+
+    /// Proves an already-generated witness, e.g. one restored with `PartitionWitness::from_bytes`,
+    /// skipping witness generation.
+    pub fn prove_with_witness(
+        &self,
+        witness: PartitionWitness<F>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>> {
+        prove_with_partition_witness::<F, C, D>(
+            &self.prover_only,
+            &self.common,
+            witness,
             &mut TimingTree::default(),
         )
     }
@@ -332,6 +667,21 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     ) -> Result<()> {
         compressed_proof_with_pis.verify(&self.verifier_only, &self.common)
     }
+
+    /// The digest identifying this circuit.
+    pub fn digest(&self) -> CircuitDigest<C, D> {
+        self.verifier_only.digest()
+    }
+}
+
+/// Statistics about the copy-equivalence classes of a circuit, see
+/// `ProverOnlyCircuitData::copy_class_stats`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CopyClassStats {
+    /// The number of classes, including targets which aren't copied anywhere.
+    pub num_classes: usize,
+    /// The number of targets in the largest class.
+    pub max_class_size: usize,
 }
 
 /// Circuit data required by the prover, but not the verifier.
@@ -342,8 +692,15 @@ pub struct ProverOnlyCircuitData<
     const D: usize,
 > {
     pub generators: Vec<WitnessGeneratorRef<F, D>>,
-    /// Generator indices (within the `Vec` above), indexed by the representative of each target
-    /// they watch.
+    /// Generators copying one target to another. These are stored unboxed, and indexed after the
+    /// boxed generators above. Copies are the only generators specialized this way: the others,
+    /// including those of the arithmetic gates, which `Gate::generators` returns boxed, and the
+    /// random value generators, stay in `generators`.
+    pub copy_generators: Vec<SimpleGeneratorAdapter<F, CopyGenerator, D>>,
+    /// Generators copying many targets at once, indexed after the copy generators above.
+    pub batch_copy_generators: Vec<CopiesGenerator>,
+    /// Generator indices (see `generator`), indexed by the representative of each target they
+    /// watch.
     pub generator_indices_by_watches: BTreeMap<usize, Vec<usize>>,
     /// Commitments to the constants polynomials and sigma polynomials.
     pub constants_sigmas_commitment: PolynomialBatch<F, C, D>,
@@ -356,20 +713,108 @@ pub struct ProverOnlyCircuitData<
     /// A map from each `Target`'s index to the index of its representative in the disjoint-set
     /// forest.
     pub representative_map: Vec<usize>,
-    /// Pre-computed roots for faster FFT.
-    pub fft_root_table: Option<FftRootTable<F>>,
+    /// Pre-computed roots for faster FFT. This may be shared with other circuits, and may cover
+    /// larger FFTs than this circuit needs.
+    pub fft_root_table: Option<Arc<FftRootTable<F>>>,
     /// A digest of the "circuit" (i.e. the instance, minus public inputs), which can be used to
     /// seed Fiat-Shamir.
     pub circuit_digest: <<C as GenericConfig<D>>::Hasher as Hasher<F>>::Hash,
+    /// The domain separator the circuit was built with, so that the digest can be recomputed
+    /// when loading serialized prover data.
+    pub domain_separator: Vec<F>,
     ///The concrete placement of the lookup gates for each lookup table index.
     pub lookup_rows: Vec<LookupWire>,
     /// A vector of (looking_in, looking_out) pairs for for each lookup table index.
     pub lut_to_lookups: Vec<Lookup>,
+    /// The most specific context of the copy constraints merged into each partition, indexed by
+    /// the partition's representative. Only used to report witness generation failures and
+    /// constraint violations.
+    pub partition_contexts: BTreeMap<usize, String>,
+    /// The context in which boxed generators were created. Each entry maps the index of the first
+    /// generator created in a context to that context, which applies up to the next entry. Only
+    /// used to report witness generation failures.
+    pub generator_contexts: BTreeMap<usize, String>,
+    /// Named groups of inputs, which can be assigned with `PartialWitness::set_by_name`.
+    pub witness_schema: WitnessSchema,
+    /// The rows of the circuit, attributed to the contexts in which they were filled. Only used
+    /// for debugging.
+    pub row_attribution: RowAttribution,
 }
 
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     ProverOnlyCircuitData<F, C, D>
 {
+    /// The total number of witness generators, boxed or not.
+    pub fn num_generators(&self) -> usize {
+        self.generators.len() + self.copy_generators.len() + self.batch_copy_generators.len()
+    }
+
+    /// Returns the generator with the given index. Boxed generators come first, followed by copy
+    /// generators and batch copy generators.
+    pub fn generator(&self, index: usize) -> &dyn WitnessGenerator<F, D> {
+        let num_boxed = self.generators.len();
+        let num_copies = self.copy_generators.len();
+        if index < num_boxed {
+            &*self.generators[index].0
+        } else if index < num_boxed + num_copies {
+            &self.copy_generators[index - num_boxed]
+        } else {
+            &self.batch_copy_generators[index - num_boxed - num_copies]
+        }
+    }
+
+    /// Returns the builder context in which the generator with the given index was created, if it
+    /// was created within one.
+    pub fn generator_context(&self, index: usize) -> Option<&str> {
+        if index >= self.generators.len() {
+            return None;
+        }
+        self.generator_contexts
+            .range(..=index)
+            .next_back()
+            .map(|(_, context)| context.as_str())
+            .filter(|context| !context.is_empty())
+    }
+
+    /// Returns the index of the representative of the copy-equivalence class containing `target`,
+    /// i.e. of the set of targets which copy constraints force to be equal.
+    pub fn representative_of(
+        &self,
+        target: Target,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> usize {
+        let index = target.index(common_data.config.num_wires, common_data.degree());
+        self.representative_map[index]
+    }
+
+    /// Lists the targets in the copy-equivalence class whose representative has the given index.
+    pub fn class_members(
+        &self,
+        representative: usize,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Vec<Target> {
+        let num_wires = common_data.config.num_wires;
+        let degree = common_data.degree();
+        self.representative_map
+            .iter()
+            .enumerate()
+            .filter(|&(_, &rep)| rep == representative)
+            .map(|(index, _)| Target::from_index(index, num_wires, degree))
+            .collect()
+    }
+
+    /// Statistics about the copy-equivalence classes of the circuit's targets.
+    pub fn copy_class_stats(&self) -> CopyClassStats {
+        let mut class_sizes = vec![0; self.representative_map.len()];
+        for &rep in &self.representative_map {
+            class_sizes[rep] += 1;
+        }
+        CopyClassStats {
+            num_classes: class_sizes.iter().filter(|&&size| size > 0).count(),
+            max_class_size: class_sizes.into_iter().max().unwrap_or(0),
+        }
+    }
+
     pub fn to_bytes(
         &self,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
@@ -391,13 +836,17 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
 }
 
 /// Circuit data required by the verifier, but not the prover.
-#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct VerifierOnlyCircuitData<C: GenericConfig<D>, const D: usize> {
     /// A commitment to each constant polynomial and each permutation polynomial.
     pub constants_sigmas_cap: MerkleCap<C::F, C::Hasher>,
     /// A digest of the "circuit" (i.e. the instance, minus public inputs), which can be used to
     /// seed Fiat-Shamir.
     pub circuit_digest: <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
+    /// The domain separator the circuit was built with, which is bound by `circuit_digest`, so
+    /// that the digest can be recomputed from serialized data.
+    pub domain_separator: Vec<C::F>,
 }
 
 impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
@@ -411,6 +860,106 @@ impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
         let mut buffer = Buffer::new(&bytes);
         buffer.read_verifier_only_circuit_data()
     }
+
+    /// The digest identifying the circuit this verifier key is for.
+    pub fn digest(&self) -> CircuitDigest<C, D> {
+        CircuitDigest(self.circuit_digest)
+    }
+}
+
+/// The digest of a circuit, which identifies its verifier key.
+///
+/// It is displayed and parsed as the lowercase hex encoding of the digest's bytes, so it can be
+/// used as a stable, human-readable ID; this is also its serde representation.
+pub struct CircuitDigest<C: GenericConfig<D>, const D: usize>(
+    pub <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
+);
+
+impl<C: GenericConfig<D>, const D: usize> Clone for CircuitDigest<C, D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> Copy for CircuitDigest<C, D> {}
+
+impl<C: GenericConfig<D>, const D: usize> PartialEq for CircuitDigest<C, D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> Eq for CircuitDigest<C, D> {}
+
+impl<C: GenericConfig<D>, const D: usize> core::hash::Hash for CircuitDigest<C, D> {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bytes().hash(state);
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> PartialEq<VerifierOnlyCircuitData<C, D>>
+    for CircuitDigest<C, D>
+{
+    fn eq(&self, verifier_only: &VerifierOnlyCircuitData<C, D>) -> bool {
+        self.0 == verifier_only.circuit_digest
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> fmt::Display for CircuitDigest<C, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .to_bytes()
+            .iter()
+            .try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> fmt::Debug for CircuitDigest<C, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CircuitDigest({})", self)
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> FromStr for CircuitDigest<C, D> {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let hash_size = <C::Hasher as Hasher<C::F>>::HASH_SIZE;
+        ensure!(
+            s.len() == 2 * hash_size,
+            "A circuit digest has {} hex digits, not {}",
+            2 * hash_size,
+            s.len()
+        );
+        let bytes = (0..hash_size)
+            .map(|i| {
+                s.get(2 * i..2 * i + 2)
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .ok_or_else(|| anyhow!("Invalid hex digits in circuit digest {:?}", s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // Decoding reduces out-of-range field elements, so check that the digest round-trips.
+        let digest = <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash::from_bytes(&bytes);
+        ensure!(
+            digest.to_bytes() == bytes,
+            "Circuit digest {:?} isn't a canonical encoding",
+            s
+        );
+        Ok(Self(digest))
+    }
+}
+
+impl<C: GenericConfig<D>, const D: usize> Serialize for CircuitDigest<C, D> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, C: GenericConfig<D>, const D: usize> Deserialize<'de> for CircuitDigest<C, D> {
+    fn deserialize<De: serde::Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
 }
 
 /// Circuit data required by both the prover and the verifier.
@@ -435,8 +984,16 @@ pub struct CommonCircuitData<F: RichField + Extendable<D>, const D: usize> {
     /// The number of constant wires.
     pub num_constants: usize,
 
+    /// The number of public inputs, which doesn't count the registrations dropped by
+    /// `DuplicatePublicInputs::Dedup`.
     pub num_public_inputs: usize,
 
+    /// How the public inputs are hashed into the digest exposed by the `PublicInputGate`.
+    pub public_input_commitment: PublicInputCommitment,
+
+    /// What the builder did with targets registered as public inputs more than once.
+    pub duplicate_public_inputs: DuplicatePublicInputs,
+
     /// The `{k_i}` valued used in `S_ID_i` in Plonk's permutation argument.
     pub k_is: Vec<F>,
 
@@ -451,6 +1008,217 @@ pub struct CommonCircuitData<F: RichField + Extendable<D>, const D: usize> {
 
     /// The stored lookup tables.
     pub luts: Vec<LookupTable>,
+
+    /// The number of rows of random values hiding the values revealed by a proof, which is zero
+    /// unless the circuit is zero-knowledge.
+    pub num_blinding_rows: usize,
+
+    /// The points at which polynomials are opened besides `zeta` and `g * zeta`.
+    pub extra_opening_points: Vec<OpeningPoint>,
+
+    /// The ranges of the public inputs registered with
+    /// `CircuitBuilder::register_named_public_inputs`, by name.
+    pub public_input_names: BTreeMap<String, Range<usize>>,
+}
+
+/// How a circuit hashes its public inputs into the digest which its proofs are bound to.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum PublicInputCommitment {
+    /// The public inputs are hashed all at once.
+    #[default]
+    Flat,
+    /// The public inputs are split into chunks of `chunk_size`, the last one possibly shorter.
+    /// Each chunk is hashed into a digest, and the digests are hashed together. A recursive
+    /// verifier can then take the digests of the chunks it doesn't inspect instead of hashing
+    /// them, which is much cheaper for circuits with many public inputs.
+    Chunked { chunk_size: usize },
+}
+
+impl PublicInputCommitment {
+    /// The ranges of the chunks of `num_public_inputs` public inputs. A flat commitment has a
+    /// single chunk.
+    pub fn chunk_ranges(&self, num_public_inputs: usize) -> Vec<Range<usize>> {
+        match *self {
+            Self::Flat => core::iter::once(0..num_public_inputs).collect(),
+            Self::Chunked { chunk_size } => (0..num_public_inputs)
+                .step_by(chunk_size)
+                .map(|start| start..(start + chunk_size).min(num_public_inputs))
+                .collect(),
+        }
+    }
+
+    /// Hashes `public_inputs` as the `PublicInputGate` of a circuit with this commitment expects.
+    pub fn hash<F: RichField, H: Hasher<F>>(&self, public_inputs: &[F]) -> H::Hash {
+        match self {
+            Self::Flat => H::hash_no_pad(public_inputs),
+            Self::Chunked { .. } => {
+                let digests = self
+                    .chunk_ranges(public_inputs.len())
+                    .into_iter()
+                    .map(|range| H::hash_no_pad(&public_inputs[range]))
+                    .collect::<Vec<_>>();
+                Self::hash_chunk_digests::<F, H>(&digests)
+            }
+        }
+    }
+
+    /// Combines the digests of the chunks of a chunked commitment.
+    pub fn hash_chunk_digests<F: RichField, H: Hasher<F>>(digests: &[H::Hash]) -> H::Hash {
+        let elements = digests
+            .iter()
+            .flat_map(|digest| digest.to_vec())
+            .collect::<Vec<_>>();
+        H::hash_no_pad(&elements)
+    }
+}
+
+/// A point at which the polynomials of some oracles are opened, in addition to `zeta` and
+/// `g * zeta` where `g` generates the circuit's subgroup. Lookup arguments and custom gates may
+/// need the values of polynomials at further shifts of `zeta`, such as `g^{-1} * zeta`.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct OpeningPoint {
+    /// The power of `g` by which the point shifts `zeta`, e.g. `-1` for `g^{-1} * zeta`.
+    pub shift: isize,
+    /// The indices of the oracles whose polynomials are all opened at the point, in increasing
+    /// order. See [`PlonkOracle`].
+    pub oracles: Vec<usize>,
+}
+
+impl OpeningPoint {
+    /// Returns `g^shift`, which multiplies `zeta` to get the point.
+    pub fn shift_factor<F: Field>(&self, g: F) -> F {
+        let power = self.shift.unsigned_abs() as u64;
+        if self.shift < 0 {
+            g.inverse().exp_u64(power)
+        } else {
+            g.exp_u64(power)
+        }
+    }
+}
+
+/// How a circuit's quotient degree factor `q` was chosen. Constraints may have degree up to `q + 1`,
+/// so that the permutation argument multiplies its terms in chunks of `q`, each chunk's product
+/// but the last one needing a partial product polynomial. On the other hand, the quotient
+/// polynomial is split into `q` polynomials. [`CircuitBuilder`] picks the factor minimizing the sum
+/// of both, among those which fit the circuit's gates, preferring higher factors on ties since
+/// they need fewer selector polynomials.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PartialProductsInfo {
+    pub quotient_degree_factor: usize,
+    /// The number of partial product polynomials per challenge.
+    pub num_partial_products: usize,
+    /// Whether the factor was set by [`CircuitConfig::force_quotient_degree_factor`].
+    pub forced: bool,
+    /// The factors allowed by the gates and config, in increasing order.
+    pub candidates: Vec<QuotientDegreeCandidate>,
+}
+
+/// A quotient degree factor considered by [`PartialProductsInfo`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct QuotientDegreeCandidate {
+    pub quotient_degree_factor: usize,
+    /// The number of partial product polynomials per challenge.
+    pub num_partial_products: usize,
+    /// The number of partial product and quotient polynomials per challenge.
+    pub cost: usize,
+}
+
+impl PartialProductsInfo {
+    /// Chooses the quotient degree factor of a circuit with the given config, whose gates need a
+    /// factor of at least `min_quotient_degree_factor`.
+    pub(crate) fn choose(
+        config: &CircuitConfig,
+        min_quotient_degree_factor: usize,
+    ) -> Result<Self> {
+        let candidates = Self::candidates(config, min_quotient_degree_factor);
+        let (first, last) = match (candidates.first(), candidates.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => bail!(
+                "The circuit's gates need a quotient degree factor of at least {}, but \
+                 max_quotient_degree_factor = {} and rate_bits = {} allow at most {}",
+                min_quotient_degree_factor,
+                config.max_quotient_degree_factor,
+                config.fri_config.rate_bits,
+                Self::max_quotient_degree_factor(config)
+            ),
+        };
+        let chosen = match config.force_quotient_degree_factor {
+            Some(factor) => {
+                ensure!(
+                    (first.quotient_degree_factor..=last.quotient_degree_factor).contains(&factor),
+                    "force_quotient_degree_factor = {} is outside of the allowed range {}..={}",
+                    factor,
+                    first.quotient_degree_factor,
+                    last.quotient_degree_factor
+                );
+                candidates[factor - first.quotient_degree_factor]
+            }
+            None => *candidates
+                .iter()
+                .rev()
+                .min_by_key(|candidate| candidate.cost)
+                .unwrap(),
+        };
+        Ok(Self {
+            quotient_degree_factor: chosen.quotient_degree_factor,
+            num_partial_products: chosen.num_partial_products,
+            forced: config.force_quotient_degree_factor.is_some(),
+            candidates,
+        })
+    }
+
+    fn max_quotient_degree_factor(config: &CircuitConfig) -> usize {
+        // The prover evaluates the quotient polynomial on the LDE of the other polynomials.
+        config
+            .max_quotient_degree_factor
+            .min(1 << config.fri_config.rate_bits.min(usize::BITS as usize - 1))
+    }
+
+    fn candidates(
+        config: &CircuitConfig,
+        min_quotient_degree_factor: usize,
+    ) -> Vec<QuotientDegreeCandidate> {
+        (min_quotient_degree_factor..=Self::max_quotient_degree_factor(config))
+            .map(|quotient_degree_factor| {
+                let num_partial_products =
+                    num_partial_products(config.num_routed_wires, quotient_degree_factor);
+                QuotientDegreeCandidate {
+                    quotient_degree_factor,
+                    num_partial_products,
+                    cost: num_partial_products + quotient_degree_factor,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PartialProductsInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Quotient degree factor {} ({}), {} partial products",
+            self.quotient_degree_factor,
+            if self.forced { "forced" } else { "cheapest" },
+            self.num_partial_products
+        )?;
+        write!(f, "factor | partial products | cost")?;
+        for candidate in &self.candidates {
+            let marker = if candidate.quotient_degree_factor == self.quotient_degree_factor {
+                " *"
+            } else {
+                ""
+            };
+            write!(
+                f,
+                "\n{:>6} | {:>16} | {:>4}{}",
+                candidate.quotient_degree_factor,
+                candidate.num_partial_products,
+                candidate.cost,
+                marker
+            )?;
+        }
+        Ok(())
+    }
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
@@ -468,6 +1236,251 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
         buffer.read_common_circuit_data(gate_serializer)
     }
 
+    /// The indices, within a proof's public inputs, of the public inputs named `name`.
+    pub fn public_inputs_range(&self, name: &str) -> Option<Range<usize>> {
+        self.public_input_names.get(name).cloned()
+    }
+
+    /// Checks that the parameters of the circuit are consistent with each other, as they are in
+    /// circuits built by [`CircuitBuilder`]. Verifying proofs against inconsistent data, e.g.
+    /// deserialized from untrusted bytes, may panic.
+    pub fn validate(&self) -> Result<()> {
+        let config = &self.config;
+        ensure!(
+            0 < config.num_routed_wires && config.num_routed_wires <= config.num_wires,
+            "num_routed_wires = {} must be between 1 and num_wires = {}",
+            config.num_routed_wires,
+            config.num_wires
+        );
+        ensure!(
+            self.k_is.len() == config.num_routed_wires,
+            "Expected a coset shift per routed wire, got {} for {} routed wires",
+            self.k_is.len(),
+            config.num_routed_wires
+        );
+        ensure!(config.num_challenges > 0, "num_challenges must be positive");
+        ensure!(!self.gates.is_empty(), "Expected at least one gate");
+        let partial_products_info = self.partial_products_info();
+        let allowed = partial_products_info
+            .candidates
+            .iter()
+            .any(|candidate| candidate.quotient_degree_factor == self.quotient_degree_factor);
+        ensure!(
+            allowed
+                && config
+                    .force_quotient_degree_factor
+                    .unwrap_or(self.quotient_degree_factor)
+                    == self.quotient_degree_factor,
+            "quotient_degree_factor = {} isn't allowed by the gates, max_quotient_degree_factor = \
+             {} and force_quotient_degree_factor = {:?}",
+            self.quotient_degree_factor,
+            config.max_quotient_degree_factor,
+            config.force_quotient_degree_factor
+        );
+        let expected_partial_products =
+            num_partial_products(config.num_routed_wires, self.quotient_degree_factor);
+        ensure!(
+            self.num_partial_products == expected_partial_products,
+            "Expected {} partial products, got {}",
+            expected_partial_products,
+            self.num_partial_products
+        );
+        if let PublicInputCommitment::Chunked { chunk_size } = self.public_input_commitment {
+            ensure!(chunk_size > 0, "Public input chunks can't be empty");
+        }
+        for (name, range) in &self.public_input_names {
+            ensure!(
+                range.start <= range.end && range.end <= self.num_public_inputs,
+                "The public inputs `{}` at {:?} exceed the {} public inputs",
+                name,
+                range,
+                self.num_public_inputs
+            );
+        }
+
+        let fri_params = &self.fri_params;
+        ensure!(
+            fri_params.config == config.fri_config,
+            "The FRI parameters don't match the FRI config"
+        );
+        ensure!(
+            fri_params.initial_cap_heights == config.initial_cap_heights(),
+            "The FRI initial cap heights {:?} don't match the config's {:?}",
+            fri_params.initial_cap_heights,
+            config.initial_cap_heights()
+        );
+        ensure!(
+            fri_params.hiding == config.zero_knowledge,
+            "FRI hiding = {} doesn't match zero_knowledge = {}",
+            fri_params.hiding,
+            config.zero_knowledge
+        );
+        let lde_bits = fri_params
+            .degree_bits
+            .checked_add(fri_params.config.rate_bits)
+            .filter(|&lde_bits| lde_bits <= F::TWO_ADICITY)
+            .ok_or_else(|| {
+                anyhow!(
+                    "degree_bits = {} and rate_bits = {} exceed the two-adicity of the field",
+                    fri_params.degree_bits,
+                    fri_params.config.rate_bits
+                )
+            })?;
+        ensure!(
+            fri_params.config.cap_height <= lde_bits,
+            "cap_height = {} exceeds the {} bits of the LDE",
+            fri_params.config.cap_height,
+            lde_bits
+        );
+        ensure!(
+            fri_params.config.proof_of_work_bits < 64,
+            "proof_of_work_bits = {} must be less than 64",
+            fri_params.config.proof_of_work_bits
+        );
+        ensure!(
+            fri_params
+                .reduction_arity_bits
+                .iter()
+                .all(|&arity_bits| arity_bits <= lde_bits),
+            "FRI arities {:?} exceed the LDE",
+            fri_params.reduction_arity_bits
+        );
+        fri_params.validate()?;
+        fri_params.validate_security(
+            self.config.security_bits,
+            F::Extension::order().bits() as usize,
+        )?;
+
+        for (i, opening_point) in self.extra_opening_points.iter().enumerate() {
+            // `g^{shift} * zeta` is `zeta` if the shift is a multiple of the degree. It may be
+            // `g * zeta`, which opens the other polynomials of the oracles there.
+            let shift = opening_point.shift.rem_euclid(self.degree() as isize);
+            ensure!(
+                shift != 0
+                    && self.extra_opening_points[..i]
+                        .iter()
+                        .all(|other| other.shift.rem_euclid(self.degree() as isize) != shift),
+                "Opening point g^{} * zeta is already opened",
+                opening_point.shift
+            );
+            ensure!(
+                !opening_point.oracles.is_empty()
+                    && opening_point.oracles.windows(2).all(|w| w[0] < w[1])
+                    && opening_point
+                        .oracles
+                        .iter()
+                        .all(|&oracle| oracle < PlonkOracle::QUOTIENT.index),
+                "Invalid oracles {:?} opened at g^{} * zeta",
+                opening_point.oracles,
+                opening_point.shift
+            );
+        }
+
+        let num_blinding_rows = if config.zero_knowledge {
+            // Each query round reveals at least three values to blind, and the blinding rows fit
+            // in the circuit. This bounds the number of query rounds before counting the values.
+            ensure!(
+                fri_params.config.num_query_rounds <= self.degree() / 3,
+                "{} FRI query rounds can't be blinded in {} rows",
+                fri_params.config.num_query_rounds,
+                self.degree()
+            );
+            let (regular_poly_points, z_points) = self.num_opening_points();
+            fri_params.num_revealed_values(D, regular_poly_points)
+                + 2 * fri_params.num_revealed_values(D, z_points)
+        } else {
+            0
+        };
+        ensure!(
+            self.num_blinding_rows == num_blinding_rows,
+            "Expected {} blinding rows, got {}",
+            num_blinding_rows,
+            self.num_blinding_rows
+        );
+
+        ensure!(!self.gates.is_empty(), "The circuit has no gates");
+        let selectors = &self.selectors_info;
+        ensure!(
+            selectors.selector_indices.len() == self.gates.len(),
+            "Expected a selector index per gate, got {} for {} gates",
+            selectors.selector_indices.len(),
+            self.gates.len()
+        );
+        for (i, (gate, &selector_index)) in self
+            .gates
+            .iter()
+            .zip(&selectors.selector_indices)
+            .enumerate()
+        {
+            let group = selectors.groups.get(selector_index);
+            ensure!(
+                group.is_some_and(|group| group.contains(&i) && group.end <= self.gates.len()),
+                "Gate {} isn't in a valid selector group",
+                i
+            );
+            let gate = &gate.0;
+            ensure!(
+                gate.num_wires() <= config.num_wires
+                    && gate.num_routed_wires() <= config.num_routed_wires
+                    && gate.num_constants() <= config.num_constants,
+                "{} doesn't fit in a row of the circuit",
+                gate.id()
+            );
+        }
+        let num_gate_constraints = self
+            .gates
+            .iter()
+            .map(|gate| gate.0.num_constraints())
+            .max()
+            .unwrap_or(0);
+        ensure!(
+            self.num_gate_constraints == num_gate_constraints,
+            "Expected {} gate constraints, got {}",
+            num_gate_constraints,
+            self.num_gate_constraints
+        );
+        let max_gate_constants = self
+            .gates
+            .iter()
+            .map(|gate| gate.0.num_constants())
+            .max()
+            .unwrap_or(0);
+        ensure!(
+            selectors.num_selectors() + self.num_lookup_selectors + max_gate_constants
+                <= self.num_constants,
+            "{} constants can't hold {} selectors, {} lookup selectors and {} gate constants",
+            self.num_constants,
+            selectors.num_selectors(),
+            self.num_lookup_selectors,
+            max_gate_constants
+        );
+
+        let (num_lookup_selectors, num_lookup_polys) = if self.luts.is_empty() {
+            (0, 0)
+        } else {
+            (
+                LookupSelectors::StartEnd as usize + self.luts.len(),
+                ceil_div_usize(
+                    LookupGate::num_slots(config),
+                    self.quotient_degree_factor - 1,
+                ) + 1,
+            )
+        };
+        ensure!(
+            self.num_lookup_selectors == num_lookup_selectors
+                && self.num_lookup_polys == num_lookup_polys,
+            "Expected {} lookup selectors and {} lookup polynomials for {} lookup tables, got {} \
+             and {}",
+            num_lookup_selectors,
+            num_lookup_polys,
+            self.luts.len(),
+            self.num_lookup_selectors,
+            self.num_lookup_polys
+        );
+
+        Ok(())
+    }
+
     pub const fn degree_bits(&self) -> usize {
         self.fri_params.degree_bits
     }
@@ -484,6 +1497,13 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
         F::primitive_root_of_unity(self.degree_bits() + self.config.fri_config.rate_bits)
     }
 
+    /// The number of independent challenges sampled for each of the permutation and lookup
+    /// arguments and the constraint combination, i.e. the number of Z and quotient polynomial
+    /// groups.
+    pub const fn num_challenges(&self) -> usize {
+        self.config.num_challenges
+    }
+
     pub fn constraint_degree(&self) -> usize {
         self.gates
             .iter()
@@ -492,10 +1512,45 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
             .expect("No gates?")
     }
 
+    /// The number of leading wire columns which some gate of the circuit uses. The columns after
+    /// them are zero in every witness, unless zero-knowledge blinding fills them with random
+    /// values, so the prover doesn't need to interpolate them.
+    pub fn num_used_wires(&self) -> usize {
+        if self.config.zero_knowledge {
+            self.config.num_wires
+        } else {
+            self.gates
+                .iter()
+                .map(|g| g.0.num_wires())
+                .max()
+                .expect("No gates?")
+        }
+    }
+
+    /// Returns how the quotient degree factor, and with it the number of partial products, was
+    /// chosen among the factors allowed by the gates and config.
+    pub fn partial_products_info(&self) -> PartialProductsInfo {
+        PartialProductsInfo {
+            quotient_degree_factor: self.quotient_degree_factor,
+            num_partial_products: self.num_partial_products,
+            forced: self.config.force_quotient_degree_factor.is_some(),
+            candidates: PartialProductsInfo::candidates(
+                &self.config,
+                min_quotient_degree_factor(&self.gates),
+            ),
+        }
+    }
+
     pub const fn quotient_degree(&self) -> usize {
         self.quotient_degree_factor * self.degree()
     }
 
+    /// The size of the largest FFT done by the prover, which its FFT root table must cover.
+    pub fn max_fft_points(&self) -> usize {
+        let rate_bits = self.config.fri_config.rate_bits;
+        1 << (self.degree_bits() + rate_bits.max(log2_ceil(self.quotient_degree_factor)))
+    }
+
     /// Range of the constants polynomials in the `constants_sigmas_commitment`.
     pub const fn constants_range(&self) -> Range<usize> {
         0..self.num_constants
@@ -508,12 +1563,12 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
 
     /// Range of the `z`s polynomials in the `zs_partial_products_commitment`.
     pub const fn zs_range(&self) -> Range<usize> {
-        0..self.config.num_challenges
+        0..self.num_challenges()
     }
 
     /// Range of the partial products polynomials in the `zs_partial_products_lookup_commitment`.
     pub const fn partial_products_range(&self) -> Range<usize> {
-        self.config.num_challenges..(self.num_partial_products + 1) * self.config.num_challenges
+        self.num_challenges()..(self.num_partial_products + 1) * self.num_challenges()
     }
 
     /// Range of lookup polynomials in the `zs_partial_products_lookup_commitment`.
@@ -527,6 +1582,31 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
             ..self.num_zs_partial_products_polys() + i * self.num_lookup_polys + 2
     }
 
+    /// The oracles opened by this circuit's proofs, from which their size can be estimated.
+    pub fn oracle_shape(&self) -> OracleShape {
+        OracleShape {
+            oracles: self.fri_oracles(),
+            num_preprocessed_oracles: 1,
+            num_openings: self.fri_all_polys().len()
+                + self.fri_next_batch_polys().len()
+                + self.num_extra_openings().iter().sum::<usize>(),
+            hiding: self.fri_params.hiding,
+            initial_cap_heights: self.fri_params.initial_cap_heights.clone(),
+        }
+    }
+
+    /// Estimates the size of this circuit's serialized proofs with public inputs, from its FRI
+    /// configuration and oracle shape. Unlike [`ProofWithPublicInputs::size_estimate`], this
+    /// doesn't depend on the layout of the PLONK openings.
+    pub fn estimated_proof_size_bytes<C: GenericConfig<D, F = F>>(&self) -> usize {
+        // The public inputs are preceded by their count.
+        let public_inputs = 8 + self.num_public_inputs * 8;
+        self.config
+            .fri_config
+            .estimated_proof_size_bytes::<C, D>(self.degree_bits(), &self.oracle_shape())
+            + public_inputs
+    }
+
     pub(crate) fn get_fri_instance(&self, zeta: F::Extension) -> FriInstanceInfo<F, D> {
         // All polynomials are opened at zeta.
         let zeta_batch = FriBatchInfo {
@@ -542,40 +1622,59 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
             polynomials: self.fri_next_batch_polys(),
         };
 
-        let openings = vec![zeta_batch, zeta_next_batch];
+        let mut openings = vec![zeta_batch, zeta_next_batch];
+        // Each extra opening point has its own batch.
+        openings.extend(
+            self.extra_opening_points
+                .iter()
+                .map(|opening_point| FriBatchInfo {
+                    point: opening_point.shift_factor(g) * zeta,
+                    polynomials: self.fri_extra_batch_polys(opening_point),
+                }),
+        );
         FriInstanceInfo {
             oracles: self.fri_oracles(),
             batches: openings,
         }
     }
 
-    pub(crate) fn get_fri_instance_target(
+    /// The FRI instance of this circuit's proofs, with the factors its points are `zeta` scaled
+    /// by rather than the points themselves.
+    pub(crate) fn get_fri_instance_shape_target(
         &self,
         builder: &mut CircuitBuilder<F, D>,
-        zeta: ExtensionTarget<D>,
-    ) -> FriInstanceInfoTarget<D> {
+    ) -> FriInstanceShapeTarget<D> {
         // All polynomials are opened at zeta.
-        let zeta_batch = FriBatchInfoTarget {
-            point: zeta,
-            polynomials: self.fri_all_polys(),
-        };
+        let mut batches = vec![(None, self.fri_all_polys())];
 
-        // The Z polynomials are also opened at g * zeta.
+        // The Z polynomials are also opened at g * zeta, and the polynomials of each extra opening
+        // point at a power of g times zeta. These powers of g are read from a table.
         let g = F::primitive_root_of_unity(self.degree_bits());
-        let zeta_next = builder.mul_const_extension(g, zeta);
-        let zeta_next_batch = FriBatchInfoTarget {
-            point: zeta_next,
-            polynomials: self.fri_next_batch_polys(),
-        };
-
-        let openings = vec![zeta_batch, zeta_next_batch];
-        FriInstanceInfoTarget {
+        let shift_factors = core::iter::once(g)
+            .chain(
+                self.extra_opening_points
+                    .iter()
+                    .map(|opening_point| opening_point.shift_factor(g)),
+            )
+            .collect::<Vec<_>>();
+        let table = builder.constant_table(&shift_factors);
+        let g_t = builder.read_table(table, 0);
+        batches.push((Some(g_t), self.fri_next_batch_polys()));
+
+        // Each extra opening point has its own batch.
+        for (i, opening_point) in self.extra_opening_points.iter().enumerate() {
+            let shift = builder.read_table(table, i + 1);
+            batches.push((Some(shift), self.fri_extra_batch_polys(opening_point)));
+        }
+        FriInstanceShapeTarget {
             oracles: self.fri_oracles(),
-            batches: openings,
+            batches,
         }
     }
 
-    fn fri_oracles(&self) -> Vec<FriOracleInfo> {
+    /// The oracles committed to by this circuit's proofs, in the order FRI opens them, so that
+    /// the `i`th one is the oracle with `PlonkOracle::index` `i`.
+    pub fn fri_oracles(&self) -> Vec<FriOracleInfo> {
         vec![
             FriOracleInfo {
                 num_polys: self.num_preprocessed_polys(),
@@ -603,10 +1702,16 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
         )
     }
 
-    pub(crate) const fn num_preprocessed_polys(&self) -> usize {
+    /// The number of polynomials in the constants and sigmas oracle.
+    pub const fn num_preprocessed_polys(&self) -> usize {
         self.sigmas_range().end
     }
 
+    /// The number of random values appended to each leaf of `oracle`'s Merkle tree.
+    pub const fn salt_size(&self, oracle: PlonkOracle) -> usize {
+        salt_size(self.fri_params.hiding && oracle.blinding)
+    }
+
     fn fri_wire_polys(&self) -> Vec<FriPolynomialInfo> {
         let num_wire_polys = self.config.num_wires;
         FriPolynomialInfo::from_range(PlonkOracle::WIRES.index, 0..num_wire_polys)
@@ -619,13 +1724,15 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
         )
     }
 
-    pub(crate) const fn num_zs_partial_products_polys(&self) -> usize {
-        self.config.num_challenges * (1 + self.num_partial_products)
+    /// The number of Z and partial product polynomials, not counting the lookup polynomials
+    /// which share their oracle.
+    pub const fn num_zs_partial_products_polys(&self) -> usize {
+        self.num_challenges() * (1 + self.num_partial_products)
     }
 
     /// Returns the total number of lookup polynomials.
     pub(crate) const fn num_all_lookup_polys(&self) -> usize {
-        self.config.num_challenges * self.num_lookup_polys
+        self.num_challenges() * self.num_lookup_polys
     }
     fn fri_zs_polys(&self) -> Vec<FriPolynomialInfo> {
         FriPolynomialInfo::from_range(PlonkOracle::ZS_PARTIAL_PRODUCTS.index, self.zs_range())
@@ -636,6 +1743,39 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
         [self.fri_zs_polys(), self.fri_lookup_polys()].concat()
     }
 
+    /// Returns the polynomials opened at an extra opening point, i.e. all polynomials of its
+    /// oracles.
+    fn fri_extra_batch_polys(&self, opening_point: &OpeningPoint) -> Vec<FriPolynomialInfo> {
+        let oracles = self.fri_oracles();
+        opening_point
+            .oracles
+            .iter()
+            .flat_map(|&oracle| FriPolynomialInfo::from_range(oracle, 0..oracles[oracle].num_polys))
+            .collect()
+    }
+
+    /// The number of points at which the "regular" polynomials and the Z polynomials may be
+    /// opened, to count the values to blind.
+    pub(crate) fn num_opening_points(&self) -> (usize, usize) {
+        let num_extra_points = self.extra_opening_points.len();
+        (1 + num_extra_points, 2 + num_extra_points)
+    }
+
+    /// The number of values opened at each extra opening point.
+    pub fn num_extra_openings(&self) -> Vec<usize> {
+        let oracles = self.fri_oracles();
+        self.extra_opening_points
+            .iter()
+            .map(|opening_point| {
+                opening_point
+                    .oracles
+                    .iter()
+                    .map(|&oracle| oracles[oracle].num_polys)
+                    .sum()
+            })
+            .collect()
+    }
+
     fn fri_quotient_polys(&self) -> Vec<FriPolynomialInfo> {
         FriPolynomialInfo::from_range(PlonkOracle::QUOTIENT.index, 0..self.num_quotient_polys())
     }
@@ -648,8 +1788,10 @@ impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
                 ..self.num_zs_partial_products_polys() + self.num_all_lookup_polys(),
         )
     }
-    pub(crate) const fn num_quotient_polys(&self) -> usize {
-        self.config.num_challenges * self.quotient_degree_factor
+
+    /// The number of chunks of the quotient polynomials, across all challenges.
+    pub const fn num_quotient_polys(&self) -> usize {
+        self.num_challenges() * self.quotient_degree_factor
     }
 
     fn fri_all_polys(&self) -> Vec<FriPolynomialInfo> {
@@ -676,3 +1818,592 @@ pub struct VerifierCircuitTarget {
     /// seed Fiat-Shamir.
     pub circuit_digest: HashOutTarget,
 }
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{format, string::ToString};
+
+    use super::*;
+    use crate::fri::oracle::SALT_SIZE;
+    use crate::gates::noop::NoopGate;
+    use crate::hash::hash_types::HashOut;
+    use crate::iop::witness::WitnessWrite;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+    use crate::plonk::verifier::VerificationError;
+    use crate::util::serialization::{DefaultGateSerializer, DefaultGeneratorSerializer};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn estimated_proof_size_is_close() -> anyhow::Result<()> {
+        let standard = CircuitConfig::standard_recursion_config();
+        let zk = CircuitConfig::standard_recursion_zk_config();
+        let mut high_rate = CircuitConfig::standard_recursion_config();
+        high_rate.fri_config.rate_bits = 6;
+        high_rate.fri_config.cap_height = 0;
+        high_rate.fri_config.num_query_rounds = 14;
+        high_rate.fri_config.reduction_strategy = FriReductionStrategy::MinSize(None);
+        let mut tall_caps = CircuitConfig::standard_recursion_config();
+        tall_caps.oracle_cap_heights = OracleCapHeights {
+            constants_sigmas: Some(8),
+            wires: Some(5),
+            zs_partial_products: None,
+            quotient: Some(2),
+        };
+
+        for config in [standard, zk, high_rate, tall_caps] {
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_public_input();
+            let mut y = x;
+            for _ in 0..1000 {
+                y = builder.mul_add(y, y, x);
+            }
+            builder.register_public_input(y);
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u32(3));
+            let actual = data.prove(pw)?.to_bytes().len();
+            let estimate = data.common.estimated_proof_size_bytes::<C>();
+            assert!(
+                estimate.abs_diff(actual) * 20 < actual,
+                "estimated {} bytes, serialized {}",
+                estimate,
+                actual
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn standard_config_counts() {
+        let build = |config| {
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_public_input();
+            let y = builder.mul_add(x, x, x);
+            builder.register_public_input(y);
+            builder.build::<C>().common
+        };
+        let common = build(CircuitConfig::standard_recursion_config());
+
+        // Two selector polynomials, and the two constants of `ConstantGate`.
+        assert_eq!(common.num_constants, 4);
+        // The constants, then a sigma for each of the 80 routed wires.
+        assert_eq!(common.num_preprocessed_polys(), 4 + 80);
+        assert_eq!(common.num_challenges(), 2);
+        // The product of 80 quotients, in chunks of 8, needs 10 - 1 partial products.
+        assert_eq!(common.quotient_degree_factor, 8);
+        assert_eq!(common.num_partial_products, 9);
+        assert_eq!(common.num_zs_partial_products_polys(), 2 * (1 + 9));
+        assert_eq!(common.num_quotient_polys(), 2 * 8);
+
+        let oracles = common.fri_oracles();
+        assert_eq!(
+            oracles.iter().map(|o| o.num_polys).collect::<Vec<_>>(),
+            [84, 135, 20, 16]
+        );
+        assert_eq!(
+            oracles.iter().map(|o| o.blinding).collect::<Vec<_>>(),
+            PlonkOracle::ALL.map(|oracle| oracle.blinding)
+        );
+        for oracle in PlonkOracle::ALL {
+            assert_eq!(common.salt_size(oracle), 0);
+        }
+
+        // With zero-knowledge, the leaves of every oracle but the preprocessed one are salted.
+        let common = build(CircuitConfig::standard_recursion_zk_config());
+        assert_eq!(common.salt_size(PlonkOracle::CONSTANTS_SIGMAS), 0);
+        for oracle in [
+            PlonkOracle::WIRES,
+            PlonkOracle::ZS_PARTIAL_PRODUCTS,
+            PlonkOracle::QUOTIENT,
+        ] {
+            assert_eq!(common.salt_size(oracle), SALT_SIZE);
+        }
+    }
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn export_timing() -> anyhow::Result<()> {
+        use core::time::Duration;
+
+        use crate::util::timing::TimingEntry;
+
+        fn find<'a>(entry: &'a TimingEntry, name: &str) -> Option<&'a TimingEntry> {
+            if entry.name == name {
+                return Some(entry);
+            }
+            entry.children.iter().find_map(|c| find(c, name))
+        }
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let mut build_timing = TimingTree::new("build", log::Level::Debug);
+        let data = builder.build_with_timing::<C>(&mut build_timing);
+        let build_entry = build_timing.export();
+        assert_eq!(build_entry.name, "build");
+        assert!(!build_entry.children.is_empty());
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(3));
+        let mut timing = TimingTree::new("prove", log::Level::Debug);
+        data.prove_with_timing(pw, &mut timing)?;
+        timing.pop();
+        let entry = timing.export();
+        assert!(entry.duration > Duration::ZERO);
+        for name in [
+            "compute wire polynomials",
+            "compute quotient polys",
+            "compute opening proofs",
+        ] {
+            let scope = find(&entry, name).unwrap_or_else(|| panic!("no scope {}", name));
+            assert!(scope.duration > Duration::ZERO, "{} took no time", name);
+        }
+
+        let json = serde_json::to_string(&entry)?;
+        assert_eq!(serde_json::from_str::<TimingEntry>(&json)?, entry);
+        Ok(())
+    }
+
+    #[test]
+    fn copy_classes() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let z = builder.add_virtual_target();
+        let unrelated = builder.add_virtual_target();
+        builder.connect(x, y);
+        builder.connect(y, z);
+        let product = builder.mul(x, unrelated);
+        builder.register_public_input(product);
+        let data = builder.build::<C>();
+        let prover_only = &data.prover_only;
+        let common = &data.common;
+
+        let rep = prover_only.representative_of(x, common);
+        assert_eq!(prover_only.representative_of(y, common), rep);
+        assert_eq!(prover_only.representative_of(z, common), rep);
+        assert_ne!(prover_only.representative_of(unrelated, common), rep);
+
+        // `x` is also routed to the multiplicand wire of an arithmetic gate.
+        let members = prover_only.class_members(rep, common);
+        assert!([x, y, z].iter().all(|t| members.contains(t)));
+        assert!(!members.contains(&unrelated));
+        assert!(members.iter().any(|t| t.is_routable(&common.config)));
+        for &member in &members {
+            let index = member.index(common.config.num_wires, common.degree());
+            assert_eq!(
+                Target::from_index(index, common.config.num_wires, common.degree()),
+                member
+            );
+        }
+
+        let stats = prover_only.copy_class_stats();
+        assert!(stats.max_class_size >= members.len());
+        assert!(stats.num_classes < prover_only.representative_map.len());
+    }
+
+    #[test]
+    fn quotient_degree_factor() -> anyhow::Result<()> {
+        let build = |config: CircuitConfig| {
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_public_input();
+            let y = builder.exp_u64(x, 5);
+            builder.register_public_input(y);
+            (x, builder.build::<C>())
+        };
+
+        // With 80 routed wires, a factor of 8 needs 9 partial products, and lower factors cost more.
+        let (_, data) = build(CircuitConfig::standard_recursion_config());
+        let info = data.common.partial_products_info();
+        assert_eq!(info.quotient_degree_factor, 8);
+        assert_eq!(info.num_partial_products, 9);
+        assert!(!info.forced);
+        assert_eq!(info.candidates.last().unwrap().quotient_degree_factor, 8);
+        assert!(info.candidates.iter().all(|candidate| candidate.cost >= 17));
+
+        // Fewer routed wires make a lower factor cheaper.
+        let config = CircuitConfig {
+            num_routed_wires: 35,
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let (_, data) = build(config);
+        let info = data.common.partial_products_info();
+        assert_eq!(info.quotient_degree_factor, 7);
+        assert_eq!(info.num_partial_products, 4);
+
+        let config = CircuitConfig {
+            force_quotient_degree_factor: Some(7),
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let (x, data) = build(config);
+        let info = data.common.partial_products_info();
+        assert!(info.forced);
+        assert_eq!(info.quotient_degree_factor, 7);
+        assert_eq!(info.num_partial_products, 11);
+        data.common.validate()?;
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(3));
+        let proof = data.prove(pw)?;
+        assert_eq!(proof.public_inputs[1], F::from_canonical_u32(243));
+        data.verify(proof)?;
+
+        let gate_serializer = DefaultGateSerializer;
+        let bytes = data.common.to_bytes(&gate_serializer).unwrap();
+        let common = CommonCircuitData::<F, D>::from_bytes(bytes, &gate_serializer).unwrap();
+        assert_eq!(common, data.common);
+
+        let mut common = data.common;
+        common.config.force_quotient_degree_factor = Some(8);
+        assert!(common.validate().is_err());
+        common.config.force_quotient_degree_factor = Some(7);
+        common.validate()?;
+
+        // 27 query rounds of 3 bits and 16 bits of proof of work only reach 97 bits.
+        common.config.fri_config.num_query_rounds -= 1;
+        common.fri_params.config.num_query_rounds -= 1;
+        let err = common.validate().unwrap_err();
+        assert!(
+            format!("{err}").contains("reaching only 97 with 27 query rounds"),
+            "{err}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "force_quotient_degree_factor = 9 is outside of the allowed range")]
+    fn quotient_degree_factor_above_max() {
+        let config = CircuitConfig {
+            force_quotient_degree_factor: Some(9),
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_public_input();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        builder.build::<C>();
+    }
+
+    #[test]
+    fn per_oracle_cap_heights() -> anyhow::Result<()> {
+        let build = |oracle_cap_heights| {
+            let config = CircuitConfig {
+                oracle_cap_heights,
+                ..CircuitConfig::standard_recursion_config()
+            };
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_public_input();
+            let mut y = x;
+            for _ in 0..1000 {
+                y = builder.mul_add(y, y, x);
+            }
+            builder.register_public_input(y);
+            (x, builder.build::<C>())
+        };
+        let (x, uniform) = build(OracleCapHeights::uniform());
+        let (_, data) = build(OracleCapHeights {
+            constants_sigmas: Some(8),
+            wires: Some(5),
+            zs_partial_products: Some(0),
+            quotient: Some(5),
+        });
+        assert_eq!(data.common.fri_params.initial_cap_heights, [8, 5, 0, 5]);
+        assert_eq!(data.verifier_only.constants_sigmas_cap.height(), 8);
+        assert_ne!(
+            data.verifier_only.circuit_digest,
+            uniform.verifier_only.circuit_digest
+        );
+
+        let prove = |data: &CircuitData<F, C, D>| {
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u32(3));
+            data.prove(pw)
+        };
+        let uniform_proof = prove(&uniform)?;
+        let proof = prove(&data)?;
+        assert_eq!(proof.proof.wires_cap.height(), 5);
+        assert_eq!(proof.proof.plonk_zs_partial_products_cap.height(), 0);
+        assert_eq!(proof.proof.quotient_polys_cap.height(), 5);
+        data.verify(proof.clone())?;
+
+        // The taller caps shorten every query's Merkle proofs by more than they cost.
+        let uniform_bytes = uniform_proof.to_bytes().len();
+        let bytes = proof.to_bytes();
+        assert!(
+            bytes.len() < uniform_bytes,
+            "{} bytes with per-oracle cap heights, {} without",
+            bytes.len(),
+            uniform_bytes
+        );
+        assert_eq!(
+            ProofWithPublicInputs::from_bytes(bytes, &data.common)?,
+            proof
+        );
+        let compressed_proof = data.compress(proof.clone())?;
+        assert_eq!(data.decompress(compressed_proof.clone())?, proof);
+        data.verify_compressed(compressed_proof)?;
+
+        // Proofs can't be checked against caps of another height.
+        assert!(uniform.verify(proof).is_err());
+
+        let gate_serializer = DefaultGateSerializer;
+        let generator_serializer = DefaultGeneratorSerializer::<C, D>::default();
+        let bytes = data
+            .to_bytes(&gate_serializer, &generator_serializer)
+            .unwrap();
+        let data_from_bytes =
+            CircuitData::<F, C, D>::from_bytes(&bytes, &gate_serializer, &generator_serializer)
+                .unwrap();
+        assert_eq!(data_from_bytes.common, data.common);
+
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The cap height 16 of initial oracle 3 exceeds the 15 bits of the LDE"
+    )]
+    fn cap_height_above_lde_bits() {
+        let config = CircuitConfig {
+            oracle_cap_heights: OracleCapHeights {
+                quotient: Some(16),
+                ..OracleCapHeights::uniform()
+            },
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        for _ in 0..4000 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        builder.build::<C>();
+    }
+
+    #[test]
+    fn prover_and_verifier_data_round_trip() -> anyhow::Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_public_input();
+        let y = builder.add_virtual_target();
+        let z = builder.mul_add(x, y, x);
+        builder.register_public_input(z);
+        let data = builder.build::<C>();
+        let gate_serializer = DefaultGateSerializer;
+        let generator_serializer = DefaultGeneratorSerializer::<C, D>::default();
+
+        let full_bytes = data
+            .to_bytes(&gate_serializer, &generator_serializer)
+            .unwrap();
+        let data_from_bytes = CircuitData::<F, C, D>::from_bytes(
+            &full_bytes,
+            &gate_serializer,
+            &generator_serializer,
+        )
+        .unwrap();
+        assert_eq!(data_from_bytes.verifier_only, data.verifier_only);
+        assert_eq!(data_from_bytes.common, data.common);
+
+        let verifier_bytes = data.verifier_data().to_bytes(&gate_serializer).unwrap();
+        let verifier_data =
+            VerifierCircuitData::<F, C, D>::from_bytes(verifier_bytes.clone(), &gate_serializer)
+                .unwrap();
+        let sigmas = data.prover_only.sigmas.clone();
+        let subgroup = data.prover_only.subgroup.clone();
+        let prover_data = data.prover_data();
+        let full_bytes = prover_data
+            .to_bytes(&gate_serializer, &generator_serializer)
+            .unwrap();
+        let compact_bytes = prover_data
+            .to_bytes_with_encoding(
+                &gate_serializer,
+                &generator_serializer,
+                ProverDataEncoding::Compact,
+            )
+            .unwrap();
+        assert!(compact_bytes.len() < full_bytes.len());
+
+        for bytes in [&full_bytes, &compact_bytes] {
+            let prover_data = ProverCircuitData::<F, C, D>::from_bytes(
+                bytes,
+                &gate_serializer,
+                &generator_serializer,
+            )
+            .unwrap();
+            assert_eq!(prover_data.prover_only.sigmas, sigmas);
+            assert_eq!(prover_data.prover_only.subgroup, subgroup);
+            assert!(prover_data.prover_only.fft_root_table.is_some());
+
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u32(2));
+            pw.set_target(y, F::from_canonical_u32(3));
+            let proof = prover_data.prove(pw)?;
+            assert_eq!(proof.public_inputs[1], F::from_canonical_u32(8));
+            verifier_data.verify(proof)?;
+        }
+
+        // Data of another kind, of another format version, in the wrong encoding, or whose header
+        // digest doesn't match its contents is refused. Bytes 9 and 10 are the encoding and the
+        // start of the digest.
+        assert!(ProverCircuitData::<F, C, D>::from_bytes(
+            &verifier_bytes,
+            &gate_serializer,
+            &generator_serializer
+        )
+        .is_err());
+        let mut bytes = verifier_bytes.clone();
+        bytes[4] += 1;
+        assert!(VerifierCircuitData::<F, C, D>::from_bytes(bytes, &gate_serializer).is_err());
+        for i in [9, 10] {
+            let mut bytes = verifier_bytes.clone();
+            bytes[i] ^= 1;
+            assert!(VerifierCircuitData::<F, C, D>::from_bytes(bytes, &gate_serializer).is_err());
+            for prover_bytes in [&full_bytes, &compact_bytes] {
+                let mut bytes = prover_bytes.clone();
+                bytes[i] ^= 1;
+                assert!(ProverCircuitData::<F, C, D>::from_bytes(
+                    &bytes,
+                    &gate_serializer,
+                    &generator_serializer
+                )
+                .is_err());
+            }
+        }
+
+        // The digest is recomputed from the contents, so data whose stored digests agree with each
+        // other but not with the circuit is refused too.
+        let mut forged = verifier_data;
+        forged.verifier_only.circuit_digest = HashOut::ZERO;
+        let bytes = forged.to_bytes(&gate_serializer).unwrap();
+        assert!(VerifierCircuitData::<F, C, D>::from_bytes(bytes, &gate_serializer).is_err());
+        let mut forged = prover_data;
+        forged.prover_only.circuit_digest = HashOut::ZERO;
+        let bytes = forged
+            .to_bytes(&gate_serializer, &generator_serializer)
+            .unwrap();
+        assert!(ProverCircuitData::<F, C, D>::from_bytes(
+            &bytes,
+            &gate_serializer,
+            &generator_serializer
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn circuit_digest_parse_round_trip() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        builder.range_check(x, 8);
+        let digest = builder.build::<C>().digest();
+
+        let hex = digest.to_string();
+        assert_eq!(hex.len(), 64);
+        assert!(hex.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')));
+        assert_eq!(hex.parse::<CircuitDigest<C, D>>().unwrap(), digest);
+        assert_eq!(
+            hex.to_uppercase().parse::<CircuitDigest<C, D>>().unwrap(),
+            digest
+        );
+        let json = serde_json::to_string(&digest).unwrap();
+        assert_eq!(json, format!("\"{}\"", hex));
+        assert_eq!(
+            serde_json::from_str::<CircuitDigest<C, D>>(&json).unwrap(),
+            digest
+        );
+
+        assert!(hex[2..].parse::<CircuitDigest<C, D>>().is_err());
+        assert!(format!("{}00", hex).parse::<CircuitDigest<C, D>>().is_err());
+        assert!(format!("zz{}", &hex[2..])
+            .parse::<CircuitDigest<C, D>>()
+            .is_err());
+        // The first element is the field order, which isn't canonical.
+        let non_canonical = format!("01000000ffffffff{}", &hex[16..]);
+        assert!(non_canonical.parse::<CircuitDigest<C, D>>().is_err());
+    }
+
+    #[test]
+    fn route_proofs_by_circuit_digest() -> anyhow::Result<()> {
+        // Three circuits with the same number of public inputs, so a proof for one has the shape
+        // of a proof for the others.
+        let circuits = (1..=3)
+            .map(|power| {
+                let mut builder =
+                    CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+                let x = builder.add_virtual_public_input();
+                let y = builder.exp_u64(x, power);
+                builder.register_public_input(y);
+                (builder.build::<C>(), x)
+            })
+            .collect::<Vec<_>>();
+        let registry = circuits
+            .iter()
+            .map(|(data, _)| (data.digest(), data.verifier_data()))
+            .collect::<hashbrown::HashMap<_, _>>();
+        assert_eq!(registry.len(), 3);
+
+        for (power, (data, x)) in (1..=3).zip(&circuits) {
+            let mut pw = PartialWitness::new();
+            pw.set_target(*x, F::from_canonical_u32(3));
+            let proof = data.prove(pw)?.with_circuit_digest_hint(data.digest());
+
+            // A router reads the hint from the header of the binary form without knowing the
+            // circuit, then deserializes the proof with the circuit it names.
+            let bytes = proof.to_bytes();
+            let digest =
+                ProofWithPublicInputs::<F, C, D>::circuit_digest_hint_from_bytes(&bytes)?.unwrap();
+            assert!(digest == data.verifier_only);
+            let verifier_data = &registry[&digest];
+            let routed = ProofWithPublicInputs::from_bytes(bytes, &verifier_data.common)?;
+            assert_eq!(routed, proof);
+            assert_eq!(
+                routed.public_inputs[1],
+                F::from_canonical_u32(3u32.pow(power))
+            );
+            verifier_data.verify(routed)?;
+
+            // The hint also survives the JSON form.
+            let json = serde_json::to_string(&proof)?;
+            let from_json: ProofWithPublicInputs<F, C, D> = serde_json::from_str(&json)?;
+            assert_eq!(from_json.circuit_digest_hint(), Some(digest));
+        }
+
+        // Without a hint, the header says so, whatever the size of the rest of the proof.
+        let (data, x) = &circuits[0];
+        let mut pw = PartialWitness::new();
+        pw.set_target(*x, F::ONE);
+        let bytes = data.prove(pw)?.to_bytes();
+        assert_eq!(
+            ProofWithPublicInputs::<F, C, D>::circuit_digest_hint_from_bytes(&bytes)?,
+            None
+        );
+        let mut unknown_header = bytes;
+        unknown_header[0] = 2;
+        assert!(
+            ProofWithPublicInputs::<F, C, D>::circuit_digest_hint_from_bytes(&unknown_header)
+                .is_err()
+        );
+
+        // A proof hinted for another circuit is rejected before checking it.
+        let (data, x) = &circuits[0];
+        let mut pw = PartialWitness::new();
+        pw.set_target(*x, F::ONE);
+        let proof = data.prove(pw)?;
+        data.verify(proof.clone())?;
+        let misrouted = proof.with_circuit_digest_hint(circuits[1].0.digest());
+        let err = data.verify(misrouted).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VerificationError>(),
+            Some(VerificationError::CircuitDigestMismatch { .. })
+        ));
+        Ok(())
+    }
+}
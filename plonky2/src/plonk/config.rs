@@ -3,8 +3,9 @@
 //! This module defines a [`Hasher`] trait as well as its recursive
 //! counterpart [`AlgebraicHasher`] for in-circuit hashing. It also
 //! provides concrete configurations, one fully recursive leveraging
-//! the Poseidon hash function both internally and natively, and one
-//! mixing Poseidon internally and truncated Keccak externally.
+//! the Poseidon hash function both internally and natively, one
+//! mixing Poseidon internally and truncated Keccak externally, and a
+//! fully recursive one over the quintic extension field.
 
 #[cfg(not(feature = "std"))]
 use alloc::{vec, vec::Vec};
@@ -14,10 +15,11 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use crate::field::extension::quadratic::QuadraticExtension;
+use crate::field::extension::quintic::QuinticExtension;
 use crate::field::extension::{Extendable, FieldExtension};
 use crate::field::goldilocks_field::GoldilocksField;
 use crate::hash::hash_types::{HashOut, RichField};
-use crate::hash::hashing::PlonkyPermutation;
+use crate::hash::hashing::{HashPadding, PlonkyPermutation};
 use crate::hash::keccak::KeccakHash;
 use crate::hash::poseidon::PoseidonHash;
 use crate::iop::target::{BoolTarget, Target};
@@ -49,12 +51,12 @@ pub trait Hasher<F: RichField>: Sized + Copy + Debug + Eq + PartialEq {
 
     /// Pad the message using the `pad10*1` rule, then hash it.
     fn hash_pad(input: &[F]) -> Self::Hash {
-        let mut padded_input = input.to_vec();
-        padded_input.push(F::ONE);
-        while (padded_input.len() + 1) % Self::Permutation::RATE != 0 {
-            padded_input.push(F::ZERO);
-        }
-        padded_input.push(F::ONE);
+        Self::hash_with_padding(input, HashPadding::Pad101)
+    }
+
+    /// Pad the message with the given rule, then hash it.
+    fn hash_with_padding(input: &[F], padding: HashPadding) -> Self::Hash {
+        let padded_input = padding.pad(input, Self::Permutation::RATE, F::ZERO, F::ONE);
         Self::hash_no_pad(&padded_input)
     }
 
@@ -115,6 +117,22 @@ impl GenericConfig<2> for PoseidonGoldilocksConfig {
     type InnerHasher = PoseidonHash;
 }
 
+/// Configuration using Poseidon over the Goldilocks field, with its quintic extension rather than
+/// the quadratic one. This gives FRI more soundness per query, at the cost of larger proofs.
+///
+/// All gates and gadgets are generic in `D`, but wider extension targets need more wires. In
+/// particular, recursively verifying a proof with a FRI arity of 16 needs an interpolation gate
+/// with 91 routed wires, more than the standard recursion config has, so circuits meant to be
+/// verified recursively should limit their FRI arity to 8.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct PoseidonGoldilocksQuinticConfig;
+impl GenericConfig<5> for PoseidonGoldilocksQuinticConfig {
+    type F = GoldilocksField;
+    type FE = QuinticExtension<Self::F>;
+    type Hasher = PoseidonHash;
+    type InnerHasher = PoseidonHash;
+}
+
 /// Configuration using truncated Keccak over the Goldilocks field.
 #[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
 pub struct KeccakGoldilocksConfig;
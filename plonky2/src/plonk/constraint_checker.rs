@@ -0,0 +1,423 @@
+//! Checks a witness against the constraints of a circuit without generating a proof.
+//!
+//! A witness which doesn't satisfy the circuit only surfaces as a verification failure deep inside
+//! FRI. Evaluating each gate's constraints on the raw wire values instead tells us exactly which
+//! row, gate and constraint is at fault.
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+use hashbrown::hash_map::Entry;
+use hashbrown::HashMap;
+use itertools::Itertools;
+
+use crate::field::extension::{Extendable, FieldExtension};
+use crate::field::polynomial::PolynomialValues;
+use crate::field::types::Field;
+use crate::fri::oracle::PolynomialBatch;
+use crate::hash::hash_types::{HashOut, RichField};
+use crate::iop::challenger::Challenger;
+use crate::iop::generator::generate_partial_witness;
+use crate::iop::target::Target;
+use crate::iop::wire::Wire;
+use crate::iop::witness::{MatrixWitness, PartialWitness, Witness};
+use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
+use crate::plonk::config::GenericConfig;
+use crate::plonk::plonk_common::PlonkOracle;
+use crate::plonk::progress::{PhaseProgress, ProgressCallback, ProverPhase};
+use crate::plonk::prover::{
+    compute_zs_partial_products_and_lookup_polys, get_permutation_and_lookup_challenges,
+    set_lookup_wires,
+};
+use crate::plonk::vars::EvaluationVars;
+use crate::util::timing::TimingTree;
+
+/// A reason why a witness doesn't satisfy a circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConstraintViolation {
+    /// Witness generation failed, so no constraint could be checked.
+    WitnessGeneration(String),
+    /// The `constraint`-th constraint of the gate placed in `row` doesn't evaluate to zero.
+    Gate {
+        row: usize,
+        gate: String,
+        constraint: usize,
+    },
+    /// `wire` doesn't hold the same value as `other`, although a copy constraint ties them
+    /// together. `context` is the context in which the copy constraint was added, if known.
+    Copy {
+        wire: Wire,
+        other: Wire,
+        context: Option<String>,
+    },
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WitnessGeneration(err) => write!(f, "witness generation failed: {}", err),
+            Self::Gate {
+                row,
+                gate,
+                constraint,
+            } => write!(
+                f,
+                "constraint {} of {} in row {} is not satisfied",
+                constraint, gate, row
+            ),
+            Self::Copy {
+                wire,
+                other,
+                context,
+            } => {
+                write!(f, "copy constraint between {:?} and {:?}", wire, other)?;
+                if let Some(context) = context {
+                    write!(f, " (connected in `{}`)", context)?;
+                }
+                write!(f, " is not satisfied")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConstraintViolation {}
+
+/// Runs witness generation on `inputs` and checks the resulting witness against every gate and
+/// copy constraint of the circuit. Only the first violation is returned unless `report_all` is
+/// set.
+///
+/// Lookup arguments are not checked, as they are only enforced through the vanishing polynomial.
+pub fn find_constraint_violations<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    inputs: PartialWitness<F>,
+    report_all: bool,
+) -> Vec<ConstraintViolation> {
+    let mut partition_witness = match generate_partial_witness(inputs, prover_data, common_data) {
+        Ok(witness) => witness,
+        Err(err) => return vec![ConstraintViolation::WitnessGeneration(err.to_string())],
+    };
+    set_lookup_wires(prover_data, common_data, &mut partition_witness);
+
+    let public_inputs = partition_witness.get_targets(&prover_data.public_inputs);
+    let public_inputs_hash = common_data
+        .public_input_commitment
+        .hash::<F, C::InnerHasher>(&public_inputs);
+    let witness = partition_witness.full_witness();
+
+    check_matrix_witness(
+        prover_data,
+        common_data,
+        &witness,
+        &public_inputs_hash,
+        report_all,
+    )
+}
+
+/// Checks the wire values of a full witness, as committed to by the prover, against every gate and
+/// copy constraint of the circuit. Gates are evaluated with `eval_unfiltered`, on the gate placed
+/// in each row.
+pub fn check_matrix_witness<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    witness: &MatrixWitness<F>,
+    public_inputs_hash: &HashOut<F>,
+    report_all: bool,
+) -> Vec<ConstraintViolation> {
+    let mut violations = Vec::new();
+    let degree = common_data.degree();
+    let num_wires = common_data.config.num_wires;
+    let selectors_info = &common_data.selectors_info;
+    let num_prefix_constants = selectors_info.num_selectors() + common_data.num_lookup_selectors;
+
+    let constants_sigmas = prover_data
+        .constants_sigmas_commitment
+        .polynomials
+        .iter()
+        .map(|poly| poly.clone().fft().values)
+        .collect::<Vec<_>>();
+    let constants = &constants_sigmas[common_data.constants_range()];
+    let opens_zs_partial_products = common_data
+        .extra_opening_points
+        .iter()
+        .any(|opening_point| {
+            opening_point
+                .oracles
+                .contains(&PlonkOracle::ZS_PARTIAL_PRODUCTS.index)
+        });
+    let zs_partial_products_lookups = if opens_zs_partial_products {
+        compute_zs_partial_products_and_lookups(
+            prover_data,
+            common_data,
+            witness,
+            public_inputs_hash,
+        )
+    } else {
+        Vec::new()
+    };
+
+    for row in 0..degree {
+        let local_constants = constants
+            .iter()
+            .map(|values| F::Extension::from_basefield(values[row]))
+            .collect_vec();
+        let local_wires = witness
+            .wire_values
+            .iter()
+            .map(|values| F::Extension::from_basefield(values[row]))
+            .collect_vec();
+        // The values at an extra opening point are those of the row `shift` rows away.
+        let extra_openings = common_data
+            .extra_opening_points
+            .iter()
+            .map(|opening_point| {
+                let other_row = (row as isize + opening_point.shift).rem_euclid(degree as isize);
+                let other_row = other_row as usize;
+                let mut values = Vec::new();
+                for &oracle in &opening_point.oracles {
+                    if oracle == PlonkOracle::CONSTANTS_SIGMAS.index {
+                        values.extend(constants_sigmas.iter().map(|v| v[other_row]));
+                    } else if oracle == PlonkOracle::WIRES.index {
+                        values.extend(witness.wire_values.iter().map(|v| v[other_row]));
+                    } else if oracle == PlonkOracle::ZS_PARTIAL_PRODUCTS.index {
+                        values.extend(
+                            zs_partial_products_lookups
+                                .iter()
+                                .map(|v| v.values[other_row]),
+                        );
+                    } else {
+                        unreachable!("The quotient oracle can't be opened at extra points");
+                    }
+                }
+                values
+                    .into_iter()
+                    .map(F::Extension::from_basefield)
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        for (i, gate) in common_data.gates.iter().enumerate() {
+            // The selector polynomial of a gate's group holds the gate's index in the rows where
+            // it is placed.
+            let selector = local_constants[selectors_info.selector_indices[i]];
+            if selector != F::Extension::from_canonical_usize(i) {
+                continue;
+            }
+
+            let vars = EvaluationVars {
+                local_constants: &local_constants[num_prefix_constants..],
+                local_wires: &local_wires,
+                public_inputs_hash,
+                extra_openings: &extra_openings,
+            };
+            for (constraint, value) in gate.0.eval_unfiltered(vars).into_iter().enumerate() {
+                if value != F::Extension::ZERO {
+                    violations.push(ConstraintViolation::Gate {
+                        row,
+                        gate: gate.0.id(),
+                        constraint,
+                    });
+                    if !report_all {
+                        return violations;
+                    }
+                }
+            }
+        }
+    }
+
+    // Compare each wire with the first wire of its partition.
+    let mut partition_wires = HashMap::new();
+    for row in 0..degree {
+        for column in 0..num_wires {
+            let wire = Wire { row, column };
+            let rep = prover_data.representative_map[Target::Wire(wire).index(num_wires, degree)];
+            match partition_wires.entry(rep) {
+                Entry::Vacant(entry) => {
+                    entry.insert(wire);
+                }
+                Entry::Occupied(entry) => {
+                    let other: Wire = *entry.get();
+                    if witness.wire_values[column][row]
+                        != witness.wire_values[other.column][other.row]
+                    {
+                        violations.push(ConstraintViolation::Copy {
+                            wire,
+                            other,
+                            context: prover_data.partition_contexts.get(&rep).cloned(),
+                        });
+                        if !report_all {
+                            return violations;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// Computes the `Z`s, partial products and lookup polynomials of `witness`, with the challenges a
+/// prover drawing them after committing to these wire values would get. In zero-knowledge
+/// circuits, the wires commitment is salted, so these are the values of one possible proof.
+fn compute_zs_partial_products_and_lookups<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    witness: &MatrixWitness<F>,
+    public_inputs_hash: &HashOut<F>,
+) -> Vec<PolynomialValues<F>> {
+    let config = &common_data.config;
+    let mut timing = TimingTree::default();
+    let wires_commitment = PolynomialBatch::<F, C, D>::from_values(
+        witness
+            .wire_values
+            .iter()
+            .map(|column| PolynomialValues::new(column.clone()))
+            .collect(),
+        config.fri_config.rate_bits,
+        config.zero_knowledge && PlonkOracle::WIRES.blinding,
+        config.cap_height(PlonkOracle::WIRES),
+        &mut timing,
+        prover_data.fft_root_table.as_deref(),
+    );
+
+    let mut challenger = Challenger::<F, C::Hasher>::new();
+    challenger.observe_hash::<C::Hasher>(prover_data.circuit_digest);
+    challenger.observe_hash::<C::InnerHasher>(*public_inputs_hash);
+    challenger.observe_cap::<C::Hasher>(&wires_commitment.merkle_tree.cap);
+    let (betas, gammas, deltas) =
+        get_permutation_and_lookup_challenges::<F, C, D>(&mut challenger, common_data);
+
+    compute_zs_partial_products_and_lookup_polys(
+        witness,
+        &betas,
+        &gammas,
+        &deltas,
+        prover_data,
+        common_data,
+        &PhaseProgress::start(&ProgressCallback::noop(), ProverPhase::PartialProducts),
+        &mut timing,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::gates::arithmetic_base::ArithmeticGate;
+    use crate::gates::gate::Gate;
+    use crate::iop::witness::WitnessWrite;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{Hasher, PoseidonGoldilocksConfig};
+    use crate::with_context;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn corrupted_witness_is_pinpointed() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let product = builder.mul(x, y);
+        let exposed = builder.add_virtual_target();
+        with_context!(
+            builder,
+            "expose the product",
+            builder.connect(product, exposed)
+        );
+        builder.register_public_input(exposed);
+        let data = builder.build::<C>();
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::from_canonical_u32(6));
+        inputs.set_target(y, F::from_canonical_u32(7));
+        assert_eq!(data.check_witness(inputs.clone()), Ok(()));
+
+        let mut partition_witness =
+            generate_partial_witness(inputs, &data.prover_only, &data.common)?;
+        set_lookup_wires(&data.prover_only, &data.common, &mut partition_witness);
+        let public_inputs = partition_witness.get_targets(&data.prover_only.public_inputs);
+        let public_inputs_hash = <C as GenericConfig<D>>::InnerHasher::hash_no_pad(&public_inputs);
+        let mut witness = partition_witness.full_witness();
+
+        let Target::Wire(product_wire) = product else {
+            panic!("the product should be a gate output");
+        };
+        witness.wire_values[product_wire.column][product_wire.row] = F::from_canonical_u32(41);
+
+        let first = check_matrix_witness(
+            &data.prover_only,
+            &data.common,
+            &witness,
+            &public_inputs_hash,
+            false,
+        );
+        let arithmetic_gate = ArithmeticGate::new_from_config(&data.common.config);
+        let op = (0..arithmetic_gate.num_ops)
+            .position(|i| ArithmeticGate::wire_ith_output(i) == product_wire.column)
+            .unwrap();
+        assert_eq!(
+            first,
+            vec![ConstraintViolation::Gate {
+                row: product_wire.row,
+                gate: Gate::<F, D>::id(&arithmetic_gate),
+                constraint: op,
+            }]
+        );
+
+        let all = check_matrix_witness(
+            &data.prover_only,
+            &data.common,
+            &witness,
+            &public_inputs_hash,
+            true,
+        );
+        assert!(all.len() > 1);
+        assert!(all.iter().any(|violation| matches!(
+            violation,
+            ConstraintViolation::Copy { context: Some(context), .. }
+                if context.contains("expose the product")
+        )));
+
+        Ok(())
+    }
+
+    #[test]
+    fn failed_generation_is_reported() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let violation = data.check_witness(PartialWitness::new()).unwrap_err();
+        assert!(matches!(
+            violation,
+            ConstraintViolation::WitnessGeneration(_)
+        ));
+    }
+}
@@ -0,0 +1,415 @@
+//! Estimates of the cost of proving a circuit, computed from its shape before it is built. See
+//! [`CircuitBuilder::cost_report`](crate::plonk::circuit_builder::CircuitBuilder::cost_report).
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fri::oracle::SALT_SIZE;
+use crate::fri::FriParams;
+use crate::hash::hash_types::RichField;
+use crate::hash::hashing::PlonkyPermutation;
+use crate::plonk::circuit_data::CircuitConfig;
+use crate::plonk::config::Hasher;
+use crate::plonk::plonk_common::PlonkOracle;
+use crate::util::partial_products::num_partial_products;
+use crate::util::{ceil_div_usize, log2_ceil};
+
+/// The size of a field element in bytes.
+const FIELD_BYTES: usize = 8;
+
+/// The cost of one of the four oracles committed to by the prover.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OracleCost {
+    pub name: String,
+    /// The number of committed polynomials, including salt polynomials.
+    pub num_polys: usize,
+    /// The size of the low-degree extensions of the polynomials.
+    pub lde_bytes: usize,
+    /// The number of permutations needed to build the oracle's Merkle tree.
+    pub merkle_permutations: usize,
+}
+
+/// An estimate of the cost of proving a circuit, returned by
+/// [`CircuitBuilder::cost_report`](crate::plonk::circuit_builder::CircuitBuilder::cost_report).
+///
+/// Hashing work is counted in permutations of the config's hashers: its Merkle tree hasher for the
+/// oracles and FRI, and its inner hasher for the public inputs. Memory estimates assume 8-byte
+/// field elements.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CostReport {
+    /// The number of rows of each gate type, by gate ID, including the rows `build` adds before
+    /// blinding and padding.
+    pub gate_rows: BTreeMap<String, usize>,
+    /// The number of rows before blinding and padding.
+    pub num_rows: usize,
+    /// The number of rows added for zero-knowledge blinding.
+    pub num_blinding_rows: usize,
+    /// The number of selector polynomials which tell the gate types apart, not counting lookup
+    /// selectors.
+    pub num_selectors: usize,
+    /// The largest number of constraints of a gate, which is the number of terms of the combined
+    /// constraint evaluated at each point of the quotient's domain.
+    pub num_gate_constraints: usize,
+    pub degree_bits: usize,
+    pub rate_bits: usize,
+    pub oracles: Vec<OracleCost>,
+    /// The size of the low-degree extensions of all oracles.
+    pub lde_memory_bytes: usize,
+    /// The peak memory used to build the circuit and prove it, including the polynomials in
+    /// coefficient form, the Merkle trees and the witness.
+    pub prover_memory_bytes: usize,
+    /// The number of permutations needed to build the oracles' Merkle trees.
+    pub merkle_permutations: usize,
+    /// The number of permutations of the FRI commit phase, including the expected number of
+    /// proof-of-work attempts.
+    pub fri_permutations: usize,
+}
+
+impl CostReport {
+    pub(crate) fn new<F: RichField, H: Hasher<F>>(
+        config: &CircuitConfig,
+        fri_params: &FriParams,
+        extension_degree: usize,
+        quotient_degree_factor: usize,
+        gate_rows: BTreeMap<String, usize>,
+        num_blinding_rows: usize,
+        num_selectors: usize,
+        num_gate_constraints: usize,
+        num_constant_polys: usize,
+        num_lookup_polys: usize,
+    ) -> Self {
+        let num_rows = gate_rows.values().sum::<usize>();
+        let degree_bits = log2_ceil(num_rows + num_blinding_rows);
+        let degree = 1 << degree_bits;
+        let rate_bits = config.fri_config.rate_bits;
+        let lde_size = degree << rate_bits;
+        let salt = if config.zero_knowledge { SALT_SIZE } else { 0 };
+
+        let num_challenges = config.num_challenges;
+        let num_zs = num_challenges
+            * (1 + num_partial_products(config.num_routed_wires, quotient_degree_factor)
+                + num_lookup_polys);
+        let oracles = [
+            (
+                "constants and sigmas",
+                num_constant_polys + config.num_routed_wires,
+            ),
+            ("wires", config.num_wires + salt),
+            ("Z and partial products", num_zs + salt),
+            ("quotient", num_challenges * quotient_degree_factor + salt),
+        ]
+        .into_iter()
+        .zip(PlonkOracle::ALL)
+        .map(|((name, num_polys), oracle)| OracleCost {
+            name: name.into(),
+            num_polys,
+            lde_bytes: num_polys * lde_size * FIELD_BYTES,
+            merkle_permutations: merkle_tree_permutations::<F, H>(
+                lde_size,
+                num_polys,
+                config.cap_height(oracle),
+            ),
+        })
+        .collect::<Vec<_>>();
+
+        let lde_memory_bytes = oracles.iter().map(|o| o.lde_bytes).sum::<usize>();
+        let merkle_permutations = oracles.iter().map(|o| o.merkle_permutations).sum();
+
+        // Each oracle also keeps its polynomials in coefficient form, and its Merkle tree's
+        // digests. The leaves of the tree are the LDE itself.
+        let num_polys = oracles.iter().map(|o| o.num_polys).sum::<usize>();
+        let coeffs_bytes = num_polys * degree * FIELD_BYTES;
+        let digests_bytes = oracles.len() * 2 * lde_size * H::HASH_SIZE;
+        // The partition witness holds an optional value for each wire, and the FRI polynomial is
+        // extended like the oracles.
+        let witness_bytes = config.num_wires * degree * 2 * FIELD_BYTES;
+        let fri_bytes = extension_degree * lde_size * FIELD_BYTES + 2 * lde_size * H::HASH_SIZE;
+        let prover_memory_bytes =
+            lde_memory_bytes + coeffs_bytes + digests_bytes + witness_bytes + fri_bytes;
+
+        let mut fri_permutations = 1 << fri_params.config.proof_of_work_bits;
+        let mut fri_size = lde_size;
+        for &arity_bits in &fri_params.reduction_arity_bits {
+            fri_size >>= arity_bits;
+            fri_permutations += merkle_tree_permutations::<F, H>(
+                fri_size,
+                extension_degree << arity_bits,
+                config.fri_config.cap_height.min(log2_ceil(fri_size)),
+            );
+        }
+
+        Self {
+            gate_rows,
+            num_rows,
+            num_blinding_rows,
+            num_selectors,
+            num_gate_constraints,
+            degree_bits,
+            rate_bits,
+            oracles,
+            lde_memory_bytes,
+            prover_memory_bytes,
+            merkle_permutations,
+            fri_permutations,
+        }
+    }
+
+    /// The total number of values in the low-degree extensions of the oracles.
+    pub fn num_lde_values(&self) -> usize {
+        self.lde_memory_bytes / FIELD_BYTES
+    }
+
+    /// Estimates the time to prove the circuit, with per-unit costs measured on the target machine.
+    pub fn estimate_proving_time(&self, calibration: &CostCalibration) -> Duration {
+        let nanos = calibration.nanos_per_row * (1usize << self.degree_bits) as f64
+            + calibration.nanos_per_lde_value * self.num_lde_values() as f64
+            + calibration.nanos_per_permutation
+                * (self.merkle_permutations + self.fri_permutations) as f64;
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+/// Counts the permutations of `H` needed to build a Merkle tree with `num_leaves` leaves of
+/// `leaf_len` elements. As in `Hasher::hash_or_noop`, leaves no larger than a hash are used as
+/// their own digests.
+fn merkle_tree_permutations<F: RichField, H: Hasher<F>>(
+    num_leaves: usize,
+    leaf_len: usize,
+    cap_height: usize,
+) -> usize {
+    let leaf_permutations = if leaf_len * FIELD_BYTES <= H::HASH_SIZE {
+        0
+    } else {
+        ceil_div_usize(leaf_len, H::Permutation::RATE)
+    };
+    num_leaves * leaf_permutations + num_leaves.saturating_sub(1 << cap_height)
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} rows ({} for blinding), degree 2^{}, LDE 2^{}",
+            self.num_rows + self.num_blinding_rows,
+            self.num_blinding_rows,
+            self.degree_bits,
+            self.degree_bits + self.rate_bits
+        )?;
+        for (gate, rows) in &self.gate_rows {
+            writeln!(f, "- {rows} rows of {gate}")?;
+        }
+        writeln!(
+            f,
+            "{} selectors, {} gate constraints",
+            self.num_selectors, self.num_gate_constraints
+        )?;
+        for oracle in &self.oracles {
+            writeln!(
+                f,
+                "- oracle {}: {} polynomials, {} bytes of LDE, {} permutations",
+                oracle.name, oracle.num_polys, oracle.lde_bytes, oracle.merkle_permutations
+            )?;
+        }
+        writeln!(
+            f,
+            "LDE memory: {} bytes, prover memory: {} bytes",
+            self.lde_memory_bytes, self.prover_memory_bytes
+        )?;
+        write!(
+            f,
+            "Merkle permutations: {}, FRI permutations: {}",
+            self.merkle_permutations, self.fri_permutations
+        )
+    }
+}
+
+/// Per-unit costs of proving, used by [`CostReport::estimate_proving_time`]. The defaults are rough
+/// figures for a single core; for better estimates, fit them to a proof on the target machine
+/// with [`CostCalibration::from_measurement`], or measure them directly.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CostCalibration {
+    /// The cost of each row of the circuit, mostly witness generation and constraint evaluation.
+    pub nanos_per_row: f64,
+    /// The cost of each value of the oracles' low-degree extensions, mostly FFTs.
+    pub nanos_per_lde_value: f64,
+    /// The cost of each permutation of the hash.
+    pub nanos_per_permutation: f64,
+}
+
+impl Default for CostCalibration {
+    fn default() -> Self {
+        Self {
+            nanos_per_row: 2000.0,
+            nanos_per_lde_value: 10.0,
+            nanos_per_permutation: 1500.0,
+        }
+    }
+}
+
+impl CostCalibration {
+    /// Scales the default costs so that the estimate for `report` matches `proving_time`, measured
+    /// on a previous run. Circuits of a similar shape can then be estimated with the result.
+    pub fn from_measurement(report: &CostReport, proving_time: Duration) -> Self {
+        let default = Self::default();
+        let estimate = report.estimate_proving_time(&default).as_nanos() as f64;
+        let scale = proving_time.as_nanos() as f64 / estimate;
+        Self {
+            nanos_per_row: default.nanos_per_row * scale,
+            nanos_per_lde_value: default.nanos_per_lde_value * scale,
+            nanos_per_permutation: default.nanos_per_permutation * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::{sync::Arc, vec};
+    #[cfg(feature = "std")]
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::gates::noop::NoopGate;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::config::{GenericConfig, KeccakGoldilocksConfig, PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Checks the estimated shape of the circuit against the built circuit.
+    fn check_estimate(builder: CircuitBuilder<F, D>) -> CostReport {
+        let report = builder.cost_report::<C>();
+        let num_routed_wires = builder.config.num_routed_wires;
+        let data = builder.build::<C>();
+        assert_eq!(report.degree_bits, data.common.degree_bits());
+        assert_eq!(
+            report.oracles[0].num_polys,
+            data.common.num_constants + num_routed_wires
+        );
+        assert_eq!(
+            report.num_selectors,
+            data.common.selectors_info.num_selectors()
+        );
+        assert_eq!(
+            report.num_gate_constraints,
+            data.common.num_gate_constraints
+        );
+        report
+    }
+
+    #[test]
+    fn estimated_degree_matches_built_circuit() {
+        let standard_config = CircuitConfig::standard_recursion_config();
+
+        // A single multiplication.
+        let mut builder = CircuitBuilder::<F, D>::new(standard_config.clone());
+        let x = builder.add_virtual_public_input();
+        let y = builder.mul(x, x);
+        builder.register_public_input(y);
+        let report = check_estimate(builder);
+        assert_eq!(report.num_blinding_rows, 0);
+
+        // Arithmetic filling exactly a power of two of rows, so that the rows added by `build`
+        // double the degree.
+        let mut builder = CircuitBuilder::<F, D>::new(standard_config.clone());
+        let mut x = builder.add_virtual_target();
+        while builder.num_gates() < 1 << 10 {
+            x = builder.mul_add(x, x, x);
+        }
+        builder.register_public_input(x);
+        let report = check_estimate(builder);
+        assert_eq!(report.degree_bits, 11);
+
+        // Many constants and public inputs, some of them constant.
+        let mut builder = CircuitBuilder::<F, D>::new(standard_config.clone());
+        let constants = builder.constants(&F::rand_vec(30));
+        builder.register_public_inputs(&constants[..10]);
+        for &c in &constants[10..] {
+            let x = builder.add_virtual_target();
+            let y = builder.mul(x, c);
+            builder.register_public_input(y);
+        }
+        for _ in 0..200 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        check_estimate(builder);
+
+        // Lookups.
+        let mut builder = CircuitBuilder::<F, D>::new(standard_config.clone());
+        let table = Arc::new((0..300u16).map(|i| (i, i.wrapping_mul(i) % 256)).collect());
+        let table_index = builder.add_lookup_table_from_pairs(table);
+        for _ in 0..40 {
+            let x = builder.add_virtual_target();
+            let y = builder.add_lookup_from_index(x, table_index);
+            builder.register_public_input(y);
+        }
+        check_estimate(builder);
+
+        // Enqueued range checks, some of which share a row placed before `build`.
+        let mut builder = CircuitBuilder::<F, D>::new(standard_config.clone());
+        for i in 0..600 {
+            let x = builder.add_virtual_target();
+            builder.enqueue_range_check(x, [0, 1, 8, 16, 64][i % 5]);
+        }
+        let x = builder.add_virtual_target();
+        builder.split_low_high(x, 8, 24);
+        builder.place_range_checks();
+        for _ in 0..100 {
+            let x = builder.add_virtual_target();
+            builder.enqueue_range_check(x, 16);
+        }
+        let report = check_estimate(builder);
+        assert_eq!(
+            report.gate_rows["RangeCheckGate { num_bits: 16, num_ops: 7 }"],
+            32
+        );
+
+        // Zero-knowledge blinding.
+        let config = CircuitConfig {
+            zero_knowledge: true,
+            ..standard_config
+        };
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_public_input();
+        let y = builder.exp_u64(x, 12345);
+        builder.register_public_input(y);
+        let report = check_estimate(builder);
+        assert!(report.num_blinding_rows > 0);
+    }
+
+    #[test]
+    fn estimate_uses_the_configs_hashers() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let poseidon = builder.cost_report::<C>();
+        let keccak = builder.cost_report::<KeccakGoldilocksConfig>();
+
+        // Both hash the public inputs with Poseidon, but Keccak's 25-byte digests are smaller.
+        assert_eq!(poseidon.gate_rows, keccak.gate_rows);
+        assert!(keccak.prover_memory_bytes < poseidon.prover_memory_bytes);
+    }
+
+    #[test]
+    fn calibration_matches_measurement() {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let report = builder.cost_report::<C>();
+
+        let calibration = CostCalibration::from_measurement(&report, Duration::from_millis(300));
+        let estimate = report.estimate_proving_time(&calibration);
+        assert!(estimate.abs_diff(Duration::from_millis(300)) < Duration::from_micros(1));
+        assert!(report.estimate_proving_time(&CostCalibration::default()) > Duration::ZERO);
+    }
+}
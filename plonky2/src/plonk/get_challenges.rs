@@ -11,7 +11,7 @@ use crate::fri::verifier::{compute_evaluation, fri_combine_initial, PrecomputedR
 use crate::gadgets::polynomial::PolynomialCoeffsExtTarget;
 use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
 use crate::hash::merkle_tree::MerkleCap;
-use crate::iop::challenger::{Challenger, RecursiveChallenger};
+use crate::iop::challenger::{Challenger, RecursiveChallenger, TranscriptLog};
 use crate::iop::target::Target;
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
@@ -19,11 +19,12 @@ use crate::plonk::config::{AlgebraicHasher, GenericConfig, Hasher};
 use crate::plonk::proof::{
     CompressedProof, CompressedProofWithPublicInputs, FriInferredElements, OpeningSet,
     OpeningSetTarget, Proof, ProofChallenges, ProofChallengesTarget, ProofTarget,
-    ProofWithPublicInputs, ProofWithPublicInputsTarget,
+    ProofWithPublicInputs,
 };
 use crate::util::reverse_bits;
 
 fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    challenger: &mut Challenger<F, C::Hasher>,
     public_inputs_hash: <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash,
     wires_cap: &MerkleCap<F, C::Hasher>,
     plonk_zs_partial_products_cap: &MerkleCap<F, C::Hasher>,
@@ -36,16 +37,19 @@ fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, cons
     common_data: &CommonCircuitData<F, D>,
 ) -> anyhow::Result<ProofChallenges<F, D>> {
     let config = &common_data.config;
-    let num_challenges = config.num_challenges;
+    let num_challenges = common_data.num_challenges();
 
-    let mut challenger = Challenger::<F, C::Hasher>::new();
     let has_lookup = common_data.num_lookup_polys != 0;
 
     // Observe the instance.
-    challenger.observe_hash::<C::Hasher>(*circuit_digest);
-    challenger.observe_hash::<C::InnerHasher>(public_inputs_hash);
+    challenger.labeled("circuit_digest", |c| {
+        c.observe_hash::<C::Hasher>(*circuit_digest)
+    });
+    challenger.labeled("public_inputs_hash", |c| {
+        c.observe_hash::<C::InnerHasher>(public_inputs_hash)
+    });
 
-    challenger.observe_cap::<C::Hasher>(wires_cap);
+    challenger.labeled("wires_cap", |c| c.observe_cap::<C::Hasher>(wires_cap));
     let plonk_betas = challenger.get_n_challenges(num_challenges);
     let plonk_gammas = challenger.get_n_challenges(num_challenges);
 
@@ -65,13 +69,19 @@ fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, cons
     };
 
     // `plonk_zs_partial_products_cap` also contains the commitment to lookup polynomials.
-    challenger.observe_cap::<C::Hasher>(plonk_zs_partial_products_cap);
+    challenger.labeled("plonk_zs_partial_products_cap", |c| {
+        c.observe_cap::<C::Hasher>(plonk_zs_partial_products_cap)
+    });
     let plonk_alphas = challenger.get_n_challenges(num_challenges);
 
-    challenger.observe_cap::<C::Hasher>(quotient_polys_cap);
+    challenger.labeled("quotient_polys_cap", |c| {
+        c.observe_cap::<C::Hasher>(quotient_polys_cap)
+    });
     let plonk_zeta = challenger.get_extension_challenge::<D>();
 
-    challenger.observe_openings(&openings.to_fri_openings());
+    challenger.labeled("openings", |c| {
+        c.observe_openings(&openings.to_fri_openings())
+    });
 
     Ok(ProofChallenges {
         plonk_betas,
@@ -98,7 +108,11 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         common_data: &CommonCircuitData<F, D>,
     ) -> anyhow::Result<Vec<usize>> {
         Ok(self
-            .get_challenges(self.get_public_inputs_hash(), circuit_digest, common_data)?
+            .get_challenges(
+                self.get_public_inputs_hash(common_data),
+                circuit_digest,
+                common_data,
+            )?
             .fri_challenges
             .fri_query_indices)
     }
@@ -109,6 +123,45 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         public_inputs_hash: <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash,
         circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
         common_data: &CommonCircuitData<F, D>,
+    ) -> anyhow::Result<ProofChallenges<F, D>> {
+        self.get_challenges_with_challenger(
+            &mut Challenger::new(),
+            public_inputs_hash,
+            circuit_digest,
+            common_data,
+        )
+    }
+
+    /// Like `get_challenges`, but also returns the labeled transcript the challenges were derived
+    /// from, to be compared with a recursive verifier's by [`diff_transcripts`].
+    ///
+    /// [`diff_transcripts`]: crate::iop::challenger::diff_transcripts
+    pub fn get_challenges_with_transcript(
+        &self,
+        public_inputs_hash: <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash,
+        circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> anyhow::Result<(ProofChallenges<F, D>, TranscriptLog<F>)> {
+        let mut challenger = Challenger::new();
+        challenger.enable_transcript_log();
+        let challenges = self.get_challenges_with_challenger(
+            &mut challenger,
+            public_inputs_hash,
+            circuit_digest,
+            common_data,
+        )?;
+        let log = challenger
+            .take_transcript_log()
+            .expect("The transcript log was enabled");
+        Ok((challenges, log))
+    }
+
+    fn get_challenges_with_challenger(
+        &self,
+        challenger: &mut Challenger<F, C::Hasher>,
+        public_inputs_hash: <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash,
+        circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
+        common_data: &CommonCircuitData<F, D>,
     ) -> anyhow::Result<ProofChallenges<F, D>> {
         let Proof {
             wires_cap,
@@ -125,6 +178,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         } = &self.proof;
 
         get_challenges::<F, C, D>(
+            challenger,
             public_inputs_hash,
             wires_cap,
             plonk_zs_partial_products_cap,
@@ -164,6 +218,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         } = &self.proof;
 
         get_challenges::<F, C, D>(
+            &mut Challenger::new(),
             public_inputs_hash,
             wires_cap,
             plonk_zs_partial_products_cap,
@@ -256,6 +311,7 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     fn get_challenges<C: GenericConfig<D, F = F>>(
         &mut self,
+        challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
         public_inputs_hash: HashOutTarget,
         wires_cap: &MerkleCapTarget,
         plonk_zs_partial_products_cap: &MerkleCapTarget,
@@ -270,17 +326,17 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     where
         C::Hasher: AlgebraicHasher<F>,
     {
-        let config = &inner_common_data.config;
-        let num_challenges = config.num_challenges;
+        let num_challenges = inner_common_data.num_challenges();
 
-        let mut challenger = RecursiveChallenger::<F, C::Hasher, D>::new(self);
         let has_lookup = inner_common_data.num_lookup_polys != 0;
 
         // Observe the instance.
-        challenger.observe_hash(&inner_circuit_digest);
-        challenger.observe_hash(&public_inputs_hash);
+        challenger.labeled("circuit_digest", |c| c.observe_hash(&inner_circuit_digest));
+        challenger.labeled("public_inputs_hash", |c| {
+            c.observe_hash(&public_inputs_hash)
+        });
 
-        challenger.observe_cap(wires_cap);
+        challenger.labeled("wires_cap", |c| c.observe_cap(wires_cap));
 
         let plonk_betas = challenger.get_n_challenges(self, num_challenges);
         let plonk_gammas = challenger.get_n_challenges(self, num_challenges);
@@ -300,13 +356,17 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             vec![]
         };
 
-        challenger.observe_cap(plonk_zs_partial_products_cap);
+        challenger.labeled("plonk_zs_partial_products_cap", |c| {
+            c.observe_cap(plonk_zs_partial_products_cap)
+        });
         let plonk_alphas = challenger.get_n_challenges(self, num_challenges);
 
-        challenger.observe_cap(quotient_polys_cap);
+        challenger.labeled("quotient_polys_cap", |c| c.observe_cap(quotient_polys_cap));
         let plonk_zeta = challenger.get_extension_challenge(self);
 
-        challenger.observe_openings(&openings.to_fri_openings());
+        challenger.labeled("openings", |c| {
+            c.observe_openings(&openings.to_fri_openings())
+        });
 
         ProofChallengesTarget {
             plonk_betas,
@@ -325,10 +385,13 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 }
 
-impl<const D: usize> ProofWithPublicInputsTarget<D> {
+impl<const D: usize> ProofTarget<D> {
+    /// Computes the targets of all Fiat-Shamir challenges used in the Plonk proof, observing the
+    /// proof with `challenger`. Enable its transcript log to compare it with a native one.
     pub(crate) fn get_challenges<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>>(
         &self,
         builder: &mut CircuitBuilder<F, D>,
+        challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
         public_inputs_hash: HashOutTarget,
         inner_circuit_digest: HashOutTarget,
         inner_common_data: &CommonCircuitData<F, D>,
@@ -348,9 +411,10 @@ impl<const D: usize> ProofWithPublicInputsTarget<D> {
                     pow_witness,
                     ..
                 },
-        } = &self.proof;
+        } = self;
 
         builder.get_challenges::<C>(
+            challenger,
             public_inputs_hash,
             wires_cap,
             plonk_zs_partial_products_cap,
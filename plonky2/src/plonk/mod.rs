@@ -6,13 +6,20 @@
 pub mod circuit_builder;
 pub mod circuit_data;
 pub mod config;
+pub mod constraint_checker;
 pub(crate) mod copy_constraint;
+pub mod cost_report;
 mod get_challenges;
 pub(crate) mod permutation_argument;
 pub mod plonk_common;
+pub mod progress;
 pub mod proof;
 pub mod prover;
+pub mod row_attribution;
+pub mod sub_circuit;
 mod validate_shape;
 pub(crate) mod vanishing_poly;
+pub(crate) mod vanishing_poly_backend;
 pub mod vars;
 pub mod verifier;
+pub mod verifier_key;
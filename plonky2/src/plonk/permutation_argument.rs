@@ -1,7 +1,6 @@
 #[cfg(not(feature = "std"))]
-use alloc::vec::Vec;
+use alloc::{vec, vec::Vec};
 
-use hashbrown::HashMap;
 use plonky2_maybe_rayon::*;
 
 use crate::field::polynomial::PolynomialValues;
@@ -10,6 +9,7 @@ use crate::iop::target::Target;
 use crate::iop::wire::Wire;
 
 /// Disjoint Set Forest data-structure following <https://en.wikipedia.org/wiki/Disjoint-set_data_structure>.
+#[derive(Debug)]
 pub struct Forest {
     /// A map of parent pointers, stored as indices.
     pub(crate) parents: Vec<usize>,
@@ -35,7 +35,7 @@ impl Forest {
         }
     }
 
-    pub(crate) fn target_index(&self, target: Target) -> usize {
+    pub fn target_index(&self, target: Target) -> usize {
         target.index(self.num_wires, self.degree)
     }
 
@@ -86,73 +86,137 @@ impl Forest {
         }
     }
 
-    /// Assumes `compress_paths` has already been called.
-    pub fn wire_partition(&mut self) -> WirePartition {
-        let mut partition = HashMap::<_, Vec<_>>::new();
-
-        // Here we keep just the Wire targets, filtering out everything else.
-        for row in 0..self.degree {
-            for column in 0..self.num_routed_wires {
-                let w = Wire { row, column };
-                let t = Target::Wire(w);
-                let x_parent = self.parents[self.target_index(t)];
-                partition.entry(x_parent).or_default().push(w);
-            }
-        }
-
-        let partition = partition.into_values().collect();
-        WirePartition { partition }
-    }
-}
-
-pub struct WirePartition {
-    partition: Vec<Vec<Wire>>,
-}
-
-impl WirePartition {
-    pub(crate) fn get_sigma_polys<F: Field>(
+    /// Computes the sigma polynomials of PLONK's permutation argument, which map each routed wire
+    /// to the next wire in its copy class. Within a class, wires are ordered by row and then by
+    /// column, and the last wire wraps around to the first; a wire without copies is its own
+    /// neighbor. Assumes `compress_paths` has already been called.
+    pub(crate) fn sigma_polys<F: Field>(
         &self,
-        degree_log: usize,
         k_is: &[F],
         subgroup: &[F],
     ) -> Vec<PolynomialValues<F>> {
-        let degree = 1 << degree_log;
-        let sigma = self.get_sigma_map(degree, k_is.len());
-
-        sigma
-            .chunks(degree)
-            .map(|chunk| {
-                let values = chunk
-                    .par_iter()
-                    .map(|&x| k_is[x / degree] * subgroup[x % degree])
-                    .collect::<Vec<_>>();
+        let degree = self.degree;
+        let num_routed_wires = self.num_routed_wires;
+
+        // Routed wires are indexed by `row * num_routed_wires + column`. `neighbors` is kept such
+        // that the wires of each class seen so far form a cycle, so that once every wire is added it
+        // holds each wire's neighbor.
+        let mut neighbors = vec![0; degree * num_routed_wires];
+        let mut last_in_class = vec![usize::MAX; self.parents.len()];
+        for row in 0..degree {
+            for column in 0..num_routed_wires {
+                let i = row * num_routed_wires + column;
+                let representative =
+                    self.parents[self.target_index(Target::Wire(Wire { row, column }))];
+                let last = last_in_class[representative];
+                if last == usize::MAX {
+                    neighbors[i] = i;
+                } else {
+                    neighbors[i] = neighbors[last];
+                    neighbors[last] = i;
+                }
+                last_in_class[representative] = i;
+            }
+        }
+        drop(last_in_class);
+
+        (0..num_routed_wires)
+            .into_par_iter()
+            .map(|column| {
+                let values = (0..degree)
+                    .map(|row| {
+                        let neighbor = neighbors[row * num_routed_wires + column];
+                        k_is[neighbor % num_routed_wires] * subgroup[neighbor / num_routed_wires]
+                    })
+                    .collect();
                 PolynomialValues::new(values)
             })
             .collect()
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashbrown::HashMap;
+    use plonky2_field::cosets::get_unique_coset_shifts;
+    use plonky2_field::goldilocks_field::GoldilocksField;
+    use plonky2_field::types::Field;
+    use rand::rngs::OsRng;
+    use rand::Rng;
 
-    /// Generates sigma in the context of Plonk, which is a map from `[kn]` to `[kn]`, where `k` is
-    /// the number of routed wires and `n` is the number of gates.
-    fn get_sigma_map(&self, degree: usize, num_routed_wires: usize) -> Vec<usize> {
-        // Find a wire's "neighbor" in the context of Plonk's "extended copy constraints" check. In
-        // other words, find the next wire in the given wire's partition. If the given wire is last in
-        // its partition, this will loop around. If the given wire has a partition all to itself, it
-        // is considered its own neighbor.
-        let mut neighbors = HashMap::with_capacity(self.partition.len());
-        for subset in &self.partition {
+    use super::*;
+
+    type F = GoldilocksField;
+
+    /// The sigma polynomials computed naively, by collecting each copy class and looking up each
+    /// wire's neighbor in a map.
+    fn sigma_polys_naive(forest: &Forest, k_is: &[F], subgroup: &[F]) -> Vec<PolynomialValues<F>> {
+        let mut partition = HashMap::<_, Vec<_>>::new();
+        for row in 0..forest.degree {
+            for column in 0..forest.num_routed_wires {
+                let w = Wire { row, column };
+                let representative = forest.parents[forest.target_index(Target::Wire(w))];
+                partition.entry(representative).or_default().push(w);
+            }
+        }
+        let mut neighbors = HashMap::new();
+        for subset in partition.values() {
             for n in 0..subset.len() {
                 neighbors.insert(subset[n], subset[(n + 1) % subset.len()]);
             }
         }
+        (0..forest.num_routed_wires)
+            .map(|column| {
+                let values = (0..forest.degree)
+                    .map(|row| {
+                        let neighbor = neighbors[&Wire { row, column }];
+                        k_is[neighbor.column] * subgroup[neighbor.row]
+                    })
+                    .collect();
+                PolynomialValues::new(values)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sigma_polys_match_naive_construction() {
+        const NUM_WIRES: usize = 10;
+        const NUM_ROUTED_WIRES: usize = 6;
+        const DEGREE_BITS: usize = 6;
+        const NUM_VIRTUAL_TARGETS: usize = 50;
+        let degree = 1 << DEGREE_BITS;
+
+        let mut forest = Forest::new(NUM_WIRES, NUM_ROUTED_WIRES, degree, NUM_VIRTUAL_TARGETS);
+        for row in 0..degree {
+            for column in 0..NUM_WIRES {
+                forest.add(Target::Wire(Wire { row, column }));
+            }
+        }
+        for index in 0..NUM_VIRTUAL_TARGETS {
+            forest.add(Target::VirtualTarget { index });
+        }
 
-        let mut sigma = Vec::with_capacity(num_routed_wires * degree);
-        for column in 0..num_routed_wires {
-            for row in 0..degree {
-                let wire = Wire { row, column };
-                let neighbor = neighbors[&wire];
-                sigma.push(neighbor.column * degree + neighbor.row);
+        let mut rng = OsRng;
+        let mut random_target = || {
+            if rng.gen_bool(0.5) {
+                Target::VirtualTarget {
+                    index: rng.gen_range(0..NUM_VIRTUAL_TARGETS),
+                }
+            } else {
+                Target::wire(rng.gen_range(0..degree), rng.gen_range(0..NUM_ROUTED_WIRES))
             }
+        };
+        for _ in 0..200 {
+            let (a, b) = (random_target(), random_target());
+            forest.merge(a, b);
         }
-        sigma
+        forest.compress_paths();
+
+        let k_is = get_unique_coset_shifts(degree, NUM_ROUTED_WIRES);
+        let subgroup = F::two_adic_subgroup(DEGREE_BITS);
+        assert_eq!(
+            forest.sigma_polys(&k_is, &subgroup),
+            sigma_polys_naive(&forest, &k_is, &subgroup)
+        );
     }
 }
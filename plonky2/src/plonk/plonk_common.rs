@@ -7,7 +7,6 @@ use crate::field::extension::Extendable;
 use crate::field::packed::PackedField;
 use crate::field::types::Field;
 use crate::fri::oracle::SALT_SIZE;
-use crate::gates::arithmetic_base::ArithmeticGate;
 use crate::hash::hash_types::RichField;
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::target::Target;
@@ -38,6 +37,14 @@ impl PlonkOracle {
         index: 3,
         blinding: true,
     };
+
+    /// The oracles, in the order of their indices.
+    pub const ALL: [PlonkOracle; 4] = [
+        Self::CONSTANTS_SIGMAS,
+        Self::WIRES,
+        Self::ZS_PARTIAL_PRODUCTS,
+        Self::QUOTIENT,
+    ];
 }
 
 pub const fn salt_size(salted: bool) -> usize {
@@ -134,7 +141,7 @@ pub fn reduce_with_powers_circuit<F: RichField + Extendable<D>, const D: usize>(
     terms: &[Target],
     alpha: Target,
 ) -> Target {
-    if terms.len() <= ArithmeticGate::new_from_config(&builder.config).num_ops + 1 {
+    if terms.len() <= builder.num_base_arithmetic_ops_per_gate() + 1 {
         terms
             .iter()
             .rev()
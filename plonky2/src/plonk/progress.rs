@@ -0,0 +1,206 @@
+//! Progress reporting for building circuits and proving them, e.g. to show a progress bar in
+//! interactive tools.
+
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+use core::fmt::{self, Debug, Display, Formatter};
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+use crate::util::ceil_div_usize;
+
+/// A phase of building a circuit or proving it, in the order in which the phases run.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum ProverPhase {
+    /// Computing the constant and sigma polynomials and committing to them, while building the
+    /// circuit.
+    Preprocessing,
+    /// Running the witness generators. The progress is the fraction of generators which have
+    /// completed.
+    WitnessGeneration,
+    WireCommitment,
+    /// Computing the partial products and Z polynomials of the permutation and lookup arguments,
+    /// and committing to them.
+    PartialProducts,
+    /// Computing the quotient polynomials and committing to them.
+    Quotient,
+    /// Evaluating all polynomials at the opening points.
+    Openings,
+    /// The rounds of the FRI commit phase.
+    FriCommit,
+    /// Searching for the FRI proof-of-work witness.
+    ProofOfWork,
+    FriQueries,
+}
+
+impl ProverPhase {
+    /// The phases of proving, in order. `Preprocessing` happens when building the circuit.
+    pub const PROVING: [Self; 8] = [
+        Self::WitnessGeneration,
+        Self::WireCommitment,
+        Self::PartialProducts,
+        Self::Quotient,
+        Self::Openings,
+        Self::FriCommit,
+        Self::ProofOfWork,
+        Self::FriQueries,
+    ];
+}
+
+impl Display for ProverPhase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Preprocessing => "preprocessing",
+            Self::WitnessGeneration => "witness generation",
+            Self::WireCommitment => "wire commitment",
+            Self::PartialProducts => "partial products",
+            Self::Quotient => "quotient",
+            Self::Openings => "openings",
+            Self::FriCommit => "FRI commit",
+            Self::ProofOfWork => "proof of work",
+            Self::FriQueries => "FRI queries",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A callback receiving the current phase and the fraction of it which has completed, from 0 to 1.
+///
+/// Each phase is reported at 0 when it starts and at 1 when it ends, and in between at most every
+/// percent of progress, so the callback is called at most about a hundred times per phase. It is
+/// called from the thread building or proving the circuit, so it should return quickly.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(ProverPhase, f32) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl Fn(ProverPhase, f32) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    /// A callback which ignores all progress.
+    pub fn noop() -> Self {
+        Self::new(|_, _| {})
+    }
+
+    /// A callback drawing a progress bar for each phase on stderr.
+    #[cfg(feature = "progress_bar")]
+    pub fn stderr() -> Self {
+        use std::io::Write;
+
+        const WIDTH: usize = 30;
+        Self::new(|phase, fraction| {
+            let filled = ((fraction * WIDTH as f32) as usize).min(WIDTH);
+            let mut stderr = std::io::stderr().lock();
+            let _ = write!(
+                stderr,
+                "\r{:<20} [{}{}] {:>3}%",
+                phase,
+                "#".repeat(filled),
+                " ".repeat(WIDTH - filled),
+                (fraction * 100.0) as usize
+            );
+            if fraction >= 1.0 {
+                let _ = writeln!(stderr);
+            }
+        })
+    }
+
+    /// The `stderr` progress bar if the `PLONKY2_PROGRESS` environment variable is set to a value
+    /// other than `0`, and a no-op otherwise.
+    #[cfg(feature = "progress_bar")]
+    pub fn from_env() -> Self {
+        match std::env::var("PLONKY2_PROGRESS") {
+            Ok(value) if !value.is_empty() && value != "0" => Self::stderr(),
+            _ => Self::noop(),
+        }
+    }
+
+    pub fn report(&self, phase: ProverPhase, fraction: f32) {
+        (self.0)(phase, fraction)
+    }
+}
+
+/// Without the `progress_bar` feature, this is a no-op. With it, this is `from_env`.
+impl Default for ProgressCallback {
+    fn default() -> Self {
+        #[cfg(feature = "progress_bar")]
+        return Self::from_env();
+        #[cfg(not(feature = "progress_bar"))]
+        Self::noop()
+    }
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Reports the progress of one phase, throttled to at most one report per percent of progress.
+#[derive(Debug)]
+pub(crate) struct PhaseProgress {
+    callback: ProgressCallback,
+    phase: ProverPhase,
+    next_report: AtomicUsize,
+}
+
+impl PhaseProgress {
+    /// Reports the start of `phase`.
+    pub(crate) fn start(callback: &ProgressCallback, phase: ProverPhase) -> Self {
+        callback.report(phase, 0.0);
+        Self {
+            callback: callback.clone(),
+            phase,
+            next_report: AtomicUsize::new(1),
+        }
+    }
+
+    /// Reports that `done` of the phase's `total` steps have completed, unless less than a
+    /// percent of progress was made since the last report.
+    pub(crate) fn update(&self, done: usize, total: usize) {
+        if done >= self.next_report.load(Ordering::Relaxed) && done < total {
+            self.callback.report(self.phase, done as f32 / total as f32);
+            self.next_report
+                .store(done + ceil_div_usize(total, 100), Ordering::Relaxed);
+        }
+    }
+
+    /// Reports the end of the phase.
+    pub(crate) fn finish(self) {
+        self.callback.report(self.phase, 1.0);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn phase_progress_is_throttled() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let callback = {
+            let reports = reports.clone();
+            ProgressCallback::new(move |phase, fraction| {
+                reports.lock().unwrap().push((phase, fraction))
+            })
+        };
+
+        let progress = PhaseProgress::start(&callback, ProverPhase::WitnessGeneration);
+        for done in 1..=10_000 {
+            progress.update(done, 10_000);
+        }
+        progress.finish();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(
+            reports.first(),
+            Some(&(ProverPhase::WitnessGeneration, 0.0))
+        );
+        assert_eq!(reports.last(), Some(&(ProverPhase::WitnessGeneration, 1.0)));
+        assert!(reports.len() <= 102, "{} reports", reports.len());
+        assert!(reports.windows(2).all(|w| w[0].1 <= w[1].1));
+    }
+}
@@ -20,14 +20,15 @@ use crate::fri::structure::{
     FriOpeningBatch, FriOpeningBatchTarget, FriOpenings, FriOpeningsTarget,
 };
 use crate::fri::FriParams;
-use crate::hash::hash_types::{MerkleCapTarget, RichField};
+use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::ext_target::ExtensionTarget;
 use crate::iop::target::Target;
-use crate::plonk::circuit_data::{CommonCircuitData, VerifierOnlyCircuitData};
+use crate::plonk::circuit_data::{CircuitDigest, CommonCircuitData, VerifierOnlyCircuitData};
 use crate::plonk::config::{GenericConfig, Hasher};
-use crate::plonk::verifier::verify_with_challenges;
-use crate::util::serialization::{Buffer, Read, Write};
+use crate::plonk::plonk_common::PlonkOracle;
+use crate::plonk::verifier::{verify_with_challenges, VerificationError};
+use crate::util::serialization::{Buffer, IoError, IoResult, Read, Write};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(bound = "")]
@@ -54,6 +55,27 @@ pub struct ProofTarget<const D: usize> {
 }
 
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> Proof<F, C, D> {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer
+            .write_proof(self)
+            .expect("Writing to a byte-vector cannot fail.");
+        buffer
+    }
+
+    /// Deserializes a proof for the circuit described by `common_data`, which determines the
+    /// expected length of each component. Fails on truncated input or trailing bytes.
+    pub fn from_bytes(bytes: &[u8], common_data: &CommonCircuitData<F, D>) -> anyhow::Result<Self> {
+        let mut buffer = Buffer::new(bytes);
+        let proof = buffer.read_proof(common_data).map_err(anyhow::Error::msg)?;
+        ensure!(
+            buffer.unread_bytes().is_empty(),
+            "{} trailing bytes after the proof",
+            buffer.unread_bytes().len()
+        );
+        Ok(proof)
+    }
+
     /// Compress the proof.
     pub fn compress(self, indices: &[usize], params: &FriParams) -> CompressedProof<F, C, D> {
         let Proof {
@@ -83,11 +105,30 @@ pub struct ProofWithPublicInputs<
 > {
     pub proof: Proof<F, C, D>,
     pub public_inputs: Vec<F>,
+    /// The digest of the circuit the proof claims to be for, which lets a router pick the
+    /// verifier key without trial verification. It isn't bound to the proof, and verifiers only
+    /// use it to reject proofs hinted for another circuit.
+    #[serde(default)]
+    pub circuit_digest_hint: Option<CircuitDigest<C, D>>,
 }
 
 impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     ProofWithPublicInputs<F, C, D>
 {
+    /// The digest of the circuit the proof claims to be for, if it was given one with
+    /// [`Self::with_circuit_digest_hint`].
+    pub fn circuit_digest_hint(&self) -> Option<CircuitDigest<C, D>> {
+        self.circuit_digest_hint
+    }
+
+    /// Attaches the digest of the circuit the proof is for, e.g. from [`CircuitData::digest`].
+    ///
+    /// [`CircuitData::digest`]: crate::plonk::circuit_data::CircuitData::digest
+    pub fn with_circuit_digest_hint(mut self, digest: CircuitDigest<C, D>) -> Self {
+        self.circuit_digest_hint = Some(digest);
+        self
+    }
+
     pub fn compress(
         self,
         circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
@@ -101,14 +142,134 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         })
     }
 
+    /// The hash of the public inputs, computed as the circuit computes it. The verifier recomputes
+    /// it rather than trusting the prover, and it is bound to the proof through the
+    /// `PublicInputGate` constraints and the Fiat-Shamir transcript.
     pub fn get_public_inputs_hash(
         &self,
+        common_data: &CommonCircuitData<F, D>,
     ) -> <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash {
-        C::InnerHasher::hash_no_pad(&self.public_inputs)
+        common_data
+            .public_input_commitment
+            .hash::<F, C::InnerHasher>(&self.public_inputs)
     }
 
+    /// The public inputs registered under `name` with
+    /// `CircuitBuilder::register_named_public_inputs`, or `None` if there are none.
+    pub fn public_inputs_range(
+        &self,
+        common_data: &CommonCircuitData<F, D>,
+        name: &str,
+    ) -> Option<&[F]> {
+        self.public_inputs
+            .get(common_data.public_inputs_range(name)?)
+    }
+
+    /// The size of the serialized form of any proof for the circuit described by `common_data`,
+    /// broken down by component. This is computed from the circuit's shape alone, so proof sizes
+    /// can be reported without generating or serializing a proof.
+    ///
+    /// ```rust
+    /// use plonky2::plonk::circuit_builder::CircuitBuilder;
+    /// use plonky2::plonk::circuit_data::CircuitConfig;
+    /// use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    /// use plonky2::plonk::proof::ProofWithPublicInputs;
+    ///
+    /// const D: usize = 2;
+    /// type C = PoseidonGoldilocksConfig;
+    /// type F = <C as GenericConfig<D>>::F;
+    ///
+    /// let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    /// let x = builder.add_virtual_public_input();
+    /// let y = builder.square(x);
+    /// builder.register_public_input(y);
+    /// let data = builder.build::<C>();
+    ///
+    /// let size = ProofWithPublicInputs::<F, C, D>::size_estimate(&data.common);
+    /// // The components, after the proof's one-byte header.
+    /// assert_eq!(
+    ///     size.total_bytes,
+    ///     1 + size.caps + size.openings + size.fri_queries + size.final_poly + size.pow
+    ///         + size.public_inputs
+    /// );
+    /// ```
+    pub fn size_estimate(common_data: &CommonCircuitData<F, D>) -> ProofSizeBreakdown {
+        const FIELD_BYTES: usize = 8;
+        let ext_bytes = D * FIELD_BYTES;
+        let hash_bytes = <C::Hasher as Hasher<F>>::HASH_SIZE;
+
+        let config = &common_data.config;
+        let fri_params = &common_data.fri_params;
+        let num_challenges = common_data.num_challenges();
+        let cap_bytes = |cap_height: usize| (1 << cap_height) * hash_bytes;
+        // A Merkle proof is its length as a byte, followed by the siblings up to the cap.
+        let merkle_proof_bytes =
+            |tree_height: usize, cap_height: usize| 1 + (tree_height - cap_height) * hash_bytes;
+
+        // The constants and sigmas cap is part of the verifier data, not of the proof.
+        let caps = [
+            PlonkOracle::WIRES,
+            PlonkOracle::ZS_PARTIAL_PRODUCTS,
+            PlonkOracle::QUOTIENT,
+        ]
+        .iter()
+        .map(|oracle| cap_bytes(fri_params.initial_cap_height(oracle.index)))
+        .sum::<usize>()
+            + fri_params.reduction_arity_bits.len() * cap_bytes(fri_params.config.cap_height);
+
+        let num_openings = common_data.num_preprocessed_polys()
+            + config.num_wires
+            + 2 * num_challenges
+            + 2 * common_data.num_all_lookup_polys()
+            + common_data.num_partial_products * num_challenges
+            + common_data.num_quotient_polys()
+            + common_data.num_extra_openings().iter().sum::<usize>();
+        let openings = num_openings * ext_bytes;
+
+        let mut query_round_bytes = common_data
+            .fri_oracles()
+            .iter()
+            .zip(PlonkOracle::ALL)
+            .map(|(info, oracle)| {
+                let leaf_len = info.num_polys + common_data.salt_size(oracle);
+                leaf_len * FIELD_BYTES
+                    + merkle_proof_bytes(
+                        fri_params.lde_bits(),
+                        fri_params.initial_cap_height(oracle.index),
+                    )
+            })
+            .sum::<usize>();
+        let mut tree_height = fri_params.lde_bits();
+        for &arity_bits in &fri_params.reduction_arity_bits {
+            tree_height -= arity_bits;
+            query_round_bytes += (1 << arity_bits) * ext_bytes
+                + merkle_proof_bytes(tree_height, fri_params.config.cap_height);
+        }
+        let fri_queries = fri_params.config.num_query_rounds * query_round_bytes;
+
+        let final_poly = fri_params.final_poly_len() * ext_bytes;
+        let pow = FIELD_BYTES;
+        // The public inputs are preceded by their count.
+        let public_inputs = 8 + common_data.num_public_inputs * FIELD_BYTES;
+        // The header of a proof without a circuit digest hint is a single byte.
+        let header = 1;
+
+        ProofSizeBreakdown {
+            caps,
+            openings,
+            fri_queries,
+            final_poly,
+            pow,
+            public_inputs,
+            total_bytes: header + caps + openings + fri_queries + final_poly + pow + public_inputs,
+        }
+    }
+
+    /// Serializes the proof, after a header holding its circuit digest hint if it has one.
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
+        write_circuit_digest_hint::<F, C, D>(&mut buffer, self.circuit_digest_hint)
+            .expect("Writing to a byte-vector cannot fail.");
         buffer
             .write_proof_with_public_inputs(self)
             .expect("Writing to a byte-vector cannot fail.");
@@ -120,11 +281,93 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         common_data: &CommonCircuitData<F, D>,
     ) -> anyhow::Result<Self> {
         let mut buffer = Buffer::new(&bytes);
-        let proof = buffer
+        let circuit_digest_hint =
+            read_circuit_digest_hint::<F, C, D>(&mut buffer).map_err(anyhow::Error::msg)?;
+        let mut proof = buffer
             .read_proof_with_public_inputs(common_data)
             .map_err(anyhow::Error::msg)?;
+        proof.circuit_digest_hint = circuit_digest_hint;
+        ensure!(
+            buffer.unread_bytes().is_empty(),
+            "{} trailing bytes after the proof",
+            buffer.unread_bytes().len()
+        );
+        ensure!(
+            proof.public_inputs.len() == common_data.num_public_inputs,
+            "Expected {} public inputs, got {}",
+            common_data.num_public_inputs,
+            proof.public_inputs.len()
+        );
         Ok(proof)
     }
+
+    /// Reads the circuit digest hint from the header of a proof serialized with
+    /// [`Self::to_bytes`], without the circuit's data. A router can then pick the verifier key
+    /// before deserializing the proof.
+    pub fn circuit_digest_hint_from_bytes(
+        bytes: &[u8],
+    ) -> anyhow::Result<Option<CircuitDigest<C, D>>> {
+        read_circuit_digest_hint::<F, C, D>(&mut Buffer::new(bytes)).map_err(anyhow::Error::msg)
+    }
+}
+
+/// The header of a serialized [`ProofWithPublicInputs`] is a tag, followed by the circuit digest
+/// hint if the tag is `WITH_CIRCUIT_DIGEST_HINT`. Other tags are reserved for later versions of
+/// the header, and rejected.
+const NO_CIRCUIT_DIGEST_HINT: u8 = 0;
+const WITH_CIRCUIT_DIGEST_HINT: u8 = 1;
+
+/// Writes the header of a serialized [`ProofWithPublicInputs`].
+fn write_circuit_digest_hint<F, C, const D: usize>(
+    buffer: &mut Vec<u8>,
+    hint: Option<CircuitDigest<C, D>>,
+) -> IoResult<()>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    match hint {
+        None => buffer.write_u8(NO_CIRCUIT_DIGEST_HINT),
+        Some(hint) => {
+            buffer.write_u8(WITH_CIRCUIT_DIGEST_HINT)?;
+            buffer.write_hash::<F, C::Hasher>(hint.0)
+        }
+    }
+}
+
+/// Reads the header of a serialized [`ProofWithPublicInputs`], returning its circuit digest hint.
+pub(crate) fn read_circuit_digest_hint<F, C, const D: usize>(
+    buffer: &mut Buffer,
+) -> IoResult<Option<CircuitDigest<C, D>>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    match buffer.read_u8()? {
+        NO_CIRCUIT_DIGEST_HINT => Ok(None),
+        WITH_CIRCUIT_DIGEST_HINT => Ok(Some(CircuitDigest(buffer.read_hash::<F, C::Hasher>()?))),
+        _ => Err(IoError),
+    }
+}
+
+/// The number of bytes taken by each component of a serialized [`ProofWithPublicInputs`], as
+/// computed by [`ProofWithPublicInputs::size_estimate`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ProofSizeBreakdown {
+    /// The wires, Z and quotient caps, and the FRI commit phase caps.
+    pub caps: usize,
+    /// The purported openings at the challenge point.
+    pub openings: usize,
+    /// The FRI query rounds, including their Merkle proofs.
+    pub fri_queries: usize,
+    /// The coefficients of the FRI final polynomial.
+    pub final_poly: usize,
+    /// The proof-of-work witness.
+    pub pow: usize,
+    /// The public inputs and their count.
+    pub public_inputs: usize,
+    /// The size of the whole proof, including its one-byte header.
+    pub total_bytes: usize,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
@@ -190,8 +433,12 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         circuit_digest: &<<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash,
         common_data: &CommonCircuitData<F, D>,
     ) -> anyhow::Result<ProofWithPublicInputs<F, C, D>> {
-        let challenges =
-            self.get_challenges(self.get_public_inputs_hash(), circuit_digest, common_data)?;
+        let challenges = self.get_challenges(
+            self.get_public_inputs_hash(common_data),
+            circuit_digest,
+            common_data,
+        )?;
+        self.check_query_indices(&challenges, common_data)?;
         let fri_inferred_elements = self.get_inferred_elements(&challenges, common_data);
         let decompressed_proof =
             self.proof
@@ -199,9 +446,43 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         Ok(ProofWithPublicInputs {
             public_inputs: self.public_inputs,
             proof: decompressed_proof,
+            circuit_digest_hint: None,
         })
     }
 
+    /// Checks that the proof contains a query round for every query index derived from the
+    /// challenges. This fails, rather than panicking during decompression, if the proof was
+    /// tampered with.
+    fn check_query_indices(
+        &self,
+        challenges: &ProofChallenges<F, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> anyhow::Result<()> {
+        let query_round_proofs = &self.proof.opening_proof.query_round_proofs;
+        for &(mut index) in &challenges.fri_challenges.fri_query_indices {
+            ensure!(
+                query_round_proofs.initial_trees_proofs.contains_key(&index),
+                "Missing initial trees proof for query index {}",
+                index
+            );
+            for (i, &arity_bits) in common_data
+                .fri_params
+                .reduction_arity_bits
+                .iter()
+                .enumerate()
+            {
+                index >>= arity_bits;
+                ensure!(
+                    query_round_proofs.steps[i].contains_key(&index),
+                    "Missing FRI query step {} for coset index {}",
+                    i,
+                    index
+                );
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn verify(
         self,
         verifier_data: &VerifierOnlyCircuitData<C, D>,
@@ -209,14 +490,18 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
     ) -> anyhow::Result<()> {
         ensure!(
             self.public_inputs.len() == common_data.num_public_inputs,
-            "Number of public inputs doesn't match circuit data."
+            VerificationError::PublicInputsLengthMismatch {
+                expected: common_data.num_public_inputs,
+                actual: self.public_inputs.len(),
+            }
         );
-        let public_inputs_hash = self.get_public_inputs_hash();
+        let public_inputs_hash = self.get_public_inputs_hash(common_data);
         let challenges = self.get_challenges(
             public_inputs_hash,
             &verifier_data.circuit_digest,
             common_data,
         )?;
+        self.check_query_indices(&challenges, common_data)?;
         let fri_inferred_elements = self.get_inferred_elements(&challenges, common_data);
         let decompressed_proof =
             self.proof
@@ -232,8 +517,11 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
 
     pub(crate) fn get_public_inputs_hash(
         &self,
+        common_data: &CommonCircuitData<F, D>,
     ) -> <<C as GenericConfig<D>>::InnerHasher as Hasher<F>>::Hash {
-        C::InnerHasher::hash_no_pad(&self.public_inputs)
+        common_data
+            .public_input_commitment
+            .hash::<F, C::InnerHasher>(&self.public_inputs)
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -252,6 +540,17 @@ impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
         let proof = buffer
             .read_compressed_proof_with_public_inputs(common_data)
             .map_err(anyhow::Error::msg)?;
+        ensure!(
+            buffer.unread_bytes().is_empty(),
+            "{} trailing bytes after the proof",
+            buffer.unread_bytes().len()
+        );
+        ensure!(
+            proof.public_inputs.len() == common_data.num_public_inputs,
+            "Expected {} public inputs, got {}",
+            common_data.num_public_inputs,
+            proof.public_inputs.len()
+        );
         Ok(proof)
     }
 }
@@ -296,6 +595,31 @@ pub struct ProofWithPublicInputsTarget<const D: usize> {
     pub public_inputs: Vec<Target>,
 }
 
+/// The public inputs of an inner proof whose circuit commits to them in chunks, as seen by a
+/// recursive verifier which only inspects some of the chunks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PublicInputChunksTarget {
+    pub chunks: Vec<PublicInputChunkTarget>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PublicInputChunkTarget {
+    /// The public inputs of a chunk which the verifier inspects, and hashes.
+    Inputs(Vec<Target>),
+    /// The digest of a chunk which the verifier doesn't inspect.
+    Digest(HashOutTarget),
+}
+
+impl PublicInputChunksTarget {
+    /// The public inputs of the given chunk, or `None` if the verifier doesn't inspect it.
+    pub fn inputs(&self, chunk: usize) -> Option<&[Target]> {
+        match &self.chunks[chunk] {
+            PublicInputChunkTarget::Inputs(inputs) => Some(inputs),
+            PublicInputChunkTarget::Digest(_) => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
 /// The purported values of each polynomial at a single point.
 pub struct OpeningSet<F: RichField + Extendable<D>, const D: usize> {
@@ -308,6 +632,10 @@ pub struct OpeningSet<F: RichField + Extendable<D>, const D: usize> {
     pub quotient_polys: Vec<F::Extension>,
     pub lookup_zs: Vec<F::Extension>,
     pub lookup_zs_next: Vec<F::Extension>,
+    /// The values at each of the circuit's extra opening points, of all polynomials of the oracles
+    /// opened there.
+    #[serde(default)]
+    pub extra_openings: Vec<Vec<F::Extension>>,
 }
 
 impl<F: RichField + Extendable<D>, const D: usize> OpeningSet<F, D> {
@@ -335,6 +663,25 @@ impl<F: RichField + Extendable<D>, const D: usize> OpeningSet<F, D> {
             eval_commitment(g * zeta, zs_partial_products_lookup_commitment);
         let quotient_polys = eval_commitment(zeta, quotient_polys_commitment);
 
+        let commitments = [
+            constants_sigmas_commitment,
+            wires_commitment,
+            zs_partial_products_lookup_commitment,
+            quotient_polys_commitment,
+        ];
+        let extra_openings = common_data
+            .extra_opening_points
+            .iter()
+            .map(|opening_point| {
+                let point = opening_point.shift_factor(g) * zeta;
+                opening_point
+                    .oracles
+                    .iter()
+                    .flat_map(|&oracle| eval_commitment(point, commitments[oracle]))
+                    .collect()
+            })
+            .collect();
+
         Self {
             constants: constants_sigmas_eval[common_data.constants_range()].to_vec(),
             plonk_sigmas: constants_sigmas_eval[common_data.sigmas_range()].to_vec(),
@@ -347,6 +694,7 @@ impl<F: RichField + Extendable<D>, const D: usize> OpeningSet<F, D> {
             lookup_zs: zs_partial_products_lookup_eval[common_data.lookup_range()].to_vec(),
             lookup_zs_next: zs_partial_products_lookup_next_eval[common_data.lookup_range()]
                 .to_vec(),
+            extra_openings,
         }
     }
     pub(crate) fn to_fri_openings(&self) -> FriOpenings<F, D> {
@@ -386,8 +734,14 @@ impl<F: RichField + Extendable<D>, const D: usize> OpeningSet<F, D> {
                 values: self.plonk_zs_next.clone(),
             }
         };
+        let extra_batches = self.extra_openings.iter().map(|values| FriOpeningBatch {
+            values: values.clone(),
+        });
         FriOpenings {
-            batches: vec![zeta_batch, zeta_next_batch],
+            batches: [zeta_batch, zeta_next_batch]
+                .into_iter()
+                .chain(extra_batches)
+                .collect(),
         }
     }
 }
@@ -404,6 +758,7 @@ pub struct OpeningSetTarget<const D: usize> {
     pub next_lookup_zs: Vec<ExtensionTarget<D>>,
     pub partial_products: Vec<ExtensionTarget<D>>,
     pub quotient_polys: Vec<ExtensionTarget<D>>,
+    pub extra_openings: Vec<Vec<ExtensionTarget<D>>>,
 }
 
 impl<const D: usize> OpeningSetTarget<D> {
@@ -444,8 +799,17 @@ impl<const D: usize> OpeningSetTarget<D> {
                 values: self.plonk_zs_next.clone(),
             }
         };
+        let extra_batches = self
+            .extra_openings
+            .iter()
+            .map(|values| FriOpeningBatchTarget {
+                values: values.clone(),
+            });
         FriOpeningsTarget {
-            batches: vec![zeta_batch, zeta_next_batch],
+            batches: [zeta_batch, zeta_next_batch]
+                .into_iter()
+                .chain(extra_batches)
+                .collect(),
         }
     }
 }
@@ -453,23 +817,176 @@ impl<const D: usize> OpeningSetTarget<D> {
 #[cfg(test)]
 mod tests {
     #[cfg(not(feature = "std"))]
-    use alloc::sync::Arc;
+    use alloc::{string::String, sync::Arc};
     #[cfg(feature = "std")]
     use std::sync::Arc;
 
     use anyhow::Result;
     use itertools::Itertools;
-    use plonky2_field::types::Sample;
+    use plonky2_field::types::{Field, Sample};
 
     use super::*;
     use crate::fri::reduction_strategies::FriReductionStrategy;
+    use crate::gates::gate::Gate;
     use crate::gates::lookup_table::LookupTable;
     use crate::gates::noop::NoopGate;
-    use crate::iop::witness::PartialWitness;
+    use crate::gates::util::StridedConstraintConsumer;
+    use crate::iop::generator::WitnessGeneratorRef;
+    use crate::iop::target::Target;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
     use crate::plonk::circuit_builder::CircuitBuilder;
-    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::circuit_data::{
+        CircuitConfig, OpeningPoint, OracleCapHeights, VerifierCircuitData,
+    };
     use crate::plonk::config::PoseidonGoldilocksConfig;
-    use crate::plonk::verifier::verify;
+    use crate::plonk::constraint_checker::{find_constraint_violations, ConstraintViolation};
+    use crate::plonk::plonk_common::PlonkOracle;
+    use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBase};
+    use crate::plonk::verifier::{verify, VerificationError};
+    use crate::util::serialization::{Buffer, DefaultGateSerializer, IoResult};
+
+    /// A gate whose constraint, enabled by its constant, requires its first wire to equal the
+    /// second wire of the previous row, which it reads from the values opened at `g^{-1} * zeta`.
+    #[derive(Debug)]
+    struct PreviousRowGate;
+
+    impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for PreviousRowGate {
+        fn id(&self) -> String {
+            "PreviousRowGate".into()
+        }
+
+        fn serialize(
+            &self,
+            _dst: &mut Vec<u8>,
+            _common_data: &CommonCircuitData<F, D>,
+        ) -> IoResult<()> {
+            Ok(())
+        }
+
+        fn deserialize(
+            _src: &mut Buffer,
+            _common_data: &CommonCircuitData<F, D>,
+        ) -> IoResult<Self> {
+            Ok(Self)
+        }
+
+        fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+            vec![vars.local_constants[0] * (vars.local_wires[0] - vars.extra_openings[0][1])]
+        }
+
+        fn eval_unfiltered_base_one(
+            &self,
+            vars: EvaluationVarsBase<F>,
+            mut yield_constr: StridedConstraintConsumer<F>,
+        ) {
+            yield_constr
+                .one(vars.local_constants[0] * (vars.local_wires[0] - vars.extra_openings(0)[1]));
+        }
+
+        fn eval_unfiltered_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: EvaluationTargets<D>,
+        ) -> Vec<ExtensionTarget<D>> {
+            let diff = builder.sub_extension(vars.local_wires[0], vars.extra_openings[0][1]);
+            vec![builder.mul_extension(vars.local_constants[0], diff)]
+        }
+
+        fn generators(
+            &self,
+            _row: usize,
+            _local_constants: &[F],
+        ) -> Vec<WitnessGeneratorRef<F, D>> {
+            Vec::new()
+        }
+
+        fn num_wires(&self) -> usize {
+            2
+        }
+
+        fn num_constants(&self) -> usize {
+            1
+        }
+
+        fn degree(&self) -> usize {
+            2
+        }
+
+        fn num_constraints(&self) -> usize {
+            1
+        }
+    }
+
+    /// A gate requiring the first `Z` to be one at the first extra opening point, which holds when
+    /// that point leads to the first row, where every `Z` starts at one.
+    #[derive(Debug)]
+    struct FirstZGate;
+
+    impl<F: RichField + Extendable<D>, const D: usize> Gate<F, D> for FirstZGate {
+        fn id(&self) -> String {
+            "FirstZGate".into()
+        }
+
+        fn serialize(
+            &self,
+            _dst: &mut Vec<u8>,
+            _common_data: &CommonCircuitData<F, D>,
+        ) -> IoResult<()> {
+            Ok(())
+        }
+
+        fn deserialize(
+            _src: &mut Buffer,
+            _common_data: &CommonCircuitData<F, D>,
+        ) -> IoResult<Self> {
+            Ok(Self)
+        }
+
+        fn eval_unfiltered(&self, vars: EvaluationVars<F, D>) -> Vec<F::Extension> {
+            vec![vars.extra_openings[0][0] - F::Extension::ONE]
+        }
+
+        fn eval_unfiltered_base_one(
+            &self,
+            vars: EvaluationVarsBase<F>,
+            mut yield_constr: StridedConstraintConsumer<F>,
+        ) {
+            yield_constr.one(vars.extra_openings(0)[0] - F::ONE);
+        }
+
+        fn eval_unfiltered_circuit(
+            &self,
+            builder: &mut CircuitBuilder<F, D>,
+            vars: EvaluationTargets<D>,
+        ) -> Vec<ExtensionTarget<D>> {
+            let one = builder.one_extension();
+            vec![builder.sub_extension(vars.extra_openings[0][0], one)]
+        }
+
+        fn generators(
+            &self,
+            _row: usize,
+            _local_constants: &[F],
+        ) -> Vec<WitnessGeneratorRef<F, D>> {
+            Vec::new()
+        }
+
+        fn num_wires(&self) -> usize {
+            0
+        }
+
+        fn num_constants(&self) -> usize {
+            0
+        }
+
+        fn degree(&self) -> usize {
+            1
+        }
+
+        fn num_constraints(&self) -> usize {
+            1
+        }
+    }
 
     #[test]
     fn test_proof_compression() -> Result<()> {
@@ -513,7 +1030,6 @@ mod tests {
     fn test_proof_compression_lookup() -> Result<()> {
         const D: usize = 2;
         type C = PoseidonGoldilocksConfig;
-        use plonky2_field::types::Field;
         type F = <C as GenericConfig<D>>::F;
 
         let mut config = CircuitConfig::standard_recursion_config();
@@ -565,4 +1081,445 @@ mod tests {
         verify(proof, &data.verifier_only, &data.common)?;
         data.verify_compressed(compressed_proof)
     }
+
+    #[test]
+    fn test_proof_serialization() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        for (cap_height, num_query_rounds) in [(0, 28), (2, 35), (4, 50)] {
+            let mut config = CircuitConfig::standard_recursion_config();
+            config.fri_config.cap_height = cap_height;
+            config.fri_config.num_query_rounds = num_query_rounds;
+
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_target();
+            let y = builder.square(x);
+            builder.register_public_input(y);
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::rand());
+            let proof = data.prove(pw)?;
+
+            let proof_bytes = proof.proof.to_bytes();
+            assert_eq!(
+                Proof::<F, C, D>::from_bytes(&proof_bytes, &data.common)?,
+                proof.proof
+            );
+
+            let bytes = proof.to_bytes();
+            let proof_from_bytes = ProofWithPublicInputs::from_bytes(bytes.clone(), &data.common)?;
+            assert_eq!(proof_from_bytes, proof);
+            data.verify(proof_from_bytes)?;
+
+            // Truncated or oversized inputs are rejected.
+            for len in [0, 1, bytes.len() / 2, bytes.len() - 1] {
+                assert!(ProofWithPublicInputs::<F, C, D>::from_bytes(
+                    bytes[..len].to_vec(),
+                    &data.common
+                )
+                .is_err());
+            }
+            let mut oversized = bytes.clone();
+            oversized.push(0);
+            assert!(ProofWithPublicInputs::<F, C, D>::from_bytes(oversized, &data.common).is_err());
+            let mut oversized = proof_bytes;
+            oversized.extend([0; 8]);
+            assert!(Proof::<F, C, D>::from_bytes(&oversized, &data.common).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_proof_size_estimate() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        // The estimate should be exact, but is only required to be close.
+        const SLACK: usize = 16;
+
+        let tall_caps = OracleCapHeights {
+            constants_sigmas: Some(5),
+            wires: Some(3),
+            zs_partial_products: Some(1),
+            quotient: None,
+        };
+        for (cap_height, zero_knowledge, oracle_cap_heights) in [
+            (0, false, OracleCapHeights::uniform()),
+            (4, false, OracleCapHeights::uniform()),
+            (4, true, OracleCapHeights::uniform()),
+            (4, false, tall_caps),
+        ] {
+            let mut config = CircuitConfig::standard_recursion_config();
+            config.fri_config.cap_height = cap_height;
+            config.zero_knowledge = zero_knowledge;
+            config.oracle_cap_heights = oracle_cap_heights;
+
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_public_input();
+            let y = builder.square(x);
+            builder.register_public_input(y);
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::rand());
+            let proof = data.prove_and_verify(pw)?;
+
+            let size = ProofWithPublicInputs::<F, C, D>::size_estimate(&data.common);
+            let actual = proof.to_bytes().len();
+            assert!(
+                size.total_bytes.abs_diff(actual) <= SLACK,
+                "estimated {} bytes, serialized {actual}",
+                size.total_bytes
+            );
+            assert_eq!(
+                size.pow + size.public_inputs,
+                8 + 8 + 8 * proof.public_inputs.len()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_proof_size_and_tampering() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let mut y = x;
+        for _ in 0..1000 {
+            y = builder.mul_add(y, y, x);
+        }
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        let proof = data.prove(pw)?;
+        let compressed_proof = data.compress(proof.clone())?;
+        assert_eq!(data.decompress(compressed_proof.clone())?, proof);
+
+        let bytes = proof.to_bytes();
+        let compressed_bytes = compressed_proof.to_bytes();
+        // Small circuits share many Merkle path nodes between queries, saving about 10%.
+        assert!(compressed_bytes.len() * 20 < bytes.len() * 19);
+
+        let verifier_data = data.verifier_data();
+        verifier_data.verify_compressed(CompressedProofWithPublicInputs::from_bytes(
+            compressed_bytes.clone(),
+            &data.common,
+        )?)?;
+
+        // Tamper with the wires cap, which comes first, and with the public input, which comes
+        // last.
+        for i in [0, 9, compressed_bytes.len() - 8] {
+            let mut tampered = compressed_bytes.clone();
+            tampered[i] ^= 1;
+            let result =
+                CompressedProofWithPublicInputs::<F, C, D>::from_bytes(tampered, &data.common)
+                    .and_then(|p| verifier_data.verify_compressed(p));
+            assert!(result.is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_round_trip() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_target();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        let proof = data.prove(pw)?;
+
+        let json = serde_json::to_string(&proof)?;
+        let proof_from_json: ProofWithPublicInputs<F, C, D> = serde_json::from_str(&json)?;
+        assert_eq!(proof_from_json, proof);
+        let bin = bincode::serialize(&proof)?;
+        let proof_from_bin: ProofWithPublicInputs<F, C, D> = bincode::deserialize(&bin)?;
+        assert_eq!(proof_from_bin, proof);
+
+        let compressed = proof
+            .clone()
+            .compress(&data.verifier_only.circuit_digest, &data.common)?;
+        let bin = bincode::serialize(&compressed)?;
+        let compressed_from_bin: CompressedProofWithPublicInputs<F, C, D> =
+            bincode::deserialize(&bin)?;
+        assert_eq!(compressed_from_bin, compressed);
+
+        let json = serde_json::to_string(&config)?;
+        assert_eq!(serde_json::from_str::<CircuitConfig>(&json)?, config);
+        let bin = bincode::serialize(&data.common.fri_params)?;
+        assert_eq!(
+            bincode::deserialize::<FriParams>(&bin)?,
+            data.common.fri_params
+        );
+
+        let json = serde_json::to_string(&data.verifier_only)?;
+        let verifier_only: VerifierOnlyCircuitData<C, D> = serde_json::from_str(&json)?;
+        assert_eq!(verifier_only, data.verifier_only);
+        let bin = bincode::serialize(&data.verifier_only)?;
+        let verifier_only: VerifierOnlyCircuitData<C, D> = bincode::deserialize(&bin)?;
+        assert_eq!(verifier_only, data.verifier_only);
+
+        // A proof that went through serde verifies against deserialized verifier data.
+        let verifier_data = VerifierCircuitData {
+            verifier_only,
+            common: data.common.clone(),
+        };
+        verifier_data.verify(proof_from_json)?;
+        verifier_data.verify(proof_from_bin)
+    }
+
+    #[test]
+    fn test_public_inputs_are_bound() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let a = builder.add_virtual_target();
+        let b = builder.add_virtual_target();
+        let sum = builder.add(a, b);
+        builder.register_named_public_inputs("operands", &[a, b]);
+        builder.register_named_public_inputs("sum", &[sum]);
+        let data = builder.build::<C>();
+
+        // The names are part of the verifier data, so a verifier which only has the serialized
+        // verifier data can read the public inputs by name.
+        let bytes = data
+            .verifier_data()
+            .to_bytes(&DefaultGateSerializer)
+            .unwrap();
+        let verifier_data =
+            VerifierCircuitData::<F, C, D>::from_bytes(bytes, &DefaultGateSerializer).unwrap();
+        let common = &verifier_data.common;
+        assert_eq!(common.public_inputs_range("sum"), Some(2..3));
+
+        let (x, y) = (F::rand(), F::rand());
+        let mut pw = PartialWitness::new();
+        pw.set_target(a, x);
+        pw.set_target(b, y);
+        let proof = data.prove(pw)?;
+        assert_eq!(
+            proof.public_inputs_range(common, "operands"),
+            Some(&[x, y][..])
+        );
+        assert_eq!(proof.public_inputs_range(common, "sum"), Some(&[x + y][..]));
+        assert_eq!(proof.public_inputs_range(common, "product"), None);
+        verifier_data.verify(proof.clone())?;
+
+        // Public inputs changed after proving no longer match the proof.
+        let mut swapped = proof.clone();
+        swapped.public_inputs.swap(0, 1);
+        let err = data.verify(swapped).unwrap_err();
+        assert!(err.downcast_ref::<VerificationError>().is_some());
+        let mut tampered = proof;
+        tampered.public_inputs[2] += F::ONE;
+        let err = data.verify(tampered).unwrap_err();
+        assert!(err.downcast_ref::<VerificationError>().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_opening_point() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let num_wires = config.num_wires;
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_public_input();
+        let y = builder.exp_u64(x, 5);
+        builder.register_public_input(y);
+        builder.add_opening_point(-1, &[PlonkOracle::WIRES]);
+        builder.add_opening_point(-1, &[PlonkOracle::CONSTANTS_SIGMAS, PlonkOracle::WIRES]);
+        let data = builder.build::<C>();
+        assert_eq!(
+            data.common.extra_opening_points,
+            [OpeningPoint {
+                shift: -1,
+                oracles: vec![
+                    PlonkOracle::CONSTANTS_SIGMAS.index,
+                    PlonkOracle::WIRES.index
+                ],
+            }]
+        );
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        let proof = data.prove(pw)?;
+        let extra_openings = &proof.proof.openings.extra_openings;
+        assert_eq!(extra_openings.len(), 1);
+        assert_eq!(
+            extra_openings[0].len(),
+            data.common.num_preprocessed_polys() + num_wires
+        );
+        data.verify(proof.clone())?;
+
+        let proof_from_bytes = ProofWithPublicInputs::from_bytes(proof.to_bytes(), &data.common)?;
+        assert_eq!(proof_from_bytes, proof);
+        let compressed_proof = data.compress(proof.clone())?;
+        assert_eq!(data.decompress(compressed_proof.clone())?, proof);
+        data.verify_compressed(compressed_proof)?;
+
+        // The values at the extra opening point are checked by FRI.
+        let mut tampered = proof.clone();
+        tampered.proof.openings.extra_openings[0][0] += <F as Extendable<D>>::Extension::ONE;
+        assert!(data.verify(tampered).is_err());
+        let mut missing = proof;
+        missing.proof.openings.extra_openings.clear();
+        assert!(data.verify(missing).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gates_read_extra_openings() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let first_row = builder.add_gate(PreviousRowGate, vec![F::ZERO]);
+        let second_row = builder.add_gate(PreviousRowGate, vec![F::ONE]);
+        assert_eq!(second_row, first_row + 1);
+        builder.add_opening_point(-1, &[PlonkOracle::WIRES]);
+        let data = builder.build::<C>();
+
+        let witness = |first_wire_of_second_row: F| {
+            let mut pw = PartialWitness::new();
+            pw.set_target(Target::wire(first_row, 0), F::from_canonical_u32(3));
+            pw.set_target(Target::wire(first_row, 1), F::from_canonical_u32(5));
+            pw.set_target(Target::wire(second_row, 0), first_wire_of_second_row);
+            pw.set_target(Target::wire(second_row, 1), F::from_canonical_u32(7));
+            pw
+        };
+
+        let good_witness = witness(F::from_canonical_u32(5));
+        assert!(find_constraint_violations(
+            &data.prover_only,
+            &data.common,
+            good_witness.clone(),
+            true
+        )
+        .is_empty());
+        let proof = data.prove(good_witness)?;
+        data.verify(proof.clone())?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&data.common);
+        let verifier_target = builder.constant_verifier_data(&data.verifier_only);
+        builder.verify_proof::<C>(&proof_target, &verifier_target, &data.common);
+        let outer_data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&proof_target, &proof);
+        outer_data.verify(outer_data.prove(pw)?)?;
+
+        let bad_witness = witness(F::from_canonical_u32(3));
+        assert_eq!(
+            find_constraint_violations(&data.prover_only, &data.common, bad_witness.clone(), true),
+            [ConstraintViolation::Gate {
+                row: second_row,
+                gate: "PreviousRowGate".into(),
+                constraint: 0,
+            }]
+        );
+        assert!(data
+            .prove(bad_witness)
+            .and_then(|proof| data.verify(proof))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gates_read_extra_zs() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let y = builder.mul(x, x);
+        builder.register_public_input(y);
+        let row = builder.add_gate(FirstZGate, vec![]);
+        assert_ne!(row, 0);
+        builder.add_opening_point(-(row as isize), &[PlonkOracle::ZS_PARTIAL_PRODUCTS]);
+        let data = builder.build::<C>();
+
+        // The constraint checker must see the same `Z`s as the prover, rather than zeros.
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        assert!(
+            find_constraint_violations(&data.prover_only, &data.common, pw.clone(), true)
+                .is_empty()
+        );
+        data.verify(data.prove(pw)?)
+    }
+
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn test_two_point_proofs_are_unchanged() -> Result<()> {
+        use crate::hash::poseidon::PoseidonHash;
+        use crate::plonk::config::Hasher;
+        use crate::plonk::prover::ProverOptions;
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let y = builder.exp_u64(x, 5);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let options = ProverOptions {
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(3));
+        let proof = data.prove_with_options(pw, &options)?;
+
+        // Pinned to proofs generated before extra opening points were supported, re-pinned once
+        // unrouted wires of the public input row stopped being randomized. The header holding the
+        // circuit digest hint is skipped.
+        let bytes = proof.to_bytes()[1..]
+            .iter()
+            .map(|&b| F::from_canonical_u8(b))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            PoseidonHash::hash_no_pad(&bytes).elements,
+            [
+                3212213674612193064,
+                1534181354751408840,
+                7418977691016750325,
+                4855985557913531342,
+            ]
+            .map(F::from_canonical_u64)
+        );
+        data.verify(proof)
+    }
 }
@@ -15,20 +15,21 @@ use crate::field::polynomial::{PolynomialCoeffs, PolynomialValues};
 use crate::field::types::Field;
 use crate::field::zero_poly_coset::ZeroPolyOnCoset;
 use crate::fri::oracle::PolynomialBatch;
+use crate::fri::prover::FriProverOptions;
 use crate::gates::lookup::LookupGate;
 use crate::gates::lookup_table::LookupTableGate;
-use crate::gates::selectors::LookupSelectors;
 use crate::hash::hash_types::RichField;
 use crate::iop::challenger::Challenger;
-use crate::iop::generator::generate_partial_witness;
+use crate::iop::generator::generate_partial_witness_with_options;
 use crate::iop::target::Target;
 use crate::iop::witness::{MatrixWitness, PartialWitness, PartitionWitness, Witness, WitnessWrite};
 use crate::plonk::circuit_builder::NUM_COINS_LOOKUP;
 use crate::plonk::circuit_data::{CommonCircuitData, ProverOnlyCircuitData};
 use crate::plonk::config::{GenericConfig, Hasher};
 use crate::plonk::plonk_common::PlonkOracle;
+use crate::plonk::progress::{PhaseProgress, ProgressCallback, ProverPhase};
 use crate::plonk::proof::{OpeningSet, Proof, ProofWithPublicInputs};
-use crate::plonk::vanishing_poly::{eval_vanishing_poly_base_batch, get_lut_poly};
+use crate::plonk::vanishing_poly::{eval_lut_polys, eval_vanishing_poly_base_batch};
 use crate::plonk::vars::EvaluationVarsBaseBatch;
 use crate::timed;
 use crate::util::partial_products::{partial_products_and_z_gx, quotient_chunk_products};
@@ -71,11 +72,14 @@ pub fn set_lookup_wires<
 
         for (inp_target, _) in prover_data.lut_to_lookups[lut_index].iter() {
             let inp_value = pw.get_target(*inp_target);
-            let idx = table_value_to_idx
-                .get(&u16::try_from(inp_value.to_canonical_u64()).unwrap())
-                .unwrap();
-
-            multiplicities[*idx] += 1;
+            // An input missing from the table can't be counted, which leaves the lookup argument
+            // unsatisfied, so the proof fails to verify.
+            let idx = u16::try_from(inp_value.to_canonical_u64())
+                .ok()
+                .and_then(|inp| table_value_to_idx.get(&inp));
+            if let Some(&idx) = idx {
+                multiplicities[idx] += 1;
+            }
         }
 
         // Pad the last `LookupGate` with the first entry from the LUT.
@@ -109,55 +113,194 @@ pub fn set_lookup_wires<
     }
 }
 
+/// Options controlling proof generation.
+#[derive(Clone, Debug, Default)]
+pub struct ProverOptions {
+    /// If set, every `RandomValueGenerator` (including the blinding rows added when the circuit
+    /// was built) derives its value from this seed instead of OS randomness, so that proving the
+    /// same witness twice yields identical proofs.
+    ///
+    /// This is meant for tests and debugging only. Anyone knowing the seed can recompute the
+    /// blinding values, so a proof generated with a fixed seed is not zero-knowledge. Note that
+    /// the salts of hiding Merkle trees are still sampled from OS randomness.
+    #[cfg(feature = "rand_chacha")]
+    pub rng_seed: Option<u64>,
+    /// If set, witness generation reports the time spent in each type of generator to the timing
+    /// tree. This adds a small overhead to every generator run.
+    #[cfg(feature = "timing")]
+    pub time_generators: bool,
+    /// If set, the quotient polynomials are evaluated on this many points of the LDE domain at a
+    /// time, and each chunk's values are stored before the next chunk is evaluated. The LDEs of
+    /// the wires, Z's and quotient polynomials are likewise committed to one coset of this many
+    /// points at a time, though of no fewer points than the circuit's degree. Smaller chunks lower
+    /// the prover's peak memory, at some cost in parallelism. The proof is the same either way.
+    pub quotient_chunk_size: Option<usize>,
+    /// If set, the FRI proof-of-work witness is searched for on a dedicated pool of this many
+    /// threads rather than on the current rayon pool. The witness found, and hence the proof, is
+    /// the same either way.
+    pub pow_threads: Option<usize>,
+    /// If set, witness generation performs each division as soon as its generator runs, rather
+    /// than deferring the divisions of each pass over the generators to share one batched field
+    /// inversion. The witness is the same either way, so this is only useful for comparisons.
+    pub eager_inversions: bool,
+    /// Called with the progress of each phase of proving.
+    pub progress: ProgressCallback,
+}
+
 pub fn prove<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     prover_data: &ProverOnlyCircuitData<F, C, D>,
     common_data: &CommonCircuitData<F, D>,
     inputs: PartialWitness<F>,
     timing: &mut TimingTree,
 ) -> Result<ProofWithPublicInputs<F, C, D>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    prove_with_options(
+        prover_data,
+        common_data,
+        inputs,
+        &ProverOptions::default(),
+        timing,
+    )
+}
+
+pub fn prove_with_options<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    inputs: PartialWitness<F>,
+    options: &ProverOptions,
+    timing: &mut TimingTree,
+) -> Result<ProofWithPublicInputs<F, C, D>>
 where
     C::Hasher: Hasher<F>,
     C::InnerHasher: Hasher<F>,
 {
     let partition_witness = timed!(
         timing,
-        &format!("run {} generators", prover_data.generators.len()),
-        generate_partial_witness(inputs, prover_data, common_data)
+        &format!("run {} generators", prover_data.num_generators()),
+        generate_partial_witness_with_options(inputs, prover_data, common_data, options, timing)?
     );
 
-    prove_with_partition_witness(prover_data, common_data, partition_witness, timing)
+    prove_partition_witness(prover_data, common_data, partition_witness, options, timing)
 }
 
+/// Proves a witness on which generation has already run, skipping witness generation.
 pub fn prove_with_partition_witness<
     F: RichField + Extendable<D>,
     C: GenericConfig<D, F = F>,
     const D: usize,
+>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    partition_witness: PartitionWitness<F>,
+    timing: &mut TimingTree,
+) -> Result<ProofWithPublicInputs<F, C, D>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    prove_partition_witness(
+        prover_data,
+        common_data,
+        partition_witness,
+        &ProverOptions::default(),
+        timing,
+    )
+}
+
+/// Proves each of `witnesses` for the same circuit, using up to `parallelism` worker threads (or
+/// rayon's default number if it is zero). The workers share `prover_data`, including its
+/// precomputed commitments and FFT roots, rather than each holding a copy.
+///
+/// Each witness gets its own result, so a witness that fails to generate or prove does not affect
+/// the others; with the `std` feature, this also holds for a witness whose generators panic.
+/// `options` applies to every proof, so a fixed `rng_seed` makes them all share the same
+/// randomness.
+pub fn prove_batch<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    witnesses: Vec<PartialWitness<F>>,
+    parallelism: usize,
+    options: &ProverOptions,
+) -> Vec<Result<ProofWithPublicInputs<F, C, D>>>
+where
+    C::Hasher: Hasher<F>,
+    C::InnerHasher: Hasher<F>,
+{
+    let prove_one = |inputs| {
+        let prove = || {
+            prove_with_options(
+                prover_data,
+                common_data,
+                inputs,
+                options,
+                &mut TimingTree::default(),
+            )
+        };
+        #[cfg(feature = "std")]
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(prove))
+            .unwrap_or_else(|_| Err(anyhow::anyhow!("Proving panicked")));
+        #[cfg(not(feature = "std"))]
+        let result = prove();
+        result
+    };
+
+    #[cfg(feature = "parallel")]
+    {
+        let pool = plonky2_maybe_rayon::rayon::ThreadPoolBuilder::new()
+            .num_threads(parallelism)
+            .build()
+            .expect("Failed to build the prover thread pool");
+        pool.install(|| witnesses.into_par_iter().map(prove_one).collect())
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    {
+        let _ = parallelism;
+        witnesses.into_iter().map(prove_one).collect()
+    }
+}
+
+fn prove_partition_witness<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
 >(
     prover_data: &ProverOnlyCircuitData<F, C, D>,
     common_data: &CommonCircuitData<F, D>,
     mut partition_witness: PartitionWitness<F>,
+    options: &ProverOptions,
     timing: &mut TimingTree,
 ) -> Result<ProofWithPublicInputs<F, C, D>>
 where
     C::Hasher: Hasher<F>,
     C::InnerHasher: Hasher<F>,
 {
-    let has_lookup = !common_data.luts.is_empty();
     let config = &common_data.config;
-    let num_challenges = config.num_challenges;
+    let num_challenges = common_data.num_challenges();
     let quotient_degree = common_data.quotient_degree();
     let degree = common_data.degree();
 
     set_lookup_wires(prover_data, common_data, &mut partition_witness);
 
     let public_inputs = partition_witness.get_targets(&prover_data.public_inputs);
-    let public_inputs_hash = C::InnerHasher::hash_no_pad(&public_inputs);
+    let public_inputs_hash = common_data
+        .public_input_commitment
+        .hash::<F, C::InnerHasher>(&public_inputs);
 
+    let progress = PhaseProgress::start(&options.progress, ProverPhase::WireCommitment);
     let witness = timed!(
         timing,
         "compute full witness",
         partition_witness.full_witness()
     );
+    progress.update(1, 3);
 
     let wires_values: Vec<PolynomialValues<F>> = timed!(
         timing,
@@ -168,19 +311,30 @@ where
             .map(|column| PolynomialValues::new(column.clone()))
             .collect()
     );
+    progress.update(2, 3);
+
+    // Columns no gate uses are zero, unless wires outside of gates were connected to something.
+    let num_used_wires = common_data.num_used_wires();
+    let num_nonzero_wires = (num_used_wires..wires_values.len())
+        .rev()
+        .find(|&i| wires_values[i].values.iter().any(|x| !x.is_zero()))
+        .map_or(num_used_wires, |i| i + 1);
 
     let wires_commitment = timed!(
         timing,
         "compute wires commitment",
-        PolynomialBatch::<F, C, D>::from_values(
+        PolynomialBatch::<F, C, D>::from_values_chunked(
             wires_values,
+            num_nonzero_wires,
             config.fri_config.rate_bits,
             config.zero_knowledge && PlonkOracle::WIRES.blinding,
-            config.fri_config.cap_height,
+            config.cap_height(PlonkOracle::WIRES),
+            options.quotient_chunk_size,
             timing,
-            prover_data.fft_root_table.as_ref(),
+            prover_data.fft_root_table.as_deref(),
         )
     );
+    progress.finish();
 
     let mut challenger = Challenger::<F, C::Hasher>::new();
 
@@ -190,69 +344,44 @@ where
 
     challenger.observe_cap::<C::Hasher>(&wires_commitment.merkle_tree.cap);
 
-    // We need 4 values per challenge: 2 for the combos, 1 for (X-combo) in the accumulators and 1 to prove that the lookup table was computed correctly.
-    // We can reuse betas and gammas for two of them.
-    let num_lookup_challenges = NUM_COINS_LOOKUP * num_challenges;
-
-    let betas = challenger.get_n_challenges(num_challenges);
-    let gammas = challenger.get_n_challenges(num_challenges);
-
-    let deltas = if has_lookup {
-        let mut delts = Vec::with_capacity(2 * num_challenges);
-        let num_additional_challenges = num_lookup_challenges - 2 * num_challenges;
-        let additional = challenger.get_n_challenges(num_additional_challenges);
-        delts.extend(&betas);
-        delts.extend(&gammas);
-        delts.extend(additional);
-        delts
-    } else {
-        vec![]
-    };
-
-    assert!(
-        common_data.quotient_degree_factor < common_data.config.num_routed_wires,
-        "When the number of routed wires is smaller that the degree, we should change the logic to avoid computing partial products."
-    );
-    let mut partial_products_and_zs = timed!(
+    let (betas, gammas, deltas) =
+        get_permutation_and_lookup_challenges::<F, C, D>(&mut challenger, common_data);
+
+    let progress = PhaseProgress::start(&options.progress, ProverPhase::PartialProducts);
+    let zs_partial_products_lookups = compute_zs_partial_products_and_lookup_polys(
+        &witness,
+        &betas,
+        &gammas,
+        &deltas,
+        prover_data,
+        common_data,
+        &progress,
         timing,
-        "compute partial products",
-        all_wires_permutation_partial_products(&witness, &betas, &gammas, prover_data, common_data)
     );
-
-    // Z is expected at the front of our batch; see `zs_range` and `partial_products_range`.
-    let plonk_z_vecs = partial_products_and_zs
-        .iter_mut()
-        .map(|partial_products_and_z| partial_products_and_z.pop().unwrap())
-        .collect();
-    let zs_partial_products = [plonk_z_vecs, partial_products_and_zs.concat()].concat();
-
-    // All lookup polys: RE and partial SLDCs.
-    let lookup_polys =
-        compute_all_lookup_polys(&witness, &deltas, prover_data, common_data, has_lookup);
-
-    let zs_partial_products_lookups = if has_lookup {
-        [zs_partial_products, lookup_polys].concat()
-    } else {
-        zs_partial_products
-    };
+    let num_zs_partial_products_lookups = zs_partial_products_lookups.len();
 
     let partial_products_zs_and_lookup_commitment = timed!(
         timing,
         "commit to partial products, Z's and, if any, lookup polynomials",
-        PolynomialBatch::from_values(
+        PolynomialBatch::from_values_chunked(
             zs_partial_products_lookups,
+            num_zs_partial_products_lookups,
             config.fri_config.rate_bits,
             config.zero_knowledge && PlonkOracle::ZS_PARTIAL_PRODUCTS.blinding,
-            config.fri_config.cap_height,
+            config.cap_height(PlonkOracle::ZS_PARTIAL_PRODUCTS),
+            options.quotient_chunk_size,
             timing,
-            prover_data.fft_root_table.as_ref(),
+            prover_data.fft_root_table.as_deref(),
         )
     );
 
+    progress.finish();
+
     challenger.observe_cap::<C::Hasher>(&partial_products_zs_and_lookup_commitment.merkle_tree.cap);
 
     let alphas = challenger.get_n_challenges(num_challenges);
 
+    let progress = PhaseProgress::start(&options.progress, ProverPhase::Quotient);
     let quotient_polys = timed!(
         timing,
         "compute quotient polys",
@@ -266,8 +395,10 @@ where
             &gammas,
             &deltas,
             &alphas,
+            options.quotient_chunk_size,
         )
     );
+    progress.update(1, 3);
 
     let all_quotient_poly_chunks: Vec<PolynomialCoeffs<F>> = timed!(
         timing,
@@ -283,20 +414,26 @@ where
             })
             .collect()
     );
+    progress.update(2, 3);
+    let num_quotient_poly_chunks = all_quotient_poly_chunks.len();
 
     let quotient_polys_commitment = timed!(
         timing,
         "commit to quotient polys",
-        PolynomialBatch::<F, C, D>::from_coeffs(
+        PolynomialBatch::<F, C, D>::from_coeffs_chunked(
             all_quotient_poly_chunks,
+            num_quotient_poly_chunks,
             config.fri_config.rate_bits,
             config.zero_knowledge && PlonkOracle::QUOTIENT.blinding,
-            config.fri_config.cap_height,
+            config.cap_height(PlonkOracle::QUOTIENT),
+            options.quotient_chunk_size,
             timing,
-            prover_data.fft_root_table.as_ref(),
+            prover_data.fft_root_table.as_deref(),
         )
     );
 
+    progress.finish();
+
     challenger.observe_cap::<C::Hasher>(&quotient_polys_commitment.merkle_tree.cap);
 
     let zeta = challenger.get_extension_challenge::<D>();
@@ -309,6 +446,7 @@ where
         "Opening point is in the subgroup."
     );
 
+    let progress = PhaseProgress::start(&options.progress, ProverPhase::Openings);
     let openings = timed!(
         timing,
         "construct the opening set, including lookups",
@@ -322,6 +460,7 @@ where
             common_data
         )
     );
+    progress.finish();
     challenger.observe_openings(&openings.to_fri_openings());
     let instance = common_data.get_fri_instance(zeta);
 
@@ -338,6 +477,10 @@ where
             ],
             &mut challenger,
             &common_data.fri_params,
+            &FriProverOptions {
+                pow_threads: options.pow_threads,
+                progress: options.progress.clone(),
+            },
             timing,
         )
     );
@@ -352,9 +495,94 @@ where
     Ok(ProofWithPublicInputs::<F, C, D> {
         proof,
         public_inputs,
+        circuit_digest_hint: None,
     })
 }
 
+/// Draws the permutation challenges `betas` and `gammas` and, if the circuit has lookups, the
+/// lookup challenges `deltas`, from a challenger which has observed the wires commitment.
+pub(crate) fn get_permutation_and_lookup_challenges<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    challenger: &mut Challenger<F, C::Hasher>,
+    common_data: &CommonCircuitData<F, D>,
+) -> (Vec<F>, Vec<F>, Vec<F>) {
+    let has_lookup = !common_data.luts.is_empty();
+    let num_challenges = common_data.num_challenges();
+
+    // We need 4 values per challenge: 2 for the combos, 1 for (X-combo) in the accumulators and 1 to prove that the lookup table was computed correctly.
+    // We can reuse betas and gammas for two of them.
+    let num_lookup_challenges = NUM_COINS_LOOKUP * num_challenges;
+
+    let betas = challenger.get_n_challenges(num_challenges);
+    let gammas = challenger.get_n_challenges(num_challenges);
+
+    let deltas = if has_lookup {
+        let mut delts = Vec::with_capacity(2 * num_challenges);
+        let num_additional_challenges = num_lookup_challenges - 2 * num_challenges;
+        let additional = challenger.get_n_challenges(num_additional_challenges);
+        delts.extend(&betas);
+        delts.extend(&gammas);
+        delts.extend(additional);
+        delts
+    } else {
+        vec![]
+    };
+    (betas, gammas, deltas)
+}
+
+/// Computes the values of the polynomials committed to in the `ZS_PARTIAL_PRODUCTS` oracle: the
+/// `Z`s, then the partial products, then the lookup polynomials, if any. `progress` is updated
+/// but not finished.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn compute_zs_partial_products_and_lookup_polys<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    witness: &MatrixWitness<F>,
+    betas: &[F],
+    gammas: &[F],
+    deltas: &[F],
+    prover_data: &ProverOnlyCircuitData<F, C, D>,
+    common_data: &CommonCircuitData<F, D>,
+    progress: &PhaseProgress,
+    timing: &mut TimingTree,
+) -> Vec<PolynomialValues<F>> {
+    let has_lookup = !common_data.luts.is_empty();
+
+    assert!(
+        common_data.quotient_degree_factor < common_data.config.num_routed_wires,
+        "When the number of routed wires is smaller that the degree, we should change the logic to avoid computing partial products."
+    );
+    let mut partial_products_and_zs = timed!(
+        timing,
+        "compute partial products",
+        all_wires_permutation_partial_products(witness, betas, gammas, prover_data, common_data)
+    );
+    progress.update(1, 3);
+
+    // Z is expected at the front of our batch; see `zs_range` and `partial_products_range`.
+    let plonk_z_vecs = partial_products_and_zs
+        .iter_mut()
+        .map(|partial_products_and_z| partial_products_and_z.pop().unwrap())
+        .collect();
+    let zs_partial_products = [plonk_z_vecs, partial_products_and_zs.concat()].concat();
+
+    // All lookup polys: RE and partial SLDCs.
+    let lookup_polys =
+        compute_all_lookup_polys(witness, deltas, prover_data, common_data, has_lookup);
+    progress.update(2, 3);
+
+    if has_lookup {
+        [zs_partial_products, lookup_polys].concat()
+    } else {
+        zs_partial_products
+    }
+}
+
 /// Compute the partial products used in the `Z` polynomials.
 fn all_wires_permutation_partial_products<
     F: RichField + Extendable<D>,
@@ -367,7 +595,7 @@ fn all_wires_permutation_partial_products<
     prover_data: &ProverOnlyCircuitData<F, C, D>,
     common_data: &CommonCircuitData<F, D>,
 ) -> Vec<Vec<PolynomialValues<F>>> {
-    (0..common_data.config.num_challenges)
+    (0..common_data.num_challenges())
         .map(|i| {
             wires_permutation_partial_products_and_zs(
                 witness,
@@ -446,7 +674,7 @@ fn wires_permutation_partial_products_and_zs<
 /// The polynomials hold the value of RE, Sum and Ldc of the Tip5 paper (<https://eprint.iacr.org/2023/107.pdf>). To reduce their
 /// numbers, we batch multiple slots in a single polynomial. Since RE only involves degree one constraints, we can batch
 /// all the slots of a row. For Sum and Ldc, batching increases the constraint degree, so we bound the number of
-/// partial polynomials according to `max_quotient_degree_factor`.
+/// partial polynomials according to `quotient_degree_factor`.
 /// As another optimization, Sum and LDC polynomials are shared (in so called partial SLDC polynomials), and the last value
 /// of the last partial polynomial is Sum(end) - LDC(end). If the lookup argument is valid, then it must be equal to 0.
 fn compute_lookup_polys<
@@ -461,7 +689,7 @@ fn compute_lookup_polys<
 ) -> Vec<PolynomialValues<F>> {
     let degree = common_data.degree();
     let num_lu_slots = LookupGate::num_slots(&common_data.config);
-    let max_lookup_degree = common_data.config.max_quotient_degree_factor - 1;
+    let max_lookup_degree = common_data.quotient_degree_factor - 1;
     let num_partial_lookups = ceil_div_usize(num_lu_slots, max_lookup_degree);
     let num_lut_slots = LookupTableGate::num_slots(&common_data.config);
     let max_lookup_table_degree = ceil_div_usize(num_lut_slots, num_partial_lookups);
@@ -580,7 +808,8 @@ fn compute_all_lookup_polys<
     lookup: bool,
 ) -> Vec<PolynomialValues<F>> {
     if lookup {
-        let polys: Vec<Vec<PolynomialValues<F>>> = (0..common_data.config.num_challenges)
+        let polys: Vec<Vec<PolynomialValues<F>>> = (0..common_data.num_challenges())
+            .into_par_iter()
             .map(|c| {
                 compute_lookup_polys(
                     witness,
@@ -615,8 +844,9 @@ fn compute_quotient_polys<
     gammas: &[F],
     deltas: &[F],
     alphas: &[F],
+    chunk_size: Option<usize>,
 ) -> Vec<PolynomialCoeffs<F>> {
-    let num_challenges = common_data.config.num_challenges;
+    let num_challenges = common_data.num_challenges();
 
     let has_lookup = common_data.num_lookup_polys != 0;
 
@@ -637,36 +867,36 @@ fn compute_quotient_polys<
     let points = F::two_adic_subgroup(common_data.degree_bits() + quotient_degree_bits);
     let lde_size = points.len();
 
+    // Gates may read the values at the extra opening points `g^shift * x`, which are `shift`
+    // times `next_step` steps away. They are read from the oracles committed so far, indexed like
+    // `PlonkOracle`; the quotient can't be opened at extra points.
+    let oracle_commitments = [
+        &prover_data.constants_sigmas_commitment,
+        wires_commitment,
+        zs_partial_products_and_lookup_commitment,
+    ];
+    let extra_opening_steps = common_data
+        .extra_opening_points
+        .iter()
+        .map(|opening_point| {
+            opening_point
+                .shift
+                .rem_euclid(common_data.degree() as isize) as usize
+                * next_step
+        })
+        .collect::<Vec<_>>();
+    let num_extra_openings = common_data.num_extra_openings();
+
     let z_h_on_coset = ZeroPolyOnCoset::new(common_data.degree_bits(), quotient_degree_bits);
 
     // Precompute the lookup table evals on the challenges in delta
     // These values are used to produce the final RE constraints for each lut,
-    // and are the same each time in check_lookup_constraints_batched.
+    // and are the same at every point of the LDE.
     // lut_poly_evals[i][j] gives the eval for the i'th challenge and the j'th lookup table
     let lut_re_poly_evals: Vec<Vec<F>> = if has_lookup {
-        let num_lut_slots = LookupTableGate::num_slots(&common_data.config);
-        (0..num_challenges)
-            .map(move |i| {
-                let cur_deltas = &deltas[NUM_COINS_LOOKUP * i..NUM_COINS_LOOKUP * (i + 1)];
-                let cur_challenge_delta = cur_deltas[LookupChallenges::ChallengeDelta as usize];
-
-                (LookupSelectors::StartEnd as usize..common_data.num_lookup_selectors)
-                    .map(|r| {
-                        let lut_row_number = ceil_div_usize(
-                            common_data.luts[r - LookupSelectors::StartEnd as usize].len(),
-                            num_lut_slots,
-                        );
-
-                        get_lut_poly(
-                            common_data,
-                            r - LookupSelectors::StartEnd as usize,
-                            cur_deltas,
-                            num_lut_slots * lut_row_number,
-                        )
-                        .eval(cur_challenge_delta)
-                    })
-                    .collect()
-            })
+        deltas
+            .chunks(NUM_COINS_LOOKUP)
+            .map(|cur_deltas| eval_lut_polys(common_data, cur_deltas))
             .collect()
     } else {
         vec![]
@@ -675,135 +905,620 @@ fn compute_quotient_polys<
     let lut_re_poly_evals_refs: Vec<&[F]> =
         lut_re_poly_evals.iter().map(|v| v.as_slice()).collect();
 
-    let points_batches = points.par_chunks(BATCH_SIZE);
-    let num_batches = ceil_div_usize(points.len(), BATCH_SIZE);
+    // The quotient values of each challenge, filled in one chunk of points at a time so that only
+    // a chunk's worth of per-point buffers is alive at once.
+    let mut quotient_values = vec![Vec::with_capacity(lde_size); num_challenges];
+    let chunk_size = chunk_size.unwrap_or(lde_size);
+    assert!(chunk_size > 0, "Quotient chunk size must be positive");
 
-    let quotient_values: Vec<Vec<F>> = points_batches
-        .enumerate()
-        .flat_map(|(batch_i, xs_batch)| {
-            // Each batch must be the same size, except the last one, which may be smaller.
-            debug_assert!(
-                xs_batch.len() == BATCH_SIZE
-                    || (batch_i == num_batches - 1 && xs_batch.len() <= BATCH_SIZE)
-            );
+    for (chunk_i, points_chunk) in points.chunks(chunk_size).enumerate() {
+        let chunk_start = chunk_i * chunk_size;
+        let chunk_values: Vec<Vec<F>> = points_chunk
+            .par_chunks(BATCH_SIZE)
+            .enumerate()
+            .flat_map(|(batch_i, xs_batch)| {
+                let batch_start = chunk_start + BATCH_SIZE * batch_i;
+                let indices_batch: Vec<usize> =
+                    (batch_start..batch_start + xs_batch.len()).collect();
+
+                let mut shifted_xs_batch = Vec::with_capacity(xs_batch.len());
+                let mut local_zs_batch = Vec::with_capacity(xs_batch.len());
+                let mut next_zs_batch = Vec::with_capacity(xs_batch.len());
+
+                let mut local_lookup_batch = Vec::with_capacity(xs_batch.len());
+                let mut next_lookup_batch = Vec::with_capacity(xs_batch.len());
+
+                let mut partial_products_batch = Vec::with_capacity(xs_batch.len());
+                let mut s_sigmas_batch = Vec::with_capacity(xs_batch.len());
+
+                let mut local_constants_batch_refs = Vec::with_capacity(xs_batch.len());
+                let mut local_wires_batch_refs = Vec::with_capacity(xs_batch.len());
+
+                for (&i, &x) in indices_batch.iter().zip(xs_batch) {
+                    let shifted_x = F::coset_shift() * x;
+                    let i_next = (i + next_step) % lde_size;
+                    let local_constants_sigmas = prover_data
+                        .constants_sigmas_commitment
+                        .get_lde_values(i, step);
+                    let local_constants = &local_constants_sigmas[common_data.constants_range()];
+                    let s_sigmas = &local_constants_sigmas[common_data.sigmas_range()];
+                    let local_wires = wires_commitment.get_lde_values(i, step);
+                    let local_zs_partial_and_lookup =
+                        zs_partial_products_and_lookup_commitment.get_lde_values(i, step);
+                    let next_zs_partial_and_lookup =
+                        zs_partial_products_and_lookup_commitment.get_lde_values(i_next, step);
+
+                    let local_zs = &local_zs_partial_and_lookup[common_data.zs_range()];
+
+                    let next_zs = &next_zs_partial_and_lookup[common_data.zs_range()];
+
+                    let partial_products =
+                        &local_zs_partial_and_lookup[common_data.partial_products_range()];
+
+                    if has_lookup {
+                        let local_lookup_zs =
+                            &local_zs_partial_and_lookup[common_data.lookup_range()];
+
+                        let next_lookup_zs =
+                            &next_zs_partial_and_lookup[common_data.lookup_range()];
+                        debug_assert_eq!(local_lookup_zs.len(), common_data.num_all_lookup_polys());
+
+                        local_lookup_batch.push(local_lookup_zs);
+                        next_lookup_batch.push(next_lookup_zs);
+                    }
+
+                    debug_assert_eq!(local_wires.len(), common_data.config.num_wires);
+                    debug_assert_eq!(local_zs.len(), num_challenges);
+
+                    local_constants_batch_refs.push(local_constants);
+                    local_wires_batch_refs.push(local_wires);
+
+                    shifted_xs_batch.push(shifted_x);
+                    local_zs_batch.push(local_zs);
+                    next_zs_batch.push(next_zs);
+                    partial_products_batch.push(partial_products);
+                    s_sigmas_batch.push(s_sigmas);
+                }
 
-            let indices_batch: Vec<usize> =
-                (BATCH_SIZE * batch_i..BATCH_SIZE * batch_i + xs_batch.len()).collect();
+                // NB (JN): I'm not sure how (in)efficient the below is. It needs measuring.
+                let mut local_constants_batch =
+                    vec![F::ZERO; xs_batch.len() * local_constants_batch_refs[0].len()];
+                for i in 0..local_constants_batch_refs[0].len() {
+                    for (j, constants) in local_constants_batch_refs.iter().enumerate() {
+                        local_constants_batch[i * xs_batch.len() + j] = constants[i];
+                    }
+                }
 
-            let mut shifted_xs_batch = Vec::with_capacity(xs_batch.len());
-            let mut local_zs_batch = Vec::with_capacity(xs_batch.len());
-            let mut next_zs_batch = Vec::with_capacity(xs_batch.len());
+                let mut local_wires_batch =
+                    vec![F::ZERO; xs_batch.len() * local_wires_batch_refs[0].len()];
+                for i in 0..local_wires_batch_refs[0].len() {
+                    for (j, wires) in local_wires_batch_refs.iter().enumerate() {
+                        local_wires_batch[i * xs_batch.len() + j] = wires[i];
+                    }
+                }
 
-            let mut local_lookup_batch = Vec::with_capacity(xs_batch.len());
-            let mut next_lookup_batch = Vec::with_capacity(xs_batch.len());
+                let extra_openings_batch = common_data
+                    .extra_opening_points
+                    .iter()
+                    .zip(&extra_opening_steps)
+                    .zip(&num_extra_openings)
+                    .map(|((opening_point, &extra_step), &num_values)| {
+                        let mut values = vec![F::ZERO; xs_batch.len() * num_values];
+                        for (j, &i) in indices_batch.iter().enumerate() {
+                            let i_extra = (i + extra_step) % lde_size;
+                            let point_values = opening_point.oracles.iter().flat_map(|&oracle| {
+                                oracle_commitments[oracle].get_lde_values(i_extra, step)
+                            });
+                            for (k, &value) in point_values.enumerate() {
+                                values[k * xs_batch.len() + j] = value;
+                            }
+                        }
+                        values
+                    })
+                    .collect::<Vec<_>>();
 
-            let mut partial_products_batch = Vec::with_capacity(xs_batch.len());
-            let mut s_sigmas_batch = Vec::with_capacity(xs_batch.len());
+                let vars_batch = EvaluationVarsBaseBatch::new(
+                    xs_batch.len(),
+                    &local_constants_batch,
+                    &local_wires_batch,
+                    public_inputs_hash,
+                )
+                .with_extra_openings(&extra_openings_batch);
 
-            let mut local_constants_batch_refs = Vec::with_capacity(xs_batch.len());
-            let mut local_wires_batch_refs = Vec::with_capacity(xs_batch.len());
+                let mut quotient_values_batch = eval_vanishing_poly_base_batch::<F, D>(
+                    common_data,
+                    &indices_batch,
+                    &shifted_xs_batch,
+                    vars_batch,
+                    &local_zs_batch,
+                    &next_zs_batch,
+                    &local_lookup_batch,
+                    &next_lookup_batch,
+                    &partial_products_batch,
+                    &s_sigmas_batch,
+                    betas,
+                    gammas,
+                    deltas,
+                    alphas,
+                    &z_h_on_coset,
+                    &lut_re_poly_evals_refs,
+                );
 
-            for (&i, &x) in indices_batch.iter().zip(xs_batch) {
-                let shifted_x = F::coset_shift() * x;
-                let i_next = (i + next_step) % lde_size;
-                let local_constants_sigmas = prover_data
-                    .constants_sigmas_commitment
-                    .get_lde_values(i, step);
-                let local_constants = &local_constants_sigmas[common_data.constants_range()];
-                let s_sigmas = &local_constants_sigmas[common_data.sigmas_range()];
-                let local_wires = wires_commitment.get_lde_values(i, step);
-                let local_zs_partial_and_lookup =
-                    zs_partial_products_and_lookup_commitment.get_lde_values(i, step);
-                let next_zs_partial_and_lookup =
-                    zs_partial_products_and_lookup_commitment.get_lde_values(i_next, step);
+                for (&i, quotient_values) in
+                    indices_batch.iter().zip(quotient_values_batch.iter_mut())
+                {
+                    let denominator_inv = z_h_on_coset.eval_inverse(i);
+                    quotient_values
+                        .iter_mut()
+                        .for_each(|v| *v *= denominator_inv);
+                }
+                quotient_values_batch
+            })
+            .collect();
 
-                let local_zs = &local_zs_partial_and_lookup[common_data.zs_range()];
+        for point_values in chunk_values {
+            for (values, v) in quotient_values.iter_mut().zip(point_values) {
+                values.push(v);
+            }
+        }
+    }
 
-                let next_zs = &next_zs_partial_and_lookup[common_data.zs_range()];
+    quotient_values
+        .into_par_iter()
+        .map(PolynomialValues::new)
+        .map(|values| values.coset_ifft(F::coset_shift()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::gates::noop::NoopGate;
+    use crate::plonk::circuit_builder::{BuildOptions, CircuitBuilder};
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn seeded_proofs_are_reproducible() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        builder.is_equal(x, y);
+        let z = builder.mul(x, y);
+        builder.register_public_input(z);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(3));
+        pw.set_target(y, F::from_canonical_u32(5));
+
+        let prove_with_seed = |seed| {
+            let options = ProverOptions {
+                rng_seed: Some(seed),
+                ..Default::default()
+            };
+            data.prove_with_options(pw.clone(), &options)
+        };
+
+        let proof_a = prove_with_seed(1)?;
+        let proof_b = prove_with_seed(1)?;
+        let proof_c = prove_with_seed(2)?;
+        assert_eq!(proof_a.to_bytes(), proof_b.to_bytes());
+        assert_ne!(proof_a.to_bytes(), proof_c.to_bytes());
+
+        data.verify(proof_a)?;
+        data.verify(proof_c)
+    }
+
+    #[test]
+    fn proofs_have_polynomials_per_challenge() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let mut proof_sizes = Vec::new();
+        let mut data_and_proofs = Vec::new();
+        for num_challenges in 1..=3 {
+            let config = CircuitConfig {
+                num_challenges,
+                // A single set of challenges from a 64-bit field can't reach 100 bits.
+                security_bits: 64,
+                ..CircuitConfig::standard_recursion_config()
+            };
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+            let x = builder.add_virtual_target();
+            let y = builder.add_virtual_target();
+            let z = builder.mul(x, y);
+            let lut = builder.add_lookup_table_from_fn(|i| i + 1, &[0, 1, 2, 3]);
+            let looked_up = builder.add_lookup_from_index(x, lut);
+            builder.register_public_input(z);
+            builder.register_public_input(looked_up);
+            let data = builder.build::<C>();
+            let common = &data.common;
+            assert_eq!(common.num_challenges(), num_challenges);
+
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u32(3));
+            pw.set_target(y, F::from_canonical_u32(5));
+            let proof = data.prove(pw)?;
+            let openings = &proof.proof.openings;
+            assert_eq!(openings.plonk_zs.len(), num_challenges);
+            assert_eq!(openings.plonk_zs_next.len(), num_challenges);
+            assert_eq!(
+                openings.partial_products.len(),
+                num_challenges * common.num_partial_products
+            );
+            assert_eq!(
+                openings.quotient_polys.len(),
+                num_challenges * common.quotient_degree_factor
+            );
+            assert_eq!(
+                openings.lookup_zs.len(),
+                num_challenges * common.num_lookup_polys
+            );
+            data.verify(proof.clone())?;
+            proof_sizes.push(proof.to_bytes().len());
+            data_and_proofs.push((data, proof));
+        }
+        assert!(proof_sizes.windows(2).all(|w| w[0] < w[1]));
+
+        // The recursive verifier checks every set of challenges too.
+        let (inner_data, inner_proof) = data_and_proofs.pop().unwrap();
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let verifier_data = builder.constant_verifier_data(&inner_data.verifier_only);
+        builder.verify_proof::<C>(&proof_target, &verifier_data, &inner_data.common);
+        let data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+        data.verify(data.prove(pw)?)
+    }
 
-                let partial_products =
-                    &local_zs_partial_and_lookup[common_data.partial_products_range()];
+    #[test]
+    fn challenge_security_depends_on_degree() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        // Two sets of challenges reach 100 bits up to degree 2^14.
+        let config = CircuitConfig::standard_recursion_config();
+        assert_eq!(config.challenge_security_bits::<F>(14), 100);
+        assert_eq!(config.challenge_security_bits::<F>(15), 98);
+        let single = CircuitConfig {
+            num_challenges: 1,
+            ..config.clone()
+        };
+        assert_eq!(single.challenge_security_bits::<F>(3), 61);
+
+        // Falling short is only warned about, so larger circuits still build with the standard
+        // config.
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        for _ in 0..1 << 14 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let layout = builder.build_layout::<C>(BuildOptions::default(), &mut TimingTree::default());
+        assert_eq!(layout.degree_bits(), 15);
+    }
 
-                if has_lookup {
-                    let local_lookup_zs = &local_zs_partial_and_lookup[common_data.lookup_range()];
+    #[cfg(feature = "timing")]
+    #[test]
+    fn timing_entries_cover_proving_phases() -> Result<()> {
+        use log::Level;
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let z = builder.mul(x, y);
+        builder.register_public_input(z);
+
+        let mut build_timing = TimingTree::new("build", Level::Debug);
+        let data = builder.build_with_timing::<C>(&mut build_timing);
+        let build_entries = build_timing.to_entries();
+        for name in [
+            "generate sigma polynomials",
+            "compute constant polynomials",
+            "commit to constants and sigmas",
+        ] {
+            assert!(build_entries.iter().any(|(entry, _, _)| entry == name));
+        }
 
-                    let next_lookup_zs = &next_zs_partial_and_lookup[common_data.lookup_range()];
-                    debug_assert_eq!(local_lookup_zs.len(), common_data.num_all_lookup_polys());
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(3));
+        pw.set_target(y, F::from_canonical_u32(5));
+        let options = ProverOptions {
+            time_generators: true,
+            ..Default::default()
+        };
+        let mut timing = TimingTree::new("prove", Level::Debug);
+        let proof = prove_with_options(&data.prover_only, &data.common, pw, &options, &mut timing)?;
+        timing.pop();
+        data.verify(proof)?;
+
+        // Each phase is a scope directly under the root, in proving order, and the generators are
+        // timed within the witness generation phase.
+        let root = timing.export();
+        let mut phases = root.children.iter();
+        for name in [
+            "run ",
+            "compute full witness",
+            "compute wire polynomials",
+            "compute wires commitment",
+            "compute partial products",
+            "commit to partial products",
+            "compute quotient polys",
+            "split up quotient polys",
+            "commit to quotient polys",
+            "construct the opening set",
+            "compute opening proofs",
+        ] {
+            assert!(
+                phases.any(|phase| phase.name.starts_with(name)),
+                "missing or misplaced phase `{}`",
+                name
+            );
+        }
+        let generators = root
+            .children
+            .iter()
+            .find(|phase| phase.name.starts_with("run "))
+            .unwrap();
+        assert!(generators
+            .children
+            .iter()
+            .any(|entry| entry.name.starts_with("ArithmeticBaseGenerator")));
 
-                    local_lookup_batch.push(local_lookup_zs);
-                    next_lookup_batch.push(next_lookup_zs);
-                }
+        // The phases account for almost all of the proving time. What's left, such as observing
+        // commitments, is cheap but not negligible next to the phases of such a small circuit.
+        let phases = root
+            .children
+            .iter()
+            .map(|phase| phase.duration)
+            .sum::<web_time::Duration>();
+        assert!(phases <= root.duration);
+        let tolerance = (root.duration / 5).max(web_time::Duration::from_millis(1));
+        assert!(
+            root.duration - phases <= tolerance,
+            "the phases took {:?} out of {:?}",
+            phases,
+            root.duration
+        );
+
+        Ok(())
+    }
 
-                debug_assert_eq!(local_wires.len(), common_data.config.num_wires);
-                debug_assert_eq!(local_zs.len(), num_challenges);
+    #[cfg(feature = "std")]
+    #[test]
+    fn progress_covers_all_phases_in_order() -> Result<()> {
+        use std::sync::{Arc, Mutex};
 
-                local_constants_batch_refs.push(local_constants);
-                local_wires_batch_refs.push(local_wires);
+        use itertools::Itertools;
 
-                shifted_xs_batch.push(shifted_x);
-                local_zs_batch.push(local_zs);
-                next_zs_batch.push(next_zs);
-                partial_products_batch.push(partial_products);
-                s_sigmas_batch.push(s_sigmas);
-            }
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
 
-            // NB (JN): I'm not sure how (in)efficient the below is. It needs measuring.
-            let mut local_constants_batch =
-                vec![F::ZERO; xs_batch.len() * local_constants_batch_refs[0].len()];
-            for i in 0..local_constants_batch_refs[0].len() {
-                for (j, constants) in local_constants_batch_refs.iter().enumerate() {
-                    local_constants_batch[i * xs_batch.len() + j] = constants[i];
-                }
-            }
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let callback = {
+            let reports = reports.clone();
+            ProgressCallback::new(move |phase, fraction| {
+                reports.lock().unwrap().push((phase, fraction))
+            })
+        };
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        builder.set_progress_callback(callback.clone());
+        let x = builder.add_virtual_target();
+        let mut y = x;
+        for _ in 0..1000 {
+            y = builder.mul(y, x);
+        }
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(3));
+        let options = ProverOptions {
+            progress: callback,
+            ..Default::default()
+        };
+        let proof = data.prove_with_options(pw, &options)?;
+        data.verify(proof)?;
+
+        let reports = reports.lock().unwrap();
+        assert!(reports.windows(2).all(|w| w[0] <= w[1]), "{reports:?}");
+        let phases = reports
+            .iter()
+            .map(|&(phase, _)| phase)
+            .dedup()
+            .collect_vec();
+        assert_eq!(phases[0], ProverPhase::Preprocessing);
+        assert_eq!(phases[1..], ProverPhase::PROVING);
+        for phase in phases {
+            let phase_reports = reports.iter().filter(|(p, _)| *p == phase).collect_vec();
+            assert_eq!(phase_reports.first().unwrap().1, 0.0);
+            assert_eq!(phase_reports.last().unwrap().1, 1.0);
+            assert!(phase_reports.len() <= 102);
+        }
+        // Witness generation reports its progress in between.
+        assert!(
+            reports
+                .iter()
+                .filter(|(p, _)| *p == ProverPhase::WitnessGeneration)
+                .count()
+                > 50
+        );
+
+        Ok(())
+    }
 
-            let mut local_wires_batch =
-                vec![F::ZERO; xs_batch.len() * local_wires_batch_refs[0].len()];
-            for i in 0..local_wires_batch_refs[0].len() {
-                for (j, wires) in local_wires_batch_refs.iter().enumerate() {
-                    local_wires_batch[i * xs_batch.len() + j] = wires[i];
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn batch_proofs_match_individual_proofs() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let z = builder.mul(x, y);
+        builder.register_public_input(z);
+        let data = builder.build_prover::<C>();
+
+        let witnesses = (0..6)
+            .map(|i| {
+                let mut pw = PartialWitness::new();
+                pw.set_target(x, F::from_canonical_u32(i));
+                // One witness is missing an input, which must not affect the others.
+                if i != 3 {
+                    pw.set_target(y, F::from_canonical_u32(i + 1));
                 }
+                pw
+            })
+            .collect::<Vec<_>>();
+        let options = ProverOptions {
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+
+        let batch = data.prove_batch_with_options(witnesses.clone(), 4, &options);
+        assert_eq!(batch.len(), witnesses.len());
+        for (i, (result, pw)) in batch.into_iter().zip(witnesses).enumerate() {
+            if i == 3 {
+                assert!(result.is_err());
+            } else {
+                assert_eq!(result?, data.prove_with_options(pw, &options)?);
             }
+        }
+        assert!(data.prove_batch(Vec::new(), 4).is_empty());
 
-            let vars_batch = EvaluationVarsBaseBatch::new(
-                xs_batch.len(),
-                &local_constants_batch,
-                &local_wires_batch,
-                public_inputs_hash,
-            );
+        Ok(())
+    }
 
-            let mut quotient_values_batch = eval_vanishing_poly_base_batch::<F, D>(
-                common_data,
-                &indices_batch,
-                &shifted_xs_batch,
-                vars_batch,
-                &local_zs_batch,
-                &next_zs_batch,
-                &local_lookup_batch,
-                &next_lookup_batch,
-                &partial_products_batch,
-                &s_sigmas_batch,
-                betas,
-                gammas,
-                deltas,
-                alphas,
-                &z_h_on_coset,
-                &lut_re_poly_evals_refs,
-            );
+    #[cfg(all(feature = "parallel", feature = "rand_chacha"))]
+    #[test]
+    fn parallel_proofs_match_serial_proofs() -> Result<()> {
+        use std::sync::Arc;
 
-            for (&i, quotient_values) in indices_batch.iter().zip(quotient_values_batch.iter_mut())
-            {
-                let denominator_inv = z_h_on_coset.eval_inverse(i);
-                quotient_values
-                    .iter_mut()
-                    .for_each(|v| *v *= denominator_inv);
-            }
-            quotient_values_batch
-        })
-        .collect();
+        use plonky2_maybe_rayon::rayon::ThreadPoolBuilder;
 
-    transpose(&quotient_values)
-        .into_par_iter()
-        .map(PolynomialValues::new)
-        .map(|values| values.coset_ifft(F::coset_shift()))
-        .collect()
+        use crate::gates::lookup_table::LookupTable;
+
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let table: LookupTable = Arc::new((0..16).map(|i| (i, i * i % 16)).collect());
+        let lut_index = builder.add_lookup_table_from_pairs(table);
+        let xs = builder.add_virtual_targets(16);
+        let mut acc = builder.one();
+        for &x in &xs {
+            let looked_up = builder.add_lookup_from_index(x, lut_index);
+            acc = builder.mul_add(acc, x, looked_up);
+        }
+        builder.register_public_input(acc);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        for (i, &x) in xs.iter().enumerate() {
+            pw.set_target(x, F::from_canonical_usize(i));
+        }
+        // The unused public input gate wires are randomized, so fix the seed.
+        let options = ProverOptions {
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        let prove_with_threads = |num_threads| {
+            ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap()
+                .install(|| data.prove_with_options(pw.clone(), &options))
+        };
+
+        let serial = prove_with_threads(1)?;
+        let parallel = prove_with_threads(4)?;
+        assert_eq!(serial, parallel);
+        data.verify(parallel)
+    }
+
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn chunked_quotient_matches_unchunked() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let mut y = x;
+        for _ in 0..100 {
+            y = builder.mul_add(y, y, x);
+        }
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(7));
+        let prove_with_chunk_size = |quotient_chunk_size| {
+            let options = ProverOptions {
+                rng_seed: Some(0),
+                quotient_chunk_size,
+                ..Default::default()
+            };
+            data.prove_with_options(pw.clone(), &options)
+        };
+
+        let unchunked = prove_with_chunk_size(None)?;
+        // Chunks that are and aren't multiples of the evaluation batch size, and a chunk which
+        // splits the LDEs into cosets larger than the circuit's degree.
+        for chunk_size in [BATCH_SIZE, 100, 1, 2 * data.common.degree()] {
+            assert_eq!(prove_with_chunk_size(Some(chunk_size))?, unchunked);
+        }
+        data.verify(unchunked)
+    }
+
+    #[test]
+    fn unused_wire_columns_are_committed_as_zero() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig {
+            num_wires: 160,
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+        assert_eq!(data.common.num_used_wires(), 135);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(7));
+        let proof = data.prove(pw)?;
+        assert!(proof.proof.openings.wires[135..]
+            .iter()
+            .all(|w| w.is_zero()));
+        data.verify(proof)
+    }
 }
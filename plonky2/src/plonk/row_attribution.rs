@@ -0,0 +1,247 @@
+//! The attribution of a circuit's rows to the contexts in which they were filled, computed by
+//! `build`. See [`CircuitData::print_row_attribution`](crate::plonk::circuit_data::CircuitData::print_row_attribution).
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use log::debug;
+
+/// The rows of a circuit, attributed to the contexts of the operations placed in them. Operations
+/// of different contexts can be packed into the same row, e.g. arithmetic operations, so a row
+/// holding `k` operations is attributed `1/k` to the context of each. Rows which aren't shared
+/// between operations, including the ones `build` adds, are attributed to the context open when
+/// they were added.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RowAttribution {
+    /// For each context with rows attributed to it, in the order the contexts were opened: its
+    /// stack of scopes, e.g. `root > a > b`, and the number of its operations in rows holding `k`
+    /// operations, keyed by `k`. A row added as a whole counts as one operation in a row of one.
+    pub contexts: Vec<(String, BTreeMap<usize, usize>)>,
+}
+
+impl RowAttribution {
+    /// Returns the rows attributed to each context, excluding those of its children.
+    pub fn rows(&self) -> BTreeMap<String, f64> {
+        self.contexts
+            .iter()
+            .map(|(context, shares)| (context.clone(), Self::context_rows(shares)))
+            .collect()
+    }
+
+    /// Returns the total number of attributed rows, i.e. the degree of the built circuit, up to
+    /// rounding.
+    pub fn num_rows(&self) -> f64 {
+        self.contexts
+            .iter()
+            .map(|(_, shares)| Self::context_rows(shares))
+            .sum()
+    }
+
+    fn context_rows(shares: &BTreeMap<usize, usize>) -> f64 {
+        shares
+            .iter()
+            .map(|(&k, &num_ops)| num_ops as f64 / k as f64)
+            .sum()
+    }
+
+    /// Logs the rows attributed to each context, including its children, as a tree. Contexts with
+    /// fewer than `min_delta` rows are left out.
+    pub fn print(&self, min_delta: usize) {
+        let mut root = AttributionNode::new("root");
+        for (context, shares) in &self.contexts {
+            let path = context.split(" > ").skip(1).collect::<Vec<_>>();
+            root.insert(&path, Self::context_rows(shares));
+        }
+        root.print(0, min_delta as f64);
+    }
+}
+
+/// A scope of a [`RowAttribution`] printed as a tree.
+struct AttributionNode {
+    name: String,
+    own_rows: f64,
+    children: Vec<AttributionNode>,
+}
+
+impl AttributionNode {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            own_rows: 0.0,
+            children: vec![],
+        }
+    }
+
+    fn insert(&mut self, path: &[&str], rows: f64) {
+        let Some((&name, rest)) = path.split_first() else {
+            self.own_rows += rows;
+            return;
+        };
+        let index = match self.children.iter().position(|c| c.name == name) {
+            Some(index) => index,
+            None => {
+                self.children.push(Self::new(name));
+                self.children.len() - 1
+            }
+        };
+        self.children[index].insert(rest, rows);
+    }
+
+    fn total_rows(&self) -> f64 {
+        self.own_rows + self.children.iter().map(Self::total_rows).sum::<f64>()
+    }
+
+    fn print(&self, depth: usize, min_delta: f64) {
+        debug!(
+            "{}{:.2} rows to {}",
+            "| ".repeat(depth),
+            self.total_rows(),
+            self.name
+        );
+        for child in &self.children {
+            if child.total_rows() >= min_delta {
+                child.print(depth + 1, min_delta);
+            }
+        }
+    }
+}
+
+/// Records, while a circuit is built, the context in which each row was added and the contexts of
+/// the operations placed in each row by `find_slot`.
+#[derive(Debug)]
+pub(crate) struct RowContextLog {
+    /// The stacks of scopes which were open at some point, in the order they were opened.
+    names: Vec<String>,
+    /// The index of each stack in `names`.
+    ids: BTreeMap<String, usize>,
+    /// The index of the open stack.
+    current: usize,
+    /// The index of the stack open when each row was added.
+    row_contexts: Vec<usize>,
+    /// The row and the index of the stack of each operation placed by `find_slot`.
+    op_contexts: Vec<(usize, usize)>,
+}
+
+impl RowContextLog {
+    pub fn new() -> Self {
+        Self {
+            names: vec!["root".to_string()],
+            ids: BTreeMap::from([("root".to_string(), 0)]),
+            current: 0,
+            row_contexts: Vec::new(),
+            op_contexts: Vec::new(),
+        }
+    }
+
+    /// Records `stack` as the open stack of scopes.
+    pub fn enter(&mut self, stack: String) {
+        let next_id = self.names.len();
+        self.current = *self.ids.entry(stack.clone()).or_insert(next_id);
+        if self.current == next_id {
+            self.names.push(stack);
+        }
+    }
+
+    pub fn add_row(&mut self) {
+        self.row_contexts.push(self.current);
+    }
+
+    pub fn add_op(&mut self, row: usize) {
+        self.op_contexts.push((row, self.current));
+    }
+
+    pub fn attribution(&self) -> RowAttribution {
+        let mut ops_per_row = vec![0; self.row_contexts.len()];
+        for &(row, _) in &self.op_contexts {
+            ops_per_row[row] += 1;
+        }
+
+        let mut shares = vec![BTreeMap::<usize, usize>::new(); self.names.len()];
+        for (row, &context) in self.row_contexts.iter().enumerate() {
+            if ops_per_row[row] == 0 {
+                *shares[context].entry(1).or_default() += 1;
+            }
+        }
+        for &(row, context) in &self.op_contexts {
+            *shares[context].entry(ops_per_row[row]).or_default() += 1;
+        }
+
+        RowAttribution {
+            contexts: self
+                .names
+                .iter()
+                .cloned()
+                .zip(shares)
+                .filter(|(_, shares)| !shares.is_empty())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+    use crate::gates::noop::NoopGate;
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn interleaved_contexts_share_rows() {
+        let config = CircuitConfig::standard_recursion_config();
+        let ops_per_row = config.num_routed_wires / 4;
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+
+        // Alternate between two contexts, so that their multiplications are packed into the same
+        // arithmetic rows: a full row, and a half-full one.
+        let xs = builder.add_virtual_targets(ops_per_row * 3 / 2);
+        for (i, &x) in xs.iter().enumerate() {
+            let context = if i % 2 == 0 { "even" } else { "odd" };
+            let y = builder.with_context(Level::Debug, context, |builder| builder.mul(x, x));
+            builder.register_public_input(y);
+        }
+        builder.with_context(Level::Debug, "noops", |builder| {
+            builder.add_gate(NoopGate, vec![]);
+            builder.add_gate(NoopGate, vec![]);
+        });
+        let data = builder.build::<C>();
+
+        let attribution = data.row_attribution();
+        let contexts = attribution
+            .contexts
+            .iter()
+            .map(|(context, _)| context.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            contexts,
+            ["root", "root > even", "root > odd", "root > noops"]
+        );
+        let half = ops_per_row / 2;
+        assert_eq!(
+            attribution.contexts[1].1,
+            BTreeMap::from([(half, half / 2), (ops_per_row, half)])
+        );
+
+        // Each context gets half of each shared row.
+        let rows = attribution.rows();
+        assert_eq!(rows["root > even"], 1.0);
+        assert_eq!(rows["root > odd"], 1.0);
+        assert_eq!(rows["root > noops"], 2.0);
+        assert!((attribution.num_rows() - data.common.degree() as f64).abs() < 1e-9);
+
+        data.print_row_attribution(0);
+    }
+}
@@ -0,0 +1,499 @@
+//! Templates of sub-circuits, which are recorded from a gadget once and then stamped out as many
+//! times as needed. See [`SubCircuitTemplate`].
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+
+use hashbrown::HashMap;
+
+use crate::field::extension::Extendable;
+use crate::gadgets::arithmetic::BaseArithmeticOperation;
+use crate::gadgets::arithmetic_extension::{
+    ExtensionArithmeticOperation, ScalarArithmeticOperation,
+};
+use crate::gates::gate::GateRef;
+use crate::hash::hash_types::RichField;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::iop::generator::{CopiesGenerator, CopyGenerator, WitnessGeneratorRef};
+use crate::iop::target::Target;
+use crate::iop::wire::Wire;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::CircuitConfig;
+
+/// An operation of a [`CircuitBuilder`] recording a template, which each instance replays.
+#[derive(Debug)]
+pub(crate) enum RecordedOp<F: RichField + Extendable<D>, const D: usize> {
+    /// A row added by `add_gate`.
+    AddGate {
+        gate: GateRef<F, D>,
+        constants: Vec<F>,
+    },
+    /// An operation placed by `find_slot`.
+    FindSlot {
+        gate: GateRef<F, D>,
+        params: Vec<F>,
+        constants: Vec<F>,
+    },
+    AddGateType(GateRef<F, D>),
+    Connect(Target, Target),
+    VirtualTarget,
+    /// A constant the recording builder didn't have a target for yet.
+    Constant(F),
+    /// The next of the recorded generators.
+    Generator,
+    PushContext(log::Level, String),
+    PopContext,
+}
+
+/// What a [`CircuitBuilder`] recorded for a template: the operations which instances replay in
+/// order, and the state they append to their builder's.
+#[derive(Debug)]
+pub(crate) struct Recording<F: RichField + Extendable<D>, const D: usize> {
+    pub(crate) ops: Vec<RecordedOp<F, D>>,
+    pub(crate) generators: Vec<WitnessGeneratorRef<F, D>>,
+    pub(crate) copy_generators: Vec<CopyGenerator>,
+    pub(crate) batch_copy_generators: Vec<CopiesGenerator>,
+    pub(crate) range_checks: Vec<(Target, usize)>,
+    pub(crate) external_inputs: Vec<Target>,
+    pub(crate) base_arithmetic_results: Vec<(BaseArithmeticOperation<F>, Target)>,
+    pub(crate) arithmetic_results: Vec<(ExtensionArithmeticOperation<F, D>, ExtensionTarget<D>)>,
+    pub(crate) scalar_arithmetic_results:
+        Vec<(ScalarArithmeticOperation<F, D>, ExtensionTarget<D>)>,
+    pub(crate) mul_const_results: Vec<((F::Extension, ExtensionTarget<D>), ExtensionTarget<D>)>,
+    /// The row and slot of each operation placed by `find_slot`.
+    pub(crate) operation_slots: Vec<(usize, usize)>,
+}
+
+/// A sub-circuit recorded once from a gadget, which can then be stamped out with
+/// [`instantiate`](Self::instantiate) much faster than by running the gadget again.
+///
+/// An instance replays the recorded gates, operations, copy constraints, generators and contexts
+/// on the targets it is given, so a circuit built from instances is the same, down to its digest,
+/// as one built by calling the gadget each time. Operations are still packed into rows shared
+/// with the rest of the circuit, constants are shared with it, and each instance opens its own
+/// contexts.
+///
+/// The gadget must only use the input targets it is given, and not capture targets of another
+/// builder. It sees them as opaque: an instance doesn't constant-fold operations on inputs which
+/// happen to be constants, or reuse the results of operations on its inputs which were memoized
+/// before it, as calling the gadget would. Public inputs, lookups, tables, ROMs and generators
+/// which don't implement `remap_targets` can't be recorded.
+#[derive(Debug)]
+pub struct SubCircuitTemplate<F: RichField + Extendable<D>, const D: usize> {
+    config: CircuitConfig,
+    num_inputs: usize,
+    outputs: Vec<Target>,
+    recording: Recording<F, D>,
+    /// For each recorded row, its operations if it was added by `find_slot`.
+    packed_rows: Vec<Option<PackedRow>>,
+    /// The wires of the operations of each gate type placed by `find_slot`.
+    op_wires: Vec<OpWires>,
+}
+
+/// A recorded row added by `find_slot`.
+#[derive(Debug)]
+struct PackedRow {
+    /// The index of the gate's wires in `SubCircuitTemplate::op_wires`.
+    op_wires: usize,
+    /// The index of the operation in each filled slot, among those placed by `find_slot`.
+    ops: Vec<usize>,
+}
+
+/// The wires of each operation of a gate, which let a recorded operation be moved to another slot.
+#[derive(Debug)]
+struct OpWires {
+    /// The wires of each slot, as listed by `Gate::op_wires`.
+    wires: Vec<Vec<usize>>,
+    /// The slot using each wire, and the wire's position among those of the slot.
+    owners: Vec<Option<(usize, usize)>>,
+}
+
+/// Where the targets of an instance's recording ended up.
+struct Instance {
+    /// The target of each virtual target of the recording, starting with the inputs.
+    virtual_targets: Vec<Target>,
+    /// The row of each recorded row, for those added by `add_gate`.
+    rows: Vec<usize>,
+    /// The row and slot of each operation placed by `find_slot`.
+    slots: Vec<(usize, usize)>,
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> SubCircuitTemplate<F, D> {
+    /// Records the sub-circuit which `gadget` builds from `num_inputs` input targets, for circuits
+    /// with the configuration `config`. The targets it returns are the outputs of each instance.
+    pub fn new(
+        config: &CircuitConfig,
+        num_inputs: usize,
+        gadget: impl FnOnce(&mut CircuitBuilder<F, D>, &[Target]) -> Vec<Target>,
+    ) -> Self {
+        let mut builder = CircuitBuilder::new(config.clone());
+        let inputs = builder.add_virtual_targets(num_inputs);
+        builder.start_recording();
+        let outputs = gadget(&mut builder, &inputs);
+        let recording = builder.into_recording();
+
+        for generator in &recording.generators {
+            assert!(
+                generator.0.remap_targets(&|t| t).is_some(),
+                "{} doesn't implement remap_targets, so it can't be part of a sub-circuit template",
+                generator.0.id()
+            );
+        }
+
+        let mut packed_rows = Vec::new();
+        let mut op_wires = Vec::new();
+        let mut op_wires_indices = HashMap::new();
+        let mut ops = recording.operation_slots.iter().enumerate();
+        for op in &recording.ops {
+            match op {
+                RecordedOp::AddGate { .. } => packed_rows.push(None),
+                RecordedOp::FindSlot { gate, .. } => {
+                    let (index, &(row, slot)) = ops.next().unwrap();
+                    if slot == 0 {
+                        let op_wires = *op_wires_indices.entry(gate.0.id()).or_insert_with(|| {
+                            op_wires.push(OpWires::new(gate));
+                            op_wires.len() - 1
+                        });
+                        packed_rows.push(Some(PackedRow {
+                            op_wires,
+                            ops: vec![],
+                        }));
+                    }
+                    let packed_row = packed_rows[row].as_mut().unwrap();
+                    debug_assert_eq!(packed_row.ops.len(), slot);
+                    packed_row.ops.push(index);
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            config: config.clone(),
+            num_inputs,
+            outputs,
+            recording,
+            packed_rows,
+            op_wires,
+        }
+    }
+
+    /// The number of input targets of each instance.
+    pub const fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    /// The number of output targets of each instance.
+    pub fn num_outputs(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Stamps out an instance of this sub-circuit on `inputs`, and returns its outputs.
+    pub fn instantiate(
+        &self,
+        builder: &mut CircuitBuilder<F, D>,
+        inputs: &[Target],
+    ) -> Vec<Target> {
+        assert_eq!(
+            inputs.len(),
+            self.num_inputs,
+            "The sub-circuit template takes {} inputs, but was given {}",
+            self.num_inputs,
+            inputs.len()
+        );
+        assert_eq!(
+            builder.config, self.config,
+            "The sub-circuit template was recorded for another CircuitConfig"
+        );
+
+        let recording = &self.recording;
+        let mut instance = Instance {
+            virtual_targets: inputs.to_vec(),
+            rows: Vec::with_capacity(self.packed_rows.len()),
+            slots: Vec::with_capacity(recording.operation_slots.len()),
+        };
+        let mut generators = recording.generators.iter();
+        for op in &recording.ops {
+            match op {
+                RecordedOp::AddGate { gate, constants } => {
+                    let row = builder.add_gate_ref(gate.clone(), constants.clone());
+                    instance.rows.push(row);
+                }
+                RecordedOp::FindSlot {
+                    gate,
+                    params,
+                    constants,
+                } => {
+                    let (_, recorded_slot) = recording.operation_slots[instance.slots.len()];
+                    if recorded_slot == 0 {
+                        // The row's operations are remapped by their slots.
+                        instance.rows.push(usize::MAX);
+                    }
+                    let slot = builder.find_slot_ref(gate.clone(), params, constants);
+                    instance.slots.push(slot);
+                }
+                RecordedOp::AddGateType(gate) => builder.add_gate_to_gate_set(gate.clone()),
+                &RecordedOp::Connect(x, y) => {
+                    builder.connect(self.remap(&instance, x), self.remap(&instance, y));
+                }
+                RecordedOp::VirtualTarget => {
+                    let target = builder.add_virtual_target();
+                    instance.virtual_targets.push(target);
+                }
+                &RecordedOp::Constant(c) => {
+                    let target = builder.constant(c);
+                    instance.virtual_targets.push(target);
+                }
+                RecordedOp::Generator => {
+                    let generator = generators.next().unwrap();
+                    let generator = generator
+                        .0
+                        .remap_targets(&|t| self.remap(&instance, t))
+                        .unwrap();
+                    builder.add_generators(vec![generator]);
+                }
+                &RecordedOp::PushContext(level, ref ctx) => builder.push_context(level, ctx),
+                RecordedOp::PopContext => builder.pop_context(),
+            }
+        }
+
+        let map = |t| self.remap(&instance, t);
+        for copy in &recording.copy_generators {
+            builder.generate_copy(map(copy.src), map(copy.dst));
+        }
+        for copies in &recording.batch_copy_generators {
+            let (srcs, dsts): (Vec<_>, Vec<_>) = copies
+                .pairs
+                .iter()
+                .map(|&(src, dst)| (map(src), map(dst)))
+                .unzip();
+            builder.generate_copies(&srcs, &dsts);
+        }
+        for &(x, num_bits) in &recording.range_checks {
+            builder.enqueue_range_check(map(x), num_bits);
+        }
+        let external_inputs = recording
+            .external_inputs
+            .iter()
+            .map(|&t| map(t))
+            .collect::<Vec<_>>();
+        builder.declare_external_inputs(&external_inputs);
+
+        // Make the results of the instance's operations available to later gadgets, as they would
+        // be had they been computed by the gadget.
+        for (operation, result) in &recording.base_arithmetic_results {
+            builder
+                .base_arithmetic_results
+                .entry(operation.remap(&map))
+                .or_insert(map(*result));
+        }
+        for (operation, result) in &recording.arithmetic_results {
+            builder
+                .arithmetic_results
+                .entry(operation.remap(&map))
+                .or_insert(result.remap(&map));
+        }
+        for (operation, result) in &recording.scalar_arithmetic_results {
+            builder
+                .scalar_arithmetic_results
+                .entry(operation.remap(&map))
+                .or_insert(result.remap(&map));
+        }
+        for ((c, x), result) in &recording.mul_const_results {
+            builder
+                .mul_const_results
+                .entry((*c, x.remap(&map)))
+                .or_insert(result.remap(&map));
+        }
+
+        self.outputs.iter().map(|&t| map(t)).collect()
+    }
+
+    /// Returns the target of `instance` for the recorded target `target`.
+    fn remap(&self, instance: &Instance, target: Target) -> Target {
+        match target {
+            Target::VirtualTarget { index } => instance.virtual_targets[index],
+            Target::Wire(Wire { row, column }) => match &self.packed_rows[row] {
+                None => Target::wire(instance.rows[row], column),
+                Some(packed_row) => {
+                    let op_wires = &self.op_wires[packed_row.op_wires];
+                    let (slot, position) = op_wires.owners[column].unwrap_or_else(|| {
+                        panic!("Column {column} isn't a wire of an operation placed by find_slot")
+                    });
+                    let (row, slot) = instance.slots[packed_row.ops[slot]];
+                    Target::wire(row, op_wires.wires[slot][position])
+                }
+            },
+        }
+    }
+}
+
+impl OpWires {
+    fn new<F: RichField + Extendable<D>, const D: usize>(gate: &GateRef<F, D>) -> Self {
+        let wires = (0..gate.0.num_ops())
+            .map(|op| gate.0.op_wires(op))
+            .collect::<Vec<_>>();
+        let mut owners = vec![None; gate.0.num_wires()];
+        for (slot, slot_wires) in wires.iter().enumerate() {
+            for (position, &wire) in slot_wires.iter().enumerate() {
+                owners[wire] = Some((slot, position));
+            }
+        }
+        Self { wires, owners }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use log::Level;
+
+    use super::*;
+    use crate::field::types::{Field, Sample};
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::plonk_common::PlonkOracle;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// A gadget using packed operations, rows of their own, generators, constants, contexts and
+    /// deferred range checks.
+    fn gadget(builder: &mut CircuitBuilder<F, D>, inputs: &[Target]) -> Vec<Target> {
+        let &[x, y] = inputs else {
+            panic!("The gadget takes two inputs");
+        };
+        builder.with_context(Level::Debug, "gadget", |builder| {
+            let seven = builder.constant(F::from_canonical_u64(7));
+            let a = builder.mul_add(x, y, seven);
+            let b = builder.mul_const(F::from_canonical_u64(3), a);
+            let a_is_b = builder.is_equal(a, b);
+            let a_ext = builder.convert_to_ext(a);
+            let a_inv = builder.inverse_extension(a_ext);
+            let bits =
+                builder.with_context(Level::Debug, "bits", |builder| builder.split_le(y, 64));
+            let (low, _) = builder.split_low_high(x, 16, 64);
+            builder.enqueue_range_check(low, 16);
+            vec![b, bits[3].target, a_is_b.target, a_inv.0[0], low]
+        })
+    }
+
+    /// Builds a circuit chaining `n` instances of `gadget`, by calling it or from a template, with
+    /// other operations interleaved so that instances share rows with them.
+    fn chained_circuit(
+        n: usize,
+        template: Option<&SubCircuitTemplate<F, D>>,
+    ) -> (CircuitBuilder<F, D>, [Target; 2]) {
+        let mut builder = CircuitBuilder::new(CircuitConfig::standard_recursion_config());
+        let inputs = builder.add_virtual_target_arr::<2>();
+        let mut instance_inputs = inputs;
+        for i in 0..n {
+            let outputs = match template {
+                Some(template) => template.instantiate(&mut builder, &instance_inputs),
+                None => gadget(&mut builder, &instance_inputs),
+            };
+            let c = builder.constant(F::from_canonical_usize(i));
+            let sum = builder.add_many([outputs[1], outputs[2], c]);
+            builder.register_public_input(sum);
+            instance_inputs = [outputs[0], builder.mul(outputs[3], outputs[4])];
+        }
+        builder.register_public_inputs(&instance_inputs);
+        (builder, inputs)
+    }
+
+    #[test]
+    fn instances_match_gadget_calls() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let template = SubCircuitTemplate::new(&config, 2, gadget);
+        assert_eq!(template.num_outputs(), 5);
+
+        let (naive, _) = chained_circuit(5, None);
+        let (templated, inputs) = chained_circuit(5, Some(&template));
+        let naive_data = naive.build::<C>();
+        let templated_data = templated.build::<C>();
+        assert_eq!(
+            templated_data.verifier_only.circuit_digest,
+            naive_data.verifier_only.circuit_digest
+        );
+        assert_eq!(
+            templated_data.row_attribution(),
+            naive_data.row_attribution()
+        );
+
+        let mut pw = PartialWitness::new();
+        pw.set_target_arr(&inputs, &F::rand_array::<2>());
+        let proof = templated_data.prove(pw)?;
+        templated_data.verify(proof)
+    }
+
+    /// A round of a Poseidon-like permutation of 12 elements, as in the `sub_circuit` benchmark.
+    fn hash_round(builder: &mut CircuitBuilder<F, D>, state: &[Target]) -> Vec<Target> {
+        let sboxed = state
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let x = builder.add_const(x, F::from_canonical_usize(i + 1));
+                builder.exp_u64(x, 7)
+            })
+            .collect::<Vec<_>>();
+        (0..state.len())
+            .map(|i| {
+                let mut acc = builder.zero();
+                for (j, &x) in sboxed.iter().enumerate() {
+                    let c = F::from_canonical_usize(1 << ((state.len() + j - i) % state.len()));
+                    acc = builder.mul_const_add(c, x, acc);
+                }
+                acc
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hash_round_instances_match_gadget_calls() {
+        let config = CircuitConfig::standard_recursion_config();
+        let template = SubCircuitTemplate::new(&config, 12, hash_round);
+        let digests = [None, Some(&template)].map(|template| {
+            let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+            let mut state = builder.add_virtual_targets(12);
+            for _ in 0..100 {
+                state = match template {
+                    Some(template) => template.instantiate(&mut builder, &state),
+                    None => hash_round(&mut builder, &state),
+                };
+            }
+            builder.register_public_inputs(&state);
+            builder.build::<C>().verifier_only.circuit_digest
+        });
+        assert_eq!(digests[0], digests[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Sub-circuit templates can't contain public inputs")]
+    fn templates_reject_public_inputs() {
+        let config = CircuitConfig::standard_recursion_config();
+        SubCircuitTemplate::<F, D>::new(&config, 1, |builder, inputs| {
+            builder.register_public_input(inputs[0]);
+            vec![]
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Sub-circuit templates can't contain extra opening points")]
+    fn templates_reject_opening_points() {
+        let config = CircuitConfig::standard_recursion_config();
+        SubCircuitTemplate::<F, D>::new(&config, 1, |builder, _inputs| {
+            builder.add_opening_point(-1, &[PlonkOracle::WIRES]);
+            vec![]
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Sub-circuit templates can't contain a domain separator")]
+    fn templates_reject_domain_separators() {
+        let config = CircuitConfig::standard_recursion_config();
+        SubCircuitTemplate::<F, D>::new(&config, 1, |builder, _inputs| {
+            builder.set_domain_separator(vec![F::ONE]);
+            vec![]
+        });
+    }
+}
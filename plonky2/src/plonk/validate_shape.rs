@@ -4,7 +4,9 @@ use crate::field::extension::Extendable;
 use crate::hash::hash_types::RichField;
 use crate::plonk::circuit_data::CommonCircuitData;
 use crate::plonk::config::GenericConfig;
+use crate::plonk::plonk_common::PlonkOracle;
 use crate::plonk::proof::{OpeningSet, Proof, ProofWithPublicInputs};
+use crate::plonk::verifier::VerificationError;
 
 pub(crate) fn validate_proof_with_pis_shape<F, C, const D: usize>(
     proof_with_pis: &ProofWithPublicInputs<F, C, D>,
@@ -17,13 +19,16 @@ where
     let ProofWithPublicInputs {
         proof,
         public_inputs,
+        circuit_digest_hint: _,
     } = proof_with_pis;
-    validate_proof_shape(proof, common_data)?;
     ensure!(
         public_inputs.len() == common_data.num_public_inputs,
-        "Number of public inputs doesn't match circuit data."
+        VerificationError::PublicInputsLengthMismatch {
+            expected: common_data.num_public_inputs,
+            actual: public_inputs.len(),
+        }
     );
-    Ok(())
+    validate_proof_shape(proof, common_data)
 }
 
 fn validate_proof_shape<F, C, const D: usize>(
@@ -54,19 +59,76 @@ where
         quotient_polys,
         lookup_zs,
         lookup_zs_next,
+        extra_openings,
     } = openings;
-    let cap_height = common_data.fri_params.config.cap_height;
-    ensure!(wires_cap.height() == cap_height);
-    ensure!(plonk_zs_partial_products_cap.height() == cap_height);
-    ensure!(quotient_polys_cap.height() == cap_height);
-    ensure!(constants.len() == common_data.num_constants);
-    ensure!(plonk_sigmas.len() == config.num_routed_wires);
-    ensure!(wires.len() == config.num_wires);
-    ensure!(plonk_zs.len() == config.num_challenges);
-    ensure!(plonk_zs_next.len() == config.num_challenges);
-    ensure!(partial_products.len() == config.num_challenges * common_data.num_partial_products);
-    ensure!(quotient_polys.len() == common_data.num_quotient_polys());
-    ensure!(lookup_zs.len() == common_data.num_all_lookup_polys());
-    ensure!(lookup_zs_next.len() == common_data.num_all_lookup_polys());
+    let cap_height = |oracle: PlonkOracle| common_data.fri_params.initial_cap_height(oracle.index);
+    let check = |component: &str, actual: usize, expected: usize| {
+        ensure!(
+            actual == expected,
+            VerificationError::shape(component, expected, actual)
+        );
+        Ok(())
+    };
+    check(
+        "wires cap height",
+        wires_cap.height(),
+        cap_height(PlonkOracle::WIRES),
+    )?;
+    check(
+        "Zs and partial products cap height",
+        plonk_zs_partial_products_cap.height(),
+        cap_height(PlonkOracle::ZS_PARTIAL_PRODUCTS),
+    )?;
+    check(
+        "quotient polynomials cap height",
+        quotient_polys_cap.height(),
+        cap_height(PlonkOracle::QUOTIENT),
+    )?;
+    check(
+        "constants openings",
+        constants.len(),
+        common_data.num_constants,
+    )?;
+    check(
+        "sigmas openings",
+        plonk_sigmas.len(),
+        config.num_routed_wires,
+    )?;
+    check("wires openings", wires.len(), config.num_wires)?;
+    check("Zs openings", plonk_zs.len(), common_data.num_challenges())?;
+    check(
+        "next Zs openings",
+        plonk_zs_next.len(),
+        common_data.num_challenges(),
+    )?;
+    check(
+        "partial products openings",
+        partial_products.len(),
+        common_data.num_challenges() * common_data.num_partial_products,
+    )?;
+    check(
+        "quotient polynomials openings",
+        quotient_polys.len(),
+        common_data.num_quotient_polys(),
+    )?;
+    check(
+        "lookup Zs openings",
+        lookup_zs.len(),
+        common_data.num_all_lookup_polys(),
+    )?;
+    check(
+        "next lookup Zs openings",
+        lookup_zs_next.len(),
+        common_data.num_all_lookup_polys(),
+    )?;
+    let num_extra_openings = common_data.num_extra_openings();
+    check(
+        "extra opening points",
+        extra_openings.len(),
+        num_extra_openings.len(),
+    )?;
+    for (values, num_openings) in extra_openings.iter().zip(num_extra_openings) {
+        check("extra openings", values.len(), num_openings)?;
+    }
     Ok(())
 }
@@ -1,14 +1,22 @@
+//! The vanishing polynomial, which combines the gate constraints, the permutation argument and the
+//! lookup argument into a single polynomial which should vanish on `H`.
+//!
+//! Its formula is written once, in [`eval_vanishing_poly_generic`], against the
+//! [`VanishingPolyBackend`] trait. The prover, the verifier and the recursive verifier evaluate it
+//! with their own backends, and only differ in how they gather its inputs.
+
 #[cfg(not(feature = "std"))]
 use alloc::{format, vec, vec::Vec};
 use core::cmp::min;
+use core::ops::Index;
 
+use log::Level;
 use plonky2_field::polynomial::PolynomialCoeffs;
 use plonky2_util::ceil_div_usize;
 
 use super::circuit_builder::{LookupChallenges, NUM_COINS_LOOKUP};
-use super::vars::EvaluationVarsBase;
 use crate::field::batch_util::batch_add_inplace;
-use crate::field::extension::{Extendable, FieldExtension};
+use crate::field::extension::Extendable;
 use crate::field::types::Field;
 use crate::field::zero_poly_coset::ZeroPolyOnCoset;
 use crate::gates::lookup::LookupGate;
@@ -21,11 +29,12 @@ use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::CommonCircuitData;
 use crate::plonk::plonk_common;
 use crate::plonk::plonk_common::eval_l_0_circuit;
+use crate::plonk::vanishing_poly_backend::{
+    BaseFieldBackend, ExtensionFieldBackend, VanishingPolyBackend,
+};
 use crate::plonk::vars::{EvaluationTargets, EvaluationVars, EvaluationVarsBaseBatch};
-use crate::util::partial_products::{check_partial_products, check_partial_products_circuit};
-use crate::util::reducing::ReducingFactorTarget;
+use crate::util::partial_products::check_partial_products;
 use crate::util::strided_view::PackedStridedView;
-use crate::with_context;
 
 /// Get the polynomial associated to a lookup table with current challenges.
 pub(crate) fn get_lut_poly<F: RichField + Extendable<D>, const D: usize>(
@@ -45,6 +54,187 @@ pub(crate) fn get_lut_poly<F: RichField + Extendable<D>, const D: usize>(
     PolynomialCoeffs::new(coeffs)
 }
 
+/// The degree of the polynomial associated to a lookup table, which fills whole rows of
+/// `LookupTableGate`s.
+fn lut_poly_degree<F: RichField + Extendable<D>, const D: usize>(
+    common_data: &CommonCircuitData<F, D>,
+    lut_index: usize,
+) -> usize {
+    let num_lut_slots = LookupTableGate::num_slots(&common_data.config);
+    let lut_row_number = ceil_div_usize(common_data.luts[lut_index].len(), num_lut_slots);
+    num_lut_slots * lut_row_number
+}
+
+/// Evaluates the polynomial associated to each lookup table at the delta challenge, with the
+/// lookup challenges `deltas` of a single round.
+pub(crate) fn eval_lut_polys<F: RichField + Extendable<D>, const D: usize>(
+    common_data: &CommonCircuitData<F, D>,
+    deltas: &[F],
+) -> Vec<F> {
+    let delta = deltas[LookupChallenges::ChallengeDelta as usize];
+    (0..common_data.luts.len())
+        .map(|lut_index| {
+            let degree = lut_poly_degree(common_data, lut_index);
+            get_lut_poly(common_data, lut_index, deltas, degree).eval(delta)
+        })
+        .collect()
+}
+
+/// The values the vanishing polynomial is evaluated on at a single point, as seen by a
+/// [`VanishingPolyBackend`]. Challenges are given in the backend's values too.
+pub(crate) struct VanishingPolyInputs<'a, V, W: ?Sized> {
+    pub(crate) x: V,
+    /// `L_0(x)`, which backends compute differently.
+    pub(crate) l_0_x: V,
+    pub(crate) local_wires: &'a W,
+    pub(crate) lookup_selectors: &'a [V],
+    pub(crate) local_zs: &'a [V],
+    pub(crate) next_zs: &'a [V],
+    pub(crate) local_lookup_zs: &'a [V],
+    pub(crate) next_lookup_zs: &'a [V],
+    pub(crate) partial_products: &'a [V],
+    pub(crate) s_sigmas: &'a [V],
+    pub(crate) betas: &'a [V],
+    pub(crate) gammas: &'a [V],
+    pub(crate) alphas: &'a [V],
+    pub(crate) deltas: &'a [V],
+    /// The evaluations of [`eval_lut_polys`] for each challenge in turn.
+    pub(crate) lut_re_poly_evals: &'a [V],
+}
+
+/// Scratch space for [`eval_vanishing_poly_generic`], which the prover reuses across points.
+pub(crate) struct VanishingPolyBuffers<V> {
+    wire_values_plus_gamma: Vec<V>,
+    numerators: Vec<V>,
+    denominators: Vec<V>,
+    z_1_terms: Vec<V>,
+    partial_products_terms: Vec<V>,
+    lookup_terms: Vec<V>,
+    terms: Vec<V>,
+}
+
+impl<V> VanishingPolyBuffers<V> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            wire_values_plus_gamma: Vec::new(),
+            numerators: Vec::new(),
+            denominators: Vec::new(),
+            z_1_terms: Vec::new(),
+            partial_products_terms: Vec::new(),
+            lookup_terms: Vec::new(),
+            terms: Vec::new(),
+        }
+    }
+}
+
+/// Evaluates the vanishing polynomial at `inputs.x`. In this context, the vanishing polynomial is
+/// a random linear combination of gate constraints, plus some other terms relating to the
+/// permutation and lookup arguments. All such terms should vanish on `H`.
+///
+/// The gate constraints are evaluated by the caller, since gates implement each backend
+/// separately.
+pub(crate) fn eval_vanishing_poly_generic<'c, F, B, W, const D: usize>(
+    backend: &mut B,
+    common_data: &CommonCircuitData<F, D>,
+    inputs: &VanishingPolyInputs<B::Value, W>,
+    constraint_terms: impl IntoIterator<Item = &'c B::Value>,
+    buffers: &mut VanishingPolyBuffers<B::Value>,
+) -> Vec<B::Value>
+where
+    F: RichField + Extendable<D>,
+    B: VanishingPolyBackend<F>,
+    B::Value: 'c,
+    W: Index<usize, Output = B::Value> + ?Sized,
+{
+    let has_lookup = common_data.num_lookup_polys != 0;
+    let max_degree = common_data.quotient_degree_factor;
+    let num_prods = common_data.num_partial_products;
+    let num_routed_wires = common_data.config.num_routed_wires;
+    let num_luts = common_data.luts.len();
+    let VanishingPolyBuffers {
+        wire_values_plus_gamma,
+        numerators,
+        denominators,
+        z_1_terms,
+        partial_products_terms,
+        lookup_terms,
+        terms,
+    } = buffers;
+
+    for i in 0..common_data.num_challenges() {
+        let z_x = inputs.local_zs[i];
+        let z_gx = inputs.next_zs[i];
+
+        // L_0(x) (Z(x) - 1) = 0.
+        z_1_terms.push(backend.mul_sub(inputs.l_0_x, z_x, inputs.l_0_x));
+
+        // If there are lookups in the circuit, then we add the lookup constraints.
+        if has_lookup {
+            let lookup_zs_range =
+                common_data.num_lookup_polys * i..common_data.num_lookup_polys * (i + 1);
+            check_lookup_constraints(
+                backend,
+                common_data,
+                inputs.local_wires,
+                &inputs.local_lookup_zs[lookup_zs_range.clone()],
+                &inputs.next_lookup_zs[lookup_zs_range],
+                inputs.lookup_selectors,
+                &inputs.deltas[NUM_COINS_LOOKUP * i..NUM_COINS_LOOKUP * (i + 1)],
+                &inputs.lut_re_poly_evals[num_luts * i..num_luts * (i + 1)],
+                lookup_terms,
+            );
+        }
+
+        let beta = inputs.betas[i];
+        let gamma = inputs.gammas[i];
+        // The numerator is `beta * s_id + wire_value + gamma`, with `s_id = k_i * x`, and the
+        // denominator is `beta * s_sigma + wire_value + gamma`. The numerator is computed as
+        // `k_i * (beta * x) + wire_value + gamma`, so that circuits multiply by the base field
+        // constant `k_i` rather than by an extension element.
+        wire_values_plus_gamma.clear();
+        for j in 0..num_routed_wires {
+            wire_values_plus_gamma.push(backend.add(inputs.local_wires[j], gamma));
+        }
+        let beta_x = backend.mul(beta, inputs.x);
+        backend.scalar_mul_add_many(
+            &common_data.k_is[..num_routed_wires],
+            beta_x,
+            wire_values_plus_gamma,
+            numerators,
+        );
+        for (&s_sigma, &wire_value_plus_gamma) in inputs.s_sigmas[..num_routed_wires]
+            .iter()
+            .zip(wire_values_plus_gamma.iter())
+        {
+            denominators.push(backend.mul_add(beta, s_sigma, wire_value_plus_gamma));
+        }
+
+        // The partial products considered for this iteration of `i`.
+        let current_partial_products = &inputs.partial_products[i * num_prods..(i + 1) * num_prods];
+        // Check the quotient partial products.
+        check_partial_products(
+            backend,
+            numerators,
+            denominators,
+            current_partial_products,
+            z_x,
+            z_gx,
+            max_degree,
+            partial_products_terms,
+        );
+
+        numerators.clear();
+        denominators.clear();
+    }
+
+    terms.clear();
+    terms.append(z_1_terms);
+    terms.append(partial_products_terms);
+    terms.append(lookup_terms);
+    terms.extend(constraint_terms);
+    backend.reduce_with_powers_multi(terms, inputs.alphas)
+}
+
 /// Evaluate the vanishing polynomial at `x`. In this context, the vanishing polynomial is a random
 /// linear combination of gate constraints, plus some other terms relating to the permutation
 /// argument. All such terms should vanish on `H`.
@@ -64,97 +254,52 @@ pub(crate) fn eval_vanishing_poly<F: RichField + Extendable<D>, const D: usize>(
     deltas: &[F],
 ) -> Vec<F::Extension> {
     let has_lookup = common_data.num_lookup_polys != 0;
-    let max_degree = common_data.quotient_degree_factor;
-    let num_prods = common_data.num_partial_products;
 
     let constraint_terms = evaluate_gate_constraints::<F, D>(common_data, vars);
 
     let lookup_selectors = &vars.local_constants[common_data.selectors_info.num_selectors()
         ..common_data.selectors_info.num_selectors() + common_data.num_lookup_selectors];
 
-    // The L_0(x) (Z(x) - 1) vanishing terms.
-    let mut vanishing_z_1_terms = Vec::new();
-
-    // The terms checking the lookup constraints, if any.
-    let mut vanishing_all_lookup_terms = if has_lookup {
-        let num_sldc_polys = common_data.num_lookup_polys - 1;
-        Vec::with_capacity(
-            common_data.config.num_challenges * (4 + common_data.luts.len() + 2 * num_sldc_polys),
-        )
+    let lut_re_poly_evals = if has_lookup {
+        deltas
+            .chunks(NUM_COINS_LOOKUP)
+            .flat_map(|cur_deltas| eval_lut_polys(common_data, cur_deltas))
+            .map(F::Extension::from)
+            .collect()
     } else {
         Vec::new()
     };
 
-    // The terms checking the partial products.
-    let mut vanishing_partial_products_terms = Vec::new();
-
-    let l_0_x = plonk_common::eval_l_0(common_data.degree(), x);
-
-    for i in 0..common_data.config.num_challenges {
-        let z_x = local_zs[i];
-        let z_gx = next_zs[i];
-        vanishing_z_1_terms.push(l_0_x * (z_x - F::Extension::ONE));
-
-        if has_lookup {
-            let cur_local_lookup_zs = &local_lookup_zs
-                [common_data.num_lookup_polys * i..common_data.num_lookup_polys * (i + 1)];
-            let cur_next_lookup_zs = &next_lookup_zs
-                [common_data.num_lookup_polys * i..common_data.num_lookup_polys * (i + 1)];
-
-            let cur_deltas = &deltas[NUM_COINS_LOOKUP * i..NUM_COINS_LOOKUP * (i + 1)];
-
-            let lookup_constraints = check_lookup_constraints(
-                common_data,
-                vars,
-                cur_local_lookup_zs,
-                cur_next_lookup_zs,
-                lookup_selectors,
-                cur_deltas.try_into().unwrap(),
-            );
-
-            vanishing_all_lookup_terms.extend(lookup_constraints);
-        }
-
-        let numerator_values = (0..common_data.config.num_routed_wires)
-            .map(|j| {
-                let wire_value = vars.local_wires[j];
-                let k_i = common_data.k_is[j];
-                let s_id = x.scalar_mul(k_i);
-                wire_value + s_id.scalar_mul(betas[i]) + gammas[i].into()
-            })
-            .collect::<Vec<_>>();
-        let denominator_values = (0..common_data.config.num_routed_wires)
-            .map(|j| {
-                let wire_value = vars.local_wires[j];
-                let s_sigma = s_sigmas[j];
-                wire_value + s_sigma.scalar_mul(betas[i]) + gammas[i].into()
-            })
-            .collect::<Vec<_>>();
-
-        // The partial products considered for this iteration of `i`.
-        let current_partial_products = &partial_products[i * num_prods..(i + 1) * num_prods];
-        // Check the quotient partial products.
-        let partial_product_checks = check_partial_products(
-            &numerator_values,
-            &denominator_values,
-            current_partial_products,
-            z_x,
-            z_gx,
-            max_degree,
-        );
-        vanishing_partial_products_terms.extend(partial_product_checks);
-    }
-
-    let vanishing_terms = [
-        vanishing_z_1_terms,
-        vanishing_partial_products_terms,
-        vanishing_all_lookup_terms,
-        constraint_terms,
-    ]
-    .concat();
-
-    let alphas = &alphas.iter().map(|&a| a.into()).collect::<Vec<_>>();
-    plonk_common::reduce_with_powers_multi(&vanishing_terms, alphas)
+    let to_ext = |xs: &[F]| xs.iter().map(|&x| x.into()).collect::<Vec<_>>();
+    let betas = to_ext(betas);
+    let gammas = to_ext(gammas);
+    let alphas = to_ext(alphas);
+    let deltas = to_ext(deltas);
+
+    let inputs = VanishingPolyInputs {
+        x,
+        l_0_x: plonk_common::eval_l_0(common_data.degree(), x),
+        local_wires: vars.local_wires,
+        lookup_selectors,
+        local_zs,
+        next_zs,
+        local_lookup_zs,
+        next_lookup_zs,
+        partial_products,
+        s_sigmas,
+        betas: &betas,
+        gammas: &gammas,
+        alphas: &alphas,
+        deltas: &deltas,
+        lut_re_poly_evals: &lut_re_poly_evals,
+    };
+    eval_vanishing_poly_generic(
+        &mut ExtensionFieldBackend::new(),
+        common_data,
+        &inputs,
+        &constraint_terms,
+        &mut VanishingPolyBuffers::new(),
+    )
 }
 
 /// Like `eval_vanishing_poly`, but specialized for base field points. Batched.
@@ -193,35 +338,17 @@ pub(crate) fn eval_vanishing_poly_base_batch<F: RichField + Extendable<D>, const
     assert_eq!(partial_products_batch.len(), n);
     assert_eq!(s_sigmas_batch.len(), n);
 
-    let max_degree = common_data.quotient_degree_factor;
-    let num_prods = common_data.num_partial_products;
-
     let num_gate_constraints = common_data.num_gate_constraints;
 
     let constraint_terms_batch =
         evaluate_gate_constraints_base_batch::<F, D>(common_data, vars_batch);
     debug_assert!(constraint_terms_batch.len() == n * num_gate_constraints);
 
-    let num_challenges = common_data.config.num_challenges;
-    let num_routed_wires = common_data.config.num_routed_wires;
-
-    let mut numerator_values = Vec::with_capacity(num_routed_wires);
-    let mut denominator_values = Vec::with_capacity(num_routed_wires);
+    // The LUT evaluations only depend on the challenges, so they are shared by all points.
+    let lut_re_poly_evals = lut_re_poly_evals.concat();
 
-    // The L_0(x) (Z(x) - 1) vanishing terms.
-    let mut vanishing_z_1_terms = Vec::with_capacity(num_challenges);
-    // The terms checking the partial products.
-    let mut vanishing_partial_products_terms = Vec::new();
-
-    // The terms checking the lookup constraints.
-    let mut vanishing_all_lookup_terms = if has_lookup {
-        let num_sldc_polys = common_data.num_lookup_polys - 1;
-        Vec::with_capacity(
-            common_data.config.num_challenges * (4 + common_data.luts.len() + 2 * num_sldc_polys),
-        )
-    } else {
-        Vec::new()
-    };
+    let mut buffers = VanishingPolyBuffers::new();
+    let mut lookup_selectors = Vec::with_capacity(common_data.num_lookup_selectors);
 
     let mut res_batch: Vec<Vec<F>> = Vec::with_capacity(n);
     for k in 0..n {
@@ -229,96 +356,43 @@ pub(crate) fn eval_vanishing_poly_base_batch<F: RichField + Extendable<D>, const
         let x = xs_batch[k];
         let vars = vars_batch.view(k);
 
-        let lookup_selectors: Vec<F> = (0..common_data.num_lookup_selectors)
-            .map(|i| vars.local_constants[common_data.selectors_info.num_selectors() + i])
-            .collect();
+        lookup_selectors.clear();
+        lookup_selectors.extend(
+            (0..common_data.num_lookup_selectors)
+                .map(|i| vars.local_constants[common_data.selectors_info.num_selectors() + i]),
+        );
 
-        let local_zs = local_zs_batch[k];
-        let next_zs = next_zs_batch[k];
-        let local_lookup_zs = if has_lookup {
-            local_lookup_zs_batch[k]
+        let (local_lookup_zs, next_lookup_zs) = if has_lookup {
+            (local_lookup_zs_batch[k], next_lookup_zs_batch[k])
         } else {
-            &[]
+            (&[][..], &[][..])
         };
 
-        let next_lookup_zs = if has_lookup {
-            next_lookup_zs_batch[k]
-        } else {
-            &[]
+        let inputs = VanishingPolyInputs {
+            x,
+            l_0_x: z_h_on_coset.eval_l_0(index, x),
+            local_wires: &vars.local_wires,
+            lookup_selectors: &lookup_selectors,
+            local_zs: local_zs_batch[k],
+            next_zs: next_zs_batch[k],
+            local_lookup_zs,
+            next_lookup_zs,
+            partial_products: partial_products_batch[k],
+            s_sigmas: s_sigmas_batch[k],
+            betas,
+            gammas,
+            alphas,
+            deltas,
+            lut_re_poly_evals: &lut_re_poly_evals,
         };
-
-        let partial_products = partial_products_batch[k];
-        let s_sigmas = s_sigmas_batch[k];
-
         let constraint_terms = PackedStridedView::new(&constraint_terms_batch, n, k);
-
-        let l_0_x = z_h_on_coset.eval_l_0(index, x);
-        for i in 0..num_challenges {
-            let z_x = local_zs[i];
-            let z_gx = next_zs[i];
-            vanishing_z_1_terms.push(l_0_x * z_x.sub_one());
-
-            // If there are lookups in the circuit, then we add the lookup constraints.
-            if has_lookup {
-                let cur_deltas = &deltas[NUM_COINS_LOOKUP * i..NUM_COINS_LOOKUP * (i + 1)];
-
-                let cur_local_lookup_zs = &local_lookup_zs
-                    [common_data.num_lookup_polys * i..common_data.num_lookup_polys * (i + 1)];
-                let cur_next_lookup_zs = &next_lookup_zs
-                    [common_data.num_lookup_polys * i..common_data.num_lookup_polys * (i + 1)];
-
-                let lookup_constraints = check_lookup_constraints_batch(
-                    common_data,
-                    vars,
-                    cur_local_lookup_zs,
-                    cur_next_lookup_zs,
-                    &lookup_selectors,
-                    cur_deltas.try_into().unwrap(),
-                    lut_re_poly_evals[i],
-                );
-                vanishing_all_lookup_terms.extend(lookup_constraints);
-            }
-
-            numerator_values.extend((0..num_routed_wires).map(|j| {
-                let wire_value = vars.local_wires[j];
-                let k_i = common_data.k_is[j];
-                let s_id = k_i * x;
-                wire_value + betas[i] * s_id + gammas[i]
-            }));
-            denominator_values.extend((0..num_routed_wires).map(|j| {
-                let wire_value = vars.local_wires[j];
-                let s_sigma = s_sigmas[j];
-                wire_value + betas[i] * s_sigma + gammas[i]
-            }));
-
-            // The partial products considered for this iteration of `i`.
-            let current_partial_products = &partial_products[i * num_prods..(i + 1) * num_prods];
-            // Check the numerator partial products.
-            let partial_product_checks = check_partial_products(
-                &numerator_values,
-                &denominator_values,
-                current_partial_products,
-                z_x,
-                z_gx,
-                max_degree,
-            );
-            vanishing_partial_products_terms.extend(partial_product_checks);
-
-            numerator_values.clear();
-            denominator_values.clear();
-        }
-
-        let vanishing_terms = vanishing_z_1_terms
-            .iter()
-            .chain(vanishing_partial_products_terms.iter())
-            .chain(vanishing_all_lookup_terms.iter())
-            .chain(constraint_terms);
-        let res = plonk_common::reduce_with_powers_multi(vanishing_terms, alphas);
-        res_batch.push(res);
-
-        vanishing_z_1_terms.clear();
-        vanishing_partial_products_terms.clear();
-        vanishing_all_lookup_terms.clear();
+        res_batch.push(eval_vanishing_poly_generic(
+            &mut BaseFieldBackend,
+            common_data,
+            &inputs,
+            constraint_terms,
+            &mut buffers,
+        ));
     }
     res_batch
 }
@@ -333,308 +407,155 @@ pub(crate) fn eval_vanishing_poly_base_batch<F: RichField + Extendable<D>, const
 /// Sum and LDC are broken down in partial polynomials to lower the constraint degree, similarly to the permutation argument.
 /// They also share the same partial SLDC polynomials, so that the last SLDC value is Sum(end) - LDC(end). The final constraint
 /// Sum(end) = LDC(end) becomes simply SLDC(end) = 0, and we can remove the LDC initial constraint.
-pub fn check_lookup_constraints<F: RichField + Extendable<D>, const D: usize>(
+///
+/// The constraints are appended to `constraints`. `lut_re_poly_evals` holds the evaluations of
+/// [`eval_lut_polys`] for this round's `deltas`.
+pub(crate) fn check_lookup_constraints<F, B, W, const D: usize>(
+    backend: &mut B,
     common_data: &CommonCircuitData<F, D>,
-    vars: EvaluationVars<F, D>,
-    local_lookup_zs: &[F::Extension],
-    next_lookup_zs: &[F::Extension],
-    lookup_selectors: &[F::Extension],
-    deltas: &[F; 4],
-) -> Vec<F::Extension> {
+    local_wires: &W,
+    local_lookup_zs: &[B::Value],
+    next_lookup_zs: &[B::Value],
+    lookup_selectors: &[B::Value],
+    deltas: &[B::Value],
+    lut_re_poly_evals: &[B::Value],
+    constraints: &mut Vec<B::Value>,
+) where
+    F: RichField + Extendable<D>,
+    B: VanishingPolyBackend<F>,
+    W: Index<usize, Output = B::Value> + ?Sized,
+{
     let num_lu_slots = LookupGate::num_slots(&common_data.config);
     let num_lut_slots = LookupTableGate::num_slots(&common_data.config);
     let lu_degree = common_data.quotient_degree_factor - 1;
     let num_sldc_polys = local_lookup_zs.len() - 1;
     let lut_degree = ceil_div_usize(num_lut_slots, num_sldc_polys);
 
-    let mut constraints = Vec::with_capacity(4 + common_data.luts.len() + 2 * num_sldc_polys);
+    constraints.reserve(4 + common_data.luts.len() + 2 * num_sldc_polys);
 
     // RE is the first polynomial stored.
     let z_re = local_lookup_zs[0];
     let next_z_re = next_lookup_zs[0];
 
-    // Partial Sums and LDCs are both stored in the remaining SLDC polynomials.
+    // Partial Sums and LDCs (i.e. the SLDC polynomials) are stored in the remaining polynomials.
     let z_x_lookup_sldcs = &local_lookup_zs[1..num_sldc_polys + 1];
     let z_gx_lookup_sldcs = &next_lookup_zs[1..num_sldc_polys + 1];
 
-    let delta_challenge_a = F::Extension::from(deltas[LookupChallenges::ChallengeA as usize]);
-    let delta_challenge_b = F::Extension::from(deltas[LookupChallenges::ChallengeB as usize]);
+    let challenge_a = deltas[LookupChallenges::ChallengeA as usize];
+    let challenge_b = deltas[LookupChallenges::ChallengeB as usize];
+    let challenge_alpha = deltas[LookupChallenges::ChallengeAlpha as usize];
+    let challenge_delta = deltas[LookupChallenges::ChallengeDelta as usize];
 
     // Compute all current looked and looking combos, i.e. the combos we need for the SLDC polynomials.
-    let current_looked_combos: Vec<F::Extension> = (0..num_lut_slots)
-        .map(|s| {
-            let input_wire = vars.local_wires[LookupTableGate::wire_ith_looked_inp(s)];
-            let output_wire = vars.local_wires[LookupTableGate::wire_ith_looked_out(s)];
-            input_wire + delta_challenge_a * output_wire
-        })
-        .collect();
-
-    let current_looking_combos: Vec<F::Extension> = (0..num_lu_slots)
-        .map(|s| {
-            let input_wire = vars.local_wires[LookupGate::wire_ith_looking_inp(s)];
-            let output_wire = vars.local_wires[LookupGate::wire_ith_looking_out(s)];
-            input_wire + delta_challenge_a * output_wire
-        })
-        .collect();
-
-    // Compute all current lookup combos, i.e. the combos used to check that the LUT is correct.
-    let current_lookup_combos: Vec<F::Extension> = (0..num_lut_slots)
+    let current_looked_combos = (0..num_lut_slots)
         .map(|s| {
-            let input_wire = vars.local_wires[LookupTableGate::wire_ith_looked_inp(s)];
-            let output_wire = vars.local_wires[LookupTableGate::wire_ith_looked_out(s)];
-            input_wire + delta_challenge_b * output_wire
+            let input_wire = local_wires[LookupTableGate::wire_ith_looked_inp(s)];
+            let output_wire = local_wires[LookupTableGate::wire_ith_looked_out(s)];
+            backend.mul_add(challenge_a, output_wire, input_wire)
         })
-        .collect();
-
-    // Check last LDC constraint.
-    constraints.push(
-        lookup_selectors[LookupSelectors::LastLdc as usize] * z_x_lookup_sldcs[num_sldc_polys - 1],
-    );
-
-    // Check initial Sum constraint.
-    constraints.push(lookup_selectors[LookupSelectors::InitSre as usize] * z_x_lookup_sldcs[0]);
-
-    // Check initial RE constraint.
-    constraints.push(lookup_selectors[LookupSelectors::InitSre as usize] * z_re);
-
-    let current_delta = deltas[LookupChallenges::ChallengeDelta as usize];
-
-    // Check final RE constraints for each different LUT.
-    for r in LookupSelectors::StartEnd as usize..common_data.num_lookup_selectors {
-        let cur_ends_selector = lookup_selectors[r];
-        let lut_row_number = ceil_div_usize(
-            common_data.luts[r - LookupSelectors::StartEnd as usize].len(),
-            num_lut_slots,
-        );
-        let cur_function_eval = get_lut_poly(
-            common_data,
-            r - LookupSelectors::StartEnd as usize,
-            deltas,
-            num_lut_slots * lut_row_number,
-        )
-        .eval(current_delta);
-
-        constraints.push(cur_ends_selector * (z_re - cur_function_eval.into()))
-    }
-
-    // Check RE row transition constraint.
-    let mut cur_sum = next_z_re;
-    for elt in &current_lookup_combos {
-        cur_sum =
-            cur_sum * F::Extension::from(deltas[LookupChallenges::ChallengeDelta as usize]) + *elt;
-    }
-    let unfiltered_re_line = z_re - cur_sum;
-
-    constraints.push(lookup_selectors[LookupSelectors::TransSre as usize] * unfiltered_re_line);
-
-    for poly in 0..num_sldc_polys {
-        // Compute prod(alpha - combo) for the current slot for Sum.
-        let lut_prod: F::Extension = (poly * lut_degree
-            ..min((poly + 1) * lut_degree, num_lut_slots))
-            .map(|i| {
-                F::Extension::from(deltas[LookupChallenges::ChallengeAlpha as usize])
-                    - current_looked_combos[i]
-            })
-            .product();
-
-        // Compute prod(alpha - combo) for the current slot for LDC.
-        let lu_prod: F::Extension = (poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots))
-            .map(|i| {
-                F::Extension::from(deltas[LookupChallenges::ChallengeAlpha as usize])
-                    - current_looking_combos[i]
-            })
-            .product();
-
-        // Function which computes, given index i: prod_{j!=i}(alpha - combo_j) for Sum.
-        let lut_prod_i = |i| {
-            (poly * lut_degree..min((poly + 1) * lut_degree, num_lut_slots))
-                .map(|j| {
-                    if j != i {
-                        F::Extension::from(deltas[LookupChallenges::ChallengeAlpha as usize])
-                            - current_looked_combos[j]
-                    } else {
-                        F::Extension::ONE
-                    }
-                })
-                .product()
-        };
-
-        // Function which computes, given index i: prod_{j!=i}(alpha - combo_j) for LDC.
-        let lu_prod_i = |i| {
-            (poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots))
-                .map(|j| {
-                    if j != i {
-                        F::Extension::from(deltas[LookupChallenges::ChallengeAlpha as usize])
-                            - current_looking_combos[j]
-                    } else {
-                        F::Extension::ONE
-                    }
-                })
-                .product()
-        };
-        // Compute sum_i(prod_{j!=i}(alpha - combo_j)) for LDC.
-        let lu_sum_prods = (poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots))
-            .fold(F::Extension::ZERO, |acc, i| acc + lu_prod_i(i));
-
-        // Compute sum_i(mul_i.prod_{j!=i}(alpha - combo_j)) for Sum.
-        let lut_sum_prods_with_mul = (poly * lut_degree
-            ..min((poly + 1) * lut_degree, num_lut_slots))
-            .fold(F::Extension::ZERO, |acc, i| {
-                acc + vars.local_wires[LookupTableGate::wire_ith_multiplicity(i)] * lut_prod_i(i)
-            });
-
-        // The previous element is the previous poly of the current row or the last poly of the next row.
-        let prev = if poly == 0 {
-            z_gx_lookup_sldcs[num_sldc_polys - 1]
-        } else {
-            z_x_lookup_sldcs[poly - 1]
-        };
-
-        // Check Sum row and col transitions. It's the same constraint, with a row transition happening for slot == 0.
-        let unfiltered_sum_transition =
-            lut_prod * (z_x_lookup_sldcs[poly] - prev) - lut_sum_prods_with_mul;
-        constraints
-            .push(lookup_selectors[LookupSelectors::TransSre as usize] * unfiltered_sum_transition);
-
-        // Check LDC row and col transitions. It's the same constraint, with a row transition happening for slot == 0.
-        let unfiltered_ldc_transition = lu_prod * (z_x_lookup_sldcs[poly] - prev) + lu_sum_prods;
-        constraints
-            .push(lookup_selectors[LookupSelectors::TransLdc as usize] * unfiltered_ldc_transition);
-    }
-
-    constraints
-}
-
-/// Same as `check_lookup_constraints`, but for the base field case.
-pub fn check_lookup_constraints_batch<F: RichField + Extendable<D>, const D: usize>(
-    common_data: &CommonCircuitData<F, D>,
-    vars: EvaluationVarsBase<F>,
-    local_lookup_zs: &[F],
-    next_lookup_zs: &[F],
-    lookup_selectors: &[F],
-    deltas: &[F; 4],
-    lut_re_poly_evals: &[F],
-) -> Vec<F> {
-    let num_lu_slots = LookupGate::num_slots(&common_data.config);
-    let num_lut_slots = LookupTableGate::num_slots(&common_data.config);
-    let lu_degree = common_data.quotient_degree_factor - 1;
-    let num_sldc_polys = local_lookup_zs.len() - 1;
-    let lut_degree = ceil_div_usize(num_lut_slots, num_sldc_polys);
-
-    let mut constraints = Vec::with_capacity(4 + common_data.luts.len() + 2 * num_sldc_polys);
-
-    // RE is the first polynomial stored.
-    let z_re = local_lookup_zs[0];
-    let next_z_re = next_lookup_zs[0];
-
-    // Partial Sums and LDCs are both stored in the remaining polynomials.
-    let z_x_lookup_sldcs = &local_lookup_zs[1..num_sldc_polys + 1];
-    let z_gx_lookup_sldcs = &next_lookup_zs[1..num_sldc_polys + 1];
-
-    // Compute all current looked and looking combos, i.e. the combos we need for the SLDC polynomials.
-    let current_looked_combos: Vec<F> = (0..num_lut_slots)
+        .collect::<Vec<_>>();
+    let current_looking_combos = (0..num_lu_slots)
         .map(|s| {
-            let input_wire = vars.local_wires[LookupTableGate::wire_ith_looked_inp(s)];
-            let output_wire = vars.local_wires[LookupTableGate::wire_ith_looked_out(s)];
-            input_wire + deltas[LookupChallenges::ChallengeA as usize] * output_wire
+            let input_wire = local_wires[LookupGate::wire_ith_looking_inp(s)];
+            let output_wire = local_wires[LookupGate::wire_ith_looking_out(s)];
+            backend.mul_add(challenge_a, output_wire, input_wire)
         })
-        .collect();
+        .collect::<Vec<_>>();
 
-    let current_looking_combos: Vec<F> = (0..num_lu_slots)
-        .map(|s| {
-            let input_wire = vars.local_wires[LookupGate::wire_ith_looking_inp(s)];
-            let output_wire = vars.local_wires[LookupGate::wire_ith_looking_out(s)];
-            input_wire + deltas[LookupChallenges::ChallengeA as usize] * output_wire
-        })
-        .collect();
+    // Compute `alpha - combo` for all looked and looking combos.
+    let current_lut_subs = current_looked_combos
+        .iter()
+        .map(|&combo| backend.sub(challenge_alpha, combo))
+        .collect::<Vec<_>>();
+    let current_lu_subs = current_looking_combos
+        .iter()
+        .map(|&combo| backend.sub(challenge_alpha, combo))
+        .collect::<Vec<_>>();
 
     // Compute all current lookup combos, i.e. the combos used to check that the LUT is correct.
-    let current_lookup_combos: Vec<F> = (0..num_lut_slots)
+    let current_lookup_combos = (0..num_lut_slots)
         .map(|s| {
-            let input_wire = vars.local_wires[LookupTableGate::wire_ith_looked_inp(s)];
-            let output_wire = vars.local_wires[LookupTableGate::wire_ith_looked_out(s)];
-            input_wire + deltas[LookupChallenges::ChallengeB as usize] * output_wire
+            let input_wire = local_wires[LookupTableGate::wire_ith_looked_inp(s)];
+            let output_wire = local_wires[LookupTableGate::wire_ith_looked_out(s)];
+            backend.mul_add(challenge_b, output_wire, input_wire)
         })
-        .collect();
+        .collect::<Vec<_>>();
 
     // Check last LDC constraint.
-    constraints.push(
-        lookup_selectors[LookupSelectors::LastLdc as usize] * z_x_lookup_sldcs[num_sldc_polys - 1],
-    );
+    constraints.push(backend.mul(
+        lookup_selectors[LookupSelectors::LastLdc as usize],
+        z_x_lookup_sldcs[num_sldc_polys - 1],
+    ));
 
     // Check initial Sum constraint.
-    constraints.push(lookup_selectors[LookupSelectors::InitSre as usize] * z_x_lookup_sldcs[0]);
+    constraints.push(backend.mul(
+        lookup_selectors[LookupSelectors::InitSre as usize],
+        z_x_lookup_sldcs[0],
+    ));
 
     // Check initial RE constraint.
-    constraints.push(lookup_selectors[LookupSelectors::InitSre as usize] * z_re);
+    constraints.push(backend.mul(lookup_selectors[LookupSelectors::InitSre as usize], z_re));
 
     // Check final RE constraints for each different LUT.
     for r in LookupSelectors::StartEnd as usize..common_data.num_lookup_selectors {
         let cur_ends_selector = lookup_selectors[r];
-
-        // Use the precomputed value for the lut poly evaluation
         let re_poly_eval = lut_re_poly_evals[r - LookupSelectors::StartEnd as usize];
-
-        constraints.push(cur_ends_selector * (z_re - re_poly_eval))
+        let cur_re = backend.sub(z_re, re_poly_eval);
+        constraints.push(backend.mul(cur_ends_selector, cur_re));
     }
 
     // Check RE row transition constraint.
     let mut cur_sum = next_z_re;
-    for elt in &current_lookup_combos {
-        cur_sum = cur_sum * deltas[LookupChallenges::ChallengeDelta as usize] + *elt;
+    for &elt in &current_lookup_combos {
+        cur_sum = backend.mul_add(cur_sum, challenge_delta, elt);
     }
-    let unfiltered_re_line = z_re - cur_sum;
+    let unfiltered_re_line = backend.sub(z_re, cur_sum);
 
-    constraints.push(lookup_selectors[LookupSelectors::TransSre as usize] * unfiltered_re_line);
+    constraints.push(backend.mul(
+        lookup_selectors[LookupSelectors::TransSre as usize],
+        unfiltered_re_line,
+    ));
 
     for poly in 0..num_sldc_polys {
+        let lut_range = poly * lut_degree..min((poly + 1) * lut_degree, num_lut_slots);
+        let lu_range = poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots);
+
         // Compute prod(alpha - combo) for the current slot for Sum.
-        let lut_prod: F = (poly * lut_degree..min((poly + 1) * lut_degree, num_lut_slots))
-            .map(|i| deltas[LookupChallenges::ChallengeAlpha as usize] - current_looked_combos[i])
-            .product();
+        let lut_prod = backend.mul_many(&current_lut_subs[lut_range.clone()]);
 
         // Compute prod(alpha - combo) for the current slot for LDC.
-        let lu_prod: F = (poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots))
-            .map(|i| deltas[LookupChallenges::ChallengeAlpha as usize] - current_looking_combos[i])
-            .product();
-
-        // Function which computes, given index i: prod_{j!=i}(alpha - combo_j) for Sum.
-        let lut_prod_i = |i| {
-            (poly * lut_degree..min((poly + 1) * lut_degree, num_lut_slots))
-                .map(|j| {
-                    if j != i {
-                        deltas[LookupChallenges::ChallengeAlpha as usize] - current_looked_combos[j]
-                    } else {
-                        F::ONE
-                    }
-                })
-                .product()
-        };
+        let lu_prod = backend.mul_many(&current_lu_subs[lu_range.clone()]);
 
-        // Function which computes, given index i: prod_{j!=i}(alpha - combo_j) for LDC.
-        let lu_prod_i = |i| {
-            (poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots))
-                .map(|j| {
-                    if j != i {
-                        deltas[LookupChallenges::ChallengeAlpha as usize]
-                            - current_looking_combos[j]
-                    } else {
-                        F::ONE
-                    }
-                })
-                .product()
-        };
+        let one = backend.one();
+        let zero = backend.zero();
 
         // Compute sum_i(prod_{j!=i}(alpha - combo_j)) for LDC.
-        let lu_sum_prods = (poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots))
-            .fold(F::ZERO, |acc, i| acc + lu_prod_i(i));
+        let mut lu_sum_prods = zero;
+        for i in lu_range.clone() {
+            let mut prod_i = one;
+            for j in lu_range.clone() {
+                if j != i {
+                    prod_i = backend.mul(prod_i, current_lu_subs[j]);
+                }
+            }
+            lu_sum_prods = backend.add(lu_sum_prods, prod_i);
+        }
 
         // Compute sum_i(mul_i.prod_{j!=i}(alpha - combo_j)) for Sum.
-        let lut_sum_prods_with_mul = (poly * lut_degree
-            ..min((poly + 1) * lut_degree, num_lut_slots))
-            .fold(F::ZERO, |acc, i| {
-                acc + vars.local_wires[LookupTableGate::wire_ith_multiplicity(i)] * lut_prod_i(i)
-            });
+        let mut lut_sum_prods_with_mul = zero;
+        for i in lut_range.clone() {
+            let mut prod_i = one;
+            for j in lut_range.clone() {
+                if j != i {
+                    prod_i = backend.mul(prod_i, current_lut_subs[j]);
+                }
+            }
+            lut_sum_prods_with_mul = backend.mul_add(
+                prod_i,
+                local_wires[LookupTableGate::wire_ith_multiplicity(i)],
+                lut_sum_prods_with_mul,
+            );
+        }
 
         // The previous element is the previous poly of the current row or the last poly of the next row.
         let prev = if poly == 0 {
@@ -643,18 +564,22 @@ pub fn check_lookup_constraints_batch<F: RichField + Extendable<D>, const D: usi
             z_x_lookup_sldcs[poly - 1]
         };
 
+        let cur_sub = backend.sub(z_x_lookup_sldcs[poly], prev);
+
         // Check Sum row and col transitions. It's the same constraint, with a row transition happening for slot == 0.
-        let unfiltered_sum_transition =
-            lut_prod * (z_x_lookup_sldcs[poly] - prev) - lut_sum_prods_with_mul;
-        constraints
-            .push(lookup_selectors[LookupSelectors::TransSre as usize] * unfiltered_sum_transition);
+        let unfiltered_sum_transition = backend.mul_sub(lut_prod, cur_sub, lut_sum_prods_with_mul);
+        constraints.push(backend.mul(
+            lookup_selectors[LookupSelectors::TransSre as usize],
+            unfiltered_sum_transition,
+        ));
 
         // Check LDC row and col transitions. It's the same constraint, with a row transition happening for slot == 0.
-        let unfiltered_ldc_transition = lu_prod * (z_x_lookup_sldcs[poly] - prev) + lu_sum_prods;
-        constraints
-            .push(lookup_selectors[LookupSelectors::TransLdc as usize] * unfiltered_ldc_transition);
+        let unfiltered_ldc_transition = backend.mul_add(lu_prod, cur_sub, lu_sum_prods);
+        constraints.push(backend.mul(
+            lookup_selectors[LookupSelectors::TransLdc as usize],
+            unfiltered_ldc_transition,
+        ));
     }
-    constraints
 }
 
 /// Evaluates all gate constraints.
@@ -729,19 +654,21 @@ pub fn evaluate_gate_constraints_circuit<F: RichField + Extendable<D>, const D:
     let mut all_gate_constraints = vec![builder.zero_extension(); common_data.num_gate_constraints];
     for (i, gate) in common_data.gates.iter().enumerate() {
         let selector_index = common_data.selectors_info.selector_indices[i];
-        with_context!(
-            builder,
+        builder.with_context(
+            Level::Debug,
             &format!("evaluate {} constraints", gate.0.id()),
-            gate.0.eval_filtered_circuit(
-                builder,
-                vars,
-                i,
-                selector_index,
-                common_data.selectors_info.groups[selector_index].clone(),
-                common_data.selectors_info.num_selectors(),
-                common_data.num_lookup_selectors,
-                &mut all_gate_constraints,
-            )
+            |builder| {
+                gate.0.eval_filtered_circuit(
+                    builder,
+                    vars,
+                    i,
+                    selector_index,
+                    common_data.selectors_info.groups[selector_index].clone(),
+                    common_data.selectors_info.num_selectors(),
+                    common_data.num_lookup_selectors,
+                    &mut all_gate_constraints,
+                )
+            },
         );
     }
     all_gate_constraints
@@ -777,6 +704,21 @@ pub(crate) fn get_lut_poly_circuit<F: RichField + Extendable<D>, const D: usize>
         })
 }
 
+/// Same as `eval_lut_polys`, but for the recursive case.
+fn eval_lut_polys_circuit<F: RichField + Extendable<D>, const D: usize>(
+    builder: &mut CircuitBuilder<F, D>,
+    common_data: &CommonCircuitData<F, D>,
+    deltas: &[Target],
+) -> Vec<ExtensionTarget<D>> {
+    (0..common_data.luts.len())
+        .map(|lut_index| {
+            let degree = lut_poly_degree(common_data, lut_index);
+            let eval = get_lut_poly_circuit(builder, common_data, lut_index, deltas, degree);
+            builder.convert_to_ext(eval)
+        })
+        .collect()
+}
+
 /// Evaluate the vanishing polynomial at `x`. In this context, the vanishing polynomial is a random
 /// linear combination of gate constraints, plus some other terms relating to the permutation
 /// argument. All such terms should vanish on `H`.
@@ -801,330 +743,321 @@ pub(crate) fn eval_vanishing_poly_circuit<F: RichField + Extendable<D>, const D:
     deltas: &[Target],
 ) -> Vec<ExtensionTarget<D>> {
     let has_lookup = common_data.num_lookup_polys != 0;
-    let max_degree = common_data.quotient_degree_factor;
-    let num_prods = common_data.num_partial_products;
 
-    let constraint_terms = with_context!(
-        builder,
-        "evaluate gate constraints",
-        evaluate_gate_constraints_circuit::<F, D>(builder, common_data, vars,)
-    );
+    let constraint_terms =
+        builder.with_context(Level::Debug, "evaluate gate constraints", |builder| {
+            evaluate_gate_constraints_circuit::<F, D>(builder, common_data, vars)
+        });
 
     let lookup_selectors = &vars.local_constants[common_data.selectors_info.num_selectors()
         ..common_data.selectors_info.num_selectors() + common_data.num_lookup_selectors];
 
-    // The L_0(x) (Z(x) - 1) vanishing terms.
-    let mut vanishing_z_1_terms = Vec::new();
+    let l_0_x = eval_l_0_circuit(builder, common_data.degree(), x, x_pow_deg);
 
-    // The terms checking lookup constraints.
-    let mut vanishing_all_lookup_terms = if has_lookup {
-        let num_sldc_polys = common_data.num_lookup_polys - 1;
-        Vec::with_capacity(
-            common_data.config.num_challenges * (4 + common_data.luts.len() + 2 * num_sldc_polys),
-        )
+    let lut_re_poly_evals = if has_lookup {
+        deltas
+            .chunks(NUM_COINS_LOOKUP)
+            .flat_map(|cur_deltas| eval_lut_polys_circuit(builder, common_data, cur_deltas))
+            .collect()
     } else {
         Vec::new()
     };
 
-    // The terms checking the partial products.
-    let mut vanishing_partial_products_terms = Vec::new();
-
-    let l_0_x = eval_l_0_circuit(builder, common_data.degree(), x, x_pow_deg);
+    let mut to_ext = |ts: &[Target]| {
+        ts.iter()
+            .map(|&t| builder.convert_to_ext(t))
+            .collect::<Vec<_>>()
+    };
+    let betas = to_ext(betas);
+    let gammas = to_ext(gammas);
+    let alphas = to_ext(alphas);
+    let deltas = to_ext(deltas);
+
+    let inputs = VanishingPolyInputs {
+        x,
+        l_0_x,
+        local_wires: vars.local_wires,
+        lookup_selectors,
+        local_zs,
+        next_zs,
+        local_lookup_zs,
+        next_lookup_zs,
+        partial_products,
+        s_sigmas,
+        betas: &betas,
+        gammas: &gammas,
+        alphas: &alphas,
+        deltas: &deltas,
+        lut_re_poly_evals: &lut_re_poly_evals,
+    };
+    eval_vanishing_poly_generic(
+        builder,
+        common_data,
+        &inputs,
+        &constraint_terms,
+        &mut VanishingPolyBuffers::new(),
+    )
+}
 
-    // Holds `k[i] * x`.
-    let mut s_ids = Vec::with_capacity(common_data.config.num_routed_wires);
-    for j in 0..common_data.config.num_routed_wires {
-        let k = builder.constant(common_data.k_is[j]);
-        s_ids.push(builder.scalar_mul_ext(k, x));
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::sync::Arc;
+    #[cfg(feature = "std")]
+    use std::sync::Arc;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::extension::FieldExtension;
+    use crate::field::types::Sample;
+    use crate::hash::hash_types::HashOut;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::util::log2_ceil;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+    type FF = <C as GenericConfig<D>>::FE;
+
+    /// The data of a circuit with a lookup, so that the vanishing polynomial has all its kinds of
+    /// terms.
+    fn common_data() -> CommonCircuitData<F, D> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let table = Arc::new((0..10).map(|i| (i, i * i)).collect());
+        let table_index = builder.add_lookup_table_from_pairs(table);
+        let x = builder.add_virtual_target();
+        let y = builder.add_lookup_from_index(x, table_index);
+        builder.register_public_input(y);
+        let common_data = builder.build::<C>().common;
+        assert_ne!(common_data.num_lookup_polys, 0);
+        common_data
     }
 
-    for i in 0..common_data.config.num_challenges {
-        let z_x = local_zs[i];
-        let z_gx = next_zs[i];
-
-        // L_0(x) (Z(x) - 1) = 0.
-        vanishing_z_1_terms.push(builder.mul_sub_extension(l_0_x, z_x, l_0_x));
-
-        // If there are lookups in the circuit, then we add the lookup constraints
-        if has_lookup {
-            let cur_local_lookup_zs = &local_lookup_zs
-                [common_data.num_lookup_polys * i..common_data.num_lookup_polys * (i + 1)];
-            let cur_next_lookup_zs = &next_lookup_zs
-                [common_data.num_lookup_polys * i..common_data.num_lookup_polys * (i + 1)];
-
-            let cur_deltas = &deltas[NUM_COINS_LOOKUP * i..NUM_COINS_LOOKUP * (i + 1)];
+    /// Openings of all the polynomials which the vanishing polynomial depends on.
+    struct Openings<T> {
+        local_constants: Vec<T>,
+        local_wires: Vec<T>,
+        local_zs: Vec<T>,
+        next_zs: Vec<T>,
+        local_lookup_zs: Vec<T>,
+        next_lookup_zs: Vec<T>,
+        partial_products: Vec<T>,
+        s_sigmas: Vec<T>,
+    }
 
-            let lookup_constraints = check_lookup_constraints_circuit(
-                builder,
-                common_data,
-                vars,
-                cur_local_lookup_zs,
-                cur_next_lookup_zs,
-                lookup_selectors,
-                cur_deltas,
-            );
-            vanishing_all_lookup_terms.extend(lookup_constraints);
+    impl<T: Sample> Openings<T> {
+        fn rand(common_data: &CommonCircuitData<F, D>) -> Self {
+            let num_challenges = common_data.config.num_challenges;
+            Self {
+                local_constants: T::rand_vec(common_data.num_constants),
+                local_wires: T::rand_vec(common_data.config.num_wires),
+                local_zs: T::rand_vec(num_challenges),
+                next_zs: T::rand_vec(num_challenges),
+                local_lookup_zs: T::rand_vec(num_challenges * common_data.num_lookup_polys),
+                next_lookup_zs: T::rand_vec(num_challenges * common_data.num_lookup_polys),
+                partial_products: T::rand_vec(num_challenges * common_data.num_partial_products),
+                s_sigmas: T::rand_vec(common_data.config.num_routed_wires),
+            }
         }
+    }
 
-        let mut numerator_values = Vec::with_capacity(common_data.config.num_routed_wires);
-        let mut denominator_values = Vec::with_capacity(common_data.config.num_routed_wires);
-
-        for j in 0..common_data.config.num_routed_wires {
-            let wire_value = vars.local_wires[j];
-            let beta_ext = builder.convert_to_ext(betas[i]);
-            let gamma_ext = builder.convert_to_ext(gammas[i]);
-
-            // The numerator is `beta * s_id + wire_value + gamma`, and the denominator is
-            // `beta * s_sigma + wire_value + gamma`.
-            let wire_value_plus_gamma = builder.add_extension(wire_value, gamma_ext);
-            let numerator = builder.mul_add_extension(beta_ext, s_ids[j], wire_value_plus_gamma);
-            let denominator =
-                builder.mul_add_extension(beta_ext, s_sigmas[j], wire_value_plus_gamma);
-            numerator_values.push(numerator);
-            denominator_values.push(denominator);
+    impl Openings<F> {
+        fn to_ext(&self) -> Openings<FF> {
+            let to_ext = |xs: &[F]| xs.iter().map(|&x| x.into()).collect();
+            Openings {
+                local_constants: to_ext(&self.local_constants),
+                local_wires: to_ext(&self.local_wires),
+                local_zs: to_ext(&self.local_zs),
+                next_zs: to_ext(&self.next_zs),
+                local_lookup_zs: to_ext(&self.local_lookup_zs),
+                next_lookup_zs: to_ext(&self.next_lookup_zs),
+                partial_products: to_ext(&self.partial_products),
+                s_sigmas: to_ext(&self.s_sigmas),
+            }
         }
-
-        // The partial products considered for this iteration of `i`.
-        let current_partial_products = &partial_products[i * num_prods..(i + 1) * num_prods];
-        // Check the quotient partial products.
-        let partial_product_checks = check_partial_products_circuit(
-            builder,
-            &numerator_values,
-            &denominator_values,
-            current_partial_products,
-            z_x,
-            z_gx,
-            max_degree,
-        );
-        vanishing_partial_products_terms.extend(partial_product_checks);
     }
 
-    let vanishing_terms = [
-        vanishing_z_1_terms,
-        vanishing_partial_products_terms,
-        vanishing_all_lookup_terms,
-        constraint_terms,
-    ]
-    .concat();
-
-    alphas
-        .iter()
-        .map(|&alpha| {
-            let alpha = builder.convert_to_ext(alpha);
-            let mut alpha = ReducingFactorTarget::new(alpha);
-            alpha.reduce(&vanishing_terms, builder)
-        })
-        .collect()
-}
-
-/// Same as `check_lookup_constraints`, but for the recursive case.
-pub fn check_lookup_constraints_circuit<F: RichField + Extendable<D>, const D: usize>(
-    builder: &mut CircuitBuilder<F, D>,
-    common_data: &CommonCircuitData<F, D>,
-    vars: EvaluationTargets<D>,
-    local_lookup_zs: &[ExtensionTarget<D>],
-    next_lookup_zs: &[ExtensionTarget<D>],
-    lookup_selectors: &[ExtensionTarget<D>],
-    deltas: &[Target],
-) -> Vec<ExtensionTarget<D>> {
-    let num_lu_slots = LookupGate::num_slots(&common_data.config);
-    let num_lut_slots = LookupTableGate::num_slots(&common_data.config);
-    let lu_degree = common_data.quotient_degree_factor - 1;
-    let num_sldc_polys = local_lookup_zs.len() - 1;
-    let lut_degree = ceil_div_usize(num_lut_slots, num_sldc_polys);
-
-    let mut constraints = Vec::with_capacity(4 + common_data.luts.len() + 2 * num_sldc_polys);
-
-    // RE is the first polynomial stored.
-    let z_re = local_lookup_zs[0];
-    let next_z_re = next_lookup_zs[0];
-
-    // Partial Sums and LDCs (i.e. the SLDC polynomials) are stored in the remaining polynomials.
-    let z_x_lookup_sldcs = &local_lookup_zs[1..num_sldc_polys + 1];
-    let z_gx_lookup_sldcs = &next_lookup_zs[1..num_sldc_polys + 1];
-
-    // Convert deltas to ExtensionTargets.
-    let ext_deltas = deltas
-        .iter()
-        .map(|d| builder.convert_to_ext(*d))
-        .collect::<Vec<_>>();
-
-    // Computing all current looked and looking combos, i.e. the combos we need for the SLDC polynomials.
-    let current_looked_combos = (0..num_lut_slots)
-        .map(|s| {
-            let input_wire = vars.local_wires[LookupTableGate::wire_ith_looked_inp(s)];
-            let output_wire = vars.local_wires[LookupTableGate::wire_ith_looked_out(s)];
-            builder.mul_add_extension(
-                ext_deltas[LookupChallenges::ChallengeA as usize],
-                output_wire,
-                input_wire,
-            )
-        })
-        .collect::<Vec<_>>();
-    let current_looking_combos = (0..num_lu_slots)
-        .map(|s| {
-            let input_wire = vars.local_wires[LookupGate::wire_ith_looking_inp(s)];
-            let output_wire = vars.local_wires[LookupGate::wire_ith_looking_out(s)];
-            builder.mul_add_extension(
-                ext_deltas[LookupChallenges::ChallengeA as usize],
-                output_wire,
-                input_wire,
-            )
-        })
-        .collect::<Vec<_>>();
-
-    let current_lut_subs = (0..num_lut_slots)
-        .map(|s| {
-            builder.sub_extension(
-                ext_deltas[LookupChallenges::ChallengeAlpha as usize],
-                current_looked_combos[s],
-            )
-        })
-        .collect::<Vec<_>>();
-
-    let current_lu_subs = (0..num_lu_slots)
-        .map(|s| {
-            builder.sub_extension(
-                ext_deltas[LookupChallenges::ChallengeAlpha as usize],
-                current_looking_combos[s],
-            )
-        })
-        .collect::<Vec<_>>();
-
-    // Computing all current lookup combos, i.e. the combos used to check that the LUT is correct.
-    let current_lookup_combos = (0..num_lut_slots)
-        .map(|s| {
-            let input_wire = vars.local_wires[LookupTableGate::wire_ith_looked_inp(s)];
-            let output_wire = vars.local_wires[LookupTableGate::wire_ith_looked_out(s)];
-            builder.mul_add_extension(
-                ext_deltas[LookupChallenges::ChallengeB as usize],
-                output_wire,
-                input_wire,
-            )
-        })
-        .collect::<Vec<_>>();
-
-    // Check last LDC constraint.
-    constraints.push(builder.mul_extension(
-        lookup_selectors[LookupSelectors::LastLdc as usize],
-        z_x_lookup_sldcs[num_sldc_polys - 1],
-    ));
+    struct Challenges {
+        betas: Vec<F>,
+        gammas: Vec<F>,
+        alphas: Vec<F>,
+        deltas: Vec<F>,
+        public_inputs_hash: HashOut<F>,
+    }
 
-    // Check initial Sum constraint.
-    constraints.push(builder.mul_extension(
-        lookup_selectors[LookupSelectors::InitSre as usize],
-        z_x_lookup_sldcs[0],
-    ));
+    impl Challenges {
+        fn rand(common_data: &CommonCircuitData<F, D>) -> Self {
+            let num_challenges = common_data.config.num_challenges;
+            Self {
+                betas: F::rand_vec(num_challenges),
+                gammas: F::rand_vec(num_challenges),
+                alphas: F::rand_vec(num_challenges),
+                deltas: F::rand_vec(num_challenges * NUM_COINS_LOOKUP),
+                public_inputs_hash: HashOut::rand(),
+            }
+        }
+    }
 
-    // Check initial RE constraint.
-    constraints
-        .push(builder.mul_extension(lookup_selectors[LookupSelectors::InitSre as usize], z_re));
+    fn eval_native(
+        common_data: &CommonCircuitData<F, D>,
+        x: FF,
+        openings: &Openings<FF>,
+        challenges: &Challenges,
+    ) -> Vec<FF> {
+        let vars = EvaluationVars {
+            local_constants: &openings.local_constants,
+            local_wires: &openings.local_wires,
+            public_inputs_hash: &challenges.public_inputs_hash,
+            extra_openings: &[],
+        };
+        eval_vanishing_poly::<F, D>(
+            common_data,
+            x,
+            vars,
+            &openings.local_zs,
+            &openings.next_zs,
+            &openings.local_lookup_zs,
+            &openings.next_lookup_zs,
+            &openings.partial_products,
+            &openings.s_sigmas,
+            &challenges.betas,
+            &challenges.gammas,
+            &challenges.alphas,
+            &challenges.deltas,
+        )
+    }
 
-    // Check final RE constraints for each different LUT.
-    for r in LookupSelectors::StartEnd as usize..common_data.num_lookup_selectors {
-        let cur_ends_selectors = lookup_selectors[r];
-        let lut_row_number = ceil_div_usize(
-            common_data.luts[r - LookupSelectors::StartEnd as usize].len(),
-            num_lut_slots,
+    #[test]
+    fn test_base_batch_matches_native() {
+        let common_data = common_data();
+        let openings = Openings::<F>::rand(&common_data);
+        let challenges = Challenges::rand(&common_data);
+
+        // A point of the prover's LDE coset.
+        let quotient_degree_bits = log2_ceil(common_data.quotient_degree_factor);
+        let index = 5;
+        let x = F::coset_shift()
+            * F::primitive_root_of_unity(common_data.degree_bits() + quotient_degree_bits)
+                .exp_u64(index as u64);
+        let z_h_on_coset = ZeroPolyOnCoset::new(common_data.degree_bits(), quotient_degree_bits);
+
+        let vars_batch = EvaluationVarsBaseBatch::new(
+            1,
+            &openings.local_constants,
+            &openings.local_wires,
+            &challenges.public_inputs_hash,
         );
-        let cur_function_eval = get_lut_poly_circuit(
-            builder,
-            common_data,
-            r - LookupSelectors::StartEnd as usize,
-            deltas,
-            num_lut_slots * lut_row_number,
+        let lut_re_poly_evals = challenges
+            .deltas
+            .chunks(NUM_COINS_LOOKUP)
+            .map(|deltas| eval_lut_polys(&common_data, deltas))
+            .collect::<Vec<_>>();
+        let lut_re_poly_evals_refs = lut_re_poly_evals
+            .iter()
+            .map(|v| v.as_slice())
+            .collect::<Vec<_>>();
+        let batch = eval_vanishing_poly_base_batch::<F, D>(
+            &common_data,
+            &[index],
+            &[x],
+            vars_batch,
+            &[&openings.local_zs],
+            &[&openings.next_zs],
+            &[&openings.local_lookup_zs],
+            &[&openings.next_lookup_zs],
+            &[&openings.partial_products],
+            &[&openings.s_sigmas],
+            &challenges.betas,
+            &challenges.gammas,
+            &challenges.deltas,
+            &challenges.alphas,
+            &z_h_on_coset,
+            &lut_re_poly_evals_refs,
         );
-        let cur_function_eval_ext = builder.convert_to_ext(cur_function_eval);
 
-        let cur_re = builder.sub_extension(z_re, cur_function_eval_ext);
-        constraints.push(builder.mul_extension(cur_ends_selectors, cur_re));
+        let native = eval_native(&common_data, x.into(), &openings.to_ext(), &challenges);
+        let batch = batch[0].iter().map(|&v| v.into()).collect::<Vec<FF>>();
+        assert_eq!(batch, native);
     }
 
-    // Check RE row transition constraint.
-    let mut cur_sum = next_z_re;
-    for elt in &current_lookup_combos {
-        cur_sum = builder.mul_add_extension(
-            cur_sum,
-            ext_deltas[LookupChallenges::ChallengeDelta as usize],
-            *elt,
+    #[test]
+    fn test_circuit_matches_native() -> Result<()> {
+        let common_data = common_data();
+        let openings = Openings::<FF>::rand(&common_data);
+        let challenges = Challenges::rand(&common_data);
+        let x = FF::rand();
+        let native = eval_native(&common_data, x, &openings, &challenges);
+
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let mut pw = PartialWitness::new();
+        let mut add_ext = |builder: &mut CircuitBuilder<F, D>, values: &[FF]| {
+            let targets = builder.add_virtual_extension_targets(values.len());
+            pw.set_extension_targets(&targets, values);
+            targets
+        };
+        let x_t = add_ext(&mut builder, &[x])[0];
+        let local_constants = add_ext(&mut builder, &openings.local_constants);
+        let local_wires = add_ext(&mut builder, &openings.local_wires);
+        let local_zs = add_ext(&mut builder, &openings.local_zs);
+        let next_zs = add_ext(&mut builder, &openings.next_zs);
+        let local_lookup_zs = add_ext(&mut builder, &openings.local_lookup_zs);
+        let next_lookup_zs = add_ext(&mut builder, &openings.next_lookup_zs);
+        let partial_products = add_ext(&mut builder, &openings.partial_products);
+        let s_sigmas = add_ext(&mut builder, &openings.s_sigmas);
+
+        let mut add = |builder: &mut CircuitBuilder<F, D>, values: &[F]| {
+            let targets = builder.add_virtual_targets(values.len());
+            pw.set_target_arr(&targets, values);
+            targets
+        };
+        let betas = add(&mut builder, &challenges.betas);
+        let gammas = add(&mut builder, &challenges.gammas);
+        let alphas = add(&mut builder, &challenges.alphas);
+        let deltas = add(&mut builder, &challenges.deltas);
+        let public_inputs_hash = builder.add_virtual_hash();
+        pw.set_hash_target(public_inputs_hash, challenges.public_inputs_hash);
+
+        let vars = EvaluationTargets {
+            local_constants: &local_constants,
+            local_wires: &local_wires,
+            public_inputs_hash: &public_inputs_hash,
+            extra_openings: &[],
+        };
+        let x_pow_deg = builder.exp_power_of_2_extension(x_t, common_data.degree_bits());
+        let vanishing_polys = eval_vanishing_poly_circuit::<F, D>(
+            &mut builder,
+            &common_data,
+            x_t,
+            x_pow_deg,
+            vars,
+            &local_zs,
+            &next_zs,
+            &local_lookup_zs,
+            &next_lookup_zs,
+            &partial_products,
+            &s_sigmas,
+            &betas,
+            &gammas,
+            &alphas,
+            &deltas,
         );
-    }
-    let unfiltered_re_line = builder.sub_extension(z_re, cur_sum);
-
-    constraints.push(builder.mul_extension(
-        lookup_selectors[LookupSelectors::TransSre as usize],
-        unfiltered_re_line,
-    ));
-
-    for poly in 0..num_sldc_polys {
-        // Compute prod(alpha - combo) for the current slot for Sum.
-        let mut lut_prod = builder.one_extension();
-        for i in poly * lut_degree..min((poly + 1) * lut_degree, num_lut_slots) {
-            lut_prod = builder.mul_extension(lut_prod, current_lut_subs[i]);
-        }
-
-        // Compute prod(alpha - combo) for the current slot for LDC.
-        let mut lu_prod = builder.one_extension();
-        for i in poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots) {
-            lu_prod = builder.mul_extension(lu_prod, current_lu_subs[i]);
+        for v in &vanishing_polys {
+            builder.register_public_inputs(&v.0);
         }
 
-        let one = builder.one_extension();
-        let zero = builder.zero_extension();
-
-        // Compute sum_i(prod_{j!=i}(alpha - combo_j)) for LDC.
-        let lu_sum_prods =
-            (poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots)).fold(zero, |acc, i| {
-                let mut prod_i = one;
-
-                for j in poly * lu_degree..min((poly + 1) * lu_degree, num_lu_slots) {
-                    if j != i {
-                        prod_i = builder.mul_extension(prod_i, current_lu_subs[j]);
-                    }
-                }
-                builder.add_extension(acc, prod_i)
-            });
-
-        // Compute sum_i(mul_i.prod_{j!=i}(alpha - combo_j)) for Sum.
-        let lut_sum_prods_mul = (poly * lut_degree..min((poly + 1) * lut_degree, num_lut_slots))
-            .fold(zero, |acc, i| {
-                let mut prod_i = one;
-
-                for j in poly * lut_degree..min((poly + 1) * lut_degree, num_lut_slots) {
-                    if j != i {
-                        prod_i = builder.mul_extension(prod_i, current_lut_subs[j]);
-                    }
-                }
-                builder.mul_add_extension(
-                    prod_i,
-                    vars.local_wires[LookupTableGate::wire_ith_multiplicity(i)],
-                    acc,
-                )
-            });
-
-        // The previous element is the previous poly of the current row or the last poly of the next row.
-        let prev = if poly == 0 {
-            z_gx_lookup_sldcs[num_sldc_polys - 1]
-        } else {
-            z_x_lookup_sldcs[poly - 1]
-        };
-
-        let cur_sub = builder.sub_extension(z_x_lookup_sldcs[poly], prev);
-
-        // Check sum row and col transitions. It's the same constraint, with a row transition happening for slot == 0.
-        let unfiltered_sum_transition =
-            builder.mul_sub_extension(lut_prod, cur_sub, lut_sum_prods_mul);
-        constraints.push(builder.mul_extension(
-            lookup_selectors[LookupSelectors::TransSre as usize],
-            unfiltered_sum_transition,
-        ));
-
-        // Check ldc row and col transitions. It's the same constraint, with a row transition happening for slot == 0.
-        let unfiltered_ldc_transition = builder.mul_add_extension(lu_prod, cur_sub, lu_sum_prods);
-        constraints.push(builder.mul_extension(
-            lookup_selectors[LookupSelectors::TransLdc as usize],
-            unfiltered_ldc_transition,
-        ));
+        let data = builder.build::<C>();
+        let proof = data.prove(pw)?;
+        let circuit = proof
+            .public_inputs
+            .chunks(D)
+            .map(|c| {
+                <FF as FieldExtension<D>>::from_basefield_array(core::array::from_fn(|i| c[i]))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(circuit, native);
+        data.verify(proof)
     }
-    constraints
 }
@@ -0,0 +1,260 @@
+//! The arithmetic which the vanishing polynomial is evaluated with.
+//!
+//! The prover evaluates the vanishing polynomial over base field points of its LDE, the verifier
+//! at an extension field challenge, and the recursive verifier in a circuit. All three share the
+//! formula in [`vanishing_poly`](crate::plonk::vanishing_poly), which is written against the
+//! [`VanishingPolyBackend`] trait below, so that they can't drift apart.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::field::extension::{Extendable, FieldExtension};
+use crate::field::types::Field;
+use crate::hash::hash_types::RichField;
+use crate::iop::ext_target::ExtensionTarget;
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::plonk_common;
+use crate::util::reducing::ReducingFactorTarget;
+
+/// The operations needed to evaluate the vanishing polynomial over some values, which are field
+/// elements for native evaluations and targets in circuits.
+pub(crate) trait VanishingPolyBackend<F: Field> {
+    type Value: Copy;
+
+    fn zero(&mut self) -> Self::Value;
+
+    fn one(&mut self) -> Self::Value;
+
+    fn add(&mut self, a: Self::Value, b: Self::Value) -> Self::Value;
+
+    fn sub(&mut self, a: Self::Value, b: Self::Value) -> Self::Value;
+
+    fn mul(&mut self, a: Self::Value, b: Self::Value) -> Self::Value;
+
+    /// Returns `a * b + c`.
+    fn mul_add(&mut self, a: Self::Value, b: Self::Value, c: Self::Value) -> Self::Value;
+
+    /// Returns `a * b - c`.
+    fn mul_sub(&mut self, a: Self::Value, b: Self::Value, c: Self::Value) -> Self::Value;
+
+    /// Multiplies `x` by the constant `c`.
+    fn scalar_mul(&mut self, c: F, x: Self::Value) -> Self::Value;
+
+    /// Pushes `c * x + a` to `out` for each constant `c` in `cs` and the matching `a` in `addends`.
+    fn scalar_mul_add_many(
+        &mut self,
+        cs: &[F],
+        x: Self::Value,
+        addends: &[Self::Value],
+        out: &mut Vec<Self::Value>,
+    ) {
+        for (&c, &a) in cs.iter().zip(addends) {
+            let product = self.scalar_mul(c, x);
+            out.push(self.add(product, a));
+        }
+    }
+
+    /// Returns the product of `terms`, which is one if there are none.
+    fn mul_many(&mut self, terms: &[Self::Value]) -> Self::Value {
+        let one = self.one();
+        terms.iter().fold(one, |acc, &t| self.mul(acc, t))
+    }
+
+    /// For each alpha in `alphas`, computes `sum_i alpha^i terms[i]`.
+    fn reduce_with_powers_multi(
+        &mut self,
+        terms: &[Self::Value],
+        alphas: &[Self::Value],
+    ) -> Vec<Self::Value>;
+}
+
+/// Evaluates over base field values, as the prover does on its LDE.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct BaseFieldBackend;
+
+impl<F: Field> VanishingPolyBackend<F> for BaseFieldBackend {
+    type Value = F;
+
+    fn zero(&mut self) -> F {
+        F::ZERO
+    }
+
+    fn one(&mut self) -> F {
+        F::ONE
+    }
+
+    fn add(&mut self, a: F, b: F) -> F {
+        a + b
+    }
+
+    fn sub(&mut self, a: F, b: F) -> F {
+        a - b
+    }
+
+    fn mul(&mut self, a: F, b: F) -> F {
+        a * b
+    }
+
+    fn mul_add(&mut self, a: F, b: F, c: F) -> F {
+        a * b + c
+    }
+
+    fn mul_sub(&mut self, a: F, b: F, c: F) -> F {
+        a * b - c
+    }
+
+    fn scalar_mul(&mut self, c: F, x: F) -> F {
+        c * x
+    }
+
+    fn mul_many(&mut self, terms: &[F]) -> F {
+        terms.iter().copied().product()
+    }
+
+    fn reduce_with_powers_multi(&mut self, terms: &[F], alphas: &[F]) -> Vec<F> {
+        plonk_common::reduce_with_powers_multi(terms, alphas)
+    }
+}
+
+/// Evaluates over extension field values, as the verifier does at its challenge point.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct ExtensionFieldBackend<const D: usize>(PhantomData<[(); D]>);
+
+impl<const D: usize> ExtensionFieldBackend<D> {
+    pub(crate) const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> VanishingPolyBackend<F>
+    for ExtensionFieldBackend<D>
+{
+    type Value = F::Extension;
+
+    fn zero(&mut self) -> F::Extension {
+        F::Extension::ZERO
+    }
+
+    fn one(&mut self) -> F::Extension {
+        F::Extension::ONE
+    }
+
+    fn add(&mut self, a: F::Extension, b: F::Extension) -> F::Extension {
+        a + b
+    }
+
+    fn sub(&mut self, a: F::Extension, b: F::Extension) -> F::Extension {
+        a - b
+    }
+
+    fn mul(&mut self, a: F::Extension, b: F::Extension) -> F::Extension {
+        a * b
+    }
+
+    fn mul_add(&mut self, a: F::Extension, b: F::Extension, c: F::Extension) -> F::Extension {
+        a * b + c
+    }
+
+    fn mul_sub(&mut self, a: F::Extension, b: F::Extension, c: F::Extension) -> F::Extension {
+        a * b - c
+    }
+
+    fn scalar_mul(&mut self, c: F, x: F::Extension) -> F::Extension {
+        x.scalar_mul(c)
+    }
+
+    fn mul_many(&mut self, terms: &[F::Extension]) -> F::Extension {
+        terms.iter().copied().product()
+    }
+
+    fn reduce_with_powers_multi(
+        &mut self,
+        terms: &[F::Extension],
+        alphas: &[F::Extension],
+    ) -> Vec<F::Extension> {
+        plonk_common::reduce_with_powers_multi(terms, alphas)
+    }
+}
+
+/// Evaluates in a circuit, as the recursive verifier does.
+impl<F: RichField + Extendable<D>, const D: usize> VanishingPolyBackend<F>
+    for CircuitBuilder<F, D>
+{
+    type Value = ExtensionTarget<D>;
+
+    fn zero(&mut self) -> ExtensionTarget<D> {
+        self.zero_extension()
+    }
+
+    fn one(&mut self) -> ExtensionTarget<D> {
+        self.one_extension()
+    }
+
+    fn add(&mut self, a: ExtensionTarget<D>, b: ExtensionTarget<D>) -> ExtensionTarget<D> {
+        self.add_extension(a, b)
+    }
+
+    fn sub(&mut self, a: ExtensionTarget<D>, b: ExtensionTarget<D>) -> ExtensionTarget<D> {
+        self.sub_extension(a, b)
+    }
+
+    fn mul(&mut self, a: ExtensionTarget<D>, b: ExtensionTarget<D>) -> ExtensionTarget<D> {
+        self.mul_extension(a, b)
+    }
+
+    fn mul_add(
+        &mut self,
+        a: ExtensionTarget<D>,
+        b: ExtensionTarget<D>,
+        c: ExtensionTarget<D>,
+    ) -> ExtensionTarget<D> {
+        self.mul_add_extension(a, b, c)
+    }
+
+    fn mul_sub(
+        &mut self,
+        a: ExtensionTarget<D>,
+        b: ExtensionTarget<D>,
+        c: ExtensionTarget<D>,
+    ) -> ExtensionTarget<D> {
+        self.mul_sub_extension(a, b, c)
+    }
+
+    fn scalar_mul(&mut self, c: F, x: ExtensionTarget<D>) -> ExtensionTarget<D> {
+        let c = self.constant(c);
+        self.scalar_mul_ext(c, x)
+    }
+
+    fn scalar_mul_add_many(
+        &mut self,
+        cs: &[F],
+        x: ExtensionTarget<D>,
+        addends: &[ExtensionTarget<D>],
+        out: &mut Vec<ExtensionTarget<D>>,
+    ) {
+        // These are the `k_is`, read from a table of their own rather than pooled with the
+        // circuit's other constants. Being in the base field, they only take one wire of each
+        // `ScalarArithmeticExtensionGate` slot.
+        let table = self.constant_table(cs);
+        for (i, &a) in addends.iter().enumerate() {
+            let c = self.read_table(table, i);
+            out.push(self.scalar_mul_add_extension(c, x, a));
+        }
+    }
+
+    fn mul_many(&mut self, terms: &[ExtensionTarget<D>]) -> ExtensionTarget<D> {
+        self.mul_many_extension(terms)
+    }
+
+    fn reduce_with_powers_multi(
+        &mut self,
+        terms: &[ExtensionTarget<D>],
+        alphas: &[ExtensionTarget<D>],
+    ) -> Vec<ExtensionTarget<D>> {
+        alphas
+            .iter()
+            .map(|&alpha| ReducingFactorTarget::new(alpha).reduce(terms, self))
+            .collect()
+    }
+}
@@ -1,5 +1,7 @@
 //! Logic for evaluating constraints.
 
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
 use core::ops::Range;
 
 use crate::field::extension::algebra::ExtensionAlgebra;
@@ -15,6 +17,9 @@ pub struct EvaluationVars<'a, F: RichField + Extendable<D>, const D: usize> {
     pub local_constants: &'a [F::Extension],
     pub local_wires: &'a [F::Extension],
     pub public_inputs_hash: &'a HashOut<F>,
+    /// The values opened at the circuit's extra opening points (see
+    /// `CircuitBuilder::add_opening_point`), laid out as in `OpeningSet::extra_openings`.
+    pub extra_openings: &'a [Vec<F::Extension>],
 }
 
 /// A batch of evaluation vars, in the base field.
@@ -26,6 +31,8 @@ pub struct EvaluationVarsBaseBatch<'a, F: Field> {
     pub local_constants: &'a [F],
     pub local_wires: &'a [F],
     pub public_inputs_hash: &'a HashOut<F>,
+    /// The values opened at each extra opening point, in the same order as the wires.
+    pub extra_openings: &'a [Vec<F>],
 }
 
 /// A view into `EvaluationVarsBaseBatch` for a particular evaluation point. Does not copy the data.
@@ -34,6 +41,9 @@ pub struct EvaluationVarsBase<'a, F: Field> {
     pub local_constants: PackedStridedView<'a, F>,
     pub local_wires: PackedStridedView<'a, F>,
     pub public_inputs_hash: &'a HashOut<F>,
+    extra_openings: &'a [Vec<F>],
+    batch_size: usize,
+    index: usize,
 }
 
 /// Like `EvaluationVarsBase`, but packed.
@@ -75,9 +85,20 @@ impl<'a, F: Field> EvaluationVarsBaseBatch<'a, F> {
             local_constants,
             local_wires,
             public_inputs_hash,
+            extra_openings: &[],
         }
     }
 
+    /// Sets the values opened at the circuit's extra opening points, one vector per point, each
+    /// in the same order as the wires.
+    pub fn with_extra_openings(mut self, extra_openings: &'a [Vec<F>]) -> Self {
+        assert!(extra_openings
+            .iter()
+            .all(|values| values.len() % self.batch_size == 0));
+        self.extra_openings = extra_openings;
+        self
+    }
+
     pub fn remove_prefix(&mut self, num_selectors: usize) {
         self.local_constants = &self.local_constants[num_selectors * self.len()..];
     }
@@ -99,6 +120,9 @@ impl<'a, F: Field> EvaluationVarsBaseBatch<'a, F> {
             local_constants,
             local_wires,
             public_inputs_hash: self.public_inputs_hash,
+            extra_openings: self.extra_openings,
+            batch_size: self.len(),
+            index,
         }
     }
 
@@ -129,6 +153,17 @@ impl<'a, F: Field> EvaluationVarsBase<'a, F> {
         let arr = self.local_wires.view(wire_range).try_into().unwrap();
         F::Extension::from_basefield_array(arr)
     }
+
+    /// The number of extra opening points.
+    pub const fn num_extra_opening_points(&self) -> usize {
+        self.extra_openings.len()
+    }
+
+    /// The values opened at the `point`th extra opening point, laid out as in
+    /// `EvaluationVars::extra_openings`.
+    pub fn extra_openings(&self, point: usize) -> PackedStridedView<'a, F> {
+        PackedStridedView::new(&self.extra_openings[point], self.batch_size, self.index)
+    }
 }
 
 /// Iterator of views (`EvaluationVarsBase`) into a `EvaluationVarsBaseBatch`.
@@ -226,6 +261,9 @@ pub struct EvaluationTargets<'a, const D: usize> {
     pub local_constants: &'a [ExtensionTarget<D>],
     pub local_wires: &'a [ExtensionTarget<D>],
     pub public_inputs_hash: &'a HashOutTarget,
+    /// The values opened at the circuit's extra opening points, as in
+    /// `EvaluationVars::extra_openings`.
+    pub extra_openings: &'a [Vec<ExtensionTarget<D>>],
 }
 
 impl<'a, const D: usize> EvaluationTargets<'a, D> {
@@ -1,5 +1,9 @@
 //! plonky2 verifier implementation.
 
+#[cfg(not(feature = "std"))]
+use alloc::string::{String, ToString};
+use core::fmt;
+
 use anyhow::{ensure, Result};
 
 use crate::field::extension::Extendable;
@@ -14,14 +18,162 @@ use crate::plonk::validate_shape::validate_proof_with_pis_shape;
 use crate::plonk::vanishing_poly::eval_vanishing_poly;
 use crate::plonk::vars::EvaluationVars;
 
+/// The reason a proof was rejected.
+///
+/// Verifier functions return [`anyhow::Result`]; the typed reason can be recovered from the
+/// error with `downcast_ref::<VerificationError>()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationError {
+    /// The proof has a different number of public inputs than the circuit.
+    PublicInputsLengthMismatch { expected: usize, actual: usize },
+    /// A component of the proof doesn't have the size the circuit expects.
+    ShapeMismatch {
+        component: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// The FRI proof-of-work witness doesn't have enough leading zeros.
+    InvalidProofOfWork,
+    /// A FRI query index falls outside the LDE domain.
+    FriQueryIndexOutOfRange {
+        round: usize,
+        index: usize,
+        domain_size: usize,
+    },
+    /// A Merkle proof opened in a FRI query round doesn't lead to the committed cap.
+    MerkleCapMismatch { round: usize, oracle: MerkleOracle },
+    /// The evaluations opened at a FRI reduction step don't contain the value folded in the
+    /// previous step.
+    FriFoldingMismatch { round: usize, step: usize },
+    /// The FRI final polynomial has more coefficients than the FRI parameters allow.
+    FinalPolyDegreeTooHigh { max_len: usize, len: usize },
+    /// The FRI final polynomial disagrees with the value folded in a query round.
+    FinalPolyEvaluationMismatch { round: usize },
+    /// The vanishing polynomial doesn't equal `Z_H(zeta) quotient(zeta)` for a challenge.
+    VanishingPolynomialMismatch { challenge: usize },
+    /// The serialized verifier-only circuit data couldn't be read, or doesn't fit the circuit.
+    MalformedVerifierKey { reason: String },
+    /// The serialized common circuit data couldn't be read, or is inconsistent.
+    MalformedCommonData { reason: String },
+    /// The serialized proof couldn't be read.
+    MalformedProof { reason: String },
+    /// The proof's circuit digest hint names another circuit than the verifier key's.
+    CircuitDigestMismatch { hint: String, expected: String },
+}
+
+/// A Merkle-committed oracle checked by the FRI verifier.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MerkleOracle {
+    /// An oracle committed before FRI, by index; see
+    /// [`PlonkOracle`](crate::plonk::plonk_common::PlonkOracle) for PLONK's.
+    Initial(usize),
+    /// The oracle committed at the given FRI reduction step.
+    CommitPhase(usize),
+}
+
+impl VerificationError {
+    pub(crate) fn shape(component: impl Into<String>, expected: usize, actual: usize) -> Self {
+        Self::ShapeMismatch {
+            component: component.into(),
+            expected,
+            actual,
+        }
+    }
+}
+
+impl fmt::Display for MerkleOracle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Initial(index) => write!(f, "initial oracle {}", index),
+            Self::CommitPhase(step) => write!(f, "commit-phase oracle of step {}", step),
+        }
+    }
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PublicInputsLengthMismatch { expected, actual } => {
+                write!(f, "expected {} public inputs, got {}", expected, actual)
+            }
+            Self::ShapeMismatch {
+                component,
+                expected,
+                actual,
+            } => write!(f, "{}: expected {}, got {}", component, expected, actual),
+            Self::InvalidProofOfWork => write!(f, "invalid proof of work witness"),
+            Self::FriQueryIndexOutOfRange {
+                round,
+                index,
+                domain_size,
+            } => write!(
+                f,
+                "query round {}: index {} is outside the domain of size {}",
+                round, index, domain_size
+            ),
+            Self::MerkleCapMismatch { round, oracle } => write!(
+                f,
+                "query round {}: Merkle proof for {} doesn't match its cap",
+                round, oracle
+            ),
+            Self::FriFoldingMismatch { round, step } => write!(
+                f,
+                "query round {}: evaluations of step {} are inconsistent with the previous step",
+                round, step
+            ),
+            Self::FinalPolyDegreeTooHigh { max_len, len } => write!(
+                f,
+                "final polynomial has {} coefficients, at most {} allowed",
+                len, max_len
+            ),
+            Self::FinalPolyEvaluationMismatch { round } => {
+                write!(
+                    f,
+                    "query round {}: final polynomial evaluation is invalid",
+                    round
+                )
+            }
+            Self::VanishingPolynomialMismatch { challenge } => write!(
+                f,
+                "vanishing polynomial doesn't match the quotient for challenge {}",
+                challenge
+            ),
+            Self::MalformedVerifierKey { reason } => {
+                write!(f, "malformed verifier key: {}", reason)
+            }
+            Self::MalformedCommonData { reason } => {
+                write!(f, "malformed common circuit data: {}", reason)
+            }
+            Self::MalformedProof { reason } => write!(f, "malformed proof: {}", reason),
+            Self::CircuitDigestMismatch { hint, expected } => write!(
+                f,
+                "proof is hinted for circuit {}, but the verifier key is for circuit {}",
+                hint, expected
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for VerificationError {}
+
 pub(crate) fn verify<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     proof_with_pis: ProofWithPublicInputs<F, C, D>,
     verifier_data: &VerifierOnlyCircuitData<C, D>,
     common_data: &CommonCircuitData<F, D>,
 ) -> Result<()> {
     validate_proof_with_pis_shape(&proof_with_pis, common_data)?;
+    if let Some(hint) = proof_with_pis.circuit_digest_hint {
+        ensure!(
+            hint == *verifier_data,
+            VerificationError::CircuitDigestMismatch {
+                hint: hint.to_string(),
+                expected: verifier_data.digest().to_string(),
+            }
+        );
+    }
 
-    let public_inputs_hash = proof_with_pis.get_public_inputs_hash();
+    let public_inputs_hash = proof_with_pis.get_public_inputs_hash(common_data);
     let challenges = proof_with_pis.get_challenges(
         public_inputs_hash,
         &verifier_data.circuit_digest,
@@ -54,6 +206,7 @@ pub(crate) fn verify_with_challenges<
         local_constants,
         local_wires,
         public_inputs_hash: &public_inputs_hash,
+        extra_openings: &proof.openings.extra_openings,
     };
     let local_zs = &proof.openings.plonk_zs;
     let next_zs = &proof.openings.plonk_zs_next;
@@ -94,7 +247,10 @@ pub(crate) fn verify_with_challenges<
         .chunks(common_data.quotient_degree_factor)
         .enumerate()
     {
-        ensure!(vanishing_polys_zeta[i] == z_h_zeta * reduce_with_powers(chunk, zeta_pow_deg));
+        ensure!(
+            vanishing_polys_zeta[i] == z_h_zeta * reduce_with_powers(chunk, zeta_pow_deg),
+            VerificationError::VanishingPolynomialMismatch { challenge: i }
+        );
     }
 
     let merkle_caps = &[
@@ -116,3 +272,199 @@ pub(crate) fn verify_with_challenges<
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use plonky2_field::types::Sample;
+
+    use super::*;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+    use crate::plonk::plonk_common::PlonkOracle;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn rejection(result: Result<()>) -> VerificationError {
+        result
+            .unwrap_err()
+            .downcast_ref::<VerificationError>()
+            .expect("not a VerificationError")
+            .clone()
+    }
+
+    #[test]
+    fn corrupted_proofs_report_the_failed_check() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_public_input();
+        let mut y = x;
+        for _ in 0..1000 {
+            y = builder.square(y);
+        }
+        builder.register_public_input(y);
+        let data: CircuitData<F, C, D> = builder.build();
+        assert!(!data.common.fri_params.reduction_arity_bits.is_empty());
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        let proof = data.prove(pw)?;
+        data.verify(proof.clone())?;
+
+        let mut p = proof.clone();
+        p.public_inputs.push(F::ONE);
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::PublicInputsLengthMismatch {
+                expected: 2,
+                actual: 3
+            }
+        );
+        let mut p = proof.clone();
+        p.public_inputs.pop();
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::PublicInputsLengthMismatch {
+                expected: 2,
+                actual: 1
+            }
+        );
+        let mut p = data.compress(proof.clone())?;
+        p.public_inputs.push(F::ONE);
+        assert_eq!(
+            rejection(data.verify_compressed(p)),
+            VerificationError::PublicInputsLengthMismatch {
+                expected: 2,
+                actual: 3
+            }
+        );
+
+        let num_wires = data.common.config.num_wires;
+        let mut p = proof.clone();
+        p.proof.openings.wires.pop();
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::shape("wires openings", num_wires, num_wires - 1)
+        );
+
+        let mut p = proof.clone();
+        p.proof.opening_proof.final_poly.coeffs.push(Field::ONE);
+        let max_len = data.common.fri_params.final_poly_len();
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::FinalPolyDegreeTooHigh {
+                max_len,
+                len: max_len + 1
+            }
+        );
+
+        let mut p = proof.clone();
+        p.proof.openings.quotient_polys[0] += Field::ONE;
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::VanishingPolynomialMismatch { challenge: 0 }
+        );
+
+        let mut p = proof.clone();
+        p.proof.opening_proof.pow_witness += F::ONE;
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::InvalidProofOfWork
+        );
+
+        // Query round proofs aren't observed by the challenger, so corrupting them leaves the
+        // challenges unchanged.
+        let mut p = proof.clone();
+        let round = &mut p.proof.opening_proof.query_round_proofs[3];
+        round.initial_trees_proof.evals_proofs[PlonkOracle::WIRES.index].0[0] += F::ONE;
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::MerkleCapMismatch {
+                round: 3,
+                oracle: MerkleOracle::Initial(PlonkOracle::WIRES.index),
+            }
+        );
+
+        let mut p = proof.clone();
+        let round = &mut p.proof.opening_proof.query_round_proofs[5];
+        round.steps[0].merkle_proof.siblings[0].elements[0] += F::ONE;
+        assert_eq!(
+            rejection(data.verify(p)),
+            VerificationError::MerkleCapMismatch {
+                round: 5,
+                oracle: MerkleOracle::CommitPhase(0),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_challenges_report_the_failed_check() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.square(x);
+        builder.register_public_input(y);
+        let data: CircuitData<F, C, D> = builder.build();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::rand());
+        let proof = data.prove(pw)?;
+        let public_inputs_hash = proof.get_public_inputs_hash(&data.common);
+        let challenges = || {
+            proof
+                .get_challenges(
+                    public_inputs_hash,
+                    &data.verifier_only.circuit_digest,
+                    &data.common,
+                )
+                .unwrap()
+        };
+        let verify = |challenges| {
+            verify_with_challenges::<F, C, D>(
+                proof.proof.clone(),
+                public_inputs_hash,
+                challenges,
+                &data.verifier_only,
+                &data.common,
+            )
+        };
+        verify(challenges())?;
+
+        let domain_size = data.common.fri_params.lde_size();
+        let mut c = challenges();
+        c.fri_challenges.fri_query_indices[2] = domain_size;
+        assert_eq!(
+            rejection(verify(c)),
+            VerificationError::FriQueryIndexOutOfRange {
+                round: 2,
+                index: domain_size,
+                domain_size,
+            }
+        );
+
+        let mut c = challenges();
+        c.fri_challenges.fri_alpha += Field::ONE;
+        let expected = if data.common.fri_params.reduction_arity_bits.is_empty() {
+            VerificationError::FinalPolyEvaluationMismatch { round: 0 }
+        } else {
+            VerificationError::FriFoldingMismatch { round: 0, step: 0 }
+        };
+        assert_eq!(rejection(verify(c)), expected);
+
+        let mut c = challenges();
+        if let Some(beta) = c.fri_challenges.fri_betas.last_mut() {
+            *beta += Field::ONE;
+            assert_eq!(
+                rejection(verify(c)),
+                VerificationError::FinalPolyEvaluationMismatch { round: 0 }
+            );
+        }
+
+        Ok(())
+    }
+}
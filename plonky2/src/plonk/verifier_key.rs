@@ -0,0 +1,525 @@
+//! Verifying proofs of circuits over Goldilocks with Poseidon given only serialized data, for
+//! integrators such as light clients which receive the verifier key and proofs as bytes.
+//!
+//! ```
+//! use plonky2::field::types::Field;
+//! use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+//! use plonky2::plonk::circuit_builder::CircuitBuilder;
+//! use plonky2::plonk::circuit_data::CircuitConfig;
+//! use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+//! use plonky2::plonk::verifier_key::VerifierKey;
+//!
+//! type C = PoseidonGoldilocksConfig;
+//! type F = <C as GenericConfig<2>>::F;
+//!
+//! let mut builder = CircuitBuilder::<F, 2>::new(CircuitConfig::standard_recursion_config());
+//! let x = builder.add_virtual_public_input();
+//! let x_squared = builder.square(x);
+//! builder.register_public_input(x_squared);
+//! let data = builder.build::<C>();
+//!
+//! let mut pw = PartialWitness::new();
+//! pw.set_target(x, F::from_canonical_u32(3));
+//! let proof_bytes = data.prove(pw).unwrap().to_bytes();
+//!
+//! let vk = VerifierKey::from_verifier_data(&data.verifier_data()).unwrap();
+//! let public_inputs = vk.verify(&proof_bytes).unwrap();
+//! assert_eq!(public_inputs, [F::from_canonical_u32(3), F::from_canonical_u32(9)]);
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::field::goldilocks_field::GoldilocksField;
+use crate::hash::hash_types::HashOut;
+use crate::plonk::circuit_builder::circuit_digest;
+use crate::plonk::circuit_data::{CommonCircuitData, VerifierCircuitData, VerifierOnlyCircuitData};
+use crate::plonk::config::PoseidonGoldilocksConfig;
+use crate::plonk::plonk_common::PlonkOracle;
+use crate::plonk::proof::{
+    read_circuit_digest_hint, CompressedProofWithPublicInputs, ProofWithPublicInputs,
+};
+use crate::plonk::verifier::{verify, VerificationError};
+use crate::util::serialization::{Buffer, DefaultGateSerializer, IoResult, Read, Remaining, Write};
+
+type F = GoldilocksField;
+type C = PoseidonGoldilocksConfig;
+const D: usize = 2;
+
+/// The serialized verifier-only and common data of a circuit, which is all that's needed to
+/// verify its proofs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VerifierKey {
+    verifier_only: Vec<u8>,
+    common: Vec<u8>,
+    circuit_digest: HashOut<F>,
+}
+
+impl VerifierKey {
+    /// Bundles the bytes of a [`VerifierOnlyCircuitData`] and a [`CommonCircuitData`] serialized
+    /// with the [`DefaultGateSerializer`], checking that they're well-formed.
+    pub fn new(verifier_only: Vec<u8>, common: Vec<u8>) -> Result<Self, VerificationError> {
+        let (verifier_data, _) = read_verifier_data(&verifier_only, &common)?;
+        Ok(Self {
+            verifier_only,
+            common,
+            circuit_digest: verifier_data.circuit_digest,
+        })
+    }
+
+    pub fn from_verifier_data(data: &VerifierCircuitData<F, C, D>) -> IoResult<Self> {
+        Ok(Self {
+            verifier_only: data.verifier_only.to_bytes()?,
+            common: data.common.to_bytes(&DefaultGateSerializer)?,
+            circuit_digest: data.verifier_only.circuit_digest,
+        })
+    }
+
+    pub fn verifier_only_bytes(&self) -> &[u8] {
+        &self.verifier_only
+    }
+
+    pub fn common_bytes(&self) -> &[u8] {
+        &self.common
+    }
+
+    /// The digest of the circuit, which proofs are bound to.
+    pub const fn circuit_digest(&self) -> HashOut<F> {
+        self.circuit_digest
+    }
+
+    /// Verifies the serialized `proof`, returning its public inputs.
+    pub fn verify(&self, proof: &[u8]) -> Result<Vec<F>, VerificationError> {
+        verify_proof_with_vk(&self.verifier_only, &self.common, proof)
+    }
+}
+
+/// Deserializes a verifier key, i.e. a [`VerifierOnlyCircuitData`], common circuit data
+/// serialized with the [`DefaultGateSerializer`] and a [`ProofWithPublicInputs`], verifies the
+/// proof and returns its public inputs.
+///
+/// Malformed inputs are rejected with the `Malformed*` variants of [`VerificationError`] rather
+/// than panicking, so the bytes needn't be trusted.
+pub fn verify_proof_with_vk(
+    vk_bytes: &[u8],
+    common_bytes: &[u8],
+    proof_bytes: &[u8],
+) -> Result<Vec<F>, VerificationError> {
+    let (verifier_only, common) = read_verifier_data(vk_bytes, common_bytes)?;
+
+    let mut buffer = Buffer::new(proof_bytes);
+    let malformed = |_| VerificationError::MalformedProof {
+        reason: "truncated or invalid encoding".to_string(),
+    };
+    let circuit_digest_hint = read_circuit_digest_hint(&mut buffer).map_err(malformed)?;
+    let mut proof: ProofWithPublicInputs<F, C, D> = buffer
+        .read_proof_with_public_inputs(&common)
+        .map_err(malformed)?;
+    proof.circuit_digest_hint = circuit_digest_hint;
+    check_fully_read(&buffer).map_err(|reason| VerificationError::MalformedProof { reason })?;
+
+    verify_deserialized(proof, &verifier_only, &common)
+}
+
+/// Like [`verify_proof_with_vk`], but for a serialized [`CompressedProofWithPublicInputs`].
+pub fn verify_compressed_proof_with_vk(
+    vk_bytes: &[u8],
+    common_bytes: &[u8],
+    proof_bytes: &[u8],
+) -> Result<Vec<F>, VerificationError> {
+    let (verifier_only, common) = read_verifier_data(vk_bytes, common_bytes)?;
+
+    let mut buffer = Buffer::new(proof_bytes);
+    let compressed_proof: CompressedProofWithPublicInputs<F, C, D> = buffer
+        .read_compressed_proof_with_public_inputs(&common)
+        .map_err(|_| VerificationError::MalformedProof {
+            reason: "truncated or invalid encoding".to_string(),
+        })?;
+    check_fully_read(&buffer).map_err(|reason| VerificationError::MalformedProof { reason })?;
+
+    let proof = compressed_proof
+        .decompress(&verifier_only.circuit_digest, &common)
+        .map_err(|err| VerificationError::MalformedProof {
+            reason: err.to_string(),
+        })?;
+    verify_deserialized(proof, &verifier_only, &common)
+}
+
+/// The version of the format of proof tuples, written in their header.
+///
+/// It must be bumped, and the golden proofs in `plonky2/tests/fixtures` regenerated, whenever a
+/// change makes previously serialized tuples unreadable or no longer accepted: a change to the
+/// serialization of verifier-only data, common data or proofs, or to the verifier itself. Tuples
+/// of another version are rejected rather than misread.
+pub const PROOF_TUPLE_FORMAT_VERSION: u32 = 6;
+
+/// The bytes starting the header of a proof tuple.
+const PROOF_TUPLE_MAGIC: [u8; 4] = *b"pk2t";
+
+/// The kind of proof in a proof tuple.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProofTupleKind {
+    Proof,
+    CompressedProof,
+}
+
+/// Serializes a proof tuple: a header, then the verifier-only data and common data of `vk`, and
+/// `proof_bytes`, a serialized proof of the given kind. It can be verified on its own with
+/// [`verify_proof_tuple`].
+pub fn proof_tuple_to_bytes(vk: &VerifierKey, kind: ProofTupleKind, proof_bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let write = |bytes: &mut Vec<u8>| -> IoResult<()> {
+        bytes.write_all(&PROOF_TUPLE_MAGIC)?;
+        bytes.write_u32(PROOF_TUPLE_FORMAT_VERSION)?;
+        bytes.write_u8(kind as u8)?;
+        for part in [vk.verifier_only_bytes(), vk.common_bytes(), proof_bytes] {
+            bytes.write_usize(part.len())?;
+            bytes.write_all(part)?;
+        }
+        Ok(())
+    };
+    write(&mut bytes).expect("Writing to a byte-vector cannot fail.");
+    bytes
+}
+
+/// Verifies a proof tuple written by [`proof_tuple_to_bytes`], returning the proof's public
+/// inputs. Tuples of another [`PROOF_TUPLE_FORMAT_VERSION`] are rejected.
+pub fn verify_proof_tuple(bytes: &[u8]) -> Result<Vec<F>, VerificationError> {
+    let malformed = |reason: &str| VerificationError::MalformedProof {
+        reason: reason.to_string(),
+    };
+
+    let mut buffer = Buffer::new(bytes);
+    let mut magic = [0; 4];
+    buffer
+        .read_exact(&mut magic)
+        .map_err(|_| malformed("truncated proof tuple header"))?;
+    if magic != PROOF_TUPLE_MAGIC {
+        return Err(malformed("not a proof tuple"));
+    }
+    let version = buffer
+        .read_u32()
+        .map_err(|_| malformed("truncated proof tuple header"))?;
+    if version != PROOF_TUPLE_FORMAT_VERSION {
+        return Err(VerificationError::MalformedProof {
+            reason: format!(
+                "proof tuple format version {}, expected {}",
+                version, PROOF_TUPLE_FORMAT_VERSION
+            ),
+        });
+    }
+    let kind = match buffer.read_u8() {
+        Ok(0) => ProofTupleKind::Proof,
+        Ok(1) => ProofTupleKind::CompressedProof,
+        _ => return Err(malformed("invalid proof tuple kind")),
+    };
+
+    let mut parts = Vec::with_capacity(3);
+    for _ in 0..3 {
+        let len = buffer
+            .read_usize()
+            .map_err(|_| malformed("truncated proof tuple"))?;
+        let unread = buffer.unread_bytes();
+        if len > unread.len() {
+            return Err(malformed("truncated proof tuple"));
+        }
+        let (part, rest) = unread.split_at(len);
+        parts.push(part);
+        buffer = Buffer::new(rest);
+    }
+    check_fully_read(&buffer).map_err(|reason| VerificationError::MalformedProof { reason })?;
+
+    match kind {
+        ProofTupleKind::Proof => verify_proof_with_vk(parts[0], parts[1], parts[2]),
+        ProofTupleKind::CompressedProof => {
+            verify_compressed_proof_with_vk(parts[0], parts[1], parts[2])
+        }
+    }
+}
+
+/// Verifies a deserialized proof, returning its public inputs.
+fn verify_deserialized(
+    proof: ProofWithPublicInputs<F, C, D>,
+    verifier_only: &VerifierOnlyCircuitData<C, D>,
+    common: &CommonCircuitData<F, D>,
+) -> Result<Vec<F>, VerificationError> {
+    let public_inputs = proof.public_inputs.clone();
+    verify::<F, C, D>(proof, verifier_only, common).map_err(|err| {
+        err.downcast::<VerificationError>().unwrap_or_else(|err| {
+            VerificationError::MalformedProof {
+                reason: err.to_string(),
+            }
+        })
+    })?;
+    Ok(public_inputs)
+}
+
+/// Deserializes and checks the verifier-only and common circuit data.
+fn read_verifier_data(
+    vk_bytes: &[u8],
+    common_bytes: &[u8],
+) -> Result<(VerifierOnlyCircuitData<C, D>, CommonCircuitData<F, D>), VerificationError> {
+    let malformed_vk = |reason| VerificationError::MalformedVerifierKey { reason };
+    let malformed_common = |reason| VerificationError::MalformedCommonData { reason };
+
+    let mut buffer = Buffer::new(vk_bytes);
+    let verifier_only: VerifierOnlyCircuitData<C, D> = buffer
+        .read_verifier_only_circuit_data()
+        .map_err(|_| malformed_vk("truncated or invalid encoding".to_string()))?;
+    check_fully_read(&buffer).map_err(malformed_vk)?;
+
+    let mut buffer = Buffer::new(common_bytes);
+    let common: CommonCircuitData<F, D> =
+        buffer
+            .read_common_circuit_data(&DefaultGateSerializer)
+            .map_err(|_| malformed_common("truncated or invalid encoding".to_string()))?;
+    check_fully_read(&buffer).map_err(malformed_common)?;
+    common
+        .validate()
+        .map_err(|err| malformed_common(err.to_string()))?;
+
+    let cap_height = common.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS);
+    if verifier_only.constants_sigmas_cap.height() != cap_height {
+        return Err(malformed_vk(format!(
+            "cap height {} doesn't match the circuit's cap height {}",
+            verifier_only.constants_sigmas_cap.height(),
+            cap_height
+        )));
+    }
+
+    let circuit_digest = circuit_digest::<F, C, D>(
+        &verifier_only.constants_sigmas_cap,
+        &verifier_only.domain_separator,
+        &common,
+    );
+    if verifier_only.circuit_digest != circuit_digest {
+        return Err(malformed_vk(
+            "circuit digest doesn't match the cap, domain separator and common data".to_string(),
+        ));
+    }
+
+    Ok((verifier_only, common))
+}
+
+fn check_fully_read(buffer: &Buffer) -> Result<(), String> {
+    match buffer.remaining() {
+        0 => Ok(()),
+        n => Err(format!("{} trailing bytes", n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+
+    /// Proves `x * y + 3` for `x = 2, y = 5`, with `x` and the result as public inputs.
+    fn proven_circuit() -> (CircuitData<F, C, D>, Vec<u8>) {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let y = builder.add_virtual_target();
+        let xy = builder.mul(x, y);
+        let three = builder.constant(F::from_canonical_u32(3));
+        let z = builder.add(xy, three);
+        builder.register_public_input(z);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(2));
+        pw.set_target(y, F::from_canonical_u32(5));
+        let proof = data.prove(pw).unwrap();
+        (data, proof.to_bytes())
+    }
+
+    /// Flips a bit in each of `num_flips` bytes spread evenly over `bytes`, cycling through the
+    /// bit positions.
+    fn bit_flips(bytes: &[u8], num_flips: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let stride = (bytes.len() / num_flips).max(1);
+        (0..bytes.len())
+            .step_by(stride)
+            .enumerate()
+            .map(move |(flip, i)| {
+                let mut flipped = bytes.to_vec();
+                flipped[i] ^= 1 << (flip % 8);
+                flipped
+            })
+    }
+
+    /// Prefixes of `bytes` of `num_lengths` lengths spread evenly below its length.
+    fn truncations(bytes: &[u8], num_lengths: usize) -> impl Iterator<Item = &[u8]> {
+        let stride = (bytes.len() / num_lengths).max(1);
+        (0..bytes.len())
+            .step_by(stride)
+            .map(move |len| &bytes[..len])
+    }
+
+    #[test]
+    fn verifies_serialized_proof() {
+        let (data, proof) = proven_circuit();
+        let vk = VerifierKey::from_verifier_data(&data.verifier_data()).unwrap();
+        let expected = [F::from_canonical_u32(2), F::from_canonical_u32(13)];
+
+        assert_eq!(
+            verify_proof_with_vk(vk.verifier_only_bytes(), vk.common_bytes(), &proof).unwrap(),
+            expected
+        );
+        assert_eq!(vk.verify(&proof).unwrap(), expected);
+        assert_eq!(vk.circuit_digest(), data.verifier_only.circuit_digest);
+
+        let rebuilt = VerifierKey::new(
+            vk.verifier_only_bytes().to_vec(),
+            vk.common_bytes().to_vec(),
+        )
+        .unwrap();
+        assert_eq!(rebuilt, vk);
+    }
+
+    #[test]
+    fn verifies_proof_tuples() {
+        let (data, proof) = proven_circuit();
+        let vk = VerifierKey::from_verifier_data(&data.verifier_data()).unwrap();
+        let expected = [F::from_canonical_u32(2), F::from_canonical_u32(13)];
+
+        let tuple = proof_tuple_to_bytes(&vk, ProofTupleKind::Proof, &proof);
+        assert_eq!(verify_proof_tuple(&tuple).unwrap(), expected);
+
+        let compressed = ProofWithPublicInputs::from_bytes(proof, &data.common)
+            .and_then(|proof| data.compress(proof))
+            .unwrap()
+            .to_bytes();
+        let compressed_tuple =
+            proof_tuple_to_bytes(&vk, ProofTupleKind::CompressedProof, &compressed);
+        assert_eq!(verify_proof_tuple(&compressed_tuple).unwrap(), expected);
+
+        let mut other_version = tuple.clone();
+        other_version[4..8].copy_from_slice(&(PROOF_TUPLE_FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            verify_proof_tuple(&other_version),
+            Err(VerificationError::MalformedProof {
+                reason: format!(
+                    "proof tuple format version {}, expected {}",
+                    PROOF_TUPLE_FORMAT_VERSION + 1,
+                    PROOF_TUPLE_FORMAT_VERSION
+                )
+            })
+        );
+        for truncated in truncations(&tuple, 64) {
+            assert!(verify_proof_tuple(truncated).is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_verifier_key() {
+        let (data, proof) = proven_circuit();
+        let vk = VerifierKey::from_verifier_data(&data.verifier_data()).unwrap();
+        let vk_bytes = vk.verifier_only_bytes();
+
+        for truncated in truncations(vk_bytes, 64) {
+            let err = verify_proof_with_vk(truncated, vk.common_bytes(), &proof).unwrap_err();
+            assert!(
+                matches!(err, VerificationError::MalformedVerifierKey { .. }),
+                "{}",
+                err
+            );
+        }
+        let mut extended = vk_bytes.to_vec();
+        extended.push(0);
+        assert_eq!(
+            verify_proof_with_vk(&extended, vk.common_bytes(), &proof),
+            Err(VerificationError::MalformedVerifierKey {
+                reason: "1 trailing bytes".to_string()
+            })
+        );
+
+        // The circuit digest is recomputed from the cap, the domain separator and the common
+        // data, so a flip anywhere in the verifier key is caught before the proof is looked at.
+        for flipped in bit_flips(vk_bytes, 64) {
+            let err = verify_proof_with_vk(&flipped, vk.common_bytes(), &proof).unwrap_err();
+            assert!(
+                matches!(err, VerificationError::MalformedVerifierKey { .. }),
+                "{}",
+                err
+            );
+        }
+
+        let mut other_separator = data.verifier_only.clone();
+        other_separator.domain_separator = vec![F::ONE];
+        assert_eq!(
+            VerifierKey::new(
+                other_separator.to_bytes().unwrap(),
+                vk.common_bytes().to_vec()
+            ),
+            Err(VerificationError::MalformedVerifierKey {
+                reason: "circuit digest doesn't match the cap, domain separator and common data"
+                    .to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_common_data() {
+        let (data, proof) = proven_circuit();
+        let vk = VerifierKey::from_verifier_data(&data.verifier_data()).unwrap();
+        let common_bytes = vk.common_bytes();
+
+        for truncated in truncations(common_bytes, 64) {
+            let err =
+                verify_proof_with_vk(vk.verifier_only_bytes(), truncated, &proof).unwrap_err();
+            assert!(
+                matches!(err, VerificationError::MalformedCommonData { .. }),
+                "{}",
+                err
+            );
+        }
+
+        // Even fields which don't affect verification, e.g. `security_bits`, are bound by the
+        // circuit digest, so every flip is rejected.
+        for flipped in bit_flips(common_bytes, 128) {
+            assert!(
+                verify_proof_with_vk(vk.verifier_only_bytes(), &flipped, &proof).is_err(),
+                "a flipped bit in the common data wasn't detected"
+            );
+        }
+
+        let mut inconsistent = data.common.clone();
+        inconsistent.num_partial_products += 1;
+        let err = VerifierKey::new(
+            vk.verifier_only_bytes().to_vec(),
+            inconsistent.to_bytes(&DefaultGateSerializer).unwrap(),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, VerificationError::MalformedCommonData { .. }),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_proof() {
+        let (data, proof) = proven_circuit();
+        let vk = VerifierKey::from_verifier_data(&data.verifier_data()).unwrap();
+
+        for truncated in truncations(&proof, 64) {
+            assert_eq!(
+                vk.verify(truncated),
+                Err(VerificationError::MalformedProof {
+                    reason: "truncated or invalid encoding".to_string()
+                })
+            );
+        }
+        for flipped in bit_flips(&proof, 128) {
+            assert!(vk.verify(&flipped).is_err());
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Two-to-one aggregation of proofs, for building binary aggregation trees.
+//!
+//! An aggregation circuit verifies two proofs of the same inner circuit, and commits to their
+//! public inputs through a single hash. Its public inputs are the four elements of
+//! `C::InnerHasher::hash_no_pad(left_pis || right_pis || [has_right])`, where `left_pis` and
+//! `right_pis` are the public inputs of the left and right proofs. The right proof may be omitted,
+//! e.g. for the last leaf of an odd level, in which case a dummy proof is verified instead,
+//! `right_pis` is all zeros and `has_right` is zero. Hashing `has_right` tells a missing right
+//! proof apart from one whose public inputs are all zero. [`combine_public_inputs`] computes the
+//! same hash natively.
+//!
+//! To aggregate further, build another aggregation circuit with the first one as its inner circuit.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use anyhow::{ensure, Result};
+
+use crate::field::extension::Extendable;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::BoolTarget;
+use crate::iop::witness::{PartialWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{CircuitData, CommonCircuitData, VerifierOnlyCircuitData};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+
+/// A circuit verifying two proofs of the same inner circuit. See the module documentation for how
+/// its public inputs are derived.
+#[derive(Debug)]
+pub struct AggregationCircuitData<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+> {
+    pub circuit_data: CircuitData<F, C, D>,
+    left_proof: ProofWithPublicInputsTarget<D>,
+    right_proof: ProofWithPublicInputsTarget<D>,
+    has_right: BoolTarget,
+}
+
+/// Builds a circuit aggregating two proofs of the circuit described by `inner_common` and
+/// `inner_verifier_only`, using the inner circuit's config.
+pub fn build_aggregation_circuit<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    const D: usize,
+>(
+    inner_common: &CommonCircuitData<F, D>,
+    inner_verifier_only: &VerifierOnlyCircuitData<C, D>,
+) -> Result<AggregationCircuitData<F, C, D>>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut builder = CircuitBuilder::<F, D>::new(inner_common.config.clone());
+    let inner_verifier_data = builder.constant_verifier_data(inner_verifier_only);
+
+    let left_proof = builder.add_virtual_proof_with_pis(inner_common);
+    builder.verify_proof::<C>(&left_proof, &inner_verifier_data, inner_common);
+
+    let right_proof = builder.add_virtual_proof_with_pis(inner_common);
+    let has_right = builder.add_virtual_bool_target_safe();
+    builder.conditionally_verify_proof_or_dummy::<C>(
+        has_right,
+        &right_proof,
+        &inner_verifier_data,
+        inner_common,
+    )?;
+
+    // Zero out the right public inputs when the right proof is missing, so that the output doesn't
+    // depend on the unverified values of its targets.
+    let right_pis = right_proof
+        .public_inputs
+        .iter()
+        .map(|&pi| builder.mul(has_right.target, pi))
+        .collect::<Vec<_>>();
+    let combined = builder.hash_n_to_hash_no_pad::<C::InnerHasher>(
+        [
+            left_proof.public_inputs.clone(),
+            right_pis,
+            vec![has_right.target],
+        ]
+        .concat(),
+    );
+    builder.register_public_inputs(&combined.elements);
+
+    Ok(AggregationCircuitData {
+        circuit_data: builder.build::<C>(),
+        left_proof,
+        right_proof,
+        has_right,
+    })
+}
+
+impl<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>
+    AggregationCircuitData<F, C, D>
+{
+    /// Proves that `left` and, if given, `right` are valid proofs of the inner circuit. Without
+    /// `right`, a dummy proof takes its place.
+    pub fn prove_aggregation(
+        &self,
+        left: &ProofWithPublicInputs<F, C, D>,
+        right: Option<&ProofWithPublicInputs<F, C, D>>,
+    ) -> Result<ProofWithPublicInputs<F, C, D>>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&self.left_proof, left);
+        // The right proof's targets must be set even when it is missing. Its verification is
+        // skipped then, so any proof of the inner circuit will do.
+        pw.set_proof_with_pis_target(&self.right_proof, right.unwrap_or(left));
+        pw.set_bool_target(self.has_right, right.is_some());
+        self.circuit_data.prove(pw)
+    }
+
+    pub fn verify(&self, proof: ProofWithPublicInputs<F, C, D>) -> Result<()> {
+        self.circuit_data.verify(proof)
+    }
+}
+
+/// Computes the public inputs of an aggregation proof, given those of the proofs it aggregates.
+pub fn combine_public_inputs<F: RichField, H: AlgebraicHasher<F>>(
+    left: &[F],
+    right: Option<&[F]>,
+) -> Result<Vec<F>> {
+    let (right, has_right) = match right {
+        Some(right) => {
+            ensure!(
+                right.len() == left.len(),
+                "Aggregated proofs must have the same number of public inputs"
+            );
+            (right.to_vec(), F::ONE)
+        }
+        None => (vec![F::ZERO; left.len()], F::ZERO),
+    };
+    Ok(H::hash_no_pad(&[left, &right, &[has_right]].concat())
+        .elements
+        .to_vec())
+}
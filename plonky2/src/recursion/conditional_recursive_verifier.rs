@@ -2,6 +2,7 @@
 use alloc::vec::Vec;
 
 use itertools::Itertools;
+use log::Level;
 
 use crate::field::extension::Extendable;
 use crate::fri::proof::{
@@ -16,7 +17,6 @@ use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::{CommonCircuitData, VerifierCircuitTarget};
 use crate::plonk::config::{AlgebraicHasher, GenericConfig};
 use crate::plonk::proof::{OpeningSetTarget, ProofTarget, ProofWithPublicInputsTarget};
-use crate::with_context;
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// Verify `proof0` if `condition` else verify `proof1`.
@@ -103,19 +103,19 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                 },
             public_inputs: public_inputs1,
         } = proof_with_pis1;
-        with_context!(self, "select proof", {
-            let selected_wires_cap = self.select_cap(b, wires_cap0, wires_cap1);
-            let selected_plonk_zs_partial_products_cap = self.select_cap(
+        self.with_context(Level::Debug, "select proof", |builder| {
+            let selected_wires_cap = builder.select_cap(b, wires_cap0, wires_cap1);
+            let selected_plonk_zs_partial_products_cap = builder.select_cap(
                 b,
                 plonk_zs_partial_products_cap0,
                 plonk_zs_partial_products_cap1,
             );
             let selected_quotient_polys_cap =
-                self.select_cap(b, quotient_polys_cap0, quotient_polys_cap1);
-            let selected_openings = self.select_opening_set(b, openings0, openings1);
+                builder.select_cap(b, quotient_polys_cap0, quotient_polys_cap1);
+            let selected_openings = builder.select_opening_set(b, openings0, openings1);
             let selected_opening_proof =
-                self.select_opening_proof(b, opening_proof0, opening_proof1);
-            let selected_public_inputs = self.select_vec(b, public_inputs0, public_inputs1);
+                builder.select_opening_proof(b, opening_proof0, opening_proof1);
+            let selected_public_inputs = builder.select_vec(b, public_inputs0, public_inputs1);
             ProofWithPublicInputsTarget {
                 proof: ProofTarget {
                     wires_cap: selected_wires_cap,
@@ -196,6 +196,12 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             next_lookup_zs: self.select_vec_ext(b, &os0.next_lookup_zs, &os1.next_lookup_zs),
             partial_products: self.select_vec_ext(b, &os0.partial_products, &os1.partial_products),
             quotient_polys: self.select_vec_ext(b, &os0.quotient_polys, &os1.quotient_polys),
+            extra_openings: os0
+                .extra_openings
+                .iter()
+                .zip_eq(&os1.extra_openings)
+                .map(|(v0, v1)| self.select_vec_ext(b, v0, v1))
+                .collect(),
         }
     }
 
@@ -347,19 +353,20 @@ mod tests {
     use crate::field::types::Sample;
     use crate::gates::noop::NoopGate;
     use crate::iop::witness::{PartialWitness, WitnessWrite};
-    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
     use crate::plonk::config::PoseidonGoldilocksConfig;
+    use crate::plonk::plonk_common::PlonkOracle;
+    use crate::plonk::proof::ProofWithPublicInputs;
     use crate::recursion::dummy_circuit::{dummy_circuit, dummy_proof};
 
-    #[test]
-    fn test_conditional_recursive_verifier() -> Result<()> {
-        init_logger();
-        const D: usize = 2;
-        type C = PoseidonGoldilocksConfig;
-        type F = <C as GenericConfig<D>>::F;
-        let config = CircuitConfig::standard_recursion_config();
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
 
-        // Generate proof.
+    /// Proves a small circuit with one random public input.
+    fn inner_proof(
+        config: &CircuitConfig,
+    ) -> Result<(CircuitData<F, C, D>, ProofWithPublicInputs<F, C, D>)> {
         let mut builder = CircuitBuilder::<F, D>::new(config.clone());
         let mut pw = PartialWitness::new();
         let t = builder.add_virtual_target();
@@ -372,6 +379,14 @@ mod tests {
         let data = builder.build::<C>();
         let proof = data.prove(pw)?;
         data.verify(proof.clone())?;
+        Ok((data, proof))
+    }
+
+    #[test]
+    fn test_conditional_recursive_verifier() -> Result<()> {
+        init_logger();
+        let config = CircuitConfig::standard_recursion_config();
+        let (data, proof) = inner_proof(&config)?;
 
         // Generate dummy proof with the same `CommonCircuitData`.
         let dummy_data = dummy_circuit(&data.common);
@@ -384,13 +399,15 @@ mod tests {
         pw.set_proof_with_pis_target(&pt, &proof);
         let dummy_pt = builder.add_virtual_proof_with_pis(&data.common);
         pw.set_proof_with_pis_target::<C, D>(&dummy_pt, &dummy_proof);
-        let inner_data =
-            builder.add_virtual_verifier_data(data.common.config.fri_config.cap_height);
+        let inner_data = builder.add_virtual_verifier_data(
+            data.common.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
         pw.set_verifier_data_target(&inner_data, &data.verifier_only);
-        let dummy_inner_data =
-            builder.add_virtual_verifier_data(data.common.config.fri_config.cap_height);
+        let dummy_inner_data = builder.add_virtual_verifier_data(
+            data.common.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
         pw.set_verifier_data_target(&dummy_inner_data, &dummy_data.verifier_only);
-        let b = builder.constant_bool(F::rand().0 % 2 == 0);
+        let b = builder.add_virtual_bool_target_safe();
         builder.conditionally_verify_proof::<C>(
             b,
             &pt,
@@ -399,11 +416,52 @@ mod tests {
             &dummy_inner_data,
             &data.common,
         );
+        // Expose the selected public input, to check which proof was verified.
+        let selected_pi = builder.select(b, pt.public_inputs[0], dummy_pt.public_inputs[0]);
+        builder.register_public_input(selected_pi);
 
         builder.print_gate_counts(100);
-        let data = builder.build::<C>();
-        let proof = data.prove(pw)?;
-        data.verify(proof)
+        let outer_data = builder.build::<C>();
+        for (condition, expected_pi) in [
+            (true, proof.public_inputs[0]),
+            (false, dummy_proof.public_inputs[0]),
+        ] {
+            let mut pw = pw.clone();
+            pw.set_bool_target(b, condition);
+            let outer_proof = outer_data.prove(pw)?;
+            assert_eq!(outer_proof.public_inputs, vec![expected_pi]);
+            outer_data.verify(outer_proof)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conditional_recursive_verifier_or_dummy() -> Result<()> {
+        init_logger();
+        let config = CircuitConfig::standard_recursion_config();
+        let (data, proof) = inner_proof(&config)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let pt = builder.add_virtual_proof_with_pis(&data.common);
+        let inner_data = builder.add_virtual_verifier_data(
+            data.common.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        let b = builder.add_virtual_bool_target_safe();
+        builder.conditionally_verify_proof_or_dummy::<C>(b, &pt, &inner_data, &data.common)?;
+        let outer_data = builder.build::<C>();
+
+        // The real proof's targets are always set, but it is only verified when `b` is true.
+        for condition in [true, false] {
+            let mut pw = PartialWitness::new();
+            pw.set_proof_with_pis_target(&pt, &proof);
+            pw.set_verifier_data_target(&inner_data, &data.verifier_only);
+            pw.set_bool_target(b, condition);
+            let outer_proof = outer_data.prove(pw)?;
+            outer_data.verify(outer_proof)?;
+        }
+
+        Ok(())
     }
 
     fn init_logger() {
@@ -3,17 +3,18 @@
 #[cfg(not(feature = "std"))]
 use alloc::vec::Vec;
 
-use anyhow::{ensure, Result};
+use anyhow::{anyhow, ensure, Result};
 
 use crate::field::extension::Extendable;
 use crate::hash::hash_types::{HashOut, HashOutTarget, MerkleCapTarget, RichField};
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::target::{BoolTarget, Target};
-use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_builder::{BuildOptions, CircuitBuilder};
 use crate::plonk::circuit_data::{
-    CommonCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
+    CircuitConfig, CommonCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
 };
 use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::plonk_common::PlonkOracle;
 use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
 use crate::util::serialization::{Buffer, IoResult, Read, Write};
 
@@ -23,7 +24,7 @@ impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
         C::Hasher: AlgebraicHasher<C::F>,
     {
         // The structure of the public inputs is `[..., circuit_digest, constants_sigmas_cap]`.
-        let cap_len = common_data.config.fri_config.num_cap_elements();
+        let cap_len = 1 << common_data.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS);
         let len = slice.len();
         ensure!(len >= 4 + 4 * cap_len, "Not enough public inputs");
         let constants_sigmas_cap = MerkleCap(
@@ -36,9 +37,12 @@ impl<C: GenericConfig<D>, const D: usize> VerifierOnlyCircuitData<C, D> {
         let circuit_digest =
             HashOut::from_partial(&slice[len - 4 - 4 * cap_len..len - 4 * cap_len]);
 
+        // The domain separator isn't part of the public inputs; it's only needed to recompute the
+        // digest, which cyclic verification doesn't do.
         Ok(Self {
             circuit_digest,
             constants_sigmas_cap,
+            domain_separator: Vec::new(),
         })
     }
 }
@@ -65,7 +69,7 @@ impl VerifierCircuitTarget {
         slice: &[Target],
         common_data: &CommonCircuitData<F, D>,
     ) -> Result<Self> {
-        let cap_len = common_data.config.fri_config.num_cap_elements();
+        let cap_len = 1 << common_data.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS);
         let len = slice.len();
         ensure!(len >= 4 + 4 * cap_len, "Not enough public inputs");
         let constants_sigmas_cap = MerkleCapTarget(
@@ -127,13 +131,10 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             common_data,
         )?;
         // Connect previous verifier data to current one. This guarantees that every proof in the cycle uses the same verifier data.
-        self.connect_hashes(
-            inner_cyclic_pis.circuit_digest,
-            verifier_data.circuit_digest,
-        );
-        self.connect_merkle_caps(
+        self.connect_verifier_data(
             &inner_cyclic_pis.constants_sigmas_cap,
-            &verifier_data.constants_sigmas_cap,
+            inner_cyclic_pis.circuit_digest,
+            &verifier_data,
         );
 
         // Verify the cyclic proof if `condition` is set to true, otherwise verify the other proof.
@@ -176,6 +177,54 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     }
 }
 
+/// The maximum number of candidate circuits `common_data_for_recursion` builds before giving up.
+const MAX_COMMON_DATA_ITERATIONS: usize = 8;
+
+/// Finds the `CommonCircuitData` of a cyclic circuit, which must be known before the circuit can
+/// be built, since the circuit verifies proofs of itself.
+///
+/// `build_circuit` adds the whole cyclic circuit to a fresh builder, given the common data of the
+/// proofs it verifies, e.g. by calling `conditionally_verify_cyclic_proof_or_dummy`. Starting from
+/// the common data of a trivial circuit, this repeatedly builds the circuit for the common data of
+/// the previous candidate, until the circuit's own common data is a fixed point. The caller should
+/// then build the actual circuit by calling `build_circuit` once more with the returned data.
+pub fn common_data_for_recursion<F, C, T, const D: usize>(
+    config: &CircuitConfig,
+    mut build_circuit: impl FnMut(&mut CircuitBuilder<F, D>, &CommonCircuitData<F, D>) -> Result<T>,
+) -> Result<CommonCircuitData<F, D>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    // Start with a circuit with only the verifier data as public inputs, as a cyclic circuit
+    // has at least those.
+    let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+    builder.add_verifier_data_public_inputs();
+    let mut common_data = builder.build::<C>().common;
+
+    for _ in 0..MAX_COMMON_DATA_ITERATIONS {
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        builder.check_goal_degree = false;
+        build_circuit(&mut builder, &common_data)?;
+        // The candidate is only built for its common data, which doesn't depend on the
+        // commitment to the constants and sigmas.
+        let data = builder.build_with_options::<C>(BuildOptions {
+            commit_to_sigma: false,
+            require_goal_common_data: false,
+            ..BuildOptions::default()
+        });
+        if data.common == common_data {
+            return Ok(common_data);
+        }
+        common_data = data.common;
+    }
+
+    Err(anyhow!(
+        "Common data for cyclic recursion did not converge after {} iterations",
+        MAX_COMMON_DATA_ITERATIONS
+    ))
+}
+
 /// Additional checks to be performed on a cyclic recursive proof in addition to verifying the proof.
 /// Checks that the purported verifier data in the public inputs match the real verifier data.
 pub fn check_cyclic_proof_verifier_data<
@@ -191,22 +240,26 @@ where
     C::Hasher: AlgebraicHasher<F>,
 {
     let pis = VerifierOnlyCircuitData::<C, D>::from_slice(&proof.public_inputs, common_data)?;
-    ensure!(verifier_data.constants_sigmas_cap == pis.constants_sigmas_cap);
-    ensure!(verifier_data.circuit_digest == pis.circuit_digest);
+    ensure!(
+        verifier_data.constants_sigmas_cap == pis.constants_sigmas_cap,
+        "The constants-sigmas cap in the public inputs doesn't match the verifier data's"
+    );
+    ensure!(
+        verifier_data.circuit_digest == pis.circuit_digest,
+        "The circuit digest in the public inputs is {}, but the verifier data's is {}",
+        pis.digest(),
+        verifier_data.digest()
+    );
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    #[cfg(not(feature = "std"))]
-    use alloc::vec;
-
     use anyhow::Result;
 
     use crate::field::extension::Extendable;
     use crate::field::types::{Field, PrimeField64};
-    use crate::gates::noop::NoopGate;
     use crate::hash::hash_types::{HashOutTarget, RichField};
     use crate::hash::hashing::hash_n_to_hash_no_pad;
     use crate::hash::poseidon::{PoseidonHash, PoseidonPermutation};
@@ -214,41 +267,12 @@ mod tests {
     use crate::plonk::circuit_builder::CircuitBuilder;
     use crate::plonk::circuit_data::{CircuitConfig, CommonCircuitData};
     use crate::plonk::config::{AlgebraicHasher, GenericConfig, PoseidonGoldilocksConfig};
-    use crate::recursion::cyclic_recursion::check_cyclic_proof_verifier_data;
+    use crate::plonk::plonk_common::PlonkOracle;
+    use crate::recursion::cyclic_recursion::{
+        check_cyclic_proof_verifier_data, common_data_for_recursion,
+    };
     use crate::recursion::dummy_circuit::cyclic_base_proof;
 
-    // Generates `CommonCircuitData` usable for recursion.
-    fn common_data_for_recursion<
-        F: RichField + Extendable<D>,
-        C: GenericConfig<D, F = F>,
-        const D: usize,
-    >() -> CommonCircuitData<F, D>
-    where
-        C::Hasher: AlgebraicHasher<F>,
-    {
-        let config = CircuitConfig::standard_recursion_config();
-        let builder = CircuitBuilder::<F, D>::new(config);
-        let data = builder.build::<C>();
-        let config = CircuitConfig::standard_recursion_config();
-        let mut builder = CircuitBuilder::<F, D>::new(config);
-        let proof = builder.add_virtual_proof_with_pis(&data.common);
-        let verifier_data =
-            builder.add_virtual_verifier_data(data.common.config.fri_config.cap_height);
-        builder.verify_proof::<C>(&proof, &verifier_data, &data.common);
-        let data = builder.build::<C>();
-
-        let config = CircuitConfig::standard_recursion_config();
-        let mut builder = CircuitBuilder::<F, D>::new(config);
-        let proof = builder.add_virtual_proof_with_pis(&data.common);
-        let verifier_data =
-            builder.add_virtual_verifier_data(data.common.config.fri_config.cap_height);
-        builder.verify_proof::<C>(&proof, &verifier_data, &data.common);
-        while builder.num_gates() < 1 << 12 {
-            builder.add_gate(NoopGate, vec![]);
-        }
-        builder.build::<C>().common
-    }
-
     /// Uses cyclic recursion to build a hash chain.
     /// The circuit has the following public input structure:
     /// - Initial hash (4)
@@ -261,51 +285,58 @@ mod tests {
         type C = PoseidonGoldilocksConfig;
         type F = <C as GenericConfig<D>>::F;
 
+        let build_circuit = |builder: &mut CircuitBuilder<F, D>,
+                             common_data: &CommonCircuitData<F, D>| {
+            let one = builder.one();
+
+            // Circuit that computes a repeated hash.
+            let initial_hash_target = builder.add_virtual_hash();
+            builder.register_public_inputs(&initial_hash_target.elements);
+            let current_hash_in = builder.add_virtual_hash();
+            let current_hash_out =
+                builder.hash_n_to_hash_no_pad::<PoseidonHash>(current_hash_in.elements.to_vec());
+            builder.register_public_inputs(&current_hash_out.elements);
+            let counter = builder.add_virtual_public_input();
+
+            let verifier_data_target = builder.add_verifier_data_public_inputs();
+            let condition = builder.add_virtual_bool_target_safe();
+
+            // Unpack inner proof's public inputs.
+            let inner_cyclic_proof_with_pis = builder.add_virtual_proof_with_pis(common_data);
+            let inner_cyclic_pis = &inner_cyclic_proof_with_pis.public_inputs;
+            let inner_cyclic_initial_hash =
+                HashOutTarget::try_from(&inner_cyclic_pis[0..4]).unwrap();
+            let inner_cyclic_latest_hash =
+                HashOutTarget::try_from(&inner_cyclic_pis[4..8]).unwrap();
+            let inner_cyclic_counter = inner_cyclic_pis[8];
+
+            // Connect our initial hash to that of our inner proof. (If there is no inner proof, the
+            // initial hash will be unconstrained, which is intentional.)
+            builder.connect_hashes(initial_hash_target, inner_cyclic_initial_hash);
+
+            // The input hash is the previous hash output if we have an inner proof, or the initial
+            // hash if this is the base case.
+            let actual_hash_in =
+                builder.select_hash(condition, inner_cyclic_latest_hash, initial_hash_target);
+            builder.connect_hashes(current_hash_in, actual_hash_in);
+
+            // Our chain length will be inner_counter + 1 if we have an inner proof, or 1 if not.
+            let new_counter = builder.mul_add(condition.target, inner_cyclic_counter, one);
+            builder.connect(counter, new_counter);
+
+            builder.conditionally_verify_cyclic_proof_or_dummy::<C>(
+                condition,
+                &inner_cyclic_proof_with_pis,
+                common_data,
+            )?;
+            Ok((condition, inner_cyclic_proof_with_pis, verifier_data_target))
+        };
+
         let config = CircuitConfig::standard_recursion_config();
+        let common_data = common_data_for_recursion::<F, C, _, D>(&config, build_circuit)?;
         let mut builder = CircuitBuilder::<F, D>::new(config);
-        let one = builder.one();
-
-        // Circuit that computes a repeated hash.
-        let initial_hash_target = builder.add_virtual_hash();
-        builder.register_public_inputs(&initial_hash_target.elements);
-        let current_hash_in = builder.add_virtual_hash();
-        let current_hash_out =
-            builder.hash_n_to_hash_no_pad::<PoseidonHash>(current_hash_in.elements.to_vec());
-        builder.register_public_inputs(&current_hash_out.elements);
-        let counter = builder.add_virtual_public_input();
-
-        let mut common_data = common_data_for_recursion::<F, C, D>();
-        let verifier_data_target = builder.add_verifier_data_public_inputs();
-        common_data.num_public_inputs = builder.num_public_inputs();
-
-        let condition = builder.add_virtual_bool_target_safe();
-
-        // Unpack inner proof's public inputs.
-        let inner_cyclic_proof_with_pis = builder.add_virtual_proof_with_pis(&common_data);
-        let inner_cyclic_pis = &inner_cyclic_proof_with_pis.public_inputs;
-        let inner_cyclic_initial_hash = HashOutTarget::try_from(&inner_cyclic_pis[0..4]).unwrap();
-        let inner_cyclic_latest_hash = HashOutTarget::try_from(&inner_cyclic_pis[4..8]).unwrap();
-        let inner_cyclic_counter = inner_cyclic_pis[8];
-
-        // Connect our initial hash to that of our inner proof. (If there is no inner proof, the
-        // initial hash will be unconstrained, which is intentional.)
-        builder.connect_hashes(initial_hash_target, inner_cyclic_initial_hash);
-
-        // The input hash is the previous hash output if we have an inner proof, or the initial hash
-        // if this is the base case.
-        let actual_hash_in =
-            builder.select_hash(condition, inner_cyclic_latest_hash, initial_hash_target);
-        builder.connect_hashes(current_hash_in, actual_hash_in);
-
-        // Our chain length will be inner_counter + 1 if we have an inner proof, or 1 if not.
-        let new_counter = builder.mul_add(condition.target, inner_cyclic_counter, one);
-        builder.connect(counter, new_counter);
-
-        builder.conditionally_verify_cyclic_proof_or_dummy::<C>(
-            condition,
-            &inner_cyclic_proof_with_pis,
-            &common_data,
-        )?;
+        let (condition, inner_cyclic_proof_with_pis, verifier_data_target) =
+            build_circuit(&mut builder, &common_data)?;
 
         let cyclic_circuit_data = builder.build::<C>();
 
@@ -368,6 +399,73 @@ mod tests {
         cyclic_circuit_data.verify(proof)
     }
 
+    /// Builds a minimal cyclic circuit, which verifies either a proof of itself or some other
+    /// proof with the same common data.
+    fn build_minimal_cyclic_circuit<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        builder: &mut CircuitBuilder<F, D>,
+        common_data: &CommonCircuitData<F, D>,
+    ) -> Result<()>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        builder.add_verifier_data_public_inputs();
+        let condition = builder.add_virtual_bool_target_safe();
+        let cyclic_proof = builder.add_virtual_proof_with_pis(common_data);
+        let other_proof = builder.add_virtual_proof_with_pis(common_data);
+        let other_verifier_data = builder.add_virtual_verifier_data(
+            common_data.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.conditionally_verify_cyclic_proof::<C>(
+            condition,
+            &cyclic_proof,
+            &other_proof,
+            &other_verifier_data,
+            common_data,
+        )
+    }
+
+    #[test]
+    fn test_cyclic_recursion_minimum_degree() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let common_data = common_data_for_recursion::<F, C, _, D>(
+            &config,
+            build_minimal_cyclic_circuit::<F, C, D>,
+        )?;
+        assert!(common_data.minimum_recursion_degree_bits::<C>() <= common_data.degree_bits());
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        build_minimal_cyclic_circuit::<F, C, D>(&mut builder, &common_data)?;
+        assert_eq!(builder.build::<C>().common, common_data);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "Recursive verification needs a degree of at least")]
+    fn test_cyclic_recursion_degree_too_small() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut common_data = common_data_for_recursion::<F, C, _, D>(
+            &config,
+            build_minimal_cyclic_circuit::<F, C, D>,
+        )
+        .unwrap();
+        common_data.fri_params.degree_bits -= 1;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let _ = build_minimal_cyclic_circuit::<F, C, D>(&mut builder, &common_data);
+    }
+
     fn iterate_poseidon<F: RichField>(initial_state: [F; 4], n: usize) -> [F; 4] {
         let mut current = initial_state;
         for _ in 0..n {
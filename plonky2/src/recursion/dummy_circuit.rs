@@ -12,17 +12,22 @@ use plonky2_util::ceil_div_usize;
 
 use crate::fri::proof::{FriProof, FriProofTarget};
 use crate::gadgets::polynomial::PolynomialCoeffsExtTarget;
+use crate::gates::constant::ConstantGate;
 use crate::gates::noop::NoopGate;
-use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
+use crate::gates::random_access::RandomAccessGate;
+use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField, NUM_HASH_OUT_ELTS};
+use crate::hash::hashing::PlonkyPermutation;
 use crate::hash::merkle_tree::MerkleCap;
 use crate::iop::generator::{GeneratedValues, SimpleGenerator};
 use crate::iop::target::Target;
 use crate::iop::witness::{PartialWitness, PartitionWitness, WitnessWrite};
 use crate::plonk::circuit_builder::CircuitBuilder;
 use crate::plonk::circuit_data::{
-    CircuitData, CommonCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
+    CircuitData, CommonCircuitData, PublicInputCommitment, VerifierCircuitTarget,
+    VerifierOnlyCircuitData,
 };
 use crate::plonk::config::{AlgebraicHasher, GenericConfig, GenericHashOut, Hasher};
+use crate::plonk::plonk_common::PlonkOracle;
 use crate::plonk::proof::{
     OpeningSet, OpeningSetTarget, Proof, ProofTarget, ProofWithPublicInputs,
     ProofWithPublicInputsTarget,
@@ -45,7 +50,7 @@ where
     C::Hasher: AlgebraicHasher<C::F>,
 {
     let pis_len = common_data.num_public_inputs;
-    let cap_elements = common_data.config.fri_config.num_cap_elements();
+    let cap_elements = 1 << common_data.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS);
     let start_vk_pis = pis_len - 4 - 4 * cap_elements;
 
     // Add the cyclic verifier data public inputs.
@@ -68,11 +73,7 @@ where
 /// Generate a proof for a dummy circuit. The `public_inputs` parameter let the caller specify
 /// certain public inputs (identified by their indices) which should be given specific values.
 /// The rest will default to zero.
-pub(crate) fn dummy_proof<
-    F: RichField + Extendable<D>,
-    C: GenericConfig<D, F = F>,
-    const D: usize,
->(
+pub fn dummy_proof<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     circuit: &CircuitData<F, C, D>,
     nonzero_public_inputs: HashMap<usize, F>,
 ) -> anyhow::Result<ProofWithPublicInputs<F, C, D>>
@@ -86,12 +87,11 @@ where
     circuit.prove(pw)
 }
 
-/// Generate a circuit matching a given `CommonCircuitData`.
-pub(crate) fn dummy_circuit<
-    F: RichField + Extendable<D>,
-    C: GenericConfig<D, F = F>,
-    const D: usize,
->(
+/// Generate a circuit matching a given `CommonCircuitData`, i.e. with the same degree, gates and
+/// number of public inputs, but no constraints besides those of the public inputs. Its proofs can
+/// stand in wherever a proof with this `CommonCircuitData` is expected. The circuit is
+/// deterministic, so its verifier data can be computed ahead of time, e.g. to whitelist it.
+pub fn dummy_circuit<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
     common_data: &CommonCircuitData<F, D>,
 ) -> CircuitData<F, C, D> {
     let config = common_data.config.clone();
@@ -101,17 +101,47 @@ pub(crate) fn dummy_circuit<
     );
 
     // Number of `NoopGate`s to add to get a circuit of size `degree` in the end.
-    // Need to account for public input hashing, a `PublicInputGate` and a `ConstantGate`.
+    // Need to account for public input hashing, a `PublicInputGate` and a gate for the constants.
     let degree = common_data.degree();
-    let num_noop_gate = degree - ceil_div_usize(common_data.num_public_inputs, 8) - 2;
+    let commitment = common_data.public_input_commitment;
+    let chunk_ranges = commitment.chunk_ranges(common_data.num_public_inputs);
+    // Each permutation of the hasher absorbs `rate` elements.
+    let rate = <<C::InnerHasher as Hasher<F>>::Permutation as PlonkyPermutation<F>>::RATE;
+    let mut num_hash_permutations = chunk_ranges
+        .iter()
+        .map(|range| ceil_div_usize(range.len(), rate))
+        .sum::<usize>();
+    if let PublicInputCommitment::Chunked { .. } = commitment {
+        num_hash_permutations += ceil_div_usize(chunk_ranges.len() * NUM_HASH_OUT_ELTS, rate);
+    }
+    let num_noop_gate = degree - num_hash_permutations - 2;
 
     let mut builder = CircuitBuilder::<F, D>::new(config);
+    builder.set_public_input_commitment(commitment);
+    builder.set_duplicate_public_inputs(common_data.duplicate_public_inputs);
     for _ in 0..num_noop_gate {
         builder.add_gate(NoopGate, vec![]);
     }
     for gate in &common_data.gates {
         builder.add_gate_to_gate_set(gate.clone());
     }
+    // The constants of a circuit without `ConstantGate`s all fit in the spare wires of its
+    // `RandomAccessGate`s, so we place ours in one of those rather than in a `ConstantGate`.
+    let has_constant_gate = common_data
+        .gates
+        .iter()
+        .any(|gate| gate.0.as_any().is::<ConstantGate>());
+    let random_access_bits = common_data.gates.iter().find_map(|gate| {
+        gate.0
+            .as_any()
+            .downcast_ref::<RandomAccessGate<F, D>>()
+            .filter(|gate| gate.num_extra_constants > 0)
+            .map(|gate| gate.bits)
+    });
+    if let (false, Some(bits)) = (has_constant_gate, random_access_bits) {
+        let zero = builder.zero();
+        builder.random_access(zero, vec![zero; 1 << bits]);
+    }
     for _ in 0..common_data.num_public_inputs {
         builder.add_virtual_public_input();
     }
@@ -133,7 +163,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         let dummy_proof_with_pis = dummy_proof::<F, C, D>(&dummy_circuit, HashMap::new())?;
         let dummy_proof_with_pis_target = self.add_virtual_proof_with_pis(common_data);
         let dummy_verifier_data_target =
-            self.add_virtual_verifier_data(self.config.fri_config.cap_height);
+            self.add_virtual_verifier_data(self.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
 
         self.add_simple_generator(DummyProofGenerator {
             proof_with_pis_target: dummy_proof_with_pis_target.clone(),
@@ -194,6 +224,7 @@ where
                 },
             },
             public_inputs: vec![],
+            circuit_digest_hint: None,
         };
 
         let verifier_data_target = VerifierCircuitTarget {
@@ -208,6 +239,7 @@ where
             circuit_digest: <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::Hash::from_bytes(
                 &vec![0; <<C as GenericConfig<D>>::Hasher as Hasher<C::F>>::HASH_SIZE],
             ),
+            domain_separator: Vec::new(),
         };
 
         Self {
@@ -258,3 +290,82 @@ where
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Field;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn dummy_proof_matches_common_data() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+
+        // A circuit with two public inputs, whose shape the dummy circuit copies.
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_public_input();
+        let x_cubed = builder.exp_u64(x, 3);
+        builder.register_public_input(x_cubed);
+        for _ in 0..100 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let common_data = builder.build::<C>().common;
+
+        let dummy_data = dummy_circuit::<F, C, D>(&common_data);
+        assert_eq!(dummy_data.common, common_data);
+        // Building the dummy circuit again gives the same verifier data.
+        assert_eq!(
+            dummy_circuit::<F, C, D>(&common_data).verifier_only,
+            dummy_data.verifier_only
+        );
+
+        let pi = F::from_canonical_u32(7);
+        let dummy_proof = dummy_proof(&dummy_data, HashMap::from([(1, pi)]))?;
+        assert_eq!(dummy_proof.public_inputs, vec![F::ZERO, pi]);
+        dummy_data.verify(dummy_proof.clone())?;
+
+        // The dummy proof is accepted in place of a real one when verification is skipped.
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&common_data);
+        let verifier_target = builder.add_virtual_verifier_data(
+            common_data.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        let condition = builder.add_virtual_bool_target_safe();
+        builder.conditionally_verify_proof_or_dummy::<C>(
+            condition,
+            &proof_target,
+            &verifier_target,
+            &common_data,
+        )?;
+        let outer_data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&proof_target, &dummy_proof);
+        pw.set_verifier_data_target(&verifier_target, &dummy_data.verifier_only);
+        pw.set_bool_target(condition, false);
+        let outer_proof = outer_data.prove(pw)?;
+        outer_data.verify(outer_proof)
+    }
+
+    #[test]
+    fn dummy_circuit_matches_chunked_commitment() -> Result<()> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        builder.set_public_input_commitment(PublicInputCommitment::Chunked { chunk_size: 20 });
+        for _ in 0..100 {
+            builder.add_virtual_public_input();
+        }
+        let common_data = builder.build::<C>().common;
+
+        let dummy_data = dummy_circuit::<F, C, D>(&common_data);
+        assert_eq!(dummy_data.common, common_data);
+        let dummy_proof = dummy_proof(&dummy_data, HashMap::new())?;
+        dummy_data.verify(dummy_proof)
+    }
+}
@@ -2,9 +2,14 @@
 //!
 //! This module also provides ways to perform conditional recursive verification
 //! (between two different circuits, depending on a condition), and cyclic
-//! recursion where a circuit implements its own verification logic.
+//! recursion where a circuit implements its own verification logic, and helpers to aggregate
+//! proofs two at a time, verify a proof of any circuit from a fixed set, or shrink a proof to a
+//! minimal degree.
 
+pub mod aggregation;
 pub mod conditional_recursive_verifier;
 pub mod cyclic_recursion;
 pub mod dummy_circuit;
 pub mod recursive_verifier;
+pub mod shrink;
+pub mod verifier_set;
@@ -1,19 +1,67 @@
+#[cfg(not(feature = "std"))]
+use alloc::{format, vec::Vec};
+use core::ops::Range;
+
+use log::Level;
+
 use crate::field::extension::Extendable;
+use crate::fri::structure::FriInstanceShapeTarget;
 use crate::hash::hash_types::{HashOutTarget, RichField};
-use crate::plonk::circuit_builder::CircuitBuilder;
-use crate::plonk::circuit_data::{CommonCircuitData, VerifierCircuitTarget};
+use crate::iop::challenger::{RecursiveChallenger, TranscriptLog};
+use crate::iop::target::Target;
+use crate::plonk::circuit_builder::{BuildOptions, CircuitBuilder};
+use crate::plonk::circuit_data::{CommonCircuitData, PublicInputCommitment, VerifierCircuitTarget};
 use crate::plonk::config::{AlgebraicHasher, GenericConfig};
-use crate::plonk::plonk_common::salt_size;
+use crate::plonk::plonk_common::PlonkOracle;
 use crate::plonk::proof::{
     OpeningSetTarget, ProofChallengesTarget, ProofTarget, ProofWithPublicInputsTarget,
+    PublicInputChunkTarget, PublicInputChunksTarget,
 };
 use crate::plonk::vanishing_poly::eval_vanishing_poly_circuit;
 use crate::plonk::vars::EvaluationTargets;
+use crate::util::log2_ceil;
 use crate::util::reducing::ReducingFactorTarget;
-use crate::with_context;
+
+impl<F: RichField + Extendable<D>, const D: usize> CommonCircuitData<F, D> {
+    /// The smallest `degree_bits` of a circuit with this circuit's config which verifies one of its
+    /// proofs, found by building such a circuit. A circuit with a fixed degree, e.g. a cyclic
+    /// circuit, needs at least this degree to verify proofs of this circuit.
+    pub fn minimum_recursion_degree_bits<C: GenericConfig<D, F = F>>(&self) -> usize
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let mut builder = CircuitBuilder::<F, D>::new(self.config.clone());
+        let proof = builder.add_virtual_proof_with_pis(self);
+        let verifier_data = builder
+            .add_virtual_verifier_data(self.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
+        builder.verify_proof::<C>(&proof, &verifier_data, self);
+        // Only the common data is needed, which doesn't depend on the commitment to the constants
+        // and sigmas.
+        let data = builder.build_with_options::<C>(BuildOptions {
+            commit_to_sigma: false,
+            require_goal_common_data: false,
+            ..BuildOptions::default()
+        });
+        data.common.degree_bits()
+    }
+}
+
+/// The parts of verifying proofs of an inner circuit which only depend on its verifier data and
+/// common data, shared by all the proofs of a batch.
+struct InnerVerifierPrecomputation<const D: usize> {
+    fri_instance_shape: FriInstanceShapeTarget<D>,
+}
 
 impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
     /// Recursively verifies an inner proof.
+    ///
+    /// The inner config `C` only needs an algebraic hasher, and is independent of the config the
+    /// outer circuit is built with. E.g. a Poseidon proof can be verified in a circuit proven with
+    /// `KeccakGoldilocksConfig`, whose proofs are cheaper to verify on the EVM.
+    ///
+    /// Panics if the circuit is expected to have a fixed common data, as in cyclic recursion, whose
+    /// degree is too small for the verifier. See
+    /// [`CommonCircuitData::minimum_recursion_degree_bits`].
     pub fn verify_proof<C: GenericConfig<D, F = F>>(
         &mut self,
         proof_with_pis: &ProofWithPublicInputsTarget<D>,
@@ -21,27 +69,272 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         inner_common_data: &CommonCircuitData<F, D>,
     ) where
         C::Hasher: AlgebraicHasher<F>,
+    {
+        let precomputation =
+            self.precompute_inner_verification(inner_verifier_data, inner_common_data);
+        let mut challenger = RecursiveChallenger::new(self);
+        self.verify_proof_with_challenger::<C>(
+            &mut challenger,
+            proof_with_pis,
+            inner_verifier_data,
+            inner_common_data,
+            &precomputation,
+        );
+        self.check_goal_degree();
+    }
+
+    /// Like `verify_proof`, but also returns the labeled transcript of the verifier's challenger.
+    /// Its values in a witness of this circuit can be compared by [`diff_transcripts`] with the
+    /// native transcript from `ProofWithPublicInputs::get_challenges_with_transcript`, to find
+    /// where the two verifiers diverge.
+    ///
+    /// [`diff_transcripts`]: crate::iop::challenger::diff_transcripts
+    pub fn verify_proof_with_transcript_log<C: GenericConfig<D, F = F>>(
+        &mut self,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        inner_verifier_data: &VerifierCircuitTarget,
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) -> TranscriptLog<Target>
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let precomputation =
+            self.precompute_inner_verification(inner_verifier_data, inner_common_data);
+        let mut challenger = RecursiveChallenger::new(self);
+        challenger.enable_transcript_log();
+        self.verify_proof_with_challenger::<C>(
+            &mut challenger,
+            proof_with_pis,
+            inner_verifier_data,
+            inner_common_data,
+            &precomputation,
+        );
+        self.check_goal_degree();
+        challenger
+            .take_transcript_log()
+            .expect("The transcript log was enabled")
+    }
+
+    fn verify_proof_with_challenger<C: GenericConfig<D, F = F>>(
+        &mut self,
+        challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        inner_verifier_data: &VerifierCircuitTarget,
+        inner_common_data: &CommonCircuitData<F, D>,
+        precomputation: &InnerVerifierPrecomputation<D>,
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
     {
         assert_eq!(
             proof_with_pis.public_inputs.len(),
-            inner_common_data.num_public_inputs
+            inner_common_data.num_public_inputs,
+            "The proof target has a different number of public inputs than the inner circuit"
         );
         let public_inputs_hash =
-            self.hash_n_to_hash_no_pad::<C::InnerHasher>(proof_with_pis.public_inputs.clone());
-        let challenges = proof_with_pis.get_challenges::<F, C>(
+            self.hash_inner_public_inputs::<C>(proof_with_pis, inner_common_data);
+        self.verify_proof_with_public_inputs_hash::<C>(
+            challenger,
+            &proof_with_pis.proof,
+            public_inputs_hash,
+            inner_verifier_data,
+            inner_common_data,
+            precomputation,
+        );
+    }
+
+    /// Recursively verifies an inner proof whose circuit commits to its public inputs in chunks,
+    /// hashing only the chunks in `public_input_chunks` which are inspected. The digests of the
+    /// other chunks are taken as they are, but are still bound to the proof, so an inner proof
+    /// can expose many public inputs without its verifier paying to hash all of them.
+    pub fn verify_proof_with_public_input_chunks<C: GenericConfig<D, F = F>>(
+        &mut self,
+        proof: &ProofTarget<D>,
+        public_input_chunks: &PublicInputChunksTarget,
+        inner_verifier_data: &VerifierCircuitTarget,
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let commitment = inner_common_data.public_input_commitment;
+        assert!(
+            matches!(commitment, PublicInputCommitment::Chunked { .. }),
+            "The inner circuit doesn't commit to its public inputs in chunks"
+        );
+        let chunk_ranges = commitment.chunk_ranges(inner_common_data.num_public_inputs);
+        assert_eq!(public_input_chunks.chunks.len(), chunk_ranges.len());
+
+        let digests = public_input_chunks
+            .chunks
+            .iter()
+            .zip(chunk_ranges)
+            .map(|(chunk, range)| match chunk {
+                PublicInputChunkTarget::Inputs(inputs) => {
+                    assert_eq!(inputs.len(), range.len());
+                    self.hash_n_to_hash_no_pad::<C::InnerHasher>(inputs.clone())
+                }
+                &PublicInputChunkTarget::Digest(digest) => digest,
+            })
+            .collect::<Vec<_>>();
+        let public_inputs_hash = self.hash_public_input_chunk_digests::<C::InnerHasher>(&digests);
+        let precomputation =
+            self.precompute_inner_verification(inner_verifier_data, inner_common_data);
+        let mut challenger = RecursiveChallenger::new(self);
+        self.verify_proof_with_public_inputs_hash::<C>(
+            &mut challenger,
+            proof,
+            public_inputs_hash,
+            inner_verifier_data,
+            inner_common_data,
+            &precomputation,
+        );
+        self.check_goal_degree();
+    }
+
+    fn verify_proof_with_public_inputs_hash<C: GenericConfig<D, F = F>>(
+        &mut self,
+        challenger: &mut RecursiveChallenger<F, C::Hasher, D>,
+        proof: &ProofTarget<D>,
+        public_inputs_hash: HashOutTarget,
+        inner_verifier_data: &VerifierCircuitTarget,
+        inner_common_data: &CommonCircuitData<F, D>,
+        precomputation: &InnerVerifierPrecomputation<D>,
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let challenges = proof.get_challenges::<F, C>(
             self,
+            challenger,
             public_inputs_hash,
             inner_verifier_data.circuit_digest,
             inner_common_data,
         );
 
         self.verify_proof_with_challenges::<C>(
-            &proof_with_pis.proof,
+            proof,
             public_inputs_hash,
             challenges,
             inner_verifier_data,
             inner_common_data,
+            precomputation,
+        );
+    }
+
+    /// Does the work of verifying proofs of the inner circuit which only depends on its verifier
+    /// data and common data, once for all the proofs verified against them.
+    fn precompute_inner_verification(
+        &mut self,
+        inner_verifier_data: &VerifierCircuitTarget,
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) -> InnerVerifierPrecomputation<D> {
+        let fri_params = &inner_common_data.fri_params;
+        assert_eq!(
+            inner_verifier_data.constants_sigmas_cap.0.len(),
+            1 << fri_params.initial_cap_height(PlonkOracle::CONSTANTS_SIGMAS.index),
+            "The verifier data's constants and sigmas cap doesn't have the inner circuit's height"
+        );
+        self.check_fri_params(fri_params);
+        InnerVerifierPrecomputation {
+            fri_instance_shape: inner_common_data.get_fri_instance_shape_target(self),
+        }
+    }
+
+    /// Panics if the circuit is expected to have a fixed common data whose degree is too small
+    /// for the verifiers added so far.
+    fn check_goal_degree(&self) {
+        if let Some(goal_common_data) = self
+            .goal_common_data
+            .as_ref()
+            .filter(|_| self.check_goal_degree)
+        {
+            // The builder adds a few more gates when building, so this is only a lower bound.
+            let required_degree_bits = log2_ceil(self.num_gates());
+            assert!(
+                required_degree_bits <= goal_common_data.degree_bits(),
+                "Recursive verification needs a degree of at least 2^{} rows, but the expected \
+                common data has degree_bits = {}",
+                required_degree_bits,
+                goal_common_data.degree_bits()
+            );
+        }
+    }
+
+    /// Recursively verifies several proofs of the same inner circuit. The checks of the verifier
+    /// data and FRI parameters, and the FRI instance's batches and the constants their points are
+    /// derived with, are done once for the batch, as are any constants the builder can reuse. Each
+    /// proof still needs its own challenges and checks, which make up most of the verifier.
+    pub fn verify_proofs_batch<C: GenericConfig<D, F = F>>(
+        &mut self,
+        proofs_with_pis: &[ProofWithPublicInputsTarget<D>],
+        inner_verifier_data: &VerifierCircuitTarget,
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        let precomputation =
+            self.precompute_inner_verification(inner_verifier_data, inner_common_data);
+        for (i, proof_with_pis) in proofs_with_pis.iter().enumerate() {
+            self.with_context(
+                Level::Debug,
+                &format!("verify proof {i} of the batch"),
+                |builder| {
+                    let mut challenger = RecursiveChallenger::new(builder);
+                    builder.verify_proof_with_challenger::<C>(
+                        &mut challenger,
+                        proof_with_pis,
+                        inner_verifier_data,
+                        inner_common_data,
+                        &precomputation,
+                    )
+                },
+            );
+        }
+        self.check_goal_degree();
+    }
+
+    /// Registers all the public inputs of `proof_with_pis` as public inputs of this circuit, and
+    /// returns the index of the first one among this circuit's public inputs.
+    pub fn register_inner_public_inputs(
+        &mut self,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+    ) -> usize {
+        let offset = self.num_public_inputs();
+        self.register_public_inputs(&proof_with_pis.public_inputs);
+        offset
+    }
+
+    /// Connects the public inputs of `proof_with_pis` in `range` to `targets`, e.g. to expose only
+    /// some of them among this circuit's public inputs.
+    pub fn connect_public_inputs_slice(
+        &mut self,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        range: Range<usize>,
+        targets: &[Target],
+    ) {
+        assert_eq!(
+            range.len(),
+            targets.len(),
+            "Public inputs slice and targets have different lengths"
         );
+        for (&inner, &outer) in proof_with_pis.public_inputs[range].iter().zip(targets) {
+            self.connect(inner, outer);
+        }
+    }
+
+    /// Hashes the public inputs of `proof_with_pis` as the verifier of a proof with config `C`
+    /// does, so that the result equals the inner proof's `get_public_inputs_hash`. This is useful
+    /// to expose a digest of the inner public inputs rather than all of them.
+    pub fn hash_inner_public_inputs<C: GenericConfig<D, F = F>>(
+        &mut self,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) -> HashOutTarget
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        self.hash_public_inputs::<C::InnerHasher>(
+            inner_common_data.public_input_commitment,
+            &proof_with_pis.public_inputs,
+        )
     }
 
     /// Recursively verifies an inner proof.
@@ -52,6 +345,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         challenges: ProofChallengesTarget<D>,
         inner_verifier_data: &VerifierCircuitTarget,
         inner_common_data: &CommonCircuitData<F, D>,
+        precomputation: &InnerVerifierPrecomputation<D>,
     ) where
         C::Hasher: AlgebraicHasher<F>,
     {
@@ -63,6 +357,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             local_constants,
             local_wires,
             public_inputs_hash: &public_inputs_hash,
+            extra_openings: &proof.openings.extra_openings,
         };
         let local_zs = &proof.openings.plonk_zs;
         let next_zs = &proof.openings.plonk_zs_next;
@@ -73,41 +368,48 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 
         let zeta_pow_deg =
             self.exp_power_of_2_extension(challenges.plonk_zeta, inner_common_data.degree_bits());
-        let vanishing_polys_zeta = with_context!(
-            self,
+        let vanishing_polys_zeta = self.with_context(
+            Level::Debug,
             "evaluate the vanishing polynomial at our challenge point, zeta.",
-            eval_vanishing_poly_circuit::<F, D>(
-                self,
-                inner_common_data,
-                challenges.plonk_zeta,
-                zeta_pow_deg,
-                vars,
-                local_zs,
-                next_zs,
-                local_lookup_zs,
-                next_lookup_zs,
-                partial_products,
-                s_sigmas,
-                &challenges.plonk_betas,
-                &challenges.plonk_gammas,
-                &challenges.plonk_alphas,
-                &challenges.plonk_deltas,
-            )
+            |builder| {
+                eval_vanishing_poly_circuit::<F, D>(
+                    builder,
+                    inner_common_data,
+                    challenges.plonk_zeta,
+                    zeta_pow_deg,
+                    vars,
+                    local_zs,
+                    next_zs,
+                    local_lookup_zs,
+                    next_lookup_zs,
+                    partial_products,
+                    s_sigmas,
+                    &challenges.plonk_betas,
+                    &challenges.plonk_gammas,
+                    &challenges.plonk_alphas,
+                    &challenges.plonk_deltas,
+                )
+            },
         );
 
-        with_context!(self, "check vanishing and quotient polynomials.", {
-            let quotient_polys_zeta = &proof.openings.quotient_polys;
-            let mut scale = ReducingFactorTarget::new(zeta_pow_deg);
-            let z_h_zeta = self.sub_extension(zeta_pow_deg, one);
-            for (i, chunk) in quotient_polys_zeta
-                .chunks(inner_common_data.quotient_degree_factor)
-                .enumerate()
-            {
-                let recombined_quotient = scale.reduce(chunk, self);
-                let computed_vanishing_poly = self.mul_extension(z_h_zeta, recombined_quotient);
-                self.connect_extension(vanishing_polys_zeta[i], computed_vanishing_poly);
-            }
-        });
+        self.with_context(
+            Level::Debug,
+            "check vanishing and quotient polynomials.",
+            |builder| {
+                let quotient_polys_zeta = &proof.openings.quotient_polys;
+                let mut scale = ReducingFactorTarget::new(zeta_pow_deg);
+                let z_h_zeta = builder.sub_extension(zeta_pow_deg, one);
+                for (i, chunk) in quotient_polys_zeta
+                    .chunks(inner_common_data.quotient_degree_factor)
+                    .enumerate()
+                {
+                    let recombined_quotient = scale.reduce(chunk, builder);
+                    let computed_vanishing_poly =
+                        builder.mul_extension(z_h_zeta, recombined_quotient);
+                    builder.connect_extension(vanishing_polys_zeta[i], computed_vanishing_poly);
+                }
+            },
+        );
 
         let merkle_caps = &[
             inner_verifier_data.constants_sigmas_cap.clone(),
@@ -116,11 +418,11 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             proof.quotient_polys_cap.clone(),
         ];
 
-        let fri_instance = inner_common_data.get_fri_instance_target(self, challenges.plonk_zeta);
-        with_context!(
-            self,
-            "verify FRI proof",
-            self.verify_fri_proof::<C>(
+        let fri_instance = precomputation
+            .fri_instance_shape
+            .at_zeta(self, challenges.plonk_zeta);
+        self.with_context(Level::Debug, "verify FRI proof", |builder| {
+            builder.verify_fri_proof_with_checked_params::<C>(
                 &fri_instance,
                 &proof.openings.to_fri_openings(),
                 &challenges.fri_challenges,
@@ -128,7 +430,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
                 &proof.opening_proof,
                 &inner_common_data.fri_params,
             )
-        );
+        });
     }
 
     pub fn add_virtual_proof_with_pis(
@@ -143,31 +445,47 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
         }
     }
 
-    fn add_virtual_proof(&mut self, common_data: &CommonCircuitData<F, D>) -> ProofTarget<D> {
-        let config = &common_data.config;
+    /// Adds targets for the public inputs of a proof of a circuit committing to them in chunks,
+    /// for `verify_proof_with_public_input_chunks`. Only the chunks in `inspected_chunks` get
+    /// targets for their public inputs; the others only get targets for their digests.
+    pub fn add_virtual_public_input_chunks(
+        &mut self,
+        common_data: &CommonCircuitData<F, D>,
+        inspected_chunks: &[usize],
+    ) -> PublicInputChunksTarget {
+        let chunks = common_data
+            .public_input_commitment
+            .chunk_ranges(common_data.num_public_inputs)
+            .into_iter()
+            .enumerate()
+            .map(|(i, range)| {
+                if inspected_chunks.contains(&i) {
+                    PublicInputChunkTarget::Inputs(self.add_virtual_targets(range.len()))
+                } else {
+                    PublicInputChunkTarget::Digest(self.add_virtual_hash())
+                }
+            })
+            .collect();
+        PublicInputChunksTarget { chunks }
+    }
+
+    pub fn add_virtual_proof(&mut self, common_data: &CommonCircuitData<F, D>) -> ProofTarget<D> {
         let fri_params = &common_data.fri_params;
-        let cap_height = fri_params.config.cap_height;
-
-        let salt = salt_size(common_data.fri_params.hiding);
-        let num_leaves_per_oracle = &[
-            common_data.num_preprocessed_polys(),
-            config.num_wires + salt,
-            common_data.num_zs_partial_products_polys() + common_data.num_all_lookup_polys() + salt,
-            common_data.num_quotient_polys() + salt,
-        ];
+        let cap_height = |oracle: PlonkOracle| fri_params.initial_cap_height(oracle.index);
 
         ProofTarget {
-            wires_cap: self.add_virtual_cap(cap_height),
-            plonk_zs_partial_products_cap: self.add_virtual_cap(cap_height),
-            quotient_polys_cap: self.add_virtual_cap(cap_height),
+            wires_cap: self.add_virtual_cap(cap_height(PlonkOracle::WIRES)),
+            plonk_zs_partial_products_cap: self
+                .add_virtual_cap(cap_height(PlonkOracle::ZS_PARTIAL_PRODUCTS)),
+            quotient_polys_cap: self.add_virtual_cap(cap_height(PlonkOracle::QUOTIENT)),
             openings: self.add_opening_set(common_data),
-            opening_proof: self.add_virtual_fri_proof(num_leaves_per_oracle, fri_params),
+            opening_proof: self.add_virtual_fri_proof(&common_data.fri_oracles(), fri_params),
         }
     }
 
     fn add_opening_set(&mut self, common_data: &CommonCircuitData<F, D>) -> OpeningSetTarget<D> {
         let config = &common_data.config;
-        let num_challenges = config.num_challenges;
+        let num_challenges = common_data.num_challenges();
         let total_partial_products = num_challenges * common_data.num_partial_products;
         let has_lookup = common_data.num_lookup_polys != 0;
         let num_lookups = if has_lookup {
@@ -185,6 +503,11 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
             next_lookup_zs: self.add_virtual_extension_targets(num_lookups),
             partial_products: self.add_virtual_extension_targets(total_partial_products),
             quotient_polys: self.add_virtual_extension_targets(common_data.num_quotient_polys()),
+            extra_openings: common_data
+                .num_extra_openings()
+                .into_iter()
+                .map(|num_openings| self.add_virtual_extension_targets(num_openings))
+                .collect(),
         }
     }
 }
@@ -192,7 +515,7 @@ impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
 #[cfg(test)]
 mod tests {
     #[cfg(not(feature = "std"))]
-    use alloc::{sync::Arc, vec};
+    use alloc::{sync::Arc, vec, vec::Vec};
     #[cfg(feature = "std")]
     use std::sync::Arc;
 
@@ -201,16 +524,25 @@ mod tests {
     use log::{info, Level};
 
     use super::*;
+    use crate::circuit_size_test;
+    use crate::field::types::Field;
     use crate::fri::reduction_strategies::FriReductionStrategy;
     use crate::fri::FriConfig;
     use crate::gadgets::lookup::{OTHER_TABLE, TIP5_TABLE};
+    use crate::gates::constant_table::ConstantTableGate;
+    use crate::gates::gate::GateRef;
     use crate::gates::lookup_table::LookupTable;
     use crate::gates::noop::NoopGate;
+    use crate::hash::hash_types::HashOut;
     use crate::iop::witness::{PartialWitness, WitnessWrite};
-    use crate::plonk::circuit_data::{CircuitConfig, VerifierOnlyCircuitData};
+    use crate::plonk::circuit_data::{
+        CircuitConfig, OracleCapHeights, VerifierCircuitData, VerifierOnlyCircuitData,
+    };
     use crate::plonk::config::{KeccakGoldilocksConfig, PoseidonGoldilocksConfig};
+    use crate::plonk::plonk_common::PlonkOracle;
     use crate::plonk::proof::{CompressedProofWithPublicInputs, ProofWithPublicInputs};
     use crate::plonk::prover::prove;
+    use crate::util::ceil_div_usize;
     use crate::util::timing::TimingTree;
 
     #[test]
@@ -229,6 +561,552 @@ mod tests {
         Ok(())
     }
 
+    circuit_size_test!(
+        recursive_verifier_size,
+        CircuitConfig::standard_recursion_config(),
+        |builder| {
+            const D: usize = 2;
+            type C = PoseidonGoldilocksConfig;
+            type F = <C as GenericConfig<D>>::F;
+            let mut inner_builder =
+                CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+            for _ in 0..4_000 {
+                inner_builder.add_gate(NoopGate, vec![]);
+            }
+            let inner_cd = inner_builder.build::<C>().common;
+
+            let proof = builder.add_virtual_proof_with_pis(&inner_cd);
+            let inner_data = builder
+                .add_virtual_verifier_data(inner_cd.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
+            builder.verify_proof::<C>(&proof, &inner_data, &inner_cd);
+        },
+        degree_bits: 12,
+        // 3464 rows (55 `ArithmeticExtensionGate`s) before the FRI openings were folded into the
+        // per-query reductions, and 3461 while constant tables took a row per `num_routed_wires`
+        // values rather than per `num_constants`.
+        num_rows: 3501,
+        gates: {
+            "ArithmeticExtensionGate" => 52,
+            "ArithmeticGate" => 70,
+            "BaseSumGate" => 57,
+            "ConstantTableGate" => 41,
+            "CosetInterpolationGate" => 56,
+            "MulExtensionGate" => 34,
+            "PoseidonGate" => 2773,
+            "PublicInputGate" => 1,
+            "RandomAccessGate" => 196,
+            "ReducingExtensionGate" => 38,
+            "ReducingGate" => 168,
+            "ScalarArithmeticExtensionGate" => 15,
+        },
+    );
+
+    /// Verifies a standard-config proof with public inputs inside an outer circuit which forwards
+    /// them, using the witness setters for both the proof and the verifier data.
+    #[test]
+    fn test_recursive_verifier_forwards_public_inputs() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_public_input();
+        let x_cubed = builder.exp_u64(x, 3);
+        builder.register_public_input(x_cubed);
+        let inner_data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(5));
+        let inner_proof = inner_data.prove(pw)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let verifier_target = builder.add_virtual_verifier_data(
+            inner_data
+                .common
+                .config
+                .cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.verify_proof::<C>(&proof_target, &verifier_target, &inner_data.common);
+        builder.register_inner_public_inputs(&proof_target);
+        let outer_data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_verifier_data_target(&verifier_target, &inner_data.verifier_only);
+        pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+        let outer_proof = outer_data.prove(pw)?;
+        assert_eq!(outer_proof.public_inputs, inner_proof.public_inputs);
+        assert_eq!(outer_proof.public_inputs[1], F::from_canonical_u32(125));
+        outer_data.verify(outer_proof)
+    }
+
+    /// Proves, serializes and recursively verifies a circuit without public inputs, whose
+    /// public inputs hash is the permutation of the zero state, in an outer circuit without any
+    /// either.
+    #[test]
+    fn test_recursive_verifier_zero_public_inputs() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_target();
+        let x_cubed = builder.exp_u64(x, 3);
+        let expected = builder.constant(F::from_canonical_u32(125));
+        builder.connect(x_cubed, expected);
+        let inner_data = builder.build::<C>();
+        assert_eq!(inner_data.common.num_public_inputs, 0);
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(5));
+        let inner_proof = inner_data.prove(pw)?;
+        assert!(inner_proof.public_inputs.is_empty());
+        assert_ne!(
+            inner_proof.get_public_inputs_hash(&inner_data.common),
+            HashOut::ZERO
+        );
+        inner_data.verify(inner_proof.clone())?;
+        test_serialization(&inner_proof, &inner_data.verifier_only, &inner_data.common)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+        assert!(proof_target.public_inputs.is_empty());
+        let verifier_target = builder.add_virtual_verifier_data(
+            inner_data
+                .common
+                .config
+                .cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.verify_proof::<C>(&proof_target, &verifier_target, &inner_data.common);
+        let outer_data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_verifier_data_target(&verifier_target, &inner_data.verifier_only);
+        pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+        let outer_proof = outer_data.prove(pw)?;
+        assert!(outer_proof.public_inputs.is_empty());
+        test_serialization(&outer_proof, &outer_data.verifier_only, &outer_data.common)?;
+        outer_data.verify(outer_proof)
+    }
+
+    #[test]
+    fn test_recursive_verifier_extra_opening_point() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_public_input();
+        let x_cubed = builder.exp_u64(x, 3);
+        builder.register_public_input(x_cubed);
+        builder.add_opening_point(-1, &[PlonkOracle::WIRES]);
+        builder.add_opening_point(2, &[PlonkOracle::WIRES, PlonkOracle::ZS_PARTIAL_PRODUCTS]);
+        let inner_data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(5));
+        let inner_proof = inner_data.prove(pw)?;
+        assert_eq!(inner_proof.proof.openings.extra_openings.len(), 2);
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let verifier_target = builder.add_virtual_verifier_data(
+            inner_data
+                .common
+                .config
+                .cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.verify_proof::<C>(&proof_target, &verifier_target, &inner_data.common);
+        let outer_data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_verifier_data_target(&verifier_target, &inner_data.verifier_only);
+        pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+        let outer_proof = outer_data.prove(pw)?;
+        outer_data.verify(outer_proof)
+    }
+
+    /// Verifies a Poseidon proof inside a circuit proven with Keccak, as done before handing a
+    /// proof to an EVM verifier.
+    #[test]
+    fn test_recursive_verifier_keccak_outer() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type PC = PoseidonGoldilocksConfig;
+        type KC = KeccakGoldilocksConfig;
+        type F = <PC as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_public_input();
+        let x_squared = builder.square(x);
+        builder.register_public_input(x_squared);
+        let inner_data = builder.build::<PC>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(9));
+        let inner_proof = inner_data.prove(pw)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let verifier_target = builder.constant_verifier_data(&inner_data.verifier_only);
+        builder.verify_proof::<PC>(&proof_target, &verifier_target, &inner_data.common);
+        builder.register_inner_public_inputs(&proof_target);
+        let outer_data = builder.build::<KC>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+        let outer_proof = outer_data.prove(pw)?;
+        assert_eq!(outer_proof.public_inputs, inner_proof.public_inputs);
+        test_serialization(&outer_proof, &outer_data.verifier_only, &outer_data.common)?;
+        outer_data.verify(outer_proof)
+    }
+
+    /// With constant verifier data and no public inputs, the inner proof's first challenger
+    /// permutation only depends on constants, and is computed natively instead of in the circuit.
+    #[test]
+    fn test_recursive_verifier_constant_verifier_data() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_target();
+        let x_cubed = builder.exp_u64(x, 3);
+        let expected = builder.constant(F::from_canonical_u32(125));
+        builder.connect(x_cubed, expected);
+        let inner_data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(5));
+        let inner_proof = inner_data.prove(pw)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let virtual_proof = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let virtual_vd = builder.add_virtual_verifier_data(
+            inner_data
+                .common
+                .config
+                .cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.verify_proof::<C>(&virtual_proof, &virtual_vd, &inner_data.common);
+        let virtual_gates = builder.num_gates();
+        let virtual_data = builder.build::<C>();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let constant_proof = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let constant_vd = builder.constant_verifier_data(&inner_data.verifier_only);
+        builder.verify_proof::<C>(&constant_proof, &constant_vd, &inner_data.common);
+        let constant_gates = builder.num_gates();
+        let constant_data = builder.build::<C>();
+
+        info!(
+            "Verifier gates: {} with virtual verifier data, {} with constant verifier data",
+            virtual_gates, constant_gates
+        );
+        assert!(constant_gates < virtual_gates);
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&virtual_proof, &inner_proof);
+        pw.set_verifier_data_target(&virtual_vd, &inner_data.verifier_only);
+        virtual_data.verify(virtual_data.prove(pw)?)?;
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&constant_proof, &inner_proof);
+        constant_data.verify(constant_data.prove(pw)?)
+    }
+
+    /// Compares the size of a verifier selecting values by query index with `random_access` and
+    /// powers of constants, to that of the naive verifier using `select` trees and variable bases.
+    #[test]
+    fn test_recursive_verifier_index_selection() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let (proof, vd, common_data) = dummy_proof::<F, C, D>(&config, 4_000)?;
+
+        let mut gates = vec![];
+        for naive_index_selection in [true, false] {
+            let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+            builder.naive_index_selection = naive_index_selection;
+            let pt = builder.add_virtual_proof_with_pis(&common_data);
+            let inner_data =
+                builder.add_virtual_verifier_data(config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
+            builder.verify_proof::<C>(&pt, &inner_data, &common_data);
+            gates.push(builder.num_gates());
+            let data = builder.build::<C>();
+
+            let mut pw = PartialWitness::new();
+            pw.set_proof_with_pis_target(&pt, &proof);
+            pw.set_verifier_data_target(&inner_data, &vd);
+            data.verify(data.prove(pw)?)?;
+        }
+
+        info!(
+            "Verifier gates: {} with select trees, {} with random access",
+            gates[0], gates[1]
+        );
+        assert!(gates[1] < gates[0]);
+
+        Ok(())
+    }
+
+    /// Compares the size of a verifier taking the coset shifts from a constant table, to that of
+    /// the naive verifier taking them as individual constants. The inner proof is itself
+    /// recursive, so that the constants don't all fit in the spare slots of other gates.
+    #[test]
+    fn test_recursive_verifier_coset_shifts_table() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let (proof, vd, common_data) = dummy_proof::<F, C, D>(&config, 4_000)?;
+        let (proof, vd, common_data) =
+            recursive_proof::<F, C, C, D>(proof, vd, common_data, &config, None, false, false)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let pt = builder.add_virtual_proof_with_pis(&common_data);
+        let inner_data =
+            builder.add_virtual_verifier_data(config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
+        builder.verify_proof::<C>(&pt, &inner_data, &common_data);
+        // One table holds the coset shifts `k_is`, one per routed wire, and another the powers of
+        // the subgroup generator the FRI points are derived with.
+        let table_gate = GateRef::<F, D>::new(ConstantTableGate::new(config.num_constants));
+        assert_eq!(
+            builder.cost_report::<C>().gate_rows[&table_gate.0.id()],
+            ceil_div_usize(config.num_routed_wires, config.num_constants) + 1
+        );
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&pt, &proof);
+        pw.set_verifier_data_target(&inner_data, &vd);
+        data.verify(data.prove(pw)?)
+    }
+
+    #[test]
+    fn test_minimum_recursion_degree_bits() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let (proof, vd, common_data) = dummy_proof::<F, C, D>(&config, 4_000)?;
+        let min_degree_bits = common_data.minimum_recursion_degree_bits::<C>();
+        assert_eq!(min_degree_bits, 12);
+
+        let (_, _, outer_common_data) =
+            recursive_proof::<F, C, C, D>(proof, vd, common_data, &config, None, false, false)?;
+        assert_eq!(outer_common_data.degree_bits(), min_degree_bits);
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_input_forwarding_helpers() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_public_input();
+        let y = builder.add_virtual_public_input();
+        let xy = builder.mul(x, y);
+        builder.register_public_input(xy);
+        let inner_data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u32(6));
+        pw.set_target(y, F::from_canonical_u32(7));
+        let inner_proof = inner_data.prove(pw)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let verifier_target = builder.constant_verifier_data(&inner_data.verifier_only);
+        builder.verify_proof::<C>(&proof_target, &verifier_target, &inner_data.common);
+        // Expose the product first, then the inner public inputs' digest, then all of them.
+        let product = builder.add_virtual_public_input();
+        builder.connect_public_inputs_slice(&proof_target, 2..3, &[product]);
+        let hash = builder.hash_inner_public_inputs::<C>(&proof_target, &inner_data.common);
+        builder.register_public_inputs(&hash.elements);
+        let offset = builder.register_inner_public_inputs(&proof_target);
+        assert_eq!(offset, 5);
+        let outer_data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+        let outer_proof = outer_data.prove(pw)?;
+        assert_eq!(outer_proof.public_inputs[0], F::from_canonical_u32(42));
+        assert_eq!(
+            outer_proof.public_inputs[1..5],
+            inner_proof
+                .get_public_inputs_hash(&inner_data.common)
+                .elements
+        );
+        assert_eq!(
+            outer_proof.public_inputs[offset..],
+            inner_proof.public_inputs
+        );
+        outer_data.verify(outer_proof)
+    }
+
+    /// A proof with many public inputs committed in chunks can be verified while only hashing the
+    /// chunks the outer circuit inspects, taking the other chunks' digests as witnesses.
+    #[test]
+    fn test_verify_proof_with_public_input_chunks() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+        let commitment = PublicInputCommitment::Chunked { chunk_size: 1024 };
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        builder.set_public_input_commitment(commitment);
+        let inputs = (0..50_000)
+            .map(|_| builder.add_virtual_public_input())
+            .collect::<Vec<_>>();
+        let inner_data = builder.build::<C>();
+        assert_ne!(
+            inner_data.common.public_input_commitment,
+            PublicInputCommitment::Flat
+        );
+        let mut pw = PartialWitness::new();
+        for (i, &input) in inputs.iter().enumerate() {
+            pw.set_target(input, F::from_canonical_usize(i));
+        }
+        let inner_proof = inner_data.prove(pw)?;
+        inner_data.verify(inner_proof.clone())?;
+        test_serialization(&inner_proof, &inner_data.verifier_only, &inner_data.common)?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let proof_target = builder.add_virtual_proof(&inner_data.common);
+        let chunks_target = builder.add_virtual_public_input_chunks(&inner_data.common, &[3]);
+        let verifier_target = builder.add_virtual_verifier_data(
+            inner_data
+                .common
+                .config
+                .cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.verify_proof_with_public_input_chunks::<C>(
+            &proof_target,
+            &chunks_target,
+            &verifier_target,
+            &inner_data.common,
+        );
+        assert!(chunks_target.inputs(2).is_none());
+        builder.register_public_input(chunks_target.inputs(3).unwrap()[5]);
+        let chunked_gates = builder.num_gates();
+        let outer_data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_proof_target(&proof_target, &inner_proof.proof);
+        pw.set_public_input_chunks_target::<C, D>(
+            &chunks_target,
+            &inner_proof.public_inputs,
+            &inner_data.common,
+        );
+        pw.set_verifier_data_target(&verifier_target, &inner_data.verifier_only);
+        let outer_proof = outer_data.prove(pw)?;
+        assert_eq!(
+            outer_proof.public_inputs,
+            [F::from_canonical_usize(3 * 1024 + 5)]
+        );
+        outer_data.verify(outer_proof)?;
+
+        // Hashing all public inputs in the circuit costs thousands of Poseidon permutations.
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+        let verifier_target = builder.add_virtual_verifier_data(
+            inner_data
+                .common
+                .config
+                .cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.verify_proof::<C>(&proof_target, &verifier_target, &inner_data.common);
+        info!(
+            "Verifier gates: {} hashing one chunk, {} hashing all public inputs",
+            chunked_gates,
+            builder.num_gates()
+        );
+        assert!(chunked_gates + 5_000 < builder.num_gates());
+
+        Ok(())
+    }
+
+    /// Verifying several proofs of the same circuit in a batch costs slightly less than verifying
+    /// them in separate circuits, since the verifier data and constants are shared.
+    #[test]
+    fn test_verify_proofs_batch() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        let config = CircuitConfig::standard_recursion_config();
+
+        let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+        let x = builder.add_virtual_public_input();
+        let x_squared = builder.square(x);
+        builder.register_public_input(x_squared);
+        let inner_data = builder.build::<C>();
+        let inner_proofs = (1..=4)
+            .map(|i| {
+                let mut pw = PartialWitness::new();
+                pw.set_target(x, F::from_canonical_u32(i));
+                inner_data.prove(pw)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let verifier_gates = |num_proofs: usize| {
+            let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+            let proof_targets = (0..num_proofs)
+                .map(|_| builder.add_virtual_proof_with_pis(&inner_data.common))
+                .collect::<Vec<_>>();
+            let verifier_target = builder.constant_verifier_data(&inner_data.verifier_only);
+            builder.verify_proofs_batch::<C>(&proof_targets, &verifier_target, &inner_data.common);
+            (builder.num_gates(), builder, proof_targets)
+        };
+        let (single_gates, _, _) = verifier_gates(1);
+        let (pair_gates, _, _) = verifier_gates(2);
+        let (batch_gates, mut builder, proof_targets) = verifier_gates(4);
+        info!(
+            "Verifier gates: {} for one proof, {} per additional proof",
+            single_gates,
+            (batch_gates - single_gates) / 3
+        );
+        // A batch of `n` proofs costs less than `n` separate verifiers, as the shared work is only
+        // done once.
+        assert!(pair_gates < 2 * single_gates);
+        assert!(batch_gates < 4 * single_gates);
+
+        for proof_target in &proof_targets {
+            builder.register_inner_public_inputs(proof_target);
+        }
+        let outer_data = builder.build::<C>();
+        let mut pw = PartialWitness::new();
+        for (proof_target, inner_proof) in proof_targets.iter().zip(&inner_proofs) {
+            pw.set_proof_with_pis_target(proof_target, inner_proof);
+        }
+        let outer_proof = outer_data.prove(pw)?;
+        assert_eq!(
+            outer_proof.public_inputs,
+            inner_proofs
+                .iter()
+                .flat_map(|proof| proof.public_inputs.clone())
+                .collect::<Vec<_>>()
+        );
+        outer_data.verify(outer_proof)
+    }
+
     #[test]
     fn test_recursive_verifier_one_lookup() -> Result<()> {
         init_logger();
@@ -364,6 +1242,7 @@ mod tests {
                 proof_of_work_bits: 20,
                 reduction_strategy: FriReductionStrategy::MinSize(None),
                 num_query_rounds: 10,
+                final_poly_degree_bits: 0,
             },
             ..high_rate_config
         };
@@ -383,6 +1262,180 @@ mod tests {
         Ok(())
     }
 
+    /// Proves and recursively verifies the same circuit under each reduction strategy.
+    #[test]
+    fn test_recursive_verifier_reduction_strategies() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let standard_config = CircuitConfig::standard_recursion_config();
+        let strategies = [
+            FriReductionStrategy::Fixed(vec![2, 2, 2]),
+            FriReductionStrategy::ConstantArityBits(4, 5),
+            FriReductionStrategy::MinSize(None),
+            // Reductions of arity 2 interpolate a line rather than using a gate.
+            FriReductionStrategy::Fixed(vec![1, 3, 1]),
+        ];
+        let mut proof_sizes = Vec::new();
+        for reduction_strategy in strategies {
+            let config = CircuitConfig {
+                fri_config: FriConfig {
+                    reduction_strategy,
+                    ..standard_config.fri_config.clone()
+                },
+                ..standard_config.clone()
+            };
+            let (proof, vd, common_data) = dummy_proof::<F, C, D>(&config, 4_000)?;
+            info!(
+                "{:?}: arities {:?}, proof length {} bytes",
+                config.fri_config.reduction_strategy,
+                common_data.fri_params.reduction_arity_bits,
+                proof.to_bytes().len()
+            );
+            proof_sizes.push(proof.to_bytes().len());
+            recursive_proof::<F, C, C, D>(
+                proof,
+                vd,
+                common_data,
+                &standard_config,
+                None,
+                false,
+                false,
+            )?;
+        }
+
+        // `MinSize` searches for the smallest proof, so it shouldn't lose to the other strategies.
+        assert!(proof_sizes[2] <= proof_sizes[0]);
+        assert!(proof_sizes[2] <= proof_sizes[1]);
+
+        Ok(())
+    }
+
+    /// Recursively verifies a proof whose oracles have different cap heights, in an outer circuit
+    /// which also uses them.
+    #[test]
+    fn test_recursive_verifier_per_oracle_cap_heights() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let standard_config = CircuitConfig::standard_recursion_config();
+        let config = CircuitConfig {
+            oracle_cap_heights: OracleCapHeights {
+                constants_sigmas: Some(6),
+                wires: Some(5),
+                zs_partial_products: Some(3),
+                quotient: Some(5),
+            },
+            ..standard_config.clone()
+        };
+
+        let (uniform_proof, _, _) = dummy_proof::<F, C, D>(&standard_config, 4_000)?;
+        let (proof, vd, common_data) = dummy_proof::<F, C, D>(&config, 4_000)?;
+        assert_eq!(vd.constants_sigmas_cap.height(), 6);
+        assert_eq!(proof.proof.wires_cap.height(), 5);
+        assert_eq!(proof.proof.plonk_zs_partial_products_cap.height(), 3);
+        assert!(proof.to_bytes().len() < uniform_proof.to_bytes().len());
+
+        let (proof, vd, common_data) =
+            recursive_proof::<F, C, C, D>(proof, vd, common_data, &config, None, false, false)?;
+        test_serialization(&proof, &vd, &common_data)?;
+
+        Ok(())
+    }
+
+    /// Proves the same circuit with final polynomials of several degrees, and recursively verifies
+    /// one of them.
+    #[test]
+    fn test_recursive_verifier_final_poly_degrees() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let standard_config = CircuitConfig::standard_recursion_config();
+        for final_poly_degree_bits in [0, 3, 5] {
+            let config = CircuitConfig {
+                fri_config: FriConfig {
+                    cap_height: 2,
+                    reduction_strategy: FriReductionStrategy::ConstantArityBits(2, 0),
+                    final_poly_degree_bits,
+                    ..standard_config.fri_config.clone()
+                },
+                ..standard_config.clone()
+            };
+            let (proof, vd, common_data) = dummy_proof::<F, C, D>(&config, 100)?;
+            let fri_params = &common_data.fri_params;
+            assert!(fri_params.final_poly_bits() >= final_poly_degree_bits);
+            assert!(fri_params.final_poly_bits() < final_poly_degree_bits + 2);
+            assert_eq!(
+                proof.proof.opening_proof.final_poly.len(),
+                fri_params.final_poly_len()
+            );
+            if final_poly_degree_bits == 3 {
+                recursive_proof::<F, C, C, D>(
+                    proof,
+                    vd,
+                    common_data,
+                    &standard_config,
+                    None,
+                    false,
+                    false,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// With more query rounds than LDE points, query indices must repeat. The compressed proof
+    /// stores each repeated path once, and can still be verified natively and recursively.
+    #[test]
+    fn test_recursive_verifier_compressed_proof() -> Result<()> {
+        init_logger();
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        let standard_config = CircuitConfig::standard_recursion_config();
+        let mut config = standard_config.clone();
+        config.fri_config.num_query_rounds = 40;
+        let (proof, vd, common_data) = dummy_proof::<F, C, D>(&config, 0)?;
+        let lde_size = common_data.fri_params.lde_size();
+        assert!(lde_size < config.fri_config.num_query_rounds);
+
+        let compressed_proof = proof.clone().compress(&vd.circuit_digest, &common_data)?;
+        let query_round_proofs = &compressed_proof.proof.opening_proof.query_round_proofs;
+        assert_eq!(
+            query_round_proofs.indices.len(),
+            config.fri_config.num_query_rounds
+        );
+        assert!(query_round_proofs.initial_trees_proofs.len() <= lde_size);
+        assert!(compressed_proof.to_bytes().len() < proof.to_bytes().len());
+        let verifier_data = VerifierCircuitData {
+            verifier_only: vd.clone(),
+            common: common_data.clone(),
+        };
+        verifier_data.verify_compressed(compressed_proof.clone())?;
+
+        let mut builder = CircuitBuilder::<F, D>::new(standard_config);
+        let pt = builder.add_virtual_proof_with_pis(&common_data);
+        let inner_data = builder.add_virtual_verifier_data(
+            common_data.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS),
+        );
+        builder.verify_proof::<C>(&pt, &inner_data, &common_data);
+        let data = builder.build::<C>();
+
+        let mut pw = PartialWitness::new();
+        pw.set_compressed_proof_with_pis_target(&pt, &compressed_proof, &vd, &common_data)?;
+        pw.set_verifier_data_target(&inner_data, &vd);
+        let proof = data.prove(pw)?;
+        data.verify(proof)
+    }
+
     #[test]
     fn test_recursive_verifier_multi_hash() -> Result<()> {
         init_logger();
@@ -642,7 +1695,8 @@ mod tests {
         let pt = builder.add_virtual_proof_with_pis(&inner_cd);
         pw.set_proof_with_pis_target(&pt, &inner_proof);
 
-        let inner_data = builder.add_virtual_verifier_data(inner_cd.config.fri_config.cap_height);
+        let inner_data = builder
+            .add_virtual_verifier_data(inner_cd.config.cap_height(PlonkOracle::CONSTANTS_SIGMAS));
         pw.set_cap_target(
             &inner_data.constants_sigmas_cap,
             &inner_vd.constants_sigmas_cap,
@@ -0,0 +1,197 @@
+//! Shrinking a proof to the smallest degree reachable by recursion, e.g. to make a final proof
+//! cheap to verify on-chain.
+//!
+//! Each layer is a circuit of the [`shrink_config`] verifying a proof of the previous layer, with
+//! the inner circuit's public inputs forwarded verbatim. A verifier circuit's size depends mostly on
+//! the inner proof's degree and FRI parameters, so the degree drops with each layer until it settles
+//! at a floor, given by [`minimal_degree_bits`].
+
+#[cfg(not(feature = "std"))]
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use anyhow::{anyhow, Result};
+
+use crate::field::extension::Extendable;
+use crate::fri::FriConfig;
+use crate::hash::hash_types::RichField;
+use crate::iop::witness::{PartialWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{
+    CircuitConfig, CircuitData, CommonCircuitData, ProverCircuitData, VerifierCircuitData,
+    VerifierOnlyCircuitData,
+};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+
+/// The maximum number of layers built before giving up on reaching a fixed degree.
+const MAX_SHRINK_LAYERS: usize = 8;
+
+/// The config of shrinking layers. A high FRI rate needs fewer queries for the same security, and
+/// so makes proofs that are cheaper to verify recursively, at the cost of a slower prover.
+pub fn shrink_config() -> CircuitConfig {
+    let standard_config = CircuitConfig::standard_recursion_config();
+    CircuitConfig {
+        fri_config: FriConfig {
+            rate_bits: 7,
+            proof_of_work_bits: 16,
+            num_query_rounds: 12,
+            ..standard_config.fri_config
+        },
+        ..standard_config
+    }
+}
+
+struct ShrinkLayer<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize> {
+    data: CircuitData<F, C, D>,
+    inner_proof: ProofWithPublicInputsTarget<D>,
+}
+
+/// Builds a circuit verifying a proof of the given inner circuit, and forwarding its public inputs.
+fn build_shrink_layer<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    const D: usize,
+>(
+    inner_common: &CommonCircuitData<F, D>,
+    inner_verifier_only: &VerifierOnlyCircuitData<C, D>,
+) -> ShrinkLayer<F, C, D>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut builder = CircuitBuilder::<F, D>::new(shrink_config());
+    let inner_proof = builder.add_virtual_proof_with_pis(inner_common);
+    let inner_verifier_data = builder.constant_verifier_data(inner_verifier_only);
+    builder.verify_proof::<C>(&inner_proof, &inner_verifier_data, inner_common);
+    builder.register_inner_public_inputs(&inner_proof);
+    ShrinkLayer {
+        data: builder.build::<C>(),
+        inner_proof,
+    }
+}
+
+/// Returns the degree that [`shrink_to_minimal`] reaches for a circuit with `num_public_inputs`
+/// public inputs, found by stacking shrinking layers on a trivial circuit until the degree stops
+/// changing.
+pub fn minimal_degree_bits<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    const D: usize,
+>(
+    num_public_inputs: usize,
+) -> Result<usize>
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut builder = CircuitBuilder::<F, D>::new(shrink_config());
+    for _ in 0..num_public_inputs {
+        builder.add_virtual_public_input();
+    }
+    let base = builder.build::<C>();
+    let (mut common, mut verifier_only) = (base.common, base.verifier_only);
+
+    for _ in 0..MAX_SHRINK_LAYERS {
+        let layer = build_shrink_layer::<F, C, D>(&common, &verifier_only);
+        let degree_bits = layer.data.common.degree_bits();
+        if degree_bits == common.degree_bits() {
+            return Ok(degree_bits);
+        }
+        (common, verifier_only) = (layer.data.common, layer.data.verifier_only);
+    }
+
+    Err(anyhow!(
+        "Shrinking layers did not reach a fixed degree after {} layers",
+        MAX_SHRINK_LAYERS
+    ))
+}
+
+/// Proves the outermost shrinking layer of a circuit, given a proof of that circuit.
+pub type ShrinkProver<F, C, const D: usize> =
+    Box<dyn Fn(&ProofWithPublicInputs<F, C, D>) -> Result<ProofWithPublicInputs<F, C, D>>>;
+
+/// Wraps `inner` in shrinking layers until the degree stops decreasing. At least one layer is
+/// always added.
+///
+/// Returns the verifier data of the outermost layer, and a function turning a proof of `inner`
+/// into a proof of that layer, with the same public inputs.
+pub fn shrink_to_minimal<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F> + 'static,
+    const D: usize,
+>(
+    inner: &CircuitData<F, C, D>,
+) -> (VerifierCircuitData<F, C, D>, ShrinkProver<F, C, D>)
+where
+    C::Hasher: AlgebraicHasher<F>,
+{
+    let mut layer = build_shrink_layer::<F, C, D>(&inner.common, &inner.verifier_only);
+    let mut layers = vec![];
+    for _ in 1..MAX_SHRINK_LAYERS {
+        let next = build_shrink_layer::<F, C, D>(&layer.data.common, &layer.data.verifier_only);
+        if next.data.common.degree_bits() >= layer.data.common.degree_bits() {
+            break;
+        }
+        layers.push(layer);
+        layer = next;
+    }
+    let verifier_data = layer.data.verifier_data();
+    layers.push(layer);
+
+    let layers = layers
+        .into_iter()
+        .map(|layer| (layer.data.prover_data(), layer.inner_proof))
+        .collect::<Vec<(ProverCircuitData<F, C, D>, _)>>();
+    let prove = move |proof: &ProofWithPublicInputs<F, C, D>| {
+        let mut proof = proof.clone();
+        for (data, inner_proof) in &layers {
+            let mut pw = PartialWitness::new();
+            pw.set_proof_with_pis_target(inner_proof, &proof);
+            proof = data.prove(pw)?;
+        }
+        Ok(proof)
+    };
+    (verifier_data, Box::new(prove))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::Field;
+    use crate::gates::noop::NoopGate;
+    use crate::plonk::config::PoseidonGoldilocksConfig;
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn shrink_medium_circuit() -> Result<()> {
+        // A circuit of degree 2^13, which computes `x^8` and pads the rest with no-ops.
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let x = builder.add_virtual_public_input();
+        let mut y = x;
+        for _ in 0..3 {
+            y = builder.square(y);
+        }
+        builder.register_public_input(y);
+        for _ in 0..1 << 12 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let inner = builder.build::<C>();
+        assert_eq!(inner.common.degree_bits(), 13);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::TWO);
+        let proof = inner.prove(pw)?;
+
+        let (verifier_data, shrink) = shrink_to_minimal(&inner);
+        let floor = minimal_degree_bits::<F, C, D>(2)?;
+        assert_eq!(floor, 12);
+        assert_eq!(verifier_data.common.degree_bits(), floor);
+        assert_eq!(verifier_data.common.config, shrink_config());
+
+        let shrunk = shrink(&proof)?;
+        assert_eq!(shrunk.public_inputs, proof.public_inputs);
+        assert_eq!(shrunk.public_inputs[1], F::from_canonical_u32(256));
+        verifier_data.verify(shrunk)
+    }
+}
@@ -0,0 +1,202 @@
+//! Recursive verification of a proof of any circuit from a fixed set.
+//!
+//! All the circuits of the set must share the same `CommonCircuitData`, which
+//! `build_with_shared_common_data` achieves by padding them to the same degree and gate set.
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+use anyhow::{ensure, Result};
+
+use crate::field::extension::Extendable;
+use crate::gates::gate::GateRef;
+use crate::gates::noop::NoopGate;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::Target;
+use crate::plonk::circuit_builder::{BuildOptions, CircuitBuilder};
+use crate::plonk::circuit_data::{
+    CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
+};
+use crate::plonk::config::{AlgebraicHasher, GenericConfig};
+use crate::plonk::proof::ProofWithPublicInputsTarget;
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilder<F, D> {
+    /// Verifies a proof of the circuit with verifier data `verifier_data[index]`, and registers
+    /// that circuit's digest as public inputs, so that the outer verifier knows which circuit was
+    /// proven. The circuits must all have `inner_common_data` as their common data.
+    ///
+    /// `index` is only range checked up to the next power of two of `verifier_data.len()`, and
+    /// larger indices select the last circuit.
+    pub fn verify_proof_from_set<C: GenericConfig<D, F = F>>(
+        &mut self,
+        proof_with_pis: &ProofWithPublicInputsTarget<D>,
+        index: Target,
+        verifier_data: &[VerifierOnlyCircuitData<C, D>],
+        inner_common_data: &CommonCircuitData<F, D>,
+    ) -> VerifierCircuitTarget
+    where
+        C::Hasher: AlgebraicHasher<F>,
+    {
+        assert!(!verifier_data.is_empty(), "The set of circuits is empty");
+        let mut verifier_data_targets = verifier_data
+            .iter()
+            .map(|vd| self.constant_verifier_data(vd))
+            .collect::<Vec<_>>();
+        // `random_access` needs a power-of-two number of entries.
+        let last = verifier_data_targets.last().unwrap().clone();
+        verifier_data_targets.resize(verifier_data.len().next_power_of_two(), last);
+
+        let selected = self.random_access_verifier_data(index, verifier_data_targets);
+        self.verify_proof::<C>(proof_with_pis, &selected, inner_common_data);
+        self.register_public_inputs(&selected.circuit_digest.elements);
+        selected
+    }
+}
+
+/// Adds the gates and targets of a circuit to a builder, returning e.g. its targets.
+pub type BuildCircuitFn<'a, F, T, const D: usize> = &'a dyn Fn(&mut CircuitBuilder<F, D>) -> T;
+
+/// Builds each of `build_circuits` into a circuit of the given config, all padded to the same
+/// degree and gate set, so that they share one `CommonCircuitData` and their proofs can be
+/// verified with `verify_proof_from_set`. Returns each circuit along with the value returned by its
+/// build function, e.g. its targets.
+///
+/// The circuits must have the same number of public inputs. Each build function is called twice:
+/// once to find the largest degree and all the gates used, and once to build the padded circuit.
+pub fn build_with_shared_common_data<F, C, T, const D: usize>(
+    config: &CircuitConfig,
+    build_circuits: &[BuildCircuitFn<F, T, D>],
+) -> Result<Vec<(CircuitData<F, C, D>, T)>>
+where
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+{
+    ensure!(!build_circuits.is_empty(), "No circuits to build");
+    let unpadded = build_circuits
+        .iter()
+        .map(|build_circuit| {
+            let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+            build_circuit(&mut builder);
+            // Only the common data is needed, which doesn't depend on the commitment to the
+            // constants and sigmas.
+            builder
+                .build_with_options::<C>(BuildOptions {
+                    commit_to_sigma: false,
+                    require_goal_common_data: false,
+                    ..BuildOptions::default()
+                })
+                .common
+        })
+        .collect::<Vec<_>>();
+    ensure!(
+        unpadded
+            .iter()
+            .all(|common| common.num_public_inputs == unpadded[0].num_public_inputs),
+        "Circuits with a shared common data must have the same number of public inputs"
+    );
+    let degree_bits = unpadded
+        .iter()
+        .map(|common| common.degree_bits())
+        .max()
+        .unwrap();
+
+    let circuits = build_circuits
+        .iter()
+        .map(|build_circuit| {
+            let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+            let value = build_circuit(&mut builder);
+            builder.add_gate_to_gate_set(GateRef::new(NoopGate));
+            for gate in unpadded.iter().flat_map(|common| &common.gates) {
+                builder.add_gate_to_gate_set(gate.clone());
+            }
+            // Pad to just over half the target size, and let the builder's own gates and padding
+            // take it the rest of the way to `2^degree_bits`.
+            let min_gates = (1 << (degree_bits - 1)) + 1;
+            for _ in builder.num_gates()..min_gates {
+                builder.add_gate(NoopGate, vec![]);
+            }
+            (builder.build::<C>(), value)
+        })
+        .collect::<Vec<_>>();
+    ensure!(
+        circuits
+            .iter()
+            .all(|(data, _)| data.common == circuits[0].0.common),
+        "Circuits could not be padded to a shared common data"
+    );
+    Ok(circuits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::types::Field;
+    use crate::hash::poseidon::PoseidonHash;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::config::{Hasher, PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[test]
+    fn verify_proofs_of_two_circuits() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+
+        // Proves knowledge of a square root of the public input.
+        let square = |builder: &mut CircuitBuilder<F, D>| {
+            let x = builder.add_virtual_target();
+            let x_squared = builder.square(x);
+            builder.register_public_input(x_squared);
+            x
+        };
+        // Proves knowledge of a Poseidon preimage of the public input, in a larger circuit.
+        let hash = |builder: &mut CircuitBuilder<F, D>| {
+            let x = builder.add_virtual_target();
+            let h = builder.hash_n_to_hash_no_pad::<PoseidonHash>(vec![x]);
+            builder.register_public_input(h.elements[0]);
+            for _ in 0..100 {
+                builder.add_gate(NoopGate, vec![]);
+            }
+            x
+        };
+        let circuits = build_with_shared_common_data::<F, C, _, D>(&config, &[&square, &hash])?;
+        let common_data = &circuits[0].0.common;
+        assert_ne!(
+            circuits[0].0.verifier_only.circuit_digest,
+            circuits[1].0.verifier_only.circuit_digest
+        );
+
+        let verifier_data = circuits
+            .iter()
+            .map(|(data, _)| data.verifier_only.clone())
+            .collect::<Vec<_>>();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let proof_target = builder.add_virtual_proof_with_pis(common_data);
+        let index = builder.add_virtual_target();
+        builder.verify_proof_from_set::<C>(&proof_target, index, &verifier_data, common_data);
+        builder.register_inner_public_inputs(&proof_target);
+        let outer_data = builder.build::<C>();
+
+        let x = F::from_canonical_u32(3);
+        let expected_outputs = [x * x, PoseidonHash::hash_no_pad(&[x]).elements[0]];
+        for (i, (data, x_target)) in circuits.iter().enumerate() {
+            let mut pw = PartialWitness::new();
+            pw.set_target(*x_target, x);
+            let inner_proof = data.prove(pw)?;
+
+            let mut pw = PartialWitness::new();
+            pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+            pw.set_target(index, F::from_canonical_usize(i));
+            let proof = outer_data.prove(pw)?;
+            assert_eq!(
+                proof.public_inputs[..4],
+                data.verifier_only.circuit_digest.elements
+            );
+            assert_eq!(proof.public_inputs[4], expected_outputs[i]);
+            outer_data.verify(proof)?;
+        }
+
+        Ok(())
+    }
+}
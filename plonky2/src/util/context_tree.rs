@@ -1,5 +1,6 @@
 #[cfg(not(feature = "std"))]
 use alloc::{
+    format,
     string::{String, ToString},
     vec,
     vec::Vec,
@@ -38,6 +39,11 @@ impl ContextTree {
         self.exit_gate_count.is_none()
     }
 
+    /// Whether a scope other than the root is open.
+    pub fn has_open_scope(&self) -> bool {
+        self.children.last().is_some_and(|child| child.is_open())
+    }
+
     /// A description of the stack of currently-open scopes.
     pub fn open_stack(&self) -> String {
         let mut stack = Vec::new();
@@ -110,6 +116,43 @@ impl ContextTree {
         }
     }
 
+    /// Exports the gate count of each scope in Brendan Gregg's folded stack format, i.e. one
+    /// `root;a;b;c N` line per scope, where `N` is the number of gates added in scope `c` but
+    /// outside its children. Tools such as `inferno` or `flamegraph.pl` render this as a
+    /// flamegraph. Scopes which are still open are counted up to `current_gate_count`.
+    pub fn export_folded(&self, current_gate_count: usize) -> String {
+        let mut lines = Vec::new();
+        self.export_folded_helper(current_gate_count, "", &mut lines);
+        lines.join("\n")
+    }
+
+    fn export_folded_helper(
+        &self,
+        current_gate_count: usize,
+        prefix: &str,
+        lines: &mut Vec<String>,
+    ) {
+        // Semicolons separate frames, and line breaks separate stacks.
+        let name = self.name.replace(';', ",").replace(['\n', '\r'], " ");
+        let stack = if prefix.is_empty() {
+            name
+        } else {
+            format!("{};{}", prefix, name)
+        };
+        let children_gates: usize = self
+            .children
+            .iter()
+            .map(|c| c.gate_count_delta(current_gate_count))
+            .sum();
+        let own_gates = self.gate_count_delta(current_gate_count) - children_gates;
+        if own_gates > 0 {
+            lines.push(format!("{} {}", stack, own_gates));
+        }
+        for child in &self.children {
+            child.export_folded_helper(current_gate_count, &stack, lines);
+        }
+    }
+
     pub fn print(&self, current_gate_count: usize) {
         self.print_helper(current_gate_count, 0);
     }
@@ -129,7 +172,8 @@ impl ContextTree {
     }
 }
 
-/// Creates a named scope; useful for debugging.
+/// Creates a named scope; useful for debugging. The context is not closed if `$exp` returns early,
+/// so prefer `CircuitBuilder::with_context` or `CircuitBuilder::context`.
 #[macro_export]
 macro_rules! with_context {
     ($builder:expr, $level:expr, $ctx:expr, $exp:expr) => {{
@@ -0,0 +1,187 @@
+//! A cache of FFT root tables that can be shared between circuits.
+
+use std::sync::{Arc, Mutex};
+
+use crate::field::fft::{fft_root_table, FftRootTable};
+use crate::field::types::Field;
+use crate::util::log2_strict;
+
+/// Hands out a single [`FftRootTable`] to every circuit built or proven with it, instead of each
+/// circuit holding its own copy.
+///
+/// Row `i` of a root table holds powers of a primitive `2^(i + 1)`-th root of unity, independently
+/// of the table's size, so the table for `n` points is a prefix of the table for any larger power
+/// of two. The cache therefore only keeps the largest table requested so far, and a request for a
+/// smaller size returns that same table.
+///
+/// Cloning the cache is cheap, and clones share the same table. Requests from several threads are
+/// serialized, so a table is computed at most once per size increase.
+#[derive(Debug)]
+pub struct FftRootTableCache<F: Field> {
+    table: Arc<Mutex<Option<Arc<FftRootTable<F>>>>>,
+}
+
+impl<F: Field> FftRootTableCache<F> {
+    pub fn new() -> Self {
+        Self {
+            table: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns a root table usable for FFTs of up to `n` points, computing it if no table that
+    /// large has been requested before.
+    pub fn get(&self, n: usize) -> Arc<FftRootTable<F>> {
+        let lg_n = log2_strict(n);
+        let mut table = self.table.lock().unwrap_or_else(|e| e.into_inner());
+        match table.as_ref() {
+            Some(t) if t.len() >= lg_n => t.clone(),
+            _ => {
+                let new_table = Arc::new(fft_root_table(n));
+                *table = Some(new_table.clone());
+                new_table
+            }
+        }
+    }
+
+    /// The number of points covered by the cached table, if any.
+    pub fn max_fft_points(&self) -> Option<usize> {
+        let table = self.table.lock().unwrap_or_else(|e| e.into_inner());
+        table.as_ref().map(|t| 1 << t.len())
+    }
+}
+
+impl<F: Field> Default for FftRootTableCache<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Field> Clone for FftRootTableCache<F> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::size_of;
+
+    use anyhow::Result;
+
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::gates::noop::NoopGate;
+    use crate::iop::witness::{PartialWitness, WitnessWrite};
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    fn table_bytes(table: &FftRootTable<F>) -> usize {
+        table.iter().map(|row| row.len() * size_of::<F>()).sum()
+    }
+
+    /// Builds a circuit computing the public `x^2` from the public `x`, padded with
+    /// `2^log_num_rows` no-op rows.
+    fn build_circuit(
+        log_num_rows: usize,
+        cache: Option<&FftRootTableCache<F>>,
+    ) -> CircuitData<F, C, D> {
+        let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        if let Some(cache) = cache {
+            builder.set_fft_root_table_cache(cache.clone());
+        }
+        let x = builder.add_virtual_public_input();
+        let x_squared = builder.square(x);
+        builder.register_public_input(x_squared);
+        for _ in 0..1 << log_num_rows {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        builder.build::<C>()
+    }
+
+    #[test]
+    fn circuits_share_one_table() {
+        let cache = FftRootTableCache::new();
+        // The largest circuit is built first, and the smaller ones reuse a prefix of its table.
+        let circuits = [10, 8, 6]
+            .map(|log_num_rows| build_circuit(log_num_rows, Some(&cache)))
+            .map(|data| data.prover_only.fft_root_table.unwrap());
+        let largest = circuits[0].clone();
+        assert_eq!(cache.max_fft_points(), Some(1 << largest.len()));
+        for table in &circuits {
+            assert!(Arc::ptr_eq(table, &largest));
+        }
+        // One reference per circuit, one held by the cache and the `largest` clone.
+        assert_eq!(Arc::strong_count(&largest), circuits.len() + 2);
+
+        // Without the cache, each circuit holds its own table, the largest of which is as big as
+        // the shared one.
+        let unshared_bytes = [10, 8, 6]
+            .map(|log_num_rows| build_circuit(log_num_rows, None))
+            .map(|data| table_bytes(&data.prover_only.fft_root_table.unwrap()));
+        assert_eq!(table_bytes(&largest), unshared_bytes[0]);
+        assert!(table_bytes(&largest) < unshared_bytes.iter().sum());
+    }
+
+    #[test]
+    fn concurrent_requests_compute_the_table_once() {
+        let cache = FftRootTableCache::<F>::new();
+        let tables = std::thread::scope(|s| {
+            let handles = (0..8)
+                .map(|_| s.spawn(|| cache.get(1 << 16)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        for table in &tables {
+            assert!(Arc::ptr_eq(table, &tables[0]));
+        }
+        assert_eq!(**tables[0], fft_root_table::<F>(1 << 16));
+    }
+
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn shared_table_leaves_proofs_unchanged() -> Result<()> {
+        use crate::plonk::prover::ProverOptions;
+
+        let cache = FftRootTableCache::new();
+        let large_table = cache.get(1 << 16);
+        let shared = build_circuit(8, Some(&cache));
+        let mut unshared = build_circuit(8, None);
+        assert!(Arc::ptr_eq(
+            shared.prover_only.fft_root_table.as_ref().unwrap(),
+            &large_table
+        ));
+        assert_eq!(shared.verifier_only, unshared.verifier_only);
+
+        let options = ProverOptions {
+            rng_seed: Some(7),
+            ..Default::default()
+        };
+        let mut pw = PartialWitness::new();
+        pw.set_target(shared.prover_only.public_inputs[0], F::rand());
+        let proof = shared.prove_with_options(pw.clone(), &options)?;
+        let unshared_proof = unshared.prove_with_options(pw.clone(), &options)?;
+        assert_eq!(proof.to_bytes(), unshared_proof.to_bytes());
+
+        // A table supplied after the circuit was built gives the same proof too.
+        unshared.share_fft_root_table(&cache);
+        assert!(Arc::ptr_eq(
+            unshared.prover_only.fft_root_table.as_ref().unwrap(),
+            &large_table
+        ));
+        let unshared_proof = unshared.prove_with_options(pw, &options)?;
+        assert_eq!(proof.to_bytes(), unshared_proof.to_bytes());
+
+        shared.verify(proof)?;
+        unshared.verify(unshared_proof)
+    }
+}
@@ -11,10 +11,13 @@ use crate::field::polynomial::PolynomialValues;
 use crate::field::types::Field;
 
 pub(crate) mod context_tree;
+#[cfg(feature = "std")]
+pub mod fft_root_table_cache;
 pub(crate) mod partial_products;
 pub mod reducing;
 pub mod serialization;
 pub mod strided_view;
+pub mod testing;
 pub mod timing;
 
 pub(crate) fn transpose_poly_values<F: Field>(polys: Vec<PolynomialValues<F>>) -> Vec<Vec<F>> {
@@ -4,11 +4,8 @@ use core::iter;
 
 use itertools::Itertools;
 
-use crate::field::extension::Extendable;
 use crate::field::types::Field;
-use crate::hash::hash_types::RichField;
-use crate::iop::ext_target::ExtensionTarget;
-use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::vanishing_poly_backend::VanishingPolyBackend;
 use crate::util::ceil_div_usize;
 
 pub(crate) fn quotient_chunk_products<F: Field>(
@@ -50,61 +47,32 @@ pub(crate) fn num_partial_products(n: usize, max_degree: usize) -> usize {
 /// Checks the relationship between each pair of partial product accumulators. In particular, this
 /// sequence of accumulators starts with `Z(x)`, then contains each partial product polynomials
 /// `p_i(x)`, and finally `Z(g x)`. See the partial products section of the Plonky2 paper.
-pub(crate) fn check_partial_products<F: Field>(
-    numerators: &[F],
-    denominators: &[F],
-    partials: &[F],
-    z_x: F,
-    z_gx: F,
+pub(crate) fn check_partial_products<F: Field, B: VanishingPolyBackend<F>>(
+    backend: &mut B,
+    numerators: &[B::Value],
+    denominators: &[B::Value],
+    partials: &[B::Value],
+    z_x: B::Value,
+    z_gx: B::Value,
     max_degree: usize,
-) -> Vec<F> {
-    debug_assert!(max_degree > 1);
-    let product_accs = iter::once(&z_x)
-        .chain(partials.iter())
-        .chain(iter::once(&z_gx));
-    let chunk_size = max_degree;
-    numerators
-        .chunks(chunk_size)
-        .zip_eq(denominators.chunks(chunk_size))
-        .zip_eq(product_accs.tuple_windows())
-        .map(|((nume_chunk, deno_chunk), (&prev_acc, &next_acc))| {
-            let num_chunk_product = nume_chunk.iter().copied().product();
-            let den_chunk_product = deno_chunk.iter().copied().product();
-            // Assert that next_acc * deno_product = prev_acc * nume_product.
-            prev_acc * num_chunk_product - next_acc * den_chunk_product
-        })
-        .collect()
-}
-
-/// Checks the relationship between each pair of partial product accumulators. In particular, this
-/// sequence of accumulators starts with `Z(x)`, then contains each partial product polynomials
-/// `p_i(x)`, and finally `Z(g x)`. See the partial products section of the Plonky2 paper.
-pub(crate) fn check_partial_products_circuit<F: RichField + Extendable<D>, const D: usize>(
-    builder: &mut CircuitBuilder<F, D>,
-    numerators: &[ExtensionTarget<D>],
-    denominators: &[ExtensionTarget<D>],
-    partials: &[ExtensionTarget<D>],
-    z_x: ExtensionTarget<D>,
-    z_gx: ExtensionTarget<D>,
-    max_degree: usize,
-) -> Vec<ExtensionTarget<D>> {
+    checks: &mut Vec<B::Value>,
+) {
     debug_assert!(max_degree > 1);
     let product_accs = iter::once(&z_x)
         .chain(partials.iter())
         .chain(iter::once(&z_gx));
     let chunk_size = max_degree;
-    numerators
+    for ((nume_chunk, deno_chunk), (&prev_acc, &next_acc)) in numerators
         .chunks(chunk_size)
         .zip_eq(denominators.chunks(chunk_size))
         .zip_eq(product_accs.tuple_windows())
-        .map(|((nume_chunk, deno_chunk), (&prev_acc, &next_acc))| {
-            let nume_product = builder.mul_many_extension(nume_chunk);
-            let deno_product = builder.mul_many_extension(deno_chunk);
-            let next_acc_deno = builder.mul_extension(next_acc, deno_product);
-            // Assert that next_acc * deno_product = prev_acc * nume_product.
-            builder.mul_sub_extension(prev_acc, nume_product, next_acc_deno)
-        })
-        .collect()
+    {
+        let nume_product = backend.mul_many(nume_chunk);
+        let deno_product = backend.mul_many(deno_chunk);
+        let next_acc_deno = backend.mul(next_acc, deno_product);
+        // Assert that next_acc * deno_product = prev_acc * nume_product.
+        checks.push(backend.mul_sub(prev_acc, nume_product, next_acc_deno));
+    }
 }
 
 #[cfg(test)]
@@ -114,6 +82,7 @@ mod tests {
 
     use super::*;
     use crate::field::goldilocks_field::GoldilocksField;
+    use crate::plonk::vanishing_poly_backend::BaseFieldBackend;
 
     #[test]
     fn test_partial_products() {
@@ -130,7 +99,7 @@ mod tests {
 
         let nums = num_partial_products(v.len(), 2);
         assert_eq!(pps.len(), nums);
-        assert!(check_partial_products(&v, &denominators, pps, z_x, z_gx, 2)
+        assert!(check(&v, &denominators, pps, z_x, z_gx, 2)
             .iter()
             .all(|x| x.is_zero()));
 
@@ -141,11 +110,33 @@ mod tests {
         assert_eq!(pps_and_z_gx, field_vec(&[6, 720]));
         let nums = num_partial_products(v.len(), 3);
         assert_eq!(pps.len(), nums);
-        assert!(check_partial_products(&v, &denominators, pps, z_x, z_gx, 3)
+        assert!(check(&v, &denominators, pps, z_x, z_gx, 3)
             .iter()
             .all(|x| x.is_zero()));
     }
 
+    fn check<F: Field>(
+        numerators: &[F],
+        denominators: &[F],
+        partials: &[F],
+        z_x: F,
+        z_gx: F,
+        max_degree: usize,
+    ) -> Vec<F> {
+        let mut checks = Vec::new();
+        check_partial_products(
+            &mut BaseFieldBackend,
+            numerators,
+            denominators,
+            partials,
+            z_x,
+            z_gx,
+            max_degree,
+            &mut checks,
+        );
+        checks
+    }
+
     fn field_vec<F: Field>(xs: &[usize]) -> Vec<F> {
         xs.iter().map(|&x| F::from_canonical_usize(x)).collect()
     }
@@ -127,6 +127,22 @@ impl<const D: usize> ReducingFactorTarget<D> {
         terms: &[Target],
         builder: &mut CircuitBuilder<F, D>,
     ) -> ExtensionTarget<D>
+    where
+        F: RichField + Extendable<D>,
+    {
+        let zero_ext = builder.zero_extension();
+        self.reduce_base_from(zero_ext, terms, builder)
+    }
+
+    /// Like `reduce_base`, but with the accumulator starting at `initial` rather than zero, which
+    /// adds `initial` times the power of the base given by `reduce_base_initial_power` to the
+    /// result. This lets callers fold a constant term into the reduction for free.
+    pub fn reduce_base_from<F>(
+        &mut self,
+        initial: ExtensionTarget<D>,
+        terms: &[Target],
+        builder: &mut CircuitBuilder<F, D>,
+    ) -> ExtensionTarget<D>
     where
         F: RichField + Extendable<D>,
     {
@@ -138,7 +154,7 @@ impl<const D: usize> ReducingFactorTarget<D> {
                 .iter()
                 .map(|&t| builder.convert_to_ext(t))
                 .collect::<Vec<_>>();
-            return self.reduce_arithmetic(&terms_ext, builder);
+            return self.reduce_arithmetic(initial, &terms_ext, builder);
         }
 
         let max_coeffs_len = ReducingGate::<D>::max_coeffs_len(
@@ -147,8 +163,7 @@ impl<const D: usize> ReducingFactorTarget<D> {
         );
         self.count += l as u64;
         let zero = builder.zero();
-        let zero_ext = builder.zero_extension();
-        let mut acc = zero_ext;
+        let mut acc = initial;
         let mut reversed_terms = terms.to_vec();
         while reversed_terms.len() % max_coeffs_len != 0 {
             reversed_terms.push(zero);
@@ -176,6 +191,23 @@ impl<const D: usize> ReducingFactorTarget<D> {
         acc
     }
 
+    /// The power of the base by which `reduce_base_from` multiplies its initial accumulator when
+    /// reducing `num_terms` terms. Reducing gates process a whole number of chunks, so it can
+    /// exceed `num_terms`.
+    pub fn reduce_base_initial_power<F>(num_terms: usize, builder: &CircuitBuilder<F, D>) -> u64
+    where
+        F: RichField + Extendable<D>,
+    {
+        if num_terms <= ArithmeticExtensionGate::<D>::new_from_config(&builder.config).num_ops + 1 {
+            return num_terms as u64;
+        }
+        let max_coeffs_len = ReducingGate::<D>::max_coeffs_len(
+            builder.config.num_wires,
+            builder.config.num_routed_wires,
+        );
+        num_terms.next_multiple_of(max_coeffs_len) as u64
+    }
+
     /// Reduces a vector of `ExtensionTarget`s using `ReducingExtensionGate`s.
     pub fn reduce<F>(
         &mut self,
@@ -189,7 +221,8 @@ impl<const D: usize> ReducingFactorTarget<D> {
 
         // For small reductions, use an arithmetic gate.
         if l <= ArithmeticExtensionGate::<D>::new_from_config(&builder.config).num_ops + 1 {
-            return self.reduce_arithmetic(terms, builder);
+            let zero_ext = builder.zero_extension();
+            return self.reduce_arithmetic(zero_ext, terms, builder);
         }
 
         let max_coeffs_len = ReducingExtensionGate::<D>::max_coeffs_len(
@@ -229,9 +262,10 @@ impl<const D: usize> ReducingFactorTarget<D> {
         acc
     }
 
-    /// Reduces a vector of `ExtensionTarget`s using `ArithmeticGate`s.
+    /// Reduces a vector of `ExtensionTarget`s using `ArithmeticGate`s, starting from `initial`.
     fn reduce_arithmetic<F>(
         &mut self,
+        initial: ExtensionTarget<D>,
         terms: &[ExtensionTarget<D>],
         builder: &mut CircuitBuilder<F, D>,
     ) -> ExtensionTarget<D>
@@ -239,12 +273,9 @@ impl<const D: usize> ReducingFactorTarget<D> {
         F: RichField + Extendable<D>,
     {
         self.count += terms.len() as u64;
-        terms
-            .iter()
-            .rev()
-            .fold(builder.zero_extension(), |acc, &et| {
-                builder.mul_add_extension(self.base, acc, et)
-            })
+        terms.iter().rev().fold(initial, |acc, &et| {
+            builder.mul_add_extension(self.base, acc, et)
+        })
     }
 
     pub fn shift<F>(
@@ -255,6 +286,14 @@ impl<const D: usize> ReducingFactorTarget<D> {
     where
         F: RichField + Extendable<D>,
     {
+        // With a constant base, the power is a constant which the multiplication can take from
+        // gate constants.
+        if let Some(base) = builder.target_as_constant_ext(self.base) {
+            let exp = base.exp_u64(self.count);
+            self.count = 0;
+            return builder.mul_const_extension(exp, x);
+        }
+
         let zero_ext = builder.zero_extension();
         let exp = if x == zero_ext {
             // The result will get zeroed out, so don't actually compute the exponentiation.
@@ -345,6 +384,57 @@ mod tests {
         verify(proof, &data.verifier_only, &data.common)
     }
 
+    /// Reduces 50 terms with a constant alpha and shifts by the resulting power, once through
+    /// `ReducingFactorTarget`, whose shift takes the power from gate constants, and once routing
+    /// the power to the wires of a `mul_extension`. Checks both results and compares their rows.
+    #[test]
+    fn test_shift_constant_alpha() -> Result<()> {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+        type FF = <C as GenericConfig<D>>::FE;
+        let n = 50;
+
+        let alpha = FF::rand();
+        let vs = FF::rand_vec(n);
+        let x = FF::rand();
+        let mut alpha_native = ReducingFactor::new(alpha);
+        let expected = [alpha_native.reduce(vs.iter()), alpha_native.shift(x)];
+
+        let mut rows = vec![];
+        for constant_shift in [true, false] {
+            let config = CircuitConfig::standard_recursion_config();
+            let mut pw = PartialWitness::new();
+            let mut builder = CircuitBuilder::<F, D>::new(config);
+
+            let mut alpha_t = ReducingFactorTarget::new(builder.constant_extension(alpha));
+            let vs_t = builder.add_virtual_extension_targets(n);
+            pw.set_extension_targets(&vs_t, &vs);
+            let x_t = builder.add_virtual_extension_target();
+            pw.set_extension_target(x_t, x);
+
+            let reduced = alpha_t.reduce(&vs_t, &mut builder);
+            let shifted = if constant_shift {
+                alpha_t.shift(x_t, &mut builder)
+            } else {
+                let power = builder.constant_extension(alpha.exp_u64(n as u64));
+                builder.mul_extension(power, x_t)
+            };
+            for (t, value) in [reduced, shifted].into_iter().zip(expected) {
+                let value = builder.constant_extension(value);
+                builder.connect_extension(t, value);
+            }
+            rows.push(builder.cost_report::<C>().num_rows);
+
+            let data = builder.build::<C>();
+            let proof = data.prove(pw)?;
+            verify(proof, &data.verifier_only, &data.common)?;
+        }
+
+        assert!(rows[0] < rows[1], "{} rows, against {}", rows[0], rows[1]);
+        Ok(())
+    }
+
     #[test]
     fn test_reduce_gadget_even() -> Result<()> {
         test_reduce_gadget(10)
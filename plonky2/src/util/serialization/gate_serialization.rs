@@ -99,6 +99,7 @@ pub mod default {
     use crate::gates::arithmetic_extension::ArithmeticExtensionGate;
     use crate::gates::base_sum::BaseSumGate;
     use crate::gates::constant::ConstantGate;
+    use crate::gates::constant_table::ConstantTableGate;
     use crate::gates::coset_interpolation::CosetInterpolationGate;
     use crate::gates::exponentiation::ExponentiationGate;
     use crate::gates::lookup::LookupGate;
@@ -109,8 +110,11 @@ pub mod default {
     use crate::gates::poseidon_mds::PoseidonMdsGate;
     use crate::gates::public_input::PublicInputGate;
     use crate::gates::random_access::RandomAccessGate;
+    use crate::gates::range_check::RangeCheckGate;
     use crate::gates::reducing::ReducingGate;
     use crate::gates::reducing_extension::ReducingExtensionGate;
+    use crate::gates::scalar_arithmetic_extension::ScalarArithmeticExtensionGate;
+    use crate::gates::switch::SwitchGate;
     use crate::hash::hash_types::RichField;
     use crate::util::serialization::GateSerializer;
     /// A gate serializer that can be used to serialize all default gates supported
@@ -131,6 +135,7 @@ pub mod default {
             ArithmeticExtensionGate<D>,
             BaseSumGate<2>,
             ConstantGate,
+            ConstantTableGate,
             CosetInterpolationGate<F, D>,
             ExponentiationGate<F, D>,
             LookupGate,
@@ -141,8 +146,11 @@ pub mod default {
             PoseidonGate<F, D>,
             PublicInputGate,
             RandomAccessGate<F, D>,
+            RangeCheckGate,
             ReducingExtensionGate<D>,
-            ReducingGate<D>
+            ReducingGate<D>,
+            ScalarArithmeticExtensionGate<D>,
+            SwitchGate<F, D>
         }
     }
 }
@@ -42,6 +42,11 @@ macro_rules! read_generator_impl {
         ))
         } else)*
         {
+            log::log!(
+                log::Level::Error,
+                "attempted to deserialize generator with tag {} which is unsupported by this generator serializer",
+                tag
+            );
             Err($crate::util::serialization::IoError)
         }
     }};
@@ -101,7 +106,7 @@ pub mod default {
 
     use plonky2_field::extension::Extendable;
 
-    use crate::gadgets::arithmetic::EqualityGenerator;
+    use crate::gadgets::arithmetic::{EqualityGenerator, NonzeroGenerator};
     use crate::gadgets::arithmetic_extension::QuotientGeneratorExtension;
     use crate::gadgets::range_check::LowHighGenerator;
     use crate::gadgets::split_base::BaseSumGenerator;
@@ -117,8 +122,11 @@ pub mod default {
     use crate::gates::poseidon::PoseidonGenerator;
     use crate::gates::poseidon_mds::PoseidonMdsGenerator;
     use crate::gates::random_access::RandomAccessGenerator;
+    use crate::gates::range_check::RangeCheckGenerator;
     use crate::gates::reducing::ReducingGenerator;
     use crate::gates::reducing_extension::ReducingGenerator as ReducingExtensionGenerator;
+    use crate::gates::scalar_arithmetic_extension::ScalarArithmeticExtensionGenerator;
+    use crate::gates::switch::SwitchGenerator;
     use crate::hash::hash_types::RichField;
     use crate::iop::generator::{
         ConstantGenerator, CopyGenerator, NonzeroTestGenerator, RandomValueGenerator,
@@ -167,16 +175,125 @@ pub mod default {
             LookupTableGenerator,
             LowHighGenerator,
             MulExtensionGenerator<F, D>,
+            NonzeroGenerator,
             NonzeroTestGenerator,
             PoseidonGenerator<F, D>,
             PoseidonMdsGenerator<D>,
             QuotientGeneratorExtension<D>,
             RandomAccessGenerator<F, D>,
             RandomValueGenerator,
+            RangeCheckGenerator,
             ReducingGenerator<D>,
             ReducingExtensionGenerator<D>,
+            ScalarArithmeticExtensionGenerator<F, D>,
             SplitGenerator,
+            SwitchGenerator<F, D>,
             WireSplitGenerator
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::plonk::circuit_builder::CircuitBuilder;
+    use crate::plonk::circuit_data::CircuitConfig;
+    use crate::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use crate::util::serialization::{Buffer, DefaultGateSerializer, WitnessGeneratorSerializer};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// A serializer which only knows about `CopyGenerator`s.
+    struct CopyOnlyGeneratorSerializer;
+
+    impl WitnessGeneratorSerializer<F, D> for CopyOnlyGeneratorSerializer {
+        impl_generator_serializer! {
+            CopyOnlyGeneratorSerializer,
+            crate::iop::generator::CopyGenerator
+        }
+    }
+
+    #[cfg(feature = "rand_chacha")]
+    #[test]
+    fn prover_data_round_trip() -> anyhow::Result<()> {
+        #[cfg(not(feature = "std"))]
+        use alloc::vec;
+
+        use crate::field::types::Field;
+        use crate::hash::poseidon::PoseidonHash;
+        use crate::iop::witness::{PartialWitness, WitnessWrite};
+        use crate::plonk::circuit_data::ProverCircuitData;
+        use crate::plonk::prover::ProverOptions;
+        use crate::util::serialization::DefaultGeneratorSerializer;
+
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.add_virtual_target();
+        let quotient = builder.div(x, y);
+        let power = builder.exp(x, y, 8);
+        let bits = builder.split_le(y, 8);
+        let index = builder.le_sum(bits[..2].iter());
+        let entry = builder.random_access(index, vec![x, y, quotient, power]);
+        let hash = builder.hash_n_to_hash_no_pad::<PoseidonHash>(vec![x, entry, power]);
+        builder.register_public_inputs(&hash.elements);
+        let x_ext = builder.convert_to_ext(x);
+        let y_ext = builder.convert_to_ext(y);
+        let quotient_ext = builder.div_extension(x_ext, y_ext);
+        builder.register_public_inputs(&quotient_ext.0);
+
+        let data = builder.build::<C>();
+        let verifier_data = data.verifier_data();
+        let prover_data = data.prover_data();
+
+        let gate_serializer = DefaultGateSerializer;
+        let generator_serializer = DefaultGeneratorSerializer::<C, D>::default();
+        let bytes = prover_data
+            .to_bytes(&gate_serializer, &generator_serializer)
+            .unwrap();
+        let prover_data_from_bytes = ProverCircuitData::<F, C, D>::from_bytes(
+            &bytes,
+            &gate_serializer,
+            &generator_serializer,
+        )
+        .unwrap();
+        assert_eq!(prover_data.prover_only, prover_data_from_bytes.prover_only);
+        assert_eq!(prover_data.common, prover_data_from_bytes.common);
+
+        let mut pw = PartialWitness::new();
+        pw.set_target(x, F::from_canonical_u64(7));
+        pw.set_target(y, F::from_canonical_u64(6));
+        // With a fixed seed, the deserialized generators must reproduce the proof exactly.
+        let options = ProverOptions {
+            rng_seed: Some(0),
+            ..Default::default()
+        };
+        let proof = prover_data.prove_with_options(pw.clone(), &options)?;
+        let proof_from_bytes = prover_data_from_bytes.prove_with_options(pw, &options)?;
+        assert_eq!(proof.to_bytes(), proof_from_bytes.to_bytes());
+        verifier_data.verify(proof)?;
+        verifier_data.verify(proof_from_bytes)
+    }
+
+    #[test]
+    fn unknown_generator_errors() {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let y = builder.mul(x, x);
+        builder.register_public_input(y);
+        let data = builder.build::<C>();
+
+        let gate_serializer = DefaultGateSerializer;
+        assert!(data
+            .to_bytes(&gate_serializer, &CopyOnlyGeneratorSerializer)
+            .is_err());
+
+        // A tag outside of the serializer's list must be rejected when reading, too.
+        let bytes = 1u32.to_le_bytes();
+        assert!(CopyOnlyGeneratorSerializer
+            .read_generator(&mut Buffer::new(&bytes), &data.common)
+            .is_err());
+    }
+}
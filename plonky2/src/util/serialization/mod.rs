@@ -5,7 +5,7 @@ pub mod generator_serialization;
 pub mod gate_serialization;
 
 #[cfg(not(feature = "std"))]
-use alloc::{collections::BTreeMap, sync::Arc, vec, vec::Vec};
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
 use core::convert::Infallible;
 use core::fmt::{Debug, Display, Formatter};
 use core::mem::size_of;
@@ -37,20 +37,24 @@ use crate::hash::hash_types::{HashOutTarget, MerkleCapTarget, RichField};
 use crate::hash::merkle_proofs::{MerkleProof, MerkleProofTarget};
 use crate::hash::merkle_tree::{MerkleCap, MerkleTree};
 use crate::iop::ext_target::ExtensionTarget;
-use crate::iop::generator::WitnessGeneratorRef;
+use crate::iop::generator::{CopiesGenerator, CopyGenerator, SimpleGenerator, WitnessGeneratorRef};
 use crate::iop::target::{BoolTarget, Target};
 use crate::iop::wire::Wire;
-use crate::plonk::circuit_builder::LookupWire;
+use crate::iop::witness::WitnessSchema;
+use crate::plonk::circuit_builder::{circuit_digest, DuplicatePublicInputs, LookupWire};
 use crate::plonk::circuit_data::{
-    CircuitConfig, CircuitData, CommonCircuitData, ProverCircuitData, ProverOnlyCircuitData,
-    VerifierCircuitData, VerifierCircuitTarget, VerifierOnlyCircuitData,
+    CircuitConfig, CircuitData, CommonCircuitData, OpeningPoint, OracleCapHeights,
+    ProverCircuitData, ProverOnlyCircuitData, PublicInputCommitment, VerifierCircuitData,
+    VerifierCircuitTarget, VerifierOnlyCircuitData,
 };
 use crate::plonk::config::{GenericConfig, GenericHashOut, Hasher};
-use crate::plonk::plonk_common::salt_size;
+use crate::plonk::plonk_common::PlonkOracle;
 use crate::plonk::proof::{
     CompressedProof, CompressedProofWithPublicInputs, OpeningSet, OpeningSetTarget, Proof,
     ProofTarget, ProofWithPublicInputs, ProofWithPublicInputsTarget,
 };
+use crate::plonk::row_attribution::RowAttribution;
+use crate::util::transpose_poly_values;
 
 /// A no_std compatible variant of `std::io::Error`
 #[derive(Debug)]
@@ -65,6 +69,62 @@ impl Display for IoError {
 /// A no_std compatible variant of `std::io::Result`
 pub type IoResult<T> = Result<T, IoError>;
 
+/// The version of the format of serialized [`CircuitData`], [`ProverCircuitData`] and
+/// [`VerifierCircuitData`], written in their header. It is bumped whenever the format changes, so
+/// that data serialized by another version is rejected rather than misread.
+pub const CIRCUIT_DATA_FORMAT_VERSION: u32 = 12;
+
+/// The bytes starting the header of serialized circuit data.
+const CIRCUIT_DATA_MAGIC: [u8; 4] = *b"pk2c";
+
+/// The circuit data a serialized header is for, so that e.g. verifier data can't be loaded as
+/// prover data.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CircuitDataKind {
+    Full,
+    Prover,
+    Verifier,
+}
+
+/// How serialized prover data stores its sigma polynomials, subgroup and FFT root table, which are
+/// large but can be recomputed.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ProverDataEncoding {
+    /// Store them as they are.
+    #[default]
+    Full,
+    /// Leave them out, and recompute them when loading the data from the constants and sigmas
+    /// commitment and the common data. This makes the data smaller, at the cost of some FFTs when
+    /// loading it.
+    Compact,
+}
+
+/// The largest number of elements preallocated for a vector whose length was read from the input.
+/// Lengths can't be trusted, so longer vectors grow as their elements are actually read.
+const MAX_PREALLOCATED_LEN: usize = 1 << 16;
+
+fn vec_with_untrusted_capacity<T>(len: usize) -> Vec<T> {
+    Vec::with_capacity(len.min(MAX_PREALLOCATED_LEN))
+}
+
+/// Checks that the digest stored with serialized circuit data is the one of its contents, rather
+/// than only another stored copy.
+fn check_circuit_digest<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    digest: <C::Hasher as Hasher<F>>::Hash,
+    constants_sigmas_cap: &MerkleCap<F, C::Hasher>,
+    domain_separator: &[F],
+    common: &CommonCircuitData<F, D>,
+) -> IoResult<()> {
+    if circuit_digest::<F, C, D>(constants_sigmas_cap, domain_separator, common) != digest {
+        return Err(IoError);
+    }
+    Ok(())
+}
+
 /// A `Read` which is able to report how many bytes are remaining.
 pub trait Remaining: Read {
     /// Returns the number of bytes remaining in the buffer.
@@ -143,7 +203,7 @@ pub trait Read {
     #[inline]
     fn read_usize_vec(&mut self) -> IoResult<Vec<usize>> {
         let len = self.read_usize()?;
-        let mut res = Vec::with_capacity(len);
+        let mut res = vec_with_untrusted_capacity(len);
         for _ in 0..len {
             res.push(self.read_usize()?);
         }
@@ -151,7 +211,18 @@ pub trait Read {
         Ok(res)
     }
 
-    /// Reads a element from the field `F` with size less than `2^64` from `self.`
+    /// Reads a UTF-8 encoded `String` from `self`.
+    #[inline]
+    fn read_string(&mut self) -> IoResult<String> {
+        let len = self.read_usize()?;
+        let bytes = (0..len)
+            .map(|_| self.read_u8())
+            .collect::<Result<Vec<_>, _>>()?;
+        String::from_utf8(bytes).map_err(|_| IoError)
+    }
+
+    /// Reads a element from the field `F` with size less than `2^64` from `self.` Fails if the
+    /// encoded value isn't canonical.
     #[inline]
     fn read_field<F>(&mut self) -> IoResult<F>
     where
@@ -159,7 +230,11 @@ pub trait Read {
     {
         let mut buf = [0; size_of::<u64>()];
         self.read_exact(&mut buf)?;
-        Ok(F::from_canonical_u64(u64::from_le_bytes(buf)))
+        let n = u64::from_le_bytes(buf);
+        if n >= F::ORDER {
+            return Err(IoError);
+        }
+        Ok(F::from_canonical_u64(n))
     }
 
     /// Reads a vector of elements from the field `F` from `self`.
@@ -252,7 +327,7 @@ pub trait Read {
             .collect::<Result<Vec<_>, _>>()
     }
 
-    /// Reads a hash value from `self`.
+    /// Reads a hash value from `self`. Fails if the encoding isn't canonical.
     #[inline]
     fn read_hash<F, H>(&mut self) -> IoResult<H::Hash>
     where
@@ -261,7 +336,11 @@ pub trait Read {
     {
         let mut buf = vec![0; H::HASH_SIZE];
         self.read_exact(&mut buf)?;
-        Ok(H::Hash::from_bytes(&buf))
+        let hash = H::Hash::from_bytes(&buf);
+        if hash.to_bytes() != buf {
+            return Err(IoError);
+        }
+        Ok(hash)
     }
 
     /// Reads a HashOutTarget value from `self`.
@@ -294,7 +373,10 @@ pub trait Read {
         F: RichField,
         H: Hasher<F>,
     {
-        let cap_length = 1 << cap_height;
+        let cap_length = u32::try_from(cap_height)
+            .ok()
+            .and_then(|cap_height| 1usize.checked_shl(cap_height))
+            .ok_or(IoError)?;
         Ok(MerkleCap(
             (0..cap_length)
                 .map(|_| self.read_hash::<F, H>())
@@ -321,7 +403,7 @@ pub trait Read {
         H: Hasher<F>,
     {
         let leaves_len = self.read_usize()?;
-        let mut leaves = Vec::with_capacity(leaves_len);
+        let mut leaves = vec_with_untrusted_capacity(leaves_len);
         for _ in 0..leaves_len {
             let leaf_len = self.read_usize()?;
             leaves.push(self.read_field_vec(leaf_len)?);
@@ -352,15 +434,19 @@ pub trait Read {
         let constants = self.read_field_ext_vec::<F, D>(common_data.num_constants)?;
         let plonk_sigmas = self.read_field_ext_vec::<F, D>(config.num_routed_wires)?;
         let wires = self.read_field_ext_vec::<F, D>(config.num_wires)?;
-        let plonk_zs = self.read_field_ext_vec::<F, D>(config.num_challenges)?;
-        let plonk_zs_next = self.read_field_ext_vec::<F, D>(config.num_challenges)?;
+        let plonk_zs = self.read_field_ext_vec::<F, D>(common_data.num_challenges())?;
+        let plonk_zs_next = self.read_field_ext_vec::<F, D>(common_data.num_challenges())?;
         let lookup_zs = self.read_field_ext_vec::<F, D>(common_data.num_all_lookup_polys())?;
         let lookup_zs_next = self.read_field_ext_vec::<F, D>(common_data.num_all_lookup_polys())?;
-        let partial_products = self
-            .read_field_ext_vec::<F, D>(common_data.num_partial_products * config.num_challenges)?;
-        let quotient_polys = self.read_field_ext_vec::<F, D>(
-            common_data.quotient_degree_factor * config.num_challenges,
+        let partial_products = self.read_field_ext_vec::<F, D>(
+            common_data.num_partial_products * common_data.num_challenges(),
         )?;
+        let quotient_polys = self.read_field_ext_vec::<F, D>(common_data.num_quotient_polys())?;
+        let extra_openings = common_data
+            .num_extra_openings()
+            .into_iter()
+            .map(|num_openings| self.read_field_ext_vec::<F, D>(num_openings))
+            .collect::<Result<Vec<_>, _>>()?;
         Ok(OpeningSet {
             constants,
             plonk_sigmas,
@@ -371,6 +457,7 @@ pub trait Read {
             quotient_polys,
             lookup_zs,
             lookup_zs_next,
+            extra_openings,
         })
     }
 
@@ -386,6 +473,10 @@ pub trait Read {
         let next_lookup_zs = self.read_target_ext_vec::<D>()?;
         let partial_products = self.read_target_ext_vec::<D>()?;
         let quotient_polys = self.read_target_ext_vec::<D>()?;
+        let num_extra_points = self.read_usize()?;
+        let extra_openings = (0..num_extra_points)
+            .map(|_| self.read_target_ext_vec::<D>())
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(OpeningSetTarget {
             constants,
@@ -397,6 +488,7 @@ pub trait Read {
             next_lookup_zs,
             partial_products,
             quotient_polys,
+            extra_openings,
         })
     }
 
@@ -436,31 +528,16 @@ pub trait Read {
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
     {
-        let config = &common_data.config;
-        let salt = salt_size(common_data.fri_params.hiding);
-        let mut evals_proofs = Vec::with_capacity(4);
-
-        let constants_sigmas_v =
-            self.read_field_vec(common_data.num_constants + config.num_routed_wires)?;
-        let constants_sigmas_p = self.read_merkle_proof()?;
-        evals_proofs.push((constants_sigmas_v, constants_sigmas_p));
-
-        let wires_v = self.read_field_vec(config.num_wires + salt)?;
-        let wires_p = self.read_merkle_proof()?;
-        evals_proofs.push((wires_v, wires_p));
-
-        let zs_partial_v = self.read_field_vec(
-            config.num_challenges
-                * (1 + common_data.num_partial_products + common_data.num_lookup_polys)
-                + salt,
-        )?;
-        let zs_partial_p = self.read_merkle_proof()?;
-        evals_proofs.push((zs_partial_v, zs_partial_p));
-
-        let quotient_v =
-            self.read_field_vec(config.num_challenges * common_data.quotient_degree_factor + salt)?;
-        let quotient_p = self.read_merkle_proof()?;
-        evals_proofs.push((quotient_v, quotient_p));
+        let evals_proofs = common_data
+            .fri_oracles()
+            .iter()
+            .zip(PlonkOracle::ALL)
+            .map(|(info, oracle)| {
+                let evals = self.read_field_vec(info.num_polys + common_data.salt_size(oracle))?;
+                let merkle_proof = self.read_merkle_proof()?;
+                Ok((evals, merkle_proof))
+            })
+            .collect::<IoResult<Vec<_>>>()?;
 
         Ok(FriInitialTreeProof { evals_proofs })
     }
@@ -469,7 +546,7 @@ pub trait Read {
     #[inline]
     fn read_target_fri_initial_proof(&mut self) -> IoResult<FriInitialTreeProofTarget> {
         let len = self.read_usize()?;
-        let mut evals_proofs = Vec::with_capacity(len);
+        let mut evals_proofs = vec_with_untrusted_capacity(len);
 
         for _ in 0..len {
             evals_proofs.push((self.read_target_vec()?, self.read_target_merkle_proof()?));
@@ -521,7 +598,7 @@ pub trait Read {
         C: GenericConfig<D, F = F>,
     {
         let config = &common_data.config;
-        let mut fqrs = Vec::with_capacity(config.fri_config.num_query_rounds);
+        let mut fqrs = vec_with_untrusted_capacity(config.fri_config.num_query_rounds);
         for _ in 0..config.fri_config.num_query_rounds {
             let initial_trees_proof = self.read_fri_initial_proof::<F, C, D>(common_data)?;
             let steps = common_data
@@ -544,7 +621,7 @@ pub trait Read {
         &mut self,
     ) -> IoResult<Vec<FriQueryRoundTarget<D>>> {
         let num_query_rounds = self.read_usize()?;
-        let mut fqrs = Vec::with_capacity(num_query_rounds);
+        let mut fqrs = vec_with_untrusted_capacity(num_query_rounds);
         for _ in 0..num_query_rounds {
             let initial_trees_proof = self.read_target_fri_initial_proof()?;
             let num_steps = self.read_usize()?;
@@ -642,6 +719,7 @@ pub trait Read {
         let num_query_rounds = self.read_usize()?;
         let proof_of_work_bits = self.read_u32()?;
         let reduction_strategy = self.read_fri_reduction_strategy()?;
+        let final_poly_degree_bits = self.read_usize()?;
 
         Ok(FriConfig {
             rate_bits,
@@ -649,6 +727,7 @@ pub trait Read {
             num_query_rounds,
             proof_of_work_bits,
             reduction_strategy,
+            final_poly_degree_bits,
         })
     }
 
@@ -662,6 +741,18 @@ pub trait Read {
         let use_base_arithmetic_gate = self.read_bool()?;
         let zero_knowledge = self.read_bool()?;
         let fri_config = self.read_fri_config()?;
+        let mut read_option = || match self.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.read_usize()?)),
+            _ => Err(IoError),
+        };
+        let force_quotient_degree_factor = read_option()?;
+        let oracle_cap_heights = OracleCapHeights {
+            constants_sigmas: read_option()?,
+            wires: read_option()?,
+            zs_partial_products: read_option()?,
+            quotient: read_option()?,
+        };
 
         Ok(CircuitConfig {
             num_wires,
@@ -670,6 +761,8 @@ pub trait Read {
             security_bits,
             num_challenges,
             max_quotient_degree_factor,
+            force_quotient_degree_factor,
+            oracle_cap_heights,
             use_base_arithmetic_gate,
             zero_knowledge,
             fri_config,
@@ -681,12 +774,14 @@ pub trait Read {
         let reduction_arity_bits = self.read_usize_vec()?;
         let degree_bits = self.read_usize()?;
         let hiding = self.read_bool()?;
+        let initial_cap_heights = self.read_usize_vec()?;
 
         Ok(FriParams {
             config,
             reduction_arity_bits,
             degree_bits,
             hiding,
+            initial_cap_heights,
         })
     }
 
@@ -705,7 +800,7 @@ pub trait Read {
     fn read_selectors_info(&mut self) -> IoResult<SelectorsInfo> {
         let selector_indices = self.read_usize_vec()?;
         let groups_len = self.read_usize()?;
-        let mut groups = Vec::with_capacity(groups_len);
+        let mut groups = vec_with_untrusted_capacity(groups_len);
         for _ in 0..groups_len {
             let start = self.read_usize()?;
             let end = self.read_usize()?;
@@ -726,7 +821,7 @@ pub trait Read {
         &mut self,
     ) -> IoResult<PolynomialBatch<F, C, D>> {
         let poly_len = self.read_usize()?;
-        let mut polynomials = Vec::with_capacity(poly_len);
+        let mut polynomials = vec_with_untrusted_capacity(poly_len);
         for _ in 0..poly_len {
             let plen = self.read_usize()?;
             polynomials.push(PolynomialCoeffs::new(self.read_field_vec(plen)?));
@@ -758,6 +853,17 @@ pub trait Read {
         let num_gate_constraints = self.read_usize()?;
         let num_constants = self.read_usize()?;
         let num_public_inputs = self.read_usize()?;
+        // A chunk size of zero stands for a flat commitment.
+        let public_input_commitment = match self.read_usize()? {
+            0 => PublicInputCommitment::Flat,
+            chunk_size => PublicInputCommitment::Chunked { chunk_size },
+        };
+        let duplicate_public_inputs = match self.read_u8()? {
+            0 => DuplicatePublicInputs::Allow,
+            1 => DuplicatePublicInputs::Dedup,
+            2 => DuplicatePublicInputs::Panic,
+            _ => return Err(IoError),
+        };
 
         let k_is_len = self.read_usize()?;
         let k_is = self.read_field_vec(k_is_len)?;
@@ -767,14 +873,32 @@ pub trait Read {
         let num_lookup_polys = self.read_usize()?;
         let num_lookup_selectors = self.read_usize()?;
         let length = self.read_usize()?;
-        let mut luts = Vec::with_capacity(length);
+        let mut luts = vec_with_untrusted_capacity(length);
 
         for _ in 0..length {
             luts.push(Arc::new(self.read_lut()?));
         }
+        let num_blinding_rows = self.read_usize()?;
+        let num_extra_opening_points = self.read_usize()?;
+        let mut extra_opening_points = vec_with_untrusted_capacity(num_extra_opening_points);
+        for _ in 0..num_extra_opening_points {
+            extra_opening_points.push(OpeningPoint {
+                // Shifts are written in two's complement.
+                shift: self.read_usize()? as isize,
+                oracles: self.read_usize_vec()?,
+            });
+        }
+        let length = self.read_usize()?;
+        let mut public_input_names = BTreeMap::new();
+        for _ in 0..length {
+            let name = self.read_string()?;
+            let start = self.read_usize()?;
+            let end = self.read_usize()?;
+            public_input_names.insert(name, start..end);
+        }
 
         let gates_len = self.read_usize()?;
-        let mut gates = Vec::with_capacity(gates_len);
+        let mut gates = vec_with_untrusted_capacity(gates_len);
 
         // We construct the common data without gates first,
         // to pass it as argument when reading the gates.
@@ -787,11 +911,16 @@ pub trait Read {
             num_gate_constraints,
             num_constants,
             num_public_inputs,
+            public_input_commitment,
+            duplicate_public_inputs,
             k_is,
             num_partial_products,
             num_lookup_polys,
             num_lookup_selectors,
             luts,
+            num_blinding_rows,
+            extra_opening_points,
+            public_input_names,
         };
 
         for _ in 0..gates_len {
@@ -804,6 +933,29 @@ pub trait Read {
         Ok(common_data)
     }
 
+    /// Reads the header written by [`Write::write_circuit_data_header`], checking that it's for
+    /// circuit data of the given kind in the current format version, and returns the encoding of
+    /// the prover data and the circuit digest it contains.
+    fn read_circuit_data_header<F: RichField, H: Hasher<F>>(
+        &mut self,
+        kind: CircuitDataKind,
+    ) -> IoResult<(ProverDataEncoding, H::Hash)> {
+        let mut magic = [0; 4];
+        self.read_exact(&mut magic)?;
+        if magic != CIRCUIT_DATA_MAGIC
+            || self.read_u32()? != CIRCUIT_DATA_FORMAT_VERSION
+            || self.read_u8()? != kind as u8
+        {
+            return Err(IoError);
+        }
+        let encoding = match self.read_u8()? {
+            0 => ProverDataEncoding::Full,
+            1 if kind != CircuitDataKind::Verifier => ProverDataEncoding::Compact,
+            _ => return Err(IoError),
+        };
+        Ok((encoding, self.read_hash::<F, H>()?))
+    }
+
     fn read_circuit_data<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -813,9 +965,29 @@ pub trait Read {
         gate_serializer: &dyn GateSerializer<F, D>,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
     ) -> IoResult<CircuitData<F, C, D>> {
+        let (encoding, digest) =
+            self.read_circuit_data_header::<F, C::Hasher>(CircuitDataKind::Full)?;
         let common = self.read_common_circuit_data(gate_serializer)?;
-        let prover_only = self.read_prover_only_circuit_data(generator_serializer, &common)?;
+        let prover_only = self.read_prover_only_circuit_data_with_encoding(
+            generator_serializer,
+            &common,
+            encoding,
+        )?;
         let verifier_only = self.read_verifier_only_circuit_data()?;
+        if prover_only.circuit_digest != digest
+            || verifier_only.circuit_digest != digest
+            || prover_only.constants_sigmas_commitment.merkle_tree.cap
+                != verifier_only.constants_sigmas_cap
+            || prover_only.domain_separator != verifier_only.domain_separator
+        {
+            return Err(IoError);
+        }
+        check_circuit_digest::<F, C, D>(
+            digest,
+            &verifier_only.constants_sigmas_cap,
+            &verifier_only.domain_separator,
+            &common,
+        )?;
         Ok(CircuitData {
             prover_only,
             verifier_only,
@@ -831,12 +1003,46 @@ pub trait Read {
         &mut self,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
         common_data: &CommonCircuitData<F, D>,
+    ) -> IoResult<ProverOnlyCircuitData<F, C, D>> {
+        self.read_prover_only_circuit_data_with_encoding(
+            generator_serializer,
+            common_data,
+            ProverDataEncoding::Full,
+        )
+    }
+
+    fn read_prover_only_circuit_data_with_encoding<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &mut self,
+        generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+        common_data: &CommonCircuitData<F, D>,
+        encoding: ProverDataEncoding,
     ) -> IoResult<ProverOnlyCircuitData<F, C, D>> {
         let gen_len = self.read_usize()?;
-        let mut generators = Vec::with_capacity(gen_len);
+        let mut generators = vec_with_untrusted_capacity(gen_len);
         for _ in 0..gen_len {
             generators.push(self.read_generator(generator_serializer, common_data)?);
         }
+        let copy_gen_len = self.read_usize()?;
+        let mut copy_generators = vec_with_untrusted_capacity(copy_gen_len);
+        for _ in 0..copy_gen_len {
+            let src = self.read_target()?;
+            let dst = self.read_target()?;
+            copy_generators.push(SimpleGenerator::<F, D>::adapter(CopyGenerator { src, dst }));
+        }
+        let batch_copy_gen_len = self.read_usize()?;
+        let mut batch_copy_generators = vec_with_untrusted_capacity(batch_copy_gen_len);
+        for _ in 0..batch_copy_gen_len {
+            let pairs_len = self.read_usize()?;
+            let mut pairs = vec_with_untrusted_capacity(pairs_len);
+            for _ in 0..pairs_len {
+                pairs.push((self.read_target()?, self.read_target()?));
+            }
+            batch_copy_generators.push(CopiesGenerator { pairs });
+        }
         let map_len = self.read_usize()?;
         let mut generator_indices_by_watches = BTreeMap::new();
         for _ in 0..map_len {
@@ -844,39 +1050,62 @@ pub trait Read {
             generator_indices_by_watches.insert(k, self.read_usize_vec()?);
         }
 
-        let constants_sigmas_commitment = self.read_polynomial_batch()?;
+        let constants_sigmas_commitment: PolynomialBatch<F, C, D> = self.read_polynomial_batch()?;
         let sigmas_len = self.read_usize()?;
-        let mut sigmas = Vec::with_capacity(sigmas_len);
+        let mut sigmas = vec_with_untrusted_capacity(sigmas_len);
         for _ in 0..sigmas_len {
             let sigma_len = self.read_usize()?;
             sigmas.push(self.read_field_vec(sigma_len)?);
         }
 
         let subgroup_len = self.read_usize()?;
-        let subgroup = self.read_field_vec(subgroup_len)?;
+        let mut subgroup = self.read_field_vec(subgroup_len)?;
 
         let public_inputs = self.read_target_vec()?;
 
         let representative_map = self.read_usize_vec()?;
 
         let is_some = self.read_bool()?;
-        let fft_root_table = match is_some {
+        let mut fft_root_table = match is_some {
             true => {
                 let table_len = self.read_usize()?;
-                let mut table = Vec::with_capacity(table_len);
+                let mut table = vec_with_untrusted_capacity(table_len);
                 for _ in 0..table_len {
                     let len = self.read_usize()?;
                     table.push(self.read_field_vec(len)?);
                 }
-                Some(table)
+                Some(Arc::new(table))
             }
             false => None,
         };
 
+        // Data in the compact encoding leaves out the fields which can be recomputed.
+        if encoding == ProverDataEncoding::Full {
+            if subgroup.len() != common_data.degree() {
+                return Err(IoError);
+            }
+        } else {
+            if !sigmas.is_empty() || !subgroup.is_empty() || fft_root_table.is_some() {
+                return Err(IoError);
+            }
+            let sigma_polys = constants_sigmas_commitment
+                .polynomials
+                .get(common_data.sigmas_range())
+                .ok_or(IoError)?;
+            sigmas =
+                transpose_poly_values(sigma_polys.iter().map(|poly| poly.clone().fft()).collect());
+            subgroup = F::two_adic_subgroup(common_data.degree_bits());
+            fft_root_table = Some(Arc::new(crate::field::fft::fft_root_table(
+                common_data.max_fft_points(),
+            )));
+        }
+
         let circuit_digest = self.read_hash::<F, <C as GenericConfig<D>>::Hasher>()?;
+        let domain_separator_len = self.read_usize()?;
+        let domain_separator = self.read_field_vec(domain_separator_len)?;
 
         let length = self.read_usize()?;
-        let mut lookup_rows = Vec::with_capacity(length);
+        let mut lookup_rows = vec_with_untrusted_capacity(length);
         for _ in 0..length {
             lookup_rows.push(LookupWire {
                 last_lu_gate: self.read_usize()?,
@@ -886,13 +1115,49 @@ pub trait Read {
         }
 
         let length = self.read_usize()?;
-        let mut lut_to_lookups = Vec::with_capacity(length);
+        let mut lut_to_lookups = vec_with_untrusted_capacity(length);
         for _ in 0..length {
             lut_to_lookups.push(self.read_target_lut()?);
         }
 
+        let length = self.read_usize()?;
+        let mut partition_contexts = BTreeMap::new();
+        for _ in 0..length {
+            let k = self.read_usize()?;
+            partition_contexts.insert(k, self.read_string()?);
+        }
+
+        let length = self.read_usize()?;
+        let mut generator_contexts = BTreeMap::new();
+        for _ in 0..length {
+            let k = self.read_usize()?;
+            generator_contexts.insert(k, self.read_string()?);
+        }
+
+        let length = self.read_usize()?;
+        let mut witness_schema = WitnessSchema::default();
+        for _ in 0..length {
+            let name = self.read_string()?;
+            witness_schema.inputs.insert(name, self.read_target_vec()?);
+        }
+
+        let length = self.read_usize()?;
+        let mut row_attribution = RowAttribution::default();
+        for _ in 0..length {
+            let context = self.read_string()?;
+            let num_shares = self.read_usize()?;
+            let mut shares = BTreeMap::new();
+            for _ in 0..num_shares {
+                let ops_per_row = self.read_usize()?;
+                shares.insert(ops_per_row, self.read_usize()?);
+            }
+            row_attribution.contexts.push((context, shares));
+        }
+
         Ok(ProverOnlyCircuitData {
             generators,
+            copy_generators,
+            batch_copy_generators,
             generator_indices_by_watches,
             constants_sigmas_commitment,
             sigmas,
@@ -901,8 +1166,13 @@ pub trait Read {
             representative_map,
             fft_root_table,
             circuit_digest,
+            domain_separator,
             lookup_rows,
             lut_to_lookups,
+            partition_contexts,
+            generator_contexts,
+            witness_schema,
+            row_attribution,
         })
     }
 
@@ -915,8 +1185,23 @@ pub trait Read {
         gate_serializer: &dyn GateSerializer<F, D>,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
     ) -> IoResult<ProverCircuitData<F, C, D>> {
+        let (encoding, digest) =
+            self.read_circuit_data_header::<F, C::Hasher>(CircuitDataKind::Prover)?;
         let common = self.read_common_circuit_data(gate_serializer)?;
-        let prover_only = self.read_prover_only_circuit_data(generator_serializer, &common)?;
+        let prover_only = self.read_prover_only_circuit_data_with_encoding(
+            generator_serializer,
+            &common,
+            encoding,
+        )?;
+        if prover_only.circuit_digest != digest {
+            return Err(IoError);
+        }
+        check_circuit_digest::<F, C, D>(
+            digest,
+            &prover_only.constants_sigmas_commitment.merkle_tree.cap,
+            &prover_only.domain_separator,
+            &common,
+        )?;
         Ok(ProverCircuitData {
             prover_only,
             common,
@@ -933,9 +1218,12 @@ pub trait Read {
         let height = self.read_usize()?;
         let constants_sigmas_cap = self.read_merkle_cap(height)?;
         let circuit_digest = self.read_hash::<F, <C as GenericConfig<D>>::Hasher>()?;
+        let domain_separator_len = self.read_usize()?;
+        let domain_separator = self.read_field_vec(domain_separator_len)?;
         Ok(VerifierOnlyCircuitData {
             constants_sigmas_cap,
             circuit_digest,
+            domain_separator,
         })
     }
 
@@ -947,8 +1235,19 @@ pub trait Read {
         &mut self,
         gate_serializer: &dyn GateSerializer<F, D>,
     ) -> IoResult<VerifierCircuitData<F, C, D>> {
+        let (_, digest) =
+            self.read_circuit_data_header::<F, C::Hasher>(CircuitDataKind::Verifier)?;
         let verifier_only = self.read_verifier_only_circuit_data()?;
+        if verifier_only.circuit_digest != digest {
+            return Err(IoError);
+        }
         let common = self.read_common_circuit_data(gate_serializer)?;
+        check_circuit_digest::<F, C, D>(
+            digest,
+            &verifier_only.constants_sigmas_cap,
+            &verifier_only.domain_separator,
+            &common,
+        )?;
         Ok(VerifierCircuitData {
             verifier_only,
             common,
@@ -974,10 +1273,14 @@ pub trait Read {
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
     {
-        let config = &common_data.config;
-        let wires_cap = self.read_merkle_cap(config.fri_config.cap_height)?;
-        let plonk_zs_partial_products_cap = self.read_merkle_cap(config.fri_config.cap_height)?;
-        let quotient_polys_cap = self.read_merkle_cap(config.fri_config.cap_height)?;
+        let fri_params = &common_data.fri_params;
+        let wires_cap =
+            self.read_merkle_cap(fri_params.initial_cap_height(PlonkOracle::WIRES.index))?;
+        let plonk_zs_partial_products_cap = self.read_merkle_cap(
+            fri_params.initial_cap_height(PlonkOracle::ZS_PARTIAL_PRODUCTS.index),
+        )?;
+        let quotient_polys_cap =
+            self.read_merkle_cap(fri_params.initial_cap_height(PlonkOracle::QUOTIENT.index))?;
         let openings = self.read_opening_set::<F, C, D>(common_data)?;
         let opening_proof = self.read_fri_proof::<F, C, D>(common_data)?;
         Ok(Proof {
@@ -1023,6 +1326,7 @@ pub trait Read {
         Ok(ProofWithPublicInputs {
             proof,
             public_inputs,
+            circuit_digest_hint: None,
         })
     }
 
@@ -1124,10 +1428,14 @@ pub trait Read {
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
     {
-        let config = &common_data.config;
-        let wires_cap = self.read_merkle_cap(config.fri_config.cap_height)?;
-        let plonk_zs_partial_products_cap = self.read_merkle_cap(config.fri_config.cap_height)?;
-        let quotient_polys_cap = self.read_merkle_cap(config.fri_config.cap_height)?;
+        let fri_params = &common_data.fri_params;
+        let wires_cap =
+            self.read_merkle_cap(fri_params.initial_cap_height(PlonkOracle::WIRES.index))?;
+        let plonk_zs_partial_products_cap = self.read_merkle_cap(
+            fri_params.initial_cap_height(PlonkOracle::ZS_PARTIAL_PRODUCTS.index),
+        )?;
+        let quotient_polys_cap =
+            self.read_merkle_cap(fri_params.initial_cap_height(PlonkOracle::QUOTIENT.index))?;
         let openings = self.read_opening_set::<F, C, D>(common_data)?;
         let opening_proof = self.read_compressed_fri_proof::<F, C, D>(common_data)?;
         Ok(CompressedProof {
@@ -1162,7 +1470,7 @@ pub trait Read {
     #[inline]
     fn read_lut(&mut self) -> IoResult<Vec<(u16, u16)>> {
         let length = self.read_usize()?;
-        let mut lut = Vec::with_capacity(length);
+        let mut lut = vec_with_untrusted_capacity(length);
         for _ in 0..length {
             lut.push((self.read_u16()?, self.read_u16()?));
         }
@@ -1174,7 +1482,7 @@ pub trait Read {
     #[inline]
     fn read_target_lut(&mut self) -> IoResult<Lookup> {
         let length = self.read_usize()?;
-        let mut lut = Vec::with_capacity(length);
+        let mut lut = vec_with_untrusted_capacity(length);
         for _ in 0..length {
             lut.push((self.read_target()?, self.read_target()?));
         }
@@ -1249,6 +1557,13 @@ pub trait Write {
         Ok(())
     }
 
+    /// Writes a UTF-8 encoded string `s` to `self`.
+    #[inline]
+    fn write_string(&mut self, s: &str) -> IoResult<()> {
+        self.write_usize(s.len())?;
+        self.write_all(s.as_bytes())
+    }
+
     /// Writes an element `x` from the field `F` to `self`.
     #[inline]
     fn write_field<F>(&mut self, x: F) -> IoResult<()>
@@ -1445,7 +1760,13 @@ pub trait Write {
         self.write_field_ext_vec::<F, D>(&os.lookup_zs)?;
         self.write_field_ext_vec::<F, D>(&os.lookup_zs_next)?;
         self.write_field_ext_vec::<F, D>(&os.partial_products)?;
-        self.write_field_ext_vec::<F, D>(&os.quotient_polys)
+        self.write_field_ext_vec::<F, D>(&os.quotient_polys)?;
+        // The number of extra openings is given by the common data, so proofs without them are
+        // unchanged.
+        for values in &os.extra_openings {
+            self.write_field_ext_vec::<F, D>(values)?;
+        }
+        Ok(())
     }
 
     /// Writes a value `os` of type [`OpeningSet`] to `self.`
@@ -1462,7 +1783,12 @@ pub trait Write {
         self.write_target_ext_vec::<D>(&os.lookup_zs)?;
         self.write_target_ext_vec::<D>(&os.next_lookup_zs)?;
         self.write_target_ext_vec::<D>(&os.partial_products)?;
-        self.write_target_ext_vec::<D>(&os.quotient_polys)
+        self.write_target_ext_vec::<D>(&os.quotient_polys)?;
+        self.write_usize(os.extra_openings.len())?;
+        for values in &os.extra_openings {
+            self.write_target_ext_vec::<D>(values)?;
+        }
+        Ok(())
     }
 
     /// Writes a value `p` of type [`MerkleProof`] to `self.`
@@ -1659,6 +1985,7 @@ pub trait Write {
             num_query_rounds,
             proof_of_work_bits,
             reduction_strategy,
+            final_poly_degree_bits,
         } = &config;
 
         self.write_usize(*rate_bits)?;
@@ -1666,6 +1993,7 @@ pub trait Write {
         self.write_usize(*num_query_rounds)?;
         self.write_u32(*proof_of_work_bits)?;
         self.write_fri_reduction_strategy(reduction_strategy)?;
+        self.write_usize(*final_poly_degree_bits)?;
 
         Ok(())
     }
@@ -1676,12 +2004,14 @@ pub trait Write {
             reduction_arity_bits,
             degree_bits,
             hiding,
+            initial_cap_heights,
         } = fri_params;
 
         self.write_fri_config(config)?;
         self.write_usize_vec(reduction_arity_bits.as_slice())?;
         self.write_usize(*degree_bits)?;
         self.write_bool(*hiding)?;
+        self.write_usize_vec(initial_cap_heights.as_slice())?;
 
         Ok(())
     }
@@ -1694,6 +2024,8 @@ pub trait Write {
             security_bits,
             num_challenges,
             max_quotient_degree_factor,
+            force_quotient_degree_factor,
+            oracle_cap_heights,
             use_base_arithmetic_gate,
             zero_knowledge,
             fri_config,
@@ -1708,6 +2040,27 @@ pub trait Write {
         self.write_bool(*use_base_arithmetic_gate)?;
         self.write_bool(*zero_knowledge)?;
         self.write_fri_config(fri_config)?;
+        let OracleCapHeights {
+            constants_sigmas,
+            wires,
+            zs_partial_products,
+            quotient,
+        } = oracle_cap_heights;
+        for option in [
+            force_quotient_degree_factor,
+            constants_sigmas,
+            wires,
+            zs_partial_products,
+            quotient,
+        ] {
+            match option {
+                None => self.write_u8(0)?,
+                Some(value) => {
+                    self.write_u8(1)?;
+                    self.write_usize(*value)?;
+                }
+            }
+        }
 
         Ok(())
     }
@@ -1776,11 +2129,16 @@ pub trait Write {
             num_gate_constraints,
             num_constants,
             num_public_inputs,
+            public_input_commitment,
+            duplicate_public_inputs,
             k_is,
             num_partial_products,
             num_lookup_polys,
             num_lookup_selectors,
             luts,
+            num_blinding_rows,
+            extra_opening_points,
+            public_input_names,
         } = common_data;
 
         self.write_circuit_config(config)?;
@@ -1791,6 +2149,11 @@ pub trait Write {
         self.write_usize(*num_gate_constraints)?;
         self.write_usize(*num_constants)?;
         self.write_usize(*num_public_inputs)?;
+        self.write_usize(match *public_input_commitment {
+            PublicInputCommitment::Flat => 0,
+            PublicInputCommitment::Chunked { chunk_size } => chunk_size,
+        })?;
+        self.write_u8(*duplicate_public_inputs as u8)?;
 
         self.write_usize(k_is.len())?;
         self.write_field_vec(k_is.as_slice())?;
@@ -1803,6 +2166,18 @@ pub trait Write {
         for lut in luts.iter() {
             self.write_lut(lut)?;
         }
+        self.write_usize(*num_blinding_rows)?;
+        self.write_usize(extra_opening_points.len())?;
+        for opening_point in extra_opening_points {
+            self.write_usize(opening_point.shift as usize)?;
+            self.write_usize_vec(&opening_point.oracles)?;
+        }
+        self.write_usize(public_input_names.len())?;
+        for (name, range) in public_input_names {
+            self.write_string(name)?;
+            self.write_usize(range.start)?;
+            self.write_usize(range.end)?;
+        }
 
         self.write_usize(gates.len())?;
         for gate in gates.iter() {
@@ -1812,6 +2187,21 @@ pub trait Write {
         Ok(())
     }
 
+    /// Writes a header identifying circuit data of the given kind, in the current format version,
+    /// with the encoding of its prover data and the circuit's digest.
+    fn write_circuit_data_header<F: RichField, H: Hasher<F>>(
+        &mut self,
+        kind: CircuitDataKind,
+        encoding: ProverDataEncoding,
+        circuit_digest: H::Hash,
+    ) -> IoResult<()> {
+        self.write_all(&CIRCUIT_DATA_MAGIC)?;
+        self.write_u32(CIRCUIT_DATA_FORMAT_VERSION)?;
+        self.write_u8(kind as u8)?;
+        self.write_u8(encoding as u8)?;
+        self.write_hash::<F, H>(circuit_digest)
+    }
+
     fn write_circuit_data<
         F: RichField + Extendable<D>,
         C: GenericConfig<D, F = F>,
@@ -1821,12 +2211,19 @@ pub trait Write {
         circuit_data: &CircuitData<F, C, D>,
         gate_serializer: &dyn GateSerializer<F, D>,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+        encoding: ProverDataEncoding,
     ) -> IoResult<()> {
+        self.write_circuit_data_header::<F, C::Hasher>(
+            CircuitDataKind::Full,
+            encoding,
+            circuit_data.verifier_only.circuit_digest,
+        )?;
         self.write_common_circuit_data(&circuit_data.common, gate_serializer)?;
-        self.write_prover_only_circuit_data(
+        self.write_prover_only_circuit_data_with_encoding(
             &circuit_data.prover_only,
             generator_serializer,
             &circuit_data.common,
+            encoding,
         )?;
         self.write_verifier_only_circuit_data(&circuit_data.verifier_only)
     }
@@ -1840,9 +2237,30 @@ pub trait Write {
         prover_only_circuit_data: &ProverOnlyCircuitData<F, C, D>,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
         common_data: &CommonCircuitData<F, D>,
+    ) -> IoResult<()> {
+        self.write_prover_only_circuit_data_with_encoding(
+            prover_only_circuit_data,
+            generator_serializer,
+            common_data,
+            ProverDataEncoding::Full,
+        )
+    }
+
+    fn write_prover_only_circuit_data_with_encoding<
+        F: RichField + Extendable<D>,
+        C: GenericConfig<D, F = F>,
+        const D: usize,
+    >(
+        &mut self,
+        prover_only_circuit_data: &ProverOnlyCircuitData<F, C, D>,
+        generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+        common_data: &CommonCircuitData<F, D>,
+        encoding: ProverDataEncoding,
     ) -> IoResult<()> {
         let ProverOnlyCircuitData {
             generators,
+            copy_generators,
+            batch_copy_generators,
             generator_indices_by_watches,
             constants_sigmas_commitment,
             sigmas,
@@ -1851,14 +2269,33 @@ pub trait Write {
             representative_map,
             fft_root_table,
             circuit_digest,
+            domain_separator,
             lookup_rows,
             lut_to_lookups,
+            partition_contexts,
+            generator_contexts,
+            witness_schema,
+            row_attribution,
         } = prover_only_circuit_data;
 
         self.write_usize(generators.len())?;
         for generator in generators.iter() {
             self.write_generator::<F, D>(generator, generator_serializer, common_data)?;
         }
+        self.write_usize(copy_generators.len())?;
+        for copy_generator in copy_generators {
+            let CopyGenerator { src, dst } = copy_generator.inner;
+            self.write_target(src)?;
+            self.write_target(dst)?;
+        }
+        self.write_usize(batch_copy_generators.len())?;
+        for batch_copy_generator in batch_copy_generators {
+            self.write_usize(batch_copy_generator.pairs.len())?;
+            for &(src, dst) in &batch_copy_generator.pairs {
+                self.write_target(src)?;
+                self.write_target(dst)?;
+            }
+        }
 
         self.write_usize(generator_indices_by_watches.len())?;
         for (k, v) in generator_indices_by_watches {
@@ -1867,10 +2304,14 @@ pub trait Write {
         }
 
         self.write_polynomial_batch(constants_sigmas_commitment)?;
+        let (sigmas, subgroup, fft_root_table) = match encoding {
+            ProverDataEncoding::Full => (&sigmas[..], &subgroup[..], fft_root_table),
+            ProverDataEncoding::Compact => (&[][..], &[][..], &None),
+        };
         self.write_usize(sigmas.len())?;
-        for i in 0..sigmas.len() {
-            self.write_usize(sigmas[i].len())?;
-            self.write_field_vec(&sigmas[i])?;
+        for sigma in sigmas {
+            self.write_usize(sigma.len())?;
+            self.write_field_vec(sigma)?;
         }
         self.write_usize(subgroup.len())?;
         self.write_field_vec(subgroup)?;
@@ -1890,6 +2331,8 @@ pub trait Write {
         }
 
         self.write_hash::<F, <C as GenericConfig<D>>::Hasher>(*circuit_digest)?;
+        self.write_usize(domain_separator.len())?;
+        self.write_field_vec(domain_separator)?;
 
         self.write_usize(lookup_rows.len())?;
         for wire in lookup_rows.iter() {
@@ -1903,6 +2346,34 @@ pub trait Write {
             self.write_target_lut(tlut)?;
         }
 
+        self.write_usize(partition_contexts.len())?;
+        for (k, v) in partition_contexts {
+            self.write_usize(*k)?;
+            self.write_string(v)?;
+        }
+
+        self.write_usize(generator_contexts.len())?;
+        for (k, v) in generator_contexts {
+            self.write_usize(*k)?;
+            self.write_string(v)?;
+        }
+
+        self.write_usize(witness_schema.inputs.len())?;
+        for (name, targets) in &witness_schema.inputs {
+            self.write_string(name)?;
+            self.write_target_vec(targets)?;
+        }
+
+        self.write_usize(row_attribution.contexts.len())?;
+        for (context, shares) in &row_attribution.contexts {
+            self.write_string(context)?;
+            self.write_usize(shares.len())?;
+            for (&ops_per_row, &num_ops) in shares {
+                self.write_usize(ops_per_row)?;
+                self.write_usize(num_ops)?;
+            }
+        }
+
         Ok(())
     }
 
@@ -1915,12 +2386,19 @@ pub trait Write {
         prover_circuit_data: &ProverCircuitData<F, C, D>,
         gate_serializer: &dyn GateSerializer<F, D>,
         generator_serializer: &dyn WitnessGeneratorSerializer<F, D>,
+        encoding: ProverDataEncoding,
     ) -> IoResult<()> {
+        self.write_circuit_data_header::<F, C::Hasher>(
+            CircuitDataKind::Prover,
+            encoding,
+            prover_circuit_data.prover_only.circuit_digest,
+        )?;
         self.write_common_circuit_data(&prover_circuit_data.common, gate_serializer)?;
-        self.write_prover_only_circuit_data(
+        self.write_prover_only_circuit_data_with_encoding(
             &prover_circuit_data.prover_only,
             generator_serializer,
             &prover_circuit_data.common,
+            encoding,
         )
     }
 
@@ -1935,11 +2413,14 @@ pub trait Write {
         let VerifierOnlyCircuitData {
             constants_sigmas_cap,
             circuit_digest,
+            domain_separator,
         } = verifier_only_circuit_data;
 
         self.write_usize(constants_sigmas_cap.height())?;
         self.write_merkle_cap(constants_sigmas_cap)?;
         self.write_hash::<F, <C as GenericConfig<D>>::Hasher>(*circuit_digest)?;
+        self.write_usize(domain_separator.len())?;
+        self.write_field_vec(domain_separator)?;
 
         Ok(())
     }
@@ -1953,6 +2434,11 @@ pub trait Write {
         verifier_circuit_data: &VerifierCircuitData<F, C, D>,
         gate_serializer: &dyn GateSerializer<F, D>,
     ) -> IoResult<()> {
+        self.write_circuit_data_header::<F, C::Hasher>(
+            CircuitDataKind::Verifier,
+            ProverDataEncoding::Full,
+            verifier_circuit_data.verifier_only.circuit_digest,
+        )?;
         self.write_verifier_only_circuit_data(&verifier_circuit_data.verifier_only)?;
         self.write_common_circuit_data(&verifier_circuit_data.common, gate_serializer)
     }
@@ -2009,6 +2495,7 @@ pub trait Write {
         let ProofWithPublicInputs {
             proof,
             public_inputs,
+            circuit_digest_hint: _,
         } = proof_with_pis;
         self.write_proof(proof)?;
         self.write_usize(public_inputs.len())?;
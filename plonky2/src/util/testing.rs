@@ -0,0 +1,418 @@
+//! Helpers to catch regressions in the size of circuits, e.g. when changing how a gadget packs its
+//! operations into gates, and to check gadgets against their native counterparts with
+//! [`assert_differential`].
+//!
+//! ```
+//! use plonky2::field::goldilocks_field::GoldilocksField;
+//! use plonky2::plonk::circuit_data::CircuitConfig;
+//! use plonky2::plonk::config::PoseidonGoldilocksConfig;
+//! use plonky2::util::testing::{assert_circuit_size, CircuitSize};
+//!
+//! assert_circuit_size::<GoldilocksField, PoseidonGoldilocksConfig, 2>(
+//!     CircuitConfig::standard_recursion_config(),
+//!     |builder| {
+//!         let x = builder.add_virtual_target();
+//!         let x_squared = builder.square(x);
+//!         let x_cubed = builder.mul(x_squared, x);
+//!         builder.register_public_input(x_cubed);
+//!     },
+//!     &CircuitSize::new(2, 4)
+//!         .with_gate("ArithmeticGate", 1)
+//!         .with_gate("PoseidonGate", 1)
+//!         .with_gate("PublicInputGate", 1)
+//!         .with_gate("ConstantGate", 1),
+//! );
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use log::Level;
+
+use crate::field::extension::Extendable;
+use crate::gates::selectors::UNUSED_SELECTOR;
+use crate::hash::hash_types::RichField;
+use crate::iop::target::Target;
+use crate::iop::witness::{PartialWitness, WitnessWrite};
+use crate::plonk::circuit_builder::CircuitBuilder;
+use crate::plonk::circuit_data::{CircuitConfig, CircuitData};
+use crate::plonk::config::GenericConfig;
+
+/// The size of a circuit: its degree, and the number of rows of each gate type, before blinding
+/// and padding.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CircuitSize {
+    pub degree_bits: usize,
+    pub num_rows: usize,
+    /// The number of rows of each gate type, by the name of the gate's type, so that e.g.
+    /// `RandomAccessGate`s of different sizes are counted together.
+    pub gate_rows: BTreeMap<String, usize>,
+}
+
+impl CircuitSize {
+    pub fn new(degree_bits: usize, num_rows: usize) -> Self {
+        Self {
+            degree_bits,
+            num_rows,
+            gate_rows: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_gate(mut self, gate_type: &str, rows: usize) -> Self {
+        self.gate_rows.insert(gate_type.to_string(), rows);
+        self
+    }
+
+    /// Describes how `self` differs from `expected`, one line per mismatch. Returns an empty
+    /// vector if they're equal.
+    pub fn diff(&self, expected: &Self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut compare = |name: &str, expected: usize, actual: usize| {
+            if expected != actual {
+                let delta = actual as isize - expected as isize;
+                lines.push(format!(
+                    "{name}: expected {expected}, got {actual} ({delta:+})"
+                ));
+            }
+        };
+        compare("degree_bits", expected.degree_bits, self.degree_bits);
+        compare("num_rows", expected.num_rows, self.num_rows);
+        let mut gate_types = expected
+            .gate_rows
+            .keys()
+            .chain(self.gate_rows.keys())
+            .collect::<Vec<_>>();
+        gate_types.sort();
+        gate_types.dedup();
+        for gate_type in gate_types {
+            compare(
+                gate_type,
+                expected.gate_rows.get(gate_type).copied().unwrap_or(0),
+                self.gate_rows.get(gate_type).copied().unwrap_or(0),
+            );
+        }
+        lines
+    }
+}
+
+/// Returns the name of a gate's type, i.e. its ID up to the first character which can't be part
+/// of a Rust identifier, dropping the gate's parameters.
+fn gate_type(gate_id: &str) -> &str {
+    let end = gate_id
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(gate_id.len());
+    &gate_id[..end]
+}
+
+/// Builds the circuit defined by `define` and measures its size, as read from the built circuit's
+/// data. Rows after the last one which isn't a `NoopGate` are counted as blinding and padding.
+pub fn measure_circuit_size<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    config: CircuitConfig,
+    define: impl FnOnce(&mut CircuitBuilder<F, D>),
+) -> CircuitSize {
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    define(&mut builder);
+    let data = builder.build::<C>();
+
+    let gate_types = data
+        .common
+        .gates
+        .iter()
+        .map(|gate| gate_type(&gate.0.id()).to_string())
+        .collect::<Vec<_>>();
+    let row_gates = row_gates(&data);
+    let num_rows = row_gates
+        .iter()
+        .rposition(|&gate| gate_types[gate] != "NoopGate")
+        .map_or(0, |row| row + 1);
+
+    let mut gate_rows = BTreeMap::<String, usize>::new();
+    for &gate in &row_gates[..num_rows] {
+        *gate_rows.entry(gate_types[gate].clone()).or_default() += 1;
+    }
+    CircuitSize {
+        degree_bits: data.common.degree_bits(),
+        num_rows,
+        gate_rows,
+    }
+}
+
+/// The index in `common.gates` of the gate of each row of `data`, read from its selector
+/// polynomials. Each row's selector in the group of its gate holds the gate's index, and its
+/// selectors in other groups hold `UNUSED_SELECTOR`.
+fn row_gates<F: RichField + Extendable<D>, C: GenericConfig<D, F = F>, const D: usize>(
+    data: &CircuitData<F, C, D>,
+) -> Vec<usize> {
+    let selectors = data.prover_only.constants_sigmas_commitment.polynomials
+        [..data.common.selectors_info.num_selectors()]
+        .iter()
+        .map(|poly| poly.clone().fft().values)
+        .collect::<Vec<_>>();
+    (0..data.common.degree())
+        .map(|row| {
+            selectors
+                .iter()
+                .map(|values| values[row].to_canonical_u64() as usize)
+                .find(|&gate| gate != UNUSED_SELECTOR)
+                .expect("Every row has a gate")
+        })
+        .collect()
+}
+
+/// Builds the circuit defined by `define` and asserts that its size is `expected`. On mismatch,
+/// panics listing each difference, e.g. `ArithmeticGate: expected 10, got 12 (+2)`.
+///
+/// Only the number of rows of each gate type is compared, so reordering gates or operations
+/// doesn't fail the assertion. Gate types not listed in `expected` are expected to be absent.
+pub fn assert_circuit_size<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    config: CircuitConfig,
+    define: impl FnOnce(&mut CircuitBuilder<F, D>),
+    expected: &CircuitSize,
+) {
+    let actual = measure_circuit_size::<F, C, D>(config, define);
+    let diff = actual.diff(expected);
+    assert!(
+        diff.is_empty(),
+        "Circuit size changed:\n  {}\nActual size: {:?}",
+        diff.join("\n  "),
+        actual
+    );
+}
+
+/// Checks the gadget `circuit` against its native counterpart `native` on `num_instances` inputs
+/// drawn with `sample`.
+///
+/// All instances are evaluated in a single circuit, which takes each instance's inputs as virtual
+/// targets and registers its outputs as public inputs, so that the circuit is only proven once.
+/// Panics if proving or verification fails, naming the instance in whose context generation
+/// failed, or if an instance's outputs differ from the native ones, listing its inputs.
+pub fn assert_differential<
+    F: RichField + Extendable<D>,
+    C: GenericConfig<D, F = F>,
+    const D: usize,
+>(
+    config: CircuitConfig,
+    num_instances: usize,
+    mut sample: impl FnMut() -> Vec<F>,
+    mut circuit: impl FnMut(&mut CircuitBuilder<F, D>, &[Target]) -> Vec<Target>,
+    native: impl Fn(&[F]) -> Vec<F>,
+) {
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let mut pw = PartialWitness::new();
+    let mut instances = Vec::with_capacity(num_instances);
+    for i in 0..num_instances {
+        let inputs = sample();
+        let input_targets = builder.add_virtual_targets(inputs.len());
+        pw.set_target_arr(&input_targets, &inputs);
+
+        builder.push_context(Level::Debug, &format!("differential instance {i}"));
+        let outputs = circuit(&mut builder, &input_targets);
+        builder.pop_context();
+        builder.register_public_inputs(&outputs);
+
+        let expected = native(&inputs);
+        assert_eq!(
+            outputs.len(),
+            expected.len(),
+            "Instance {i}: the circuit has {} outputs, but the native version {}",
+            outputs.len(),
+            expected.len()
+        );
+        instances.push((inputs, expected));
+    }
+
+    let data = builder.build::<C>();
+    let proof = data
+        .prove(pw)
+        .unwrap_or_else(|err| panic!("Proving failed: {err:#}"));
+    let public_inputs = proof.public_inputs.clone();
+    data.verify(proof)
+        .unwrap_or_else(|err| panic!("Verification failed: {err:#}"));
+
+    let mut outputs = public_inputs.into_iter();
+    for (i, (inputs, expected)) in instances.into_iter().enumerate() {
+        let actual = outputs.by_ref().take(expected.len()).collect::<Vec<_>>();
+        assert_eq!(
+            actual, expected,
+            "Instance {i}: the circuit's outputs differ from the native ones on inputs {inputs:?}"
+        );
+    }
+}
+
+/// Declares a test asserting the size of a circuit over the Goldilocks field with Poseidon, with
+/// [`assert_circuit_size`].
+///
+/// ```ignore
+/// circuit_size_test!(
+///     random_access_size,
+///     CircuitConfig::standard_recursion_config(),
+///     |builder| {
+///         let list = builder.add_virtual_targets(16);
+///         let index = builder.add_virtual_target();
+///         builder.random_access(index, list);
+///     },
+///     degree_bits: 1,
+///     num_rows: 2,
+///     gates: {
+///         "PublicInputGate" => 1,
+///         "RandomAccessGate" => 1,
+///     },
+/// );
+/// ```
+#[macro_export]
+macro_rules! circuit_size_test {
+    (
+        $name:ident,
+        $config:expr,
+        |$builder:ident| $define:block,
+        degree_bits: $degree_bits:expr,
+        num_rows: $num_rows:expr,
+        gates: { $($gate_type:literal => $rows:expr),* $(,)? } $(,)?
+    ) => {
+        #[test]
+        fn $name() {
+            $crate::util::testing::assert_circuit_size::<
+                $crate::field::goldilocks_field::GoldilocksField,
+                $crate::plonk::config::PoseidonGoldilocksConfig,
+                2,
+            >(
+                $config,
+                |$builder: &mut $crate::plonk::circuit_builder::CircuitBuilder<
+                    $crate::field::goldilocks_field::GoldilocksField,
+                    2,
+                >| $define,
+                &$crate::util::testing::CircuitSize::new($degree_bits, $num_rows)
+                    $(.with_gate($gate_type, $rows))*,
+            );
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec;
+
+    use super::*;
+    use crate::field::types::Sample;
+    use crate::gates::noop::NoopGate;
+
+    #[test]
+    fn diff_lists_each_mismatch() {
+        let expected = CircuitSize::new(3, 7)
+            .with_gate("ArithmeticGate", 5)
+            .with_gate("PoseidonGate", 2);
+        let actual = CircuitSize::new(3, 8)
+            .with_gate("ArithmeticGate", 5)
+            .with_gate("ConstantGate", 1)
+            .with_gate("PoseidonGate", 2);
+        assert!(expected.diff(&expected).is_empty());
+        assert_eq!(
+            actual.diff(&expected),
+            [
+                "num_rows: expected 7, got 8 (+1)",
+                "ConstantGate: expected 0, got 1 (+1)",
+            ]
+        );
+    }
+
+    type F = crate::field::goldilocks_field::GoldilocksField;
+    type C = crate::plonk::config::PoseidonGoldilocksConfig;
+
+    fn sample_pair() -> Vec<F> {
+        F::rand_vec(2)
+    }
+
+    #[test]
+    fn differential_accepts_matching_gadget() {
+        assert_differential::<F, C, 2>(
+            CircuitConfig::standard_recursion_config(),
+            16,
+            sample_pair,
+            |builder, inputs| vec![builder.mul(inputs[0], inputs[1])],
+            |inputs| vec![inputs[0] * inputs[1]],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "the circuit's outputs differ from the native ones")]
+    fn differential_catches_wrong_output() {
+        // Squares the first input instead of multiplying the two.
+        assert_differential::<F, C, 2>(
+            CircuitConfig::standard_recursion_config(),
+            16,
+            sample_pair,
+            |builder, inputs| vec![builder.square(inputs[0])],
+            |inputs| vec![inputs[0] * inputs[1]],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "differential instance 0")]
+    fn differential_catches_unsatisfiable_gadget() {
+        // Asserts the inputs are different, but feeds the gadget equal ones.
+        let x = F::rand();
+        assert_differential::<F, C, 2>(
+            CircuitConfig::standard_recursion_config(),
+            4,
+            || vec![x, x],
+            |builder, inputs| {
+                builder.assert_ne(inputs[0], inputs[1]);
+                vec![]
+            },
+            |_| vec![],
+        );
+    }
+
+    #[test]
+    fn size_is_read_from_built_circuit() {
+        let size = measure_circuit_size::<F, C, 2>(
+            CircuitConfig::standard_recursion_config(),
+            |builder| {
+                // Enqueued range checks only get their gates when the circuit is built.
+                for _ in 0..10 {
+                    let x = builder.add_virtual_target();
+                    builder.enqueue_range_check(x, 16);
+                }
+                // A `NoopGate` added by hand is counted, as `build` adds its gates after it.
+                builder.add_gate(NoopGate, vec![]);
+            },
+        );
+        assert_eq!(
+            size,
+            CircuitSize::new(3, 6)
+                .with_gate("ConstantGate", 2)
+                .with_gate("NoopGate", 1)
+                .with_gate("PublicInputGate", 1)
+                .with_gate("RangeCheckGate", 2)
+        );
+    }
+
+    #[test]
+    fn gate_types_drop_parameters() {
+        assert_eq!(
+            gate_type("ArithmeticGate { num_ops: 20 }"),
+            "ArithmeticGate"
+        );
+        assert_eq!(
+            gate_type("PoseidonGate(PhantomData<GoldilocksField>)<WIDTH=12>"),
+            "PoseidonGate"
+        );
+        assert_eq!(gate_type("NoopGate"), "NoopGate");
+    }
+}
@@ -1,7 +1,22 @@
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec, vec::Vec};
+#[cfg(not(feature = "timing"))]
+use core::time::Duration;
+
 use log::{log, Level};
+use serde::{Deserialize, Serialize};
 #[cfg(feature = "timing")]
 use web_time::{Duration, Instant};
 
+/// A scope of a [`TimingTree`] and the time it took, as plain data which can be serialized, e.g.
+/// with `serde_json` to collect metrics from a prover.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimingEntry {
+    pub name: String,
+    pub duration: Duration,
+    pub children: Vec<TimingEntry>,
+}
+
 /// The hierarchy of scopes, and the time consumed by each one. Useful for profiling.
 #[cfg(feature = "timing")]
 #[derive(Debug)]
@@ -121,6 +136,28 @@ impl TimingTree {
     #[cfg(not(feature = "timing"))]
     pub fn pop(&mut self) {}
 
+    /// Adds an already-finished scope which took `duration`, below the deepest open scope. This is
+    /// useful to report time which was aggregated over many short operations.
+    #[cfg(feature = "timing")]
+    pub fn push_finished(&mut self, ctx: &str, level: log::Level, duration: Duration) {
+        self.push(ctx, level);
+        let scope = self.deepest_open_scope();
+        scope.exit_time = Some(scope.enter_time + duration);
+    }
+
+    #[cfg(not(feature = "timing"))]
+    pub fn push_finished(&mut self, _ctx: &str, _level: log::Level, _duration: Duration) {}
+
+    #[cfg(feature = "timing")]
+    fn deepest_open_scope(&mut self) -> &mut Self {
+        match self.children.last() {
+            Some(last_child) if last_child.is_open() => {
+                self.children.last_mut().unwrap().deepest_open_scope()
+            }
+            _ => self,
+        }
+    }
+
     #[cfg(feature = "timing")]
     fn duration(&self) -> Duration {
         self.exit_time
@@ -145,6 +182,48 @@ impl TimingTree {
         }
     }
 
+    /// Lists every scope in depth-first order, as `(name, duration, depth)` triples, the root
+    /// having depth 0. Scopes which are still open are timed up to now.
+    #[cfg(feature = "timing")]
+    pub fn to_entries(&self) -> Vec<(String, Duration, usize)> {
+        let mut entries = Vec::new();
+        self.to_entries_helper(0, &mut entries);
+        entries
+    }
+
+    #[cfg(not(feature = "timing"))]
+    pub fn to_entries(&self) -> Vec<(String, Duration, usize)> {
+        Vec::new()
+    }
+
+    #[cfg(feature = "timing")]
+    fn to_entries_helper(&self, depth: usize, entries: &mut Vec<(String, Duration, usize)>) {
+        entries.push((self.name.clone(), self.duration(), depth));
+        for child in &self.children {
+            child.to_entries_helper(depth + 1, entries);
+        }
+    }
+
+    /// Exports this tree as a [`TimingEntry`]. Scopes which are still open are timed up to now.
+    /// Without the `timing` feature, nothing is timed, and this returns an empty entry.
+    #[cfg(feature = "timing")]
+    pub fn export(&self) -> TimingEntry {
+        TimingEntry {
+            name: self.name.clone(),
+            duration: self.duration(),
+            children: self.children.iter().map(|c| c.export()).collect(),
+        }
+    }
+
+    #[cfg(not(feature = "timing"))]
+    pub fn export(&self) -> TimingEntry {
+        TimingEntry {
+            name: String::new(),
+            duration: Duration::ZERO,
+            children: vec![],
+        }
+    }
+
     #[cfg(feature = "timing")]
     pub fn print(&self) {
         self.print_helper(0);
@@ -0,0 +1,118 @@
+//! Aggregates leaf proofs into a single proof through a binary tree of aggregation circuits.
+
+use anyhow::Result;
+use plonky2::field::types::Field;
+use plonky2::gates::noop::NoopGate;
+use plonky2::hash::poseidon::PoseidonHash;
+use plonky2::iop::target::Target;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{CircuitConfig, CircuitData};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::proof::ProofWithPublicInputs;
+use plonky2::recursion::aggregation::{build_aggregation_circuit, combine_public_inputs};
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// A leaf circuit proving knowledge of `x` such that `x^2` equals its public input.
+fn leaf_circuit() -> (CircuitData<F, C, D>, Target) {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let x = builder.add_virtual_target();
+    let x_squared = builder.square(x);
+    builder.register_public_input(x_squared);
+    // The dummy proof standing in for a missing right proof comes from a circuit padded with
+    // no-op gates, so the leaf circuit must use that gate as well.
+    builder.add_gate(NoopGate, vec![]);
+    (builder.build::<C>(), x)
+}
+
+/// The leaf circuit's data, and proofs of it.
+type LeafProofs = (CircuitData<F, C, D>, Vec<ProofWithPublicInputs<F, C, D>>);
+
+fn leaf_proofs(n: u32) -> Result<LeafProofs> {
+    let (leaf_data, x) = leaf_circuit();
+    let proofs = (1..=n)
+        .map(|i| {
+            let mut pw = PartialWitness::new();
+            pw.set_target(x, F::from_canonical_u32(i));
+            leaf_data.prove(pw)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((leaf_data, proofs))
+}
+
+#[test]
+fn aggregate_four_leaves() -> Result<()> {
+    let (leaf_data, leaves) = leaf_proofs(4)?;
+    let level1 = build_aggregation_circuit(&leaf_data.common, &leaf_data.verifier_only)?;
+    let level2 = build_aggregation_circuit(
+        &level1.circuit_data.common,
+        &level1.circuit_data.verifier_only,
+    )?;
+
+    let left = level1.prove_aggregation(&leaves[0], Some(&leaves[1]))?;
+    let right = level1.prove_aggregation(&leaves[2], Some(&leaves[3]))?;
+    level1.verify(left.clone())?;
+    level1.verify(right.clone())?;
+    let root = level2.prove_aggregation(&left, Some(&right))?;
+    level2.verify(root.clone())?;
+
+    let combine = |left: &[F], right: &[F]| {
+        combine_public_inputs::<F, PoseidonHash>(left, Some(right)).unwrap()
+    };
+    let squares = (1..=4u32)
+        .map(|i| F::from_canonical_u32(i * i))
+        .collect::<Vec<_>>();
+    let expected = combine(
+        &combine(&squares[0..1], &squares[1..2]),
+        &combine(&squares[2..3], &squares[3..4]),
+    );
+    assert_eq!(root.public_inputs, expected);
+
+    Ok(())
+}
+
+#[test]
+fn aggregate_odd_leaf_with_dummy() -> Result<()> {
+    let (leaf_data, leaves) = leaf_proofs(1)?;
+    let level1 = build_aggregation_circuit(&leaf_data.common, &leaf_data.verifier_only)?;
+
+    let proof = level1.prove_aggregation(&leaves[0], None)?;
+    assert_eq!(
+        proof.public_inputs,
+        combine_public_inputs::<F, PoseidonHash>(&[F::ONE], None)?
+    );
+    level1.verify(proof)
+}
+
+#[test]
+fn missing_right_proof_differs_from_zero_public_inputs() -> Result<()> {
+    // The proof for `x = 0` has a zero public input, like the dummy standing in for a missing
+    // right proof.
+    let (leaf_data, x) = leaf_circuit();
+    let mut pw = PartialWitness::new();
+    pw.set_target(x, F::ZERO);
+    let zero_leaf = leaf_data.prove(pw)?;
+    assert_eq!(zero_leaf.public_inputs, [F::ZERO]);
+    let mut pw = PartialWitness::new();
+    pw.set_target(x, F::ONE);
+    let leaf = leaf_data.prove(pw)?;
+
+    let level1 = build_aggregation_circuit(&leaf_data.common, &leaf_data.verifier_only)?;
+    let with_zero = level1.prove_aggregation(&leaf, Some(&zero_leaf))?;
+    let without_right = level1.prove_aggregation(&leaf, None)?;
+    level1.verify(with_zero.clone())?;
+    level1.verify(without_right.clone())?;
+
+    // A verifier expecting two aggregated proofs rejects the proof with a missing right proof.
+    let expected = combine_public_inputs::<F, PoseidonHash>(&[F::ONE], Some(&[F::ZERO]))?;
+    assert_eq!(with_zero.public_inputs, expected);
+    assert_ne!(without_right.public_inputs, expected);
+    assert_eq!(
+        without_right.public_inputs,
+        combine_public_inputs::<F, PoseidonHash>(&[F::ONE], None)?
+    );
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Checks that `CircuitBuilder::build_verifier` allocates less memory than a full build, by
+//! dropping the state only needed to prove before committing to the constants and sigmas.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+/// Wraps the system allocator to track the peak number of bytes allocated.
+struct PeakAlloc {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = self.current.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            self.peak.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+impl PeakAlloc {
+    /// Runs `f` and returns its result along with the peak number of bytes allocated while it
+    /// ran, on top of what was already allocated.
+    fn measure<T>(&self, f: impl FnOnce() -> T) -> (T, usize) {
+        let baseline = self.current.load(Ordering::Relaxed);
+        self.peak.store(baseline, Ordering::Relaxed);
+        let result = f();
+        (result, self.peak.load(Ordering::Relaxed) - baseline)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc {
+    current: AtomicUsize::new(0),
+    peak: AtomicUsize::new(0),
+};
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// Builds a circuit squaring a value repeatedly, so that most rows have witness generators.
+fn squaring_circuit(num_squarings: usize) -> CircuitBuilder<F, D> {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let mut x = builder.add_virtual_target();
+    for _ in 0..num_squarings {
+        x = builder.square(x);
+    }
+    builder.register_public_input(x);
+    builder
+}
+
+#[test]
+fn build_verifier_lowers_peak_memory() {
+    // Large enough for the witness generators and the wire partition to weigh on the peak, and
+    // small enough to build quickly.
+    const NUM_SQUARINGS: usize = 1 << 14;
+
+    let (full, full_peak) = GLOBAL.measure(|| squaring_circuit(NUM_SQUARINGS).build::<C>());
+    let full = full.verifier_data();
+    let (verifier, verifier_peak) =
+        GLOBAL.measure(|| squaring_circuit(NUM_SQUARINGS).build_verifier::<C>());
+    assert_eq!(verifier, full);
+    println!(
+        "full build: peak allocation {} KiB, verifier build: peak allocation {} KiB",
+        full_peak >> 10,
+        verifier_peak >> 10
+    );
+    assert!(
+        verifier_peak * 4 < full_peak * 3,
+        "the verifier build peaked at {} bytes, against {} for the full build",
+        verifier_peak,
+        full_peak
+    );
+}
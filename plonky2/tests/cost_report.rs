@@ -0,0 +1,97 @@
+//! Checks the memory estimates of `CircuitBuilder::cost_report` against the memory actually
+//! allocated to build and prove small circuits.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Result;
+use plonky2::field::types::Sample;
+use plonky2::gates::noop::NoopGate;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+/// Wraps the system allocator to track the peak number of bytes allocated.
+struct PeakAlloc {
+    current: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = self.current.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            self.peak.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        self.current.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+impl PeakAlloc {
+    /// Runs `f` and returns the peak number of bytes allocated while it ran, on top of what was
+    /// already allocated.
+    fn measure<T>(&self, f: impl FnOnce() -> T) -> usize {
+        let baseline = self.current.load(Ordering::Relaxed);
+        self.peak.store(baseline, Ordering::Relaxed);
+        drop(f());
+        self.peak.load(Ordering::Relaxed) - baseline
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc {
+    current: AtomicUsize::new(0),
+    peak: AtomicUsize::new(0),
+};
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// Builds and proves circuits of a few degrees, and checks that the peak memory allocated is
+/// within a factor of two of the estimate.
+#[test]
+fn prover_memory_estimate() -> Result<()> {
+    for (degree_bits, zero_knowledge) in [(10, false), (12, false), (10, true)] {
+        let config = CircuitConfig {
+            zero_knowledge,
+            ..CircuitConfig::standard_recursion_config()
+        };
+        let mut builder = CircuitBuilder::<F, D>::new(config);
+        let x = builder.add_virtual_target();
+        let mut y = x;
+        for _ in 0..100 {
+            y = builder.mul_add(y, y, x);
+        }
+        builder.register_public_input(y);
+        while builder.num_gates() < (1 << (degree_bits - 1)) + 1 {
+            builder.add_gate(NoopGate, vec![]);
+        }
+        let report = builder.cost_report::<C>();
+        // Blinding rows may grow the degree.
+        assert!(report.degree_bits >= degree_bits);
+
+        let mut inputs = PartialWitness::new();
+        inputs.set_target(x, F::rand());
+        let mut result = Ok(());
+        let measured = GLOBAL.measure(|| {
+            let data = builder.build::<C>();
+            result = data.prove(inputs).map(drop);
+        });
+        result?;
+
+        let estimated = report.prover_memory_bytes;
+        assert!(
+            measured <= 2 * estimated && estimated <= 2 * measured,
+            "estimated {estimated} bytes, measured {measured} bytes"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! An IVC chain built with cyclic recursion: each step's circuit verifies the previous step's
+//! proof, which is a proof of the same circuit, and increments a counter.
+
+use anyhow::Result;
+use hashbrown::HashMap;
+use plonky2::field::types::Field;
+use plonky2::iop::target::BoolTarget;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::{
+    CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitTarget,
+};
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use plonky2::plonk::proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget};
+use plonky2::recursion::cyclic_recursion::{
+    check_cyclic_proof_verifier_data, common_data_for_recursion,
+};
+use plonky2::recursion::dummy_circuit::cyclic_base_proof;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+struct CounterTargets {
+    condition: BoolTarget,
+    inner_proof: ProofWithPublicInputsTarget<D>,
+    verifier_data: VerifierCircuitTarget,
+}
+
+/// Builds the counter circuit, whose first public input is the number of steps proven so far,
+/// followed by the circuit's own verifier data.
+fn build_counter_circuit(
+    builder: &mut CircuitBuilder<F, D>,
+    common_data: &CommonCircuitData<F, D>,
+) -> Result<CounterTargets> {
+    let counter = builder.add_virtual_public_input();
+    let verifier_data = builder.add_verifier_data_public_inputs();
+
+    // In the base case there is no inner proof, and the counter starts at 1.
+    let condition = builder.add_virtual_bool_target_safe();
+    let inner_proof = builder.add_virtual_proof_with_pis(common_data);
+    let inner_counter = inner_proof.public_inputs[0];
+    let one = builder.one();
+    let new_counter = builder.mul_add(condition.target, inner_counter, one);
+    builder.connect(counter, new_counter);
+
+    builder.conditionally_verify_cyclic_proof_or_dummy::<C>(
+        condition,
+        &inner_proof,
+        common_data,
+    )?;
+    Ok(CounterTargets {
+        condition,
+        inner_proof,
+        verifier_data,
+    })
+}
+
+fn prove_step(
+    data: &CircuitData<F, C, D>,
+    targets: &CounterTargets,
+    inner_proof: Option<&ProofWithPublicInputs<F, C, D>>,
+) -> Result<ProofWithPublicInputs<F, C, D>> {
+    let mut pw = PartialWitness::new();
+    pw.set_verifier_data_target(&targets.verifier_data, &data.verifier_only);
+    match inner_proof {
+        Some(inner_proof) => {
+            pw.set_bool_target(targets.condition, true);
+            pw.set_proof_with_pis_target(&targets.inner_proof, inner_proof);
+        }
+        None => {
+            pw.set_bool_target(targets.condition, false);
+            let base_proof = cyclic_base_proof(&data.common, &data.verifier_only, HashMap::new());
+            pw.set_proof_with_pis_target(&targets.inner_proof, &base_proof);
+        }
+    }
+    let proof = data.prove(pw)?;
+    check_cyclic_proof_verifier_data(&proof, &data.verifier_only, &data.common)?;
+    data.verify(proof.clone())?;
+    Ok(proof)
+}
+
+#[test]
+fn counter_ivc() -> Result<()> {
+    let config = CircuitConfig::standard_recursion_config();
+    let common_data = common_data_for_recursion::<F, C, _, D>(&config, build_counter_circuit)?;
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let targets = build_counter_circuit(&mut builder, &common_data)?;
+    let data = builder.build::<C>();
+    assert_eq!(data.common, common_data);
+
+    let mut proof = prove_step(&data, &targets, None)?;
+    assert_eq!(proof.public_inputs[0], F::ONE);
+    for step in 2..=3 {
+        proof = prove_step(&data, &targets, Some(&proof))?;
+        assert_eq!(proof.public_inputs[0], F::from_canonical_u32(step));
+    }
+
+    check_cyclic_proof_verifier_data(&proof, &data.verifier_only, &data.common)?;
+
+    // Verifier data with another digest doesn't match the one embedded in the public inputs.
+    let mut other_verifier_only = data.verifier_only.clone();
+    other_verifier_only.circuit_digest.elements[0] += F::ONE;
+    let err =
+        check_cyclic_proof_verifier_data(&proof, &other_verifier_only, &data.common).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("The circuit digest in the public inputs is"));
+
+    data.verify(proof)
+}
@@ -0,0 +1,131 @@
+//! Golden proofs: proof tuples of a small canonical circuit, serialized once per config and
+//! checked in under `tests/fixtures`. Verifying them catches changes to the serialization formats
+//! or to the verifier which would break proofs already in the wild.
+//!
+//! Such a change must bump `PROOF_TUPLE_FORMAT_VERSION`; the fixtures are then regenerated with
+//! `cargo test -p plonky2 --release --test golden_proofs -- --ignored generate_golden_proofs`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use plonky2::field::types::Field;
+use plonky2::hash::poseidon::PoseidonHash;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, Hasher, PoseidonGoldilocksConfig};
+use plonky2::plonk::verifier_key::{
+    proof_tuple_to_bytes, verify_proof_tuple, ProofTupleKind, VerifierKey,
+    PROOF_TUPLE_FORMAT_VERSION,
+};
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+/// The fixtures, by name, with their config and whether the proof is compressed.
+fn golden_configs() -> [(&'static str, CircuitConfig, ProofTupleKind); 4] {
+    [
+        (
+            "standard_recursion",
+            CircuitConfig::standard_recursion_config(),
+            ProofTupleKind::Proof,
+        ),
+        (
+            "standard_recursion_zk",
+            CircuitConfig::standard_recursion_zk_config(),
+            ProofTupleKind::Proof,
+        ),
+        (
+            "standard_ecc",
+            CircuitConfig::standard_ecc_config(),
+            ProofTupleKind::Proof,
+        ),
+        (
+            "standard_recursion_compressed",
+            CircuitConfig::standard_recursion_config(),
+            ProofTupleKind::CompressedProof,
+        ),
+    ]
+}
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.v{}.bin", name, PROOF_TUPLE_FORMAT_VERSION))
+}
+
+/// The public inputs of the canonical circuit's golden proofs.
+fn expected_public_inputs() -> Vec<F> {
+    let (x, y) = (F::from_canonical_u32(2), F::from_canonical_u32(5));
+    let z = x * y + F::from_canonical_u32(3);
+    let hash = PoseidonHash::hash_no_pad(&[x, y, z]);
+    [vec![x, z], hash.elements.to_vec()].concat()
+}
+
+/// Proves `z = x * y + 3` and the hash of `(x, y, z)` for `x = 2, y = 5`, with `x`, `z` and the
+/// hash as public inputs, and range checks `y`, so that the proof exercises a few common gates.
+fn golden_proof_tuple(config: CircuitConfig, kind: ProofTupleKind) -> Result<Vec<u8>> {
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let x = builder.add_virtual_public_input();
+    let y = builder.add_virtual_target();
+    let xy = builder.mul(x, y);
+    let three = builder.constant(F::from_canonical_u32(3));
+    let z = builder.add(xy, three);
+    builder.register_public_input(z);
+    let hash = builder.hash_n_to_hash_no_pad::<PoseidonHash>(vec![x, y, z]);
+    builder.register_public_inputs(&hash.elements);
+    builder.range_check(y, 8);
+    let data = builder.build::<C>();
+
+    let mut pw = PartialWitness::new();
+    pw.set_target(x, F::from_canonical_u32(2));
+    pw.set_target(y, F::from_canonical_u32(5));
+    let proof = data.prove(pw)?;
+    let proof_bytes = match kind {
+        ProofTupleKind::Proof => proof.to_bytes(),
+        ProofTupleKind::CompressedProof => data.compress(proof)?.to_bytes(),
+    };
+
+    let vk = VerifierKey::from_verifier_data(&data.verifier_data())
+        .map_err(|_| anyhow::Error::msg("failed to serialize the verifier key"))?;
+    Ok(proof_tuple_to_bytes(&vk, kind, &proof_bytes))
+}
+
+#[test]
+#[ignore]
+fn generate_golden_proofs() -> Result<()> {
+    for (name, config, kind) in golden_configs() {
+        let bytes = golden_proof_tuple(config, kind)?;
+        assert_eq!(verify_proof_tuple(&bytes)?, expected_public_inputs());
+        let path = fixture_path(name);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, bytes)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn verify_golden_proofs() {
+    for (name, _, _) in golden_configs() {
+        let path = fixture_path(name);
+        let bytes = fs::read(&path).unwrap_or_else(|err| {
+            panic!(
+                "Missing golden proof {} ({}). If PROOF_TUPLE_FORMAT_VERSION was bumped, \
+                 regenerate the fixtures with `generate_golden_proofs`.",
+                path.display(),
+                err
+            )
+        });
+        let public_inputs = verify_proof_tuple(&bytes).unwrap_or_else(|err| {
+            panic!(
+                "The golden proof {} no longer verifies: {}. Changes which break serialized \
+                 proofs must bump PROOF_TUPLE_FORMAT_VERSION.",
+                path.display(),
+                err
+            )
+        });
+        assert_eq!(public_inputs, expected_public_inputs(), "{}", name);
+    }
+}
@@ -0,0 +1,78 @@
+//! Proving and recursively verifying circuits over the quintic extension of Goldilocks.
+
+use anyhow::Result;
+use plonky2::field::extension::quintic::QuinticExtension;
+use plonky2::field::types::{Field, Sample};
+use plonky2::fri::reduction_strategies::FriReductionStrategy;
+use plonky2::fri::FriConfig;
+use plonky2::gates::noop::NoopGate;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksQuinticConfig};
+
+const D: usize = 5;
+type C = PoseidonGoldilocksQuinticConfig;
+type F = <C as GenericConfig<D>>::F;
+type FF = QuinticExtension<F>;
+
+#[test]
+fn prove_extension_arithmetic() -> Result<()> {
+    let mut builder = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+    let x = builder.add_virtual_extension_target();
+    let y = builder.add_virtual_extension_target();
+    // (x * y + x) / y
+    let xy = builder.mul_extension(x, y);
+    let sum = builder.add_extension(xy, x);
+    let quotient = builder.div_extension(sum, y);
+    builder.register_public_inputs(&quotient.0);
+    let data = builder.build::<C>();
+
+    let (x_value, y_value) = (FF::rand(), FF::rand());
+    let mut pw = PartialWitness::new();
+    pw.set_extension_target(x, x_value);
+    pw.set_extension_target(y, y_value);
+    let proof = data.prove(pw)?;
+
+    let expected = (x_value * y_value + x_value) / y_value;
+    assert_eq!(proof.public_inputs, expected.0);
+    data.verify(proof)
+}
+
+#[test]
+fn recursive_verification() -> Result<()> {
+    // An interpolation gate of arity 16 needs 91 routed wires at D = 5, more than the standard
+    // config has, so FRI folds by 8 at most.
+    let config = CircuitConfig {
+        fri_config: FriConfig {
+            reduction_strategy: FriReductionStrategy::ConstantArityBits(3, 5),
+            ..CircuitConfig::standard_recursion_config().fri_config
+        },
+        ..CircuitConfig::standard_recursion_config()
+    };
+
+    let mut builder = CircuitBuilder::<F, D>::new(config.clone());
+    let x = builder.add_virtual_target();
+    let x_pow = builder.exp_u64(x, 7);
+    builder.register_public_input(x_pow);
+    for _ in 0..2_000 {
+        builder.add_gate(NoopGate, vec![]);
+    }
+    let inner_data = builder.build::<C>();
+    let mut pw = PartialWitness::new();
+    pw.set_target(x, F::TWO);
+    let inner_proof = inner_data.prove(pw)?;
+
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let proof_target = builder.add_virtual_proof_with_pis(&inner_data.common);
+    let verifier_data = builder.constant_verifier_data(&inner_data.verifier_only);
+    builder.verify_proof::<C>(&proof_target, &verifier_data, &inner_data.common);
+    builder.register_inner_public_inputs(&proof_target);
+    let outer_data = builder.build::<C>();
+
+    let mut pw = PartialWitness::new();
+    pw.set_proof_with_pis_target(&proof_target, &inner_proof);
+    let outer_proof = outer_data.prove(pw)?;
+    assert_eq!(outer_proof.public_inputs, [F::from_canonical_u32(128)]);
+    outer_data.verify(outer_proof)
+}
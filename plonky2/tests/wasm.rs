@@ -0,0 +1,36 @@
+//! Checks that building, proving and verifying work on `wasm32-unknown-unknown`, where
+//! `std::time::Instant` panics. Run with
+//! `cargo test --target wasm32-unknown-unknown --no-default-features --test wasm`, using
+//! `wasm-bindgen-test-runner` as the target's runner.
+
+#![cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+
+use plonky2::field::types::Field;
+use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+use plonky2::plonk::circuit_builder::CircuitBuilder;
+use plonky2::plonk::circuit_data::CircuitConfig;
+use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const D: usize = 2;
+type C = PoseidonGoldilocksConfig;
+type F = <C as GenericConfig<D>>::F;
+
+#[wasm_bindgen_test]
+fn prove_and_verify_tiny_circuit() {
+    let config = CircuitConfig::standard_recursion_config();
+    let mut builder = CircuitBuilder::<F, D>::new(config);
+    let x = builder.add_virtual_target();
+    let y = builder.add_virtual_target();
+    let z = builder.mul(x, y);
+    builder.register_public_input(z);
+    let data = builder.build::<C>();
+
+    let mut pw = PartialWitness::new();
+    pw.set_target(x, F::from_canonical_u32(6));
+    pw.set_target(y, F::from_canonical_u32(7));
+    let proof = data.prove(pw).unwrap();
+    assert_eq!(proof.public_inputs, vec![F::from_canonical_u32(42)]);
+
+    data.verifier_data().verify(proof).unwrap();
+}
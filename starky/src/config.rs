@@ -56,6 +56,7 @@ impl StarkConfig {
                 proof_of_work_bits: 16,
                 reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
                 num_query_rounds: 84,
+                final_poly_degree_bits: 0,
             },
         }
     }
@@ -119,6 +120,7 @@ mod tests {
                 proof_of_work_bits: 16,
                 reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
                 num_query_rounds: 28,
+                final_poly_degree_bits: 0,
             },
         );
         assert!(high_rate_config.check_config::<F, D>().is_ok());
@@ -138,6 +140,7 @@ mod tests {
                 proof_of_work_bits: 16,
                 reduction_strategy: FriReductionStrategy::ConstantArityBits(4, 5),
                 num_query_rounds: 50,
+                final_poly_degree_bits: 0,
             },
         );
         // The conjectured security yields `rate_bits` * `num_query_rounds` + `proof_of_work_bits` = 66
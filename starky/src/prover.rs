@@ -13,6 +13,7 @@ use plonky2::field::polynomial::{PolynomialCoeffs, PolynomialValues};
 use plonky2::field::types::Field;
 use plonky2::field::zero_poly_coset::ZeroPolyOnCoset;
 use plonky2::fri::oracle::PolynomialBatch;
+use plonky2::fri::prover::FriProverOptions;
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::challenger::Challenger;
 use plonky2::plonk::config::GenericConfig;
@@ -309,6 +310,7 @@ where
             &initial_merkle_trees,
             challenger,
             &fri_params,
+            &FriProverOptions::default(),
             timing,
         )
     );
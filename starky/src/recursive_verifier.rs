@@ -9,6 +9,7 @@ use anyhow::{ensure, Result};
 use itertools::Itertools;
 use plonky2::field::extension::Extendable;
 use plonky2::field::types::Field;
+use plonky2::fri::structure::FriOracleInfo;
 use plonky2::fri::witness_util::set_fri_proof_target;
 use plonky2::hash::hash_types::RichField;
 use plonky2::iop::challenger::RecursiveChallenger;
@@ -253,12 +254,16 @@ pub fn add_virtual_stark_proof<F: RichField + Extendable<D>, S: Stark<F, D>, con
     let fri_params = config.fri_params(degree_bits);
     let cap_height = fri_params.config.cap_height;
 
-    let num_leaves_per_oracle = once(S::COLUMNS)
+    let oracles = once(S::COLUMNS)
         .chain(
             (stark.uses_lookups() || stark.requires_ctls())
                 .then(|| stark.num_lookup_helper_columns(config) + num_ctl_helper_zs),
         )
         .chain(once(stark.quotient_degree_factor() * config.num_challenges))
+        .map(|num_polys| FriOracleInfo {
+            num_polys,
+            blinding: false,
+        })
         .collect_vec();
 
     let auxiliary_polys_cap = (stark.uses_lookups() || stark.requires_ctls())
@@ -275,7 +280,7 @@ pub fn add_virtual_stark_proof<F: RichField + Extendable<D>, S: Stark<F, D>, con
             num_ctl_zs,
             config,
         ),
-        opening_proof: builder.add_virtual_fri_proof(&num_leaves_per_oracle, &fri_params),
+        opening_proof: builder.add_virtual_fri_proof(&oracles, &fri_params),
     }
 }
 
@@ -207,7 +207,8 @@ pub trait Stark<F: RichField + Extendable<D>, const D: usize>: Sync {
             ]
             .concat(),
         };
-        let zeta_next = builder.mul_const_extension(g, zeta);
+        let g_ext = builder.constant_extension(g.into());
+        let zeta_next = builder.mul_extension(g_ext, zeta);
         let zeta_next_batch = FriBatchInfoTarget {
             point: zeta_next,
             polynomials: [trace_info, auxiliary_polys_info].concat(),